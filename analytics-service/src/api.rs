@@ -0,0 +1,66 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Json, Response},
+    routing::get,
+    Router,
+};
+use ticket_master::{
+    area_sales_csv, hourly_sales_csv, ApiError, ApiResponse, EventSalesReport, HourlySales, SalesAggregator,
+};
+
+type ApiResult<T> = std::result::Result<Json<ApiResponse<T>>, ApiError>;
+// CSV exports answer errors with the same JSON body
+type CsvResult = std::result::Result<Response, ApiError>;
+
+/// Reports as JSON, and CSV under `.csv` paths for spreadsheets
+pub fn routes(aggregator: SalesAggregator) -> Router {
+    Router::new()
+        .route("/reports/events", get(list_events))
+        .route("/reports/events.csv", get(export_events))
+        .route("/reports/events/:event_id", get(get_event))
+        .route("/reports/events/:event_id/sales.csv", get(export_event))
+        .route("/reports/events/:event_id/hourly", get(get_hourly))
+        .route("/reports/events/:event_id/hourly.csv", get(export_hourly))
+        .with_state(aggregator)
+}
+
+async fn list_events(State(aggregator): State<SalesAggregator>) -> ApiResult<Vec<EventSalesReport>> {
+    Ok(Json(ApiResponse::success(aggregator.events()?)))
+}
+
+async fn export_events(State(aggregator): State<SalesAggregator>) -> CsvResult {
+    Ok(csv(area_sales_csv(&aggregator.events()?)))
+}
+
+async fn get_event(
+    State(aggregator): State<SalesAggregator>,
+    Path(event_id): Path<String>,
+) -> ApiResult<EventSalesReport> {
+    Ok(Json(ApiResponse::success(event_report(&aggregator, &event_id)?)))
+}
+
+async fn export_event(State(aggregator): State<SalesAggregator>, Path(event_id): Path<String>) -> CsvResult {
+    Ok(csv(area_sales_csv(&[event_report(&aggregator, &event_id)?])))
+}
+
+async fn get_hourly(
+    State(aggregator): State<SalesAggregator>,
+    Path(event_id): Path<String>,
+) -> ApiResult<Vec<HourlySales>> {
+    event_report(&aggregator, &event_id)?;
+    Ok(Json(ApiResponse::success(aggregator.hourly(&event_id)?)))
+}
+
+async fn export_hourly(State(aggregator): State<SalesAggregator>, Path(event_id): Path<String>) -> CsvResult {
+    event_report(&aggregator, &event_id)?;
+    Ok(csv(hourly_sales_csv(&aggregator.hourly(&event_id)?)))
+}
+
+fn event_report(aggregator: &SalesAggregator, event_id: &str) -> std::result::Result<EventSalesReport, ApiError> {
+    aggregator.event(event_id)?.ok_or_else(|| ApiError::not_found("No sales of this event seen"))
+}
+
+fn csv(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/csv; charset=utf-8")], body).into_response()
+}
@@ -0,0 +1,33 @@
+use clap::Parser;
+use ticket_master::{run_service, CommonArgs, Result};
+
+mod api;
+mod service;
+
+#[derive(Parser, Debug)]
+#[command(name = "analytics-service")]
+#[command(about = "Analytics Service for Ticket Master")]
+struct Args {
+    /// Port of the reporting API; overrides `analytics.service.port`, 8083 when neither is set
+    #[arg(short = 'p', long = "port")]
+    port: Option<u16>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let configured = args.common.service_config("analytics-service", |config| {
+        if let Some(port) = args.port {
+            config.analytics_service.port = Some(port);
+        }
+    });
+    let Some(config) = configured.await? else {
+        return Ok(());
+    };
+
+    // SIGTERM or Ctrl+C stops consuming; records in flight are applied and committed first
+    run_service(config, service::build).await
+}
@@ -0,0 +1,93 @@
+use ticket_master::{
+    AreaStatus, ConsumerRunner, KafkaConsumer, KafkaMessage, MessageConsumer, MessageHandler, Metrics,
+    RecordProcessor, Reservation, Result, RocksDBStore, SalesAggregator, ServiceBootstrap, ServiceConfig,
+    StateChange, TicketMasterError, Topics, health_router,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use tracing::{error, warn, Instrument};
+
+use crate::api;
+
+const ANALYTICS_SERVICE: &str = "analytics-service";
+const TOPICS: [&str; 2] = [Topics::STATE_USER_RESERVATION, Topics::STATE_EVENT_AREA_STATUS];
+const DEFAULT_PORT: u16 = 8083;
+
+/// Aggregates the reservations and area statuses of the topics and serves
+/// the reports
+pub type AnalyticsService<C = KafkaConsumer> = ConsumerRunner<AnalyticsProcessor, C>;
+
+/// Aggregates the reservations and area statuses it reads; shared by all dispatcher lanes
+pub struct AnalyticsProcessor {
+    aggregator: SalesAggregator,
+    metrics: Metrics,
+}
+
+pub async fn build(config: ServiceConfig) -> Result<AnalyticsService> {
+    let bootstrap = ServiceBootstrap::new(&config).await?;
+    let metrics = bootstrap.metrics().clone();
+
+    // Rebuilt from the topics if lost: the aggregates only depend on the latest of each record
+    let store = RocksDBStore::new(format!("{}/analytics/{}", config.state_dir, config.application_id))?;
+    let aggregator = SalesAggregator::new(Arc::new(store));
+
+    let mut service = with_clients(&config, bootstrap.consumer()?, aggregator.clone(), metrics)?;
+    let port = config.analytics_service.port.unwrap_or(DEFAULT_PORT);
+    let app = api::routes(aggregator).merge(health_router(bootstrap.readiness(&TOPICS)?));
+    bootstrap.serve(&mut service, "analytics-api", port, app).await?;
+
+    // Heartbeats are the only thing the service produces
+    bootstrap.start_heartbeat(&mut service, bootstrap.plain_producer()?, env!("CARGO_PKG_VERSION"));
+    Ok(service)
+}
+
+/// Build the service on the given consumer, e.g. from a `MockKafka`;
+/// failed records are counted in `metrics`
+pub fn with_clients<C: MessageConsumer>(
+    config: &ServiceConfig,
+    consumer: C,
+    aggregator: SalesAggregator,
+    metrics: Metrics,
+) -> Result<AnalyticsService<C>> {
+    // Records are applied in order per key; the aggregator locks each area it changes
+    let processor = AnalyticsProcessor { aggregator, metrics: metrics.clone() };
+    ConsumerRunner::new(config, consumer, &TOPICS, processor, metrics)
+}
+
+// Failed records are logged and skipped; the next record of the same key corrects the figures
+#[async_trait::async_trait]
+impl RecordProcessor for AnalyticsProcessor {
+    fn on_failure(&self, e: TicketMasterError) -> Result<()> {
+        error!("Error aggregating: {}", e);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageHandler for AnalyticsProcessor {
+    async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+        async {
+            match message.topic.as_str() {
+                Topics::STATE_USER_RESERVATION => match message.state_change::<Reservation>().await? {
+                    StateChange::Upsert(reservation) => self.aggregator.apply_reservation(&reservation, Utc::now()),
+                    StateChange::Delete(reservation_id) => self.aggregator.delete_reservation(reservation_id),
+                },
+                Topics::STATE_EVENT_AREA_STATUS => match message.state_change::<AreaStatus>().await? {
+                    StateChange::Upsert(area_status) => self.aggregator.apply_area_status(&area_status),
+                    StateChange::Delete(event_area_id) => self.aggregator.delete_area(event_area_id),
+                },
+                _ => {
+                    warn!("Unknown topic: {}", message.topic);
+                    Ok(())
+                }
+            }
+        }
+        .instrument(message.span())
+        .await
+        .map_err(|e: TicketMasterError| {
+            let e = e.with_context(message.error_context(ANALYTICS_SERVICE));
+            self.metrics.record_error(&e);
+            e
+        })
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::{Path, PathBuf};
+use ticket_master::{Result, RocksDBStore};
+use tracing::info;
+
+/// Directory holding the per-partition area status stores, in the state
+/// directory as well as in a backup
+pub fn area_status_dir(root: &Path) -> PathBuf {
+    root.join("area-status")
+}
+
+/// Partition stores found under `dir`, as (partition, path) in partition order
+pub fn partition_dirs(dir: &Path) -> Result<Vec<(i32, PathBuf)>> {
+    let mut partitions = Vec::new();
+    if !dir.exists() {
+        return Ok(partitions);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if let Some(partition) = entry.file_name().to_str().and_then(|name| name.parse::<i32>().ok()) {
+            partitions.push((partition, entry.path()));
+        }
+    }
+    partitions.sort();
+    Ok(partitions)
+}
+
+/// Checkpoint every area status store in `state_dir` to `backup_dir`
+///
+/// Run while the service is stopped, since RocksDB allows a single process
+/// to open a store. Returns the partitions backed up.
+pub fn backup_area_status_stores(state_dir: &Path, backup_dir: &Path) -> Result<Vec<i32>> {
+    let target = area_status_dir(backup_dir);
+    std::fs::create_dir_all(&target)?;
+
+    let mut partitions = Vec::new();
+    for (partition, path) in partition_dirs(&area_status_dir(state_dir))? {
+        let store = RocksDBStore::new(&path)?;
+        store.create_checkpoint(target.join(partition.to_string()))?;
+        info!("Backed up area status store for partition {}", partition);
+        partitions.push(partition);
+    }
+    Ok(partitions)
+}
@@ -0,0 +1,7 @@
+pub mod backup;
+pub mod service;
+pub mod standby;
+mod warmup;
+
+pub use service::EventService;
+pub use standby::StandbyService;
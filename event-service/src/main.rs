@@ -1,80 +1,140 @@
 use clap::Parser;
-use std::path::PathBuf;
-use ticket_master::{Result, ServiceConfig};
+use std::path::{Path, PathBuf};
+use ticket_master::{
+    init_logging, redacted_config, shutdown_telemetry, CommonArgs, ConfigLoader, ConfigWatcher, LogFilter, Result,
+    SecretResolver, ServiceConfig, ShutdownCoordinator, Supervisor,
+};
 use tracing::{info, error};
 
-mod service;
-mod strategies;
-
-use service::EventService;
+use event_service::{backup, EventService, StandbyService};
 
 #[derive(Parser, Debug)]
 #[command(name = "event-service")]
 #[command(about = "Event Service for Ticket Master")]
 struct Args {
-    /// State directory for storage
-    #[arg(short = 'd', long = "state-dir", default_value = "/tmp/kafka-streams")]
-    state_dir: PathBuf,
+    #[command(flatten)]
+    common: CommonArgs,
 
-    /// Config file path
-    #[arg(short = 'c', long = "config", default_value = "../client.dev.properties")]
-    config: PathBuf,
+    /// Run as a warm standby that only replicates the state store changelog
+    #[arg(long = "standby")]
+    standby: bool,
 
-    /// Stream config file path
-    #[arg(long = "stream-config")]
-    stream_config: Option<PathBuf>,
+    /// Reprocess history from this time (RFC 3339 or epoch milliseconds) to rebuild state
+    #[arg(long = "replay-from", value_parser = parse_timestamp_millis, conflicts_with = "standby")]
+    replay_from: Option<i64>,
 
-    /// Show help information
-    #[arg(short = 'h', long = "help")]
-    help: bool,
-}
+    /// Checkpoint the area status stores into this directory and exit; run while the service is stopped
+    #[arg(long = "backup-dir", conflicts_with_all = ["standby", "replay_from", "restore_from"])]
+    backup_dir: Option<PathBuf>,
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    /// Roll the area status stores back to a backup taken with --backup-dir before starting
+    #[arg(long = "restore-from", conflicts_with = "standby")]
+    restore_from: Option<PathBuf>,
+}
 
+fn main() -> Result<()> {
     let args = Args::parse();
 
-    if args.help {
-        println!("Event Service for Ticket Master");
-        return Ok(());
-    }
+    // Initialize tracing
+    let log_filter = init_logging("event-service", args.common.log_format)?;
 
     info!("Starting Event Service");
-    info!("State directory: {:?}", args.state_dir);
-    info!("Config file: {:?}", args.config);
 
     // Load configuration
-    let mut config = load_config(&args.config)?;
+    let loader = args.common.config_loader("event-service");
+    let mut config = with_defaults(args.common.load_config(loader.clone())?);
     config.application_id = "event-service".to_string();
-    config.state_dir = args.state_dir.to_string_lossy().to_string();
+    if args.common.print_config {
+        println!("{}", redacted_config(&config)?);
+        return Ok(());
+    }
+    info!("State directory: {}", config.state_dir);
+
+    // Built by hand, as its worker threads are configured
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(worker_threads) = config.event_service.worker_threads {
+        info!("Worker threads: {}", worker_threads);
+        runtime.worker_threads(worker_threads);
+    }
+    let result = runtime.enable_all().build()?.block_on(run(args, loader, config, log_filter));
+    shutdown_telemetry();
+    result
+}
+
+async fn run(args: Args, loader: ConfigLoader, mut config: ServiceConfig, log_filter: LogFilter) -> Result<()> {
+    SecretResolver::from_env().resolve_config(&mut config).await?;
+    log_filter.set(config.log_filter.as_deref())?;
 
-    if let Some(stream_config_path) = args.stream_config {
-        // Load additional stream configuration
-        info!("Loading stream config from: {:?}", stream_config_path);
-        config = ticket_master::merge_stream_properties(config, stream_config_path)?;
+    if let Some(backup_dir) = args.backup_dir {
+        let partitions = backup::backup_area_status_stores(Path::new(&config.state_dir), &backup_dir)?;
+        info!("Backed up area status stores for partitions {:?} to {:?}", partitions, backup_dir);
+        return Ok(());
     }
 
+    // SIGTERM or Ctrl+C stops consuming; in-flight messages finish and are committed first
+    let coordinator = ShutdownCoordinator::default();
+    let shutdown = coordinator.subscribe();
+    let restarts_stopped = coordinator.subscribe();
+    coordinator.signal_on_termination();
+
+    if args.standby {
+        let standby = StandbyService::new(config).await?;
+        info!("Event Service standby started successfully");
+        standby.run(shutdown).await?;
+        return Ok(());
+    }
+
+    // Runtime-tunable settings follow the config file
+    let config_watcher = ConfigWatcher::start(loader, &config)?;
+    config_watcher.watch_log_filter(log_filter);
+
     // Create and start the event service
-    let service = EventService::new(config).await?;
+    let restart_policy = config.supervisor.policy();
+    let mut service = EventService::new(config).await?;
+    service.watch_settings(&config_watcher);
+    if let Some(restore_dir) = &args.restore_from {
+        info!("Restoring area status stores from {:?}", restore_dir);
+        service.restore_area_status(restore_dir)?;
+    }
+    if let Some(timestamp_ms) = args.replay_from {
+        info!("Replaying from timestamp {}", timestamp_ms);
+        service = service.with_replay_from(timestamp_ms);
+    }
     
     info!("Event Service started successfully");
     
-    // Run the service
-    service.run().await?;
+    // Run the service, restarting it from the committed offsets if it crashes
+    service.register_shutdown(&coordinator).await;
+    let supervisor = Supervisor::new("event-service", restart_policy).with_metrics(service.metrics().clone());
+    let service = &service;
+    let mut first_run = Some(shutdown);
+    supervisor
+        .run(restarts_stopped, |restarts| {
+            let shutdown = first_run.take().unwrap_or_else(|| coordinator.subscribe());
+            async move {
+                if restarts > 0 {
+                    service.rejoin()?;
+                }
+                service.run(shutdown).await
+            }
+        })
+        .await?;
+    coordinator.shutdown().await?;
+    info!("Event Service shut down");
 
     Ok(())
 }
 
-fn load_config(config_path: &PathBuf) -> Result<ServiceConfig> {
-    use ticket_master::parse_properties_file;
-    
-    // Parse the Java properties file
-    let mut config = parse_properties_file(config_path, "event-service")?;
-    
+fn parse_timestamp_millis(value: &str) -> std::result::Result<i64, String> {
+    if let Ok(millis) = value.parse::<i64>() {
+        return Ok(millis);
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|time| time.timestamp_millis())
+        .map_err(|e| format!("expected RFC 3339 or epoch milliseconds: {}", e))
+}
+
+fn with_defaults(mut config: ServiceConfig) -> ServiceConfig {
     // Set service-specific defaults
     if config.commit_interval_ms.is_none() {
         config.commit_interval_ms = Some(20);
@@ -83,5 +143,5 @@ fn load_config(config_path: &PathBuf) -> Result<ServiceConfig> {
         config.processing_guarantee = Some("exactly_once_v2".to_string());
     }
     
-    Ok(config)
+    config
 }
\ No newline at end of file
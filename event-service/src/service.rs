@@ -1,90 +1,297 @@
 use ticket_master::{
     Result, TicketMasterError, ServiceConfig, KafkaConsumer, KafkaProducer,
     CreateEvent, AreaStatus, ReserveSeat, ReservationResult, ReservationResultEnum,
-    ReservationErrorCode, ReservationType, Seat, Topics, Stores, event_area_key,
-    StateStore, ProcessingContext
+    Seat, Topics, Stores, event_area_key,
+    changelog_topic, ProcessingContext, RebalanceListener,
+    RocksDBStore, TopicPartition, Changelog, KafkaMessage, ConsumerRunner, RecordProcessor,
+    MessageHandler, Metrics, spawn_delivery_monitor,
+    MessageConsumer, MessageProducer, DedupStore, spawn_dedup_purge, ReplayWatermarks, VersionedStore, Versioned,
+    CompactAreaStatus, StrategyRegistry, AREA_STRATEGY_HEADER, spawn_store_size_reporter, spawn_store_compaction, AssignedPartitions,
+    StateQueryHandler, QueryHost, key_partition, serve_state_queries, spawn_query_host_announcer, AreaAdminCommand,
+    CacheStore, StateStoreBackend, WarmupConfig, ReadinessProbe, serve_metrics_and_health_on, StoreWriteBatch,
+    AuditEntity, AuditRecord, send_audit_record, EventSourcingConfig, CheckAvailability, Availability, ConfigWatcher,
+    CircuitBreaker, RetryPolicies, retry_transient, ShutdownCoordinator, KafkaProducerShutdown,
+    StateStoresShutdown, HttpServerShutdown, HeartbeatPublisher, ServiceBootstrap, READINESS_TIMEOUT, PartitionDrain,
 };
-use crate::strategies::{ReservationStrategy, SelfPickStrategy, RandomStrategy};
-use std::collections::HashMap;
-use std::time::Duration;
-use tracing::{info, error, warn};
-use tokio::signal;
+use crate::backup::{area_status_dir, partition_dirs};
+use crate::warmup::Warmup;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, error, warn, Instrument, Span};
+use tokio::sync::broadcast;
+use tokio::time::{interval, Interval};
 
-pub struct EventService {
-    consumer: KafkaConsumer,
-    producer: KafkaProducer,
-    context: ProcessingContext,
-    strategies: HashMap<ReservationType, Box<dyn ReservationStrategy + Send + Sync>>,
+const STORE_SIZE_REPORT_INTERVAL: Duration = Duration::from_secs(30);
+// Actors audit records name for area setup and operator commands
+const EVENT_SERVICE: &str = "event-service";
+const TOPICS: [&str; 4] = [
+    Topics::COMMAND_EVENT_CREATE_EVENT,
+    Topics::COMMAND_EVENT_RESERVE_SEAT,
+    Topics::INTERNAL_EVENT_AREA_STATUS,
+    Topics::COMMAND_EVENT_ADMIN,
+];
+const ADMIN: &str = "admin";
+
+/// Consumes the event topics on a `ConsumerRunner`, with the area status
+/// stores of the assigned partitions
+pub struct EventService<C = KafkaConsumer, P = KafkaProducer> {
+    runner: ConsumerRunner<EventProcessor<P>, C>,
+    // Finished once the stores of the first assignment are warmed up
+    warmup: Warmup,
+}
+
+// The built-in strategies, with the defaults set in `event.service.strategy.default.<type>`
+fn strategy_registry(config: &ServiceConfig) -> Result<StrategyRegistry> {
+    let mut registry = StrategyRegistry::default();
+    for (reservation_type, name) in &config.event_service.strategy_defaults {
+        registry.set_default(reservation_type, name)?;
+    }
+    Ok(registry)
+}
+
+/// Handles event-service messages; shared by all dispatcher lanes
+struct EventProcessor<P> {
+    producer: P,
+    area_stores: AreaStatusStores,
+    dedup: Arc<DedupStore>,
+    // Messages a `--replay-from` reprocesses on purpose, which dedup must not skip
+    replayed: Arc<ReplayWatermarks>,
+    strategies: Arc<StrategyRegistry>,
+    // Reservations whose result the handler is publishing itself
+    publishing: Mutex<HashSet<String>>,
+    // Messages failing on a transient error are processed again
+    retry: RetryPolicies,
+    // How often results left pending are published again
+    pending_results_interval: Duration,
+    metrics: Metrics,
 }
 
 impl EventService {
     pub async fn new(config: ServiceConfig) -> Result<Self> {
         let kafka_config = config.to_kafka_config();
-        
-        let consumer = KafkaConsumer::new(kafka_config.clone())?;
-        let producer = KafkaProducer::new(kafka_config)?;
-        
-        // Subscribe to topics
-        consumer.subscribe(&[
-            Topics::COMMAND_EVENT_CREATE_EVENT,
-            Topics::COMMAND_EVENT_RESERVE_SEAT,
-        ])?;
 
-        // Initialize state stores with RocksDB
-        let context = ProcessingContext::with_state_dir(config.state_dir.clone());
-        context.add_rocksdb_store(Stores::AREA_STATUS.to_string(), "area-status")?;
+        let bootstrap = ServiceBootstrap::new(&config).await?;
+        let metrics = bootstrap.metrics().clone();
 
-        // Initialize reservation strategies
-        let mut strategies: HashMap<ReservationType, Box<dyn ReservationStrategy + Send + Sync>> = HashMap::new();
-        strategies.insert(ReservationType::SelfPick, Box::new(SelfPickStrategy));
-        strategies.insert(ReservationType::Random, Box::new(RandomStrategy));
+        // Area status stores are opened per assigned partition
+        let mut context = ProcessingContext::with_state_dir(config.state_dir.clone())
+            .with_metrics(metrics.clone(), config.store_metrics_per_store)
+            .with_sync_writes(config.store_sync_writes);
+        let changelog = if config.changelog_enabled {
+            let changelog = Changelog::new(kafka_config.clone())?.with_topic_resolver(config.topic_resolver());
+            context = context.with_changelog(changelog.clone());
+            Some(changelog)
+        } else {
+            None
+        };
+        let context = Arc::new(context);
+        spawn_store_size_reporter(context.clone(), STORE_SIZE_REPORT_INTERVAL);
+        if let Some(every) = config.store_compaction_interval() {
+            spawn_store_compaction(context.clone(), every);
+        }
+        let serdes = bootstrap.serdes().clone();
+        let consumer = |listener: Arc<dyn RebalanceListener>| -> Result<KafkaConsumer> {
+            Ok(KafkaConsumer::with_rebalance_listener(kafka_config.clone(), listener)?
+                .with_serdes(serdes.clone())
+                .with_topic_resolver(config.topic_resolver())
+                .with_metrics(metrics.clone()))
+        };
+        let (producer, delivery_reports) = KafkaProducer::with_serdes(kafka_config.clone(), serdes.clone())?
+            .with_topic_resolver(config.topic_resolver())
+            .with_metrics(metrics.clone())
+            .with_circuit_breaker(Arc::new(CircuitBreaker::kafka_producer().with_metrics(metrics.clone())))
+            .with_delivery_reports();
+        let mut service = Self::with_clients(&config, consumer, producer.clone(), context.clone(), metrics.clone())?;
+        if let Some(port) = config.metrics_port {
+            // Not ready until the hot area statuses are in memory
+            let readiness = ReadinessProbe::new(READINESS_TIMEOUT).with_check(service.warmup.clone());
+            let server = serve_metrics_and_health_on(port, metrics.clone(), readiness).await?;
+            service.runner.add_shutdown_component(HttpServerShutdown::new("metrics-server").with_task(server));
+        }
+        spawn_delivery_monitor(delivery_reports);
 
-        Ok(Self {
-            consumer,
-            producer,
+        // The changelog must be co-partitioned with the reserve-seat topic
+        let partitions = service.runner.consumer().partition_count(Topics::COMMAND_EVENT_RESERVE_SEAT)?.max(1);
+        if let Some(changelog) = changelog {
+            changelog.ensure_topic(&changelog_topic(Stores::AREA_STATUS), partitions).await?;
+        }
+
+        // Other instances read the area statuses this one owns over HTTP
+        if let Some(server) = &config.application_server {
+            let area_stores = service.runner.processor().area_stores.clone();
+            let assigned = area_stores.assigned.subscribe();
+            let queries = AreaStatusQueries {
+                stores: area_stores,
+                partition_count: partitions,
+                strategies: strategy_registry(&config)?,
+            };
+            let query_server = serve_state_queries(server, Arc::new(queries)).await?;
+            service.runner.add_shutdown_component(HttpServerShutdown::new("state-query-server").with_task(query_server));
+            let host = QueryHost::new(&config.application_id, server, partitions);
+            spawn_query_host_announcer(producer.clone(), host, assigned);
+        }
+
+        let heartbeat = HeartbeatPublisher::from_config(producer.clone(), &config, env!("CARGO_PKG_VERSION"))
+            .with_consumer(service.runner.consumer().clone(), &TOPICS);
+        heartbeat.start();
+        service.runner.add_shutdown_component(heartbeat);
+        service.runner.add_shutdown_component(KafkaProducerShutdown::new(producer));
+        service.runner.add_shutdown_component(StateStoresShutdown::new(context));
+        Ok(service)
+    }
+
+    /// Have `coordinator` leave the consumer group, stop the HTTP servers
+    /// and heartbeats, flush the producer and persist the area status stores
+    /// once `run` has returned
+    pub async fn register_shutdown(&mut self, coordinator: &ShutdownCoordinator) {
+        self.runner.register_shutdown(coordinator).await;
+    }
+}
+
+impl<C: MessageConsumer, P: MessageProducer> EventService<C, P> {
+    /// Build the service on the given clients, e.g. from a `MockKafka`;
+    /// `consumer` makes the consumer reporting its rebalances to the given
+    /// listener, which opens the area status stores of assigned partitions
+    /// in `context` and drains revoked ones. Message retries are counted in
+    /// `metrics`.
+    pub fn with_clients<F>(
+        config: &ServiceConfig,
+        consumer: F,
+        producer: P,
+        context: Arc<ProcessingContext>,
+        metrics: Metrics,
+    ) -> Result<Self>
+    where
+        F: FnOnce(Arc<dyn RebalanceListener>) -> Result<C>,
+    {
+        let area_stores = AreaStatusStores::new(
             context,
-            strategies,
+            Arc::new(AssignedPartitions::new(Topics::COMMAND_EVENT_RESERVE_SEAT)),
+            config.store_cache_capacity(),
+            config.event_sourcing.clone(),
+        );
+        let drain = PartitionDrain::new();
+        let warmup = Warmup::default();
+        let listener = AreaStatusRebalanceListener::new(area_stores.clone(), drain.clone(), config.warmup.clone(), warmup.clone());
+        let consumer = consumer(Arc::new(listener))?;
+
+        let dedup = Arc::new(DedupStore::open(
+            format!("{}/dedup/{}", config.state_dir, config.application_id),
+            config.dedup_ttl(),
+        )?);
+        spawn_dedup_purge(dedup.clone());
+
+        // Messages are keyed by event#area, so each area is still updated in order
+        let processor = EventProcessor {
+            producer,
+            area_stores,
+            dedup,
+            replayed: Arc::default(),
+            strategies: Arc::new(strategy_registry(config)?),
+            publishing: Mutex::new(HashSet::new()),
+            retry: config.retry.policies(),
+            pending_results_interval: config.commit_interval(),
+            metrics: metrics.clone(),
+        };
+        Ok(Self {
+            runner: ConsumerRunner::new(config, consumer, &TOPICS, processor, metrics)?.with_partition_drain(drain),
+            warmup,
         })
     }
 
-    pub async fn run(&self) -> Result<()> {
-        info!("Event Service is running...");
+    pub fn metrics(&self) -> &Metrics {
+        self.runner.metrics()
+    }
 
-        loop {
-            tokio::select! {
-                // Handle shutdown signal
-                _ = signal::ctrl_c() => {
-                    info!("Received shutdown signal");
-                    break;
-                }
-                
-                // Process messages
-                message_result = self.consumer.recv_message(Duration::from_millis(100)) => {
-                    match message_result? {
-                        Some(message) => {
-                            if let Err(e) = self.process_message(&message).await {
-                                error!("Error processing message: {}", e);
-                            } else {
-                                // Commit the message after successful processing
-                                if let Err(e) = self.consumer.commit_message(&message) {
-                                    error!("Error committing message: {}", e);
-                                }
-                            }
-                        }
-                        None => {
-                            // No message received (timeout)
-                            continue;
-                        }
-                    }
-                }
-            }
+    /// Leave and join the consumer group again, so processing resumes from
+    /// the committed offsets, e.g. before `run` is restarted after a crash
+    pub fn rejoin(&self) -> Result<()> {
+        self.runner.rejoin()
+    }
+
+    /// Reprocess every assigned partition from `timestamp_ms` (epoch millis)
+    /// instead of the committed offsets
+    pub fn with_replay_from(self, timestamp_ms: i64) -> Self {
+        let replayed = self.runner.processor().replayed.clone();
+        Self {
+            runner: self.runner.with_replay_from(timestamp_ms, replayed),
+            ..self
         }
+    }
 
-        info!("Event Service shutting down...");
+    /// Roll the area status stores back to a backup taken with `--backup-dir`
+    ///
+    /// Partitions without a store in the backup are left as they are.
+    pub fn restore_area_status(&self, backup_dir: &Path) -> Result<()> {
+        let area_stores = &self.runner.processor().area_stores;
+        for (partition, path) in partition_dirs(&area_status_dir(backup_dir))? {
+            let store = area_stores.open(partition)?;
+            let restored = store.restore_from_checkpoint(&path)?;
+            drop(store);
+            area_stores.close(partition)?;
+            info!("Restored {} area status entries for partition {} from {:?}", restored, partition, path);
+        }
         Ok(())
     }
 
-    async fn process_message(&self, message: &ticket_master::KafkaMessage) -> Result<()> {
+    /// Resize the area status caches as the watched config file changes
+    pub fn watch_settings(&self, watcher: &ConfigWatcher) -> tokio::task::JoinHandle<()> {
+        let context = self.runner.processor().area_stores.context.clone();
+        watcher.on_change(move |settings| context.set_cache_capacity(settings.store_cache_capacity))
+    }
+
+    /// Process messages until `shutdown` is signalled, then finish the
+    /// messages in flight and commit their offsets
+    pub async fn run(&self, shutdown: broadcast::Receiver<()>) -> Result<()> {
+        self.runner.run(shutdown).await
+    }
+}
+
+// Failed messages are logged and skipped so they do not hold back the
+// partition's commits
+#[async_trait::async_trait]
+impl<P: MessageProducer> RecordProcessor for EventProcessor<P> {
+    fn timer(&self) -> Option<Interval> {
+        Some(interval(self.pending_results_interval))
+    }
+
+    // Results left by a failed send, or by a crash before startup
+    async fn tick(&self) {
+        if let Err(e) = self.publish_pending_results().await {
+            warn!("Failed to publish pending reservation results: {}", e);
+        }
+    }
+
+    fn write_stalled(&self) -> Result<bool> {
+        self.area_stores.write_stalled()
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: MessageProducer> MessageHandler for EventProcessor<P> {
+    async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+        // Messages processed before a crash or rebalance are redelivered
+        if !self.replayed.is_replayed(message) && self.dedup.is_duplicate(message)? {
+            info!("Skipping duplicate message {}:{}@{}", message.topic, message.partition, message.offset);
+            return Ok(());
+        }
+        retry_transient(&self.retry, "process message", Some(&self.metrics), || self.process_message(message))
+            .instrument(message.span())
+            .await
+            .map_err(|e| {
+                let e = e.with_context(message.error_context(EVENT_SERVICE));
+                self.metrics.record_error(&e);
+                e
+            })?;
+        self.dedup.mark_processed(message)
+    }
+}
+
+impl<P: MessageProducer> EventProcessor<P> {
+    async fn process_message(&self, message: &KafkaMessage) -> Result<()> {
         match message.topic.as_str() {
             Topics::COMMAND_EVENT_CREATE_EVENT => {
                 self.handle_create_event(message).await
@@ -92,6 +299,12 @@ impl EventService {
             Topics::COMMAND_EVENT_RESERVE_SEAT => {
                 self.handle_reserve_seat(message).await
             }
+            Topics::INTERNAL_EVENT_AREA_STATUS => {
+                self.handle_area_init(message).await
+            }
+            Topics::COMMAND_EVENT_ADMIN => {
+                self.handle_admin_command(message).await
+            }
             _ => {
                 warn!("Unknown topic: {}", message.topic);
                 Ok(())
@@ -99,30 +312,30 @@ impl EventService {
         }
     }
 
-    async fn handle_create_event(&self, message: &ticket_master::KafkaMessage) -> Result<()> {
+    async fn handle_create_event(&self, message: &KafkaMessage) -> Result<()> {
         let event_name = message.key.as_ref()
             .ok_or_else(|| TicketMasterError::InvalidArgument("Missing event name key".to_string()))?;
         
-        let create_event: CreateEvent = message.deserialize_value()?;
+        let create_event: CreateEvent = message.deserialize_value().await?;
         
         info!("Creating event: {}", event_name);
 
-        let area_status_store = self.context
-            .get_rocksdb_store(Stores::AREA_STATUS)
-            .ok_or_else(|| TicketMasterError::InvalidArgument("Area status store not found".to_string()))?;
-
-        // Create area status for each area and store them
+        // Re-key each area by event#area so the instance owning the matching
+        // reserve-seat partition initializes its store
         for area in &create_event.areas {
             let area_status = AreaStatus::from_area(event_name, area);
             let key = event_area_key(event_name, &area.area_id);
-            
-            area_status_store.put(&key, &area_status)?;
-            
-            // Emit area status to state topic
-            self.producer.send(
-                Topics::STATE_EVENT_AREA_STATUS,
+            let headers: Vec<(&str, &[u8])> = area
+                .strategy
+                .iter()
+                .map(|strategy| (AREA_STRATEGY_HEADER, strategy.as_bytes()))
+                .collect();
+
+            self.producer.send_with_headers(
+                Topics::INTERNAL_EVENT_AREA_STATUS,
                 &key,
                 &area_status,
+                &headers,
             ).await?;
         }
 
@@ -130,61 +343,738 @@ impl EventService {
         Ok(())
     }
 
-    async fn handle_reserve_seat(&self, message: &ticket_master::KafkaMessage) -> Result<()> {
+    async fn handle_area_init(&self, message: &KafkaMessage) -> Result<()> {
+        let event_area_id = message.key.as_ref()
+            .ok_or_else(|| TicketMasterError::InvalidArgument("Missing event area key".to_string()))?;
+
+        let area_status: AreaStatus = message.deserialize_value().await?;
+        let strategy = message
+            .header(AREA_STRATEGY_HEADER)
+            .map(|strategy| String::from_utf8_lossy(strategy).into_owned());
+
+        let area_status_store = self.area_stores.partition_store(message.partition)?;
+        area_status_store.put_with(event_area_id, &CompactAreaStatus::from(&area_status), |batch| {
+            batch.put(&processed_offset_key(&message.topic), &message.offset)?;
+            match &strategy {
+                Some(strategy) => batch.put(&area_strategy_key(event_area_id), strategy)?,
+                None => batch.delete(&area_strategy_key(event_area_id)),
+            };
+            Ok(())
+        })?;
+
+        // Emit area status to state topic
+        self.producer.send(
+            Topics::STATE_EVENT_AREA_STATUS,
+            event_area_id,
+            &area_status,
+        ).await?;
+        let audit = AuditRecord::new(AuditEntity::Area, event_area_id, &message_audit_id(message), EVENT_SERVICE, "AreaInitialized")
+            .with_change(None, available(area_status.available_seats as usize));
+        send_audit_record(&self.producer, &audit).await?;
+
+        info!("Area initialized: {} (partition {})", event_area_id, message.partition);
+        Ok(())
+    }
+
+    async fn handle_reserve_seat(&self, message: &KafkaMessage) -> Result<()> {
         let event_area_id = message.key.as_ref()
             .ok_or_else(|| TicketMasterError::InvalidArgument("Missing event area key".to_string()))?;
         
-        let reserve_request: ReserveSeat = message.deserialize_value()?;
+        let reserve_request: ReserveSeat = message.deserialize_value().await?;
+        Span::current().record("reservation_id", reserve_request.reservation_id.as_str());
         
         info!("Processing seat reservation: {}", reserve_request.reservation_id);
 
-        let area_status_store = self.context
-            .get_rocksdb_store(Stores::AREA_STATUS)
-            .ok_or_else(|| TicketMasterError::InvalidArgument("Area status store not found".to_string()))?;
-
-        // Get current area status
-        let mut area_status = area_status_store.get::<AreaStatus>(event_area_id)?
-            .ok_or_else(|| TicketMasterError::InvalidEventArea(event_area_id.clone()))?;
-
-        // Get reservation strategy
-        let strategy = self.strategies.get(&reserve_request.reservation_type)
-            .ok_or_else(|| TicketMasterError::InvalidReservationStrategy(format!("{:?}", reserve_request.reservation_type)))?;
-
-        // Execute reservation
-        let result = strategy.reserve(&mut area_status, &reserve_request)?;
-
-        // If successful, update the area status
-        if result.result == ReservationResultEnum::Success {
-            // Update seat availability
-            for seat in &result.seats {
-                if let Some(seat_status) = area_status.seats
-                    .get_mut(seat.row as usize)
-                    .and_then(|row| row.get_mut(seat.col as usize)) {
-                    seat_status.is_available = false;
-                }
-            }
-            area_status.available_seats -= result.seats.len() as i32;
-            
-            // Update state store
-            area_status_store.put(event_area_id, &area_status)?;
-            
-            // Emit updated area status
+        let reservation_id = reserve_request.reservation_id.clone();
+        self.publishing.lock().unwrap().insert(reservation_id.clone());
+        let processed = self.reserve_seat(message, event_area_id, &reserve_request).await;
+        self.publishing.lock().unwrap().remove(&reservation_id);
+        processed
+    }
+
+    async fn reserve_seat(&self, message: &KafkaMessage, event_area_id: &str, reserve_request: &ReserveSeat) -> Result<()> {
+        let area_status_store = self.area_stores.partition_store(message.partition)?;
+        let pending_key = pending_result_key(&reserve_request.reservation_id);
+
+        // Redelivered after the seats were taken but before the result went out
+        if let Some(pending) = area_status_store.store().get::<PendingResult>(&pending_key)? {
+            warn!("Reservation {} was already processed, publishing its result again", reserve_request.reservation_id);
+            return self.publish_pending(&area_status_store, &pending_key, &pending).await;
+        }
+
+        // The strategy's bitmap scans and the store's reads and writes block;
+        // on the blocking pool, areas on other lanes are reserved meanwhile
+        let pending = {
+            let store = area_status_store.clone();
+            let strategies = self.strategies.clone();
+            let event_area_id = event_area_id.to_string();
+            let reserve_request = reserve_request.clone();
+            let (topic, offset) = (message.topic.clone(), message.offset);
+            tokio::task::spawn_blocking(move || {
+                reserve_in_store(&store, &strategies, &event_area_id, &reserve_request, &topic, offset)
+            })
+            .await
+            .map_err(|e| TicketMasterError::Io(std::io::Error::other(e)))??
+        };
+
+        self.publish_pending(&area_status_store, &pending_key, &pending).await?;
+        info!("Seat reservation processed: {} -> {:?}", 
+               reserve_request.reservation_id, pending.result.result);
+        Ok(())
+    }
+
+    /// Emit the area status if the reservation changed it, then the result,
+    /// and only then forget the result
+    async fn publish_pending(&self, store: &AreaStatusStore, key: &str, pending: &PendingResult) -> Result<()> {
+        if pending.area_changed {
+            // The latest status, which includes the reserved seats
+            let area_status = store.peek(&pending.event_area_id)?
+                .ok_or_else(|| TicketMasterError::InvalidEventArea(pending.event_area_id.clone()))?;
             self.producer.send(
                 Topics::STATE_EVENT_AREA_STATUS,
-                event_area_id,
-                &area_status,
+                &pending.event_area_id,
+                &area_status.value.to_area_status(),
             ).await?;
-        }
 
-        // Send reservation result
+            // Redelivered or republished results keep their audit id
+            let result = &pending.result;
+            let remaining = area_status.value.available_seats() as usize;
+            let audit = AuditRecord::new(
+                AuditEntity::Area,
+                &pending.event_area_id,
+                &format!("reserve#{}", result.reservation_id),
+                &result.reservation_id,
+                "SeatsReserved",
+            )
+            .with_change(Some(available(remaining + result.seats.len())), available(remaining));
+            send_audit_record(&self.producer, &audit).await?;
+        }
         self.producer.send(
             Topics::RESPONSE_RESERVATION_RESULT,
-            &reserve_request.reservation_id,
-            &result,
+            &pending.result.reservation_id,
+            &pending.result,
         ).await?;
+        store.store().delete(key)
+    }
 
-        info!("Seat reservation processed: {} -> {:?}", 
-               reserve_request.reservation_id, result.result);
+    /// Publish results still recorded in the open area status stores
+    async fn publish_pending_results(&self) -> Result<()> {
+        for partition in self.area_stores.open_partitions() {
+            let Some(store) = self.area_stores.open_store(partition)? else {
+                continue;
+            };
+            let pending: Vec<(String, PendingResult)> = store.store().scan_prefix(PENDING_RESULT_PREFIX).collect::<Result<_>>()?;
+            for (key, pending) in pending {
+                if self.publishing.lock().unwrap().contains(&pending.result.reservation_id) {
+                    continue;
+                }
+                self.publish_pending(&store, &key, &pending).await?;
+                info!("Published pending result of reservation {}", pending.result.reservation_id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_admin_command(&self, message: &KafkaMessage) -> Result<()> {
+        let event_area_id = message.key.as_ref()
+            .ok_or_else(|| TicketMasterError::InvalidArgument("Missing event area key".to_string()))?;
+
+        // Always JSON, whatever serde the other topics use
+        let payload = message.payload.as_deref()
+            .ok_or_else(|| TicketMasterError::InvalidArgument("Empty admin command".to_string()))?;
+        let command: AreaAdminCommand = serde_json::from_slice(payload)?;
+        info!("Admin command for {}: {:?}", event_area_id, command);
+
+        let area_status_store = self.area_stores.partition_store(message.partition)?;
+        let (area_status, released_from) = match command {
+            AreaAdminCommand::ReleaseSeat { row, col, .. } => loop {
+                let current = area_status_store.get(event_area_id)?
+                    .ok_or_else(|| TicketMasterError::InvalidEventArea(event_area_id.clone()))?;
+                let mut area_status = current.value;
+                let before = area_status.available_seats() as usize;
+                if !area_status.seats.contains(row, col) {
+                    return Err(TicketMasterError::InvalidArgument(format!(
+                        "Seat {}-{} is outside area {}", row, col, event_area_id
+                    )));
+                }
+                area_status.release(&[Seat { row, col }]);
+
+                let released = [Seat { row, col }];
+                let written = area_status_store.compare_and_record_with(event_area_id, current.version, &area_status, &released, true, |batch| {
+                    batch.put(&processed_offset_key(&message.topic), &message.offset)?;
+                    Ok(())
+                });
+                match written {
+                    Ok(_) => break (area_status, Some(before)),
+                    Err(TicketMasterError::VersionConflict { .. }) => {
+                        warn!("Area {} changed while releasing seat {}-{}, retrying", event_area_id, row, col);
+                    }
+                    Err(e) => return Err(e),
+                }
+            },
+            AreaAdminCommand::ReemitAreaStatus { .. } => {
+                let current = area_status_store.get(event_area_id)?
+                    .ok_or_else(|| TicketMasterError::InvalidEventArea(event_area_id.clone()))?;
+                (current.value, None)
+            }
+            AreaAdminCommand::DeleteArea { .. } => {
+                return self.delete_area(&area_status_store, event_area_id, message).await;
+            }
+        };
+
+        self.producer.send(
+            Topics::STATE_EVENT_AREA_STATUS,
+            event_area_id,
+            &area_status.to_area_status(),
+        ).await?;
+        if let Some(before) = released_from {
+            let audit = AuditRecord::new(AuditEntity::Area, event_area_id, &message_audit_id(message), ADMIN, "SeatReleased")
+                .with_change(Some(available(before)), available(area_status.available_seats() as usize));
+            send_audit_record(&self.producer, &audit).await?;
+        }
+        Ok(())
+    }
+
+    // Deleting an area already gone publishes its tombstone again, so a
+    // redelivered command finishes what a crash interrupted
+    async fn delete_area(&self, area_status_store: &AreaStatusStore, event_area_id: &str, message: &KafkaMessage) -> Result<()> {
+        let before = area_status_store.get(event_area_id)?.map(|current| current.value.available_seats() as usize);
+        area_status_store.delete_with(event_area_id, |batch| {
+            batch.put(&processed_offset_key(&message.topic), &message.offset)?;
+            Ok(())
+        })?;
+
+        self.producer.send_tombstone(Topics::STATE_EVENT_AREA_STATUS, event_area_id).await?;
+        let audit = AuditRecord::new(AuditEntity::Area, event_area_id, &message_audit_id(message), ADMIN, "AreaDeleted")
+            .with_change(before.map(available), "deleted".to_string());
+        send_audit_record(&self.producer, &audit).await?;
+        info!("Area deleted: {}", event_area_id);
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Take the seats for `reserve_request` and record its result to publish
+fn reserve_in_store(
+    area_status_store: &AreaStatusStore,
+    strategies: &StrategyRegistry,
+    event_area_id: &str,
+    reserve_request: &ReserveSeat,
+    topic: &str,
+    offset: i64,
+) -> Result<PendingResult> {
+    // Each area is reserved on one dispatcher lane, so conflicts only come
+    // from writers outside the lanes; retried from a fresh read all the same
+    let pending_key = pending_result_key(&reserve_request.reservation_id);
+    let area_strategy: Option<String> = area_status_store.store().get(&area_strategy_key(event_area_id))?;
+    loop {
+        let current = area_status_store.get(event_area_id)?
+            .ok_or_else(|| TicketMasterError::InvalidEventArea(event_area_id.to_string()))?;
+        let mut area_status = current.value;
+
+        // Execute reservation with the strategy for the request type and area
+        let strategy = strategies.select(area_strategy.as_deref(), reserve_request)?;
+        let result = strategy.reserve(&mut area_status, reserve_request)?;
+        let pending = PendingResult {
+            event_area_id: event_area_id.to_string(),
+            area_changed: result.result == ReservationResultEnum::Success,
+            result,
+        };
+        if !pending.area_changed {
+            area_status_store.store().put(&pending_key, &pending)?;
+            return Ok(pending);
+        }
+
+        // Update seat availability
+        area_status.reserve(&pending.result.seats);
+
+        // Seats, counter, the processed offset and the result to publish
+        // are written together, so a crash or a failed send cannot leave
+        // them out of step
+        let written = area_status_store.compare_and_record_with(event_area_id, current.version, &area_status, &pending.result.seats, false, |batch| {
+            batch.put(&processed_offset_key(topic), &offset)?;
+            batch.put(&pending_key, &pending)?;
+            Ok(())
+        });
+        match written {
+            Ok(_) => return Ok(pending),
+            Err(TicketMasterError::VersionConflict { .. }) => {
+                warn!("Area {} changed while reserving {}, retrying", event_area_id, reserve_request.reservation_id);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Area statuses stored as JSON before the bitmap form are converted on read
+fn get_area_status(store: &VersionedStore, key: &str) -> Result<Option<Versioned<CompactAreaStatus>>> {
+    // Read before the snapshot, so a snapshot written in between only means
+    // replaying, in order, events it already holds
+    let snapshot_version: Option<u64> = store.store().get(&snapshot_version_key(key))?;
+    let found = match store.get::<CompactAreaStatus>(key) {
+        Ok(found) => found,
+        Err(_) => store.get::<AreaStatus>(key)?.map(|legacy| Versioned {
+            value: CompactAreaStatus::from(&legacy.value),
+            version: legacy.version,
+        }),
+    };
+    let Some(mut found) = found else {
+        return Ok(None);
+    };
+
+    // Stores written before snapshots were tracked have no seat events
+    if let Some(snapshot_version) = snapshot_version.filter(|&snapshot| snapshot < found.version) {
+        let (from, to) = (seat_event_key(key, snapshot_version + 1), seat_event_key(key, found.version + 1));
+        for event in store.store().range::<SeatEvent>(&from, &to) {
+            let (_, event) = event?;
+            if event.available {
+                found.value.release(&event.seats);
+            } else {
+                found.value.reserve(&event.seats);
+            }
+        }
+    }
+    Ok(Some(found))
+}
+
+/// A reservation result waiting to be published, written in the same batch
+/// as the seats it reserved
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingResult {
+    event_area_id: String,
+    result: ReservationResult,
+    /// Whether seats were taken, so the area status has to be emitted too
+    area_changed: bool,
+}
+
+/// Seats taken or released in one write to an area, recorded under the
+/// version the write moved the area to
+#[derive(Debug, Serialize, Deserialize)]
+struct SeatEvent {
+    seats: Vec<Seat>,
+    available: bool,
+    at_ms: i64,
+}
+
+// Audit id of a change made by one message, the same when it is redelivered
+fn message_audit_id(message: &KafkaMessage) -> String {
+    format!("{}-{}@{}", message.topic, message.partition, message.offset)
+}
+
+// How audit records describe an area's state
+fn available(seats: usize) -> String {
+    format!("{} available", seats)
+}
+
+// Store key of the strategy set for an area when its event was created
+fn area_strategy_key(event_area_id: &str) -> String {
+    format!("__strategy#{}", event_area_id)
+}
+
+const PENDING_RESULT_PREFIX: &str = "__pending_result#";
+
+// When each area status was last written, in epoch millis, to warm up the
+// most recently updated areas first
+const UPDATED_AT_PREFIX: &str = "__updated_at#";
+
+fn updated_at_key(event_area_id: &str) -> String {
+    format!("{}{}", UPDATED_AT_PREFIX, event_area_id)
+}
+
+// The version of the area status written in full under the area's key;
+// seat events after it are replayed on read
+fn snapshot_version_key(event_area_id: &str) -> String {
+    format!("__snapshot_version#{}", event_area_id)
+}
+
+// Zero-padded, so an area's events sort by version
+fn seat_event_key(event_area_id: &str, version: u64) -> String {
+    format!("__seat_event#{}#{:020}", event_area_id, version)
+}
+
+fn pending_result_key(reservation_id: &str) -> String {
+    format!("{}{}", PENDING_RESULT_PREFIX, reservation_id)
+}
+
+// Store key of the last message applied to an area status store from `topic`
+fn processed_offset_key(topic: &str) -> String {
+    format!("__processed_offset#{}", topic)
+}
+
+
+/// One partition's area status store, read through an in-memory cache
+///
+/// Writes go to RocksDB first, then replace the cached status. Writes that
+/// fail, e.g. on a version conflict, drop it so the next read is fresh.
+/// With event sourcing, seat changes are appended as seat events and the
+/// full area is only written as a snapshot every so many of them.
+#[derive(Clone)]
+struct AreaStatusStore {
+    store: Arc<RocksDBStore>,
+    cache: CacheStore<String, Versioned<CompactAreaStatus>>,
+    event_sourcing: EventSourcingConfig,
+}
+
+impl AreaStatusStore {
+    fn store(&self) -> &Arc<RocksDBStore> {
+        &self.store
+    }
+
+    /// The area's status, cached once read
+    ///
+    /// Only the lane processing the area may call this, so a status read
+    /// from RocksDB can't replace a newer one cached by a write meanwhile.
+    fn get(&self, event_area_id: &str) -> Result<Option<Versioned<CompactAreaStatus>>> {
+        let key = event_area_id.to_string();
+        if let Some(cached) = self.cache.get(&key) {
+            return Ok(Some(cached));
+        }
+        let found = get_area_status(&VersionedStore::new(self.store.clone()), event_area_id)?;
+        if let Some(found) = &found {
+            self.cache.put(key, found.clone());
+        }
+        Ok(found)
+    }
+
+    /// The area's status without caching it, for readers outside the lanes
+    fn peek(&self, event_area_id: &str) -> Result<Option<Versioned<CompactAreaStatus>>> {
+        match self.cache.get(&event_area_id.to_string()) {
+            Some(cached) => Ok(Some(cached)),
+            None => get_area_status(&VersionedStore::new(self.store.clone()), event_area_id),
+        }
+    }
+
+    fn put_with<F>(&self, event_area_id: &str, area_status: &CompactAreaStatus, extra: F) -> Result<u64>
+    where
+        F: FnOnce(&mut StoreWriteBatch<'_>) -> Result<()>,
+    {
+        self.write(event_area_id, None, area_status, None, extra)
+    }
+
+    /// Write `area_status`, which has `seats` reserved or released since
+    /// `expected_version`, unless another writer got there first
+    fn compare_and_record_with<F>(
+        &self,
+        event_area_id: &str,
+        expected_version: u64,
+        area_status: &CompactAreaStatus,
+        seats: &[Seat],
+        available: bool,
+        extra: F,
+    ) -> Result<u64>
+    where
+        F: FnOnce(&mut StoreWriteBatch<'_>) -> Result<()>,
+    {
+        let event = SeatEvent {
+            seats: seats.to_vec(),
+            available,
+            at_ms: chrono::Utc::now().timestamp_millis(),
+        };
+        self.write(event_area_id, Some(expected_version), area_status, Some(&event), extra)
+    }
+
+    fn write<F>(
+        &self,
+        event_area_id: &str,
+        expected_version: Option<u64>,
+        area_status: &CompactAreaStatus,
+        event: Option<&SeatEvent>,
+        extra: F,
+    ) -> Result<u64>
+    where
+        F: FnOnce(&mut StoreWriteBatch<'_>) -> Result<()>,
+    {
+        let event = event.filter(|_| self.event_sourcing.enabled);
+        // Without a version to count from, or a snapshot to replay onto, write a snapshot
+        let snapshot = match (event, expected_version) {
+            (Some(_), Some(expected)) => {
+                let snapshot_version: Option<u64> = self.store.get(&snapshot_version_key(event_area_id))?;
+                snapshot_version.is_none_or(|snapshot| expected + 1 - snapshot >= self.event_sourcing.snapshot_interval)
+            }
+            _ => true,
+        };
+        let value = snapshot.then_some(area_status);
+        let written = VersionedStore::new(self.store.clone()).write_with(event_area_id, expected_version, value, |batch, version| {
+            batch.put(&updated_at_key(event_area_id), &event.map_or(chrono::Utc::now().timestamp_millis(), |event| event.at_ms))?;
+            if let Some(event) = event {
+                batch.put(&seat_event_key(event_area_id, version), event)?;
+            }
+            if snapshot {
+                batch.put(&snapshot_version_key(event_area_id), &version)?;
+            }
+            extra(batch)
+        });
+        self.cache_written(event_area_id, area_status, written)
+    }
+
+    /// Delete the area's status with its seat events and bookkeeping keys
+    fn delete_with<F>(&self, event_area_id: &str, extra: F) -> Result<u64>
+    where
+        F: FnOnce(&mut StoreWriteBatch<'_>) -> Result<()>,
+    {
+        let seat_events: Vec<String> = self
+            .store
+            .range::<SeatEvent>(&seat_event_key(event_area_id, 0), &seat_event_key(event_area_id, u64::MAX))
+            .map(|entry| entry.map(|(key, _)| key))
+            .collect::<Result<_>>()?;
+        let deleted = VersionedStore::new(self.store.clone()).delete_with(event_area_id, |batch| {
+            for key in &seat_events {
+                batch.delete(key);
+            }
+            batch.delete(&area_strategy_key(event_area_id));
+            batch.delete(&updated_at_key(event_area_id));
+            batch.delete(&snapshot_version_key(event_area_id));
+            extra(batch)
+        });
+        self.cache.remove(&event_area_id.to_string());
+        deleted
+    }
+
+    fn cache_written(&self, event_area_id: &str, area_status: &CompactAreaStatus, written: Result<u64>) -> Result<u64> {
+        match &written {
+            Ok(version) => self.cache.put(
+                event_area_id.to_string(),
+                Versioned { value: area_status.clone(), version: *version },
+            ),
+            Err(_) => {
+                self.cache.remove(&event_area_id.to_string());
+            }
+        }
+        written
+    }
+
+    /// Cache the configured areas, then the most recently updated, up to
+    /// `warmup.recent_areas` of them; returns how many were found
+    fn warm_up(&self, warmup: &WarmupConfig) -> Result<usize> {
+        let mut recent: Vec<(i64, String)> = self
+            .store
+            .scan_prefix::<i64>(UPDATED_AT_PREFIX)
+            .map(|entry| entry.map(|(key, updated_at)| (updated_at, key[UPDATED_AT_PREFIX.len()..].to_string())))
+            .collect::<Result<_>>()?;
+        recent.sort_unstable_by(|a, b| b.cmp(a));
+
+        let recent = recent.into_iter().take(warmup.recent_areas).map(|(_, event_area_id)| event_area_id);
+        let mut seen = HashSet::new();
+        let mut loaded = 0;
+        // Areas listed for other partitions simply aren't in this store
+        for event_area_id in warmup.areas.iter().cloned().chain(recent) {
+            if loaded >= self.cache.capacity() {
+                break;
+            }
+            if seen.insert(event_area_id.clone()) && self.get(&event_area_id)?.is_some() {
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+/// Area status stores, one RocksDB instance and cache per assigned partition
+#[derive(Clone)]
+struct AreaStatusStores {
+    context: Arc<ProcessingContext>,
+    // Kept by the rebalance listener; only these partitions' stores are opened
+    assigned: Arc<AssignedPartitions>,
+    cache_capacity: usize,
+    event_sourcing: EventSourcingConfig,
+}
+
+impl AreaStatusStores {
+    fn new(
+        context: Arc<ProcessingContext>,
+        assigned: Arc<AssignedPartitions>,
+        cache_capacity: usize,
+        event_sourcing: EventSourcingConfig,
+    ) -> Self {
+        Self { context, assigned, cache_capacity, event_sourcing }
+    }
+
+    /// The partition's store, opening it if the partition is assigned
+    ///
+    /// A message of a revoked partition still being processed would
+    /// otherwise reopen the store another instance now writes.
+    fn get_or_open(&self, partition: i32) -> Result<Arc<RocksDBStore>> {
+        if !self.assigned.current().contains(&partition) {
+            return Err(TicketMasterError::StoreNotFound(format!(
+                "area status partition {}, which is not assigned to this instance",
+                partition
+            )));
+        }
+        self.open(partition)
+    }
+
+    /// The partition's store, opening it whether or not it is assigned, e.g.
+    /// to restore it before the service starts consuming
+    fn open(&self, partition: i32) -> Result<Arc<RocksDBStore>> {
+        let handle = Stores::AREA_STATUS_STORE.partition(partition);
+        let opened = !self.context.has_store(&handle);
+        if opened {
+            info!("Opening area status store for partition {}", partition);
+            self.context.open_rocksdb_store(&handle, &format!("area-status/{}", partition))?;
+            // Cached statuses never expire; writes keep them current
+            let cache = Stores::AREA_STATUS_CACHE_STORE.partition(partition);
+            self.context.add_cache_store(&cache, self.cache_capacity, Duration::MAX);
+        }
+
+        let store = self.context.rocksdb_store(&handle)?;
+        if opened {
+            if let Some(offset) = store.get::<i64>(&processed_offset_key(Topics::COMMAND_EVENT_RESERVE_SEAT))? {
+                info!("Area status store for partition {} reflects reservations up to offset {}", partition, offset);
+            }
+        }
+        Ok(store)
+    }
+
+    /// The partition's store if it is open, without opening it
+    fn open_store(&self, partition: i32) -> Result<Option<AreaStatusStore>> {
+        let handle = Stores::AREA_STATUS_STORE.partition(partition);
+        if !self.context.has_store(&handle) {
+            return Ok(None);
+        }
+        Ok(Some(AreaStatusStore {
+            store: self.context.rocksdb_store(&handle)?,
+            cache: self.cache(partition)?,
+            event_sourcing: self.event_sourcing.clone(),
+        }))
+    }
+
+    fn cache(&self, partition: i32) -> Result<CacheStore<String, Versioned<CompactAreaStatus>>> {
+        match self.context.store(&Stores::AREA_STATUS_CACHE_STORE.partition(partition))? {
+            StateStoreBackend::Cache(cache) => Ok(cache),
+            _ => Err(TicketMasterError::InvalidArgument(format!(
+                "Area status cache of partition {} is not an in-memory cache",
+                partition
+            ))),
+        }
+    }
+
+    /// Partitions whose stores are open
+    fn open_partitions(&self) -> Vec<i32> {
+        self.context.store_partitions(&Stores::AREA_STATUS_STORE)
+    }
+
+    /// The partition's store, opening it if needed
+    fn partition_store(&self, partition: i32) -> Result<AreaStatusStore> {
+        Ok(AreaStatusStore {
+            store: self.get_or_open(partition)?,
+            cache: self.cache(partition)?,
+            event_sourcing: self.event_sourcing.clone(),
+        })
+    }
+
+    fn write_stalled(&self) -> Result<bool> {
+        self.context.write_stalled()
+    }
+
+    /// Sync and flush every open store
+    fn close(&self, partition: i32) -> Result<()> {
+        self.context.remove_store(&Stores::AREA_STATUS_CACHE_STORE.partition(partition));
+        let handle = Stores::AREA_STATUS_STORE.partition(partition);
+        if self.context.has_store(&handle) {
+            self.context.rocksdb_store(&handle)?.persist()?;
+            self.context.remove_store(&handle);
+            info!("Closed area status store for partition {}", partition);
+        }
+        Ok(())
+    }
+}
+
+/// Answers state queries from the stores of the partitions this instance owns
+struct AreaStatusQueries {
+    stores: AreaStatusStores,
+    partition_count: i32,
+    strategies: StrategyRegistry,
+}
+
+impl StateQueryHandler for AreaStatusQueries {
+    fn query(&self, store: &str, key: &str) -> Result<Option<serde_json::Value>> {
+        if store != Stores::AREA_STATUS {
+            return Ok(None);
+        }
+        let Some(partition_store) = self.stores.open_store(key_partition(key, self.partition_count))? else {
+            return Ok(None);
+        };
+        // Served in the verbose form the API returns
+        partition_store.peek(key)?
+            .map(|area_status| serde_json::to_value(area_status.value.to_area_status()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn check_availability(&self, request: &CheckAvailability) -> Result<Option<Availability>> {
+        let key = event_area_key(&request.event_id, &request.area_id);
+        let Some(partition_store) = self.stores.open_store(key_partition(&key, self.partition_count))? else {
+            return Ok(None);
+        };
+        let Some(area_status) = partition_store.peek(&key)? else {
+            return Ok(None);
+        };
+        let area_strategy: Option<String> = partition_store.store().get(&area_strategy_key(&key))?;
+        self.strategies
+            .check_availability(area_strategy.as_deref(), &area_status.value, request)
+            .map(Some)
+    }
+}
+
+/// Opens and closes per-partition area status stores as the assignment
+/// changes, warming up the caches of newly assigned ones
+struct AreaStatusRebalanceListener {
+    stores: AreaStatusStores,
+    // Commits what the runner's lanes finished of revoked partitions
+    drain: PartitionDrain,
+    warmup_config: WarmupConfig,
+    warmup: Warmup,
+}
+
+impl AreaStatusRebalanceListener {
+    fn new(stores: AreaStatusStores, drain: PartitionDrain, warmup_config: WarmupConfig, warmup: Warmup) -> Self {
+        Self { stores, drain, warmup_config, warmup }
+    }
+
+    fn warm_up(&self, partition: i32) -> Result<()> {
+        let started = Instant::now();
+        let loaded = self.stores.partition_store(partition)?.warm_up(&self.warmup_config)?;
+        info!("Warmed up {} area statuses for partition {} in {:?}", loaded, partition, started.elapsed());
+        Ok(())
+    }
+}
+
+// Reserve-seat, internal area status and admin topics are co-partitioned, so
+// all map onto the same store for a given partition number
+fn store_partitions(partitions: &[TopicPartition]) -> BTreeSet<i32> {
+    partitions
+        .iter()
+        .filter(|tp| {
+            tp.topic == Topics::COMMAND_EVENT_RESERVE_SEAT
+                || tp.topic == Topics::INTERNAL_EVENT_AREA_STATUS
+                || tp.topic == Topics::COMMAND_EVENT_ADMIN
+        })
+        .map(|tp| tp.partition)
+        .collect()
+}
+
+impl RebalanceListener for AreaStatusRebalanceListener {
+    fn on_partitions_revoked(&self, partitions: &[TopicPartition]) {
+        // Lanes still writing to the stores finish, and their offsets are
+        // committed, before the stores are closed
+        self.drain.drain(partitions);
+        self.stores.assigned.on_partitions_revoked(partitions);
+        // Persist everything written so far before another instance takes over
+        for partition in store_partitions(partitions) {
+            if let Err(e) = self.stores.close(partition) {
+                error!("Failed to close area status store for partition {}: {}", partition, e);
+            }
+        }
+    }
+
+    fn on_partitions_assigned(&self, partitions: &[TopicPartition]) {
+        self.stores.assigned.on_partitions_assigned(partitions);
+        // Runs before any message of the partitions is processed
+        for partition in store_partitions(partitions) {
+            if let Err(e) = self.warm_up(partition) {
+                error!("Failed to open area status store for partition {}: {}", partition, e);
+            }
+        }
+        // A failed warm-up only leaves reads to RocksDB, so it doesn't hold back readiness
+        self.warmup.finish();
+    }
+}
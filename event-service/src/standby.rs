@@ -0,0 +1,69 @@
+use ticket_master::{
+    Result, ServiceConfig, ProcessingContext, StandbyReplica, Stores, changelog_topic
+};
+use std::time::Duration;
+use tracing::{info, error};
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+/// Warm replica of the event-service area status stores
+///
+/// Tails the area status changelog into a local state directory. Restarting
+/// the instance without `--standby` on the same state directory promotes it,
+/// and only the changelog tail since the last checkpoint is replayed.
+pub struct StandbyService {
+    replica: StandbyReplica,
+    checkpoint_interval: Duration,
+}
+
+impl StandbyService {
+    pub async fn new(config: ServiceConfig) -> Result<Self> {
+        let kafka_config = config.to_kafka_config();
+
+        // Stores are written from the changelog only, so they are not logged themselves
+        let context = ProcessingContext::with_state_dir(config.state_dir.clone());
+        let topic = config.topic_resolver().resolve(&changelog_topic(Stores::AREA_STATUS)).into_owned();
+        let replica = StandbyReplica::new(&kafka_config, &topic, |partition| {
+            let handle = Stores::AREA_STATUS_STORE.partition(partition);
+            context.open_rocksdb_store(&handle, &format!("area-status/{}", partition))?;
+            context.rocksdb_store(&handle)
+        })?;
+
+        let checkpoint_interval = config.commit_interval().max(Duration::from_millis(100));
+
+        Ok(Self {
+            replica,
+            checkpoint_interval,
+        })
+    }
+
+    /// Replicate until `shutdown` is signalled, then checkpoint
+    pub async fn run(mut self, mut shutdown: broadcast::Receiver<()>) -> Result<()> {
+        info!("Event Service standby is running...");
+
+        let mut last_checkpoint = Instant::now();
+        loop {
+            tokio::select! {
+                _ = shutdown.recv() => {
+                    info!("Received shutdown signal");
+                    break;
+                }
+
+                applied = self.replica.apply_next(Duration::from_millis(100)) => {
+                    if let Err(e) = applied {
+                        error!("Error applying changelog record: {}", e);
+                    }
+                }
+            }
+
+            if last_checkpoint.elapsed() >= self.checkpoint_interval {
+                self.replica.checkpoint()?;
+                last_checkpoint = Instant::now();
+            }
+        }
+
+        self.replica.checkpoint()?;
+        info!("Event Service standby shutting down...");
+        Ok(())
+    }
+}
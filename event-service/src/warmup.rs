@@ -0,0 +1,37 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use ticket_master::ReadinessCheck;
+
+/// Whether the area statuses of the first partition assignment are in memory
+///
+/// Clones share the flag, so the rebalance listener finishes the warm-up the
+/// readiness probe checks.
+#[derive(Clone, Default)]
+pub struct Warmup {
+    done: Arc<AtomicBool>,
+}
+
+impl Warmup {
+    pub fn finish(&self) {
+        self.done.store(true, Ordering::Release);
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadinessCheck for Warmup {
+    fn name(&self) -> &str {
+        "area_status_warmup"
+    }
+
+    async fn check(&self) -> std::result::Result<(), String> {
+        if self.is_done() {
+            Ok(())
+        } else {
+            Err("area statuses are still being loaded".to_string())
+        }
+    }
+}
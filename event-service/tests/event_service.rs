@@ -0,0 +1,97 @@
+use chrono::{Duration as TimeDelta, Utc};
+use event_service::EventService;
+use std::sync::Arc;
+use tempfile::tempdir;
+use ticket_master::*;
+use tokio::time::{sleep, timeout, Duration};
+
+// Poll `done` until it holds, failing the test after 10 seconds
+async fn wait_until(mut done: impl FnMut() -> bool) {
+    timeout(Duration::from_secs(10), async {
+        while !done() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting");
+}
+
+fn create_event(event_name: &str, area_id: &str) -> CreateEvent {
+    let now = Utc::now();
+    CreateEvent {
+        artist: "artist".to_string(),
+        event_name: event_name.to_string(),
+        reservation_opening_time: now,
+        reservation_closing_time: now + TimeDelta::hours(1),
+        event_start_time: now + TimeDelta::hours(2),
+        event_end_time: now + TimeDelta::hours(3),
+        areas: vec![Area {
+            area_id: area_id.to_string(),
+            price: 100,
+            row_count: 2,
+            col_count: 5,
+            strategy: None,
+        }],
+    }
+}
+
+#[tokio::test]
+async fn test_event_service_answers_reserve_seat_over_mock_kafka() {
+    let kafka = MockKafka::new();
+    let state_dir = tempdir().unwrap();
+    let config = ConfigLoader::new("event-service")
+        .with_override("state.dir", &state_dir.path().to_string_lossy())
+        .with_override("commit.interval.ms", "10")
+        .load()
+        .unwrap();
+    let context = Arc::new(ProcessingContext::with_state_dir(config.state_dir.clone()));
+    let service = EventService::with_clients(
+        &config,
+        |listener| Ok(kafka.consumer_with_rebalance_listener("event-service", listener)),
+        kafka.producer(),
+        context,
+        Metrics::new().unwrap(),
+    )
+    .unwrap();
+
+    let producer = kafka.producer();
+    producer.send(Topics::COMMAND_EVENT_CREATE_EVENT, "concert", &create_event("concert", "A")).await.unwrap();
+    let reserve_seat = ReserveSeat {
+        reservation_id: "reservation-1".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 2,
+        num_of_seat: 0,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        preferences: vec![],
+    };
+
+    let (stop, _) = tokio::sync::broadcast::channel(1);
+    let run = service.run(stop.subscribe());
+    let drive = async {
+        // The area is initialized through the internal area status topic,
+        // and only then can seats of it be reserved
+        wait_until(|| !kafka.messages(Topics::STATE_EVENT_AREA_STATUS).is_empty()).await;
+        producer.send(Topics::COMMAND_EVENT_RESERVE_SEAT, &event_area_key("concert", "A"), &reserve_seat).await.unwrap();
+        wait_until(|| !kafka.messages(Topics::RESPONSE_RESERVATION_RESULT).is_empty()).await;
+        stop.send(()).unwrap();
+    };
+    let (result, _) = tokio::join!(run, drive);
+    result.unwrap();
+
+    let results = kafka.messages(Topics::RESPONSE_RESERVATION_RESULT);
+    let result: ReservationResult = results[0].deserialize_value().await.unwrap();
+    assert_eq!(result.reservation_id, "reservation-1");
+    assert_eq!(result.result, ReservationResultEnum::Success);
+    assert_eq!(result.seats.len(), 2);
+
+    // The reserved seats are gone from the area's latest status
+    let statuses = kafka.messages(Topics::STATE_EVENT_AREA_STATUS);
+    let area_status: AreaStatus = statuses.last().unwrap().deserialize_value().await.unwrap();
+    assert_eq!(area_status.available_seats, 8);
+
+    // and everything consumed is committed by shutdown
+    let partition = TopicPartition::new(Topics::COMMAND_EVENT_RESERVE_SEAT, 0);
+    assert_eq!(kafka.committed_offset("event-service", &partition), Some(1));
+}
@@ -0,0 +1,32 @@
+use clap::Parser;
+use ticket_master::{run_service, CommonArgs, Result};
+
+mod service;
+
+#[derive(Parser, Debug)]
+#[command(name = "export-service")]
+#[command(about = "Export Service for Ticket Master")]
+struct Args {
+    /// Port of the metrics and health endpoints; overrides `export.service.port`, 8086 when neither is set
+    #[arg(short = 'p', long = "port")]
+    port: Option<u16>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let configured = args.common.service_config("export-service", |config| {
+        if let Some(port) = args.port {
+            config.export.port = Some(port);
+        }
+    });
+    let Some(config) = configured.await? else {
+        return Ok(());
+    };
+
+    // SIGTERM or Ctrl+C stops consuming; records in flight are applied and committed first
+    run_service(config, service::build).await
+}
@@ -0,0 +1,161 @@
+use chrono::Utc;
+use ticket_master::{
+    AreaStatus, ConsumerRunner, KafkaConsumer, KafkaMessage, LocalObjectStorage, MessageConsumer, MessageHandler,
+    Metrics, ObjectStorage, RecordProcessor, Reservation, Result, RocksDBStore, S3Bucket, S3ObjectStorage,
+    ServiceBootstrap, ServiceConfig, SnapshotExporter, StateChange, TicketMasterError, Topics, health_router,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{interval_at, Instant, Interval};
+use tracing::{error, info, warn, Instrument};
+
+const EXPORT_SERVICE: &str = "export-service";
+const TOPICS: [&str; 2] = [Topics::STATE_EVENT_AREA_STATUS, Topics::STATE_USER_RESERVATION];
+const DEFAULT_PORT: u16 = 8086;
+
+/// Keeps the area statuses and reservations of the topics and exports a
+/// snapshot of them every `export.interval.ms`
+pub type ExportService<C = KafkaConsumer> = ConsumerRunner<ExportProcessor, C>;
+
+/// Keeps the area statuses and reservations it reads; shared by all dispatcher lanes
+pub struct ExportProcessor {
+    exporter: SnapshotExporter,
+    storage: Arc<dyn ObjectStorage>,
+    export_interval: Duration,
+    metrics: Metrics,
+}
+
+pub async fn build(config: ServiceConfig) -> Result<ExportService> {
+    let bootstrap = ServiceBootstrap::new(&config).await?;
+
+    // Rebuilt from the topics if lost, as only the latest of each record matters
+    let store = RocksDBStore::new(format!("{}/export/{}", config.state_dir, config.application_id))?;
+    let exporter = SnapshotExporter::new(Arc::new(store), config.export.format)?
+        .with_prefix(config.export.prefix.as_deref().unwrap_or_default());
+    let storage = export_storage(&config)?;
+
+    let mut service = with_clients(&config, bootstrap.consumer()?, exporter, storage, bootstrap.metrics().clone())?;
+    let port = config.export.port.unwrap_or(DEFAULT_PORT);
+    bootstrap.serve(&mut service, "export-health", port, health_router(bootstrap.readiness(&TOPICS)?)).await?;
+
+    // Only heartbeats are produced
+    bootstrap.start_heartbeat(&mut service, bootstrap.plain_producer()?, env!("CARGO_PKG_VERSION"));
+    Ok(service)
+}
+
+/// S3 when `export.s3.bucket` is set, files under `export.local.dir` otherwise
+fn export_storage(config: &ServiceConfig) -> Result<Arc<dyn ObjectStorage>> {
+    let export = &config.export;
+    if let Some(bucket) = &export.s3_bucket {
+        info!("Exporting to S3 bucket {}", bucket);
+        let storage = S3ObjectStorage::new(S3Bucket {
+            name: bucket,
+            region: export.s3_region.as_deref(),
+            endpoint: export.s3_endpoint.as_deref(),
+            access_key: export.s3_access_key.as_deref(),
+            secret_key: export.s3_secret_key.as_deref(),
+        })?;
+        return Ok(Arc::new(storage));
+    }
+    let dir = export.local_dir.clone().unwrap_or_else(|| format!("{}/export", config.state_dir));
+    info!("Exporting to {}", dir);
+    let base_url = format!("file://{}", dir);
+    Ok(Arc::new(LocalObjectStorage::new(dir, &base_url)))
+}
+
+/// Build the service on the given consumer and storage, e.g. from a
+/// `MockKafka`; failed records and exported rows are counted in `metrics`
+pub fn with_clients<C: MessageConsumer>(
+    config: &ServiceConfig,
+    consumer: C,
+    exporter: SnapshotExporter,
+    storage: Arc<dyn ObjectStorage>,
+    metrics: Metrics,
+) -> Result<ExportService<C>> {
+    // Records are applied in order per key and each writes a key of its own
+    let processor = ExportProcessor {
+        exporter,
+        storage,
+        export_interval: config.export.interval(),
+        metrics: metrics.clone(),
+    };
+    ConsumerRunner::new(config, consumer, &TOPICS, processor, metrics)
+}
+
+// Failed records are logged and skipped; the next record of the same key corrects the store
+#[async_trait::async_trait]
+impl RecordProcessor for ExportProcessor {
+    fn on_failure(&self, e: TicketMasterError) -> Result<()> {
+        error!("Error applying record: {}", e);
+        Ok(())
+    }
+
+    // The first snapshot waits an interval, so the topics are read by then
+    fn timer(&self) -> Option<Interval> {
+        Some(interval_at(Instant::now() + self.export_interval, self.export_interval))
+    }
+
+    async fn tick(&self) {
+        if let Err(e) = self.export().await {
+            error!("Error exporting snapshot: {}", e);
+        }
+    }
+}
+
+impl ExportProcessor {
+    // Every file is uploaded, even after one fails; the snapshot counts as exported only if all were
+    async fn export(&self) -> Result<()> {
+        let exporter = self.exporter.clone();
+        let snapshot_at = Utc::now();
+        let files = tokio::task::spawn_blocking(move || exporter.snapshot(snapshot_at))
+            .await
+            .map_err(|e| TicketMasterError::Io(std::io::Error::other(e)))??;
+
+        let content_type = self.exporter.format().content_type();
+        let (count, mut failed) = (files.len(), 0);
+        for file in files {
+            match self.storage.put(&file.key, content_type, file.data).await {
+                Ok(_) => self.metrics.exported_rows.with_label_values(&[file.table]).inc_by(file.rows as f64),
+                Err(e) => {
+                    warn!("Failed to export {}: {}", file.key, e);
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            return Err(TicketMasterError::Export(format!("{} of {} files failed to upload", failed, count)));
+        }
+        self.metrics.export_last_success.set(snapshot_at.timestamp() as f64);
+        info!("Exported snapshot of {} files", count);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageHandler for ExportProcessor {
+    async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+        async {
+            match message.topic.as_str() {
+                Topics::STATE_EVENT_AREA_STATUS => match message.state_change::<AreaStatus>().await? {
+                    StateChange::Upsert(area_status) => self.exporter.apply_area_status(&area_status),
+                    StateChange::Delete(event_area_id) => self.exporter.delete_area(event_area_id),
+                },
+                Topics::STATE_USER_RESERVATION => match message.state_change::<Reservation>().await? {
+                    StateChange::Upsert(reservation) => self.exporter.apply_reservation(&reservation),
+                    StateChange::Delete(reservation_id) => self.exporter.delete_reservation(reservation_id),
+                },
+                _ => {
+                    warn!("Unknown topic: {}", message.topic);
+                    Ok(())
+                }
+            }
+        }
+        .instrument(message.span())
+        .await
+        .map_err(|e: TicketMasterError| {
+            let e = e.with_context(message.error_context(EXPORT_SERVICE));
+            self.metrics.record_error(&e);
+            e
+        })
+    }
+}
@@ -0,0 +1,298 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use chrono::Utc;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use ticket_master::{ApiResponse, AreaStatus, ErrorCode, Reservation, UserProfile, UserTokens};
+use tracing::{debug, error};
+
+use crate::upstream::{Answer, Upstreams};
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+// Most reservations an event page or profile lists
+const RESERVATION_PAGE_LIMIT: &str = "200";
+
+/// A failed request: the gateway's own error, or an internal service's passed on
+#[derive(Debug)]
+pub enum ApiError {
+    Gateway(ticket_master::ApiError),
+    /// An error answer of an internal service, with its status and body
+    Upstream { status: StatusCode, body: Value },
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self::Gateway(ticket_master::ApiError::new(status, code, message))
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::Gateway(ticket_master::ApiError::not_found(message))
+    }
+
+    pub fn bad_gateway(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_GATEWAY, "upstream_unavailable", message)
+    }
+
+    pub fn upstream(status: StatusCode, body: Value) -> Self {
+        Self::Upstream { status, body }
+    }
+
+    fn unauthorized(code: &'static str, message: &str) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, code, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Gateway(e) => e.into_response(),
+            Self::Upstream { status, body } => {
+                let mut response = (status, Json(body)).into_response();
+                response.extensions_mut().insert(ErrorCode("upstream_error"));
+                response
+            }
+        }
+    }
+}
+
+type ApiResult<T> = std::result::Result<Json<ApiResponse<T>>, ApiError>;
+
+/// The user a request's bearer token was issued to
+#[derive(Debug, Clone)]
+pub struct Customer(pub String);
+
+/// What the gateway needs to answer: the internal services and the token key
+#[derive(Clone)]
+pub struct Gateway {
+    upstreams: Upstreams,
+    tokens: UserTokens,
+}
+
+impl Gateway {
+    pub fn new(upstreams: Upstreams, tokens: UserTokens) -> Self {
+        Self { upstreams, tokens }
+    }
+}
+
+/// An event with its areas' seat maps and the caller's reservations for it
+#[derive(Debug, Serialize)]
+struct EventPage {
+    event_id: String,
+    areas: Vec<AreaStatus>,
+    reservations: Vec<Reservation>,
+}
+
+/// The caller's profile, when user-service knows it, and reservations
+#[derive(Debug, Serialize)]
+struct Me {
+    user_id: String,
+    profile: Option<UserProfile>,
+    reservations: Vec<Reservation>,
+}
+
+// All that ownership checks need of a reservation
+#[derive(Debug, Deserialize)]
+struct OwnedReservation {
+    user_id: String,
+}
+
+/// The customer API; every route needs a bearer token from `UserTokens`
+pub fn routes(gateway: Gateway) -> Router {
+    Router::new()
+        .route("/events", get(list_events))
+        .route("/events/:event_id", get(get_event_page))
+        .route("/me", get(get_me))
+        .route("/me/reservations", get(list_my_reservations))
+        .route("/reservations", post(create_reservation))
+        .route("/reservations/:reservation_id", get(get_reservation))
+        .route("/reservations/:reservation_id/payment", post(pay_reservation))
+        .route_layer(middleware::from_fn_with_state(gateway.clone(), require_user))
+        .with_state(gateway)
+}
+
+/// Reject requests without a valid bearer token and tell handlers whose it is
+async fn require_user(State(gateway): State<Gateway>, mut request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let Some(token) = token else {
+        return ApiError::unauthorized("missing_token", "A bearer token is required").into_response();
+    };
+    let Some(user_id) = gateway.tokens.verify(token, Utc::now()) else {
+        return ApiError::unauthorized("invalid_token", "The bearer token is invalid or expired").into_response();
+    };
+    debug!("Request from user {}", user_id);
+    request.extensions_mut().insert(Customer(user_id));
+    next.run(request).await
+}
+
+/// Give every request an `x-request-id`, so the calls it fans out to log the same one
+pub async fn assign_request_id(mut request: Request, next: Next) -> Response {
+    let request_id = match request.headers().get(REQUEST_ID_HEADER) {
+        Some(value) => value.clone(),
+        None => {
+            let generated = HeaderValue::from_str(&uuid::Uuid::new_v4().to_string()).expect("UUIDs are valid header values");
+            request.headers_mut().insert(REQUEST_ID_HEADER, generated.clone());
+            generated
+        }
+    };
+    let mut response = next.run(request).await;
+    response.headers_mut().entry(REQUEST_ID_HEADER).or_insert(request_id);
+    response
+}
+
+async fn list_events(
+    State(gateway): State<Gateway>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, ApiError> {
+    let query = pass_on(&query, &["limit", "cursor", "sort"]);
+    let answer = gateway.upstreams.ticket(Method::GET, "/events", &query, None, &headers).await?;
+    Ok(passed_on(answer))
+}
+
+// Areas and reservations are fetched at once
+async fn get_event_page(
+    State(gateway): State<Gateway>,
+    Extension(Customer(user_id)): Extension<Customer>,
+    Path(event_id): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult<EventPage> {
+    let areas_path = format!("/events/{}/areas", event_id);
+    let query = [
+        ("user_id", user_id),
+        ("event_id", event_id.clone()),
+        ("limit", RESERVATION_PAGE_LIMIT.to_string()),
+    ];
+    let (areas, reservations) = tokio::try_join!(
+        gateway.upstreams.ticket(Method::GET, &areas_path, &[], None, &headers),
+        gateway.upstreams.ticket(Method::GET, "/reservations", &query, None, &headers),
+    )?;
+    let areas: Vec<AreaStatus> = areas.data()?;
+    if areas.is_empty() {
+        return Err(ApiError::not_found("Event not found"));
+    }
+    Ok(Json(ApiResponse::success(EventPage {
+        event_id,
+        areas,
+        reservations: reservations.data()?,
+    })))
+}
+
+// The profile is left out rather than failing the page when user-service is down
+async fn get_me(
+    State(gateway): State<Gateway>,
+    Extension(Customer(user_id)): Extension<Customer>,
+    headers: HeaderMap,
+) -> ApiResult<Me> {
+    let profile_path = format!("/users/{}", user_id);
+    let query = [("user_id", user_id.clone()), ("limit", RESERVATION_PAGE_LIMIT.to_string())];
+    let (profile, reservations) = tokio::join!(
+        gateway.upstreams.user(&profile_path, &headers),
+        gateway.upstreams.ticket(Method::GET, "/reservations", &query, None, &headers),
+    );
+    let profile = match profile {
+        Some(Ok(answer)) => answer.data()?,
+        Some(Err(ApiError::Upstream { status: StatusCode::NOT_FOUND, .. })) | None => None,
+        Some(Err(e)) => {
+            error!("Profile of user {} unavailable: {:?}", user_id, e);
+            None
+        }
+    };
+    Ok(Json(ApiResponse::success(Me {
+        user_id,
+        profile,
+        reservations: reservations?.data()?,
+    })))
+}
+
+async fn list_my_reservations(
+    State(gateway): State<Gateway>,
+    Extension(Customer(user_id)): Extension<Customer>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, ApiError> {
+    let mut query = pass_on(&query, &["limit", "cursor", "sort", "state", "event_id"]);
+    query.push(("user_id", user_id));
+    let answer = gateway.upstreams.ticket(Method::GET, "/reservations", &query, None, &headers).await?;
+    Ok(passed_on(answer))
+}
+
+// Reservations are always made for the caller, whatever `user_id` the body names
+async fn create_reservation(
+    State(gateway): State<Gateway>,
+    Extension(Customer(user_id)): Extension<Customer>,
+    headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> std::result::Result<Response, ApiError> {
+    let Some(fields) = body.as_object_mut() else {
+        return Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed", "The body must be a JSON object"));
+    };
+    fields.insert("user_id".to_string(), Value::String(user_id));
+    let answer = gateway.upstreams.ticket(Method::POST, "/reservations", &[], Some(&body), &headers).await?;
+    Ok(passed_on(answer))
+}
+
+async fn get_reservation(
+    State(gateway): State<Gateway>,
+    Extension(customer): Extension<Customer>,
+    Path(reservation_id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, ApiError> {
+    let query = pass_on(&query, &["wait"]);
+    let answer = owned_reservation(&gateway, &customer, &reservation_id, &query, &headers).await?;
+    Ok(passed_on(answer))
+}
+
+async fn pay_reservation(
+    State(gateway): State<Gateway>,
+    Extension(customer): Extension<Customer>,
+    Path(reservation_id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> std::result::Result<Response, ApiError> {
+    owned_reservation(&gateway, &customer, &reservation_id, &[], &headers).await?;
+    let path = format!("/reservations/{}/payment", reservation_id);
+    let answer = gateway.upstreams.ticket(Method::POST, &path, &[], Some(&body), &headers).await?;
+    Ok(passed_on(answer))
+}
+
+// Other users' reservations are answered as not found, so their ids don't leak
+async fn owned_reservation(
+    gateway: &Gateway,
+    Customer(user_id): &Customer,
+    reservation_id: &str,
+    query: &[(&str, String)],
+    headers: &HeaderMap,
+) -> std::result::Result<Answer, ApiError> {
+    let path = format!("/reservations/{}", reservation_id);
+    let answer = gateway.upstreams.ticket(Method::GET, &path, query, None, headers).await?;
+    let reservation: OwnedReservation = answer.data()?;
+    if reservation.user_id != *user_id {
+        return Err(ApiError::not_found("Reservation not found"));
+    }
+    Ok(answer)
+}
+
+// The query parameters of `names` the customer set
+fn pass_on<'a>(query: &HashMap<String, String>, names: &[&'a str]) -> Vec<(&'a str, String)> {
+    names
+        .iter()
+        .filter_map(|name| query.get(*name).map(|value| (*name, value.clone())))
+        .collect()
+}
+
+fn passed_on(answer: Answer) -> Response {
+    (answer.status, Json(answer.body)).into_response()
+}
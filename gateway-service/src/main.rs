@@ -0,0 +1,82 @@
+use axum::middleware;
+use clap::Parser;
+use std::net::SocketAddr;
+use ticket_master::{
+    health_router, serve_metrics, shutdown_telemetry, CommonArgs, HttpReadiness, Metrics, ReadinessProbe, Result,
+    ShutdownCoordinator, TicketMasterError, UserTokens, READINESS_TIMEOUT,
+};
+use tracing::info;
+
+mod api;
+mod upstream;
+
+use api::Gateway;
+use upstream::Upstreams;
+
+const DEFAULT_PORT: u16 = 8084;
+
+#[derive(Parser, Debug)]
+#[command(name = "gateway-service")]
+#[command(about = "Customer API gateway for Ticket Master")]
+struct Args {
+    /// Port to listen on; overrides `gateway.service.port`, 8084 when neither is set
+    #[arg(short = 'p', long = "port")]
+    port: Option<u16>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let configured = args.common.service_config("gateway-service", |config| {
+        if let Some(port) = args.port {
+            config.gateway_service.port = Some(port);
+        }
+    });
+    let Some(config) = configured.await? else {
+        return Ok(());
+    };
+
+    let port = config.gateway_service.port.unwrap_or(DEFAULT_PORT);
+    let secret = config.gateway_service.token_secret.as_deref().ok_or_else(|| {
+        TicketMasterError::InvalidArgument("gateway.service.token.secret is required".to_string())
+    })?;
+    let tokens = UserTokens::new(secret)?;
+    let upstreams = Upstreams::new(&config.gateway_service)?;
+    info!("Fronting ticket-service at {}", upstreams.ticket_url());
+
+    // Ready once the services every route needs are
+    let mut readiness = ReadinessProbe::new(READINESS_TIMEOUT).with_check(HttpReadiness::new(
+        "ticket_service",
+        &format!("{}/health/ready", upstreams.ticket_url()),
+    ));
+    if let Some(user_url) = upstreams.user_url() {
+        readiness = readiness.with_check(HttpReadiness::new("user_service", &format!("{}/health/ready", user_url)));
+    }
+
+    let metrics = Metrics::new()?;
+    let app = serve_metrics(api::routes(Gateway::new(upstreams, tokens)).merge(health_router(readiness)), metrics)
+        .layer(middleware::from_fn(api::assign_request_id));
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("Gateway Service listening on {}", addr);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+
+    // On SIGTERM stop accepting connections and finish in-flight requests
+    let coordinator = ShutdownCoordinator::default();
+    let mut stopping = coordinator.subscribe();
+    coordinator.signal_on_termination();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            let _ = stopping.recv().await;
+        })
+        .await?;
+    info!("HTTP server stopped");
+
+    coordinator.shutdown().await?;
+    info!("Gateway Service shut down");
+    shutdown_telemetry();
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use axum::http::{HeaderMap, StatusCode};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use ticket_master::{GatewayServiceConfig, Result, TicketMasterError};
+
+use crate::api::ApiError;
+
+/// Headers of a customer request passed on to the internal services
+const FORWARDED_HEADERS: [&str; 5] = ["x-request-id", "x-queue-token", "idempotency-key", "traceparent", "tracestate"];
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// Clients of the internal services behind the gateway
+#[derive(Clone)]
+pub struct Upstreams {
+    http: reqwest::Client,
+    ticket_url: String,
+    user_url: Option<String>,
+    ticket_api_key: Option<String>,
+}
+
+/// A 2xx answer of an internal service, with a body shaped like ticket-service's `ApiResponse`
+#[derive(Debug)]
+pub struct Answer {
+    pub status: StatusCode,
+    pub body: Value,
+}
+
+impl Answer {
+    pub fn data<T: DeserializeOwned>(&self) -> std::result::Result<T, ApiError> {
+        serde_json::from_value(self.body.get("data").cloned().unwrap_or(Value::Null))
+            .map_err(|e| ApiError::bad_gateway(format!("Unreadable answer: {}", e)))
+    }
+}
+
+impl Upstreams {
+    pub fn new(config: &GatewayServiceConfig) -> Result<Self> {
+        let ticket_url = config.ticket_url.as_deref().filter(|url| !url.is_empty()).ok_or_else(|| {
+            TicketMasterError::InvalidArgument("gateway.service.ticket.url is required".to_string())
+        })?;
+        let http = reqwest::Client::builder()
+            .timeout(config.timeout())
+            .build()
+            .map_err(|e| TicketMasterError::InvalidArgument(format!("Failed to create the HTTP client: {}", e)))?;
+        Ok(Self {
+            http,
+            ticket_url: ticket_url.trim_end_matches('/').to_string(),
+            user_url: config.user_url.as_deref().filter(|url| !url.is_empty()).map(|url| url.trim_end_matches('/').to_string()),
+            ticket_api_key: config.ticket_api_key.clone(),
+        })
+    }
+
+    pub fn ticket_url(&self) -> &str {
+        &self.ticket_url
+    }
+
+    pub fn user_url(&self) -> Option<&str> {
+        self.user_url.as_deref()
+    }
+
+    /// Call ticket-service with the API key, passing on the customer's `headers`
+    pub async fn ticket(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&Value>,
+        headers: &HeaderMap,
+    ) -> std::result::Result<Answer, ApiError> {
+        let mut request = self.http.request(method, format!("{}{}", self.ticket_url, path)).query(query);
+        if let Some(api_key) = &self.ticket_api_key {
+            request = request.header(API_KEY_HEADER, api_key);
+        }
+        send("ticket-service", request, body, headers).await
+    }
+
+    /// Call user-service; `None` when no user-service URL is set
+    pub async fn user(&self, path: &str, headers: &HeaderMap) -> Option<std::result::Result<Answer, ApiError>> {
+        let url = self.user_url.as_ref()?;
+        Some(send("user-service", self.http.get(format!("{}{}", url, path)), None, headers).await)
+    }
+}
+
+// Error answers are passed on as they came, so clients see the service's own codes
+async fn send(
+    service: &str,
+    mut request: reqwest::RequestBuilder,
+    body: Option<&Value>,
+    headers: &HeaderMap,
+) -> std::result::Result<Answer, ApiError> {
+    for name in FORWARDED_HEADERS {
+        if let Some(value) = headers.get(name).and_then(|value| value.to_str().ok()) {
+            request = request.header(name, value);
+        }
+    }
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+    let response = request.send().await.map_err(|e| {
+        if e.is_timeout() {
+            ApiError::new(StatusCode::GATEWAY_TIMEOUT, "upstream_timeout", format!("{} did not answer in time", service))
+        } else {
+            ApiError::bad_gateway(format!("{} is unreachable: {}", service, e))
+        }
+    })?;
+
+    // reqwest and axum use different versions of the `http` types
+    let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let body = response.json::<Value>().await.unwrap_or(Value::Null);
+    if status.is_success() {
+        Ok(Answer { status, body })
+    } else if body.is_object() {
+        Err(ApiError::upstream(status, body))
+    } else {
+        Err(ApiError::bad_gateway(format!("{} answered {}", service, status)))
+    }
+}
@@ -0,0 +1,71 @@
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use ticket_master::{Notification, NotificationChannel, NotificationConfig, Result, SmtpSecurity, TicketMasterError};
+
+/// Emails notifications through an SMTP server
+pub struct SmtpChannel {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: String,
+}
+
+impl SmtpChannel {
+    /// The channel of the `notification.smtp.` properties; none without a host
+    pub fn from_config(config: &NotificationConfig) -> Result<Option<Self>> {
+        let Some(host) = &config.smtp_host else {
+            return Ok(None);
+        };
+        let from = config.email_from.as_deref().ok_or_else(|| {
+            TicketMasterError::InvalidArgument("notification.email.from is required with notification.smtp.host".to_string())
+        })?;
+
+        let mut builder = match config.smtp_security.unwrap_or_default() {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host).map_err(smtp_error)?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(smtp_error)?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if let Some(port) = config.smtp_port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &config.smtp_username {
+            let password = config.smtp_password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+
+        Ok(Some(Self {
+            mailer: builder.build(),
+            from: parse_mailbox(from)?,
+            to: config.email_to.clone().unwrap_or_else(|| "{user_id}".to_string()),
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for SmtpChannel {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let to = parse_mailbox(&self.to.replace("{user_id}", &notification.user_id))?;
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(notification.subject.as_str())
+            .body(notification.body.clone())
+            .map_err(|e| TicketMasterError::Notification(format!("Failed to build the email: {}", e)))?;
+        self.mailer.send(email).await.map_err(smtp_error)?;
+        Ok(())
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox> {
+    address
+        .parse()
+        .map_err(|e| TicketMasterError::Notification(format!("Invalid email address {}: {}", address, e)))
+}
+
+fn smtp_error(e: lettre::transport::smtp::Error) -> TicketMasterError {
+    TicketMasterError::Notification(format!("SMTP failed: {}", e))
+}
@@ -0,0 +1,24 @@
+use clap::Parser;
+use ticket_master::{run_service, CommonArgs, Result};
+
+mod email;
+mod service;
+
+#[derive(Parser, Debug)]
+#[command(name = "notification-service")]
+#[command(about = "Notification Service for Ticket Master")]
+struct Args {
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let Some(config) = args.common.service_config("notification-service", |_| {}).await? else {
+        return Ok(());
+    };
+
+    // SIGTERM or Ctrl+C stops consuming; notifications in flight finish and are committed first
+    run_service(config, service::build).await
+}
@@ -0,0 +1,96 @@
+use ticket_master::{
+    ConsumerRunner, HttpSmsGateway, KafkaConsumer, KafkaMessage, MessageConsumer, MessageHandler, Metrics, Notifier,
+    RecordProcessor, Reservation, Result, ServiceBootstrap, ServiceConfig, SmsChannel, TicketMasterError, Topics,
+    WebhookChannel,
+};
+use crate::email::SmtpChannel;
+use tracing::{error, info, warn, Instrument};
+
+const NOTIFICATION_SERVICE: &str = "notification-service";
+const TOPICS: [&str; 1] = [Topics::STATE_USER_RESERVATION];
+
+/// Notifies of the reservations on `state.user.reservation`
+pub type NotificationService<C = KafkaConsumer> = ConsumerRunner<NotificationProcessor, C>;
+
+/// Notifies of the reservations it reads; shared by all dispatcher lanes
+pub struct NotificationProcessor {
+    notifier: Notifier,
+    metrics: Metrics,
+}
+
+pub async fn build(config: ServiceConfig) -> Result<NotificationService> {
+    let bootstrap = ServiceBootstrap::new(&config).await?;
+    let metrics = bootstrap.metrics().clone();
+
+    let mut notifier = Notifier::open(
+        format!("{}/notifications/{}", config.state_dir, config.application_id),
+        config.notification.templates(),
+    )?
+    .with_metrics(metrics.clone());
+    if let Some(smtp) = SmtpChannel::from_config(&config.notification)? {
+        notifier = notifier.with_channel(smtp);
+    }
+    if let Some(url) = &config.notification.webhook_url {
+        notifier = notifier.with_channel(WebhookChannel::new(url)?);
+    }
+    if let Some(url) = &config.notification.sms_gateway_url {
+        notifier = notifier.with_channel(SmsChannel::new(HttpSmsGateway::new(url)?));
+    }
+    if notifier.channels().next().is_none() {
+        warn!("No notification channel configured; reservations are only marked notified");
+    }
+    info!("Notifying by {}", notifier.channels().collect::<Vec<_>>().join(", "));
+
+    let mut service = with_clients(&config, bootstrap.consumer()?, notifier, metrics)?;
+    bootstrap.serve_metrics_port(&mut service).await?;
+
+    // Heartbeats are the only thing the service produces
+    bootstrap.start_heartbeat(&mut service, bootstrap.plain_producer()?, env!("CARGO_PKG_VERSION"));
+    Ok(service)
+}
+
+/// Build the service on the given consumer, e.g. from a `MockKafka`;
+/// failed notifications are counted in `metrics`
+pub fn with_clients<C: MessageConsumer>(
+    config: &ServiceConfig,
+    consumer: C,
+    notifier: Notifier,
+    metrics: Metrics,
+) -> Result<NotificationService<C>> {
+    // Reservations are keyed by id, so each one's outcomes are still notified in order
+    let processor = NotificationProcessor { notifier, metrics: metrics.clone() };
+    ConsumerRunner::new(config, consumer, &TOPICS, processor, metrics)
+}
+
+// Failed notifications are logged and skipped; their outcome is not sent again
+#[async_trait::async_trait]
+impl RecordProcessor for NotificationProcessor {
+    fn on_failure(&self, e: TicketMasterError) -> Result<()> {
+        error!("Error notifying: {}", e);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageHandler for NotificationProcessor {
+    async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+        // Deleted reservations are failed or cancelled ones, already notified
+        if message.is_tombstone() {
+            return Ok(());
+        }
+        async {
+            let reservation: Reservation = message.deserialize_value().await?;
+            if self.notifier.notify(&reservation).await? {
+                info!("Notified {} of reservation {} being {:?}", reservation.user_id, reservation.reservation_id, reservation.state);
+            }
+            Ok(())
+        }
+        .instrument(message.span())
+        .await
+        .map_err(|e: TicketMasterError| {
+            let e = e.with_context(message.error_context(NOTIFICATION_SERVICE));
+            self.metrics.record_error(&e);
+            e
+        })
+    }
+}
@@ -0,0 +1,23 @@
+use clap::Parser;
+use ticket_master::{run_service, CommonArgs, Result};
+
+mod service;
+
+#[derive(Parser, Debug)]
+#[command(name = "payment-service")]
+#[command(about = "Payment Service for Ticket Master")]
+struct Args {
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let Some(config) = args.common.service_config("payment-service", |_| {}).await? else {
+        return Ok(());
+    };
+
+    // SIGTERM or Ctrl+C stops consuming; charges in flight finish and are committed first
+    run_service(config, service::build).await
+}
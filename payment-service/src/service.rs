@@ -0,0 +1,109 @@
+use ticket_master::{
+    ChargePayment, CircuitBreaker, ConsumerRunner, HttpPaymentProvider, KafkaConsumer, KafkaMessage, KafkaProducer,
+    MessageConsumer, MessageHandler, MessageProducer, Metrics, MockPaymentProvider, PaymentProviderKind, Payments,
+    RecordProcessor, Result, RetryPolicies, ServiceBootstrap, ServiceConfig, TicketMasterError, Topics,
+    retry_transient,
+};
+use std::sync::Arc;
+use tracing::{error, info, Instrument};
+
+const PAYMENT_SERVICE: &str = "payment-service";
+const TOPICS: [&str; 1] = [Topics::COMMAND_PAYMENT_CHARGE];
+
+/// Charges the commands on `command.payment.charge` and publishes their results
+pub type PaymentService<C = KafkaConsumer, P = KafkaProducer> = ConsumerRunner<PaymentProcessor<P>, C>;
+
+/// Charges the commands it reads and publishes their results; shared by all
+/// dispatcher lanes
+pub struct PaymentProcessor<P> {
+    producer: P,
+    payments: Payments,
+    // Charges and result sends failing on a transient error are made again
+    retry: RetryPolicies,
+    metrics: Metrics,
+}
+
+pub async fn build(config: ServiceConfig) -> Result<PaymentService> {
+    let bootstrap = ServiceBootstrap::new(&config).await?;
+    let metrics = bootstrap.metrics().clone();
+    let producer = KafkaProducer::with_serdes(config.to_kafka_config(), bootstrap.serdes().clone())?
+        .with_topic_resolver(config.topic_resolver())
+        .with_metrics(metrics.clone())
+        .with_circuit_breaker(Arc::new(CircuitBreaker::kafka_producer().with_metrics(metrics.clone())));
+
+    let path = format!("{}/payments/{}", config.state_dir, config.application_id);
+    let payments = match config.payment.provider {
+        PaymentProviderKind::Mock => Payments::open(path, MockPaymentProvider)?,
+        PaymentProviderKind::Http => {
+            let url = config.payment.api_url.as_deref().ok_or_else(|| {
+                TicketMasterError::InvalidArgument("payment.api.url is required with the http provider".to_string())
+            })?;
+            Payments::open(path, HttpPaymentProvider::new(url, config.payment.api_key.as_deref().unwrap_or_default())?)?
+        }
+    }
+    .with_metrics(metrics.clone());
+    info!("Charging through the {} payment provider", payments.provider());
+
+    let mut service = with_clients(&config, bootstrap.consumer()?, producer.clone(), payments, metrics)?;
+    bootstrap.serve_metrics_port(&mut service).await?;
+    bootstrap.start_heartbeat(&mut service, producer, env!("CARGO_PKG_VERSION"));
+    Ok(service)
+}
+
+/// Build the service on the given clients, e.g. from a `MockKafka`; failed
+/// charges and retries are counted in `metrics`
+pub fn with_clients<C: MessageConsumer, P: MessageProducer>(
+    config: &ServiceConfig,
+    consumer: C,
+    producer: P,
+    payments: Payments,
+    metrics: Metrics,
+) -> Result<PaymentService<C, P>> {
+    // Charges are keyed by reservation, so one reservation is never charged twice at once
+    let processor = PaymentProcessor {
+        producer,
+        payments,
+        retry: config.retry.policies(),
+        metrics: metrics.clone(),
+    };
+    ConsumerRunner::new(config, consumer, &TOPICS, processor, metrics)
+}
+
+// Commands failing past their retries are logged and skipped; the client may charge again
+#[async_trait::async_trait]
+impl<P: MessageProducer> RecordProcessor for PaymentProcessor<P> {
+    fn on_failure(&self, e: TicketMasterError) -> Result<()> {
+        error!("Error charging: {}", e);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: MessageProducer> MessageHandler for PaymentProcessor<P> {
+    async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+        retry_transient(&self.retry, "charge payment", Some(&self.metrics), || self.process_message(message))
+            .instrument(message.span())
+            .await
+            .map_err(|e| {
+                let e = e.with_context(message.error_context(PAYMENT_SERVICE));
+                self.metrics.record_error(&e);
+                e
+            })
+    }
+}
+
+impl<P: MessageProducer> PaymentProcessor<P> {
+    // Charged again on a redelivery, the stored result is published again
+    async fn process_message(&self, message: &KafkaMessage) -> Result<()> {
+        let charge: ChargePayment = message.deserialize_value().await?;
+        let result = self.payments.charge(&charge).await?;
+        self.producer.send(Topics::RESPONSE_PAYMENT_RESULT, &result.reservation_id, &result).await?;
+        info!(
+            "Charge {} of reservation {} {}",
+            charge.idempotency_key,
+            charge.reservation_id,
+            result.status.as_str()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use clap::Parser;
+use ticket_master::{run_service, CommonArgs, Result};
+
+mod service;
+
+#[derive(Parser, Debug)]
+#[command(name = "reconciliation-service")]
+#[command(about = "Reconciliation Service for Ticket Master")]
+struct Args {
+    /// Port of the report, metrics and health endpoints; overrides `reconciliation.service.port`, 8085 when neither is set
+    #[arg(short = 'p', long = "port")]
+    port: Option<u16>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let configured = args.common.service_config("reconciliation-service", |config| {
+        if let Some(port) = args.port {
+            config.reconciliation_service.port = Some(port);
+        }
+    });
+    let Some(config) = configured.await? else {
+        return Ok(());
+    };
+
+    // SIGTERM or Ctrl+C stops consuming; records in flight are applied and committed first
+    run_service(config, service::build).await
+}
@@ -0,0 +1,204 @@
+use axum::{extract::State, response::Json, routing::get, Router};
+use ticket_master::{
+    AlertCheck, AreaAdminCommand, AreaStatus, CircuitBreaker, ConsumerRunner, KafkaConsumer, KafkaMessage,
+    KafkaProducer, MessageConsumer, MessageHandler, MessageProducer, Metrics, RecordProcessor, ReconciliationReport,
+    Reservation, Result, RocksDBStore, SeatReconciler, ServiceBootstrap, ServiceConfig, StateChange,
+    TicketMasterError, Topics, WebhookNotifier, health_router, spawn_alert_monitor,
+};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::{interval, Interval};
+use tracing::{error, info, warn, Instrument};
+
+const RECONCILIATION_SERVICE: &str = "reconciliation-service";
+const TOPICS: [&str; 2] = [Topics::STATE_EVENT_AREA_STATUS, Topics::STATE_USER_RESERVATION];
+const DEFAULT_PORT: u16 = 8085;
+const SEAT_INVENTORY_ALERT: &str = "seat_inventory";
+
+/// Keeps the area statuses and reservations of the topics and reconciles them
+/// every `reconciliation.service.interval.ms`
+pub type ReconciliationService<C = KafkaConsumer, P = KafkaProducer> = ConsumerRunner<ReconciliationProcessor<P>, C>;
+
+/// Keeps the area statuses and reservations it reads; shared by all dispatcher lanes
+pub struct ReconciliationProcessor<P> {
+    reconciler: SeatReconciler,
+    producer: P,
+    metrics: Metrics,
+    reconcile_interval: Duration,
+    auto_repair: bool,
+    // Discrepancies of the last run, confirmed or not
+    previous: Mutex<ReconciliationReport>,
+    // Discrepancies of the last run that the run before found too
+    latest: Arc<Mutex<ReconciliationReport>>,
+}
+
+pub async fn build(config: ServiceConfig) -> Result<ReconciliationService> {
+    let bootstrap = ServiceBootstrap::new(&config).await?;
+    let metrics = bootstrap.metrics().clone();
+    // Repairs are admin commands and heartbeats, neither needing the topic serdes
+    let producer = bootstrap
+        .plain_producer()?
+        .with_circuit_breaker(Arc::new(CircuitBreaker::kafka_producer().with_metrics(metrics.clone())));
+
+    // Rebuilt from the topics if lost, as only the latest of each record matters
+    let store = RocksDBStore::new(format!("{}/reconciliation/{}", config.state_dir, config.application_id))?;
+    let reconciler = SeatReconciler::new(Arc::new(store));
+
+    let mut service = with_clients(&config, bootstrap.consumer()?, producer.clone(), reconciler, metrics)?;
+    let latest = service.processor().latest.clone();
+
+    let port = config.reconciliation_service.port.unwrap_or(DEFAULT_PORT);
+    let app = report_routes(latest.clone()).merge(health_router(bootstrap.readiness(&TOPICS)?));
+    bootstrap.serve(&mut service, "reconciliation-api", port, app).await?;
+
+    if let Some(notifier) = WebhookNotifier::from_config(&config)? {
+        spawn_alert_monitor(notifier, config.alerts.check_interval(), move || {
+            let check = seat_inventory_check(&latest.lock().unwrap_or_else(PoisonError::into_inner));
+            async move { vec![check] }
+        });
+        info!("Alerting on seat inventory discrepancies");
+    }
+
+    bootstrap.start_heartbeat(&mut service, producer, env!("CARGO_PKG_VERSION"));
+    Ok(service)
+}
+
+/// Build the service on the given clients, e.g. from a `MockKafka`; failed
+/// records and discrepancies are counted in `metrics`
+pub fn with_clients<C: MessageConsumer, P: MessageProducer>(
+    config: &ServiceConfig,
+    consumer: C,
+    producer: P,
+    reconciler: SeatReconciler,
+    metrics: Metrics,
+) -> Result<ReconciliationService<C, P>> {
+    // Records are applied in order per key and each writes a key of its own
+    let processor = ReconciliationProcessor {
+        reconciler,
+        producer,
+        metrics: metrics.clone(),
+        reconcile_interval: config.reconciliation_service.interval(),
+        auto_repair: config.reconciliation_service.auto_repair,
+        previous: Mutex::new(ReconciliationReport::default()),
+        latest: Arc::new(Mutex::new(ReconciliationReport::default())),
+    };
+    ConsumerRunner::new(config, consumer, &TOPICS, processor, metrics)
+}
+
+// Failed records are logged and skipped; the next record of the same key corrects the store
+#[async_trait::async_trait]
+impl<P: MessageProducer> RecordProcessor for ReconciliationProcessor<P> {
+    fn on_failure(&self, e: TicketMasterError) -> Result<()> {
+        error!("Error applying record: {}", e);
+        Ok(())
+    }
+
+    fn timer(&self) -> Option<Interval> {
+        Some(interval(self.reconcile_interval))
+    }
+
+    async fn tick(&self) {
+        if let Err(e) = self.reconcile().await {
+            error!("Error reconciling seat inventory: {}", e);
+        }
+    }
+}
+
+impl<P: MessageProducer> ReconciliationProcessor<P> {
+    // Only discrepancies found twice in a row are reported or repaired
+    async fn reconcile(&self) -> Result<()> {
+        let reconciler = self.reconciler.clone();
+        let report = tokio::task::spawn_blocking(move || reconciler.reconcile())
+            .await
+            .map_err(|e| TicketMasterError::Io(std::io::Error::other(e)))??;
+        let confirmed = {
+            let mut previous = self.previous.lock().unwrap_or_else(PoisonError::into_inner);
+            let confirmed = report.confirmed_by(&previous);
+            *previous = report;
+            confirmed
+        };
+
+        self.metrics.record_seat_discrepancies(&confirmed.counts());
+        if !confirmed.discrepancies.is_empty() {
+            warn!(
+                "{} seat inventory discrepancies in {} areas checked: {:?}",
+                confirmed.discrepancies.len(),
+                confirmed.areas_checked,
+                confirmed.counts()
+            );
+        }
+        if self.auto_repair {
+            for (event_id, area_id) in confirmed.miscounted_areas() {
+                self.reemit_area(event_id, area_id).await?;
+                self.metrics.seat_counter_repairs.inc();
+            }
+        }
+        *self.latest.lock().unwrap_or_else(PoisonError::into_inner) = confirmed;
+        Ok(())
+    }
+
+    // Event-service publishes the area again, its counter taken from the seats it holds
+    async fn reemit_area(&self, event_id: String, area_id: String) -> Result<()> {
+        info!("Having area {}#{} re-emitted to repair its counter", event_id, area_id);
+        let command = AreaAdminCommand::ReemitAreaStatus { event_id, area_id };
+        // Admin commands are always JSON
+        let payload = serde_json::to_vec(&command)?;
+        self.producer
+            .send_bytes_with_headers(Topics::COMMAND_EVENT_ADMIN, None, &command.key(), Some(&payload), &[])
+            .await
+    }
+}
+
+/// `GET /reconciliation`: the discrepancies of the last run confirmed by the run before
+fn report_routes(latest: Arc<Mutex<ReconciliationReport>>) -> Router {
+    Router::new()
+        .route(
+            "/reconciliation",
+            get(|State(latest): State<Arc<Mutex<ReconciliationReport>>>| async move {
+                Json(latest.lock().unwrap_or_else(PoisonError::into_inner).clone())
+            }),
+        )
+        .with_state(latest)
+}
+
+fn seat_inventory_check(report: &ReconciliationReport) -> AlertCheck {
+    if report.discrepancies.is_empty() {
+        return AlertCheck::ok(SEAT_INVENTORY_ALERT);
+    }
+    let problems: Vec<String> = report
+        .counts()
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(kind, count)| format!("{} {}", count, kind))
+        .collect();
+    AlertCheck::firing(SEAT_INVENTORY_ALERT, format!("Seat inventory discrepancies: {}", problems.join(", ")))
+}
+
+#[async_trait::async_trait]
+impl<P: MessageProducer> MessageHandler for ReconciliationProcessor<P> {
+    async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+        async {
+            match message.topic.as_str() {
+                Topics::STATE_EVENT_AREA_STATUS => match message.state_change::<AreaStatus>().await? {
+                    StateChange::Upsert(area_status) => self.reconciler.apply_area_status(&area_status),
+                    StateChange::Delete(event_area_id) => self.reconciler.delete_area(event_area_id),
+                },
+                Topics::STATE_USER_RESERVATION => match message.state_change::<Reservation>().await? {
+                    StateChange::Upsert(reservation) => self.reconciler.apply_reservation(&reservation),
+                    StateChange::Delete(reservation_id) => self.reconciler.delete_reservation(reservation_id),
+                },
+                _ => {
+                    warn!("Unknown topic: {}", message.topic);
+                    Ok(())
+                }
+            }
+        }
+        .instrument(message.span())
+        .await
+        .map_err(|e: TicketMasterError| {
+            let e = e.with_context(message.error_context(RECONCILIATION_SERVICE));
+            self.metrics.record_error(&e);
+            e
+        })
+    }
+}
@@ -1,78 +1,81 @@
 use clap::Parser;
-use std::path::PathBuf;
-use ticket_master::{Result, ServiceConfig};
+use ticket_master::{init_logging, redacted_config, shutdown_telemetry, CommonArgs, ConfigWatcher, Result, SecretResolver, ServiceConfig,
+    ShutdownCoordinator, Supervisor};
 use tracing::{info, error};
 
-mod service;
-
-use service::ReservationService;
+use reservation_service::ReservationService;
 
 #[derive(Parser, Debug)]
 #[command(name = "reservation-service")]
 #[command(about = "Reservation Service for Ticket Master")]
 struct Args {
-    /// State directory for storage
-    #[arg(short = 'd', long = "state-dir", default_value = "/tmp/kafka-streams")]
-    state_dir: PathBuf,
-
-    /// Config file path
-    #[arg(short = 'c', long = "config")]
-    config: PathBuf,
-
-    /// Stream config file path
-    #[arg(long = "stream-config")]
-    stream_config: Option<PathBuf>,
-
-    /// Show help information
-    #[arg(short = 'h', long = "help")]
-    help: bool,
+    #[command(flatten)]
+    common: CommonArgs,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
-
     let args = Args::parse();
 
-    if args.help {
-        println!("Reservation Service for Ticket Master");
-        return Ok(());
-    }
+    // Initialize tracing
+    let log_filter = init_logging("reservation-service", args.common.log_format)?;
 
     info!("Starting Reservation Service");
-    info!("State directory: {:?}", args.state_dir);
-    info!("Config file: {:?}", args.config);
 
     // Load configuration
-    let mut config = load_config(&args.config)?;
+    let loader = args.common.config_loader("reservation-service");
+    let mut config = with_defaults(args.common.load_config(loader.clone())?);
     config.application_id = "reservation-service".to_string();
-    config.state_dir = args.state_dir.to_string_lossy().to_string();
-
-    if let Some(stream_config_path) = args.stream_config {
-        info!("Loading stream config from: {:?}", stream_config_path);
-        config = ticket_master::merge_stream_properties(config, stream_config_path)?;
+    if args.common.print_config {
+        println!("{}", redacted_config(&config)?);
+        return Ok(());
     }
+    info!("State directory: {}", config.state_dir);
+
+    SecretResolver::from_env().resolve_config(&mut config).await?;
+    log_filter.set(config.log_filter.as_deref())?;
+
+    // Runtime-tunable settings follow the config file
+    let config_watcher = ConfigWatcher::start(loader, &config)?;
+    config_watcher.watch_log_filter(log_filter);
+
+    // SIGTERM or Ctrl+C stops consuming; in-flight messages finish and are committed first
+    let coordinator = ShutdownCoordinator::default();
+    let shutdown = coordinator.subscribe();
+    let restarts_stopped = coordinator.subscribe();
+    coordinator.signal_on_termination();
 
     // Create and start the reservation service
-    let service = ReservationService::new(config).await?;
+    let restart_policy = config.supervisor.policy();
+    let mut service = ReservationService::new(config).await?;
+    service.watch_settings(&config_watcher);
+    service.register_shutdown(&coordinator).await;
     
     info!("Reservation Service started successfully");
     
-    // Run the service
-    service.run().await?;
+    // Run the service, restarting it from the committed offsets if it crashes
+    let supervisor = Supervisor::new("reservation-service", restart_policy).with_metrics(service.metrics().clone());
+    let service = &service;
+    let mut first_run = Some(shutdown);
+    supervisor
+        .run(restarts_stopped, |restarts| {
+            let shutdown = first_run.take().unwrap_or_else(|| coordinator.subscribe());
+            async move {
+                if restarts > 0 {
+                    service.rejoin()?;
+                }
+                service.run(shutdown).await
+            }
+        })
+        .await?;
+    coordinator.shutdown().await?;
+    info!("Reservation Service shut down");
+    shutdown_telemetry();
 
     Ok(())
 }
 
-fn load_config(config_path: &PathBuf) -> Result<ServiceConfig> {
-    use ticket_master::parse_properties_file;
-    
-    // Parse the Java properties file
-    let mut config = parse_properties_file(config_path, "reservation-service")?;
-    
+fn with_defaults(mut config: ServiceConfig) -> ServiceConfig {
     // Set service-specific defaults
     if config.commit_interval_ms.is_none() {
         config.commit_interval_ms = Some(20);
@@ -81,5 +84,5 @@ fn load_config(config_path: &PathBuf) -> Result<ServiceConfig> {
         config.processing_guarantee = Some("exactly_once_v2".to_string());
     }
     
-    Ok(config)
+    config
 }
\ No newline at end of file
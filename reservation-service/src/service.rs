@@ -2,86 +2,320 @@ use ticket_master::{
     Result, TicketMasterError, ServiceConfig, KafkaConsumer, KafkaProducer,
     CreateReservation, Reservation, ReservationResult, ReservationState, 
     ReserveSeat, AreaStatus, Topics, Stores, event_area_key,
-    StateStore, ProcessingContext, RocksDBStore
+    StateStoreBackend, ProcessingContext, RocksDBStore, KafkaMessage, ConsumerRunner, RecordProcessor,
+    Metrics, serve_metrics_on, spawn_delivery_monitor, MessageConsumer, MessageProducer, DedupStore,
+    MessageHandler, ReservationTransition,
+    spawn_dedup_purge, IndexedStore, AssignedPartitions, StateQueryHandler, QueryHost,
+    serve_state_queries, spawn_query_host_announcer, REQUEST_ID_HEADER, PRIORITY_HEADER, DeadlineIndex,
+    ReservationResultEnum, ReservationErrorCode, AreaAdminCommand, AuditRecord, send_audit_record, ConfigWatcher,
+    ReservationEvent, Seat, WebhookNotifier, ReservationFailureRate, spawn_alert_monitor, CircuitBreaker,
+    RetryPolicies, retry_transient, ShutdownCoordinator, KafkaProducerShutdown,
+    StateStoresShutdown, HttpServerShutdown, HeartbeatPublisher, PaymentResult, PaymentStatus, AuditEntity,
+    ReservationAdminCommand, ServiceBootstrap
 };
-use std::time::Duration;
-use tracing::{info, error, warn};
-use tokio::signal;
-
-pub struct ReservationService {
-    consumer: KafkaConsumer,
-    producer: KafkaProducer,
-    context: ProcessingContext,
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, error, warn, Instrument, Span};
+use tokio::sync::broadcast;
+use tokio::time::{interval, Interval};
+
+const AREA_STATUS_CACHE_CAPACITY: usize = 10_000;
+const AREA_STATUS_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const TIMEOUT_BATCH_SIZE: usize = 500;
+// Locks serializing updates of one reservation; reservations share them by hash
+const RESERVATION_LOCKS: usize = 64;
+// Actors audit records name for changes made from results and timeouts
+const EVENT_SERVICE: &str = "event-service";
+const RESERVATION_SERVICE: &str = "reservation-service";
+const PAYMENT_SERVICE: &str = "payment-service";
+const ADMIN: &str = "admin";
+// Names of the consumers, as used in `consumers.<name>.` properties
+const COMMANDS: &str = "commands";
+const RESULTS: &str = "results";
+const AREA_STATUS: &str = "area-status";
+const PAYMENTS: &str = "payments";
+// Admin commands are keyed by reservation id too, so they are applied on the reservation's lane
+const COMMAND_TOPICS: [&str; 2] = [Topics::COMMAND_RESERVATION_CREATE_RESERVATION, Topics::COMMAND_RESERVATION_ADMIN];
+
+/// The service's consumers, one per input topic, so a backlog of results,
+/// area status updates or payments doesn't hold up new reservations
+pub struct ReservationConsumers<C> {
+    pub commands: C,
+    pub results: C,
+    pub area_statuses: C,
+    pub payments: C,
+}
+
+/// Runs each of the service's consumers on a `ConsumerRunner` of its own,
+/// all sharing one processor
+pub struct ReservationService<C = KafkaConsumer, P = KafkaProducer> {
+    runners: ReservationConsumers<ConsumerRunner<ReservationProcessor<P>, C>>,
+}
+
+/// Handles reservation-service messages; shared by every consumer's lanes
+struct ReservationProcessor<P> {
+    producer: P,
+    context: Arc<ProcessingContext>,
+    reservations: IndexedStore<Reservation>,
+    area_status_cache: StateStoreBackend<String, AreaStatus>,
+    deadlines: DeadlineIndex,
+    holds: DeadlineIndex,
+    hold_ttl: Option<Duration>,
+    // Milliseconds, changed by `watch_settings`
+    reservation_timeout_ms: AtomicU64,
+    dedup: Arc<DedupStore>,
+    // Results and timeouts may update a reservation at the same time
+    locks: Vec<Mutex<()>>,
+    // Messages failing on a transient error are processed again
+    retry: RetryPolicies,
+    metrics: Metrics,
 }
 
 impl ReservationService {
     pub async fn new(config: ServiceConfig) -> Result<Self> {
         let kafka_config = config.to_kafka_config();
+        let bootstrap = ServiceBootstrap::new(&config).await?;
+        let metrics = bootstrap.metrics().clone();
+        let serdes = bootstrap.serdes().clone();
         
-        let consumer = KafkaConsumer::new(kafka_config.clone())?;
-        let producer = KafkaProducer::new(kafka_config)?;
-        
-        // Subscribe to topics
-        consumer.subscribe(&[
-            Topics::COMMAND_RESERVATION_CREATE_RESERVATION,
-            Topics::RESPONSE_RESERVATION_RESULT,
-            Topics::STATE_EVENT_AREA_STATUS,
-        ])?;
-
-        // Initialize state stores with RocksDB
-        let context = ProcessingContext::with_state_dir(config.state_dir.clone());
-        
-        // Reservation store
-        context.add_rocksdb_store(Stores::RESERVATION.to_string(), "reservations")?;
-        
-        // Area status cache
-        context.add_rocksdb_store(Stores::EVENT_AREA_STATUS_CACHE.to_string(), "area-status-cache")?;
+        // Reservations are keyed, and so owned, by the partition of their create command
+        let assigned = Arc::new(AssignedPartitions::new(Topics::COMMAND_RESERVATION_CREATE_RESERVATION));
+        let consumer = |name: &str| -> Result<KafkaConsumer> {
+            Ok(KafkaConsumer::new(config.consumer_kafka_config(name))?
+                .with_serdes(serdes.clone())
+                .with_topic_resolver(config.topic_resolver())
+                .with_metrics(metrics.clone()))
+        };
+        let consumers = ReservationConsumers {
+            commands: KafkaConsumer::with_rebalance_listener(config.consumer_kafka_config(COMMANDS), assigned.clone())?
+                .with_serdes(serdes.clone())
+                .with_topic_resolver(config.topic_resolver())
+                .with_metrics(metrics.clone()),
+            results: consumer(RESULTS)?,
+            area_statuses: consumer(AREA_STATUS)?,
+            payments: consumer(PAYMENTS)?,
+        };
+        let (producer, delivery_reports) = KafkaProducer::with_serdes(kafka_config, serdes)?
+            .with_topic_resolver(config.topic_resolver())
+            .with_metrics(metrics.clone())
+            .with_circuit_breaker(Arc::new(CircuitBreaker::kafka_producer().with_metrics(metrics.clone())))
+            .with_delivery_reports();
+        spawn_delivery_monitor(delivery_reports);
 
-        Ok(Self {
-            consumer,
+        if let (Some(notifier), Some(threshold)) = (WebhookNotifier::from_config(&config)?, config.alerts.reservation_failure_rate) {
+            let mut failure_rate = ReservationFailureRate::new(metrics.clone(), threshold, config.alerts.reservation_min_results());
+            spawn_alert_monitor(notifier, config.alerts.check_interval(), move || {
+                let check = failure_rate.check();
+                async move { check.into_iter().collect() }
+            });
+            info!("Alerting on reservation failure rates over {}", threshold);
+        }
+
+        let mut service = Self::with_clients(&config, consumers, producer.clone(), metrics.clone())?;
+        let context = service.runners.commands.processor().context.clone();
+        let commands = &mut service.runners.commands;
+        if let Some(port) = config.metrics_port {
+            let server = serve_metrics_on(port, metrics).await?;
+            commands.add_shutdown_component(HttpServerShutdown::new("metrics-server").with_task(server));
+        }
+        if let Some(server) = &config.application_server {
+            let partitions = commands.consumer().partition_count(Topics::COMMAND_RESERVATION_CREATE_RESERVATION)?.max(1);
+            let queries = ReservationQueries {
+                store: commands.processor().reservations.store().clone(),
+            };
+            let query_server = serve_state_queries(server, Arc::new(queries)).await?;
+            commands.add_shutdown_component(HttpServerShutdown::new("state-query-server").with_task(query_server));
+            let host = QueryHost::new(&config.application_id, server, partitions);
+            spawn_query_host_announcer(producer.clone(), host, assigned.subscribe());
+        }
+        let runners = &service.runners;
+        let heartbeat = HeartbeatPublisher::from_config(producer.clone(), &config, env!("CARGO_PKG_VERSION"))
+            .with_consumer(runners.commands.consumer().clone(), runners.commands.topics())
+            .with_consumer(runners.results.consumer().clone(), runners.results.topics())
+            .with_consumer(runners.area_statuses.consumer().clone(), runners.area_statuses.topics())
+            .with_consumer(runners.payments.consumer().clone(), runners.payments.topics());
+        heartbeat.start();
+        let commands = &mut service.runners.commands;
+        commands.add_shutdown_component(heartbeat);
+        commands.add_shutdown_component(KafkaProducerShutdown::new(producer));
+        commands.add_shutdown_component(StateStoresShutdown::new(context));
+        Ok(service)
+    }
+
+    /// Have `coordinator` leave the consumer groups, stop the HTTP servers
+    /// and heartbeats, flush the producer and persist the stores once `run`
+    /// has returned
+    pub async fn register_shutdown(&mut self, coordinator: &ShutdownCoordinator) {
+        self.runners.commands.register_shutdown(coordinator).await;
+        self.runners.results.register_shutdown(coordinator).await;
+        self.runners.area_statuses.register_shutdown(coordinator).await;
+        self.runners.payments.register_shutdown(coordinator).await;
+    }
+}
+
+impl<C: MessageConsumer, P: MessageProducer> ReservationService<C, P> {
+    /// Build the service on the given clients, e.g. from a `MockKafka`;
+    /// decided reservations and message retries are counted in `metrics`
+    pub fn with_clients(
+        config: &ServiceConfig,
+        consumers: ReservationConsumers<C>,
+        producer: P,
+        metrics: Metrics,
+    ) -> Result<Self> {
+        // Reservations in RocksDB; the area status cache is bounded so it
+        // doesn't grow with every event ever seen
+        let context = ProcessingContext::builder(config.state_dir.clone())
+            .soft_delete_retention(config.soft_delete_retention())
+            .rocksdb_store(Stores::RESERVATION_STORE, "reservations")
+            .rocksdb_store(Stores::RESERVATION_DEADLINE_STORE, "reservation-deadlines")
+            .rocksdb_store(Stores::RESERVATION_HOLD_STORE, "reservation-holds")
+            .cache_store(Stores::EVENT_AREA_STATUS_CACHE_STORE, AREA_STATUS_CACHE_CAPACITY, AREA_STATUS_CACHE_TTL)
+            .build()?;
+        // Indexed by user so a user's reservations can be listed
+        let reservations = IndexedStore::new(context.rocksdb_store(&Stores::RESERVATION_STORE)?)
+            .with_index(Stores::RESERVATIONS_BY_USER, |reservation: &Reservation| vec![reservation.user_id.clone()]);
+        let area_status_cache = context.store(&Stores::EVENT_AREA_STATUS_CACHE_STORE)?;
+        // When to stop waiting for event-service to answer a reservation
+        let deadlines = DeadlineIndex::new(context.rocksdb_store(&Stores::RESERVATION_DEADLINE_STORE)?);
+        // When to give up on payment for a reserved reservation
+        let holds = DeadlineIndex::new(context.rocksdb_store(&Stores::RESERVATION_HOLD_STORE)?);
+
+        let dedup = Arc::new(DedupStore::open(
+            format!("{}/dedup/{}", config.state_dir, config.application_id),
+            config.dedup_ttl(),
+        )?);
+        spawn_dedup_purge(dedup.clone());
+
+        let processor = Arc::new(ReservationProcessor {
             producer,
-            context,
+            context: Arc::new(context),
+            reservations,
+            area_status_cache,
+            deadlines,
+            holds,
+            hold_ttl: config.reservation_service.hold_ttl(),
+            reservation_timeout_ms: AtomicU64::new(config.reservation_timeout().as_millis() as u64),
+            dedup,
+            locks: (0..RESERVATION_LOCKS).map(|_| Mutex::new(())).collect(),
+            retry: config.retry.policies(),
+            metrics: metrics.clone(),
+        });
+
+        // Messages are keyed by reservation or event#area, so each stays in order
+        let runner = |name: &str, consumer: C, topics: &'static [&'static str]| {
+            ConsumerRunner::for_consumer(config, name, consumer, topics, processor.clone(), metrics.clone())
+        };
+        Ok(Self {
+            runners: ReservationConsumers {
+                commands: runner(COMMANDS, consumers.commands, &COMMAND_TOPICS)?.without_timer(),
+                // Pending results time out on the consumer of the results they stand in for
+                results: runner(RESULTS, consumers.results, &[Topics::RESPONSE_RESERVATION_RESULT])?,
+                area_statuses: runner(AREA_STATUS, consumers.area_statuses, &[Topics::STATE_EVENT_AREA_STATUS])?.without_timer(),
+                payments: runner(PAYMENTS, consumers.payments, &[Topics::RESPONSE_PAYMENT_RESULT])?.without_timer(),
+            },
+        })
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        self.runners.commands.metrics()
+    }
+
+    /// Leave and join the consumer groups again, so processing resumes from
+    /// the committed offsets, e.g. before `run` is restarted after a crash
+    pub fn rejoin(&self) -> Result<()> {
+        self.runners.commands.rejoin()?;
+        self.runners.results.rejoin()?;
+        self.runners.area_statuses.rejoin()?;
+        self.runners.payments.rejoin()
+    }
+
+    /// Apply the reservation timeout of the watched config file as it changes;
+    /// reservations already waiting keep their deadline
+    pub fn watch_settings(&self, watcher: &ConfigWatcher) -> tokio::task::JoinHandle<()> {
+        let processor = self.runners.commands.processor().clone();
+        watcher.on_change(move |settings| {
+            processor.reservation_timeout_ms.store(settings.reservation_timeout_ms, Ordering::Relaxed);
         })
     }
 
-    pub async fn run(&self) -> Result<()> {
+    /// Process messages until `stop` is signalled, then finish the messages
+    /// in flight and commit their offsets
+    ///
+    /// Each consumer tracks and commits its own offsets and pauses on its own backlog.
+    pub async fn run(&self, mut stop: broadcast::Receiver<()>) -> Result<()> {
         info!("Reservation Service is running...");
 
-        loop {
-            tokio::select! {
-                // Handle shutdown signal
-                _ = signal::ctrl_c() => {
-                    info!("Received shutdown signal");
-                    break;
-                }
-                
-                // Process messages
-                message_result = self.consumer.recv_message(Duration::from_millis(100)) => {
-                    match message_result? {
-                        Some(message) => {
-                            if let Err(e) = self.process_message(&message).await {
-                                error!("Error processing message: {}", e);
-                            } else {
-                                // Commit the message after successful processing
-                                if let Err(e) = self.consumer.commit_message(&message) {
-                                    error!("Error committing message: {}", e);
-                                }
-                            }
-                        }
-                        None => {
-                            // No message received (timeout)
-                            continue;
-                        }
-                    }
-                }
-            }
-        }
+        let (shutdown, _) = broadcast::channel(1);
+        let signal = async {
+            let _ = stop.recv().await;
+            let _ = shutdown.send(());
+            Ok(())
+        };
+        tokio::try_join!(
+            signal,
+            self.runners.commands.run(shutdown.subscribe()),
+            self.runners.results.run(shutdown.subscribe()),
+            self.runners.area_statuses.run(shutdown.subscribe()),
+            self.runners.payments.run(shutdown.subscribe()),
+        )?;
 
         info!("Reservation Service shutting down...");
         Ok(())
     }
+}
+
+// Failed messages are logged and skipped so they do not hold back the
+// partition's commits
+#[async_trait::async_trait]
+impl<P: MessageProducer> RecordProcessor for ReservationProcessor<P> {
+    fn timer(&self) -> Option<Interval> {
+        Some(interval(TIMEOUT_CHECK_INTERVAL))
+    }
+
+    // Reservations event-service didn't answer in time, and holds left unpaid
+    async fn tick(&self) {
+        if let Err(e) = self.fail_timed_out_reservations().await {
+            error!("Error failing timed out reservations: {}", e);
+        }
+        if let Err(e) = self.cancel_expired_holds().await {
+            error!("Error cancelling expired holds: {}", e);
+        }
+    }
 
-    async fn process_message(&self, message: &ticket_master::KafkaMessage) -> Result<()> {
+    fn write_stalled(&self) -> Result<bool> {
+        self.context.write_stalled()
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: MessageProducer> MessageHandler for ReservationProcessor<P> {
+    // Messages processed before a crash are redelivered; skip those
+    async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+        if self.dedup.is_duplicate(message)? {
+            info!("Skipping duplicate message {}:{}@{}", message.topic, message.partition, message.offset);
+            return Ok(());
+        }
+        retry_transient(&self.retry, "process message", Some(&self.metrics), || self.process_message(message))
+            .instrument(message.span())
+            .await
+            .map_err(|e| {
+                let e = e.with_context(message.error_context(RESERVATION_SERVICE));
+                self.metrics.record_error(&e);
+                e
+            })?;
+        self.dedup.mark_processed(message)
+    }
+}
+
+impl<P: MessageProducer> ReservationProcessor<P> {
+    fn reservation_timeout(&self) -> Duration {
+        Duration::from_millis(self.reservation_timeout_ms.load(Ordering::Relaxed))
+    }
+
+    async fn process_message(&self, message: &KafkaMessage) -> Result<()> {
         match message.topic.as_str() {
             Topics::COMMAND_RESERVATION_CREATE_RESERVATION => {
                 self.handle_create_reservation(message).await
@@ -92,6 +326,12 @@ impl ReservationService {
             Topics::STATE_EVENT_AREA_STATUS => {
                 self.handle_area_status_update(message).await
             }
+            Topics::RESPONSE_PAYMENT_RESULT => {
+                self.handle_payment_result(message).await
+            }
+            Topics::COMMAND_RESERVATION_ADMIN => {
+                self.handle_admin_command(message).await
+            }
             _ => {
                 warn!("Unknown topic: {}", message.topic);
                 Ok(())
@@ -99,27 +339,73 @@ impl ReservationService {
         }
     }
 
-    async fn handle_create_reservation(&self, message: &ticket_master::KafkaMessage) -> Result<()> {
+    // Apply a change to the stored reservation, storing it if it is allowed;
+    // None if there is no such reservation
+    fn update<F>(&self, reservation_id: &str, change: F) -> Result<Option<(Reservation, Result<ReservationTransition>)>>
+    where
+        F: FnOnce(&mut Reservation) -> Result<ReservationTransition>,
+    {
+        let _guard = self.lock(reservation_id);
+        let Some(mut reservation) = self.reservations.get(reservation_id)? else {
+            return Ok(None);
+        };
+        let transition = change(&mut reservation);
+        if transition.is_ok() {
+            self.store_reservation(reservation_id, &reservation)?;
+            match reservation.state {
+                ReservationState::Reserved => self.metrics.record_reservation_attempt(true, reservation.seats.len() as i32),
+                ReservationState::Failed => self.metrics.record_reservation_attempt(false, 0),
+                _ => {}
+            }
+        }
+        Ok(Some((reservation, transition)))
+    }
+
+    fn lock(&self, reservation_id: &str) -> MutexGuard<'_, ()> {
+        let mut hasher = DefaultHasher::new();
+        reservation_id.hash(&mut hasher);
+        self.locks[hasher.finish() as usize % self.locks.len()].lock().unwrap()
+    }
+
+    // Cancelled reservations stay readable for the soft-delete retention
+    fn store_reservation(&self, reservation_id: &str, reservation: &Reservation) -> Result<()> {
+        if reservation.state == ReservationState::Cancelled {
+            self.reservations.put_soft_deleted(reservation_id, reservation)
+        } else {
+            self.reservations.put(reservation_id, reservation)
+        }
+    }
+
+    async fn handle_create_reservation(&self, message: &KafkaMessage) -> Result<()> {
         let reservation_id = message.key.as_ref()
             .ok_or_else(|| TicketMasterError::InvalidArgument("Missing reservation ID key".to_string()))?;
+        Span::current().record("reservation_id", reservation_id.as_str());
         
-        let create_request: CreateReservation = message.deserialize_value()?;
+        let create_request: CreateReservation = message.deserialize_value().await?;
         
         info!("Creating reservation: {}", reservation_id);
 
-        let reservation_store: StateStore<String, Reservation> = self.context
-            .get_store(Stores::RESERVATION)
-            .ok_or_else(|| TicketMasterError::InvalidArgument("Reservation store not found".to_string()))?;
+        // Reservations don't keep seat preferences or priority, only the seat request does
+        let preferences = create_request.preferences.clone();
+        let priority = create_request.priority;
 
         // Create new reservation
         let reservation = Reservation::new(create_request);
         
         // Store the reservation
-        reservation_store.put(reservation_id.clone(), reservation.clone());
+        self.store_reservation(reservation_id, &reservation)?;
+        send_audit_record(
+            &self.producer,
+            &AuditRecord::reservation_created(reservation_id, &reservation.user_id, &reservation.state),
+        ).await?;
 
         // Check reservation state and process accordingly
         match reservation.state {
             ReservationState::Processing => {
+                // Scheduled before sending, so a crash in between still times out
+                let due_ms = now_ms() + self.reservation_timeout().as_millis() as i64;
+                self.deadlines.schedule(reservation_id, due_ms)?;
+
                 // Send reserve seat command
                 let reserve_seat = ReserveSeat {
                     reservation_id: reservation.reservation_id.clone(),
@@ -129,14 +415,23 @@ impl ReservationService {
                     num_of_seat: reservation.num_of_seat,
                     reservation_type: reservation.reservation_type.clone(),
                     seats: reservation.seats.clone(),
+                    preferences,
                 };
 
                 let event_area_key = event_area_key(&reservation.event_id, &reservation.area_id);
+                // Carry the request id and the lane on to event-service
+                let mut headers: Vec<(&str, &[u8])> = message
+                    .request_id()
+                    .map(|request_id| (REQUEST_ID_HEADER, request_id.as_bytes()))
+                    .into_iter()
+                    .collect();
+                headers.push((PRIORITY_HEADER, priority.as_str().as_bytes()));
                 
-                self.producer.send(
+                self.producer.send_with_headers(
                     Topics::COMMAND_EVENT_RESERVE_SEAT,
                     &event_area_key,
                     &reserve_seat,
+                    &headers,
                 ).await?;
 
                 info!("Sent reserve seat command for reservation: {}", reservation_id);
@@ -159,25 +454,34 @@ impl ReservationService {
         Ok(())
     }
 
-    async fn handle_reservation_result(&self, message: &ticket_master::KafkaMessage) -> Result<()> {
+    async fn handle_reservation_result(&self, message: &KafkaMessage) -> Result<()> {
         let reservation_id = message.key.as_ref()
             .ok_or_else(|| TicketMasterError::InvalidArgument("Missing reservation ID key".to_string()))?;
+        Span::current().record("reservation_id", reservation_id.as_str());
         
-        let result: ReservationResult = message.deserialize_value()?;
+        let result: ReservationResult = message.deserialize_value().await?;
         
         info!("Processing reservation result: {} -> {:?}", reservation_id, result.result);
 
-        let reservation_store: StateStore<String, Reservation> = self.context
-            .get_store(Stores::RESERVATION)
-            .ok_or_else(|| TicketMasterError::InvalidArgument("Reservation store not found".to_string()))?;
-
-        // Get existing reservation
-        if let Some(mut reservation) = reservation_store.get(reservation_id) {
-            // Update reservation with result
-            reservation.update_from_result(&result);
-            
-            // Store updated reservation
-            reservation_store.put(reservation_id.clone(), reservation.clone());
+        // Update reservation with result
+        if let Some((reservation, transition)) = self.update(reservation_id, |reservation| reservation.update_from_result(&result))? {
+            let transition = match transition {
+                Ok(transition) => transition,
+                // The reservation already timed out; give back seats reserved too late
+                Err(_) if reservation.state == ReservationState::Failed && result.result == ReservationResultEnum::Success => {
+                    warn!("Result for timed out reservation {}, releasing its seats", reservation_id);
+                    return self.release_seats(&reservation, &result.seats).await;
+                }
+                // A repeated result finds it already moved on
+                Err(e) => {
+                    warn!("Ignoring reservation result: {}", e);
+                    return Ok(());
+                }
+            };
+            send_audit_record(&self.producer, &AuditRecord::from_transition(&transition, EVENT_SERVICE)).await?;
+            if let (Some(hold_ttl), ReservationState::Reserved) = (self.hold_ttl, &reservation.state) {
+                self.holds.schedule(reservation_id, now_ms() + hold_ttl.as_millis() as i64)?;
+            }
 
             // Send to user reservation state topic
             self.producer.send(
@@ -194,22 +498,193 @@ impl ReservationService {
         Ok(())
     }
 
-    async fn handle_area_status_update(&self, message: &ticket_master::KafkaMessage) -> Result<()> {
+    // Mark reservations paid once charged; declined and failed charges leave
+    // them reserved, so the user can pay again until the hold expires
+    async fn handle_payment_result(&self, message: &KafkaMessage) -> Result<()> {
+        let reservation_id = message.key.as_ref()
+            .ok_or_else(|| TicketMasterError::InvalidArgument("Missing reservation ID key".to_string()))?;
+        Span::current().record("reservation_id", reservation_id.as_str());
+
+        let payment: PaymentResult = message.deserialize_value().await?;
+        if payment.status != PaymentStatus::Succeeded {
+            info!(
+                "Payment {} of reservation {} {}: {}",
+                payment.idempotency_key,
+                reservation_id,
+                payment.status.as_str(),
+                payment.message.as_deref().unwrap_or_default()
+            );
+            return Ok(());
+        }
+
+        match self.update(reservation_id, |reservation| reservation.transition(ReservationEvent::PaymentReceived))? {
+            Some((reservation, Ok(transition))) => {
+                send_audit_record(&self.producer, &AuditRecord::from_transition(&transition, PAYMENT_SERVICE)).await?;
+                self.producer.send(
+                    Topics::STATE_USER_RESERVATION,
+                    reservation_id,
+                    &reservation,
+                ).await?;
+                info!("Reservation paid: {}", reservation_id);
+            }
+            // Paid already, e.g. a redelivered result
+            Some((reservation, Err(_))) if reservation.state == ReservationState::Paid => {}
+            // The hold expired before the charge went through; the seats are gone
+            Some((reservation, Err(_))) => warn!(
+                "Reservation {} charged under {} while {:?}; the charge needs refunding",
+                reservation_id,
+                payment.reference.as_deref().unwrap_or(&payment.idempotency_key),
+                reservation.state
+            ),
+            None => warn!("Reservation not found for payment: {}", reservation_id),
+        }
+        Ok(())
+    }
+
+    // Fail reservations event-service hasn't answered in time and tell their users
+    async fn fail_timed_out_reservations(&self) -> Result<()> {
+        let now_ms = now_ms();
+        for deadline in self.deadlines.due(now_ms, TIMEOUT_BATCH_SIZE)? {
+            let timed_out = ReservationResult {
+                reservation_id: deadline.id.clone(),
+                result: ReservationResultEnum::Failed,
+                error_code: Some(ReservationErrorCode::Timeout),
+                error_message: Some(format!(
+                    "No reservation result within {}ms",
+                    self.reservation_timeout().as_millis()
+                )),
+                seats: vec![],
+            };
+            // Only reservations still processing can fail; the others already have their result
+            if let Some((reservation, Ok(transition))) = self.update(&deadline.id, |reservation| reservation.update_from_result(&timed_out))? {
+                send_audit_record(&self.producer, &AuditRecord::from_transition(&transition, RESERVATION_SERVICE)).await?;
+                self.producer.send(
+                    Topics::STATE_USER_RESERVATION,
+                    &deadline.id,
+                    &reservation,
+                ).await?;
+                warn!("Reservation timed out: {}", deadline.id);
+            }
+            self.deadlines.remove(&deadline)?;
+        }
+        Ok(())
+    }
+
+    // Cancel reserved reservations not paid within the hold TTL and give back their seats
+    async fn cancel_expired_holds(&self) -> Result<()> {
+        for deadline in self.holds.due(now_ms(), TIMEOUT_BATCH_SIZE)? {
+            // Paid reservations can't be cancelled and keep their seats
+            if let Some((reservation, Ok(transition))) = self.update(&deadline.id, |reservation| reservation.transition(ReservationEvent::Cancelled))? {
+                self.release_seats(&reservation, &reservation.seats).await?;
+                send_audit_record(&self.producer, &AuditRecord::from_transition(&transition, RESERVATION_SERVICE)).await?;
+                self.producer.send(
+                    Topics::STATE_USER_RESERVATION,
+                    &deadline.id,
+                    &reservation,
+                ).await?;
+                warn!("Reservation hold expired: {}", deadline.id);
+            }
+            self.holds.remove(&deadline)?;
+        }
+        Ok(())
+    }
+
+    async fn release_seats(&self, reservation: &Reservation, seats: &[Seat]) -> Result<()> {
+        for seat in seats {
+            let command = AreaAdminCommand::ReleaseSeat {
+                event_id: reservation.event_id.clone(),
+                area_id: reservation.area_id.clone(),
+                row: seat.row,
+                col: seat.col,
+            };
+            // Admin commands are always JSON
+            let payload = serde_json::to_vec(&command)?;
+            self.producer.send_bytes_with_headers(
+                Topics::COMMAND_EVENT_ADMIN,
+                None,
+                &command.key(),
+                Some(&payload),
+                &[],
+            ).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_admin_command(&self, message: &KafkaMessage) -> Result<()> {
+        // Always JSON, whatever serde the other topics use
+        let payload = message.payload.as_deref()
+            .ok_or_else(|| TicketMasterError::InvalidArgument("Empty admin command".to_string()))?;
+        let command: ReservationAdminCommand = serde_json::from_slice(payload)?;
+        info!("Admin command for {}: {:?}", command.key(), command);
+
+        match command {
+            ReservationAdminCommand::DeleteReservation { reservation_id } => self.delete_reservation(&reservation_id).await,
+        }
+    }
+
+    // Deleting a reservation already gone publishes its tombstone again, so
+    // a redelivered command finishes what a crash interrupted
+    async fn delete_reservation(&self, reservation_id: &str) -> Result<()> {
+        Span::current().record("reservation_id", reservation_id);
+        let deleted = {
+            let _guard = self.lock(reservation_id);
+            let reservation = self.reservations.get(reservation_id)?;
+            if let Some(reservation) = &reservation {
+                if !reservation.state.is_deletable() {
+                    return Err(TicketMasterError::InvalidArgument(format!(
+                        "Reservation {} is {:?}; only failed and cancelled reservations can be deleted",
+                        reservation_id, reservation.state
+                    )));
+                }
+                self.reservations.delete(reservation_id)?;
+            }
+            reservation
+        };
+
+        self.producer.send_tombstone(Topics::STATE_USER_RESERVATION, reservation_id).await?;
+        let audit = AuditRecord::new(AuditEntity::Reservation, reservation_id, "Deleted", ADMIN, "Deleted")
+            .with_change(deleted.map(|reservation| format!("{:?}", reservation.state)), "Deleted".to_string());
+        send_audit_record(&self.producer, &audit).await?;
+        info!("Reservation deleted: {}", reservation_id);
+        Ok(())
+    }
+
+    async fn handle_area_status_update(&self, message: &KafkaMessage) -> Result<()> {
         let event_area_key = message.key.as_ref()
             .ok_or_else(|| TicketMasterError::InvalidArgument("Missing event area key".to_string()))?;
+        // Tombstones of deleted areas
+        if message.is_tombstone() {
+            self.area_status_cache.remove(event_area_key)?;
+            return Ok(());
+        }
         
-        let area_status: AreaStatus = message.deserialize_value()?;
+        let area_status: AreaStatus = message.deserialize_value().await?;
         
-        let area_status_cache: StateStore<String, AreaStatus> = self.context
-            .get_store(Stores::EVENT_AREA_STATUS_CACHE)
-            .ok_or_else(|| TicketMasterError::InvalidArgument("Area status cache not found".to_string()))?;
+        // Update cache; the least recently used area is evicted once it is full
+        self.area_status_cache.put(event_area_key.clone(), area_status)?;
 
-        // Update cache
-        area_status_cache.put(event_area_key.clone(), area_status);
-        
-        // Note: In a real implementation with LRU cache, you'd implement eviction logic here
-        // For now, we just store everything in the DashMap
-        
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis() as i64).unwrap_or(0)
+}
+
+/// Answers state queries for reservations from the reservation store
+struct ReservationQueries {
+    store: Arc<RocksDBStore>,
+}
+
+impl StateQueryHandler for ReservationQueries {
+    fn query(&self, store: &str, key: &str) -> Result<Option<serde_json::Value>> {
+        if store != Stores::RESERVATION {
+            return Ok(None);
+        }
+        self.store
+            .get::<Reservation>(key)?
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(Into::into)
+    }
+}
@@ -0,0 +1,34 @@
+use clap::Parser;
+use ticket_master::{run_service, CommonArgs, Result};
+
+mod postgres;
+mod service;
+
+#[derive(Parser, Debug)]
+#[command(name = "sink-service")]
+#[command(about = "PostgreSQL sink of reservations for Ticket Master")]
+struct Args {
+    /// Port of the metrics and health endpoints; overrides `sink.service.port`, 8087 when neither is set
+    #[arg(short = 'p', long = "port")]
+    port: Option<u16>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let configured = args.common.service_config("sink-service", |config| {
+        if let Some(port) = args.port {
+            config.sink.port = Some(port);
+        }
+    });
+    let Some(config) = configured.await? else {
+        return Ok(());
+    };
+
+    // SIGTERM or Ctrl+C stops consuming; records in flight are written and committed first.
+    // A write failing past its retries ends the run, and the supervisor restarts it
+    run_service(config, service::build).await
+}
@@ -0,0 +1,98 @@
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::types::Json;
+use ticket_master::{ReadinessCheck, Reservation, ReservationTable, Result, SinkConfig, TicketMasterError};
+use tracing::info;
+
+/// Reservations mirrored into a PostgreSQL table
+#[derive(Clone)]
+pub struct PostgresSink {
+    pool: PgPool,
+    table: ReservationTable,
+    upsert: String,
+    delete: String,
+}
+
+impl PostgresSink {
+    /// Connect lazily, so the service starts while the database is still coming up
+    pub fn from_config(config: &SinkConfig) -> Result<Self> {
+        let url = config.postgres_url.as_deref().ok_or_else(|| {
+            TicketMasterError::InvalidArgument("sink.postgres.url is required".to_string())
+        })?;
+        let table = ReservationTable::new(config.table())?;
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections())
+            .connect_lazy(url)
+            .map_err(|e| TicketMasterError::InvalidArgument(format!("Invalid sink.postgres.url: {}", e)))?;
+        Ok(Self { upsert: table.upsert_statement(), delete: table.delete_statement(), pool, table })
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    /// Create the table and its indexes unless they exist
+    pub async fn migrate(&self) -> Result<()> {
+        for statement in self.table.create_statements() {
+            sqlx::query(&statement).execute(&self.pool).await.map_err(sink_error)?;
+        }
+        info!("Mirroring reservations into table {}", self.table.name());
+        Ok(())
+    }
+
+    /// Write `reservation` as read at `offset`, unless a later record of it is already written
+    pub async fn upsert(&self, reservation: &Reservation, offset: i64) -> Result<()> {
+        // States and types are written as in the topic's JSON, e.g. `Reserved`
+        sqlx::query(&self.upsert)
+            .bind(&reservation.reservation_id)
+            .bind(&reservation.user_id)
+            .bind(&reservation.event_id)
+            .bind(&reservation.area_id)
+            .bind(format!("{:?}", reservation.state))
+            .bind(format!("{:?}", reservation.reservation_type))
+            .bind(reservation.num_of_seats)
+            .bind(Json(&reservation.seats))
+            .bind(reservation.failed_reason.as_ref().map(|reason| reason.message.as_str()))
+            .bind(offset)
+            .execute(&self.pool)
+            .await
+            .map_err(sink_error)?;
+        Ok(())
+    }
+
+    /// Delete the row of a reservation whose tombstone was read at `offset`
+    pub async fn delete(&self, reservation_id: &str, offset: i64) -> Result<()> {
+        sqlx::query(&self.delete)
+            .bind(reservation_id)
+            .bind(offset)
+            .execute(&self.pool)
+            .await
+            .map_err(sink_error)?;
+        Ok(())
+    }
+}
+
+fn sink_error(error: sqlx::Error) -> TicketMasterError {
+    TicketMasterError::Sink(error.to_string())
+}
+
+/// Ready once the database answers a query
+pub struct PostgresReadiness {
+    pool: PgPool,
+}
+
+impl PostgresReadiness {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadinessCheck for PostgresReadiness {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn check(&self) -> std::result::Result<(), String> {
+        sqlx::query("SELECT 1").execute(&self.pool).await.map(|_| ()).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,110 @@
+use ticket_master::{
+    ConsumerRunner, KafkaConsumer, KafkaMessage, MessageConsumer, MessageHandler, Metrics, RecordProcessor,
+    Reservation, Result, RetryConfig, ServiceBootstrap, ServiceConfig, StateChange, TicketMasterError, Topics,
+    health_router, retry_with_backoff,
+};
+use std::time::Duration;
+use tracing::{error, warn, Instrument};
+
+use crate::postgres::{PostgresReadiness, PostgresSink};
+
+const SINK_SERVICE: &str = "sink-service";
+const TOPICS: [&str; 1] = [Topics::STATE_USER_RESERVATION];
+const DEFAULT_PORT: u16 = 8087;
+// Writes are retried for about a minute before the service restarts
+const WRITE_ATTEMPTS: u32 = 10;
+const WRITE_RETRY_MAX_DELAY: Duration = Duration::from_secs(15);
+
+/// Mirrors the reservations of `state.user.reservation` into PostgreSQL
+pub type SinkService<C = KafkaConsumer> = ConsumerRunner<SinkProcessor, C>;
+
+/// Writes the reservations it reads to PostgreSQL; shared by all dispatcher lanes
+pub struct SinkProcessor {
+    sink: PostgresSink,
+    retry: RetryConfig,
+    metrics: Metrics,
+}
+
+pub async fn build(config: ServiceConfig) -> Result<SinkService> {
+    let bootstrap = ServiceBootstrap::new(&config).await?;
+    let sink = PostgresSink::from_config(&config.sink)?;
+
+    let mut service = with_clients(&config, bootstrap.consumer()?, sink.clone(), bootstrap.metrics().clone())?;
+    let readiness = bootstrap.readiness(&TOPICS)?.with_check(PostgresReadiness::new(sink.pool().clone()));
+    let port = config.sink.port.unwrap_or(DEFAULT_PORT);
+    bootstrap.serve(&mut service, "sink-health", port, health_router(readiness)).await?;
+
+    // Only heartbeats are produced
+    bootstrap.start_heartbeat(&mut service, bootstrap.plain_producer()?, env!("CARGO_PKG_VERSION"));
+    Ok(service)
+}
+
+/// Build the service on the given consumer, e.g. from a `MockKafka`; failed
+/// records are counted in `metrics`
+pub fn with_clients<C: MessageConsumer>(
+    config: &ServiceConfig,
+    consumer: C,
+    sink: PostgresSink,
+    metrics: Metrics,
+) -> Result<SinkService<C>> {
+    // Records are written in order per key and each writes a row of its own
+    let processor = SinkProcessor {
+        sink,
+        retry: RetryConfig::with_delays(WRITE_ATTEMPTS, Duration::from_millis(100), WRITE_RETRY_MAX_DELAY),
+        metrics: metrics.clone(),
+    };
+    ConsumerRunner::new(config, consumer, &TOPICS, processor, metrics)
+}
+
+#[async_trait::async_trait]
+impl RecordProcessor for SinkProcessor {
+    // The table is created if needed before anything is written
+    async fn start(&self) -> Result<()> {
+        self.sink.migrate().await
+    }
+
+    // Records that can't be read are logged and skipped; a failed write stops
+    // processing, and its offset stays uncommitted until the service restarts
+    fn on_failure(&self, e: TicketMasterError) -> Result<()> {
+        if matches!(e.root_cause(), TicketMasterError::Sink(_)) {
+            return Err(e);
+        }
+        error!("Error reading record: {}", e);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageHandler for SinkProcessor {
+    async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+        async {
+            match message.topic.as_str() {
+                Topics::STATE_USER_RESERVATION => match message.state_change::<Reservation>().await? {
+                    StateChange::Upsert(reservation) => {
+                        retry_with_backoff(&self.retry, "write reservation", || {
+                            self.sink.upsert(&reservation, message.offset)
+                        })
+                        .await
+                    }
+                    StateChange::Delete(reservation_id) => {
+                        retry_with_backoff(&self.retry, "delete reservation", || {
+                            self.sink.delete(reservation_id, message.offset)
+                        })
+                        .await
+                    }
+                },
+                _ => {
+                    warn!("Unknown topic: {}", message.topic);
+                    Ok(())
+                }
+            }
+        }
+        .instrument(message.span())
+        .await
+        .map_err(|e: TicketMasterError| {
+            let e = e.with_context(message.error_context(SINK_SERVICE));
+            self.metrics.record_error(&e);
+            e
+        })
+    }
+}
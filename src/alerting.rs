@@ -0,0 +1,256 @@
+use crate::{Metrics, Result, ServiceConfig, TicketMasterError};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The payload shape alert webhooks expect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// Slack incoming webhooks, and others taking `{"text"}`
+    #[default]
+    Slack,
+    /// Microsoft Teams incoming webhooks, as a message card
+    Teams,
+    /// PagerDuty Events API v2; resolving an alert resolves its incident
+    PagerDuty,
+}
+
+impl FromStr for WebhookFormat {
+    type Err = TicketMasterError;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format.trim().to_ascii_lowercase().as_str() {
+            "slack" => Ok(Self::Slack),
+            "teams" => Ok(Self::Teams),
+            "pagerduty" => Ok(Self::PagerDuty),
+            _ => Err(TicketMasterError::InvalidArgument(format!(
+                "Unknown webhook format {}, use slack, teams or pagerduty",
+                format
+            ))),
+        }
+    }
+}
+
+/// A condition as last checked: firing with what is wrong, or clear
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertCheck {
+    pub name: String,
+    pub problem: Option<String>,
+}
+
+impl AlertCheck {
+    pub fn ok(name: impl Into<String>) -> Self {
+        Self { name: name.into(), problem: None }
+    }
+
+    pub fn firing(name: impl Into<String>, problem: impl Into<String>) -> Self {
+        Self { name: name.into(), problem: Some(problem.into()) }
+    }
+}
+
+/// Posts alerts of one service to a webhook
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    http: reqwest::Client,
+    url: String,
+    format: WebhookFormat,
+    source: String,
+    routing_key: Option<String>,
+}
+
+impl WebhookNotifier {
+    /// Alerts of `source`, e.g. the application id, posted to `url`
+    pub fn new(url: &str, format: WebhookFormat, source: &str) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .map_err(|e| TicketMasterError::InvalidArgument(format!("Failed to create the webhook client: {}", e)))?;
+        Ok(Self {
+            http,
+            url: url.to_string(),
+            format,
+            source: source.to_string(),
+            routing_key: None,
+        })
+    }
+
+    /// The integration key PagerDuty events are routed with
+    pub fn with_routing_key(mut self, routing_key: &str) -> Self {
+        self.routing_key = Some(routing_key.to_string());
+        self
+    }
+
+    /// The notifier of the `alert.` properties; none without a webhook URL
+    pub fn from_config(config: &ServiceConfig) -> Result<Option<Self>> {
+        let alerts = &config.alerts;
+        let Some(url) = &alerts.webhook_url else {
+            return Ok(None);
+        };
+        let format = alerts.webhook_format.unwrap_or_default();
+        let notifier = Self::new(url, format, &config.application_id)?;
+        match (format, &alerts.pagerduty_routing_key) {
+            (_, Some(routing_key)) => Ok(Some(notifier.with_routing_key(routing_key))),
+            (WebhookFormat::PagerDuty, None) => Err(TicketMasterError::InvalidArgument(
+                "alert.pagerduty.routing.key is required with the pagerduty webhook format".to_string(),
+            )),
+            (_, None) => Ok(Some(notifier)),
+        }
+    }
+
+    /// The body posted when `name` starts firing with `problem`, or clears when `None`
+    pub fn payload(&self, name: &str, problem: Option<&str>) -> Value {
+        let title = match problem {
+            Some(_) => format!("[{}] FIRING {}", self.source, name),
+            None => format!("[{}] RESOLVED {}", self.source, name),
+        };
+        match self.format {
+            WebhookFormat::Slack => match problem {
+                Some(problem) => json!({ "text": format!("{}: {}", title, problem) }),
+                None => json!({ "text": title }),
+            },
+            WebhookFormat::Teams => json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "summary": title,
+                "themeColor": if problem.is_some() { "D93F0B" } else { "2EB886" },
+                "title": title,
+                "text": problem.unwrap_or("Back to normal"),
+            }),
+            WebhookFormat::PagerDuty => {
+                let dedup_key = format!("{}/{}", self.source, name);
+                match problem {
+                    Some(problem) => json!({
+                        "routing_key": self.routing_key,
+                        "event_action": "trigger",
+                        "dedup_key": dedup_key,
+                        "payload": {
+                            "summary": format!("{}: {}", name, problem),
+                            "source": self.source,
+                            "severity": "error",
+                        },
+                    }),
+                    None => json!({
+                        "routing_key": self.routing_key,
+                        "event_action": "resolve",
+                        "dedup_key": dedup_key,
+                    }),
+                }
+            }
+        }
+    }
+
+    pub async fn notify(&self, name: &str, problem: Option<&str>) -> Result<()> {
+        self.http
+            .post(&self.url)
+            .json(&self.payload(name, problem))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| TicketMasterError::InvalidArgument(format!("Alert webhook failed: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Notifies once when a checked condition starts firing and once when it clears
+pub struct AlertMonitor {
+    notifier: WebhookNotifier,
+    firing: HashSet<String>,
+}
+
+impl AlertMonitor {
+    pub fn new(notifier: WebhookNotifier) -> Self {
+        Self { notifier, firing: HashSet::new() }
+    }
+
+    /// Notify of the checks that changed; conditions missing from `checks`
+    /// keep their state, and a failed notification is tried again next update
+    pub async fn update(&mut self, checks: Vec<AlertCheck>) {
+        for check in checks {
+            let firing = self.firing.contains(&check.name);
+            if check.problem.is_some() == firing {
+                continue;
+            }
+            match self.notifier.notify(&check.name, check.problem.as_deref()).await {
+                Ok(()) if firing => {
+                    info!("Alert {} resolved", check.name);
+                    self.firing.remove(&check.name);
+                }
+                Ok(()) => {
+                    warn!("Alert {} firing: {}", check.name, check.problem.unwrap_or_default());
+                    self.firing.insert(check.name);
+                }
+                Err(e) => warn!("Failed to notify of alert {}: {}", check.name, e),
+            }
+        }
+    }
+
+    pub fn is_firing(&self, name: &str) -> bool {
+        self.firing.contains(name)
+    }
+}
+
+/// Run `check` every `interval` and notify of the alerts it changes
+pub fn spawn_alert_monitor<F, Fut>(notifier: WebhookNotifier, interval: Duration, mut check: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Vec<AlertCheck>> + Send,
+{
+    tokio::spawn(async move {
+        let mut monitor = AlertMonitor::new(notifier);
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            monitor.update(check().await).await;
+        }
+    })
+}
+
+/// Share of the reservations decided since the last check that failed, from
+/// the reservation counters of `Metrics`
+pub struct ReservationFailureRate {
+    metrics: Metrics,
+    threshold: f64,
+    min_results: u64,
+    successful: f64,
+    failed: f64,
+}
+
+impl ReservationFailureRate {
+    /// Fires when more than `threshold` of at least `min_results` results failed
+    pub fn new(metrics: Metrics, threshold: f64, min_results: u64) -> Self {
+        let successful = metrics.reservations_successful.get();
+        let failed = metrics.reservations_failed.get();
+        Self { metrics, threshold, min_results, successful, failed }
+    }
+
+    /// Too few results leave the alert as it was
+    pub fn check(&mut self) -> Option<AlertCheck> {
+        let successful = self.metrics.reservations_successful.get();
+        let failed = self.metrics.reservations_failed.get();
+        let (new_successful, new_failed) = (successful - self.successful, failed - self.failed);
+        let results = new_successful + new_failed;
+        if results < self.min_results.max(1) as f64 {
+            return None;
+        }
+        self.successful = successful;
+        self.failed = failed;
+
+        let rate = new_failed / results;
+        Some(if rate > self.threshold {
+            AlertCheck::firing(
+                "reservation_failure_rate",
+                format!("{:.0}% of the last {} reservations failed", rate * 100.0, results),
+            )
+        } else {
+            AlertCheck::ok("reservation_failure_rate")
+        })
+    }
+}
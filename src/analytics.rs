@@ -0,0 +1,319 @@
+use crate::{event_area_key, AreaStatus, Reservation, ReservationState, Result, RocksDBStore};
+use chrono::{DateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+// Key prefixes of the aggregates in the analytics store
+const AREA_PREFIX: &str = "area#";
+const HOUR_PREFIX: &str = "hour#";
+const RESERVATION_PREFIX: &str = "reservation#";
+
+/// Sales of one area, as far as analytics-service has seen them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AreaSales {
+    pub event_id: String,
+    pub area_id: String,
+    pub price: i32,
+    /// From the area's latest status
+    pub available_seats: i32,
+    /// Seats of reserved and paid reservations
+    pub tickets_sold: i64,
+    /// Seats of paid reservations
+    pub tickets_paid: i64,
+}
+
+impl AreaSales {
+    /// Paid seats at the area's price
+    pub fn revenue(&self) -> i64 {
+        self.tickets_paid * self.price as i64
+    }
+
+    /// Share of the area's seats sold, from 0 to 1
+    pub fn sell_through(&self) -> f64 {
+        let capacity = self.tickets_sold + self.available_seats.max(0) as i64;
+        if capacity == 0 {
+            return 0.0;
+        }
+        self.tickets_sold as f64 / capacity as f64
+    }
+
+    pub fn report(&self) -> AreaSalesReport {
+        AreaSalesReport {
+            sales: self.clone(),
+            revenue: self.revenue(),
+            sell_through: self.sell_through(),
+        }
+    }
+}
+
+/// `AreaSales` with the figures derived from it, as reported
+#[derive(Debug, Clone, Serialize)]
+pub struct AreaSalesReport {
+    #[serde(flatten)]
+    pub sales: AreaSales,
+    pub revenue: i64,
+    pub sell_through: f64,
+}
+
+/// Totals of an event over its areas
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EventSalesReport {
+    pub event_id: String,
+    pub tickets_sold: i64,
+    pub tickets_paid: i64,
+    pub revenue: i64,
+    pub sell_through: f64,
+    pub areas: Vec<AreaSalesReport>,
+}
+
+/// Tickets sold in an area in one hour, by when their reservation was first seen reserved
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HourlySales {
+    pub event_id: String,
+    pub area_id: String,
+    /// Start of the hour, e.g. `2026-10-16T21:00:00Z`
+    pub hour: DateTime<Utc>,
+    pub tickets_sold: i64,
+}
+
+// What a reservation currently adds to the aggregates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CountedReservation {
+    event_id: String,
+    area_id: String,
+    seats: i64,
+    paid: bool,
+    hour: DateTime<Utc>,
+}
+
+/// Keeps ticket sales per event, area and hour in a RocksDB store, from
+/// reservations and area statuses
+///
+/// What each reservation adds is kept too, so a reservation seen again, or
+/// moving from reserved to paid or cancelled, only changes the figures by
+/// the difference.
+#[derive(Clone)]
+pub struct SalesAggregator {
+    store: Arc<RocksDBStore>,
+}
+
+impl SalesAggregator {
+    pub fn new(store: Arc<RocksDBStore>) -> Self {
+        Self { store }
+    }
+
+    /// Take the area's price and remaining seats from its latest status
+    pub fn apply_area_status(&self, area_status: &AreaStatus) -> Result<()> {
+        let key = area_key(&area_status.event_id, &area_status.area_id);
+        let _guard = self.store.lock_key(&key);
+        let mut sales = self.area_sales(&area_status.event_id, &area_status.area_id)?;
+        sales.price = area_status.price;
+        sales.available_seats = area_status.available_seats;
+        self.store.put(&key, &sales)
+    }
+
+    /// Count the reservation's seats as sold while it is reserved or paid;
+    /// `now` dates sales first seen with it
+    pub fn apply_reservation(&self, reservation: &Reservation, now: DateTime<Utc>) -> Result<()> {
+        let reservation_key = format!("{}{}", RESERVATION_PREFIX, reservation.reservation_id);
+        let _guard = self.store.lock_key(&area_key(&reservation.event_id, &reservation.area_id));
+        let previous: Option<CountedReservation> = self.store.get(&reservation_key)?;
+        let counted = match reservation.state {
+            ReservationState::Reserved | ReservationState::Paid => Some(CountedReservation {
+                event_id: reservation.event_id.clone(),
+                area_id: reservation.area_id.clone(),
+                seats: reservation.seats.len() as i64,
+                paid: reservation.state == ReservationState::Paid,
+                hour: previous.as_ref().map(|previous| previous.hour).unwrap_or_else(|| start_of_hour(now)),
+            }),
+            _ => None,
+        };
+        if counted == previous {
+            return Ok(());
+        }
+        self.recount(&reservation_key, previous, counted)
+    }
+
+    /// Take back what a deleted reservation added to the figures
+    pub fn delete_reservation(&self, reservation_id: &str) -> Result<()> {
+        let reservation_key = format!("{}{}", RESERVATION_PREFIX, reservation_id);
+        let Some(previous) = self.store.get::<CountedReservation>(&reservation_key)? else {
+            return Ok(());
+        };
+        let _guard = self.store.lock_key(&area_key(&previous.event_id, &previous.area_id));
+        self.recount(&reservation_key, Some(previous), None)
+    }
+
+    /// Forget a deleted area with its hourly sales
+    pub fn delete_area(&self, event_area_id: &str) -> Result<()> {
+        let area = format!("{}{}", AREA_PREFIX, event_area_id);
+        let _guard = self.store.lock_key(&area);
+        let hours: Vec<String> = self
+            .store
+            .scan_prefix::<HourlySales>(&format!("{}{}#", HOUR_PREFIX, event_area_id))
+            .map(|entry| entry.map(|(key, _)| key))
+            .collect::<Result<_>>()?;
+        let mut batch = self.store.write_batch();
+        batch.delete(&area);
+        for key in &hours {
+            batch.delete(key);
+        }
+        batch.commit()
+    }
+
+    // Replace what a reservation counted as `previous` by `counted` in the
+    // figures of its area and hour; the caller holds the area's lock
+    fn recount(&self, reservation_key: &str, previous: Option<CountedReservation>, counted: Option<CountedReservation>) -> Result<()> {
+        let Some(latest) = counted.as_ref().or(previous.as_ref()) else {
+            return Ok(());
+        };
+        let mut sales = self.area_sales(&latest.event_id, &latest.area_id)?;
+        let hour_key = hour_key(&latest.event_id, &latest.area_id, &latest.hour);
+        let mut hourly = self.store.get::<HourlySales>(&hour_key)?.unwrap_or_else(|| HourlySales {
+            event_id: latest.event_id.clone(),
+            area_id: latest.area_id.clone(),
+            hour: latest.hour,
+            tickets_sold: 0,
+        });
+        for (counted, sign) in [(&previous, -1), (&counted, 1)] {
+            if let Some(counted) = counted {
+                sales.tickets_sold += sign * counted.seats;
+                hourly.tickets_sold += sign * counted.seats;
+                if counted.paid {
+                    sales.tickets_paid += sign * counted.seats;
+                }
+            }
+        }
+
+        let mut batch = self.store.write_batch();
+        batch.put(&area_key(&latest.event_id, &latest.area_id), &sales)?;
+        batch.put(&hour_key, &hourly)?;
+        match &counted {
+            Some(counted) => batch.put(reservation_key, counted)?,
+            None => batch.delete(reservation_key),
+        };
+        batch.commit()
+    }
+
+    fn area_sales(&self, event_id: &str, area_id: &str) -> Result<AreaSales> {
+        Ok(self.store.get(&area_key(event_id, area_id))?.unwrap_or_else(|| AreaSales {
+            event_id: event_id.to_string(),
+            area_id: area_id.to_string(),
+            ..AreaSales::default()
+        }))
+    }
+
+    /// Totals of every event seen, by event id
+    pub fn events(&self) -> Result<Vec<EventSalesReport>> {
+        let mut areas: BTreeMap<String, Vec<AreaSales>> = BTreeMap::new();
+        for entry in self.store.scan_prefix::<AreaSales>(AREA_PREFIX) {
+            let (_, sales) = entry?;
+            areas.entry(sales.event_id.clone()).or_default().push(sales);
+        }
+        Ok(areas.into_iter().map(|(event_id, areas)| event_report(event_id, areas)).collect())
+    }
+
+    /// Totals of one event with its areas; `None` if nothing of it has been seen
+    pub fn event(&self, event_id: &str) -> Result<Option<EventSalesReport>> {
+        let prefix = area_key(event_id, "");
+        let areas = self
+            .store
+            .scan_prefix::<AreaSales>(&prefix)
+            .map(|entry| entry.map(|(_, sales)| sales))
+            .collect::<Result<Vec<_>>>()?;
+        if areas.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(event_report(event_id.to_string(), areas)))
+    }
+
+    /// Tickets sold in each area of an event, hour by hour
+    pub fn hourly(&self, event_id: &str) -> Result<Vec<HourlySales>> {
+        let prefix = format!("{}{}", HOUR_PREFIX, event_area_key(event_id, ""));
+        self.store
+            .scan_prefix::<HourlySales>(&prefix)
+            .map(|entry| entry.map(|(_, hourly)| hourly))
+            .filter(|entry| !matches!(entry, Ok(hourly) if hourly.tickets_sold == 0))
+            .collect()
+    }
+}
+
+fn event_report(event_id: String, areas: Vec<AreaSales>) -> EventSalesReport {
+    let total = areas.iter().fold(AreaSales::default(), |mut total, sales| {
+        total.tickets_sold += sales.tickets_sold;
+        total.tickets_paid += sales.tickets_paid;
+        total.available_seats += sales.available_seats.max(0);
+        total
+    });
+    EventSalesReport {
+        event_id,
+        tickets_sold: total.tickets_sold,
+        tickets_paid: total.tickets_paid,
+        revenue: areas.iter().map(AreaSales::revenue).sum(),
+        sell_through: total.sell_through(),
+        areas: areas.iter().map(AreaSales::report).collect(),
+    }
+}
+
+/// Area sales as CSV with a header row, one line per area
+pub fn area_sales_csv(reports: &[EventSalesReport]) -> String {
+    let mut csv = String::from("event_id,area_id,price,tickets_sold,tickets_paid,available_seats,revenue,sell_through\n");
+    for area in reports.iter().flat_map(|report| &report.areas) {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{:.4}",
+            csv_field(&area.sales.event_id),
+            csv_field(&area.sales.area_id),
+            area.sales.price,
+            area.sales.tickets_sold,
+            area.sales.tickets_paid,
+            area.sales.available_seats,
+            area.revenue,
+            area.sell_through,
+        );
+    }
+    csv
+}
+
+/// Hourly sales as CSV with a header row
+pub fn hourly_sales_csv(hours: &[HourlySales]) -> String {
+    let mut csv = String::from("event_id,area_id,hour,tickets_sold\n");
+    for hourly in hours {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{}",
+            csv_field(&hourly.event_id),
+            csv_field(&hourly.area_id),
+            hourly.hour.to_rfc3339(),
+            hourly.tickets_sold,
+        );
+    }
+    csv
+}
+
+// Quoted when it holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn area_key(event_id: &str, area_id: &str) -> String {
+    format!("{}{}", AREA_PREFIX, event_area_key(event_id, area_id))
+}
+
+// Hours sort in time order under each area
+fn hour_key(event_id: &str, area_id: &str, hour: &DateTime<Utc>) -> String {
+    format!("{}{}#{}", HOUR_PREFIX, event_area_key(event_id, area_id), hour.format("%Y-%m-%dT%H"))
+}
+
+fn start_of_hour(time: DateTime<Utc>) -> DateTime<Utc> {
+    time.with_minute(0)
+        .and_then(|time| time.with_second(0))
+        .and_then(|time| time.with_nanosecond(0))
+        .unwrap_or(time)
+}
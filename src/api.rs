@@ -0,0 +1,174 @@
+use crate::{ErrorCode, TicketMasterError};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tracing::error;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// Id of the request the current task is handling, if any
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|request_id| request_id.clone()).ok()
+}
+
+/// Handle a request as `request_id`, so the responses built for it carry it
+pub async fn with_request_id<F: Future>(request_id: String, handle: F) -> F::Output {
+    CURRENT_REQUEST_ID.scope(request_id, handle).await
+}
+
+/// Body of every response of the HTTP services
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiResponse<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub error: Option<String>,
+    /// Machine-readable error code, e.g. `not_found`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Per-field problems of a request that failed validation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Vec<FieldError>>,
+    /// Cursor of the next page of a list; absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Id of the request, as in `x-request-id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn success(data: T) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+            code: None,
+            details: None,
+            next_cursor: None,
+            request_id: current_request_id(),
+        }
+    }
+
+    pub fn error(code: &str, message: String) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(message),
+            code: Some(code.to_string()),
+            details: None,
+            next_cursor: None,
+            request_id: current_request_id(),
+        }
+    }
+}
+
+impl<T> ApiResponse<Vec<T>> {
+    /// One page of a list, and the cursor of the next if there is one
+    pub fn page(items: Vec<T>, next_cursor: Option<String>) -> Self {
+        Self {
+            next_cursor,
+            ..Self::success(items)
+        }
+    }
+}
+
+/// Why one field of a request body was rejected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldError {
+    /// Path of the field, e.g. `areas[0].row_count`
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// A failed request: its status code, a machine-readable error code and a
+/// message, sent as an `ApiResponse` body
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    details: Vec<FieldError>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            details: Vec::new(),
+        }
+    }
+
+    /// 422 listing every field that failed validation
+    pub fn validation(details: Vec<FieldError>) -> Self {
+        Self {
+            details,
+            ..Self::new(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed", "Request validation failed")
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+}
+
+impl From<TicketMasterError> for ApiError {
+    fn from(e: TicketMasterError) -> Self {
+        let (status, code) = match e.root_cause() {
+            TicketMasterError::InvalidArgument(_) => (StatusCode::BAD_REQUEST, "invalid_argument"),
+            TicketMasterError::InvalidEventArea(_) => (StatusCode::NOT_FOUND, "event_area_not_found"),
+            TicketMasterError::InvalidReservationStrategy(_) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "invalid_reservation_strategy")
+            }
+            TicketMasterError::SeatNotAvailable { .. } => (StatusCode::CONFLICT, "seat_not_available"),
+            TicketMasterError::InsufficientSeats => (StatusCode::CONFLICT, "insufficient_seats"),
+            TicketMasterError::VersionConflict { .. } => (StatusCode::CONFLICT, "version_conflict"),
+            TicketMasterError::StoreNotFound(_) => (StatusCode::NOT_FOUND, "store_not_found"),
+            TicketMasterError::IllegalTransition { .. } => (StatusCode::CONFLICT, "illegal_transition"),
+            TicketMasterError::UnknownUser(_) => (StatusCode::UNPROCESSABLE_ENTITY, "unknown_user"),
+            TicketMasterError::UserExists(_) => (StatusCode::CONFLICT, "user_exists"),
+            TicketMasterError::CircuitOpen => (StatusCode::SERVICE_UNAVAILABLE, "circuit_open"),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+        };
+        Self::new(status, code, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if self.status.is_server_error() {
+            error!("Request failed ({}): {}", self.code, self.message);
+        }
+        let mut body = ApiResponse::<()>::error(self.code, self.message);
+        if !self.details.is_empty() {
+            body.details = Some(self.details);
+        }
+        let mut response = (self.status, Json(body)).into_response();
+        // Labels the request in the error metrics
+        response.extensions_mut().insert(ErrorCode(self.code));
+        response
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{Result, RocksDBStore, TicketMasterError};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use uuid::Uuid;
+
+const API_KEY_PREFIX: &str = "tm_";
+
+/// An issued API key; the secret itself is never stored, only its SHA-256
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    /// Who or what the key was issued to, e.g. `load-test`
+    pub name: String,
+    pub secret_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none()
+    }
+}
+
+/// API keys for service-to-service callers, kept in RocksDB
+///
+/// Keys look like `tm_<id>.<secret>`. The id finds the stored entry and the
+/// secret is checked against its hash, so a copy of the store does not give
+/// away usable keys. Revoked keys are kept for the record.
+pub struct ApiKeyStore {
+    store: RocksDBStore,
+}
+
+impl ApiKeyStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        std::fs::create_dir_all(&path)?;
+        Ok(Self {
+            store: RocksDBStore::new(path)?,
+        })
+    }
+
+    /// Issue a key for `name`; the returned key string is the only copy of the secret
+    pub fn issue(&self, name: &str) -> Result<(ApiKey, String)> {
+        if name.trim().is_empty() {
+            return Err(TicketMasterError::InvalidArgument("API key name must not be empty".to_string()));
+        }
+        let id = Uuid::new_v4().simple().to_string();
+        let secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let key = ApiKey {
+            id: id.clone(),
+            name: name.trim().to_string(),
+            secret_hash: hash_secret(&secret),
+            created_at: Utc::now(),
+            revoked_at: None,
+        };
+        self.store.put(&id, &key)?;
+        Ok((key, format!("{}{}.{}", API_KEY_PREFIX, id, secret)))
+    }
+
+    /// Revoke the key; returns false if there is no such key
+    pub fn revoke(&self, id: &str) -> Result<bool> {
+        let _guard = self.store.lock_key(id);
+        let Some(mut key) = self.store.get::<ApiKey>(id)? else {
+            return Ok(false);
+        };
+        if key.revoked_at.is_none() {
+            key.revoked_at = Some(Utc::now());
+            self.store.put(id, &key)?;
+        }
+        Ok(true)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<ApiKey>> {
+        self.store.get(id)
+    }
+
+    /// Every key issued, revoked ones included
+    pub fn list(&self) -> Result<Vec<ApiKey>> {
+        Ok(self.store.entries::<ApiKey>()?.into_iter().map(|(_, key)| key).collect())
+    }
+
+    /// The active key `presented` belongs to, if it is one
+    pub fn verify(&self, presented: &str) -> Result<Option<ApiKey>> {
+        let Some((id, secret)) = presented
+            .strip_prefix(API_KEY_PREFIX)
+            .and_then(|key| key.split_once('.'))
+        else {
+            return Ok(None);
+        };
+        Ok(self
+            .store
+            .get::<ApiKey>(id)?
+            .filter(|key| key.is_active() && constant_time_eq(&key.secret_hash, &hash_secret(secret))))
+    }
+}
+
+/// Bearer tokens naming the user a customer-facing request comes from
+///
+/// Tokens look like `<user_id>.<expiry>.<signature>`: the expiry in Unix
+/// seconds and an HMAC-SHA256 of the two, keyed with a secret shared by the
+/// login front end issuing them and the gateway checking them.
+#[derive(Clone)]
+pub struct UserTokens {
+    secret: Vec<u8>,
+}
+
+impl UserTokens {
+    pub fn new(secret: &str) -> Result<Self> {
+        if secret.is_empty() {
+            return Err(TicketMasterError::InvalidArgument("The user token secret must not be empty".to_string()));
+        }
+        Ok(Self { secret: secret.as_bytes().to_vec() })
+    }
+
+    /// A token for `user_id`, good for `ttl` from `now`
+    pub fn issue(&self, user_id: &str, ttl: Duration, now: DateTime<Utc>) -> String {
+        let claims = format!("{}.{}", user_id, (now + ttl).timestamp());
+        let signature = self.sign(&claims);
+        format!("{}.{}", claims, signature)
+    }
+
+    /// The user a token was issued to, unless it is forged or expired at `now`
+    pub fn verify(&self, token: &str, now: DateTime<Utc>) -> Option<String> {
+        let (claims, signature) = token.rsplit_once('.')?;
+        if !constant_time_eq(&self.sign(claims), signature) {
+            return None;
+        }
+        // User ids may hold dots, so the expiry is the last part of the claims
+        let (user_id, expiry) = claims.rsplit_once('.')?;
+        let expires_at = Utc.timestamp_opt(expiry.parse().ok()?, 0).single()?;
+        (!user_id.is_empty() && now < expires_at).then(|| user_id.to_string())
+    }
+
+    fn sign(&self, claims: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(claims.as_bytes());
+        mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+fn hash_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Compare without stopping at the first difference, so timing doesn't leak secrets
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -20,7 +20,8 @@ pub mod schemas {
                     {"name": "areaId", "type": "string"},
                     {"name": "price", "type": "int"},
                     {"name": "rowCount", "type": "int"},
-                    {"name": "colCount", "type": "int"}
+                    {"name": "colCount", "type": "int"},
+                    {"name": "strategy", "type": ["null", "string"], "default": null}
                 ]
             }}}
         ]
@@ -64,7 +65,7 @@ pub mod schemas {
             {"name": "reservationType", "type": {
                 "type": "enum",
                 "name": "ReservationType",
-                "symbols": ["SELF_PICK", "RANDOM"]
+                "symbols": ["SELF_PICK", "RANDOM", "CONTINUOUS_RANDOM"]
             }},
             {"name": "seats", "type": {"type": "array", "items": {
                 "type": "record",
@@ -73,7 +74,12 @@ pub mod schemas {
                     {"name": "row", "type": "int"},
                     {"name": "col", "type": "int"}
                 ]
-            }}}
+            }}},
+            {"name": "preferences", "type": {"type": "array", "items": {
+                "type": "enum",
+                "name": "SeatPreference",
+                "symbols": ["AISLE", "FRONT", "TOGETHER"]
+            }}, "default": []}
         ]
     }
     "#;
@@ -93,7 +99,7 @@ pub mod schemas {
             {"name": "reservationType", "type": {
                 "type": "enum",
                 "name": "ReservationType",
-                "symbols": ["SELF_PICK", "RANDOM"]
+                "symbols": ["SELF_PICK", "RANDOM", "CONTINUOUS_RANDOM"]
             }},
             {"name": "seats", "type": {"type": "array", "items": {
                 "type": "record",
@@ -102,7 +108,17 @@ pub mod schemas {
                     {"name": "row", "type": "int"},
                     {"name": "col", "type": "int"}
                 ]
-            }}}
+            }}},
+            {"name": "preferences", "type": {"type": "array", "items": {
+                "type": "enum",
+                "name": "SeatPreference",
+                "symbols": ["AISLE", "FRONT", "TOGETHER"]
+            }}, "default": []},
+            {"name": "priority", "type": {
+                "type": "enum",
+                "name": "ReservationPriority",
+                "symbols": ["GENERAL", "PRIORITY"]
+            }, "default": "GENERAL"}
         ]
     }
     "#;
@@ -121,7 +137,7 @@ pub mod schemas {
             {"name": "reservationType", "type": {
                 "type": "enum",
                 "name": "ReservationType",
-                "symbols": ["SELF_PICK", "RANDOM"]
+                "symbols": ["SELF_PICK", "RANDOM", "CONTINUOUS_RANDOM"]
             }},
             {"name": "seats", "type": {"type": "array", "items": {
                 "type": "record",
@@ -165,24 +181,115 @@ pub mod schemas {
             {"name": "errorCode", "type": ["null", {
                 "type": "enum",
                 "name": "ReservationErrorCode",
-                "symbols": ["SEAT_UNAVAILABLE", "INSUFFICIENT_SEATS", "INVALID_AREA", "INVALID_EVENT"]
+                "symbols": ["SEAT_UNAVAILABLE", "INSUFFICIENT_SEATS", "INVALID_AREA", "INVALID_EVENT", "TIMEOUT"]
             }], "default": null},
             {"name": "errorMessage", "type": ["null", "string"], "default": null}
         ]
     }
     "#;
+
+    pub const EVENT_ARTWORK_SCHEMA: &str = r#"
+    {
+        "type": "record",
+        "name": "EventArtwork",
+        "namespace": "lab.tall15421542.app.domain.beans",
+        "fields": [
+            {"name": "eventId", "type": "string"},
+            {"name": "url", "type": "string"},
+            {"name": "objectKey", "type": "string"},
+            {"name": "contentType", "type": "string"},
+            {"name": "size", "type": "long"},
+            {"name": "uploadedAt", "type": "long"}
+        ]
+    }
+    "#;
+
+    pub const USER_PROFILE_SCHEMA: &str = r#"
+    {
+        "type": "record",
+        "name": "UserProfile",
+        "namespace": "lab.tall15421542.app.domain.beans",
+        "fields": [
+            {"name": "userId", "type": "string"},
+            {"name": "email", "type": "string"},
+            {"name": "displayName", "type": "string"},
+            {"name": "phone", "type": ["null", "string"], "default": null},
+            {"name": "createdAt", "type": "long"},
+            {"name": "updatedAt", "type": "long"}
+        ]
+    }
+    "#;
+
+    pub const CHARGE_PAYMENT_SCHEMA: &str = r#"
+    {
+        "type": "record",
+        "name": "ChargePayment",
+        "namespace": "lab.tall15421542.app.domain.beans",
+        "fields": [
+            {"name": "reservationId", "type": "string"},
+            {"name": "userId", "type": "string"},
+            {"name": "amount", "type": "long"},
+            {"name": "currency", "type": "string"},
+            {"name": "paymentMethod", "type": "string"},
+            {"name": "idempotencyKey", "type": "string"}
+        ]
+    }
+    "#;
+
+    pub const PAYMENT_RESULT_SCHEMA: &str = r#"
+    {
+        "type": "record",
+        "name": "PaymentResult",
+        "namespace": "lab.tall15421542.app.domain.beans",
+        "fields": [
+            {"name": "reservationId", "type": "string"},
+            {"name": "idempotencyKey", "type": "string"},
+            {"name": "status", "type": {
+                "type": "enum",
+                "name": "PaymentStatus",
+                "symbols": ["SUCCEEDED", "DECLINED", "FAILED"]
+            }},
+            {"name": "amount", "type": "long"},
+            {"name": "currency", "type": "string"},
+            {"name": "provider", "type": "string"},
+            {"name": "reference", "type": ["null", "string"], "default": null},
+            {"name": "message", "type": ["null", "string"], "default": null},
+            {"name": "processedAt", "type": "long"}
+        ]
+    }
+    "#;
 }
 
-/// Schema subjects for Schema Registry
+/// Schema subjects for Schema Registry, named `<topic>-value` (TopicNameStrategy)
 pub mod subjects {
-    pub const CREATE_EVENT: &str = "create-event-value";
-    pub const AREA_STATUS: &str = "area-status-value";
-    pub const RESERVE_SEAT: &str = "reserve-seat-value";
-    pub const CREATE_RESERVATION: &str = "create-reservation-value";
-    pub const RESERVATION: &str = "reservation-value";
-    pub const RESERVATION_RESULT: &str = "reservation-result-value";
+    pub const CREATE_EVENT: &str = "command.event.create_event-value";
+    pub const AREA_STATUS: &str = "state.event.area_status-value";
+    pub const INTERNAL_AREA_STATUS: &str = "internal.event.area_status-value";
+    pub const RESERVE_SEAT: &str = "command.event.reserve_seat-value";
+    pub const CREATE_RESERVATION: &str = "command.reservation.create_reservation-value";
+    pub const RESERVATION: &str = "state.user.reservation-value";
+    pub const RESERVATION_RESULT: &str = "response.reservation.result-value";
+    pub const EVENT_ARTWORK: &str = "state.event.artwork-value";
+    pub const USER_PROFILE: &str = "state.user.profile-value";
+    pub const CHARGE_PAYMENT: &str = "command.payment.charge-value";
+    pub const PAYMENT_RESULT: &str = "response.payment.result-value";
 }
 
+/// Every subject with the schema registered under it
+pub const SUBJECT_SCHEMAS: [(&str, &str); 11] = [
+    (subjects::CREATE_EVENT, schemas::CREATE_EVENT_SCHEMA),
+    (subjects::AREA_STATUS, schemas::AREA_STATUS_SCHEMA),
+    (subjects::INTERNAL_AREA_STATUS, schemas::AREA_STATUS_SCHEMA),
+    (subjects::RESERVE_SEAT, schemas::RESERVE_SEAT_SCHEMA),
+    (subjects::CREATE_RESERVATION, schemas::CREATE_RESERVATION_SCHEMA),
+    (subjects::RESERVATION, schemas::RESERVATION_SCHEMA),
+    (subjects::RESERVATION_RESULT, schemas::RESERVATION_RESULT_SCHEMA),
+    (subjects::EVENT_ARTWORK, schemas::EVENT_ARTWORK_SCHEMA),
+    (subjects::USER_PROFILE, schemas::USER_PROFILE_SCHEMA),
+    (subjects::CHARGE_PAYMENT, schemas::CHARGE_PAYMENT_SCHEMA),
+    (subjects::PAYMENT_RESULT, schemas::PAYMENT_RESULT_SCHEMA),
+];
+
 /// Initialize all schemas in the serializer
 pub fn load_all_schemas(serializer: &mut crate::AvroSerializer) -> crate::Result<()> {
     use apache_avro::Schema;
@@ -195,6 +302,10 @@ pub fn load_all_schemas(serializer: &mut crate::AvroSerializer) -> crate::Result
         ("create_reservation", schemas::CREATE_RESERVATION_SCHEMA),
         ("reservation", schemas::RESERVATION_SCHEMA),
         ("reservation_result", schemas::RESERVATION_RESULT_SCHEMA),
+        ("event_artwork", schemas::EVENT_ARTWORK_SCHEMA),
+        ("user_profile", schemas::USER_PROFILE_SCHEMA),
+        ("charge_payment", schemas::CHARGE_PAYMENT_SCHEMA),
+        ("payment_result", schemas::PAYMENT_RESULT_SCHEMA),
     ];
 
     for (name, schema_str) in schemas {
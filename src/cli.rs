@@ -0,0 +1,156 @@
+use crate::{
+    init_logging, merge_stream_properties, ConfigLoader, LogFormat, Result, SecretResolver, ServiceConfig,
+    DEFAULT_PROFILE,
+};
+use serde_json::Value;
+use std::path::PathBuf;
+use tracing::info;
+
+// Shown in place of secrets by `--print-config`
+const REDACTED: &str = "********";
+// Credentials, as JSON pointers into the config
+const SECRET_FIELDS: [&str; 16] = [
+    "/kafka/sasl_password",
+    "/kafka/ssl_key_password",
+    "/kafka/schema_registry_basic_auth",
+    "/admin_token",
+    "/artwork/s3_access_key",
+    "/artwork/s3_secret_key",
+    "/export/s3_access_key",
+    "/export/s3_secret_key",
+    "/sink/postgres_url",
+    "/alerts/webhook_url",
+    "/alerts/pagerduty_routing_key",
+    "/notification/smtp_password",
+    "/notification/webhook_url",
+    "/payment/api_key",
+    "/gateway_service/ticket_api_key",
+    "/gateway_service/token_secret",
+];
+// Passed-through properties whose values are masked
+const SECRET_PROPERTY_WORDS: [&str; 4] = ["password", "secret", "token", "jaas"];
+
+/// Flags every service takes, flattened into each service's own arguments
+///
+/// The file flags fall back to environment variables, so containers can be
+/// configured without a command line. Settings are layered as the profile's
+/// defaults, the config file, environment variables, then flags.
+#[derive(clap::Args, Debug, Clone)]
+pub struct CommonArgs {
+    /// Environment to run in, e.g. `dev`, `staging` or `prod`; picks the
+    /// config file and the defaults under it
+    #[arg(long = "profile", env = "TICKET_MASTER_PROFILE", default_value = DEFAULT_PROFILE)]
+    pub profile: String,
+
+    /// Directory holding `client.<profile>.properties`
+    #[arg(long = "config-dir", env = "TICKET_MASTER_CONFIG_DIR", default_value = "appConfig")]
+    pub config_dir: PathBuf,
+
+    /// Config file to read instead of the profile's: Java properties, YAML or TOML
+    #[arg(short = 'c', long = "config", env = "TICKET_MASTER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Properties file merged over the config, e.g. for Kafka client tuning
+    #[arg(long = "stream-config", visible_alias = "producer-config", env = "TICKET_MASTER_STREAM_CONFIG")]
+    pub stream_config: Option<PathBuf>,
+
+    /// State directory for storage; overrides `state.dir` and `STATE_DIR`
+    #[arg(short = 'd', long = "state-dir")]
+    pub state_dir: Option<PathBuf>,
+
+    /// Write logs as human-readable text or as one JSON object per line
+    #[arg(long = "log-format", env = "TICKET_MASTER_LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Print the config the service would run with as JSON, secrets masked, and exit
+    #[arg(long = "print-config")]
+    pub print_config: bool,
+}
+
+impl CommonArgs {
+    /// `--config`, or else `client.<profile>.properties` in `--config-dir`
+    pub fn config_file(&self) -> PathBuf {
+        match &self.config {
+            Some(config) => config.clone(),
+            None => self.config_dir.join(format!("client.{}.properties", self.profile)),
+        }
+    }
+
+    /// The loader for `application_id`; the config watcher reloads through it
+    pub fn config_loader(&self, application_id: &str) -> ConfigLoader {
+        let loader = ConfigLoader::new(application_id)
+            .with_profile(&self.profile)
+            .with_file(self.config_file())
+            .with_env();
+        match &self.state_dir {
+            Some(state_dir) => loader.with_override("state.dir", &state_dir.to_string_lossy()),
+            None => loader,
+        }
+    }
+
+    /// Load the config with `loader`, then merge `--stream-config` over it
+    pub fn load_config(&self, loader: ConfigLoader) -> Result<ServiceConfig> {
+        info!("Profile {}, config file: {:?}", self.profile, self.config_file());
+        let config = loader.load()?;
+        match &self.stream_config {
+            Some(stream_config) => {
+                info!("Loading stream config from: {:?}", stream_config);
+                merge_stream_properties(config, stream_config)
+            }
+            None => Ok(config),
+        }
+    }
+
+    /// Start logging and load the config of `application_id`, as a service
+    /// without a config watcher does on startup
+    ///
+    /// `configure` applies the service's own flags. Secrets are resolved only
+    /// after `--print-config`, which prints the config and returns `None`.
+    pub async fn service_config<F>(&self, application_id: &str, configure: F) -> Result<Option<ServiceConfig>>
+    where
+        F: FnOnce(&mut ServiceConfig),
+    {
+        let log_filter = init_logging(application_id, self.log_format)?;
+        info!("Starting {}", application_id);
+
+        let mut config = self.load_config(self.config_loader(application_id))?;
+        config.application_id = application_id.to_string();
+        configure(&mut config);
+        if self.print_config {
+            println!("{}", redacted_config(&config)?);
+            return Ok(None);
+        }
+        info!("State directory: {}", config.state_dir);
+
+        SecretResolver::from_env().resolve_config(&mut config).await?;
+        log_filter.set(config.log_filter.as_deref())?;
+        Ok(Some(config))
+    }
+}
+
+/// `config` as pretty JSON with credentials masked; secret references such as
+/// `${vault:...}` are shown as they are
+pub fn redacted_config(config: &ServiceConfig) -> Result<String> {
+    let mut json = serde_json::to_value(config)?;
+    for pointer in SECRET_FIELDS {
+        if let Some(secret) = json.pointer_mut(pointer) {
+            redact(secret);
+        }
+    }
+    if let Some(properties) = json["kafka"]["additional_properties"].as_object_mut() {
+        for (key, value) in properties {
+            if SECRET_PROPERTY_WORDS.iter().any(|word| key.contains(word)) {
+                redact(value);
+            }
+        }
+    }
+    Ok(serde_json::to_string_pretty(&json)?)
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::String(secret) if secret.starts_with("${") => {}
+        Value::Null => {}
+        _ => *value = Value::String(REDACTED.to_string()),
+    }
+}
@@ -1,5 +1,7 @@
+use crate::{ExportFormat, NotificationOutcome, PaymentProviderKind, SmtpSecurity, NotificationTemplate, NotificationTemplates, ReservationType, RestartPolicy, Result, RetryConfig, RetryPolicies, SerdeFormat, TicketMasterError, TopicResolver, WebhookFormat, STATISTICS_INTERVAL_PROPERTY};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KafkaConfig {
@@ -10,6 +12,17 @@ pub struct KafkaConfig {
     pub sasl_username: Option<String>,
     pub sasl_password: Option<String>,
     pub ssl_ca_location: Option<String>,
+    /// Client certificate (PEM) presented to brokers requiring mTLS
+    pub ssl_certificate_location: Option<String>,
+    pub ssl_key_location: Option<String>,
+    /// Password of the key at `ssl_key_location`, if it is encrypted
+    pub ssl_key_password: Option<String>,
+    /// `https` checks broker host names against their certificates, `none` doesn't
+    pub ssl_endpoint_identification_algorithm: Option<String>,
+    /// `user:password` for the Schema Registry
+    pub schema_registry_basic_auth: Option<String>,
+    /// How often clients emit librdkafka statistics into metrics; 0 turns them off
+    pub statistics_interval_ms: Option<u64>,
     pub additional_properties: HashMap<String, String>,
 }
 
@@ -23,11 +36,946 @@ impl Default for KafkaConfig {
             sasl_username: None,
             sasl_password: None,
             ssl_ca_location: None,
+            ssl_certificate_location: None,
+            ssl_key_location: None,
+            ssl_key_password: None,
+            ssl_endpoint_identification_algorithm: None,
+            schema_registry_basic_auth: None,
+            statistics_interval_ms: None,
             additional_properties: HashMap::new(),
         }
     }
 }
 
+impl KafkaConfig {
+    /// The user and, if given, password in `schema_registry_basic_auth`
+    pub fn schema_registry_credentials(&self) -> Option<(&str, Option<&str>)> {
+        let auth = self.schema_registry_basic_auth.as_deref()?;
+        Some(match auth.split_once(':') {
+            Some((user, password)) => (user, Some(password)),
+            None => (auth, None),
+        })
+    }
+}
+
+/// Value formats per topic; topics without an override use `default_format`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SerdeConfig {
+    pub default_format: SerdeFormat,
+    pub topic_formats: HashMap<String, SerdeFormat>,
+}
+
+/// Producer compression and request sizing
+///
+/// Area status messages carry every seat of an area, so the defaults compress
+/// and allow requests well above the librdkafka 1 MB default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProducerConfig {
+    pub compression_type: String,
+    pub message_max_bytes: u32,
+    pub batch_size: Option<u32>,
+    pub linger_ms: Option<u32>,
+}
+
+impl Default for ProducerConfig {
+    fn default() -> Self {
+        Self {
+            compression_type: "lz4".to_string(),
+            message_max_bytes: 2 * 1024 * 1024,
+            batch_size: None,
+            linger_ms: None,
+        }
+    }
+}
+
+impl ProducerConfig {
+    /// Apply a producer tuning property; returns false if `key` is not one.
+    /// Java client names and `producer.`-prefixed Kafka Streams names are accepted.
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let parse = |value: &str| {
+            value.trim().parse::<u32>().map_err(|e| {
+                TicketMasterError::InvalidArgument(format!("Invalid value for {}: {} ({})", key, value, e))
+            })
+        };
+
+        match key.strip_prefix("producer.").unwrap_or(key) {
+            "compression.type" => self.compression_type = value.trim().to_string(),
+            "message.max.bytes" | "max.request.size" => self.message_max_bytes = parse(value)?,
+            "batch.size" => self.batch_size = Some(parse(value)?),
+            "linger.ms" => self.linger_ms = Some(parse(value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Browser origins allowed to call the HTTP API
+///
+/// No cross-origin request is allowed until origins are listed. `permissive`
+/// allows every origin, method and header, for local development only.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CorsConfig {
+    /// Origins like `https://tickets.example.com`, or `*` for any
+    pub allowed_origins: Vec<String>,
+    /// The methods and headers the API uses when empty
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight answer
+    pub max_age_secs: Option<u64>,
+    pub permissive: bool,
+}
+
+impl CorsConfig {
+    /// Apply a `cors.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let list = |value: &str| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        match key {
+            "cors.allowed.origins" => self.allowed_origins = list(value),
+            "cors.allowed.methods" => self.allowed_methods = list(value),
+            "cors.allowed.headers" => self.allowed_headers = list(value),
+            "cors.allow.credentials" => self.allow_credentials = value.trim() == "true",
+            "cors.max.age.secs" => {
+                self.max_age_secs = Some(value.trim().parse().map_err(|e| {
+                    TicketMasterError::InvalidArgument(format!("Invalid value for {}: {} ({})", key, value, e))
+                })?)
+            }
+            "cors.permissive" => self.permissive = value.trim() == "true",
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Where uploaded event artwork is kept
+///
+/// Artwork goes to S3 when a bucket is set. Otherwise it is written to local
+/// disk and ticket-service serves it under `/artwork`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArtworkConfig {
+    /// Defaults to `<state dir>/artwork`
+    pub local_dir: Option<String>,
+    /// Prefix of artwork URLs, e.g. a CDN in front of the bucket
+    pub public_url: Option<String>,
+    pub s3_bucket: Option<String>,
+    pub s3_region: Option<String>,
+    /// An S3-compatible store such as MinIO, addressed path-style
+    pub s3_endpoint: Option<String>,
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` are used when unset
+    pub s3_access_key: Option<String>,
+    pub s3_secret_key: Option<String>,
+    pub max_bytes: Option<usize>,
+}
+
+const DEFAULT_ARTWORK_MAX_BYTES: usize = 5 * 1024 * 1024;
+
+impl ArtworkConfig {
+    /// Apply an `artwork.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let value = Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        match key {
+            "artwork.local.dir" => self.local_dir = value,
+            "artwork.public.url" => self.public_url = value,
+            "artwork.s3.bucket" => self.s3_bucket = value,
+            "artwork.s3.region" => self.s3_region = value,
+            "artwork.s3.endpoint" => self.s3_endpoint = value,
+            "artwork.s3.access.key" => self.s3_access_key = value,
+            "artwork.s3.secret.key" => self.s3_secret_key = value,
+            "artwork.max.bytes" => {
+                self.max_bytes = value
+                    .map(|value| {
+                        value.parse().map_err(|e| {
+                            TicketMasterError::InvalidArgument(format!("Invalid value for {}: {} ({})", key, value, e))
+                        })
+                    })
+                    .transpose()?
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Largest image accepted for upload
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes.unwrap_or(DEFAULT_ARTWORK_MAX_BYTES)
+    }
+}
+
+/// Area statuses event-service loads into memory before it reports ready
+///
+/// For every partition assigned, the listed areas are loaded first, then the
+/// most recently updated ones, so an on-sale start doesn't hit RocksDB cold.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupConfig {
+    /// `event#area` keys, e.g. of the areas going on sale next
+    pub areas: Vec<String>,
+    /// Most recently updated areas loaded per partition
+    pub recent_areas: usize,
+}
+
+const DEFAULT_WARMUP_RECENT_AREAS: usize = 100;
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            areas: Vec::new(),
+            recent_areas: DEFAULT_WARMUP_RECENT_AREAS,
+        }
+    }
+}
+
+impl WarmupConfig {
+    /// Apply a `warmup.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "warmup.areas" => {
+                self.areas = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|area| !area.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "warmup.recent.areas" => {
+                self.recent_areas = value.trim().parse().map_err(|e| {
+                    TicketMasterError::InvalidArgument(format!("Invalid value for {}: {} ({})", key, value, e))
+                })?
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// How event-service writes seat changes to its area status stores
+///
+/// With event sourcing, each change is appended as a seat event and the full
+/// area is only rewritten as a snapshot every `snapshot_interval` changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSourcingConfig {
+    pub enabled: bool,
+    /// Seat events between two snapshots of an area
+    pub snapshot_interval: u64,
+}
+
+const DEFAULT_SNAPSHOT_INTERVAL: u64 = 100;
+
+impl Default for EventSourcingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+        }
+    }
+}
+
+impl EventSourcingConfig {
+    /// Apply an `area.status.` event sourcing property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            TicketMasterError::InvalidArgument(format!("Invalid value for {}: {} ({})", key, value, e))
+        };
+        match key {
+            "area.status.event.sourcing.enabled" => self.enabled = value.trim().parse().map_err(|e| invalid(&e))?,
+            "area.status.snapshot.interval" => {
+                self.snapshot_interval = value.trim().parse().map_err(|e| invalid(&e))?;
+                if self.snapshot_interval == 0 {
+                    return Err(invalid(&"must be at least 1"));
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Overrides for one consumer of a service that reads with several
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsumerConfig {
+    /// Consumer group, namespaced like topics; the service's own group when unset
+    pub group_id: Option<String>,
+    /// The service's `auto.offset.reset` when unset
+    pub auto_offset_reset: Option<String>,
+    /// Messages of this consumer processed concurrently
+    pub parallelism: Option<usize>,
+}
+
+/// Consumer group settings of the service, from `group.id`,
+/// `group.instance.id` and `auto.offset.reset`, and per-consumer overrides
+/// by consumer name, set with `consumers.<name>.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsumersConfig {
+    /// Namespaced like topics; the application id when unset
+    pub group_id: Option<String>,
+    /// Static group membership: a consumer restarted with the same id within
+    /// the session timeout gets its partitions back without a rebalance
+    pub group_instance_id: Option<String>,
+    /// `earliest`, `latest` or `error`; `earliest` when unset
+    pub auto_offset_reset: Option<String>,
+    pub consumers: HashMap<String, ConsumerConfig>,
+}
+
+impl ConsumersConfig {
+    /// Apply a consumer group or `consumers.` property; returns false if `key` is neither
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "group.id" => self.group_id = Some(value.trim().to_string()),
+            "group.instance.id" => self.group_instance_id = Some(value.trim().to_string()).filter(|id| !id.is_empty()),
+            "auto.offset.reset" => self.auto_offset_reset = Some(parse_offset_reset(key, value)?),
+            _ => return self.apply_consumer_property(key, value),
+        }
+        Ok(true)
+    }
+
+    fn apply_consumer_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let Some((name, setting)) = key.strip_prefix("consumers.").and_then(|rest| rest.split_once('.')) else {
+            return Ok(false);
+        };
+        let consumer = self.consumers.entry(name.to_string()).or_default();
+        match setting {
+            "group.id" => consumer.group_id = Some(value.trim().to_string()),
+            "auto.offset.reset" => consumer.auto_offset_reset = Some(parse_offset_reset(key, value)?),
+            "parallelism" => {
+                consumer.parallelism = Some(value.trim().parse().map_err(|e| {
+                    TicketMasterError::InvalidArgument(format!("Invalid value for {}: {} ({})", key, value, e))
+                })?)
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn get(&self, name: &str) -> ConsumerConfig {
+        self.consumers.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn auto_offset_reset(&self) -> &str {
+        self.auto_offset_reset.as_deref().unwrap_or(DEFAULT_AUTO_OFFSET_RESET)
+    }
+}
+
+// The librdkafka name of an offset reset policy; Java's `none` is `error`
+fn parse_offset_reset(key: &str, value: &str) -> Result<String> {
+    let policy = match value.trim().to_lowercase().as_str() {
+        "earliest" | "smallest" | "beginning" => "earliest",
+        "latest" | "largest" | "end" => "latest",
+        "error" | "none" => "error",
+        _ => {
+            return Err(TicketMasterError::InvalidArgument(format!(
+                "Invalid value for {}: {} (expected earliest, latest or none)",
+                key, value
+            )))
+        }
+    };
+    Ok(policy.to_string())
+}
+
+fn parse_property<T>(key: &str, value: &str) -> Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| TicketMasterError::InvalidArgument(format!("Invalid value for {}: {} ({})", key, value, e)))
+}
+
+/// Requests per second one ticket-service instance answers, beyond which it answers 429
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    pub requests_per_second: u32,
+    /// Requests allowed at once after a quiet spell; `requests_per_second` when unset
+    pub burst: Option<u32>,
+}
+
+/// Settings of ticket-service only, from `ticket.service.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TicketServiceConfig {
+    /// Port of the HTTP API; `--port` overrides it
+    pub port: Option<u16>,
+    pub cors: CorsConfig,
+    /// No limit when unset
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Reject reservations of users user-service hasn't registered
+    #[serde(default)]
+    pub users_required: bool,
+}
+
+impl TicketServiceConfig {
+    /// Apply a `ticket.service.` property, or a `cors.` one; returns false if `key` is neither
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let Some(setting) = key.strip_prefix("ticket.service.") else {
+            return self.cors.apply_property(key, value);
+        };
+        match setting {
+            "port" => self.port = Some(parse_property(key, value)?),
+            "users.required" => self.users_required = parse_property(key, value)?,
+            "rate.limit.requests.per.second" => {
+                let requests_per_second = parse_property(key, value)?;
+                match &mut self.rate_limit {
+                    Some(rate_limit) => rate_limit.requests_per_second = requests_per_second,
+                    None => self.rate_limit = Some(RateLimitConfig { requests_per_second, burst: None }),
+                }
+            }
+            "rate.limit.burst" => {
+                let burst = Some(parse_property(key, value)?);
+                match &mut self.rate_limit {
+                    Some(rate_limit) => rate_limit.burst = burst,
+                    // The rate must follow, which parsing checks
+                    None => self.rate_limit = Some(RateLimitConfig { requests_per_second: 0, burst }),
+                }
+            }
+            _ => return self.cors.apply_property(setting, value),
+        }
+        Ok(true)
+    }
+}
+
+/// Settings of event-service only, from `event.service.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventServiceConfig {
+    /// Strategies for random and continuous random reservations in areas
+    /// without one of their own, set with `strategy.default.<type>`
+    pub strategy_defaults: HashMap<ReservationType, String>,
+    /// Tokio worker threads; one per CPU when unset
+    pub worker_threads: Option<usize>,
+}
+
+impl EventServiceConfig {
+    /// Apply an `event.service.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let Some(setting) = key.strip_prefix("event.service.") else {
+            return Ok(false);
+        };
+        if let Some(reservation_type) = setting.strip_prefix("strategy.default.") {
+            let reservation_type = match reservation_type {
+                "random" => ReservationType::Random,
+                "continuous_random" => ReservationType::ContinuousRandom,
+                _ => {
+                    return Err(TicketMasterError::InvalidArgument(format!(
+                        "Invalid property {}: strategy defaults are for random and continuous_random reservations",
+                        key
+                    )))
+                }
+            };
+            self.strategy_defaults.insert(reservation_type, value.trim().to_string());
+            return Ok(true);
+        }
+        match setting {
+            "worker.threads" => self.worker_threads = Some(parse_property(key, value)?).filter(|threads| *threads > 0),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Settings of reservation-service only, from `reservation.service.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReservationServiceConfig {
+    /// How long seats of a reserved reservation are held for payment before
+    /// it is cancelled and they are released; held until paid when unset
+    pub hold_ttl_ms: Option<u64>,
+}
+
+impl ReservationServiceConfig {
+    /// Apply a `reservation.service.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "reservation.service.hold.ttl.ms" => self.hold_ttl_ms = Some(parse_property(key, value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn hold_ttl(&self) -> Option<Duration> {
+        self.hold_ttl_ms.map(Duration::from_millis)
+    }
+}
+
+/// Settings of user-service only, from `user.service.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserServiceConfig {
+    /// Port of the HTTP API; `--port` overrides it
+    pub port: Option<u16>,
+}
+
+impl UserServiceConfig {
+    /// Apply a `user.service.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "user.service.port" => self.port = Some(parse_property(key, value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Settings of analytics-service only, from `analytics.service.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnalyticsServiceConfig {
+    /// Port of the reporting API; `--port` overrides it
+    pub port: Option<u16>,
+}
+
+impl AnalyticsServiceConfig {
+    /// Apply an `analytics.service.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "analytics.service.port" => self.port = Some(parse_property(key, value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Settings of reconciliation-service only, from `reconciliation.service.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReconciliationServiceConfig {
+    /// Port of the report, metrics and health endpoints; `--port` overrides it
+    pub port: Option<u16>,
+    pub interval_ms: Option<u64>,
+    /// Have event-service re-emit areas whose available seat counter is off
+    pub auto_repair: bool,
+}
+
+const DEFAULT_RECONCILIATION_INTERVAL_MS: u64 = 60_000;
+
+impl ReconciliationServiceConfig {
+    /// Apply a `reconciliation.service.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "reconciliation.service.port" => self.port = Some(parse_property(key, value)?),
+            "reconciliation.service.interval.ms" => self.interval_ms = Some(parse_property(key, value)?),
+            "reconciliation.service.auto.repair" => self.auto_repair = parse_property(key, value)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.unwrap_or(DEFAULT_RECONCILIATION_INTERVAL_MS).max(1))
+    }
+}
+
+/// Settings of export-service only, from `export.` properties
+///
+/// Snapshots go to S3 when a bucket is set. Otherwise they are written
+/// under a local directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportConfig {
+    /// Port of the metrics and health endpoints; `--port` overrides it
+    pub port: Option<u16>,
+    pub interval_ms: Option<u64>,
+    pub format: ExportFormat,
+    /// Prefix of every exported key, e.g. `warehouse/ticket-master`
+    pub prefix: Option<String>,
+    /// Defaults to `<state dir>/export`
+    pub local_dir: Option<String>,
+    pub s3_bucket: Option<String>,
+    pub s3_region: Option<String>,
+    /// An S3-compatible store such as MinIO, addressed path-style
+    pub s3_endpoint: Option<String>,
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` are used when unset
+    pub s3_access_key: Option<String>,
+    pub s3_secret_key: Option<String>,
+}
+
+const DEFAULT_EXPORT_INTERVAL_MS: u64 = 60 * 60 * 1000;
+
+impl ExportConfig {
+    /// Apply an `export.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let text = || Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        match key {
+            "export.service.port" => self.port = Some(parse_property(key, value)?),
+            "export.interval.ms" => self.interval_ms = Some(parse_property(key, value)?),
+            "export.format" => self.format = value.parse()?,
+            "export.prefix" => self.prefix = text(),
+            "export.local.dir" => self.local_dir = text(),
+            "export.s3.bucket" => self.s3_bucket = text(),
+            "export.s3.region" => self.s3_region = text(),
+            "export.s3.endpoint" => self.s3_endpoint = text(),
+            "export.s3.access.key" => self.s3_access_key = text(),
+            "export.s3.secret.key" => self.s3_secret_key = text(),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// How often a snapshot is taken; hourly by default
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.unwrap_or(DEFAULT_EXPORT_INTERVAL_MS).max(1))
+    }
+}
+
+/// Settings of sink-service only, from `sink.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SinkConfig {
+    /// Port of the metrics and health endpoints; `--port` overrides it
+    pub port: Option<u16>,
+    /// e.g. `postgres://sink:<password>@postgres:5432/ticket_master`
+    pub postgres_url: Option<String>,
+    /// Table reservations are mirrored into; `reservations` when unset
+    pub table: Option<String>,
+    pub max_connections: Option<u32>,
+}
+
+const DEFAULT_SINK_TABLE: &str = "reservations";
+const DEFAULT_SINK_MAX_CONNECTIONS: u32 = 5;
+
+impl SinkConfig {
+    /// Apply a `sink.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let text = || Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        match key {
+            "sink.service.port" => self.port = Some(parse_property(key, value)?),
+            "sink.postgres.url" => self.postgres_url = text(),
+            "sink.postgres.table" => self.table = text(),
+            "sink.postgres.max.connections" => self.max_connections = Some(parse_property(key, value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn table(&self) -> &str {
+        self.table.as_deref().unwrap_or(DEFAULT_SINK_TABLE)
+    }
+
+    pub fn max_connections(&self) -> u32 {
+        self.max_connections.unwrap_or(DEFAULT_SINK_MAX_CONNECTIONS).max(1)
+    }
+}
+
+/// Settings of gateway-service only, from `gateway.service.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GatewayServiceConfig {
+    /// Port of the customer API; `--port` overrides it
+    pub port: Option<u16>,
+    /// Base URL of ticket-service, e.g. `http://ticket-service:8080`
+    pub ticket_url: Option<String>,
+    /// Base URL of user-service; profiles aren't served without it
+    pub user_url: Option<String>,
+    /// Sent as `x-api-key` to ticket-service when it requires API keys
+    pub ticket_api_key: Option<String>,
+    /// Key of the HMAC signing the bearer tokens of customers
+    pub token_secret: Option<String>,
+    /// Longest the gateway waits for an internal service to answer
+    pub timeout_ms: Option<u64>,
+}
+
+const DEFAULT_GATEWAY_TIMEOUT_MS: u64 = 10_000;
+
+impl GatewayServiceConfig {
+    /// Apply a `gateway.service.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "gateway.service.port" => self.port = Some(parse_property(key, value)?),
+            "gateway.service.ticket.url" => self.ticket_url = Some(value.trim().to_string()),
+            "gateway.service.user.url" => self.user_url = Some(value.trim().to_string()),
+            "gateway.service.ticket.api.key" => self.ticket_api_key = Some(value.to_string()),
+            "gateway.service.token.secret" => self.token_secret = Some(value.to_string()),
+            "gateway.service.timeout.ms" => self.timeout_ms = Some(parse_property(key, value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_GATEWAY_TIMEOUT_MS))
+    }
+}
+
+/// Webhook alerts, from `alert.` properties; none are sent without a webhook URL
+///
+/// ticket-service alerts on consumer lag and open circuit breakers,
+/// reservation-service on the reservation failure rate.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertConfig {
+    /// A Slack or Teams incoming webhook, or the PagerDuty Events API v2 endpoint
+    pub webhook_url: Option<String>,
+    pub webhook_format: Option<WebhookFormat>,
+    /// Integration key of the PagerDuty service; required with the `pagerduty` format
+    pub pagerduty_routing_key: Option<String>,
+    /// Alert when a watched consumer group is more messages than this behind on a topic
+    pub consumer_lag_threshold: Option<i64>,
+    /// Alert when more than this fraction of the reservations decided since the last check failed
+    pub reservation_failure_rate: Option<f64>,
+    /// Reservations that must be decided before a failure rate is judged
+    pub reservation_min_results: Option<u64>,
+    pub check_interval_ms: Option<u64>,
+}
+
+const DEFAULT_ALERT_CHECK_INTERVAL_MS: u64 = 30_000;
+const DEFAULT_RESERVATION_MIN_RESULTS: u64 = 20;
+
+impl AlertConfig {
+    /// Apply an `alert.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "alert.webhook.url" => self.webhook_url = Some(value.trim().to_string()).filter(|url| !url.is_empty()),
+            "alert.webhook.format" => self.webhook_format = Some(value.parse()?),
+            "alert.pagerduty.routing.key" => self.pagerduty_routing_key = Some(value.trim().to_string()),
+            "alert.consumer.lag.threshold" => self.consumer_lag_threshold = Some(parse_property(key, value)?),
+            "alert.reservation.failure.rate" => {
+                let rate: f64 = parse_property(key, value)?;
+                if !(0.0..=1.0).contains(&rate) {
+                    return Err(TicketMasterError::InvalidArgument(format!(
+                        "Invalid value for {}: {} is not between 0 and 1",
+                        key, value
+                    )));
+                }
+                self.reservation_failure_rate = Some(rate);
+            }
+            "alert.reservation.min.results" => self.reservation_min_results = Some(parse_property(key, value)?),
+            "alert.check.interval.ms" => self.check_interval_ms = Some(parse_property(key, value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn check_interval(&self) -> Duration {
+        Duration::from_millis(self.check_interval_ms.unwrap_or(DEFAULT_ALERT_CHECK_INTERVAL_MS).max(1))
+    }
+
+    pub fn reservation_min_results(&self) -> u64 {
+        self.reservation_min_results.unwrap_or(DEFAULT_RESERVATION_MIN_RESULTS)
+    }
+}
+
+/// Overrides of one retry policy; unset fields keep the policy's defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetryPolicyConfig {
+    pub max_attempts: Option<u32>,
+    pub initial_delay_ms: Option<u64>,
+    pub max_delay_ms: Option<u64>,
+}
+
+impl RetryPolicyConfig {
+    fn apply(&mut self, prefix: &str, key: &str, value: &str) -> Result<bool> {
+        let Some(field) = key.strip_prefix(prefix) else {
+            return Ok(false);
+        };
+        match field {
+            "max.attempts" => {
+                let max_attempts: u32 = parse_property(key, value)?;
+                if max_attempts == 0 {
+                    return Err(TicketMasterError::InvalidArgument(format!("{} must be at least 1", key)));
+                }
+                self.max_attempts = Some(max_attempts);
+            }
+            "initial.delay.ms" => self.initial_delay_ms = Some(parse_property(key, value)?),
+            "max.delay.ms" => self.max_delay_ms = Some(parse_property(key, value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn over(&self, mut defaults: RetryConfig) -> RetryConfig {
+        if let Some(max_attempts) = self.max_attempts {
+            defaults.max_attempts = max_attempts;
+        }
+        if let Some(initial_delay_ms) = self.initial_delay_ms {
+            defaults.initial_delay = Duration::from_millis(initial_delay_ms);
+        }
+        if let Some(max_delay_ms) = self.max_delay_ms {
+            defaults.max_delay = Duration::from_millis(max_delay_ms);
+        }
+        defaults
+    }
+}
+
+/// How event-service and reservation-service retry messages that failed on
+/// a transient error, from `retry.kafka.send.` and `retry.state.store.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetryPoliciesConfig {
+    #[serde(default)]
+    pub kafka_send: RetryPolicyConfig,
+    #[serde(default)]
+    pub state_store: RetryPolicyConfig,
+}
+
+impl RetryPoliciesConfig {
+    /// Apply a `retry.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        Ok(self.kafka_send.apply("retry.kafka.send.", key, value)?
+            || self.state_store.apply("retry.state.store.", key, value)?)
+    }
+
+    pub fn policies(&self) -> RetryPolicies {
+        RetryPolicies {
+            kafka_send: self.kafka_send.over(RetryConfig::kafka_producer()),
+            state_store: self.state_store.over(RetryConfig::state_store()),
+        }
+    }
+}
+
+/// Restarts of the event-service and reservation-service processing loops,
+/// from `supervisor.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SupervisorConfig {
+    pub max_restarts: Option<u32>,
+    pub initial_backoff_ms: Option<u64>,
+    pub max_backoff_ms: Option<u64>,
+    /// A loop running this long without crashing starts its restart count over
+    pub stable_after_ms: Option<u64>,
+}
+
+impl SupervisorConfig {
+    /// Apply a `supervisor.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "supervisor.max.restarts" => self.max_restarts = Some(parse_property(key, value)?),
+            "supervisor.initial.backoff.ms" => self.initial_backoff_ms = Some(parse_property(key, value)?),
+            "supervisor.max.backoff.ms" => self.max_backoff_ms = Some(parse_property(key, value)?),
+            "supervisor.stable.after.ms" => self.stable_after_ms = Some(parse_property(key, value)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn policy(&self) -> RestartPolicy {
+        let defaults = RestartPolicy::default();
+        RestartPolicy {
+            max_restarts: self.max_restarts.unwrap_or(defaults.max_restarts),
+            initial_backoff: self.initial_backoff_ms.map_or(defaults.initial_backoff, Duration::from_millis),
+            max_backoff: self.max_backoff_ms.map_or(defaults.max_backoff, Duration::from_millis),
+            stable_after: self.stable_after_ms.map_or(defaults.stable_after, Duration::from_millis),
+        }
+    }
+}
+
+/// Instance heartbeats on `state.service.heartbeat`, from `heartbeat.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HeartbeatConfig {
+    pub interval_ms: Option<u64>,
+    /// Names the instance in the cluster overview
+    pub instance_id: Option<String>,
+}
+
+impl HeartbeatConfig {
+    /// Apply a `heartbeat.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "heartbeat.interval.ms" => {
+                let interval_ms: u64 = parse_property(key, value)?;
+                if interval_ms == 0 {
+                    return Err(TicketMasterError::InvalidArgument(format!("{} must be at least 1", key)));
+                }
+                self.interval_ms = Some(interval_ms);
+            }
+            "heartbeat.instance.id" => self.instance_id = Some(value.trim().to_string()).filter(|id| !id.is_empty()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.unwrap_or(DEFAULT_HEARTBEAT_INTERVAL_MS))
+    }
+}
+
+/// notification-service channels and templates, from `notification.` properties
+///
+/// Every configured channel gets every notification.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    pub webhook_url: Option<String>,
+    /// Takes `{"user_id", "text"}` and texts the user
+    pub sms_gateway_url: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_security: Option<SmtpSecurity>,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub email_from: Option<String>,
+    /// Address emails are sent to, with `{user_id}` replaced; the user id itself when unset
+    pub email_to: Option<String>,
+    /// From `notification.template.<outcome>.subject` and `.body`
+    pub templates: BTreeMap<NotificationOutcome, NotificationTemplate>,
+}
+
+impl NotificationConfig {
+    /// Apply a `notification.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let text = || Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        match key {
+            "notification.webhook.url" => self.webhook_url = text(),
+            "notification.sms.gateway.url" => self.sms_gateway_url = text(),
+            "notification.smtp.host" => self.smtp_host = text(),
+            "notification.smtp.port" => self.smtp_port = Some(parse_property(key, value)?),
+            "notification.smtp.security" => self.smtp_security = Some(value.parse()?),
+            "notification.smtp.username" => self.smtp_username = text(),
+            "notification.smtp.password" => self.smtp_password = Some(value.to_string()),
+            "notification.email.from" => self.email_from = text(),
+            "notification.email.to" => self.email_to = text(),
+            _ => {
+                let Some(template) = key.strip_prefix("notification.template.") else {
+                    return Ok(false);
+                };
+                let (outcome, part) = template.rsplit_once('.').unwrap_or((template, ""));
+                let outcome: NotificationOutcome = outcome.parse()?;
+                let template = self.templates.entry(outcome).or_insert_with(|| NotificationTemplate::default_for(outcome));
+                match part {
+                    "subject" => template.subject = value.to_string(),
+                    "body" => template.body = value.to_string(),
+                    _ => return Ok(false),
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn templates(&self) -> NotificationTemplates {
+        self.templates
+            .iter()
+            .fold(NotificationTemplates::default(), |templates, (outcome, template)| {
+                templates.with_template(*outcome, template.clone())
+            })
+    }
+}
+
+const DEFAULT_PAYMENT_CURRENCY: &str = "usd";
+
+/// How payment-service charges and ticket-service prices, from `payment.` properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PaymentConfig {
+    /// `mock` (default) or `http`
+    pub provider: PaymentProviderKind,
+    /// Base URL of the Stripe-like API; required with the `http` provider
+    pub api_url: Option<String>,
+    pub api_key: Option<String>,
+    /// ISO 4217 currency of area prices; `usd` when unset
+    pub currency: Option<String>,
+}
+
+impl PaymentConfig {
+    /// Apply a `payment.` property; returns false if `key` is not one
+    pub fn apply_property(&mut self, key: &str, value: &str) -> Result<bool> {
+        let text = || Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        match key {
+            "payment.provider" => self.provider = value.parse()?,
+            "payment.api.url" => self.api_url = text(),
+            "payment.api.key" => self.api_key = Some(value.to_string()),
+            "payment.currency" => self.currency = text().map(|currency| currency.to_ascii_lowercase()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn currency(&self) -> &str {
+        self.currency.as_deref().unwrap_or(DEFAULT_PAYMENT_CURRENCY)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceConfig {
     pub application_id: String,
@@ -35,15 +983,226 @@ pub struct ServiceConfig {
     pub kafka: KafkaConfig,
     pub commit_interval_ms: Option<u64>,
     pub processing_guarantee: Option<String>,
+    /// Mirror state stores to compacted changelog topics and restore from them
+    #[serde(default)]
+    pub changelog_enabled: bool,
+    #[serde(default)]
+    pub serdes: SerdeConfig,
+    /// Number of messages processed concurrently; messages with the same key stay ordered
+    #[serde(default)]
+    pub processing_parallelism: Option<usize>,
+    /// Consumption pauses once this many messages are waiting to be processed
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+    /// How long processed messages are remembered to skip redeliveries
+    #[serde(default)]
+    pub dedup_ttl_ms: Option<u64>,
+    /// Environment prefix applied to every topic, e.g. `staging`
+    #[serde(default)]
+    pub topic_prefix: Option<String>,
+    /// Republish area status updates to WebSocket subscribers
+    #[serde(default)]
+    pub area_status_bridge_enabled: bool,
+    /// Also label state store metrics with the store name
+    #[serde(default)]
+    pub store_metrics_per_store: bool,
+    /// How often RocksDB stores are fully compacted; never when unset
+    #[serde(default)]
+    pub store_compaction_interval_ms: Option<u64>,
+    /// fsync the RocksDB WAL on every state store write
+    #[serde(default)]
+    pub store_sync_writes: bool,
+    /// Values kept in memory by cached state stores
+    #[serde(default)]
+    pub store_cache_capacity: Option<usize>,
+    /// How long soft-deleted values, e.g. cancelled reservations, stay readable
+    #[serde(default)]
+    pub soft_delete_retention_ms: Option<u64>,
+    /// `host:port` this instance serves state queries on, as in Kafka Streams
+    #[serde(default)]
+    pub application_server: Option<String>,
+    /// Read state owned by other instances through their state query endpoints
+    #[serde(default)]
+    pub interactive_queries_enabled: bool,
+    /// Materialize the area status and reservation state topics into local stores
+    #[serde(default)]
+    pub global_tables_enabled: bool,
+    /// Require an API key from callers of the ticket-service API
+    #[serde(default)]
+    pub api_keys_enabled: bool,
+    /// Token for the API key admin endpoints; they are not served without one
+    #[serde(default)]
+    pub admin_token: Option<String>,
+    /// Queue tokens admitted per second for each event; no waiting room when unset
+    #[serde(default)]
+    pub waiting_room_admission_rate: Option<u32>,
+    /// Port the Kafka services serve `/metrics` on; the ticket service serves it with its API
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
+    /// How long reservation-service waits for a seat reservation result before failing the reservation
+    #[serde(default)]
+    pub reservation_timeout_ms: Option<u64>,
+    /// Log filter in `RUST_LOG` syntax, used over `RUST_LOG`
+    #[serde(default)]
+    pub log_filter: Option<String>,
+    #[serde(default)]
+    pub producer: ProducerConfig,
+    #[serde(default)]
+    pub ticket_service: TicketServiceConfig,
+    #[serde(default)]
+    pub event_service: EventServiceConfig,
+    #[serde(default)]
+    pub reservation_service: ReservationServiceConfig,
+    #[serde(default)]
+    pub user_service: UserServiceConfig,
+    #[serde(default)]
+    pub analytics_service: AnalyticsServiceConfig,
+    #[serde(default)]
+    pub gateway_service: GatewayServiceConfig,
+    #[serde(default)]
+    pub reconciliation_service: ReconciliationServiceConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
+    #[serde(default)]
+    pub sink: SinkConfig,
+    #[serde(default)]
+    pub artwork: ArtworkConfig,
+    #[serde(default)]
+    pub warmup: WarmupConfig,
+    #[serde(default)]
+    pub event_sourcing: EventSourcingConfig,
+    #[serde(default)]
+    pub consumers: ConsumersConfig,
+    #[serde(default)]
+    pub alerts: AlertConfig,
+    #[serde(default)]
+    pub retry: RetryPoliciesConfig,
+    #[serde(default)]
+    pub supervisor: SupervisorConfig,
+    #[serde(default)]
+    pub heartbeat: HeartbeatConfig,
+    #[serde(default)]
+    pub notification: NotificationConfig,
+    #[serde(default)]
+    pub payment: PaymentConfig,
 }
 
+const DEFAULT_COMMIT_INTERVAL_MS: u64 = 1000;
+const DEFAULT_AUTO_OFFSET_RESET: &str = "earliest";
+const DEFAULT_MAX_IN_FLIGHT: usize = 1000;
+const DEFAULT_DEDUP_TTL_MS: u64 = 60 * 60 * 1000;
+const DEFAULT_STORE_CACHE_CAPACITY: usize = 10_000;
+const DEFAULT_SOFT_DELETE_RETENTION_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+const DEFAULT_RESERVATION_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_STATISTICS_INTERVAL_MS: u64 = 30_000;
+const DEFAULT_HEARTBEAT_INTERVAL_MS: u64 = 10_000;
+
 impl ServiceConfig {
+    /// Config from environment variables alone, e.g. `BOOTSTRAP_SERVERS` and `STATE_DIR`; see `ConfigLoader`
+    pub fn from_env(application_id: &str) -> Result<Self> {
+        crate::ConfigLoader::new(application_id).with_env().load()
+    }
+
+    /// How often processed offsets (and standby checkpoints) are committed
+    pub fn commit_interval(&self) -> Duration {
+        Duration::from_millis(self.commit_interval_ms.unwrap_or(DEFAULT_COMMIT_INTERVAL_MS))
+    }
+
+    /// In-flight message limit used for consumer backpressure
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.unwrap_or(DEFAULT_MAX_IN_FLIGHT)
+    }
+
+    pub fn dedup_ttl(&self) -> Duration {
+        Duration::from_millis(self.dedup_ttl_ms.unwrap_or(DEFAULT_DEDUP_TTL_MS))
+    }
+
+    pub fn store_cache_capacity(&self) -> usize {
+        self.store_cache_capacity.unwrap_or(DEFAULT_STORE_CACHE_CAPACITY)
+    }
+
+    pub fn soft_delete_retention(&self) -> Duration {
+        Duration::from_millis(self.soft_delete_retention_ms.unwrap_or(DEFAULT_SOFT_DELETE_RETENTION_MS))
+    }
+
+    pub fn reservation_timeout(&self) -> Duration {
+        Duration::from_millis(self.reservation_timeout_ms.unwrap_or(DEFAULT_RESERVATION_TIMEOUT_MS))
+    }
+
+    pub fn store_compaction_interval(&self) -> Option<Duration> {
+        self.store_compaction_interval_ms.map(Duration::from_millis)
+    }
+
+    /// The service's consumer group, namespaced like topics
+    pub fn group_id(&self) -> String {
+        self.topic_resolver()
+            .resolve(self.consumers.group_id.as_deref().unwrap_or(&self.application_id))
+            .into_owned()
+    }
+
+    /// Kafka config of the consumer `name`, in its own group if it has one
+    ///
+    /// With static membership the consumer's name is added to the instance
+    /// id, as consumers of one service may share a group.
+    pub fn consumer_kafka_config(&self, name: &str) -> rdkafka::ClientConfig {
+        let mut config = self.to_kafka_config();
+        let consumer = self.consumers.get(name);
+        if let Some(group_id) = &consumer.group_id {
+            config.set("group.id", self.topic_resolver().resolve(group_id));
+        }
+        if let Some(auto_offset_reset) = &consumer.auto_offset_reset {
+            config.set("auto.offset.reset", auto_offset_reset);
+        }
+        if let Some(group_instance_id) = &self.consumers.group_instance_id {
+            config.set("group.instance.id", format!("{}-{}", group_instance_id, name));
+        }
+        config
+    }
+
+    /// Concurrency of the consumer `name`; `processing.parallelism`, or the number of CPUs, when unset
+    pub fn consumer_parallelism(&self, name: &str) -> usize {
+        self.consumers
+            .get(name)
+            .parallelism
+            .or(self.processing_parallelism)
+            .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+            .max(1)
+    }
+
+    /// `heartbeat.instance.id`, else the static group member id or state
+    /// query server, which already tell instances apart
+    pub fn instance_id(&self) -> Option<String> {
+        self.heartbeat
+            .instance_id
+            .clone()
+            .or_else(|| self.consumers.group_instance_id.clone())
+            .or_else(|| self.application_server.clone())
+    }
+
+    pub fn topic_resolver(&self) -> TopicResolver {
+        TopicResolver::new(self.topic_prefix.as_deref())
+    }
+
     pub fn to_kafka_config(&self) -> rdkafka::ClientConfig {
         let mut config = rdkafka::ClientConfig::new();
         
         config.set("bootstrap.servers", &self.kafka.bootstrap_servers);
-        config.set("group.id", &self.application_id);
-        config.set("auto.offset.reset", "earliest");
+        config.set("group.id", self.group_id());
+        config.set("auto.offset.reset", self.consumers.auto_offset_reset());
+        if let Some(group_instance_id) = &self.consumers.group_instance_id {
+            config.set("group.instance.id", group_instance_id);
+        }
+        // Offsets are committed by the services once messages are processed
+        config.set("enable.auto.commit", "false");
+
+        config.set("compression.type", &self.producer.compression_type);
+        config.set("message.max.bytes", self.producer.message_max_bytes.to_string());
+        if let Some(batch_size) = self.producer.batch_size {
+            config.set("batch.size", batch_size.to_string());
+        }
+        if let Some(linger_ms) = self.producer.linger_ms {
+            config.set("linger.ms", linger_ms.to_string());
+        }
         
         if let Some(security_protocol) = &self.kafka.security_protocol {
             config.set("security.protocol", security_protocol);
@@ -64,6 +1223,25 @@ impl ServiceConfig {
         if let Some(ssl_ca_location) = &self.kafka.ssl_ca_location {
             config.set("ssl.ca.location", ssl_ca_location);
         }
+
+        if let Some(ssl_certificate_location) = &self.kafka.ssl_certificate_location {
+            config.set("ssl.certificate.location", ssl_certificate_location);
+        }
+
+        if let Some(ssl_key_location) = &self.kafka.ssl_key_location {
+            config.set("ssl.key.location", ssl_key_location);
+        }
+
+        if let Some(ssl_key_password) = &self.kafka.ssl_key_password {
+            config.set("ssl.key.password", ssl_key_password);
+        }
+
+        if let Some(algorithm) = &self.kafka.ssl_endpoint_identification_algorithm {
+            config.set("ssl.endpoint.identification.algorithm", algorithm);
+        }
+
+        let statistics_interval_ms = self.kafka.statistics_interval_ms.unwrap_or(DEFAULT_STATISTICS_INTERVAL_MS);
+        config.set(STATISTICS_INTERVAL_PROPERTY, statistics_interval_ms.to_string());
         
         // Add additional properties
         for (key, value) in &self.kafka.additional_properties {
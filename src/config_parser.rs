@@ -1,53 +1,389 @@
-use crate::{Result, TicketMasterError, ServiceConfig, KafkaConfig};
+use crate::{read_secret_file, Result, TicketMasterError, ServiceConfig, KafkaConfig, SerdeConfig, ProducerConfig, TicketServiceConfig, EventServiceConfig, ReservationServiceConfig, UserServiceConfig, AnalyticsServiceConfig, GatewayServiceConfig, ReconciliationServiceConfig, ExportConfig, SinkConfig, ArtworkConfig, WarmupConfig, EventSourcingConfig, ConsumersConfig, AlertConfig, RetryPoliciesConfig, SupervisorConfig, HeartbeatConfig, NotificationConfig, PaymentConfig};
+use config::{FileFormat, Value, ValueKind};
 use java_properties::PropertiesIter;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_STATE_DIR: &str = "/tmp/kafka-streams";
+/// The profile services run with when none is given
+pub const DEFAULT_PROFILE: &str = "dev";
+// Conveniences for a local cluster that shouldn't reach shared environments
+const DEV_DEFAULTS: [(&str, &str); 2] = [("cors.permissive", "true"), ("allow.auto.create.topics", "true")];
+const DEFAULTS: [(&str, &str); 1] = [("allow.auto.create.topics", "false")];
+
+/// Properties `profile` starts from, below the config file
+pub fn profile_defaults(profile: &str) -> &'static [(&'static str, &'static str)] {
+    match profile {
+        DEFAULT_PROFILE => &DEV_DEFAULTS,
+        _ => &DEFAULTS,
+    }
+}
 
 /// Parse Java properties file into ServiceConfig
+///
+/// `.yaml`, `.yml` and `.toml` files are read as in `ConfigLoader`.
 pub fn parse_properties_file<P: AsRef<Path>>(path: P, application_id: &str) -> Result<ServiceConfig> {
-    let file = File::open(&path).map_err(|e| {
-        TicketMasterError::InvalidArgument(format!("Failed to open config file {:?}: {}", path.as_ref(), e))
+    parse_properties(read_config_file(path.as_ref(), application_id)?, application_id)
+}
+
+/// Builds a `ServiceConfig` from layers of properties, each overriding the
+/// ones before: built-in defaults, the defaults of a profile, a properties
+/// file, environment variables, then overrides such as command line flags
+///
+/// An environment variable sets the property named like it in lowercase,
+/// with `_` read as `.`, e.g. `BOOTSTRAP_SERVERS` or `SASL_PASSWORD`. Only
+/// variables naming a property the config knows are used, so unrelated
+/// ones aren't passed on to Kafka.
+///
+/// The file is read as YAML or TOML when its extension says so, and as Java
+/// properties otherwise. Nested sections are joined into dotted property
+/// names, so `bootstrap: { servers: ... }` sets `bootstrap.servers`. Two
+/// sections are special: `kafka` holds properties whose names are used
+/// without the section name, and `services.<application id>` holds the
+/// properties of one service, applied over the shared ones. Sections of
+/// other services are ignored. Lists are joined with commas.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    application_id: String,
+    defaults: Vec<(String, String)>,
+    file: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    overrides: Vec<(String, String)>,
+}
+
+impl ConfigLoader {
+    pub fn new(application_id: &str) -> Self {
+        Self {
+            application_id: application_id.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Start from the defaults of `profile`, see `profile_defaults`
+    pub fn with_profile(mut self, profile: &str) -> Self {
+        self.defaults = profile_defaults(profile)
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        self
+    }
+
+    pub fn with_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Read the environment of this process
+    pub fn with_env(self) -> Self {
+        self.with_env_vars(std::env::vars())
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn with_env_vars<I: IntoIterator<Item = (String, String)>>(mut self, vars: I) -> Self {
+        self.env.extend(vars);
+        self
+    }
+
+    /// Set `key` over every other layer
+    pub fn with_override(mut self, key: &str, value: &str) -> Self {
+        self.overrides.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn load(self) -> Result<ServiceConfig> {
+        let mut properties: HashMap<String, String> = self.defaults.into_iter().collect();
+        if let Some(path) = &self.file {
+            properties.extend(read_config_file(path, &self.application_id)?);
+        }
+        for (name, value) in self.env {
+            let key = env_property(&name);
+            if is_service_property(&key, &value) {
+                properties.insert(key, value);
+            }
+        }
+        properties.extend(self.overrides);
+        parse_properties(properties, &self.application_id)
+    }
+}
+
+// `BOOTSTRAP_SERVERS` names `bootstrap.servers`
+fn env_property(name: &str) -> String {
+    name.to_lowercase().replace('_', ".")
+}
+
+// Whether `key` is parsed into the config rather than passed through to Kafka;
+// a bad value still counts, so the error is reported when the layers are parsed
+fn is_service_property(key: &str, value: &str) -> bool {
+    parse_properties(HashMap::from([(key.to_string(), value.to_string())]), "")
+        .map(|config| config.kafka.additional_properties.is_empty())
+        .unwrap_or(true)
+}
+
+fn read_config_file(path: &Path, application_id: &str) -> Result<HashMap<String, String>> {
+    let format = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yaml") | Some("yml") => FileFormat::Yaml,
+        Some("toml") => FileFormat::Toml,
+        _ => return read_properties_file(path),
+    };
+    let mut sections = config::Config::builder()
+        .add_source(config::File::from(path).format(format))
+        .build()?
+        .try_deserialize::<config::Map<String, Value>>()?;
+
+    let kafka = sections.remove("kafka");
+    let services = sections.remove("services");
+    let mut properties = HashMap::new();
+    flatten_section(None, Value::from(sections), &mut properties)?;
+    if let Some(kafka) = kafka {
+        flatten_section(None, kafka, &mut properties)?;
+    }
+    if let Some(mut services) = services.map(Value::into_table).transpose()? {
+        if let Some(service) = services.remove(application_id) {
+            flatten_section(None, service, &mut properties)?;
+        }
+    }
+    Ok(properties)
+}
+
+// Adds a property per value under `value`, named by its path of section names
+fn flatten_section(name: Option<&str>, value: Value, properties: &mut HashMap<String, String>) -> Result<()> {
+    match (value.kind, name) {
+        (ValueKind::Table(table), _) => {
+            for (key, value) in table {
+                let key = match name {
+                    Some(name) => format!("{}.{}", name, key),
+                    None => key,
+                };
+                flatten_section(Some(&key), value, properties)?;
+            }
+        }
+        (ValueKind::Nil, _) | (_, None) => {}
+        (ValueKind::Array(values), Some(name)) => {
+            let values = values
+                .into_iter()
+                .map(Value::into_string)
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            properties.insert(name.to_string(), values.join(","));
+        }
+        (kind, Some(name)) => {
+            properties.insert(name.to_string(), Value::new(None, kind).into_string()?);
+        }
+    }
+    Ok(())
+}
+
+fn read_properties_file(path: &Path) -> Result<HashMap<String, String>> {
+    let file = File::open(path).map_err(|e| {
+        TicketMasterError::InvalidArgument(format!("Failed to open config file {:?}: {}", path, e))
     })?;
     
     let reader = BufReader::new(file);
-    let properties: HashMap<String, String> = PropertiesIter::new(reader)
+    PropertiesIter::new(reader)
         .collect::<std::result::Result<HashMap<_, _>, _>>()
-        .map_err(|e| TicketMasterError::InvalidArgument(format!("Failed to parse properties: {}", e)))?;
+        .map_err(|e| TicketMasterError::InvalidArgument(format!("Failed to parse properties: {}", e)))
+}
 
+/// Parse properties, as read from a properties file, into ServiceConfig
+pub fn parse_properties(properties: HashMap<String, String>, application_id: &str) -> Result<ServiceConfig> {
     let mut kafka_config = KafkaConfig::default();
+    let mut state_dir = DEFAULT_STATE_DIR.to_string();
     let mut additional_properties = HashMap::new();
+    let mut secret_files = Vec::new();
     let mut commit_interval_ms = None;
     let mut processing_guarantee = None;
+    let mut changelog_enabled = false;
+    let mut serdes = SerdeConfig::default();
+    let mut processing_parallelism = None;
+    let mut max_in_flight = None;
+    let mut dedup_ttl_ms = None;
+    let mut topic_prefix = None;
+    let mut area_status_bridge_enabled = false;
+    let mut store_metrics_per_store = false;
+    let mut store_compaction_interval_ms = None;
+    let mut store_sync_writes = false;
+    let mut store_cache_capacity = None;
+    let mut soft_delete_retention_ms = None;
+    let mut application_server = None;
+    let mut interactive_queries_enabled = false;
+    let mut global_tables_enabled = false;
+    let mut api_keys_enabled = false;
+    let mut admin_token = None;
+    let mut waiting_room_admission_rate = None;
+    let mut metrics_port = None;
+    let mut reservation_timeout_ms = None;
+    let mut log_filter = None;
+    let mut producer = ProducerConfig::default();
+    let mut ticket_service = TicketServiceConfig::default();
+    let mut event_service = EventServiceConfig::default();
+    let mut reservation_service = ReservationServiceConfig::default();
+    let mut user_service = UserServiceConfig::default();
+    let mut analytics_service = AnalyticsServiceConfig::default();
+    let mut gateway_service = GatewayServiceConfig::default();
+    let mut reconciliation_service = ReconciliationServiceConfig::default();
+    let mut export = ExportConfig::default();
+    let mut sink = SinkConfig::default();
+    let mut artwork = ArtworkConfig::default();
+    let mut warmup = WarmupConfig::default();
+    let mut event_sourcing = EventSourcingConfig::default();
+    let mut consumers = ConsumersConfig::default();
+    let mut alerts = AlertConfig::default();
+    let mut retry = RetryPoliciesConfig::default();
+    let mut supervisor = SupervisorConfig::default();
+    let mut heartbeat = HeartbeatConfig::default();
+    let mut notification = NotificationConfig::default();
+    let mut payment = PaymentConfig::default();
 
     for (key, value) in properties {
         match key.as_str() {
+            "state.dir" => state_dir = value.trim().to_string(),
             "bootstrap.servers" => kafka_config.bootstrap_servers = value,
             "schema.registry.url" => kafka_config.schema_registry_url = Some(value),
             "security.protocol" => kafka_config.security_protocol = Some(value),
             "sasl.mechanism" => kafka_config.sasl_mechanism = Some(value),
             "sasl.username" => kafka_config.sasl_username = Some(value),
             "sasl.password" => kafka_config.sasl_password = Some(value),
+            "schema.registry.basic.auth" => kafka_config.schema_registry_basic_auth = Some(value),
+            "sasl.password.file" | "schema.registry.basic.auth.file" | "ssl.key.password.file" => {
+                secret_files.push((key, value))
+            }
             "ssl.ca.location" => kafka_config.ssl_ca_location = Some(value),
+            "ssl.certificate.location" => kafka_config.ssl_certificate_location = Some(value),
+            "ssl.key.location" => kafka_config.ssl_key_location = Some(value),
+            "ssl.key.password" => kafka_config.ssl_key_password = Some(value),
+            // Java clients turn the check off with an empty algorithm
+            "ssl.endpoint.identification.algorithm" => {
+                kafka_config.ssl_endpoint_identification_algorithm = Some(match value.trim() {
+                    "" => "none".to_string(),
+                    algorithm => algorithm.to_lowercase(),
+                })
+            }
+            "statistics.interval.ms" => kafka_config.statistics_interval_ms = value.trim().parse().ok(),
             "commit.interval.ms" => {
                 commit_interval_ms = Some(value.parse().unwrap_or(20));
             },
             "processing.guarantee" => processing_guarantee = Some(value),
+            "processing.parallelism" => processing_parallelism = value.trim().parse().ok(),
+            "processing.max.in.flight" => max_in_flight = value.trim().parse().ok(),
+            "dedup.ttl.ms" => dedup_ttl_ms = value.trim().parse().ok(),
+            "topic.prefix" => topic_prefix = Some(value),
+            "state.changelog.enabled" => changelog_enabled = value.trim() == "true",
+            "area.status.bridge.enabled" => area_status_bridge_enabled = value.trim() == "true",
+            "state.store.metrics.per.store" => store_metrics_per_store = value.trim() == "true",
+            "state.compaction.interval.ms" => store_compaction_interval_ms = value.trim().parse().ok(),
+            "state.wal.sync" => store_sync_writes = value.trim() == "true",
+            "state.cache.capacity" => store_cache_capacity = value.trim().parse().ok(),
+            "state.soft.delete.retention.ms" => soft_delete_retention_ms = value.trim().parse().ok(),
+            "application.server" => application_server = Some(value.trim().to_string()),
+            "interactive.queries.enabled" => interactive_queries_enabled = value.trim() == "true",
+            "global.tables.enabled" => global_tables_enabled = value.trim() == "true",
+            "auth.api.keys.enabled" => api_keys_enabled = value.trim() == "true",
+            "auth.admin.token" => admin_token = Some(value.trim().to_string()).filter(|token| !token.is_empty()),
+            "waiting.room.admission.rate" => waiting_room_admission_rate = value.trim().parse().ok(),
+            "metrics.port" => metrics_port = value.trim().parse().ok(),
+            "reservation.timeout.ms" => reservation_timeout_ms = value.trim().parse().ok(),
+            "log.filter" => log_filter = Some(value.trim().to_string()).filter(|filter| !filter.is_empty()),
+            "serde.default.format" => serdes.default_format = value.parse()?,
+            _ if key.starts_with("serde.topic.") => {
+                let topic = key.trim_start_matches("serde.topic.").to_string();
+                serdes.topic_formats.insert(topic, value.parse()?);
+            }
             _ => {
-                additional_properties.insert(key, value);
+                if !producer.apply_property(&key, &value)?
+                    && !ticket_service.apply_property(&key, &value)?
+                    && !event_service.apply_property(&key, &value)?
+                    && !reservation_service.apply_property(&key, &value)?
+                    && !user_service.apply_property(&key, &value)?
+                    && !analytics_service.apply_property(&key, &value)?
+                    && !gateway_service.apply_property(&key, &value)?
+                    && !reconciliation_service.apply_property(&key, &value)?
+                    && !export.apply_property(&key, &value)?
+                    && !sink.apply_property(&key, &value)?
+                    && !artwork.apply_property(&key, &value)?
+                    && !warmup.apply_property(&key, &value)?
+                    && !event_sourcing.apply_property(&key, &value)?
+                    && !consumers.apply_property(&key, &value)?
+                    && !alerts.apply_property(&key, &value)?
+                    && !retry.apply_property(&key, &value)?
+                    && !supervisor.apply_property(&key, &value)?
+                    && !heartbeat.apply_property(&key, &value)?
+                    && !notification.apply_property(&key, &value)?
+                    && !payment.apply_property(&key, &value)?
+                {
+                    additional_properties.insert(key, value);
+                }
             }
         }
     }
 
+    if ticket_service.rate_limit.as_ref().is_some_and(|rate_limit| rate_limit.requests_per_second == 0) {
+        return Err(TicketMasterError::InvalidArgument(
+            "ticket.service.rate.limit.requests.per.second must be set and above 0".to_string(),
+        ));
+    }
+
     kafka_config.additional_properties = additional_properties;
+    // A secret read from a file wins over one set in plain text
+    for (key, path) in secret_files {
+        let secret = Some(read_secret_file(path.trim())?);
+        match key.as_str() {
+            "sasl.password.file" => kafka_config.sasl_password = secret,
+            "ssl.key.password.file" => kafka_config.ssl_key_password = secret,
+            _ => kafka_config.schema_registry_basic_auth = secret,
+        }
+    }
 
     Ok(ServiceConfig {
         application_id: application_id.to_string(),
-        state_dir: "/tmp/kafka-streams".to_string(),
+        state_dir,
         kafka: kafka_config,
         commit_interval_ms,
         processing_guarantee,
+        changelog_enabled,
+        serdes,
+        processing_parallelism,
+        max_in_flight,
+        dedup_ttl_ms,
+        topic_prefix,
+        area_status_bridge_enabled,
+        store_metrics_per_store,
+        store_compaction_interval_ms,
+        store_sync_writes,
+        store_cache_capacity,
+        soft_delete_retention_ms,
+        application_server,
+        interactive_queries_enabled,
+        global_tables_enabled,
+        api_keys_enabled,
+        admin_token,
+        waiting_room_admission_rate,
+        metrics_port,
+        reservation_timeout_ms,
+        log_filter,
+        producer,
+        ticket_service,
+        event_service,
+        reservation_service,
+        user_service,
+        analytics_service,
+        gateway_service,
+        reconciliation_service,
+        export,
+        sink,
+        artwork,
+        warmup,
+        event_sourcing,
+        consumers,
+        alerts,
+        retry,
+        supervisor,
+        heartbeat,
+        notification,
+        payment,
     })
 }
 
@@ -64,7 +400,9 @@ pub fn merge_stream_properties<P: AsRef<Path>>(mut config: ServiceConfig, path:
 
     // Merge stream-specific properties
     for (key, value) in properties {
-        config.kafka.additional_properties.insert(key, value);
+        if !config.producer.apply_property(&key, &value)? {
+            config.kafka.additional_properties.insert(key, value);
+        }
     }
 
     Ok(config)
@@ -0,0 +1,142 @@
+use crate::{ConfigLoader, LogFilter, Result, ServiceConfig, TicketMasterError};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+// Quiet time after a change before the file is loaded, as writes come in several events
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Settings the services apply while running when their config file changes
+///
+/// Every other setting is read once, at startup.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuntimeSettings {
+    pub log_filter: Option<String>,
+    /// Only changes the rate of a waiting room enabled at startup
+    pub waiting_room_admission_rate: Option<u32>,
+    pub store_cache_capacity: usize,
+    pub reservation_timeout_ms: u64,
+}
+
+impl RuntimeSettings {
+    pub fn from_config(config: &ServiceConfig) -> Self {
+        Self {
+            log_filter: config.log_filter.clone(),
+            waiting_room_admission_rate: config.waiting_room_admission_rate,
+            store_cache_capacity: config.store_cache_capacity(),
+            reservation_timeout_ms: config.reservation_timeout().as_millis() as u64,
+        }
+    }
+}
+
+/// Reloads a config file whenever it changes and publishes its `RuntimeSettings`
+///
+/// The file's directory is watched rather than the file, so files replaced
+/// by editors or by Kubernetes ConfigMap updates are picked up. A file that
+/// fails to load leaves the settings as they were.
+pub struct ConfigWatcher {
+    settings: watch::Receiver<RuntimeSettings>,
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Watch the file `loader` reads, starting from the settings in `config`
+    pub fn start(loader: ConfigLoader, config: &ServiceConfig) -> Result<Self> {
+        let path = loader
+            .file()
+            .ok_or_else(|| TicketMasterError::InvalidArgument("No config file to watch".to_string()))?
+            .to_path_buf();
+        let file_name = path.file_name().map(|name| name.to_os_string());
+        let (sender, settings) = watch::channel(RuntimeSettings::from_config(config));
+        let (changes, changed) = mpsc::channel();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("Watching the config file failed: {}", e);
+                    return;
+                }
+            };
+            // ConfigMaps are updated by swapping their `..data` directory
+            let touches_file = event.paths.iter().filter_map(|path| path.file_name()).any(|name| {
+                Some(name) == file_name.as_deref() || name.to_string_lossy().starts_with("..")
+            });
+            if touches_file && !matches!(event.kind, EventKind::Access(_)) {
+                let _ = changes.send(());
+            }
+        })
+        .map_err(|e| watch_error(&path, e))?;
+
+        // Ends once the watcher, and with it `changes`, is dropped
+        std::thread::spawn(move || {
+            while changed.recv().is_ok() {
+                while changed.recv_timeout(SETTLE_TIME).is_ok() {}
+                match loader.clone().load() {
+                    Ok(config) => {
+                        let updated = RuntimeSettings::from_config(&config);
+                        sender.send_if_modified(|settings| {
+                            if *settings == updated {
+                                return false;
+                            }
+                            info!("Config file changed, applying {:?}", updated);
+                            *settings = updated;
+                            true
+                        });
+                    }
+                    Err(e) => warn!("Keeping the current settings, the config file failed to load: {}", e),
+                }
+            }
+        });
+
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| watch_error(&path, e))?;
+        Ok(Self {
+            settings,
+            _watcher: watcher,
+        })
+    }
+
+    /// The settings as of the last change
+    pub fn settings(&self) -> RuntimeSettings {
+        self.settings.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<RuntimeSettings> {
+        self.settings.clone()
+    }
+
+    /// Call `apply` with the settings each time they change, until the watcher is dropped
+    pub fn on_change<F>(&self, apply: F) -> JoinHandle<()>
+    where
+        F: Fn(&RuntimeSettings) + Send + 'static,
+    {
+        let mut settings = self.subscribe();
+        tokio::spawn(async move {
+            while settings.changed().await.is_ok() {
+                let current = settings.borrow_and_update().clone();
+                apply(&current);
+            }
+        })
+    }
+
+    /// Keep `log_filter` on the `log.filter` of the watched file
+    pub fn watch_log_filter(&self, log_filter: LogFilter) -> JoinHandle<()> {
+        self.on_change(move |settings| {
+            if let Err(e) = log_filter.set(settings.log_filter.as_deref()) {
+                warn!("Keeping the current log filter: {}", e);
+            }
+        })
+    }
+}
+
+fn watch_error(path: &Path, e: notify::Error) -> TicketMasterError {
+    TicketMasterError::InvalidArgument(format!("Failed to watch config file {:?}: {}", path, e))
+}
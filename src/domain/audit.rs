@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use super::reservation::{ReservationState, ReservationTransition};
+
+/// What an audit record is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEntity {
+    Reservation,
+    Area,
+}
+
+impl AuditEntity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reservation => "reservation",
+            Self::Area => "area",
+        }
+    }
+}
+
+/// One state change of a reservation or area, as published on `state.audit`
+///
+/// `audit_id` is derived from what caused the change, so publishing the same
+/// change again, e.g. after a redelivery, doesn't add a second record.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub audit_id: String,
+    pub entity: AuditEntity,
+    /// Reservation id, or event#area for areas
+    pub entity_id: String,
+    /// Who made the change: a user id, a service or `admin`
+    pub actor: String,
+    pub action: String,
+    pub from: Option<String>,
+    pub to: String,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl AuditRecord {
+    pub fn new(entity: AuditEntity, entity_id: &str, audit_id: &str, actor: &str, action: &str) -> Self {
+        Self {
+            audit_id: audit_id.to_string(),
+            entity,
+            entity_id: entity_id.to_string(),
+            actor: actor.to_string(),
+            action: action.to_string(),
+            from: None,
+            to: String::new(),
+            occurred_at: Utc::now(),
+        }
+    }
+
+    pub fn with_change(mut self, from: Option<String>, to: String) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    /// A reservation created by `user_id` in `state`
+    pub fn reservation_created(reservation_id: &str, user_id: &str, state: &ReservationState) -> Self {
+        Self::new(AuditEntity::Reservation, reservation_id, "Created", user_id, "Created")
+            .with_change(None, format!("{:?}", state))
+    }
+
+    /// Record `transition` as made by `actor`; a reservation reaches each state once at most
+    pub fn from_transition(transition: &ReservationTransition, actor: &str) -> Self {
+        let to = format!("{:?}", transition.to);
+        Self {
+            occurred_at: transition.occurred_at,
+            ..Self::new(AuditEntity::Reservation, &transition.reservation_id, &to, actor, transition.event.name())
+                .with_change(Some(format!("{:?}", transition.from)), to)
+        }
+    }
+
+    /// Key records of one entity share as a prefix, and the key on `state.audit`
+    pub fn entity_key(&self) -> String {
+        audit_entity_key(self.entity, &self.entity_id)
+    }
+}
+
+pub fn audit_entity_key(entity: AuditEntity, entity_id: &str) -> String {
+    format!("{}#{}", entity.as_str(), entity_id)
+}
@@ -1,5 +1,9 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+
+use super::layout::SeatLayout;
+use super::reservation::ReservationErrorCode;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Area {
@@ -7,6 +11,9 @@ pub struct Area {
     pub price: i32,
     pub row_count: i32,
     pub col_count: i32,
+    /// Strategy for reservations that don't name their seats; `random` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,34 +47,99 @@ pub struct AreaStatus {
 
 impl AreaStatus {
     pub fn from_area(event_name: &str, area: &Area) -> Self {
-        let area_id = area.area_id.clone();
-        let row_count = area.row_count;
-        let col_count = area.col_count;
-        let available_seats = row_count * col_count;
-        
-        let mut seats = Vec::new();
-        for i in 0..row_count {
-            let mut row = Vec::new();
-            for j in 0..col_count {
-                row.push(SeatStatus {
-                    row: i,
-                    col: j,
-                    is_available: true,
-                });
+        Self::from_layout(event_name, &SeatLayout::from(area))
+    }
+
+    /// A fresh area with every seat of `layout` available; grid positions
+    /// without a seat are never available
+    pub fn from_layout(event_name: &str, layout: &SeatLayout) -> Self {
+        let mut seats: Vec<Vec<SeatStatus>> = (0..layout.row_count)
+            .map(|row| {
+                (0..layout.col_count)
+                    .map(|col| SeatStatus {
+                        row,
+                        col,
+                        is_available: false,
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut available_seats = 0;
+        for seat in &layout.seats {
+            let status = seats
+                .get_mut(seat.row as usize)
+                .and_then(|row| row.get_mut(seat.col as usize))
+                .filter(|status| !status.is_available);
+            if let Some(status) = status {
+                status.is_available = true;
+                available_seats += 1;
             }
-            seats.push(row);
         }
-        
+
         Self {
             event_id: event_name.to_string(),
-            area_id,
-            price: area.price,
-            row_count,
-            col_count,
+            area_id: layout.area_id.clone(),
+            price: layout.price,
+            row_count: layout.row_count,
+            col_count: layout.col_count,
             available_seats,
             seats,
         }
     }
+
+    /// Fingerprint of the area's contents; changes whenever a seat does
+    pub fn version(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.event_id.hash(&mut hasher);
+        self.area_id.hash(&mut hasher);
+        (self.price, self.row_count, self.col_count, self.available_seats).hash(&mut hasher);
+        for seat in self.seats.iter().flatten() {
+            (seat.row, seat.col, seat.is_available).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Seats whose availability changed since `previous`; `None` when the
+    /// seat layout differs and only a full status will do
+    pub fn delta_since(&self, previous: &AreaStatus) -> Option<AreaStatusDelta> {
+        let same_layout = self.row_count == previous.row_count
+            && self.col_count == previous.col_count
+            && self.seats.len() == previous.seats.len()
+            && self.seats.iter().zip(&previous.seats).all(|(row, prev)| row.len() == prev.len());
+        if !same_layout {
+            return None;
+        }
+
+        let changed = self
+            .seats
+            .iter()
+            .flatten()
+            .zip(previous.seats.iter().flatten())
+            .filter(|(seat, prev)| seat.is_available != prev.is_available)
+            .map(|(seat, _)| seat.clone())
+            .collect();
+        Some(AreaStatusDelta {
+            event_id: self.event_id.clone(),
+            area_id: self.area_id.clone(),
+            available_seats: self.available_seats,
+            changed,
+        })
+    }
+}
+
+/// Changes to an area's seats between two of its statuses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AreaStatusDelta {
+    pub event_id: String,
+    pub area_id: String,
+    pub available_seats: i32,
+    pub changed: Vec<SeatStatus>,
+}
+
+impl AreaStatusDelta {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,6 +151,9 @@ pub struct ReserveSeat {
     pub num_of_seat: i32,
     pub reservation_type: ReservationType,
     pub seats: Vec<Seat>,
+    /// What the party would like from the seats the random strategies pick
+    #[serde(default)]
+    pub preferences: Vec<SeatPreference>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,11 +162,119 @@ pub struct Seat {
     pub col: i32,
 }
 
+/// Dry run of a random or continuous random reservation: the strategy picks
+/// seats as it would for a `ReserveSeat`, but nothing is taken
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckAvailability {
+    pub event_id: String,
+    pub area_id: String,
+    pub num_of_seats: i32,
+    pub reservation_type: ReservationType,
+    #[serde(default)]
+    pub preferences: Vec<SeatPreference>,
+}
+
+impl CheckAvailability {
+    /// The seat request the strategy is run with; it has no reservation id
+    pub fn to_reserve_seat(&self) -> ReserveSeat {
+        ReserveSeat {
+            reservation_id: String::new(),
+            event_id: self.event_id.clone(),
+            area_id: self.area_id.clone(),
+            num_of_seats: self.num_of_seats,
+            num_of_seat: 0,
+            reservation_type: self.reservation_type.clone(),
+            seats: Vec::new(),
+            preferences: self.preferences.clone(),
+        }
+    }
+}
+
+/// Answer to a `CheckAvailability`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Availability {
+    pub event_id: String,
+    pub area_id: String,
+    pub num_of_seats: i32,
+    pub reservation_type: ReservationType,
+    pub available: bool,
+    /// Whether `seats` are side by side in one row
+    pub adjacent: bool,
+    /// Seats a reservation would get now; they are not held for anyone
+    pub seats: Vec<Seat>,
+    pub available_seats: i32,
+    pub error_code: Option<ReservationErrorCode>,
+    pub error_message: Option<String>,
+}
+
+/// Operator command for one area, applied by the event service instance owning it
+///
+/// Sent on `command.event.admin` keyed by event#area, always as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum AreaAdminCommand {
+    /// Make a seat available again, whichever reservation holds it
+    ReleaseSeat { event_id: String, area_id: String, row: i32, col: i32 },
+    /// Publish the area's stored status to the state topic again
+    ReemitAreaStatus { event_id: String, area_id: String },
+    /// Delete the area and its seats, publishing a tombstone for it
+    DeleteArea { event_id: String, area_id: String },
+}
+
+impl AreaAdminCommand {
+    /// Key the command is sent under, the same as the area's reserve-seat commands
+    pub fn key(&self) -> String {
+        match self {
+            Self::ReleaseSeat { event_id, area_id, .. }
+            | Self::ReemitAreaStatus { event_id, area_id }
+            | Self::DeleteArea { event_id, area_id } => crate::event_area_key(event_id, area_id),
+        }
+    }
+}
+
+/// Artwork of an event, as published on `state.event.artwork` keyed by event id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventArtwork {
+    pub event_id: String,
+    /// Where clients load the image from
+    pub url: String,
+    /// Key of the image in object storage
+    pub object_key: String,
+    pub content_type: String,
+    pub size: u64,
+    pub uploaded_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ReservationType {
     SelfPick,
     Random,
     Invalid,
+    /// Adjacent seats in one row, random ones when no row has enough
+    ContinuousRandom,
+}
+
+/// A wish about where randomly picked seats should be
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum SeatPreference {
+    /// At least one seat at the end of a row
+    Aisle,
+    /// Rows as close to the front (row 0) as possible
+    Front,
+    /// All seats side by side, even at the cost of worse ones
+    Together,
+}
+
+impl SeatPreference {
+    /// Parse the lowercase name used by the REST API
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "aisle" => Some(Self::Aisle),
+            "front" => Some(Self::Front),
+            "together" => Some(Self::Together),
+            _ => None,
+        }
+    }
 }
 
 impl Default for ReservationType {
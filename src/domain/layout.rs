@@ -0,0 +1,192 @@
+use super::event::{Area, Seat};
+use crate::{Result, TicketMasterError};
+use std::collections::BTreeSet;
+
+// Rows and columns beyond this are taken to be typos rather than venues
+const MAX_LAYOUT_DIMENSION: i32 = 1000;
+// Enough problems to fix a file in one go without flooding the response
+const MAX_REPORTED_PROBLEMS: usize = 20;
+const SEAT_TYPES: [&str; 3] = ["seat", "standard", "accessible"];
+const GAP_TYPES: [&str; 3] = ["none", "gap", "aisle"];
+
+/// The seats of an area as laid out in the venue
+///
+/// Positions of the `row_count` x `col_count` grid not in `seats` have no
+/// seat, e.g. aisles or the corners of a curved section.
+#[derive(Debug, Clone)]
+pub struct SeatLayout {
+    pub area_id: String,
+    pub price: i32,
+    pub row_count: i32,
+    pub col_count: i32,
+    /// Seats in row-major order
+    pub seats: Vec<Seat>,
+}
+
+impl SeatLayout {
+    /// Read a layout from CSV with a `row,col,type,price` header, in any
+    /// column order, separated by `,` or `;` as spreadsheets export it
+    ///
+    /// Rows and columns count from 0. `type` is `seat`, `standard` or
+    /// `accessible` for a seat and `none`, `gap` or `aisle` for a position
+    /// without one; positions not listed have no seat either. Every seat of an
+    /// area has the same price.
+    pub fn from_csv(area_id: &str, csv: &str) -> Result<Self> {
+        let mut lines = csv
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let (_, header) = lines
+            .next()
+            .ok_or_else(|| TicketMasterError::InvalidArgument("Seat layout is empty".to_string()))?;
+        let delimiter = if header.contains(';') && !header.contains(',') { ';' } else { ',' };
+        let columns = Columns::parse(header, delimiter)?;
+
+        let mut problems = Vec::new();
+        let mut positions = BTreeSet::new();
+        let mut seats = BTreeSet::new();
+        let mut prices = BTreeSet::new();
+        for (number, line) in lines {
+            match columns.read(line, delimiter) {
+                Ok(record) => {
+                    if !positions.insert((record.row, record.col)) {
+                        problems.push(format!("line {}: seat {}-{} is listed twice", number, record.row, record.col));
+                    } else if record.is_seat {
+                        seats.insert((record.row, record.col));
+                        prices.insert(record.price);
+                    }
+                }
+                Err(problem) => problems.push(format!("line {}: {}", number, problem)),
+            }
+        }
+
+        if problems.is_empty() && seats.is_empty() {
+            problems.push("the layout has no seats".to_string());
+        }
+        if prices.len() > 1 {
+            let prices: Vec<String> = prices.iter().map(i32::to_string).collect();
+            problems.push(format!(
+                "seats have several prices ({}); import each price as an area of its own",
+                prices.join(", ")
+            ));
+        }
+        if !problems.is_empty() {
+            let more = problems.len().saturating_sub(MAX_REPORTED_PROBLEMS);
+            problems.truncate(MAX_REPORTED_PROBLEMS);
+            if more > 0 {
+                problems.push(format!("and {} more", more));
+            }
+            return Err(TicketMasterError::InvalidArgument(format!("Invalid seat layout: {}", problems.join("; "))));
+        }
+
+        Ok(Self {
+            area_id: area_id.to_string(),
+            price: prices.into_iter().next().unwrap_or_default(),
+            row_count: positions.iter().map(|(row, _)| row + 1).max().unwrap_or_default(),
+            col_count: positions.iter().map(|(_, col)| col + 1).max().unwrap_or_default(),
+            seats: seats.into_iter().map(|(row, col)| Seat { row, col }).collect(),
+        })
+    }
+
+    pub fn seat_count(&self) -> usize {
+        self.seats.len()
+    }
+}
+
+impl From<&Area> for SeatLayout {
+    /// The full rectangular grid of `area`
+    fn from(area: &Area) -> Self {
+        Self {
+            area_id: area.area_id.clone(),
+            price: area.price,
+            row_count: area.row_count,
+            col_count: area.col_count,
+            seats: (0..area.row_count)
+                .flat_map(|row| (0..area.col_count).map(move |col| Seat { row, col }))
+                .collect(),
+        }
+    }
+}
+
+// Index of each column in the header
+struct Columns {
+    row: usize,
+    col: usize,
+    seat_type: usize,
+    price: usize,
+}
+
+struct Record {
+    row: i32,
+    col: i32,
+    is_seat: bool,
+    price: i32,
+}
+
+impl Columns {
+    fn parse(header: &str, delimiter: char) -> Result<Self> {
+        let names: Vec<String> = header.split(delimiter).map(|name| unquote(name).to_lowercase()).collect();
+        let index = |name: &str| {
+            names.iter().position(|column| column == name).ok_or_else(|| {
+                TicketMasterError::InvalidArgument(format!("Seat layout header has no {} column", name))
+            })
+        };
+        Ok(Self {
+            row: index("row")?,
+            col: index("col")?,
+            seat_type: index("type")?,
+            price: index("price")?,
+        })
+    }
+
+    fn read(&self, line: &str, delimiter: char) -> std::result::Result<Record, String> {
+        let fields: Vec<&str> = line.split(delimiter).map(unquote).collect();
+        let field = |index: usize, name: &str| fields.get(index).copied().ok_or_else(|| format!("{} is missing", name));
+        let position = |index: usize, name: &str| {
+            let value = field(index, name)?;
+            value
+                .parse::<i32>()
+                .ok()
+                .filter(|value| (0..MAX_LAYOUT_DIMENSION).contains(value))
+                .ok_or_else(|| format!("{} must be a number from 0 to {}, not {:?}", name, MAX_LAYOUT_DIMENSION - 1, value))
+        };
+
+        let row = position(self.row, "row")?;
+        let col = position(self.col, "col")?;
+        let seat_type = field(self.seat_type, "type")?.to_lowercase();
+        let is_seat = if SEAT_TYPES.contains(&seat_type.as_str()) {
+            true
+        } else if GAP_TYPES.contains(&seat_type.as_str()) {
+            false
+        } else {
+            return Err(format!(
+                "type must be one of {} or {}, not {:?}",
+                SEAT_TYPES.join(", "),
+                GAP_TYPES.join(", "),
+                seat_type
+            ));
+        };
+        // Gaps have no price to check
+        let price = if is_seat {
+            let value = field(self.price, "price")?;
+            value
+                .parse::<i32>()
+                .ok()
+                .filter(|price| *price >= 0)
+                .ok_or_else(|| format!("price must be a whole number of at least 0, not {:?}", value))?
+        } else {
+            0
+        };
+        Ok(Record { row, col, is_seat, price })
+    }
+}
+
+fn unquote(field: &str) -> &str {
+    let field = field.trim();
+    field
+        .strip_prefix('"')
+        .and_then(|field| field.strip_suffix('"'))
+        .unwrap_or(field)
+        .trim()
+}
@@ -1,7 +1,19 @@
 pub mod event;
 pub mod reservation;
 pub mod schemas;
+pub mod proto;
+pub mod seat_map;
+pub mod layout;
+pub mod audit;
+pub mod user;
+pub mod payment;
 
 pub use event::*;
 pub use reservation::*;
-pub use schemas::*;
\ No newline at end of file
+pub use schemas::*;
+pub use proto::*;
+pub use seat_map::*;
+pub use layout::*;
+pub use audit::*;
+pub use user::*;
+pub use payment::*;
\ No newline at end of file
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Command to charge a user for a reserved reservation, keyed by reservation id
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChargePayment {
+    pub reservation_id: String,
+    pub user_id: String,
+    /// In the currency's smallest unit, e.g. cents
+    pub amount: i64,
+    /// ISO 4217, lowercase, e.g. `usd`
+    pub currency: String,
+    /// Token of the card or other method to charge, from the provider's client SDK
+    pub payment_method: String,
+    /// Charges with the same key are made once; the first result answers the others
+    pub idempotency_key: String,
+}
+
+impl ChargePayment {
+    /// The key of a charge when the client doesn't give one: a retry with the
+    /// same payment method is the same charge, another method a new one
+    pub fn default_idempotency_key(reservation_id: &str, payment_method: &str) -> String {
+        format!("{}:{}", reservation_id, payment_method)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentStatus {
+    /// Charged; the reservation is paid
+    Succeeded,
+    /// Refused by the provider, e.g. for insufficient funds; not worth retrying as is
+    Declined,
+    /// Not charged because the provider couldn't be reached or erred; may be retried
+    Failed,
+}
+
+impl PaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Succeeded => "succeeded",
+            Self::Declined => "declined",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Outcome of a `ChargePayment`, keyed by reservation id
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentResult {
+    pub reservation_id: String,
+    pub idempotency_key: String,
+    pub status: PaymentStatus,
+    pub amount: i64,
+    pub currency: String,
+    /// Name of the provider that handled the charge
+    pub provider: String,
+    /// The provider's id of the charge, when one was made
+    pub reference: Option<String>,
+    /// Why the charge was declined or failed
+    pub message: Option<String>,
+    pub processed_at: DateTime<Utc>,
+}
+
+impl PaymentResult {
+    pub fn new(charge: &ChargePayment, provider: &str, status: PaymentStatus, processed_at: DateTime<Utc>) -> Self {
+        Self {
+            reservation_id: charge.reservation_id.clone(),
+            idempotency_key: charge.idempotency_key.clone(),
+            status,
+            amount: charge.amount,
+            currency: charge.currency.clone(),
+            provider: provider.to_string(),
+            reference: None,
+            message: None,
+            processed_at,
+        }
+    }
+
+    pub fn with_reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
@@ -0,0 +1,896 @@
+use crate::{Result, TicketMasterError};
+use super::event::{
+    Area, AreaStatus, CreateEvent, EventArtwork, ReservationType, ReserveSeat, Seat, SeatPreference, SeatStatus,
+};
+use super::reservation::{
+    CreateReservation, FailedReason, Reservation, ReservationErrorCode, ReservationPriority, ReservationResult, ReservationResultEnum,
+    ReservationState,
+};
+use super::payment::{ChargePayment, PaymentResult, PaymentStatus};
+use super::user::UserProfile;
+use chrono::{DateTime, Utc};
+use prost::Message;
+
+/// Protobuf schema of all domain commands and events, as registered with the Schema Registry
+pub const PROTO_SCHEMA: &str = include_str!("../main/resources/proto/ticket_master.proto");
+
+/// Domain types that have a Protobuf representation in `PROTO_SCHEMA`
+pub trait ProtobufMessage: Sized {
+    /// Fully qualified message name, e.g. `ticket_master.AreaStatus`
+    const FULL_NAME: &'static str;
+
+    fn encode_protobuf(&self) -> Vec<u8>;
+    fn decode_protobuf(data: &[u8]) -> Result<Self>;
+}
+
+/// Wire types mirroring `ticket_master.proto`
+pub mod pb {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ReservationType {
+        Invalid = 0,
+        SelfPick = 1,
+        Random = 2,
+        ContinuousRandom = 3,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum SeatPreference {
+        Unspecified = 0,
+        Aisle = 1,
+        Front = 2,
+        Together = 3,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ReservationPriority {
+        General = 0,
+        Priority = 1,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ReservationState {
+        Processing = 0,
+        Reserved = 1,
+        Failed = 2,
+        Paid = 3,
+        Cancelled = 4,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ReservationResultEnum {
+        Success = 0,
+        Failed = 1,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ReservationErrorCode {
+        InvalidEventArea = 0,
+        InvalidArgument = 1,
+        SeatNotAvailable = 2,
+        InsufficientSeats = 3,
+        Timeout = 4,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum PaymentStatus {
+        Succeeded = 0,
+        Declined = 1,
+        Failed = 2,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Seat {
+        #[prost(int32, tag = "1")]
+        pub row: i32,
+        #[prost(int32, tag = "2")]
+        pub col: i32,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SeatStatus {
+        #[prost(int32, tag = "1")]
+        pub row: i32,
+        #[prost(int32, tag = "2")]
+        pub col: i32,
+        #[prost(bool, tag = "3")]
+        pub is_available: bool,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SeatRow {
+        #[prost(message, repeated, tag = "1")]
+        pub seats: Vec<SeatStatus>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Area {
+        #[prost(string, tag = "1")]
+        pub area_id: String,
+        #[prost(int32, tag = "2")]
+        pub price: i32,
+        #[prost(int32, tag = "3")]
+        pub row_count: i32,
+        #[prost(int32, tag = "4")]
+        pub col_count: i32,
+        #[prost(string, optional, tag = "5")]
+        pub strategy: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CreateEvent {
+        #[prost(string, tag = "1")]
+        pub artist: String,
+        #[prost(string, tag = "2")]
+        pub event_name: String,
+        #[prost(int64, tag = "3")]
+        pub reservation_opening_time: i64,
+        #[prost(int64, tag = "4")]
+        pub reservation_closing_time: i64,
+        #[prost(int64, tag = "5")]
+        pub event_start_time: i64,
+        #[prost(int64, tag = "6")]
+        pub event_end_time: i64,
+        #[prost(message, repeated, tag = "7")]
+        pub areas: Vec<Area>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct AreaStatus {
+        #[prost(string, tag = "1")]
+        pub event_id: String,
+        #[prost(string, tag = "2")]
+        pub area_id: String,
+        #[prost(int32, tag = "3")]
+        pub price: i32,
+        #[prost(int32, tag = "4")]
+        pub row_count: i32,
+        #[prost(int32, tag = "5")]
+        pub col_count: i32,
+        #[prost(int32, tag = "6")]
+        pub available_seats: i32,
+        #[prost(message, repeated, tag = "7")]
+        pub seats: Vec<SeatRow>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ReserveSeat {
+        #[prost(string, tag = "1")]
+        pub reservation_id: String,
+        #[prost(string, tag = "2")]
+        pub event_id: String,
+        #[prost(string, tag = "3")]
+        pub area_id: String,
+        #[prost(int32, tag = "4")]
+        pub num_of_seats: i32,
+        #[prost(int32, tag = "5")]
+        pub num_of_seat: i32,
+        #[prost(enumeration = "ReservationType", tag = "6")]
+        pub reservation_type: i32,
+        #[prost(message, repeated, tag = "7")]
+        pub seats: Vec<Seat>,
+        #[prost(enumeration = "SeatPreference", repeated, tag = "8")]
+        pub preferences: Vec<i32>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CreateReservation {
+        #[prost(string, tag = "1")]
+        pub reservation_id: String,
+        #[prost(string, tag = "2")]
+        pub user_id: String,
+        #[prost(string, tag = "3")]
+        pub event_id: String,
+        #[prost(string, tag = "4")]
+        pub area_id: String,
+        #[prost(int32, tag = "5")]
+        pub num_of_seats: i32,
+        #[prost(int32, tag = "6")]
+        pub num_of_seat: i32,
+        #[prost(enumeration = "ReservationType", tag = "7")]
+        pub reservation_type: i32,
+        #[prost(message, repeated, tag = "8")]
+        pub seats: Vec<Seat>,
+        #[prost(enumeration = "SeatPreference", repeated, tag = "9")]
+        pub preferences: Vec<i32>,
+        #[prost(enumeration = "ReservationPriority", tag = "10")]
+        pub priority: i32,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Reservation {
+        #[prost(string, tag = "1")]
+        pub reservation_id: String,
+        #[prost(string, tag = "2")]
+        pub user_id: String,
+        #[prost(string, tag = "3")]
+        pub event_id: String,
+        #[prost(string, tag = "4")]
+        pub area_id: String,
+        #[prost(int32, tag = "5")]
+        pub num_of_seats: i32,
+        #[prost(int32, tag = "6")]
+        pub num_of_seat: i32,
+        #[prost(enumeration = "ReservationType", tag = "7")]
+        pub reservation_type: i32,
+        #[prost(message, repeated, tag = "8")]
+        pub seats: Vec<Seat>,
+        #[prost(enumeration = "ReservationState", tag = "9")]
+        pub state: i32,
+        #[prost(string, tag = "10")]
+        pub failed_reason: String,
+        #[prost(enumeration = "ReservationErrorCode", optional, tag = "11")]
+        pub failed_code: Option<i32>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ReservationResult {
+        #[prost(string, tag = "1")]
+        pub reservation_id: String,
+        #[prost(enumeration = "ReservationResultEnum", tag = "2")]
+        pub result: i32,
+        #[prost(enumeration = "ReservationErrorCode", optional, tag = "3")]
+        pub error_code: Option<i32>,
+        #[prost(string, optional, tag = "4")]
+        pub error_message: Option<String>,
+        #[prost(message, repeated, tag = "5")]
+        pub seats: Vec<Seat>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct EventArtwork {
+        #[prost(string, tag = "1")]
+        pub event_id: String,
+        #[prost(string, tag = "2")]
+        pub url: String,
+        #[prost(string, tag = "3")]
+        pub object_key: String,
+        #[prost(string, tag = "4")]
+        pub content_type: String,
+        #[prost(uint64, tag = "5")]
+        pub size: u64,
+        #[prost(int64, tag = "6")]
+        pub uploaded_at: i64,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct UserProfile {
+        #[prost(string, tag = "1")]
+        pub user_id: String,
+        #[prost(string, tag = "2")]
+        pub email: String,
+        #[prost(string, tag = "3")]
+        pub display_name: String,
+        #[prost(string, optional, tag = "4")]
+        pub phone: Option<String>,
+        #[prost(int64, tag = "5")]
+        pub created_at: i64,
+        #[prost(int64, tag = "6")]
+        pub updated_at: i64,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ChargePayment {
+        #[prost(string, tag = "1")]
+        pub reservation_id: String,
+        #[prost(string, tag = "2")]
+        pub user_id: String,
+        #[prost(int64, tag = "3")]
+        pub amount: i64,
+        #[prost(string, tag = "4")]
+        pub currency: String,
+        #[prost(string, tag = "5")]
+        pub payment_method: String,
+        #[prost(string, tag = "6")]
+        pub idempotency_key: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct PaymentResult {
+        #[prost(string, tag = "1")]
+        pub reservation_id: String,
+        #[prost(string, tag = "2")]
+        pub idempotency_key: String,
+        #[prost(enumeration = "PaymentStatus", tag = "3")]
+        pub status: i32,
+        #[prost(int64, tag = "4")]
+        pub amount: i64,
+        #[prost(string, tag = "5")]
+        pub currency: String,
+        #[prost(string, tag = "6")]
+        pub provider: String,
+        #[prost(string, optional, tag = "7")]
+        pub reference: Option<String>,
+        #[prost(string, optional, tag = "8")]
+        pub message: Option<String>,
+        #[prost(int64, tag = "9")]
+        pub processed_at: i64,
+    }
+}
+
+fn to_millis(time: &DateTime<Utc>) -> i64 {
+    time.timestamp_millis()
+}
+
+fn from_millis(millis: i64) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| TicketMasterError::InvalidArgument(format!("Timestamp out of range: {}", millis)))
+}
+
+impl From<&ReservationType> for pb::ReservationType {
+    fn from(value: &ReservationType) -> Self {
+        match value {
+            ReservationType::SelfPick => Self::SelfPick,
+            ReservationType::Random => Self::Random,
+            ReservationType::Invalid => Self::Invalid,
+            ReservationType::ContinuousRandom => Self::ContinuousRandom,
+        }
+    }
+}
+
+impl From<pb::ReservationType> for ReservationType {
+    fn from(value: pb::ReservationType) -> Self {
+        match value {
+            pb::ReservationType::SelfPick => Self::SelfPick,
+            pb::ReservationType::Random => Self::Random,
+            pb::ReservationType::Invalid => Self::Invalid,
+            pb::ReservationType::ContinuousRandom => Self::ContinuousRandom,
+        }
+    }
+}
+
+impl From<ReservationPriority> for pb::ReservationPriority {
+    fn from(value: ReservationPriority) -> Self {
+        match value {
+            ReservationPriority::General => Self::General,
+            ReservationPriority::Priority => Self::Priority,
+        }
+    }
+}
+
+impl From<pb::ReservationPriority> for ReservationPriority {
+    fn from(value: pb::ReservationPriority) -> Self {
+        match value {
+            pb::ReservationPriority::General => Self::General,
+            pb::ReservationPriority::Priority => Self::Priority,
+        }
+    }
+}
+
+impl From<&SeatPreference> for pb::SeatPreference {
+    fn from(value: &SeatPreference) -> Self {
+        match value {
+            SeatPreference::Aisle => Self::Aisle,
+            SeatPreference::Front => Self::Front,
+            SeatPreference::Together => Self::Together,
+        }
+    }
+}
+
+fn preferences_to_pb(preferences: &[SeatPreference]) -> Vec<i32> {
+    preferences.iter().map(|preference| pb::SeatPreference::from(preference) as i32).collect()
+}
+
+// Unspecified preferences are dropped rather than rejected
+fn preferences_from_pb(preferences: Vec<i32>) -> Result<Vec<SeatPreference>> {
+    let mut parsed = Vec::with_capacity(preferences.len());
+    for preference in preferences {
+        match pb::SeatPreference::try_from(preference)? {
+            pb::SeatPreference::Unspecified => {}
+            pb::SeatPreference::Aisle => parsed.push(SeatPreference::Aisle),
+            pb::SeatPreference::Front => parsed.push(SeatPreference::Front),
+            pb::SeatPreference::Together => parsed.push(SeatPreference::Together),
+        }
+    }
+    Ok(parsed)
+}
+
+impl From<&ReservationState> for pb::ReservationState {
+    fn from(value: &ReservationState) -> Self {
+        match value {
+            ReservationState::Processing => Self::Processing,
+            ReservationState::Reserved => Self::Reserved,
+            ReservationState::Failed => Self::Failed,
+            ReservationState::Paid => Self::Paid,
+            ReservationState::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+impl From<pb::ReservationState> for ReservationState {
+    fn from(value: pb::ReservationState) -> Self {
+        match value {
+            pb::ReservationState::Processing => Self::Processing,
+            pb::ReservationState::Reserved => Self::Reserved,
+            pb::ReservationState::Failed => Self::Failed,
+            pb::ReservationState::Paid => Self::Paid,
+            pb::ReservationState::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+impl From<&ReservationResultEnum> for pb::ReservationResultEnum {
+    fn from(value: &ReservationResultEnum) -> Self {
+        match value {
+            ReservationResultEnum::Success => Self::Success,
+            ReservationResultEnum::Failed => Self::Failed,
+        }
+    }
+}
+
+impl From<pb::ReservationResultEnum> for ReservationResultEnum {
+    fn from(value: pb::ReservationResultEnum) -> Self {
+        match value {
+            pb::ReservationResultEnum::Success => Self::Success,
+            pb::ReservationResultEnum::Failed => Self::Failed,
+        }
+    }
+}
+
+impl From<&ReservationErrorCode> for pb::ReservationErrorCode {
+    fn from(value: &ReservationErrorCode) -> Self {
+        match value {
+            ReservationErrorCode::InvalidEventArea => Self::InvalidEventArea,
+            ReservationErrorCode::InvalidArgument => Self::InvalidArgument,
+            ReservationErrorCode::SeatNotAvailable => Self::SeatNotAvailable,
+            ReservationErrorCode::InsufficientSeats => Self::InsufficientSeats,
+            ReservationErrorCode::Timeout => Self::Timeout,
+        }
+    }
+}
+
+impl From<pb::ReservationErrorCode> for ReservationErrorCode {
+    fn from(value: pb::ReservationErrorCode) -> Self {
+        match value {
+            pb::ReservationErrorCode::InvalidEventArea => Self::InvalidEventArea,
+            pb::ReservationErrorCode::InvalidArgument => Self::InvalidArgument,
+            pb::ReservationErrorCode::SeatNotAvailable => Self::SeatNotAvailable,
+            pb::ReservationErrorCode::InsufficientSeats => Self::InsufficientSeats,
+            pb::ReservationErrorCode::Timeout => Self::Timeout,
+        }
+    }
+}
+
+impl From<&Seat> for pb::Seat {
+    fn from(value: &Seat) -> Self {
+        Self {
+            row: value.row,
+            col: value.col,
+        }
+    }
+}
+
+impl From<pb::Seat> for Seat {
+    fn from(value: pb::Seat) -> Self {
+        Self {
+            row: value.row,
+            col: value.col,
+        }
+    }
+}
+
+impl From<&SeatStatus> for pb::SeatStatus {
+    fn from(value: &SeatStatus) -> Self {
+        Self {
+            row: value.row,
+            col: value.col,
+            is_available: value.is_available,
+        }
+    }
+}
+
+impl From<pb::SeatStatus> for SeatStatus {
+    fn from(value: pb::SeatStatus) -> Self {
+        Self {
+            row: value.row,
+            col: value.col,
+            is_available: value.is_available,
+        }
+    }
+}
+
+impl From<&Area> for pb::Area {
+    fn from(value: &Area) -> Self {
+        Self {
+            area_id: value.area_id.clone(),
+            price: value.price,
+            row_count: value.row_count,
+            col_count: value.col_count,
+            strategy: value.strategy.clone(),
+        }
+    }
+}
+
+impl From<pb::Area> for Area {
+    fn from(value: pb::Area) -> Self {
+        Self {
+            area_id: value.area_id,
+            price: value.price,
+            row_count: value.row_count,
+            col_count: value.col_count,
+            strategy: value.strategy,
+        }
+    }
+}
+
+impl From<&CreateEvent> for pb::CreateEvent {
+    fn from(value: &CreateEvent) -> Self {
+        Self {
+            artist: value.artist.clone(),
+            event_name: value.event_name.clone(),
+            reservation_opening_time: to_millis(&value.reservation_opening_time),
+            reservation_closing_time: to_millis(&value.reservation_closing_time),
+            event_start_time: to_millis(&value.event_start_time),
+            event_end_time: to_millis(&value.event_end_time),
+            areas: value.areas.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::CreateEvent> for CreateEvent {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::CreateEvent) -> Result<Self> {
+        Ok(Self {
+            artist: value.artist,
+            event_name: value.event_name,
+            reservation_opening_time: from_millis(value.reservation_opening_time)?,
+            reservation_closing_time: from_millis(value.reservation_closing_time)?,
+            event_start_time: from_millis(value.event_start_time)?,
+            event_end_time: from_millis(value.event_end_time)?,
+            areas: value.areas.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+impl From<&AreaStatus> for pb::AreaStatus {
+    fn from(value: &AreaStatus) -> Self {
+        Self {
+            event_id: value.event_id.clone(),
+            area_id: value.area_id.clone(),
+            price: value.price,
+            row_count: value.row_count,
+            col_count: value.col_count,
+            available_seats: value.available_seats,
+            seats: value
+                .seats
+                .iter()
+                .map(|row| pb::SeatRow {
+                    seats: row.iter().map(Into::into).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::AreaStatus> for AreaStatus {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::AreaStatus) -> Result<Self> {
+        Ok(Self {
+            event_id: value.event_id,
+            area_id: value.area_id,
+            price: value.price,
+            row_count: value.row_count,
+            col_count: value.col_count,
+            available_seats: value.available_seats,
+            seats: value
+                .seats
+                .into_iter()
+                .map(|row| row.seats.into_iter().map(Into::into).collect())
+                .collect(),
+        })
+    }
+}
+
+impl From<&ReserveSeat> for pb::ReserveSeat {
+    fn from(value: &ReserveSeat) -> Self {
+        Self {
+            reservation_id: value.reservation_id.clone(),
+            event_id: value.event_id.clone(),
+            area_id: value.area_id.clone(),
+            num_of_seats: value.num_of_seats,
+            num_of_seat: value.num_of_seat,
+            reservation_type: pb::ReservationType::from(&value.reservation_type) as i32,
+            seats: value.seats.iter().map(Into::into).collect(),
+            preferences: preferences_to_pb(&value.preferences),
+        }
+    }
+}
+
+impl TryFrom<pb::ReserveSeat> for ReserveSeat {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::ReserveSeat) -> Result<Self> {
+        Ok(Self {
+            reservation_id: value.reservation_id,
+            event_id: value.event_id,
+            area_id: value.area_id,
+            num_of_seats: value.num_of_seats,
+            num_of_seat: value.num_of_seat,
+            reservation_type: pb::ReservationType::try_from(value.reservation_type)?.into(),
+            seats: value.seats.into_iter().map(Into::into).collect(),
+            preferences: preferences_from_pb(value.preferences)?,
+        })
+    }
+}
+
+impl From<&CreateReservation> for pb::CreateReservation {
+    fn from(value: &CreateReservation) -> Self {
+        Self {
+            reservation_id: value.reservation_id.clone(),
+            user_id: value.user_id.clone(),
+            event_id: value.event_id.clone(),
+            area_id: value.area_id.clone(),
+            num_of_seats: value.num_of_seats,
+            num_of_seat: value.num_of_seat,
+            reservation_type: pb::ReservationType::from(&value.reservation_type) as i32,
+            seats: value.seats.iter().map(Into::into).collect(),
+            preferences: preferences_to_pb(&value.preferences),
+            priority: pb::ReservationPriority::from(value.priority) as i32,
+        }
+    }
+}
+
+impl TryFrom<pb::CreateReservation> for CreateReservation {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::CreateReservation) -> Result<Self> {
+        Ok(Self {
+            reservation_id: value.reservation_id,
+            user_id: value.user_id,
+            event_id: value.event_id,
+            area_id: value.area_id,
+            num_of_seats: value.num_of_seats,
+            num_of_seat: value.num_of_seat,
+            reservation_type: pb::ReservationType::try_from(value.reservation_type)?.into(),
+            seats: value.seats.into_iter().map(Into::into).collect(),
+            preferences: preferences_from_pb(value.preferences)?,
+            priority: pb::ReservationPriority::try_from(value.priority)?.into(),
+        })
+    }
+}
+
+impl From<&Reservation> for pb::Reservation {
+    fn from(value: &Reservation) -> Self {
+        Self {
+            reservation_id: value.reservation_id.clone(),
+            user_id: value.user_id.clone(),
+            event_id: value.event_id.clone(),
+            area_id: value.area_id.clone(),
+            num_of_seats: value.num_of_seats,
+            num_of_seat: value.num_of_seat,
+            reservation_type: pb::ReservationType::from(&value.reservation_type) as i32,
+            seats: value.seats.iter().map(Into::into).collect(),
+            state: pb::ReservationState::from(&value.state) as i32,
+            failed_reason: value.failed_reason.as_ref().map(|reason| reason.message.clone()).unwrap_or_default(),
+            failed_code: value
+                .failed_reason
+                .as_ref()
+                .and_then(|reason| reason.code.as_ref())
+                .map(|code| pb::ReservationErrorCode::from(code) as i32),
+        }
+    }
+}
+
+impl TryFrom<pb::Reservation> for Reservation {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::Reservation) -> Result<Self> {
+        Ok(Self {
+            reservation_id: value.reservation_id,
+            user_id: value.user_id,
+            event_id: value.event_id,
+            area_id: value.area_id,
+            num_of_seats: value.num_of_seats,
+            num_of_seat: value.num_of_seat,
+            reservation_type: pb::ReservationType::try_from(value.reservation_type)?.into(),
+            seats: value.seats.into_iter().map(Into::into).collect(),
+            state: pb::ReservationState::try_from(value.state)?.into(),
+            failed_reason: failed_reason_from_pb(value.failed_reason, value.failed_code)?,
+        })
+    }
+}
+
+// A reservation that hasn't failed has neither a message nor a code
+fn failed_reason_from_pb(message: String, code: Option<i32>) -> Result<Option<FailedReason>> {
+    let code = code.map(|code| pb::ReservationErrorCode::try_from(code).map(Into::into)).transpose()?;
+    if message.is_empty() && code.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(FailedReason { code, message }))
+}
+
+impl From<&ReservationResult> for pb::ReservationResult {
+    fn from(value: &ReservationResult) -> Self {
+        Self {
+            reservation_id: value.reservation_id.clone(),
+            result: pb::ReservationResultEnum::from(&value.result) as i32,
+            error_code: value.error_code.as_ref().map(|code| pb::ReservationErrorCode::from(code) as i32),
+            error_message: value.error_message.clone(),
+            seats: value.seats.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::ReservationResult> for ReservationResult {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::ReservationResult) -> Result<Self> {
+        Ok(Self {
+            reservation_id: value.reservation_id,
+            result: pb::ReservationResultEnum::try_from(value.result)?.into(),
+            error_code: value
+                .error_code
+                .map(|code| pb::ReservationErrorCode::try_from(code).map(Into::into))
+                .transpose()?,
+            error_message: value.error_message,
+            seats: value.seats.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+impl From<&EventArtwork> for pb::EventArtwork {
+    fn from(value: &EventArtwork) -> Self {
+        Self {
+            event_id: value.event_id.clone(),
+            url: value.url.clone(),
+            object_key: value.object_key.clone(),
+            content_type: value.content_type.clone(),
+            size: value.size,
+            uploaded_at: to_millis(&value.uploaded_at),
+        }
+    }
+}
+
+impl TryFrom<pb::EventArtwork> for EventArtwork {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::EventArtwork) -> Result<Self> {
+        Ok(Self {
+            event_id: value.event_id,
+            url: value.url,
+            object_key: value.object_key,
+            content_type: value.content_type,
+            size: value.size,
+            uploaded_at: from_millis(value.uploaded_at)?,
+        })
+    }
+}
+
+impl From<&UserProfile> for pb::UserProfile {
+    fn from(value: &UserProfile) -> Self {
+        Self {
+            user_id: value.user_id.clone(),
+            email: value.email.clone(),
+            display_name: value.display_name.clone(),
+            phone: value.phone.clone(),
+            created_at: to_millis(&value.created_at),
+            updated_at: to_millis(&value.updated_at),
+        }
+    }
+}
+
+impl TryFrom<pb::UserProfile> for UserProfile {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::UserProfile) -> Result<Self> {
+        Ok(Self {
+            user_id: value.user_id,
+            email: value.email,
+            display_name: value.display_name,
+            phone: value.phone,
+            created_at: from_millis(value.created_at)?,
+            updated_at: from_millis(value.updated_at)?,
+        })
+    }
+}
+
+impl From<&PaymentStatus> for pb::PaymentStatus {
+    fn from(value: &PaymentStatus) -> Self {
+        match value {
+            PaymentStatus::Succeeded => Self::Succeeded,
+            PaymentStatus::Declined => Self::Declined,
+            PaymentStatus::Failed => Self::Failed,
+        }
+    }
+}
+
+impl From<pb::PaymentStatus> for PaymentStatus {
+    fn from(value: pb::PaymentStatus) -> Self {
+        match value {
+            pb::PaymentStatus::Succeeded => Self::Succeeded,
+            pb::PaymentStatus::Declined => Self::Declined,
+            pb::PaymentStatus::Failed => Self::Failed,
+        }
+    }
+}
+
+impl From<&ChargePayment> for pb::ChargePayment {
+    fn from(value: &ChargePayment) -> Self {
+        Self {
+            reservation_id: value.reservation_id.clone(),
+            user_id: value.user_id.clone(),
+            amount: value.amount,
+            currency: value.currency.clone(),
+            payment_method: value.payment_method.clone(),
+            idempotency_key: value.idempotency_key.clone(),
+        }
+    }
+}
+
+impl TryFrom<pb::ChargePayment> for ChargePayment {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::ChargePayment) -> Result<Self> {
+        Ok(Self {
+            reservation_id: value.reservation_id,
+            user_id: value.user_id,
+            amount: value.amount,
+            currency: value.currency,
+            payment_method: value.payment_method,
+            idempotency_key: value.idempotency_key,
+        })
+    }
+}
+
+impl From<&PaymentResult> for pb::PaymentResult {
+    fn from(value: &PaymentResult) -> Self {
+        Self {
+            reservation_id: value.reservation_id.clone(),
+            idempotency_key: value.idempotency_key.clone(),
+            status: pb::PaymentStatus::from(&value.status) as i32,
+            amount: value.amount,
+            currency: value.currency.clone(),
+            provider: value.provider.clone(),
+            reference: value.reference.clone(),
+            message: value.message.clone(),
+            processed_at: to_millis(&value.processed_at),
+        }
+    }
+}
+
+impl TryFrom<pb::PaymentResult> for PaymentResult {
+    type Error = TicketMasterError;
+
+    fn try_from(value: pb::PaymentResult) -> Result<Self> {
+        Ok(Self {
+            reservation_id: value.reservation_id,
+            idempotency_key: value.idempotency_key,
+            status: pb::PaymentStatus::try_from(value.status)?.into(),
+            amount: value.amount,
+            currency: value.currency,
+            provider: value.provider,
+            reference: value.reference,
+            message: value.message,
+            processed_at: from_millis(value.processed_at)?,
+        })
+    }
+}
+
+macro_rules! protobuf_message {
+    ($($domain:ident),* $(,)?) => {
+        $(
+            impl ProtobufMessage for $domain {
+                const FULL_NAME: &'static str = concat!("ticket_master.", stringify!($domain));
+
+                fn encode_protobuf(&self) -> Vec<u8> {
+                    pb::$domain::from(self).encode_to_vec()
+                }
+
+                fn decode_protobuf(data: &[u8]) -> Result<Self> {
+                    pb::$domain::decode(data)?.try_into()
+                }
+            }
+        )*
+    };
+}
+
+protobuf_message!(CreateEvent, AreaStatus, ReserveSeat, CreateReservation, Reservation, ReservationResult, EventArtwork, UserProfile,
+    ChargePayment, PaymentResult);
@@ -1,5 +1,7 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use super::event::{Seat, ReservationType};
+use super::event::{Seat, ReservationType, SeatPreference};
+use crate::{Result, TicketMasterError};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateReservation {
@@ -11,6 +13,38 @@ pub struct CreateReservation {
     pub num_of_seat: i32,
     pub reservation_type: ReservationType,
     pub seats: Vec<Seat>,
+    /// Passed on to the event service with the seat request
+    #[serde(default)]
+    pub preferences: Vec<SeatPreference>,
+    #[serde(default)]
+    pub priority: ReservationPriority,
+}
+
+/// Which lane a reservation is processed on; priority requests, e.g. presale
+/// or VIP, go ahead of general sale when the services have a backlog
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ReservationPriority {
+    #[default]
+    General,
+    Priority,
+}
+
+impl ReservationPriority {
+    /// Parse the lowercase name used by the REST API and the priority header
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "general" => Some(Self::General),
+            "priority" => Some(Self::Priority),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::General => "general",
+            Self::Priority => "priority",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,7 +58,59 @@ pub struct Reservation {
     pub reservation_type: ReservationType,
     pub seats: Vec<Seat>,
     pub state: ReservationState,
-    pub failed_reason: String,
+    #[serde(default, with = "failed_reason_format")]
+    pub failed_reason: Option<FailedReason>,
+}
+
+/// Why a reservation failed, so clients can tell e.g. sold out from a taken seat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedReason {
+    /// `None` for reservations that failed before codes were kept
+    pub code: Option<ReservationErrorCode>,
+    pub message: String,
+}
+
+// Reservations used to keep only the message, as a string. Bincode still
+// gets a string, JSON of the reason or empty without one, so stored
+// reservations keep their layout; JSON accepts either form.
+mod failed_reason_format {
+    use super::FailedReason;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(reason: &Option<FailedReason>, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return reason.serialize(serializer);
+        }
+        let encoded = match reason {
+            Some(reason) => serde_json::to_string(reason).map_err(serde::ser::Error::custom)?,
+            None => String::new(),
+        };
+        serializer.serialize_str(&encoded)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<FailedReason>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Stored {
+            Structured(FailedReason),
+            Message(String),
+        }
+
+        let stored = if deserializer.is_human_readable() {
+            Option::<Stored>::deserialize(deserializer)?
+        } else {
+            let encoded = String::deserialize(deserializer)?;
+            match serde_json::from_str::<FailedReason>(&encoded) {
+                Ok(reason) => Some(Stored::Structured(reason)),
+                Err(_) => Some(Stored::Message(encoded)),
+            }
+        };
+        Ok(match stored {
+            Some(Stored::Structured(reason)) => Some(reason),
+            Some(Stored::Message(message)) if !message.is_empty() => Some(FailedReason { code: None, message }),
+            _ => None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -36,6 +122,77 @@ pub enum ReservationState {
     Cancelled,
 }
 
+/// Something that happens to a reservation and may move it to another state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReservationEvent {
+    SeatsReserved { seats: Vec<Seat> },
+    ReservationFailed { reason: FailedReason },
+    PaymentReceived,
+    Cancelled,
+}
+
+impl ReservationEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SeatsReserved { .. } => "SeatsReserved",
+            Self::ReservationFailed { .. } => "ReservationFailed",
+            Self::PaymentReceived => "PaymentReceived",
+            Self::Cancelled => "Cancelled",
+        }
+    }
+}
+
+/// Domain event recording one accepted transition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReservationTransition {
+    pub reservation_id: String,
+    pub from: ReservationState,
+    pub to: ReservationState,
+    pub event: ReservationEvent,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl ReservationState {
+    /// The state `event` moves a reservation in this state to, if it may happen at all
+    ///
+    /// Failed, Paid and Cancelled are final.
+    pub fn next(&self, event: &ReservationEvent) -> Option<Self> {
+        use ReservationEvent as E;
+        match (self, event) {
+            (Self::Processing, E::SeatsReserved { .. }) => Some(Self::Reserved),
+            (Self::Processing, E::ReservationFailed { .. }) => Some(Self::Failed),
+            (Self::Processing | Self::Reserved, E::Cancelled) => Some(Self::Cancelled),
+            (Self::Reserved, E::PaymentReceived) => Some(Self::Paid),
+            _ => None,
+        }
+    }
+
+    /// Whether a reservation in this state may be deleted: it holds no
+    /// seats and nothing moves it on any more
+    pub fn is_deletable(&self) -> bool {
+        matches!(self, Self::Failed | Self::Cancelled)
+    }
+}
+
+/// Operator command for one reservation, applied by the reservation service
+///
+/// Sent on `command.reservation.admin` keyed by reservation id, always as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ReservationAdminCommand {
+    /// Delete a failed or cancelled reservation, publishing a tombstone for it
+    DeleteReservation { reservation_id: String },
+}
+
+impl ReservationAdminCommand {
+    /// Key the command is sent under, the same as the reservation's other commands
+    pub fn key(&self) -> &str {
+        match self {
+            Self::DeleteReservation { reservation_id } => reservation_id,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReservationResult {
     pub reservation_id: String,
@@ -57,6 +214,8 @@ pub enum ReservationErrorCode {
     InvalidArgument,
     SeatNotAvailable,
     InsufficientSeats,
+    /// No result arrived from event-service in time
+    Timeout,
 }
 
 impl Reservation {
@@ -71,20 +230,43 @@ impl Reservation {
             reservation_type: create_req.reservation_type,
             seats: create_req.seats,
             state: ReservationState::Processing,
-            failed_reason: String::new(),
+            failed_reason: None,
         }
     }
 
-    pub fn update_from_result(&mut self, result: &ReservationResult) {
-        match result.result {
-            ReservationResultEnum::Success => {
-                self.state = ReservationState::Reserved;
-                self.seats = result.seats.clone();
-            }
-            ReservationResultEnum::Failed => {
-                self.state = ReservationState::Failed;
-                self.failed_reason = result.error_message.clone().unwrap_or_default();
-            }
+    /// Apply `event`, or reject it if the current state doesn't allow it
+    pub fn transition(&mut self, event: ReservationEvent) -> Result<ReservationTransition> {
+        let to = self.state.next(&event).ok_or_else(|| TicketMasterError::IllegalTransition {
+            reservation_id: self.reservation_id.clone(),
+            state: format!("{:?}", self.state),
+            event: event.name().to_string(),
+        })?;
+        match &event {
+            ReservationEvent::SeatsReserved { seats } => self.seats = seats.clone(),
+            ReservationEvent::ReservationFailed { reason } => self.failed_reason = Some(reason.clone()),
+            ReservationEvent::PaymentReceived | ReservationEvent::Cancelled => {}
         }
+        let from = std::mem::replace(&mut self.state, to.clone());
+        Ok(ReservationTransition {
+            reservation_id: self.reservation_id.clone(),
+            from,
+            to,
+            event,
+            occurred_at: Utc::now(),
+        })
+    }
+
+    pub fn update_from_result(&mut self, result: &ReservationResult) -> Result<ReservationTransition> {
+        self.transition(match result.result {
+            ReservationResultEnum::Success => ReservationEvent::SeatsReserved {
+                seats: result.seats.clone(),
+            },
+            ReservationResultEnum::Failed => ReservationEvent::ReservationFailed {
+                reason: FailedReason {
+                    code: result.error_code.clone(),
+                    message: result.error_message.clone().unwrap_or_default(),
+                },
+            },
+        })
     }
 }
\ No newline at end of file
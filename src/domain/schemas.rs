@@ -1,13 +1,91 @@
+use super::event::{AreaStatus, EventArtwork};
+use super::seat_map::CompactAreaStatus;
+use super::reservation::Reservation;
+use super::user::UserProfile;
+use crate::{AuditRecord, Deadline, StoreHandle, Versioned};
+use std::borrow::Cow;
+
 // Kafka topic definitions
+//
+// State topics are compacted and keyed by the entity they hold the latest
+// record of. A tombstone, a record with the key and no payload, deletes the
+// entity: compaction drops its earlier records, and whatever is built from
+// the topic removes it too.
 pub struct Topics;
 
 impl Topics {
     pub const COMMAND_EVENT_CREATE_EVENT: &'static str = "command.event.create_event";
     pub const COMMAND_EVENT_RESERVE_SEAT: &'static str = "command.event.reserve_seat";
     pub const RESPONSE_RESERVATION_RESULT: &'static str = "response.reservation.result";
+    // Compacted; the latest status of each area, keyed by event#area
     pub const STATE_EVENT_AREA_STATUS: &'static str = "state.event.area_status";
     pub const COMMAND_RESERVATION_CREATE_RESERVATION: &'static str = "command.reservation.create_reservation";
+    // Compacted; the latest of each reservation, keyed by reservation id
     pub const STATE_USER_RESERVATION: &'static str = "state.user.reservation";
+    // Operator commands per reservation; co-partitioned with create-reservation commands and always JSON
+    pub const COMMAND_RESERVATION_ADMIN: &'static str = "command.reservation.admin";
+    // Area status re-keyed by event#area so it lands on the same partition as reserve-seat commands
+    pub const INTERNAL_EVENT_AREA_STATUS: &'static str = "internal.event.area_status";
+    // Operator commands per area; co-partitioned with reserve-seat commands and always JSON
+    pub const COMMAND_EVENT_ADMIN: &'static str = "command.event.admin";
+    // Compacted; the latest artwork of each event, keyed by event id
+    pub const STATE_EVENT_ARTWORK: &'static str = "state.event.artwork";
+    // Compacted; where each instance serves state queries and which partitions it owns
+    pub const INTERNAL_QUERY_HOSTS: &'static str = "internal.query.hosts";
+    // Append-only reservation and area state changes, keyed by entity and always JSON
+    pub const STATE_AUDIT: &'static str = "state.audit";
+    // Compacted; the latest heartbeat of each service instance, keyed by instance id
+    pub const STATE_SERVICE_HEARTBEAT: &'static str = "state.service.heartbeat";
+    // Compacted; the latest profile of each registered user, keyed by user id
+    pub const STATE_USER_PROFILE: &'static str = "state.user.profile";
+    // Charges for reserved reservations, keyed by reservation id
+    pub const COMMAND_PAYMENT_CHARGE: &'static str = "command.payment.charge";
+    // Outcomes of charges, keyed by reservation id
+    pub const RESPONSE_PAYMENT_RESULT: &'static str = "response.payment.result";
+
+    /// The compacted state topics, where a tombstone deletes its key
+    pub const STATE_TOPICS: [&'static str; 5] = [
+        Self::STATE_EVENT_AREA_STATUS,
+        Self::STATE_USER_RESERVATION,
+        Self::STATE_EVENT_ARTWORK,
+        Self::STATE_USER_PROFILE,
+        Self::STATE_SERVICE_HEARTBEAT,
+    ];
+}
+
+/// Maps the logical topic names in `Topics` to the topics used on the cluster
+///
+/// With a prefix such as `staging`, `command.event.create_event` becomes
+/// `staging.command.event.create_event`, so several environments can share
+/// one cluster. Without a prefix names are unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TopicResolver {
+    prefix: Option<String>,
+}
+
+impl TopicResolver {
+    pub fn new(prefix: Option<&str>) -> Self {
+        let prefix = prefix.map(|p| p.trim().trim_end_matches('.')).filter(|p| !p.is_empty());
+        Self {
+            prefix: prefix.map(|p| format!("{}.", p)),
+        }
+    }
+
+    /// Cluster topic for a logical topic name
+    pub fn resolve<'a>(&self, topic: &'a str) -> Cow<'a, str> {
+        match &self.prefix {
+            Some(prefix) => Cow::Owned(format!("{}{}", prefix, topic)),
+            None => Cow::Borrowed(topic),
+        }
+    }
+
+    /// Logical name of a cluster topic; topics without the prefix are returned as is
+    pub fn logical<'a>(&self, topic: &'a str) -> &'a str {
+        match &self.prefix {
+            Some(prefix) => topic.strip_prefix(prefix.as_str()).unwrap_or(topic),
+            None => topic,
+        }
+    }
 }
 
 // State store definitions
@@ -17,9 +95,36 @@ impl Stores {
     pub const AREA_STATUS: &'static str = "AreaStatus";
     pub const RESERVATION: &'static str = "Reservation";
     pub const EVENT_AREA_STATUS_CACHE: &'static str = "eventAreaStatusCache";
+    pub const EVENT_ARTWORK: &'static str = "EventArtwork";
+    /// Area statuses event-service keeps in memory, with their store versions
+    pub const AREA_STATUS_CACHE: &'static str = "AreaStatusCache";
+    /// When reservation-service stops waiting for each reservation's result
+    pub const RESERVATION_DEADLINES: &'static str = "ReservationDeadlines";
+    /// When reservation-service cancels each reserved reservation left unpaid
+    pub const RESERVATION_HOLDS: &'static str = "ReservationHolds";
+    pub const AUDIT_LOG: &'static str = "AuditLog";
+    /// Index of the reservation store by user id
+    pub const RESERVATIONS_BY_USER: &'static str = "reservationsByUser";
+    pub const USER_PROFILE: &'static str = "UserProfile";
+
+    pub const AREA_STATUS_STORE: StoreHandle<String, CompactAreaStatus> = StoreHandle::new(Self::AREA_STATUS);
+    pub const RESERVATION_STORE: StoreHandle<String, Reservation> = StoreHandle::new(Self::RESERVATION);
+    pub const EVENT_AREA_STATUS_CACHE_STORE: StoreHandle<String, AreaStatus> = StoreHandle::new(Self::EVENT_AREA_STATUS_CACHE);
+    pub const EVENT_ARTWORK_STORE: StoreHandle<String, EventArtwork> = StoreHandle::new(Self::EVENT_ARTWORK);
+    pub const AREA_STATUS_CACHE_STORE: StoreHandle<String, Versioned<CompactAreaStatus>> =
+        StoreHandle::new(Self::AREA_STATUS_CACHE);
+    pub const RESERVATION_DEADLINE_STORE: StoreHandle<String, Deadline> = StoreHandle::new(Self::RESERVATION_DEADLINES);
+    pub const RESERVATION_HOLD_STORE: StoreHandle<String, Deadline> = StoreHandle::new(Self::RESERVATION_HOLDS);
+    pub const AUDIT_LOG_STORE: StoreHandle<String, AuditRecord> = StoreHandle::new(Self::AUDIT_LOG);
+    pub const USER_PROFILE_STORE: StoreHandle<String, UserProfile> = StoreHandle::new(Self::USER_PROFILE);
 }
 
 // Utility functions for key generation
 pub fn event_area_key(event_id: &str, area_id: &str) -> String {
     format!("{}#{}", event_id, area_id)
+}
+
+// Name of the per-partition instance of a state store
+pub fn partition_store_name(store: &str, partition: i32) -> String {
+    format!("{}-{}", store, partition)
 }
\ No newline at end of file
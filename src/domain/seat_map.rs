@@ -0,0 +1,268 @@
+use super::event::{AreaStatus, Seat, SeatStatus};
+use serde::{Deserialize, Serialize};
+
+const WORD_BITS: usize = 64;
+
+/// Seat availability of an area, one bit per seat (set when available)
+///
+/// Rows start on a word boundary so a row can be scanned a word at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeatBitmap {
+    row_count: u32,
+    col_count: u32,
+    words: Vec<u64>,
+}
+
+impl SeatBitmap {
+    /// A bitmap with every seat available
+    pub fn new(row_count: i32, col_count: i32) -> Self {
+        let mut bitmap = Self::empty(row_count, col_count);
+        let cols = bitmap.col_count as usize;
+        let words_per_row = bitmap.words_per_row().max(1);
+        for row in bitmap.words.chunks_mut(words_per_row) {
+            for (i, word) in row.iter_mut().enumerate() {
+                let bits = (cols - i * WORD_BITS).min(WORD_BITS);
+                *word = if bits == WORD_BITS { u64::MAX } else { (1u64 << bits) - 1 };
+            }
+        }
+        bitmap
+    }
+
+    /// A bitmap with no seat available
+    pub fn empty(row_count: i32, col_count: i32) -> Self {
+        let row_count = row_count.max(0) as u32;
+        let col_count = col_count.max(0) as u32;
+        let words_per_row = (col_count as usize).div_ceil(WORD_BITS);
+        Self {
+            row_count,
+            col_count,
+            words: vec![0; row_count as usize * words_per_row],
+        }
+    }
+
+    pub fn row_count(&self) -> i32 {
+        self.row_count as i32
+    }
+
+    pub fn col_count(&self) -> i32 {
+        self.col_count as i32
+    }
+
+    pub fn contains(&self, row: i32, col: i32) -> bool {
+        self.position(row, col).is_some()
+    }
+
+    /// Whether the seat is available; seats outside the area are not
+    pub fn is_available(&self, row: i32, col: i32) -> bool {
+        self.position(row, col)
+            .map(|(row, col)| self.words[self.word_index(row, col)] & bit(col) != 0)
+            .unwrap_or(false)
+    }
+
+    /// Mark the seat available or taken; returns false if it is outside the area
+    pub fn set_available(&mut self, row: i32, col: i32, available: bool) -> bool {
+        let Some((row, col)) = self.position(row, col) else {
+            return false;
+        };
+        let index = self.word_index(row, col);
+        if available {
+            self.words[index] |= bit(col);
+        } else {
+            self.words[index] &= !bit(col);
+        }
+        true
+    }
+
+    pub fn available_count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Available seats in row-major order
+    pub fn available(&self) -> impl Iterator<Item = Seat> + '_ {
+        let words_per_row = self.words_per_row();
+        self.words.iter().enumerate().flat_map(move |(index, &word)| {
+            let row = index / words_per_row;
+            let base = (index % words_per_row) * WORD_BITS;
+            SetBits(word).map(move |offset| Seat {
+                row: row as i32,
+                col: (base + offset) as i32,
+            })
+        })
+    }
+
+    /// The first `len` adjacent available seats in a single row, if any
+    pub fn find_run(&self, len: usize) -> Option<Vec<Seat>> {
+        if len == 0 || len > self.col_count as usize {
+            return None;
+        }
+        for row in 0..self.row_count as usize {
+            let mut col = 0;
+            while let Some(start) = self.next_in_row(row, col, true) {
+                let end = self.next_in_row(row, start, false).unwrap_or(self.col_count as usize);
+                if end - start >= len {
+                    return Some(
+                        (start..start + len)
+                            .map(|col| Seat { row: row as i32, col: col as i32 })
+                            .collect(),
+                    );
+                }
+                col = end;
+            }
+        }
+        None
+    }
+
+    /// Every stretch of adjacent available seats, as (row, first col, col after the last)
+    pub fn available_runs(&self) -> Vec<(i32, i32, i32)> {
+        let mut runs = Vec::new();
+        for row in 0..self.row_count as usize {
+            let mut col = 0;
+            while let Some(start) = self.next_in_row(row, col, true) {
+                let end = self.next_in_row(row, start, false).unwrap_or(self.col_count as usize);
+                runs.push((row as i32, start as i32, end as i32));
+                col = end;
+            }
+        }
+        runs
+    }
+
+    /// Build from the verbose grid, by position within it
+    pub fn from_seat_statuses(row_count: i32, col_count: i32, seats: &[Vec<SeatStatus>]) -> Self {
+        let mut bitmap = Self::empty(row_count, col_count);
+        for (row, statuses) in seats.iter().enumerate() {
+            for (col, status) in statuses.iter().enumerate() {
+                if status.is_available {
+                    bitmap.set_available(row as i32, col as i32, true);
+                }
+            }
+        }
+        bitmap
+    }
+
+    /// The verbose grid used on the wire and in API responses
+    pub fn to_seat_statuses(&self) -> Vec<Vec<SeatStatus>> {
+        (0..self.row_count())
+            .map(|row| {
+                (0..self.col_count())
+                    .map(|col| SeatStatus {
+                        row,
+                        col,
+                        is_available: self.is_available(row, col),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn words_per_row(&self) -> usize {
+        (self.col_count as usize).div_ceil(WORD_BITS)
+    }
+
+    fn word_index(&self, row: usize, col: usize) -> usize {
+        row * self.words_per_row() + col / WORD_BITS
+    }
+
+    fn position(&self, row: i32, col: i32) -> Option<(usize, usize)> {
+        let row = usize::try_from(row).ok().filter(|&row| row < self.row_count as usize)?;
+        let col = usize::try_from(col).ok().filter(|&col| col < self.col_count as usize)?;
+        Some((row, col))
+    }
+
+    // First column at or after `from` in `row` whose availability is `available`
+    fn next_in_row(&self, row: usize, from: usize, available: bool) -> Option<usize> {
+        let cols = self.col_count as usize;
+        let mut col = from;
+        while col < cols {
+            let word = self.words[self.word_index(row, col)];
+            let word = if available { word } else { !word };
+            let masked = word & (u64::MAX << (col % WORD_BITS));
+            if masked != 0 {
+                let found = col - col % WORD_BITS + masked.trailing_zeros() as usize;
+                return (found < cols).then_some(found);
+            }
+            col += WORD_BITS - col % WORD_BITS;
+        }
+        None
+    }
+}
+
+fn bit(col: usize) -> u64 {
+    1u64 << (col % WORD_BITS)
+}
+
+// Offsets of the set bits of a word, lowest first
+struct SetBits(u64);
+
+impl Iterator for SetBits {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let offset = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(offset)
+    }
+}
+
+/// Area status as kept in the event service's store, with seats as a bitmap
+///
+/// Converts to and from `AreaStatus`, which stays the form sent to Kafka and
+/// returned by the API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactAreaStatus {
+    pub event_id: String,
+    pub area_id: String,
+    pub price: i32,
+    pub seats: SeatBitmap,
+}
+
+impl CompactAreaStatus {
+    pub fn available_seats(&self) -> i32 {
+        self.seats.available_count() as i32
+    }
+
+    /// Mark `seats` as taken
+    pub fn reserve(&mut self, seats: &[Seat]) {
+        for seat in seats {
+            self.seats.set_available(seat.row, seat.col, false);
+        }
+    }
+
+    /// Mark `seats` as available again
+    pub fn release(&mut self, seats: &[Seat]) {
+        for seat in seats {
+            self.seats.set_available(seat.row, seat.col, true);
+        }
+    }
+
+    pub fn to_area_status(&self) -> AreaStatus {
+        AreaStatus::from(self)
+    }
+}
+
+impl From<&AreaStatus> for CompactAreaStatus {
+    fn from(status: &AreaStatus) -> Self {
+        Self {
+            event_id: status.event_id.clone(),
+            area_id: status.area_id.clone(),
+            price: status.price,
+            seats: SeatBitmap::from_seat_statuses(status.row_count, status.col_count, &status.seats),
+        }
+    }
+}
+
+impl From<&CompactAreaStatus> for AreaStatus {
+    fn from(status: &CompactAreaStatus) -> Self {
+        Self {
+            event_id: status.event_id.clone(),
+            area_id: status.area_id.clone(),
+            price: status.price,
+            row_count: status.seats.row_count(),
+            col_count: status.seats.col_count(),
+            available_seats: status.available_seats(),
+            seats: status.seats.to_seat_statuses(),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::{Result, TicketMasterError};
+
+/// A registered user, as published on `state.user.profile` keyed by user id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub user_id: String,
+    pub email: String,
+    pub display_name: String,
+    pub phone: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Registration of a new user; the id is what reservations carry as `user_id`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterUser {
+    pub user_id: String,
+    pub email: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub phone: Option<String>,
+}
+
+/// Changes to a profile; fields left out keep their value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateUserProfile {
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    /// An empty phone number removes it
+    pub phone: Option<String>,
+}
+
+impl UserProfile {
+    pub fn register(request: RegisterUser, now: DateTime<Utc>) -> Result<Self> {
+        let user_id = request.user_id.trim();
+        if user_id.is_empty() {
+            return Err(TicketMasterError::InvalidArgument("User id must not be empty".to_string()));
+        }
+        let mut profile = Self {
+            user_id: user_id.to_string(),
+            email: String::new(),
+            display_name: String::new(),
+            phone: None,
+            created_at: now,
+            updated_at: now,
+        };
+        profile.apply(
+            UpdateUserProfile {
+                email: Some(request.email),
+                display_name: Some(request.display_name),
+                phone: request.phone,
+            },
+            now,
+        )?;
+        Ok(profile)
+    }
+
+    /// Apply `update`, leaving the profile as it was if any field is invalid
+    pub fn apply(&mut self, update: UpdateUserProfile, now: DateTime<Utc>) -> Result<()> {
+        let email = match update.email {
+            Some(email) => validate_email(&email)?,
+            None => self.email.clone(),
+        };
+        let display_name = match update.display_name {
+            Some(name) if name.trim().is_empty() => {
+                return Err(TicketMasterError::InvalidArgument("Display name must not be empty".to_string()))
+            }
+            Some(name) => name.trim().to_string(),
+            None => self.display_name.clone(),
+        };
+        let phone = match update.phone {
+            Some(phone) if phone.trim().is_empty() => None,
+            Some(phone) => Some(validate_phone(&phone)?),
+            None => self.phone.clone(),
+        };
+
+        self.email = email;
+        self.display_name = display_name;
+        self.phone = phone;
+        self.updated_at = now;
+        Ok(())
+    }
+}
+
+fn validate_email(email: &str) -> Result<String> {
+    let email = email.trim();
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && domain.contains('.') && !domain.contains('@') => Ok(email.to_string()),
+        _ => Err(TicketMasterError::InvalidArgument(format!("Invalid email address: {}", email))),
+    }
+}
+
+// Digits with an optional leading +, ignoring spaces and dashes
+fn validate_phone(phone: &str) -> Result<String> {
+    let phone: String = phone.chars().filter(|c| !matches!(c, ' ' | '-')).collect();
+    let digits = phone.strip_prefix('+').unwrap_or(&phone);
+    if digits.len() < 5 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(TicketMasterError::InvalidArgument(format!("Invalid phone number: {}", phone)));
+    }
+    Ok(phone)
+}
@@ -1,3 +1,8 @@
+use crate::RetryOperation;
+use rdkafka::types::RDKafkaErrorCode;
+use rocksdb::ErrorKind;
+use std::collections::BTreeMap;
+use std::fmt;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,6 +15,12 @@ pub enum TicketMasterError {
     
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+
+    #[error("Bincode error: {0}")]
+    Bincode(#[from] bincode::Error),
+
+    #[error("Protobuf error: {0}")]
+    Protobuf(#[from] prost::DecodeError),
     
     #[error("Configuration error: {0}")]
     Config(#[from] config::ConfigError),
@@ -28,12 +39,166 @@ pub enum TicketMasterError {
     
     #[error("Insufficient seats available")]
     InsufficientSeats,
+
+    #[error("Version conflict on {key}: expected version {expected}, found {actual}")]
+    VersionConflict { key: String, expected: u64, actual: u64 },
+    
+    #[error("Schema for subject {0} is not BACKWARD compatible with the registered version")]
+    IncompatibleSchema(String),
+
+    #[error("Schema Registry error: {0}")]
+    SchemaRegistry(String),
     
+    #[error("State query failed: {0}")]
+    StateQuery(String),
+
+    #[error("Secret error: {0}")]
+    Secret(String),
+
+    #[error("Object storage error: {0}")]
+    ObjectStorage(String),
+
+    #[error("State store not registered: {0}")]
+    StoreNotFound(String),
+
+    #[error("Reservation {reservation_id} can't take {event} while {state}")]
+    IllegalTransition { reservation_id: String, state: String, event: String },
+
     #[error("Invalid argument: {0}")]
     InvalidArgument(String),
+
+    #[error("Circuit breaker is open")]
+    CircuitOpen,
+
+    #[error("Panicked: {0}")]
+    Panicked(String),
+
+    #[error("Notification failed: {0}")]
+    Notification(String),
+
+    #[error("Unknown user: {0}")]
+    UnknownUser(String),
+
+    #[error("User already registered: {0}")]
+    UserExists(String),
+
+    #[error("Payment error: {0}")]
+    Payment(String),
+
+    #[error("Export error: {0}")]
+    Export(String),
+
+    #[error("Sink error: {0}")]
+    Sink(String),
+
+    /// An error with what was being done when it happened
+    #[error("{context}: {source}")]
+    Contextual {
+        context: ErrorContext,
+        #[source]
+        source: Box<TicketMasterError>,
+    },
     
     #[error("RocksDB error: {0}")]
     RocksDB(#[from] rocksdb::Error),
 }
 
+impl TicketMasterError {
+    /// This error, as having happened during `context`
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        TicketMasterError::Contextual { context, source: Box::new(self) }
+    }
+
+    /// The error under any context added to it
+    pub fn root_cause(&self) -> &TicketMasterError {
+        match self {
+            TicketMasterError::Contextual { source, .. } => source.root_cause(),
+            other => other,
+        }
+    }
+
+    /// Contexts added to this error, the outermost first
+    pub fn contexts(&self) -> Vec<&ErrorContext> {
+        let mut contexts = Vec::new();
+        let mut error = self;
+        while let TicketMasterError::Contextual { context, source } = error {
+            contexts.push(context);
+            error = source;
+        }
+        contexts
+    }
+
+    /// The kind of operation this error came from, if retrying it may succeed:
+    /// Kafka sends timing out or finding no broker, state store IO
+    pub fn transient_operation(&self) -> Option<RetryOperation> {
+        match self.root_cause() {
+            TicketMasterError::Kafka(e) => match e.rdkafka_error_code()? {
+                RDKafkaErrorCode::MessageTimedOut
+                | RDKafkaErrorCode::QueueFull
+                | RDKafkaErrorCode::RequestTimedOut
+                | RDKafkaErrorCode::BrokerTransportFailure
+                | RDKafkaErrorCode::AllBrokersDown
+                | RDKafkaErrorCode::LeaderNotAvailable
+                | RDKafkaErrorCode::NotLeaderForPartition
+                | RDKafkaErrorCode::NotEnoughReplicas
+                | RDKafkaErrorCode::NotEnoughReplicasAfterAppend
+                | RDKafkaErrorCode::NetworkException => Some(RetryOperation::KafkaSend),
+                _ => None,
+            },
+            TicketMasterError::Io(_) => Some(RetryOperation::StateStore),
+            TicketMasterError::RocksDB(e) => match e.kind() {
+                ErrorKind::IOError | ErrorKind::Busy | ErrorKind::TimedOut | ErrorKind::TryAgain | ErrorKind::Incomplete => {
+                    Some(RetryOperation::StateStore)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// What was being done when an error happened, with key/value details
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub operation: String,
+    pub component: String,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl ErrorContext {
+    pub fn new(operation: &str, component: &str) -> Self {
+        Self {
+            operation: operation.to_string(),
+            component: component.to_string(),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_metadata(mut self, key: &str, value: impl ToString) -> Self {
+        self.add_metadata(key, value);
+        self
+    }
+
+    pub fn add_metadata(&mut self, key: &str, value: impl ToString) {
+        self.metadata.insert(key.to_string(), value.to_string());
+    }
+}
+
+/// `operation in component (key=value, ...)`
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}", self.operation, self.component)?;
+        if !self.metadata.is_empty() {
+            let metadata: Vec<String> = self.metadata.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+            write!(f, " ({})", metadata.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Wrap errors with additional context; see `TicketMasterError::with_context`
+pub fn wrap_error(error: TicketMasterError, context: ErrorContext) -> TicketMasterError {
+    error.with_context(context)
+}
+
 pub type Result<T> = std::result::Result<T, TicketMasterError>;
\ No newline at end of file
@@ -0,0 +1,335 @@
+use crate::{event_area_key, AreaStatus, Reservation, ReservationState, ReservationType, Result, RocksDBStore, Seat, TicketMasterError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// Key prefixes of the export store
+const RESERVATION_PREFIX: &str = "reservation#";
+const AREA_PREFIX: &str = "area#";
+
+pub const RESERVATIONS_TABLE: &str = "reservations";
+pub const AREA_STATUSES_TABLE: &str = "area_statuses";
+
+/// File format of data exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One JSON object per line
+    #[default]
+    Jsonl,
+    /// Needs the `parquet` feature
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jsonl => "jsonl",
+            Self::Parquet => "parquet",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Jsonl => "application/x-ndjson",
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    /// Whether this build can write the format
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Self::Jsonl => true,
+            Self::Parquet => cfg!(feature = "parquet"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = TicketMasterError;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format.trim().to_ascii_lowercase().as_str() {
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(TicketMasterError::InvalidArgument(format!(
+                "Unknown export format {}, use jsonl or parquet",
+                format
+            ))),
+        }
+    }
+}
+
+/// A reservation as exported
+#[derive(Debug, Clone, Serialize)]
+pub struct ReservationRow {
+    pub snapshot_at: DateTime<Utc>,
+    pub reservation_id: String,
+    pub user_id: String,
+    pub event_id: String,
+    pub area_id: String,
+    pub state: ReservationState,
+    pub reservation_type: ReservationType,
+    pub num_of_seats: i32,
+    pub seats: Vec<Seat>,
+    pub failed_reason: Option<String>,
+}
+
+impl ReservationRow {
+    fn new(reservation: Reservation, snapshot_at: DateTime<Utc>) -> Self {
+        Self {
+            snapshot_at,
+            reservation_id: reservation.reservation_id,
+            user_id: reservation.user_id,
+            event_id: reservation.event_id,
+            area_id: reservation.area_id,
+            state: reservation.state,
+            reservation_type: reservation.reservation_type,
+            num_of_seats: reservation.num_of_seats,
+            seats: reservation.seats,
+            failed_reason: reservation.failed_reason.map(|reason| reason.message),
+        }
+    }
+}
+
+/// An area's seat counts as exported; the seat grid is left out
+#[derive(Debug, Clone, Serialize)]
+pub struct AreaStatusRow {
+    pub snapshot_at: DateTime<Utc>,
+    pub event_id: String,
+    pub area_id: String,
+    pub price: i32,
+    pub row_count: i32,
+    pub col_count: i32,
+    pub available_seats: i32,
+}
+
+impl AreaStatusRow {
+    fn new(area_status: AreaStatus, snapshot_at: DateTime<Utc>) -> Self {
+        Self {
+            snapshot_at,
+            event_id: area_status.event_id,
+            area_id: area_status.area_id,
+            price: area_status.price,
+            row_count: area_status.row_count,
+            col_count: area_status.col_count,
+            available_seats: area_status.available_seats,
+        }
+    }
+}
+
+// The rows of one exported table
+trait ExportRow: Serialize {
+    const TABLE: &'static str;
+
+    #[cfg(feature = "parquet")]
+    fn schema() -> arrow_schema::SchemaRef;
+}
+
+impl ExportRow for ReservationRow {
+    const TABLE: &'static str = RESERVATIONS_TABLE;
+
+    #[cfg(feature = "parquet")]
+    fn schema() -> arrow_schema::SchemaRef {
+        use arrow_schema::{DataType, Field, Schema};
+
+        Arc::new(Schema::new(vec![
+            Field::new("snapshot_at", parquet_format::timestamp(), false),
+            Field::new("reservation_id", DataType::Utf8, false),
+            Field::new("user_id", DataType::Utf8, false),
+            Field::new("event_id", DataType::Utf8, false),
+            Field::new("area_id", DataType::Utf8, false),
+            Field::new("state", DataType::Utf8, false),
+            Field::new("reservation_type", DataType::Utf8, false),
+            Field::new("num_of_seats", DataType::Int32, false),
+            Field::new("seats", parquet_format::seats(), false),
+            Field::new("failed_reason", DataType::Utf8, true),
+        ]))
+    }
+}
+
+impl ExportRow for AreaStatusRow {
+    const TABLE: &'static str = AREA_STATUSES_TABLE;
+
+    #[cfg(feature = "parquet")]
+    fn schema() -> arrow_schema::SchemaRef {
+        use arrow_schema::{DataType, Field, Schema};
+
+        Arc::new(Schema::new(vec![
+            Field::new("snapshot_at", parquet_format::timestamp(), false),
+            Field::new("event_id", DataType::Utf8, false),
+            Field::new("area_id", DataType::Utf8, false),
+            Field::new("price", DataType::Int32, false),
+            Field::new("row_count", DataType::Int32, false),
+            Field::new("col_count", DataType::Int32, false),
+            Field::new("available_seats", DataType::Int32, false),
+        ]))
+    }
+}
+
+/// One file of a snapshot, with the key to upload it under
+#[derive(Debug, Clone)]
+pub struct ExportFile {
+    pub key: String,
+    pub table: &'static str,
+    pub rows: usize,
+    pub data: Vec<u8>,
+}
+
+/// Keeps the latest reservations and area statuses in a RocksDB store and
+/// writes snapshots of them, one file per table and event
+#[derive(Clone)]
+pub struct SnapshotExporter {
+    store: Arc<RocksDBStore>,
+    format: ExportFormat,
+    prefix: String,
+}
+
+impl SnapshotExporter {
+    pub fn new(store: Arc<RocksDBStore>, format: ExportFormat) -> Result<Self> {
+        if !format.is_supported() {
+            return Err(TicketMasterError::InvalidArgument(
+                "Parquet exports need a build with the parquet feature".to_string(),
+            ));
+        }
+        Ok(Self { store, format, prefix: String::new() })
+    }
+
+    /// Put every key under `prefix`, e.g. `warehouse/ticket-master`
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_matches('/').to_string();
+        self
+    }
+
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    pub fn apply_reservation(&self, reservation: &Reservation) -> Result<()> {
+        self.store.put(&format!("{}{}", RESERVATION_PREFIX, reservation.reservation_id), reservation)
+    }
+
+    pub fn apply_area_status(&self, area_status: &AreaStatus) -> Result<()> {
+        let key = format!("{}{}", AREA_PREFIX, event_area_key(&area_status.event_id, &area_status.area_id));
+        self.store.put(&key, area_status)
+    }
+
+    /// Leave a deleted reservation out of the snapshots from now on
+    pub fn delete_reservation(&self, reservation_id: &str) -> Result<()> {
+        self.store.delete(&format!("{}{}", RESERVATION_PREFIX, reservation_id))
+    }
+
+    /// Leave a deleted area out of the snapshots from now on
+    pub fn delete_area(&self, event_area_id: &str) -> Result<()> {
+        self.store.delete(&format!("{}{}", AREA_PREFIX, event_area_id))
+    }
+
+    /// The files of a snapshot taken at `snapshot_at`, partitioned by date
+    /// and event as `<prefix>/<table>/date=<YYYY-MM-DD>/event_id=<event>/<table>-<time>.<format>`
+    pub fn snapshot(&self, snapshot_at: DateTime<Utc>) -> Result<Vec<ExportFile>> {
+        let mut reservations: BTreeMap<String, Vec<ReservationRow>> = BTreeMap::new();
+        for entry in self.store.scan_prefix::<Reservation>(RESERVATION_PREFIX) {
+            let (_, reservation) = entry?;
+            let row = ReservationRow::new(reservation, snapshot_at);
+            reservations.entry(row.event_id.clone()).or_default().push(row);
+        }
+        let mut area_statuses: BTreeMap<String, Vec<AreaStatusRow>> = BTreeMap::new();
+        for entry in self.store.scan_prefix::<AreaStatus>(AREA_PREFIX) {
+            let (_, area_status) = entry?;
+            let row = AreaStatusRow::new(area_status, snapshot_at);
+            area_statuses.entry(row.event_id.clone()).or_default().push(row);
+        }
+
+        let mut files = Vec::new();
+        for (event_id, rows) in &reservations {
+            files.push(self.file(event_id, snapshot_at, rows)?);
+        }
+        for (event_id, rows) in &area_statuses {
+            files.push(self.file(event_id, snapshot_at, rows)?);
+        }
+        Ok(files)
+    }
+
+    fn file<R: ExportRow>(&self, event_id: &str, snapshot_at: DateTime<Utc>, rows: &[R]) -> Result<ExportFile> {
+        let key = format!(
+            "{}/date={}/event_id={}/{}-{}.{}",
+            R::TABLE,
+            snapshot_at.format("%Y-%m-%d"),
+            partition_value(event_id),
+            R::TABLE,
+            snapshot_at.format("%Y%m%dT%H%M%SZ"),
+            self.format.extension()
+        );
+        Ok(ExportFile {
+            key: if self.prefix.is_empty() { key } else { format!("{}/{}", self.prefix, key) },
+            table: R::TABLE,
+            rows: rows.len(),
+            data: self.encode(rows)?,
+        })
+    }
+
+    fn encode<R: ExportRow>(&self, rows: &[R]) -> Result<Vec<u8>> {
+        match self.format {
+            ExportFormat::Jsonl => {
+                let mut data = Vec::new();
+                for row in rows {
+                    serde_json::to_writer(&mut data, row)?;
+                    data.push(b'\n');
+                }
+                Ok(data)
+            }
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => parquet_format::write(R::schema(), rows),
+            // Refused by `new`
+            #[cfg(not(feature = "parquet"))]
+            ExportFormat::Parquet => Err(TicketMasterError::Export("Parquet support is not built in".to_string())),
+        }
+    }
+}
+
+// A partition value is one key segment, whatever the event is called
+fn partition_value(value: &str) -> String {
+    value.replace(['/', '\\'], "_")
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_format {
+    use super::{Result, TicketMasterError};
+    use arrow_schema::{DataType, Field, Fields, SchemaRef, TimeUnit};
+    use parquet::arrow::ArrowWriter;
+    use serde::Serialize;
+    use std::sync::Arc;
+
+    // Rows per record batch, and so per row group at most
+    const BATCH_ROWS: usize = 1024;
+
+    pub(super) fn timestamp() -> DataType {
+        DataType::Timestamp(TimeUnit::Millisecond, Some("+00:00".into()))
+    }
+
+    pub(super) fn seats() -> DataType {
+        let seat = Fields::from(vec![Field::new("row", DataType::Int32, false), Field::new("col", DataType::Int32, false)]);
+        DataType::List(Arc::new(Field::new("item", DataType::Struct(seat), false)))
+    }
+
+    pub(super) fn write<R: Serialize>(schema: SchemaRef, rows: &[R]) -> Result<Vec<u8>> {
+        let mut decoder = arrow_json::ReaderBuilder::new(schema.clone())
+            .with_batch_size(BATCH_ROWS)
+            .build_decoder()
+            .map_err(export_error)?;
+        let mut writer = ArrowWriter::try_new(Vec::new(), schema, None).map_err(export_error)?;
+        for chunk in rows.chunks(BATCH_ROWS) {
+            decoder.serialize(chunk).map_err(export_error)?;
+            if let Some(batch) = decoder.flush().map_err(export_error)? {
+                writer.write(&batch).map_err(export_error)?;
+            }
+        }
+        writer.into_inner().map_err(export_error)
+    }
+
+    fn export_error(error: impl std::fmt::Display) -> TicketMasterError {
+        TicketMasterError::Export(error.to_string())
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{global_table_position, MessageConsumer, RocksDBStore, TicketMasterError};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use futures::future::join_all;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::timeout;
+
+/// A dependency a service needs before it can take traffic
+#[async_trait::async_trait]
+pub trait ReadinessCheck: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// `Ok` once the dependency is usable, otherwise why it isn't
+    async fn check(&self) -> std::result::Result<(), String>;
+}
+
+/// How one dependency fared in a readiness probe
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyStatus {
+    pub name: String,
+    pub ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of a readiness probe; ready only if every dependency is
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub dependencies: Vec<DependencyStatus>,
+}
+
+/// Runs every registered readiness check at once, each bounded by a timeout
+#[derive(Clone)]
+pub struct ReadinessProbe {
+    checks: Vec<Arc<dyn ReadinessCheck>>,
+    timeout: Duration,
+}
+
+impl ReadinessProbe {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            checks: Vec::new(),
+            timeout,
+        }
+    }
+
+    pub fn with_check<R: ReadinessCheck + 'static>(mut self, check: R) -> Self {
+        self.checks.push(Arc::new(check));
+        self
+    }
+
+    pub async fn check(&self) -> Readiness {
+        let dependencies = join_all(self.checks.iter().map(|check| async move {
+            let result = match timeout(self.timeout, check.check()).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("no answer within {:?}", self.timeout)),
+            };
+            DependencyStatus {
+                name: check.name().to_string(),
+                ready: result.is_ok(),
+                error: result.err(),
+            }
+        }))
+        .await;
+        Readiness {
+            ready: dependencies.iter().all(|dependency| dependency.ready),
+            dependencies,
+        }
+    }
+}
+
+/// `/health/live` and `/health/ready` for services without an HTTP API
+///
+/// Readiness answers 503 with the failing dependencies until `probe` passes.
+pub fn health_router(probe: ReadinessProbe) -> axum::Router {
+    axum::Router::new()
+        .route("/health/live", axum::routing::get(|| async { StatusCode::OK }))
+        .route(
+            "/health/ready",
+            axum::routing::get(|State(probe): State<ReadinessProbe>| async move {
+                let readiness = probe.check().await;
+                let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+                (status, Json(readiness))
+            }),
+        )
+        .with_state(probe)
+}
+
+/// Ready once the brokers answer a metadata request for every topic
+pub struct KafkaReadiness {
+    consumer: Arc<dyn MessageConsumer>,
+    topics: Vec<&'static str>,
+}
+
+impl KafkaReadiness {
+    pub fn new<C: MessageConsumer>(consumer: C, topics: &[&'static str]) -> Self {
+        Self {
+            consumer: Arc::new(consumer),
+            topics: topics.to_vec(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadinessCheck for KafkaReadiness {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    async fn check(&self) -> std::result::Result<(), String> {
+        let consumer = self.consumer.clone();
+        let topics = self.topics.clone();
+        // Metadata requests block until the brokers answer
+        tokio::task::spawn_blocking(move || {
+            for topic in topics {
+                if consumer.partition_count(topic).map_err(|e| e.to_string())? == 0 {
+                    return Err(format!("topic {} does not exist", topic));
+                }
+            }
+            Ok(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+/// Ready once the Schema Registry lists its subjects
+pub struct SchemaRegistryReadiness {
+    url: String,
+    http: reqwest::Client,
+    basic_auth: Option<(String, Option<String>)>,
+}
+
+impl SchemaRegistryReadiness {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: format!("{}/subjects", url.trim_end_matches('/')),
+            http: reqwest::Client::new(),
+            basic_auth: None,
+        }
+    }
+
+    pub fn with_basic_auth(mut self, user: &str, password: Option<&str>) -> Self {
+        self.basic_auth = Some((user.to_string(), password.map(str::to_string)));
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadinessCheck for SchemaRegistryReadiness {
+    fn name(&self) -> &str {
+        "schema_registry"
+    }
+
+    async fn check(&self) -> std::result::Result<(), String> {
+        let mut request = self.http.get(&self.url);
+        if let Some((user, password)) = &self.basic_auth {
+            request = request.basic_auth(user, password.as_ref());
+        }
+        request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|e| TicketMasterError::SchemaRegistry(e.to_string()).to_string())
+    }
+}
+
+/// Ready once another service's readiness endpoint answers 2xx, e.g.
+/// `http://ticket-service:8080/health/ready`
+pub struct HttpReadiness {
+    name: String,
+    url: String,
+    http: reqwest::Client,
+}
+
+impl HttpReadiness {
+    pub fn new(name: &str, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadinessCheck for HttpReadiness {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn check(&self) -> std::result::Result<(), String> {
+        self.http
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Ready once a global table's store has caught up with its topic
+///
+/// The end offsets of the first check are the target, so records arriving
+/// meanwhile don't keep a busy topic from ever counting as restored. Once
+/// restored the table stays ready.
+pub struct GlobalTableReadiness {
+    name: String,
+    topic: &'static str,
+    store: Arc<RocksDBStore>,
+    consumer: Arc<dyn MessageConsumer>,
+    state: Mutex<Restoration>,
+}
+
+#[derive(Default)]
+enum Restoration {
+    #[default]
+    Started,
+    Restoring(HashMap<i32, i64>),
+    Restored,
+}
+
+impl GlobalTableReadiness {
+    /// `consumer` only fetches the topic's end offsets; it is never subscribed
+    pub fn new<C: MessageConsumer>(name: &str, topic: &'static str, store: Arc<RocksDBStore>, consumer: C) -> Self {
+        Self {
+            name: name.to_string(),
+            topic,
+            store,
+            consumer: Arc::new(consumer),
+            state: Mutex::new(Restoration::Started),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, Restoration> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadinessCheck for GlobalTableReadiness {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn check(&self) -> std::result::Result<(), String> {
+        if matches!(*self.state(), Restoration::Started) {
+            let consumer = self.consumer.clone();
+            let topic = self.topic;
+            let lag = tokio::task::spawn_blocking(move || consumer.group_lag(&[topic]))
+                .await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| e.to_string())?;
+            let target = lag.into_iter().map(|partition| (partition.partition, partition.high_watermark)).collect();
+            let mut state = self.state();
+            if matches!(*state, Restoration::Started) {
+                *state = Restoration::Restoring(target);
+            }
+        }
+
+        let mut state = self.state();
+        let Restoration::Restoring(target) = &*state else {
+            return Ok(());
+        };
+        let mut behind = 0;
+        for (partition, end) in target {
+            let position = global_table_position(&self.store, self.topic, *partition)
+                .map_err(|e| e.to_string())?
+                .unwrap_or(0);
+            behind += (end - position).max(0);
+        }
+        if behind > 0 {
+            return Err(format!("{} records of {} still to restore", behind, self.topic));
+        }
+        *state = Restoration::Restored;
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{audit_entity_key, AuditEntity, AuditRecord, MessageConsumer, MessageProducer, Result, RocksDBStore, Topics};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, warn};
+
+/// Audit records by entity, kept in a `RocksDBStore`
+///
+/// Records are only ever added. Each is keyed by its entity and audit id, so
+/// appending a record again leaves a single copy.
+#[derive(Clone)]
+pub struct AuditLog {
+    store: Arc<RocksDBStore>,
+}
+
+impl AuditLog {
+    pub fn new(store: Arc<RocksDBStore>) -> Self {
+        Self { store }
+    }
+
+    pub fn store(&self) -> &Arc<RocksDBStore> {
+        &self.store
+    }
+
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        self.store.put(&format!("{}#{}", record.entity_key(), record.audit_id), record)
+    }
+
+    /// Records of one entity, oldest first
+    pub fn history(&self, entity: AuditEntity, entity_id: &str) -> Result<Vec<AuditRecord>> {
+        let prefix = format!("{}#", audit_entity_key(entity, entity_id));
+        let mut records = self
+            .store
+            .scan_prefix::<AuditRecord>(&prefix)
+            .map(|entry| entry.map(|(_, record)| record))
+            .collect::<Result<Vec<_>>>()?;
+        // Ids with another '#' would also match the prefix
+        records.retain(|record| record.entity_id == entity_id);
+        records.sort_by_key(|record| record.occurred_at);
+        Ok(records)
+    }
+}
+
+/// Publish `record` on `state.audit`, keyed by its entity
+///
+/// Always JSON, whatever serde the other topics use.
+pub async fn send_audit_record<P: MessageProducer>(producer: &P, record: &AuditRecord) -> Result<()> {
+    let payload = serde_json::to_vec(record)?;
+    producer
+        .send_bytes_with_headers(Topics::STATE_AUDIT, None, &record.entity_key(), Some(&payload), &[])
+        .await
+}
+
+/// Append every record on `state.audit` to `log`
+///
+/// The consumer should use a group of its own, so each instance keeps the
+/// whole log.
+pub fn spawn_audit_log<C: MessageConsumer>(consumer: C, log: AuditLog) -> Result<JoinHandle<()>> {
+    consumer.subscribe(&[Topics::STATE_AUDIT])?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let message = match consumer.recv_message(Duration::from_secs(1)).await {
+                Ok(Some(message)) => message,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("Audit log failed to receive: {}", e);
+                    continue;
+                }
+            };
+            let Some(payload) = message.payload.as_deref() else {
+                continue;
+            };
+
+            match serde_json::from_slice::<AuditRecord>(payload) {
+                Ok(record) => {
+                    if let Err(e) = log.append(&record) {
+                        error!("Failed to append audit record {}: {}", record.audit_id, e);
+                    }
+                }
+                Err(e) => warn!("Skipping undecodable audit record at offset {}: {}", message.offset, e),
+            }
+        }
+    }))
+}
@@ -1,39 +1,86 @@
-use crate::{Result, TicketMasterError};
+use crate::{codec_error, Result, TicketMasterError, SUBJECT_SCHEMAS};
 use apache_avro::{Schema, Writer, Reader, from_value, to_value};
+use apache_avro::schema_compatibility::SchemaCompatibility;
 use schema_registry_converter::async_impl::{
-    schema_registry::SrSettings,
+    schema_registry::{SrSettings, get_all_subjects, get_schema_by_subject, post_schema},
     avro::{AvroEncoder, AvroDecoder},
 };
+use schema_registry_converter::schema_registry_common::{SchemaType, SubjectNameStrategy, SuppliedSchema};
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use tracing::info;
 
 /// Avro serializer with Schema Registry support
 pub struct AvroSerializer {
     encoder: AvroEncoder,
     decoder: AvroDecoder,
     schemas: HashMap<String, Schema>,
+    sr_settings: SrSettings,
 }
 
 impl AvroSerializer {
     pub async fn new(schema_registry_url: &str) -> Result<Self> {
-        let sr_settings = SrSettings::new(schema_registry_url.to_string());
+        Self::with_settings(SrSettings::new(schema_registry_url.to_string())).await
+    }
+
+    pub async fn with_settings(sr_settings: SrSettings) -> Result<Self> {
         let encoder = AvroEncoder::new(sr_settings.clone());
-        let decoder = AvroDecoder::new(sr_settings);
+        let decoder = AvroDecoder::new(sr_settings.clone());
         
         Ok(Self {
             encoder,
             decoder,
             schemas: HashMap::new(),
+            sr_settings,
         })
     }
 
+    /// Register all schemas from `avro_schemas` with the Schema Registry
+    ///
+    /// Each schema is checked for BACKWARD compatibility against the latest
+    /// version of its subject first, so an incompatible schema fails at startup
+    /// rather than on the first send.
+    pub async fn register_schemas(&self) -> Result<()> {
+        let existing = get_all_subjects(&self.sr_settings).await
+            .map_err(|e| TicketMasterError::SchemaRegistry(format!("Failed to list subjects: {}", e)))?;
+
+        for (subject, schema_str) in SUBJECT_SCHEMAS {
+            let schema = Schema::parse_str(schema_str)?;
+
+            if existing.iter().any(|s| s == subject) {
+                // RecordNameStrategy uses the given name as the subject verbatim
+                let strategy = SubjectNameStrategy::RecordNameStrategy(subject.to_string());
+                let latest = get_schema_by_subject(&self.sr_settings, &strategy).await
+                    .map_err(|e| TicketMasterError::SchemaRegistry(format!("Failed to fetch latest schema for {}: {}", subject, e)))?;
+                let latest_schema = Schema::parse_str(&latest.schema)?;
+
+                // BACKWARD: consumers on the new schema can read data written with the latest one
+                if !SchemaCompatibility::can_read(&latest_schema, &schema) {
+                    return Err(TicketMasterError::IncompatibleSchema(subject.to_string()));
+                }
+            }
+
+            let supplied = SuppliedSchema {
+                name: None,
+                schema_type: SchemaType::Avro,
+                schema: schema.canonical_form(),
+                references: vec![],
+            };
+            let registered = post_schema(&self.sr_settings, subject.to_string(), supplied).await
+                .map_err(|e| TicketMasterError::SchemaRegistry(format!("Failed to register schema for {}: {}", subject, e)))?;
+            info!("Registered schema for {} with id {}", subject, registered.id);
+        }
+
+        Ok(())
+    }
+
     pub async fn serialize<T>(&self, subject: &str, value: &T) -> Result<Vec<u8>>
     where
         T: Serialize,
     {
         let avro_value = to_value(value)?;
         let encoded = self.encoder.encode(avro_value, subject).await
-            .map_err(|e| TicketMasterError::InvalidArgument(format!("Avro encoding error: {}", e)))?;
+            .map_err(|e| codec_error("Avro encoding", e))?;
         Ok(encoded)
     }
 
@@ -42,7 +89,7 @@ impl AvroSerializer {
         T: for<'de> Deserialize<'de>,
     {
         let decoded = self.decoder.decode(Some(data)).await
-            .map_err(|e| TicketMasterError::InvalidArgument(format!("Avro decoding error: {}", e)))?;
+            .map_err(|e| codec_error("Avro decoding", e))?;
         
         let value = from_value::<T>(&decoded.value)?;
         Ok(value)
@@ -84,7 +131,7 @@ impl AvroKafkaProducer {
 
     pub async fn send_avro<T>(&self, topic: &str, key: &str, value: &T, subject: &str) -> Result<()>
     where
-        T: Serialize,
+        T: crate::SerdeValue,
     {
         if let Some(serializer) = &self.serializer {
             let serialized = serializer.serialize(subject, value).await?;
@@ -98,7 +145,7 @@ impl AvroKafkaProducer {
 
     pub async fn send_json<T>(&self, topic: &str, key: &str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: crate::SerdeValue,
     {
         self.producer.send(topic, key, value).await
     }
@@ -141,18 +188,17 @@ impl AvroKafkaConsumer {
 
     pub async fn deserialize_avro<T>(&self, message: &crate::KafkaMessage) -> Result<T>
     where
-        T: for<'de> Deserialize<'de>,
+        T: crate::SerdeValue,
     {
         if let Some(deserializer) = &self.deserializer {
             if let Some(payload) = &message.payload {
-                let bytes = payload.as_bytes();
-                deserializer.deserialize(bytes).await
+                deserializer.deserialize(payload).await
             } else {
                 Err(TicketMasterError::InvalidArgument("Empty message payload".to_string()))
             }
         } else {
-            // Fallback to JSON
-            message.deserialize_value()
+            // Fallback to the message's configured serde
+            message.deserialize_value().await
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,46 @@
+use crate::{MessageConsumer, Result};
+use tracing::{info, warn};
+
+/// Pauses consumption while too many messages are in flight or the state
+/// stores are stalling writes
+///
+/// Consumption resumes once the in-flight count has dropped to half the limit
+/// and no store is stalled, so the consumer does not flap around the threshold.
+#[derive(Debug)]
+pub struct Backpressure {
+    max_in_flight: usize,
+    resume_at: usize,
+    paused: bool,
+}
+
+impl Backpressure {
+    pub fn new(max_in_flight: usize) -> Self {
+        let max_in_flight = max_in_flight.max(1);
+        Self {
+            max_in_flight,
+            resume_at: max_in_flight / 2,
+            paused: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pause or resume `consumer` according to the current load
+    pub fn update<C: MessageConsumer>(&mut self, consumer: &C, in_flight: usize, write_stalled: bool) -> Result<()> {
+        if !self.paused && (in_flight >= self.max_in_flight || write_stalled) {
+            consumer.pause()?;
+            self.paused = true;
+            warn!(
+                "Pausing consumption: {} messages in flight (limit {}), write stalled: {}",
+                in_flight, self.max_in_flight, write_stalled
+            );
+        } else if self.paused && in_flight <= self.resume_at && !write_stalled {
+            consumer.resume()?;
+            self.paused = false;
+            info!("Resuming consumption: {} messages in flight", in_flight);
+        }
+        Ok(())
+    }
+}
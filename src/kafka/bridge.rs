@@ -0,0 +1,77 @@
+use crate::{AreaStatus, MessageConsumer, Result, Topics};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, warn};
+
+const DEFAULT_HUB_CAPACITY: usize = 1024;
+
+/// Broadcasts area status updates to local subscribers such as WebSocket clients
+///
+/// Subscribers that fall more than the channel capacity behind skip the
+/// missed updates; each update carries the whole area, so the next one
+/// brings them up to date.
+#[derive(Clone)]
+pub struct AreaStatusHub {
+    sender: broadcast::Sender<Arc<AreaStatus>>,
+}
+
+impl Default for AreaStatusHub {
+    fn default() -> Self {
+        Self::new(DEFAULT_HUB_CAPACITY)
+    }
+}
+
+impl AreaStatusHub {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<AreaStatus>> {
+        self.sender.subscribe()
+    }
+
+    /// Send `status` to every subscriber; returns how many received it
+    pub fn publish(&self, status: AreaStatus) -> usize {
+        self.sender.send(Arc::new(status)).unwrap_or(0)
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+/// Republish every update on `state.event.area_status` to `hub`
+///
+/// The consumer should use a group of its own (and not commit offsets), so
+/// each instance of a service sees every update.
+pub fn spawn_area_status_bridge<C: MessageConsumer>(consumer: C, hub: AreaStatusHub) -> Result<JoinHandle<()>> {
+    consumer.subscribe(&[Topics::STATE_EVENT_AREA_STATUS])?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let message = match consumer.recv_message(Duration::from_secs(1)).await {
+                Ok(Some(message)) => message,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("Area status bridge failed to receive: {}", e);
+                    continue;
+                }
+            };
+            // Subscribers of a deleted area simply get no more updates
+            if message.is_tombstone() {
+                continue;
+            }
+
+            match message.deserialize_value::<AreaStatus>().await {
+                Ok(status) => {
+                    let receivers = hub.publish(status);
+                    debug!("Bridged area status {:?} to {} subscribers", message.key, receivers);
+                }
+                Err(e) => warn!("Skipping undecodable area status at offset {}: {}", message.offset, e),
+            }
+        }
+    }))
+}
@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+struct CacheEntry<V> {
+    value: V,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+struct CacheInner<K, V> {
+    entries: HashMap<K, CacheEntry<V>>,
+    // Keys by last use, least recently used first
+    recency: BTreeMap<u64, K>,
+    clock: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> CacheInner<K, V> {
+    fn touch(&mut self, key: &K) {
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = self.clock;
+            self.recency.insert(self.clock, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.last_used);
+        Some(entry.value)
+    }
+}
+
+/// In-memory store holding at most `capacity` entries, each for at most `ttl`
+///
+/// When full, the least recently read or written entry is evicted. Expired
+/// entries are dropped when read or by `purge_expired`.
+pub struct CacheStore<K, V> {
+    inner: Arc<Mutex<CacheInner<K, V>>>,
+    capacity: Arc<AtomicUsize>,
+    ttl: Duration,
+}
+
+impl<K, V> CacheStore<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                clock: 0,
+            })),
+            capacity: Arc::new(AtomicUsize::new(capacity.max(1))),
+            ttl,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut inner = self.inner.lock().unwrap();
+        let expired = inner.entries.get(key)?.inserted_at.elapsed() > self.ttl;
+        if expired {
+            inner.remove(key);
+            return None;
+        }
+        inner.touch(key);
+        inner.entries.get(key).map(|entry| entry.value.clone())
+    }
+
+    pub fn put(&self, key: K, value: V) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.remove(&key);
+        while inner.entries.len() >= self.capacity() {
+            let Some((_, oldest)) = inner.recency.pop_first() else {
+                break;
+            };
+            inner.entries.remove(&oldest);
+        }
+
+        inner.clock += 1;
+        let last_used = inner.clock;
+        inner.recency.insert(last_used, key.clone());
+        inner.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                inserted_at: Instant::now(),
+                last_used,
+            },
+        );
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.inner.lock().unwrap().remove(key)
+    }
+
+    /// Drop entries older than the TTL; returns how many
+    pub fn purge_expired(&self) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let expired: Vec<K> = inner
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.inserted_at.elapsed() > self.ttl)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            inner.remove(key);
+        }
+        expired.len()
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.recency.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Change the capacity of this store and its clones; a smaller one is
+    /// reached as entries are next put
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity.max(1), Ordering::Relaxed);
+    }
+}
+
+impl<K, V> Clone for CacheStore<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            capacity: Arc::clone(&self.capacity),
+            ttl: self.ttl,
+        }
+    }
+}
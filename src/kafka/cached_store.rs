@@ -0,0 +1,108 @@
+use crate::{CacheStore, Result, RocksDBStore};
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Keeps hot values of a `RocksDBStore` in memory, writing through to RocksDB
+///
+/// Reads are served from memory once a key has been read or written; writes
+/// go to RocksDB first and only then to the cache. Beyond `capacity` keys,
+/// the least recently used are dropped. Writes made to the RocksDB store
+/// directly are not seen until the key is `invalidate`d.
+pub struct CachedStore<K, V> {
+    store: Arc<RocksDBStore>,
+    // Values never expire; RocksDB stays the source of truth
+    cache: CacheStore<K, V>,
+}
+
+impl<K, V> CachedStore<K, V>
+where
+    K: Hash + Eq + Clone + ToString,
+    V: Clone + Serialize + for<'de> Deserialize<'de>,
+{
+    pub fn new(store: Arc<RocksDBStore>, capacity: usize) -> Self {
+        Self {
+            store,
+            cache: CacheStore::new(capacity, Duration::MAX),
+        }
+    }
+
+    pub fn store(&self) -> &Arc<RocksDBStore> {
+        &self.store
+    }
+
+    pub fn get(&self, key: &K) -> Result<Option<V>> {
+        if let Some(value) = self.cache.get(key) {
+            return Ok(Some(value));
+        }
+        // Held so a concurrent put can't be overwritten by the older value read here
+        let name = key.to_string();
+        let _guard = self.store.lock_key(&name);
+        let value: Option<V> = self.store.get(&name)?;
+        if let Some(value) = &value {
+            self.cache.put(key.clone(), value.clone());
+        }
+        Ok(value)
+    }
+
+    pub fn put(&self, key: K, value: V) -> Result<()> {
+        let name = key.to_string();
+        let _guard = self.store.lock_key(&name);
+        self.store.put(&name, &value)?;
+        self.cache.put(key, value);
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &K) -> Result<Option<V>> {
+        let name = key.to_string();
+        let _guard = self.store.lock_key(&name);
+        let existing = match self.cache.remove(key) {
+            Some(value) => Some(value),
+            None => self.store.get(&name)?,
+        };
+        self.store.delete(&name)?;
+        Ok(existing)
+    }
+
+    pub fn contains_key(&self, key: &K) -> Result<bool> {
+        Ok(self.cache.get(key).is_some() || self.store.contains_key(&key.to_string())?)
+    }
+
+    /// Drop `key` from memory, so the next read goes to RocksDB
+    pub fn invalidate(&self, key: &K) {
+        self.cache.remove(key);
+    }
+
+    pub fn clear(&self) {
+        self.cache.clear();
+    }
+
+    /// Number of keys held in memory
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    /// Change the capacity of this store and its clones; a smaller one is
+    /// reached as keys are next added
+    pub fn set_capacity(&self, capacity: usize) {
+        self.cache.set_capacity(capacity);
+    }
+}
+
+impl<K, V> Clone for CachedStore<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+            cache: self.cache.clone(),
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{KafkaProducer, Result, RocksDBStore, TicketMasterError, TopicResolver, STATISTICS_INTERVAL_PROPERTY};
+use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
+use rdkafka::client::DefaultClientContext;
+use rdkafka::consumer::{BaseConsumer, Consumer, StreamConsumer};
+use rdkafka::types::RDKafkaErrorCode;
+use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+const METADATA_TIMEOUT: Duration = Duration::from_secs(10);
+const RESTORE_POLL_TIMEOUT: Duration = Duration::from_secs(1);
+const RESTORE_MAX_IDLE_POLLS: u32 = 30;
+
+/// Name of the compacted topic backing a state store
+pub fn changelog_topic(store_name: &str) -> String {
+    format!("{}-changelog", store_name)
+}
+
+// The checkpoint sits next to the store directory and holds the next
+// changelog offset to apply
+fn checkpoint_path(store: &RocksDBStore) -> PathBuf {
+    let mut path = store.path().as_os_str().to_owned();
+    path.push(".checkpoint");
+    PathBuf::from(path)
+}
+
+fn read_checkpoint(store: &RocksDBStore) -> Option<i64> {
+    std::fs::read_to_string(checkpoint_path(store))
+        .ok()
+        .and_then(|content| content.trim().parse().ok())
+}
+
+fn write_checkpoint(store: &RocksDBStore, offset: i64) -> Result<()> {
+    std::fs::write(checkpoint_path(store), offset.to_string())?;
+    Ok(())
+}
+
+fn restore_consumer_config(config: &ClientConfig) -> ClientConfig {
+    let mut config = config.clone();
+    config.set("enable.auto.commit", "false");
+    config
+}
+
+/// A single store mutation; `value: None` is a delete (tombstone)
+#[derive(Debug, Clone)]
+struct ChangelogRecord {
+    topic: String,
+    partition: Option<i32>,
+    key: String,
+    value: Option<Vec<u8>>,
+}
+
+/// Mirrors state store writes to compacted changelog topics and restores
+/// stores by replaying them
+///
+/// Writes are queued and produced in order by a background task, so store
+/// puts stay synchronous.
+#[derive(Clone)]
+pub struct Changelog {
+    sender: mpsc::UnboundedSender<ChangelogRecord>,
+    config: ClientConfig,
+    topics: TopicResolver,
+}
+
+impl Changelog {
+    /// Create a changelog writer; must be called from within a Tokio runtime
+    pub fn new(mut config: ClientConfig) -> Result<Self> {
+        // Its clients have no metrics to record statistics in
+        config.remove(STATISTICS_INTERVAL_PROPERTY);
+        let producer = KafkaProducer::new(config.clone())?;
+        let (sender, mut receiver) = mpsc::unbounded_channel::<ChangelogRecord>();
+
+        tokio::spawn(async move {
+            while let Some(record) = receiver.recv().await {
+                if let Err(e) = producer
+                    .send_bytes(&record.topic, record.partition, &record.key, record.value.as_deref())
+                    .await
+                {
+                    error!("Failed to write changelog record {} to {}: {}", record.key, record.topic, e);
+                }
+            }
+        });
+
+        Ok(Self {
+            sender,
+            config,
+            topics: TopicResolver::default(),
+        })
+    }
+
+    /// Write to and restore from the cluster topics `topics` resolves changelog names to
+    pub fn with_topic_resolver(mut self, topics: TopicResolver) -> Self {
+        self.topics = topics;
+        self
+    }
+
+    /// Queue a put for the changelog topic
+    pub fn log_put(&self, topic: &str, partition: Option<i32>, key: &str, value: &[u8]) {
+        self.log(topic, partition, key, Some(value.to_vec()));
+    }
+
+    /// Queue a tombstone for the changelog topic
+    pub fn log_delete(&self, topic: &str, partition: Option<i32>, key: &str) {
+        self.log(topic, partition, key, None);
+    }
+
+    fn log(&self, topic: &str, partition: Option<i32>, key: &str, value: Option<Vec<u8>>) {
+        let record = ChangelogRecord {
+            topic: self.topics.resolve(topic).into_owned(),
+            partition,
+            key: key.to_string(),
+            value,
+        };
+        if self.sender.send(record).is_err() {
+            error!("Changelog writer for {} is closed, dropping record {}", topic, key);
+        }
+    }
+
+    /// Create the compacted changelog topic if it does not exist yet
+    pub async fn ensure_topic(&self, topic: &str, num_partitions: i32) -> Result<()> {
+        let topic = &*self.topics.resolve(topic);
+        let admin: AdminClient<DefaultClientContext> = self.config.create()?;
+        let new_topic = NewTopic::new(topic, num_partitions, TopicReplication::Fixed(-1))
+            .set("cleanup.policy", "compact");
+
+        let results = admin.create_topics([&new_topic], &AdminOptions::new()).await?;
+        for result in results {
+            match result {
+                Ok(name) => info!("Created changelog topic {}", name),
+                Err((_, RDKafkaErrorCode::TopicAlreadyExists)) => {}
+                Err((name, code)) => {
+                    return Err(TicketMasterError::InvalidArgument(format!(
+                        "Failed to create changelog topic {}: {}",
+                        name, code
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replay a changelog partition into `store`
+    ///
+    /// Replay starts from the store's checkpoint when one exists, otherwise
+    /// from the beginning. Returns the number of records applied. A missing
+    /// topic restores nothing.
+    pub fn restore(&self, store: &RocksDBStore, topic: &str, partition: i32) -> Result<u64> {
+        let topic = &*self.topics.resolve(topic);
+        let consumer: BaseConsumer = restore_consumer_config(&self.config).create()?;
+
+        let metadata = consumer.fetch_metadata(Some(topic), METADATA_TIMEOUT)?;
+        let exists = metadata
+            .topics()
+            .iter()
+            .any(|t| t.name() == topic && t.error().is_none() && t.partitions().iter().any(|p| p.id() == partition));
+        if !exists {
+            info!("Changelog {} partition {} does not exist yet, nothing to restore", topic, partition);
+            return Ok(0);
+        }
+
+        let (low, high) = consumer.fetch_watermarks(topic, partition, METADATA_TIMEOUT)?;
+        let start = read_checkpoint(store).unwrap_or(low).max(low);
+        if high <= start {
+            return Ok(0);
+        }
+
+        let mut tpl = TopicPartitionList::new();
+        tpl.add_partition_offset(topic, partition, Offset::Offset(start))?;
+        consumer.assign(&tpl)?;
+
+        let mut restored = 0;
+        let mut idle_polls = 0;
+        loop {
+            match consumer.poll(RESTORE_POLL_TIMEOUT) {
+                Some(Ok(message)) => {
+                    idle_polls = 0;
+                    if let Some(key) = message.key() {
+                        store.restore_raw(key, message.payload())?;
+                        restored += 1;
+                    }
+                    if message.offset() >= high - 1 {
+                        break;
+                    }
+                }
+                Some(Err(e)) => return Err(TicketMasterError::Kafka(e)),
+                None => {
+                    idle_polls += 1;
+                    if idle_polls >= RESTORE_MAX_IDLE_POLLS {
+                        warn!("Timed out restoring {} partition {} after {} records", topic, partition, restored);
+                        return Err(TicketMasterError::InvalidArgument(format!(
+                            "Timed out restoring changelog {} partition {}",
+                            topic, partition
+                        )));
+                    }
+                }
+            }
+        }
+
+        write_checkpoint(store, high)?;
+        info!("Restored {} records from {} partition {}", restored, topic, partition);
+        Ok(restored)
+    }
+}
+
+/// Keeps local copies of stores warm by continuously applying their changelog
+///
+/// A standby never serves traffic. Its stores and checkpoints use the same
+/// layout as an active instance, so promoting it only replays the changelog
+/// tail written since the last checkpoint.
+pub struct StandbyReplica {
+    consumer: StreamConsumer,
+    stores: HashMap<i32, Arc<RocksDBStore>>,
+    positions: HashMap<i32, i64>,
+}
+
+impl StandbyReplica {
+    /// Open a store per changelog partition via `open_store` and start tailing
+    /// each partition from its checkpoint
+    pub fn new<F>(config: &ClientConfig, topic: &str, open_store: F) -> Result<Self>
+    where
+        F: Fn(i32) -> Result<Arc<RocksDBStore>>,
+    {
+        let consumer: StreamConsumer = restore_consumer_config(config).create()?;
+
+        let metadata = consumer.fetch_metadata(Some(topic), METADATA_TIMEOUT)?;
+        let partitions: Vec<i32> = metadata
+            .topics()
+            .iter()
+            .filter(|t| t.name() == topic && t.error().is_none())
+            .flat_map(|t| t.partitions().iter().map(|p| p.id()))
+            .collect();
+        if partitions.is_empty() {
+            return Err(TicketMasterError::InvalidArgument(format!(
+                "Changelog topic {} does not exist",
+                topic
+            )));
+        }
+
+        let mut stores = HashMap::new();
+        let mut tpl = TopicPartitionList::new();
+        for partition in partitions {
+            let store = open_store(partition)?;
+            let offset = read_checkpoint(&store).map(Offset::Offset).unwrap_or(Offset::Beginning);
+            tpl.add_partition_offset(topic, partition, offset)?;
+            stores.insert(partition, store);
+        }
+        consumer.assign(&tpl)?;
+
+        info!("Standby replica tailing {} partitions of {}", stores.len(), topic);
+        Ok(Self {
+            consumer,
+            stores,
+            positions: HashMap::new(),
+        })
+    }
+
+    /// Apply the next changelog record; returns `false` if none arrived in time
+    pub async fn apply_next(&mut self, timeout: Duration) -> Result<bool> {
+        match tokio::time::timeout(timeout, self.consumer.recv()).await {
+            Ok(Ok(message)) => {
+                if let (Some(store), Some(key)) = (self.stores.get(&message.partition()), message.key()) {
+                    store.restore_raw(key, message.payload())?;
+                }
+                self.positions.insert(message.partition(), message.offset() + 1);
+                Ok(true)
+            }
+            Ok(Err(e)) => Err(TicketMasterError::Kafka(e)),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Flush stores and record how far each partition has been applied
+    pub fn checkpoint(&self) -> Result<()> {
+        for (partition, offset) in &self.positions {
+            if let Some(store) = self.stores.get(partition) {
+                store.flush()?;
+                write_checkpoint(store, *offset)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a store mirrors its writes
+#[derive(Clone)]
+pub struct ChangelogTarget {
+    pub changelog: Changelog,
+    pub topic: String,
+    pub partition: Option<i32>,
+}
@@ -1,57 +1,382 @@
-use crate::{Result, TicketMasterError};
-use rdkafka::consumer::{Consumer, StreamConsumer};
-use rdkafka::{ClientConfig, Message, TopicPartitionList};
-use serde::de::DeserializeOwned;
-use std::time::Duration;
+use crate::{continue_trace, kafka_error_code, ErrorContext, Metrics, ReservationPriority, StatisticsContext, Result, SerdeValue, TicketMasterError, TopicResolver, TopicSerdes};
+use rdkafka::consumer::{CommitMode, Consumer, ConsumerContext, Rebalance, StreamConsumer};
+use rdkafka::error::KafkaError;
+use rdkafka::message::{BorrowedMessage, Headers};
+use rdkafka::statistics::Statistics;
+use rdkafka::{ClientConfig, ClientContext, Message, Offset, TopicPartitionList};
+use serde::Serialize;
+use futures::{Stream, StreamExt};
+use std::collections::BTreeSet;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 use tokio::time::timeout;
+use tracing::{info, info_span, warn, Span};
+
+const SEEK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Header carrying the id of the HTTP request a command came from, so its
+/// processing can be traced across services
+pub const REQUEST_ID_HEADER: &str = "request-id";
+
+/// Header set to `priority` on reservation commands of the priority lane
+pub const PRIORITY_HEADER: &str = "priority";
+
+/// A topic/partition pair as reported by rebalance callbacks
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TopicPartition {
+    pub topic: String,
+    pub partition: i32,
+}
+
+impl TopicPartition {
+    pub fn new(topic: &str, partition: i32) -> Self {
+        Self {
+            topic: topic.to_string(),
+            partition,
+        }
+    }
+
+    // Partitions of a cluster topic list, named by their logical topics
+    fn from_list(tpl: &TopicPartitionList, topics: &TopicResolver) -> Vec<Self> {
+        tpl.elements()
+            .iter()
+            .map(|elem| Self::new(topics.logical(elem.topic()), elem.partition()))
+            .collect()
+    }
+}
+
+/// How far a consumer group is behind on one partition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PartitionLag {
+    pub topic: String,
+    pub partition: i32,
+    /// Next offset the group will read; `None` if it never committed
+    pub committed: Option<i64>,
+    pub high_watermark: i64,
+    /// Messages not yet committed, counting every retained one without a commit
+    pub lag: i64,
+}
+
+/// Listener notified when the consumer group assignment changes
+///
+/// Callbacks run on the consumer's polling thread and should return quickly.
+pub trait RebalanceListener: Send + Sync {
+    /// Called before partitions are taken away from this consumer
+    fn on_partitions_revoked(&self, partitions: &[TopicPartition]);
+
+    /// Called after partitions have been assigned to this consumer
+    fn on_partitions_assigned(&self, partitions: &[TopicPartition]);
+}
+
+/// Consumer context that forwards rebalance callbacks to an optional listener
+/// and records librdkafka statistics
+#[derive(Default)]
+pub struct RebalanceContext {
+    listener: Option<Arc<dyn RebalanceListener>>,
+    topics: RwLock<TopicResolver>,
+    statistics: StatisticsContext,
+}
+
+impl RebalanceContext {
+    fn topics(&self) -> TopicResolver {
+        self.topics.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+}
+
+impl ClientContext for RebalanceContext {
+    fn stats(&self, statistics: Statistics) {
+        self.statistics.stats(statistics);
+    }
+}
+
+impl ConsumerContext for RebalanceContext {
+    fn pre_rebalance(&self, rebalance: &Rebalance) {
+        match rebalance {
+            Rebalance::Revoke(tpl) => {
+                let partitions = TopicPartition::from_list(tpl, &self.topics());
+                info!("Partitions revoked: {:?}", partitions);
+                if let Some(listener) = &self.listener {
+                    listener.on_partitions_revoked(&partitions);
+                }
+            }
+            Rebalance::Error(e) => warn!("Rebalance error: {}", e),
+            Rebalance::Assign(_) => {}
+        }
+    }
+
+    fn post_rebalance(&self, rebalance: &Rebalance) {
+        if let Rebalance::Assign(tpl) = rebalance {
+            let partitions = TopicPartition::from_list(tpl, &self.topics());
+            info!("Partitions assigned: {:?}", partitions);
+            if let Some(listener) = &self.listener {
+                listener.on_partitions_assigned(&partitions);
+            }
+        }
+    }
+}
 
 pub struct KafkaConsumer {
-    consumer: StreamConsumer,
+    consumer: StreamConsumer<RebalanceContext>,
+    serdes: Arc<TopicSerdes>,
+    topics: TopicResolver,
+    metrics: Option<Metrics>,
 }
 
 impl KafkaConsumer {
     pub fn new(config: ClientConfig) -> Result<Self> {
-        let consumer: StreamConsumer = config.create()?;
-        Ok(Self { consumer })
+        let consumer = config.create_with_context(RebalanceContext::default())?;
+        Ok(Self {
+            consumer,
+            serdes: Arc::new(TopicSerdes::json()),
+            topics: TopicResolver::default(),
+            metrics: None,
+        })
+    }
+
+    /// Create a consumer whose rebalance callbacks are forwarded to `listener`
+    pub fn with_rebalance_listener(config: ClientConfig, listener: Arc<dyn RebalanceListener>) -> Result<Self> {
+        let context = RebalanceContext {
+            listener: Some(listener),
+            ..RebalanceContext::default()
+        };
+        let consumer = config.create_with_context(context)?;
+        Ok(Self {
+            consumer,
+            serdes: Arc::new(TopicSerdes::json()),
+            topics: TopicResolver::default(),
+            metrics: None,
+        })
+    }
+
+    /// Decode received values with the serde configured for each topic
+    pub fn with_serdes(mut self, serdes: Arc<TopicSerdes>) -> Self {
+        self.serdes = serdes;
+        self
+    }
+
+    /// Subscribe to and report the cluster topics `topics` resolves logical
+    /// topic names to; received messages carry the logical names
+    pub fn with_topic_resolver(mut self, topics: TopicResolver) -> Self {
+        *self.consumer.context().topics.write().unwrap_or_else(PoisonError::into_inner) = topics.clone();
+        self.topics = topics;
+        self
+    }
+
+    /// Record received messages and their payload sizes, commits and
+    /// errors per topic in `metrics`, along with the client's librdkafka statistics
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.consumer.context().statistics.attach(metrics.clone());
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Number of partitions of `topic`, or 0 if the topic does not exist
+    pub fn partition_count(&self, topic: &str) -> Result<i32> {
+        let topic = &*self.topics.resolve(topic);
+        let metadata = self.consumer.fetch_metadata(Some(topic), Duration::from_secs(10))?;
+        Ok(metadata
+            .topics()
+            .iter()
+            .find(|t| t.name() == topic && t.error().is_none())
+            .map(|t| t.partitions().len() as i32)
+            .unwrap_or(0))
+    }
+
+    /// Partitions currently assigned to this consumer
+    pub fn assignment(&self) -> Result<Vec<TopicPartition>> {
+        let tpl = self.consumer.assignment()?;
+        Ok(TopicPartition::from_list(&tpl, &self.topics))
+    }
+
+    /// Stop fetching from all assigned partitions until `resume` is called
+    ///
+    /// Partitions assigned by a later rebalance are not paused.
+    pub fn pause(&self) -> Result<()> {
+        let assignment = self.consumer.assignment()?;
+        self.consumer.pause(&assignment)?;
+        Ok(())
+    }
+
+    /// Resume fetching from all assigned partitions
+    pub fn resume(&self) -> Result<()> {
+        let assignment = self.consumer.assignment()?;
+        self.consumer.resume(&assignment)?;
+        Ok(())
     }
 
     pub fn subscribe(&self, topics: &[&str]) -> Result<()> {
-        self.consumer.subscribe(topics)?;
+        let resolved: Vec<_> = topics.iter().map(|topic| self.topics.resolve(topic)).collect();
+        let resolved: Vec<&str> = resolved.iter().map(|topic| topic.as_ref()).collect();
+        self.consumer.subscribe(&resolved)?;
+        Ok(())
+    }
+
+    /// Leave the consumer group, so its partitions are reassigned without
+    /// waiting for the session to time out
+    pub fn unsubscribe(&self) -> Result<()> {
+        self.consumer.unsubscribe();
         Ok(())
     }
 
     pub async fn recv_message(&self, timeout_duration: Duration) -> Result<Option<KafkaMessage>> {
         match timeout(timeout_duration, self.consumer.recv()).await {
-            Ok(Ok(message)) => {
-                let key = message.key()
-                    .map(|k| String::from_utf8_lossy(k).to_string());
-                
-                let payload = message.payload()
-                    .map(|p| String::from_utf8_lossy(p).to_string());
-
-                let topic = message.topic().to_string();
-                let partition = message.partition();
-                let offset = message.offset();
-
-                Ok(Some(KafkaMessage {
-                    topic,
-                    partition,
-                    offset,
-                    key,
-                    payload,
-                }))
-            }
-            Ok(Err(e)) => Err(TicketMasterError::Kafka(e)),
+            Ok(Ok(message)) => Ok(Some(self.to_kafka_message(&message))),
+            Ok(Err(e)) => Err(self.receive_error(e)),
             Err(_) => Ok(None), // Timeout
         }
     }
 
-    pub fn commit_message(&self, message: &KafkaMessage) -> Result<()> {
+    /// Received messages as a stream; it never ends on its own
+    pub fn stream(&self) -> impl Stream<Item = Result<KafkaMessage>> + '_ {
+        self.consumer.stream().map(move |result| match result {
+            Ok(message) => Ok(self.to_kafka_message(&message)),
+            Err(e) => Err(self.receive_error(e)),
+        })
+    }
+
+    /// Continue consuming `partition` from `offset`
+    pub fn seek_to_offset(&self, partition: &TopicPartition, offset: i64) -> Result<()> {
+        let topic = self.topics.resolve(&partition.topic);
+        self.consumer.seek(&topic, partition.partition, Offset::Offset(offset), SEEK_TIMEOUT)?;
+        Ok(())
+    }
+
+    /// Continue consuming each partition from its first message at or after
+    /// `timestamp_ms`, or from the end if there is none
+    pub fn seek_to_timestamp(&self, partitions: &[TopicPartition], timestamp_ms: i64) -> Result<()> {
+        let mut tpl = TopicPartitionList::new();
+        for tp in partitions {
+            tpl.add_partition_offset(&self.topics.resolve(&tp.topic), tp.partition, Offset::Offset(timestamp_ms))?;
+        }
+        let offsets = self.consumer.offsets_for_times(tpl, SEEK_TIMEOUT)?;
+        self.seek_partitions(offsets)
+    }
+
+    /// Continue consuming each partition from its earliest retained message
+    pub fn seek_to_beginning(&self, partitions: &[TopicPartition]) -> Result<()> {
+        let mut tpl = TopicPartitionList::new();
+        for tp in partitions {
+            tpl.add_partition_offset(&self.topics.resolve(&tp.topic), tp.partition, Offset::Beginning)?;
+        }
+        self.seek_partitions(tpl)
+    }
+
+    /// Lag of this consumer's group on every partition of `topics`
+    ///
+    /// Reads the group's committed offsets without joining it, so a consumer
+    /// created with another service's `group.id` can watch that service.
+    pub fn group_lag(&self, topics: &[&str]) -> Result<Vec<PartitionLag>> {
         let mut tpl = TopicPartitionList::new();
-        tpl.add_partition_offset(&message.topic, message.partition, rdkafka::Offset::Offset(message.offset + 1))?;
-        self.consumer.commit(&tpl, rdkafka::consumer::CommitMode::Sync)?;
+        for topic in topics {
+            for partition in 0..self.partition_count(topic)? {
+                tpl.add_partition(&self.topics.resolve(topic), partition);
+            }
+        }
+        let committed = self.consumer.committed_offsets(tpl, SEEK_TIMEOUT)?;
+
+        committed
+            .elements()
+            .iter()
+            .map(|elem| {
+                let (low, high) = self.consumer.fetch_watermarks(elem.topic(), elem.partition(), SEEK_TIMEOUT)?;
+                let committed = match elem.offset() {
+                    Offset::Offset(offset) => Some(offset),
+                    _ => None,
+                };
+                Ok(PartitionLag {
+                    topic: self.topics.logical(elem.topic()).to_string(),
+                    partition: elem.partition(),
+                    committed,
+                    high_watermark: high,
+                    lag: (high - committed.unwrap_or(low).max(low)).max(0),
+                })
+            })
+            .collect()
+    }
+
+    // Seeks only apply to partitions currently assigned to this consumer
+    fn seek_partitions(&self, tpl: TopicPartitionList) -> Result<()> {
+        let result = self.consumer.seek_partitions(tpl, SEEK_TIMEOUT)?;
+        for elem in result.elements() {
+            elem.error()?;
+        }
         Ok(())
     }
+
+    fn to_kafka_message(&self, message: &BorrowedMessage<'_>) -> KafkaMessage {
+        let topic = self.topics.logical(message.topic()).to_string();
+        if let Some(metrics) = &self.metrics {
+            metrics.record_kafka_receive(&topic, message.payload_len());
+        }
+        KafkaMessage {
+            topic,
+            partition: message.partition(),
+            offset: message.offset(),
+            key: message.key().map(|k| String::from_utf8_lossy(k).to_string()),
+            payload: message.payload().map(|p| p.to_vec()),
+            headers: message
+                .headers()
+                .map(|headers| {
+                    headers
+                        .iter()
+                        .map(|header| (header.key.to_string(), header.value.map(<[u8]>::to_vec).unwrap_or_default()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            serdes: self.serdes.clone(),
+        }
+    }
+
+    pub fn commit_message(&self, message: &KafkaMessage) -> Result<()> {
+        let mut tpl = TopicPartitionList::new();
+        tpl.add_partition_offset(&self.topics.resolve(&message.topic), message.partition, Offset::Offset(message.offset + 1))?;
+        self.commit(&tpl, CommitMode::Sync)
+    }
+
+    /// Commit the given next-offsets per partition without waiting for the broker
+    pub fn commit_offsets(&self, offsets: &[(TopicPartition, i64)]) -> Result<()> {
+        self.commit_offsets_with_mode(offsets, CommitMode::Async)
+    }
+
+    /// Commit the given next-offsets per partition and wait for the broker
+    pub fn commit_offsets_sync(&self, offsets: &[(TopicPartition, i64)]) -> Result<()> {
+        self.commit_offsets_with_mode(offsets, CommitMode::Sync)
+    }
+
+    fn commit_offsets_with_mode(&self, offsets: &[(TopicPartition, i64)], mode: CommitMode) -> Result<()> {
+        if offsets.is_empty() {
+            return Ok(());
+        }
+
+        let mut tpl = TopicPartitionList::new();
+        for (tp, offset) in offsets {
+            tpl.add_partition_offset(&self.topics.resolve(&tp.topic), tp.partition, Offset::Offset(*offset))?;
+        }
+        self.commit(&tpl, mode)
+    }
+
+    // Asynchronous commits are timed until they are queued
+    fn commit(&self, tpl: &TopicPartitionList, mode: CommitMode) -> Result<()> {
+        let started = Instant::now();
+        let result = self.consumer.commit(tpl, mode);
+        if let Some(metrics) = &self.metrics {
+            let error = result.as_ref().err().map(kafka_error_code);
+            let elements = tpl.elements();
+            let topics: BTreeSet<_> = elements.iter().map(|elem| self.topics.logical(elem.topic())).collect();
+            for topic in topics {
+                metrics.record_kafka_commit(topic, started.elapsed(), error.as_deref());
+            }
+        }
+        Ok(result?)
+    }
+
+    // Receive errors aren't tied to a topic
+    fn receive_error(&self, e: KafkaError) -> TicketMasterError {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_kafka_error("", &kafka_error_code(&e));
+        }
+        TicketMasterError::Kafka(e)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -60,20 +385,96 @@ pub struct KafkaMessage {
     pub partition: i32,
     pub offset: i64,
     pub key: Option<String>,
-    pub payload: Option<String>,
+    pub payload: Option<Vec<u8>>,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub serdes: Arc<TopicSerdes>,
 }
 
 impl KafkaMessage {
-    pub fn deserialize_value<T>(&self) -> Result<T>
+    /// Whether the message deletes its key, as a record without a payload
+    /// does on the compacted state topics
+    pub fn is_tombstone(&self) -> bool {
+        self.payload.is_none()
+    }
+
+    /// Value of the first header named `name`
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_slice())
+    }
+
+    /// Id of the HTTP request that led to this message, from `REQUEST_ID_HEADER`
+    pub fn request_id(&self) -> Option<&str> {
+        self.header(REQUEST_ID_HEADER).and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    /// Lane of a reservation command, from `PRIORITY_HEADER`; general without one
+    pub fn priority(&self) -> ReservationPriority {
+        self.header(PRIORITY_HEADER)
+            .and_then(|value| std::str::from_utf8(value).ok())
+            .and_then(ReservationPriority::parse)
+            .unwrap_or_default()
+    }
+
+    /// Span to process the message in, so its logs carry the request id;
+    /// it continues the trace the message was sent in
+    ///
+    /// Handlers record the `reservation_id` of the message once they know it.
+    pub fn span(&self) -> Span {
+        let span = info_span!(
+            "message",
+            otel.kind = "consumer",
+            topic = %self.topic,
+            partition = self.partition,
+            offset = self.offset,
+            request_id = self.request_id().unwrap_or_default(),
+            reservation_id = tracing::field::Empty,
+        );
+        continue_trace(&span, self.headers.iter().map(|(name, value)| (name.as_str(), value.as_slice())));
+        span
+    }
+
+    /// Context of an error processing this message in `component`
+    pub fn error_context(&self, component: &str) -> ErrorContext {
+        let mut context = ErrorContext::new("process message", component)
+            .with_metadata("topic", &self.topic)
+            .with_metadata("partition", self.partition)
+            .with_metadata("offset", self.offset);
+        if let Some(key) = &self.key {
+            context.add_metadata("key", key);
+        }
+        context
+    }
+
+    /// Decode the value with the serde configured for the message's topic
+    pub async fn deserialize_value<T>(&self) -> Result<T>
     where
-        T: DeserializeOwned,
+        T: SerdeValue,
     {
         match &self.payload {
             Some(payload) => {
-                let value = serde_json::from_str(payload)?;
+                let value = self.serdes.serde_for::<T>(&self.topic)?.deserialize(&self.topic, payload).await?;
                 Ok(value)
             }
             None => Err(TicketMasterError::InvalidArgument("Empty message payload".to_string())),
         }
     }
+
+    /// The value a state topic record sets its key to, or the key a
+    /// tombstone deletes; a tombstone without a key fails like an empty payload
+    pub async fn state_change<T>(&self) -> Result<StateChange<'_, T>>
+    where
+        T: SerdeValue,
+    {
+        match (&self.key, self.is_tombstone()) {
+            (Some(key), true) => Ok(StateChange::Delete(key)),
+            _ => Ok(StateChange::Upsert(self.deserialize_value().await?)),
+        }
+    }
+}
+
+/// What a record of a compacted state topic does to its key
+#[derive(Debug)]
+pub enum StateChange<'a, T> {
+    Upsert(T),
+    Delete(&'a str),
 }
\ No newline at end of file
@@ -0,0 +1,61 @@
+use crate::{Result, RocksDBStore};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// An id that falls due at `due_ms` (epoch millis)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deadline {
+    pub id: String,
+    pub due_ms: i64,
+}
+
+/// Ids by the time they fall due, kept in a `RocksDBStore`
+///
+/// Keys start with the zero-padded due time, so due ids are found with one
+/// range scan, earliest first. Deadlines stay until removed, so one whose
+/// handling fails is found again by the next scan.
+pub struct DeadlineIndex {
+    store: Arc<RocksDBStore>,
+}
+
+impl DeadlineIndex {
+    pub fn new(store: Arc<RocksDBStore>) -> Self {
+        Self { store }
+    }
+
+    pub fn store(&self) -> &Arc<RocksDBStore> {
+        &self.store
+    }
+
+    pub fn schedule(&self, id: &str, due_ms: i64) -> Result<Deadline> {
+        let deadline = Deadline {
+            id: id.to_string(),
+            due_ms,
+        };
+        self.store.put(&deadline_key(&deadline), &deadline)?;
+        Ok(deadline)
+    }
+
+    /// Up to `limit` deadlines due at or before `now_ms`, earliest first
+    pub fn due(&self, now_ms: i64, limit: usize) -> Result<Vec<Deadline>> {
+        let end = due_prefix(now_ms.saturating_add(1));
+        self.store
+            .range::<Deadline>("", &end)
+            .take(limit)
+            .map(|entry| entry.map(|(_, deadline)| deadline))
+            .collect()
+    }
+
+    pub fn remove(&self, deadline: &Deadline) -> Result<()> {
+        self.store.delete(&deadline_key(deadline))
+    }
+}
+
+// Times before the epoch sort as the epoch
+fn due_prefix(due_ms: i64) -> String {
+    format!("{:020}", due_ms.max(0))
+}
+
+fn deadline_key(deadline: &Deadline) -> String {
+    format!("{}#{}", due_prefix(deadline.due_ms), deadline.id)
+}
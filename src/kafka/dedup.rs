@@ -0,0 +1,138 @@
+use crate::{KafkaMessage, Result, RocksDBStore, Topics, OUTBOX_ID_HEADER};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+use tracing::{error, info};
+
+// Expired entries deleted per write batch when purging
+const PURGE_BATCH_SIZE: usize = 1024;
+// Topics whose messages carry no id and are legitimately sent twice with the
+// same payload, e.g. a seat released, reserved again and released again, or
+// an area status back to the seats it had before
+const UNDEDUPLICATED_TOPICS: [&str; 9] = [
+    Topics::COMMAND_EVENT_ADMIN,
+    Topics::COMMAND_RESERVATION_ADMIN,
+    Topics::INTERNAL_EVENT_AREA_STATUS,
+    Topics::STATE_EVENT_AREA_STATUS,
+    Topics::STATE_USER_RESERVATION,
+    Topics::STATE_EVENT_ARTWORK,
+    Topics::STATE_AUDIT,
+    Topics::STATE_SERVICE_HEARTBEAT,
+    Topics::STATE_USER_PROFILE,
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Processed {
+    processed_at_ms: i64,
+}
+
+/// Remembers processed messages for `ttl`, so a message redelivered after a
+/// crash or rebalance is not applied twice
+///
+/// Offsets are committed after processing, so messages processed since the
+/// last commit are delivered again. A message published through an outbox is
+/// identified by its `OUTBOX_ID_HEADER`, so a republished command is caught
+/// too; any other by topic, key, request id and a SHA-256 of the payload.
+/// Admin commands, area initializations and state updates are never skipped,
+/// as they carry no id of their own and repeat legitimately.
+pub struct DedupStore {
+    store: RocksDBStore,
+    ttl: Duration,
+}
+
+impl DedupStore {
+    pub fn open<P: AsRef<Path>>(path: P, ttl: Duration) -> Result<Self> {
+        std::fs::create_dir_all(&path)?;
+        Ok(Self {
+            store: RocksDBStore::new(path)?,
+            ttl,
+        })
+    }
+
+    /// Whether `message` was processed within the TTL
+    pub fn is_duplicate(&self, message: &KafkaMessage) -> Result<bool> {
+        let Some(key) = dedup_key(message) else {
+            return Ok(false);
+        };
+        let processed = self.store.get::<Processed>(&key)?;
+        Ok(processed.is_some_and(|processed| !self.is_expired(&processed, now_ms())))
+    }
+
+    pub fn mark_processed(&self, message: &KafkaMessage) -> Result<()> {
+        match dedup_key(message) {
+            Some(key) => self.store.put(&key, &Processed { processed_at_ms: now_ms() }),
+            None => Ok(()),
+        }
+    }
+
+    /// Forget messages processed longer than the TTL ago; returns how many
+    ///
+    /// Entries are read one at a time and deleted in batches, so the purge
+    /// holds little in memory however many messages were processed.
+    pub fn purge_expired(&self) -> Result<usize> {
+        let now = now_ms();
+        let mut purged = 0;
+        let mut batch = self.store.write_batch();
+        for entry in self.store.scan_prefix::<Processed>("") {
+            let (key, processed) = entry?;
+            if self.is_expired(&processed, now) {
+                batch.delete(&key);
+                purged += 1;
+            }
+            if batch.len() >= PURGE_BATCH_SIZE {
+                std::mem::replace(&mut batch, self.store.write_batch()).commit()?;
+            }
+        }
+        batch.commit()?;
+        Ok(purged)
+    }
+
+    fn is_expired(&self, processed: &Processed, now_ms: i64) -> bool {
+        now_ms - processed.processed_at_ms > self.ttl.as_millis() as i64
+    }
+}
+
+/// Purge expired entries from `dedup` once per TTL
+pub fn spawn_dedup_purge(dedup: Arc<DedupStore>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut timer = interval(dedup.ttl.max(Duration::from_secs(1)));
+        loop {
+            timer.tick().await;
+            match dedup.purge_expired() {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} expired dedup entries", purged),
+                Err(e) => error!("Failed to purge dedup entries: {}", e),
+            }
+        }
+    })
+}
+
+// `None` for messages that are never skipped
+fn dedup_key(message: &KafkaMessage) -> Option<String> {
+    if UNDEDUPLICATED_TOPICS.contains(&message.topic.as_str()) {
+        return None;
+    }
+    if let Some(outbox_id) = message.header(OUTBOX_ID_HEADER) {
+        return Some(format!("{}|outbox|{}", message.topic, String::from_utf8_lossy(outbox_id)));
+    }
+    let digest = Sha256::digest(message.payload.as_deref().unwrap_or_default());
+    let mut key = format!(
+        "{}|{}|{}|",
+        message.topic,
+        message.key.as_deref().unwrap_or_default(),
+        message.request_id().unwrap_or_default()
+    );
+    for byte in digest {
+        let _ = write!(key, "{:02x}", byte);
+    }
+    Some(key)
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
@@ -0,0 +1,113 @@
+use crate::{KafkaMessage, ReservationPriority, Result, TicketMasterError};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::error;
+
+const LANE_CAPACITY: usize = 64;
+
+/// Processes a single message on behalf of a `KeyedDispatcher`
+#[async_trait::async_trait]
+pub trait MessageHandler: Send + Sync + 'static {
+    async fn handle(&self, message: &KafkaMessage) -> Result<()>;
+}
+
+/// Outcome of processing a dispatched message
+#[derive(Debug)]
+pub struct Completion {
+    pub message: KafkaMessage,
+    pub result: Result<()>,
+}
+
+/// Processes messages concurrently on a fixed number of lanes
+///
+/// Messages are assigned to a lane by key (or by partition when they have no
+/// key), and each lane processes its messages one at a time, so messages with
+/// the same key are handled in the order they were dispatched.
+///
+/// Messages of the priority lane (see `KafkaMessage::priority`) are queued
+/// separately and a lane takes them before any general message it has
+/// waiting, so they only keep their order relative to each other.
+pub struct KeyedDispatcher {
+    lanes: Vec<Lane>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl KeyedDispatcher {
+    /// Start `parallelism` lanes; completions are reported on the returned receiver
+    pub fn new<H: MessageHandler>(parallelism: usize, handler: Arc<H>) -> (Self, mpsc::UnboundedReceiver<Completion>) {
+        let (completed_tx, completed_rx) = mpsc::unbounded_channel();
+
+        let mut lanes = Vec::new();
+        let mut workers = Vec::new();
+        for _ in 0..parallelism.max(1) {
+            let (general, mut general_rx) = mpsc::channel::<KafkaMessage>(LANE_CAPACITY);
+            let (priority, mut priority_rx) = mpsc::channel::<KafkaMessage>(LANE_CAPACITY);
+            let handler = handler.clone();
+            let completed_tx = completed_tx.clone();
+
+            workers.push(tokio::spawn(async move {
+                loop {
+                    let message = tokio::select! {
+                        biased;
+                        Some(message) = priority_rx.recv() => message,
+                        Some(message) = general_rx.recv() => message,
+                        else => break,
+                    };
+                    let result = handler.handle(&message).await;
+                    if completed_tx.send(Completion { message, result }).is_err() {
+                        error!("Completion receiver dropped, stopping dispatcher lane");
+                        break;
+                    }
+                }
+            }));
+            lanes.push(Lane { general, priority });
+        }
+
+        (Self { lanes, workers }, completed_rx)
+    }
+
+    /// Number of lanes processing in parallel
+    pub fn parallelism(&self) -> usize {
+        self.lanes.len()
+    }
+
+    /// Queue a message on its lane, waiting if the lane is full
+    pub async fn dispatch(&self, message: KafkaMessage) -> Result<()> {
+        let lane = self.lane_for(&message);
+        let queue = match message.priority() {
+            ReservationPriority::General => &self.lanes[lane].general,
+            ReservationPriority::Priority => &self.lanes[lane].priority,
+        };
+        queue
+            .send(message)
+            .await
+            .map_err(|_| TicketMasterError::InvalidArgument(format!("Dispatcher lane {} is closed", lane)))
+    }
+
+    /// Stop accepting messages and wait for queued ones to finish
+    pub async fn shutdown(self) {
+        drop(self.lanes);
+        for worker in self.workers {
+            if let Err(e) = worker.await {
+                error!("Dispatcher lane panicked: {}", e);
+            }
+        }
+    }
+
+    fn lane_for(&self, message: &KafkaMessage) -> usize {
+        let mut hasher = DefaultHasher::new();
+        match &message.key {
+            Some(key) => key.hash(&mut hasher),
+            None => (&message.topic, message.partition).hash(&mut hasher),
+        }
+        (hasher.finish() % self.lanes.len() as u64) as usize
+    }
+}
+
+struct Lane {
+    general: mpsc::Sender<KafkaMessage>,
+    priority: mpsc::Sender<KafkaMessage>,
+}
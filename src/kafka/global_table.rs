@@ -0,0 +1,148 @@
+use crate::{KafkaMessage, MessageConsumer, Result, RocksDBStore, SerdeValue, TopicPartition};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+type Convert<V, S> = Box<dyn Fn(V) -> S + Send + Sync>;
+type OnApplied = Box<dyn Fn(&str) + Send + Sync>;
+
+/// Materializes a whole topic into a local store that nothing else writes,
+/// like a Kafka Streams GlobalKTable
+///
+/// The consumer needs a group of its own so it is assigned every partition.
+/// The next offset of each partition is written in the same batch as the
+/// record under `__global_offset#<topic>#<partition>`, and reading resumes
+/// there after a restart. Tombstones delete the key.
+pub struct GlobalTable<C, V, S> {
+    consumer: C,
+    topic: String,
+    store: Arc<RocksDBStore>,
+    convert: Convert<V, S>,
+    on_applied: Option<OnApplied>,
+    positioned: HashSet<i32>,
+}
+
+impl<C, V, S> GlobalTable<C, V, S>
+where
+    C: MessageConsumer,
+    V: SerdeValue,
+    S: Serialize,
+{
+    /// Store the records of `topic` in `store` as `convert` turns them
+    pub fn new<F>(consumer: C, topic: &str, store: Arc<RocksDBStore>, convert: F) -> Result<Self>
+    where
+        F: Fn(V) -> S + Send + Sync + 'static,
+    {
+        consumer.subscribe(&[topic])?;
+        Ok(Self {
+            consumer,
+            topic: topic.to_string(),
+            store,
+            convert: Box::new(convert),
+            on_applied: None,
+            positioned: HashSet::new(),
+        })
+    }
+
+    /// Call `on_applied` with each key written or deleted, while the key is
+    /// still locked, e.g. to drop it from a cache in front of the store
+    pub fn with_on_applied<F>(mut self, on_applied: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_applied = Some(Box::new(on_applied));
+        self
+    }
+
+    pub fn store(&self) -> &Arc<RocksDBStore> {
+        &self.store
+    }
+
+    /// Next offset to apply from `partition`, if any record of it has been
+    pub fn position(&self, partition: i32) -> Result<Option<i64>> {
+        global_table_position(&self.store, &self.topic, partition)
+    }
+
+    /// Apply the next record; returns `false` if none arrived in time
+    pub async fn apply_next(&mut self, timeout: Duration) -> Result<bool> {
+        let Some(message) = self.consumer.recv_message(timeout).await? else {
+            return Ok(false);
+        };
+        if self.reposition(&message)? {
+            return Ok(true);
+        }
+        self.apply(&message).await?;
+        Ok(true)
+    }
+
+    // Seek the message's partition to the stored position the first time it
+    // shows up; the message was fetched from the old position and is skipped
+    fn reposition(&mut self, message: &KafkaMessage) -> Result<bool> {
+        if !self.positioned.insert(message.partition) {
+            return Ok(false);
+        }
+        match self.position(message.partition)? {
+            Some(position) if position > message.offset => {
+                let partition = TopicPartition::new(&message.topic, message.partition);
+                self.consumer.seek_to_offset(&partition, position)?;
+                info!("Resuming {} partition {} at offset {}", self.topic, message.partition, position);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn apply(&self, message: &KafkaMessage) -> Result<()> {
+        let value = match (&message.key, &message.payload) {
+            (Some(_), Some(_)) => Some((self.convert)(message.deserialize_value::<V>().await?)),
+            _ => None,
+        };
+
+        let offset_key = offset_key(&self.topic, message.partition);
+        let Some(key) = &message.key else {
+            // Keyless records can't be looked up; only the position moves
+            return self.store.put(&offset_key, &(message.offset + 1));
+        };
+
+        let _guard = self.store.lock_key(key);
+        let mut batch = self.store.write_batch();
+        match &value {
+            Some(value) => batch.put(key, value)?,
+            None => batch.delete(key),
+        };
+        batch.put(&offset_key, &(message.offset + 1))?;
+        batch.commit()?;
+        if let Some(on_applied) = &self.on_applied {
+            on_applied(key);
+        }
+        Ok(())
+    }
+}
+
+/// Keep applying records to `table` in the background
+pub fn spawn_global_table<C, V, S>(mut table: GlobalTable<C, V, S>) -> JoinHandle<()>
+where
+    C: MessageConsumer,
+    V: SerdeValue + 'static,
+    S: Serialize + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = table.apply_next(Duration::from_secs(1)).await {
+                error!("Error applying {} record to global table: {}", table.topic, e);
+            }
+        }
+    })
+}
+
+/// Next offset of `topic`'s `partition` to apply to the global table in `store`
+pub fn global_table_position(store: &RocksDBStore, topic: &str, partition: i32) -> Result<Option<i64>> {
+    store.get(&offset_key(topic, partition))
+}
+
+fn offset_key(topic: &str, partition: i32) -> String {
+    format!("__global_offset#{}#{}", topic, partition)
+}
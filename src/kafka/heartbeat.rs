@@ -0,0 +1,245 @@
+use crate::{
+    KafkaProducer, MessageConsumer, MessageProducer, Result, ServiceConfig, ShutdownComponent, TicketMasterError, Topics,
+};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+// Heartbeats an instance may miss before it is reported dead
+const MISSED_HEARTBEATS: i64 = 3;
+
+/// What an instance last reported about itself on `state.service.heartbeat`
+///
+/// Keyed by instance id, so the compacted topic keeps the latest heartbeat of
+/// each; an instance shutting down cleanly leaves a tombstone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceHeartbeat {
+    pub instance_id: String,
+    pub application_id: String,
+    pub version: String,
+    /// Assigned partitions, per topic
+    pub partitions: BTreeMap<String, Vec<i32>>,
+    /// Messages the instance's group has yet to commit on its assigned partitions, per topic
+    pub lag: BTreeMap<String, i64>,
+    pub sent_at_ms: i64,
+    /// How often the instance sends heartbeats
+    pub interval_ms: u64,
+}
+
+impl InstanceHeartbeat {
+    pub fn age_ms(&self, now_ms: i64) -> i64 {
+        (now_ms - self.sent_at_ms).max(0)
+    }
+
+    /// Alive until a few heartbeats in a row are missed
+    pub fn is_alive(&self, now_ms: i64) -> bool {
+        self.age_ms(now_ms) <= self.interval_ms as i64 * MISSED_HEARTBEATS
+    }
+}
+
+/// Publishes the heartbeats of this instance
+#[derive(Clone)]
+pub struct HeartbeatPublisher<P = KafkaProducer> {
+    producer: P,
+    application_id: String,
+    instance_id: String,
+    version: String,
+    interval: Duration,
+    consumers: Vec<(Arc<dyn MessageConsumer>, Vec<&'static str>)>,
+    task: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl<P: MessageProducer + Clone> HeartbeatPublisher<P> {
+    pub fn new(producer: P, application_id: &str, instance_id: &str, version: &str, interval: Duration) -> Self {
+        Self {
+            producer,
+            application_id: application_id.to_string(),
+            instance_id: instance_id.to_string(),
+            version: version.to_string(),
+            interval,
+            consumers: Vec::new(),
+            task: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// From the `heartbeat.` properties; without an id to go by the instance
+    /// gets a new one on every start
+    pub fn from_config(producer: P, config: &ServiceConfig, version: &str) -> Self {
+        let instance_id = config
+            .instance_id()
+            .unwrap_or_else(|| format!("{}-{}", config.application_id, Uuid::new_v4()));
+        Self::new(producer, &config.application_id, &instance_id, version, config.heartbeat.interval())
+    }
+
+    /// Report the partitions of `topics` assigned to `consumer`, and its
+    /// group's lag on them
+    pub fn with_consumer(mut self, consumer: Arc<dyn MessageConsumer>, topics: &[&'static str]) -> Self {
+        self.consumers.push((consumer, topics.to_vec()));
+        self
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// The instance as it is now
+    pub async fn heartbeat(&self) -> Result<InstanceHeartbeat> {
+        let mut partitions: BTreeMap<String, Vec<i32>> = BTreeMap::new();
+        let mut lag: BTreeMap<String, i64> = BTreeMap::new();
+        for (consumer, topics) in &self.consumers {
+            let (consumer, topics) = (consumer.clone(), topics.clone());
+            // Committed offsets and watermarks are fetched from the brokers
+            let (assignment, group_lag) = tokio::task::spawn_blocking(move || -> Result<_> {
+                Ok((consumer.assignment()?, consumer.group_lag(&topics)?))
+            })
+            .await
+            .map_err(|e| TicketMasterError::Io(std::io::Error::other(e)))??;
+
+            for partition in group_lag {
+                if assignment.iter().any(|tp| tp.topic == partition.topic && tp.partition == partition.partition) {
+                    partitions.entry(partition.topic.clone()).or_default().push(partition.partition);
+                    *lag.entry(partition.topic).or_default() += partition.lag;
+                }
+            }
+        }
+
+        Ok(InstanceHeartbeat {
+            instance_id: self.instance_id.clone(),
+            application_id: self.application_id.clone(),
+            version: self.version.clone(),
+            partitions,
+            lag,
+            sent_at_ms: chrono::Utc::now().timestamp_millis(),
+            interval_ms: self.interval.as_millis() as u64,
+        })
+    }
+
+    pub async fn publish(&self) -> Result<()> {
+        let heartbeat = self.heartbeat().await?;
+        // Always JSON, whatever serde the other topics use
+        let payload = serde_json::to_vec(&heartbeat)?;
+        self.producer
+            .send_bytes(Topics::STATE_SERVICE_HEARTBEAT, None, &self.instance_id, Some(&payload))
+            .await?;
+        debug!("Sent heartbeat of {} with lag {:?}", self.instance_id, heartbeat.lag);
+        Ok(())
+    }
+
+    /// Publish a heartbeat every interval until shut down
+    pub fn start(&self) {
+        let publisher = self.clone();
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(publisher.interval);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = publisher.publish().await {
+                    error!("Failed to send heartbeat of {}: {}", publisher.instance_id, e);
+                }
+            }
+        });
+        if let Some(previous) = self.task.lock().unwrap_or_else(PoisonError::into_inner).replace(task) {
+            previous.abort();
+        }
+    }
+}
+
+// Stops the heartbeats and leaves a tombstone, so the instance drops out of
+// the overview at once instead of being reported dead
+#[async_trait::async_trait]
+impl<P: MessageProducer + Clone> ShutdownComponent for HeartbeatPublisher<P> {
+    async fn shutdown(&self) -> Result<()> {
+        let task = self.task.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(task) = task {
+            task.abort();
+            let _ = task.await;
+        }
+        self.producer.send_tombstone(Topics::STATE_SERVICE_HEARTBEAT, &self.instance_id).await?;
+        info!("Removed instance {} from the cluster overview", self.instance_id);
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "heartbeat"
+    }
+}
+
+/// An instance in the cluster overview
+#[derive(Debug, Clone, Serialize)]
+pub struct InstanceStatus {
+    #[serde(flatten)]
+    pub heartbeat: InstanceHeartbeat,
+    pub alive: bool,
+    /// Since the last heartbeat
+    pub age_ms: i64,
+}
+
+/// The latest heartbeat of every instance
+#[derive(Clone, Default)]
+pub struct ClusterDirectory {
+    instances: Arc<DashMap<String, InstanceHeartbeat>>,
+}
+
+impl ClusterDirectory {
+    pub fn update(&self, heartbeat: InstanceHeartbeat) {
+        self.instances.insert(heartbeat.instance_id.clone(), heartbeat);
+    }
+
+    pub fn remove(&self, instance_id: &str) {
+        self.instances.remove(instance_id);
+    }
+
+    /// Every instance by application and id; dead ones are kept until their
+    /// heartbeat is removed from the topic
+    pub fn overview(&self) -> Vec<InstanceStatus> {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let mut instances: Vec<_> = self
+            .instances
+            .iter()
+            .map(|heartbeat| InstanceStatus {
+                alive: heartbeat.is_alive(now_ms),
+                age_ms: heartbeat.age_ms(now_ms),
+                heartbeat: heartbeat.clone(),
+            })
+            .collect();
+        instances.sort_by(|a, b| {
+            (&a.heartbeat.application_id, &a.heartbeat.instance_id)
+                .cmp(&(&b.heartbeat.application_id, &b.heartbeat.instance_id))
+        });
+        instances
+    }
+}
+
+/// Keep `directory` up to date from `state.service.heartbeat`
+///
+/// Like the query directory, the consumer should use a group of its own
+/// reading from the earliest offset.
+pub fn spawn_cluster_directory<C: MessageConsumer>(consumer: C, directory: ClusterDirectory) -> Result<JoinHandle<()>> {
+    consumer.subscribe(&[Topics::STATE_SERVICE_HEARTBEAT])?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let message = match consumer.recv_message(Duration::from_secs(1)).await {
+                Ok(Some(message)) => message,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("Cluster directory failed to receive: {}", e);
+                    continue;
+                }
+            };
+
+            match (&message.payload, &message.key) {
+                (None, Some(key)) => directory.remove(key),
+                (Some(payload), _) => match serde_json::from_slice::<InstanceHeartbeat>(payload) {
+                    Ok(heartbeat) => directory.update(heartbeat),
+                    Err(e) => warn!("Skipping undecodable heartbeat at offset {}: {}", message.offset, e),
+                },
+                (None, None) => {}
+            }
+        }
+    }))
+}
@@ -0,0 +1,173 @@
+use crate::{Result, RocksDBStore, StoreWriteBatch};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+type KeyExtractor<T> = Box<dyn Fn(&T) -> Vec<String> + Send + Sync>;
+
+struct StoreIndex<T> {
+    name: String,
+    extract: KeyExtractor<T>,
+}
+
+/// Wraps a `RocksDBStore` so named secondary indexes follow every put and delete
+///
+/// An index maps a value to any number of secondary keys, e.g. a reservation
+/// to its user. Entries live under `__index#<index>#<secondary key>#<key>`
+/// and are written in the same batch as the value, so `find` never sees a
+/// value without its entries or the other way round.
+pub struct IndexedStore<T> {
+    store: Arc<RocksDBStore>,
+    indexes: Vec<StoreIndex<T>>,
+}
+
+impl<T> IndexedStore<T>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    pub fn new(store: Arc<RocksDBStore>) -> Self {
+        Self {
+            store,
+            indexes: Vec::new(),
+        }
+    }
+
+    /// Index values under the keys `extract` returns for them
+    pub fn with_index<F>(mut self, name: &str, extract: F) -> Self
+    where
+        F: Fn(&T) -> Vec<String> + Send + Sync + 'static,
+    {
+        self.indexes.push(StoreIndex {
+            name: name.to_string(),
+            extract: Box::new(extract),
+        });
+        self
+    }
+
+    pub fn store(&self) -> &Arc<RocksDBStore> {
+        &self.store
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<T>> {
+        self.store.get(key)
+    }
+
+    pub fn put(&self, key: &str, value: &T) -> Result<()> {
+        self.write(key, value, None)
+    }
+
+    /// Put `value` soft-deleted now, together with its index entries, so
+    /// both stay readable until a compaction after the retention purges them
+    pub fn put_soft_deleted(&self, key: &str, value: &T) -> Result<()> {
+        self.write(key, value, Some(Utc::now()))
+    }
+
+    pub fn delete(&self, key: &str) -> Result<()> {
+        let _guard = self.store.lock_key(key);
+        let mut batch = self.store.write_batch();
+        if let Some(previous) = self.store.get::<T>(key)? {
+            self.remove_entries(&mut batch, key, &previous);
+        }
+        batch.delete(key);
+        batch.commit()
+    }
+
+    /// Values indexed under `secondary_key` by `index`, in key order
+    pub fn find(&self, index: &str, secondary_key: &str) -> Result<Vec<(String, T)>> {
+        let prefix = format!("{}{}#", index_prefix(index), secondary_key);
+        let mut found = Vec::new();
+        for entry in self.store.scan_prefix::<(String, String)>(&prefix) {
+            let (_, (indexed_key, key)) = entry?;
+            // The prefix also matches secondary keys that merely start with this one
+            if indexed_key != secondary_key {
+                continue;
+            }
+            if let Some(value) = self.store.get(&key)? {
+                found.push((key, value));
+            }
+        }
+        Ok(found)
+    }
+
+    /// Rewrite every index from the stored values, e.g. after adding an index
+    /// to a store that already holds data; returns the number of values indexed
+    ///
+    /// Keys starting with `__` are bookkeeping, not values, and are skipped.
+    pub fn rebuild_indexes(&self) -> Result<usize> {
+        let mut batch = self.store.write_batch();
+        for index in &self.indexes {
+            for entry in self.store.scan_prefix::<(String, String)>(&index_prefix(&index.name)) {
+                let (entry_key, _) = entry?;
+                batch.delete(&entry_key);
+            }
+        }
+
+        let mut indexed = 0;
+        for key in self.store.keys() {
+            let key = key?;
+            if key.starts_with("__") {
+                continue;
+            }
+            if let Some(value) = self.store.get::<T>(&key)? {
+                let deleted_at = self.store.deleted_at(&key)?;
+                self.add_entries(&mut batch, &key, &value, deleted_at)?;
+                indexed += 1;
+            }
+        }
+        batch.commit()?;
+        Ok(indexed)
+    }
+
+    fn write(&self, key: &str, value: &T, deleted_at: Option<DateTime<Utc>>) -> Result<()> {
+        let _guard = self.store.lock_key(key);
+        let mut batch = self.store.write_batch();
+        if let Some(previous) = self.store.get::<T>(key)? {
+            self.remove_entries(&mut batch, key, &previous);
+        }
+        match deleted_at {
+            Some(deleted_at) => batch.put_soft_deleted(key, value, deleted_at)?,
+            None => batch.put(key, value)?,
+        };
+        self.add_entries(&mut batch, key, value, deleted_at)?;
+        batch.commit()
+    }
+
+    // Entries of a soft-deleted value are soft-deleted alike, so compaction
+    // purges them with the value
+    fn add_entries(
+        &self,
+        batch: &mut StoreWriteBatch<'_>,
+        key: &str,
+        value: &T,
+        deleted_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        for index in &self.indexes {
+            for secondary_key in (index.extract)(value) {
+                let entry = (secondary_key, key.to_string());
+                let entry_key = entry_key(&index.name, &entry.0, key);
+                match deleted_at {
+                    Some(deleted_at) => batch.put_soft_deleted(&entry_key, &entry, deleted_at)?,
+                    None => batch.put(&entry_key, &entry)?,
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_entries(&self, batch: &mut StoreWriteBatch<'_>, key: &str, value: &T) {
+        for index in &self.indexes {
+            for secondary_key in (index.extract)(value) {
+                batch.delete(&entry_key(&index.name, &secondary_key, key));
+            }
+        }
+    }
+}
+
+fn index_prefix(index: &str) -> String {
+    format!("__index#{}#", index)
+}
+
+// Entries hold (secondary key, key), since either may contain `#`
+fn entry_key(index: &str, secondary_key: &str, key: &str) -> String {
+    format!("{}{}#{}", index_prefix(index), secondary_key, key)
+}
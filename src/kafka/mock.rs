@@ -0,0 +1,454 @@
+use crate::{
+    KafkaMessage, PartitionLag, RebalanceListener, Result, SerdeValue, TicketMasterError, TopicPartition, TopicSerdes,
+};
+use futures::Stream;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::timeout;
+
+const DEFAULT_PARTITIONS: i32 = 1;
+
+#[derive(Debug, Clone)]
+struct MockRecord {
+    key: Option<String>,
+    payload: Option<Vec<u8>>,
+    headers: Vec<(String, Vec<u8>)>,
+    timestamp_ms: i64,
+}
+
+#[derive(Default)]
+struct Broker {
+    topics: HashMap<String, Vec<Vec<MockRecord>>>,
+    // Next offset to consume, per consumer group and partition
+    committed: HashMap<(String, TopicPartition), i64>,
+}
+
+impl Broker {
+    fn topic(&mut self, topic: &str, default_partitions: i32) -> &mut Vec<Vec<MockRecord>> {
+        self.topics
+            .entry(topic.to_string())
+            .or_insert_with(|| vec![Vec::new(); default_partitions.max(1) as usize])
+    }
+
+    fn record(&self, partition: &TopicPartition, offset: i64) -> Option<&MockRecord> {
+        self.topics
+            .get(&partition.topic)?
+            .get(partition.partition as usize)?
+            .get(usize::try_from(offset).ok()?)
+    }
+
+    fn end_offset(&self, partition: &TopicPartition) -> i64 {
+        self.topics
+            .get(&partition.topic)
+            .and_then(|partitions| partitions.get(partition.partition as usize))
+            .map_or(0, |records| records.len() as i64)
+    }
+}
+
+/// In-memory Kafka cluster for tests
+///
+/// Topics are created on first use with the default partition count. Keyed
+/// messages are partitioned by a hash of the key, so messages with the same
+/// key land on the same partition, although not the one Kafka would choose.
+/// Every consumer of a group is assigned all partitions of its subscription.
+#[derive(Clone)]
+pub struct MockKafka {
+    broker: Arc<Mutex<Broker>>,
+    changed: Arc<Notify>,
+    default_partitions: i32,
+}
+
+impl Default for MockKafka {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockKafka {
+    pub fn new() -> Self {
+        Self::with_default_partitions(DEFAULT_PARTITIONS)
+    }
+
+    pub fn with_default_partitions(partitions: i32) -> Self {
+        Self {
+            broker: Arc::new(Mutex::new(Broker::default())),
+            changed: Arc::new(Notify::new()),
+            default_partitions: partitions.max(1),
+        }
+    }
+
+    /// Create `topic` with `partitions` partitions unless it already exists
+    pub fn create_topic(&self, topic: &str, partitions: i32) {
+        self.broker().topic(topic, partitions);
+    }
+
+    pub fn producer(&self) -> MockProducer {
+        MockProducer {
+            kafka: self.clone(),
+            serdes: Arc::new(TopicSerdes::json()),
+        }
+    }
+
+    pub fn consumer(&self, group_id: &str) -> MockConsumer {
+        MockConsumer {
+            kafka: self.clone(),
+            group_id: group_id.to_string(),
+            serdes: Arc::new(TopicSerdes::json()),
+            state: Mutex::new(ConsumerState::default()),
+            listener: None,
+        }
+    }
+
+    /// Consumer reporting the partitions `subscribe` assigns and
+    /// `unsubscribe` revokes to `listener`, like `KafkaConsumer::with_rebalance_listener`
+    pub fn consumer_with_rebalance_listener(&self, group_id: &str, listener: Arc<dyn RebalanceListener>) -> MockConsumer {
+        MockConsumer {
+            listener: Some(listener),
+            ..self.consumer(group_id)
+        }
+    }
+
+    /// Every message on `topic`, partition by partition
+    pub fn messages(&self, topic: &str) -> Vec<KafkaMessage> {
+        let broker = self.broker();
+        let serdes = Arc::new(TopicSerdes::json());
+        let Some(partitions) = broker.topics.get(topic) else {
+            return Vec::new();
+        };
+
+        partitions
+            .iter()
+            .enumerate()
+            .flat_map(|(partition, records)| {
+                let serdes = serdes.clone();
+                records.iter().enumerate().map(move |(offset, record)| {
+                    to_kafka_message(topic, partition as i32, offset as i64, record, serdes.clone())
+                })
+            })
+            .collect()
+    }
+
+    /// Offset committed by `group_id` for `partition`
+    pub fn committed_offset(&self, group_id: &str, partition: &TopicPartition) -> Option<i64> {
+        self.broker().committed.get(&(group_id.to_string(), partition.clone())).copied()
+    }
+
+    fn append(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        key: &str,
+        payload: Option<&[u8]>,
+        headers: &[(&str, &[u8])],
+    ) -> Result<(i32, i64)> {
+        let mut broker = self.broker();
+        let partitions = broker.topic(topic, self.default_partitions);
+        let partition = match partition {
+            Some(partition) if partition >= 0 && (partition as usize) < partitions.len() => partition,
+            Some(partition) => {
+                return Err(TicketMasterError::InvalidArgument(format!(
+                    "Partition {} does not exist on topic {}",
+                    partition, topic
+                )))
+            }
+            None => {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                (hasher.finish() % partitions.len() as u64) as i32
+            }
+        };
+
+        let records = &mut partitions[partition as usize];
+        records.push(MockRecord {
+            key: Some(key.to_string()),
+            payload: payload.map(<[u8]>::to_vec),
+            headers: headers.iter().map(|(key, value)| (key.to_string(), value.to_vec())).collect(),
+            timestamp_ms: chrono::Utc::now().timestamp_millis(),
+        });
+        let offset = records.len() as i64 - 1;
+        drop(broker);
+
+        self.changed.notify_waiters();
+        Ok((partition, offset))
+    }
+
+    fn broker(&self) -> MutexGuard<'_, Broker> {
+        self.broker.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn to_kafka_message(topic: &str, partition: i32, offset: i64, record: &MockRecord, serdes: Arc<TopicSerdes>) -> KafkaMessage {
+    KafkaMessage {
+        topic: topic.to_string(),
+        partition,
+        offset,
+        key: record.key.clone(),
+        payload: record.payload.clone(),
+        headers: record.headers.clone(),
+        serdes,
+    }
+}
+
+/// Producer writing to a `MockKafka`, with the same API as `KafkaProducer`
+#[derive(Clone)]
+pub struct MockProducer {
+    kafka: MockKafka,
+    serdes: Arc<TopicSerdes>,
+}
+
+impl MockProducer {
+    pub fn with_serdes(mut self, serdes: Arc<TopicSerdes>) -> Self {
+        self.serdes = serdes;
+        self
+    }
+
+    pub fn serdes(&self) -> &TopicSerdes {
+        &self.serdes
+    }
+
+    pub async fn send<T>(&self, topic: &str, key: &str, value: &T) -> Result<()>
+    where
+        T: SerdeValue,
+    {
+        let payload = self.serdes.serde_for::<T>(topic)?.serialize(topic, value).await?;
+        self.send_bytes(topic, None, key, Some(&payload)).await
+    }
+
+    pub async fn send_bytes(&self, topic: &str, partition: Option<i32>, key: &str, payload: Option<&[u8]>) -> Result<()> {
+        self.send_bytes_with_headers(topic, partition, key, payload, &[]).await
+    }
+
+    pub async fn send_bytes_with_headers(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        key: &str,
+        payload: Option<&[u8]>,
+        headers: &[(&str, &[u8])],
+    ) -> Result<()> {
+        self.kafka.append(topic, partition, key, payload, headers)?;
+        Ok(())
+    }
+
+    pub async fn flush(&self, _timeout: Duration) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct ConsumerState {
+    subscription: Vec<String>,
+    positions: HashMap<TopicPartition, i64>,
+    paused: bool,
+    // Partition index to poll first, so partitions are consumed round-robin
+    next_partition: usize,
+}
+
+/// Consumer reading from a `MockKafka`, with the same API as `KafkaConsumer`
+///
+/// Consumption starts at the group's committed offsets, or at the beginning
+/// of each partition.
+pub struct MockConsumer {
+    kafka: MockKafka,
+    group_id: String,
+    serdes: Arc<TopicSerdes>,
+    state: Mutex<ConsumerState>,
+    listener: Option<Arc<dyn RebalanceListener>>,
+}
+
+impl MockConsumer {
+    pub fn with_serdes(mut self, serdes: Arc<TopicSerdes>) -> Self {
+        self.serdes = serdes;
+        self
+    }
+
+    pub fn partition_count(&self, topic: &str) -> Result<i32> {
+        Ok(self.kafka.broker().topics.get(topic).map_or(0, |partitions| partitions.len() as i32))
+    }
+
+    pub fn assignment(&self) -> Result<Vec<TopicPartition>> {
+        let broker = self.kafka.broker();
+        let state = self.state();
+        Ok(assigned_partitions(&broker, &state.subscription))
+    }
+
+    /// Revokes the whole previous assignment first, as an eager rebalance does
+    pub fn subscribe(&self, topics: &[&str]) -> Result<()> {
+        self.revoke_assignment()?;
+        {
+            let mut broker = self.kafka.broker();
+            for topic in topics {
+                broker.topic(topic, self.kafka.default_partitions);
+            }
+
+            let mut state = self.state();
+            state.subscription = topics.iter().map(|topic| topic.to_string()).collect();
+            state.positions.clear();
+        }
+        if let Some(listener) = &self.listener {
+            listener.on_partitions_assigned(&self.assignment()?);
+        }
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self) -> Result<()> {
+        self.revoke_assignment()?;
+        let mut state = self.state();
+        state.subscription.clear();
+        state.positions.clear();
+        Ok(())
+    }
+
+    // Called without the broker locked, as listeners may commit
+    fn revoke_assignment(&self) -> Result<()> {
+        if let Some(listener) = &self.listener {
+            let assigned = self.assignment()?;
+            if !assigned.is_empty() {
+                listener.on_partitions_revoked(&assigned);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn pause(&self) -> Result<()> {
+        self.state().paused = true;
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        self.state().paused = false;
+        self.kafka.changed.notify_waiters();
+        Ok(())
+    }
+
+    pub async fn recv_message(&self, timeout_duration: Duration) -> Result<Option<KafkaMessage>> {
+        Ok(timeout(timeout_duration, self.next_message()).await.ok())
+    }
+
+    pub fn stream(&self) -> impl Stream<Item = Result<KafkaMessage>> + '_ {
+        futures::stream::unfold(self, |consumer| async move { Some((Ok(consumer.next_message().await), consumer)) })
+    }
+
+    pub fn seek_to_offset(&self, partition: &TopicPartition, offset: i64) -> Result<()> {
+        self.state().positions.insert(partition.clone(), offset.max(0));
+        self.kafka.changed.notify_waiters();
+        Ok(())
+    }
+
+    pub fn seek_to_timestamp(&self, partitions: &[TopicPartition], timestamp_ms: i64) -> Result<()> {
+        let broker = self.kafka.broker();
+        let mut state = self.state();
+        for tp in partitions {
+            let end = broker.end_offset(tp);
+            let offset = (0..end)
+                .find(|offset| broker.record(tp, *offset).is_some_and(|record| record.timestamp_ms >= timestamp_ms))
+                .unwrap_or(end);
+            state.positions.insert(tp.clone(), offset);
+        }
+        drop(state);
+        drop(broker);
+
+        self.kafka.changed.notify_waiters();
+        Ok(())
+    }
+
+    pub fn seek_to_beginning(&self, partitions: &[TopicPartition]) -> Result<()> {
+        let mut state = self.state();
+        for tp in partitions {
+            state.positions.insert(tp.clone(), 0);
+        }
+        drop(state);
+
+        self.kafka.changed.notify_waiters();
+        Ok(())
+    }
+
+    pub fn commit_message(&self, message: &KafkaMessage) -> Result<()> {
+        self.commit_offsets(&[(TopicPartition::new(&message.topic, message.partition), message.offset + 1)])
+    }
+
+    pub fn commit_offsets(&self, offsets: &[(TopicPartition, i64)]) -> Result<()> {
+        let mut broker = self.kafka.broker();
+        for (tp, offset) in offsets {
+            broker.committed.insert((self.group_id.clone(), tp.clone()), *offset);
+        }
+        Ok(())
+    }
+
+    pub fn commit_offsets_sync(&self, offsets: &[(TopicPartition, i64)]) -> Result<()> {
+        self.commit_offsets(offsets)
+    }
+
+    pub fn group_lag(&self, topics: &[&str]) -> Result<Vec<PartitionLag>> {
+        let broker = self.kafka.broker();
+        let partitions = topics.iter().flat_map(|topic| {
+            let count = broker.topics.get(*topic).map_or(0, Vec::len);
+            (0..count as i32).map(move |partition| TopicPartition::new(topic, partition))
+        });
+        Ok(partitions
+            .map(|tp| {
+                let committed = broker.committed.get(&(self.group_id.clone(), tp.clone())).copied();
+                let high_watermark = broker.end_offset(&tp);
+                PartitionLag {
+                    lag: (high_watermark - committed.unwrap_or(0)).max(0),
+                    topic: tp.topic,
+                    partition: tp.partition,
+                    committed,
+                    high_watermark,
+                }
+            })
+            .collect())
+    }
+
+    async fn next_message(&self) -> KafkaMessage {
+        loop {
+            // Register interest before polling so an append in between is not missed
+            let changed = self.kafka.changed.notified();
+            if let Some(message) = self.poll() {
+                return message;
+            }
+            changed.await;
+        }
+    }
+
+    fn poll(&self) -> Option<KafkaMessage> {
+        let broker = self.kafka.broker();
+        let mut state = self.state();
+        if state.paused {
+            return None;
+        }
+
+        let partitions = assigned_partitions(&broker, &state.subscription);
+        for i in 0..partitions.len() {
+            let index = (state.next_partition + i) % partitions.len();
+            let tp = &partitions[index];
+            let committed = broker.committed.get(&(self.group_id.clone(), tp.clone())).copied();
+            let position = *state.positions.entry(tp.clone()).or_insert(committed.unwrap_or(0));
+
+            if let Some(record) = broker.record(tp, position) {
+                state.positions.insert(tp.clone(), position + 1);
+                state.next_partition = index + 1;
+                return Some(to_kafka_message(&tp.topic, tp.partition, position, record, self.serdes.clone()));
+            }
+        }
+        None
+    }
+
+    fn state(&self) -> MutexGuard<'_, ConsumerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn assigned_partitions(broker: &Broker, subscription: &[String]) -> Vec<TopicPartition> {
+    subscription
+        .iter()
+        .flat_map(|topic| {
+            let count = broker.topics.get(topic).map_or(0, Vec::len);
+            (0..count as i32).map(move |partition| TopicPartition::new(topic, partition))
+        })
+        .collect()
+}
@@ -2,10 +2,52 @@ pub mod producer;
 pub mod consumer;
 pub mod streams;
 pub mod rocksdb_store;
+pub mod cache_store;
+pub mod cached_store;
+pub mod versioned_store;
+pub mod indexed_store;
 pub mod avro_serializer;
+pub mod changelog;
+pub mod serdes;
+pub mod dispatcher;
+pub mod offsets;
+pub mod backpressure;
+pub mod mock;
+pub mod traits;
+pub mod outbox;
+pub mod dedup;
+pub mod bridge;
+pub mod query;
+pub mod global_table;
+pub mod deadline_index;
+pub mod audit_log;
+pub mod statistics;
+pub mod heartbeat;
+pub mod replay;
 
 pub use producer::*;
 pub use consumer::*;
 pub use streams::*;
 pub use rocksdb_store::*;
-pub use avro_serializer::*;
\ No newline at end of file
+pub use cache_store::*;
+pub use cached_store::*;
+pub use versioned_store::*;
+pub use indexed_store::*;
+pub use avro_serializer::*;
+pub use changelog::*;
+pub use serdes::*;
+pub use dispatcher::*;
+pub use offsets::*;
+pub use backpressure::*;
+pub use mock::*;
+pub use traits::*;
+pub use outbox::*;
+pub use dedup::*;
+pub use bridge::*;
+pub use query::*;
+pub use global_table::*;
+pub use deadline_index::*;
+pub use audit_log::*;
+pub use statistics::*;
+pub use heartbeat::*;
+pub use replay::*;
\ No newline at end of file
@@ -0,0 +1,210 @@
+use crate::{KafkaMessage, MessageConsumer, RebalanceListener, TopicPartition};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, PoisonError, Weak};
+use std::time::Duration;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tracing::{error, info, warn};
+
+// Longest a rebalance waits for the records of revoked partitions in flight
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+struct PartitionOffsets {
+    in_flight: BTreeSet<i64>,
+    // One past the highest completed offset
+    completed_up_to: Option<i64>,
+    committed: Option<i64>,
+}
+
+impl PartitionOffsets {
+    // Everything below the lowest in-flight offset has been processed
+    fn commit_position(&self) -> Option<i64> {
+        let completed_up_to = self.completed_up_to?;
+        match self.in_flight.first() {
+            Some(&lowest) => Some(lowest.min(completed_up_to)),
+            None => Some(completed_up_to),
+        }
+    }
+}
+
+/// Tracks processed offsets so commits never skip a message still being processed
+///
+/// Messages are tracked when consumed and completed when processing finishes,
+/// in any order. `take_commits` returns, per partition, the offset after the
+/// highest contiguous completed message, if it moved since the last call.
+#[derive(Debug, Default)]
+pub struct OffsetTracker {
+    partitions: HashMap<TopicPartition, PartitionOffsets>,
+}
+
+impl OffsetTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that a message was consumed and is about to be processed
+    pub fn track(&mut self, message: &KafkaMessage) {
+        self.partitions
+            .entry(TopicPartition::new(&message.topic, message.partition))
+            .or_default()
+            .in_flight
+            .insert(message.offset);
+    }
+
+    /// Record that a message is done, whether it succeeded or was skipped;
+    /// messages of partitions no longer tracked, e.g. revoked ones, are ignored
+    pub fn complete(&mut self, message: &KafkaMessage) {
+        let Some(partition) = self.partitions.get_mut(&TopicPartition::new(&message.topic, message.partition)) else {
+            return;
+        };
+        partition.in_flight.remove(&message.offset);
+        let next = message.offset + 1;
+        partition.completed_up_to = Some(partition.completed_up_to.map_or(next, |up_to| up_to.max(next)));
+    }
+
+    /// Number of tracked messages that have not completed yet
+    pub fn in_flight(&self) -> usize {
+        self.partitions.values().map(|partition| partition.in_flight.len()).sum()
+    }
+
+    /// Number of tracked messages of `partitions` that have not completed yet
+    pub fn in_flight_of(&self, partitions: &[TopicPartition]) -> usize {
+        partitions
+            .iter()
+            .filter_map(|tp| self.partitions.get(tp))
+            .map(|partition| partition.in_flight.len())
+            .sum()
+    }
+
+    /// Stop tracking `partitions`, e.g. once they are revoked, returning the
+    /// offsets still to commit for them
+    pub fn remove(&mut self, partitions: &[TopicPartition]) -> Vec<(TopicPartition, i64)> {
+        partitions
+            .iter()
+            .filter_map(|tp| {
+                let partition = self.partitions.remove(tp)?;
+                let position = partition.commit_position()?;
+                (partition.committed != Some(position)).then(|| (tp.clone(), position))
+            })
+            .collect()
+    }
+
+    /// Offsets to commit, marking them as committed
+    pub fn take_commits(&mut self) -> Vec<(TopicPartition, i64)> {
+        let mut commits = Vec::new();
+        for (tp, partition) in &mut self.partitions {
+            if let Some(position) = partition.commit_position() {
+                if partition.committed != Some(position) {
+                    partition.committed = Some(position);
+                    commits.push((tp.clone(), position));
+                }
+            }
+        }
+        commits
+    }
+}
+
+/// An `OffsetTracker` a `ConsumerRunner` shares with its consumer's
+/// rebalance listener, so the records of revoked partitions are processed
+/// and committed before another consumer takes the partitions over
+///
+/// Clones share the offsets. As a listener itself it only drains; listeners
+/// doing more call `drain` first, while the partitions' state is still theirs.
+#[derive(Clone, Default)]
+pub struct PartitionDrain {
+    shared: Arc<DrainState>,
+}
+
+#[derive(Default)]
+struct DrainState {
+    offsets: Mutex<OffsetTracker>,
+    completed: Condvar,
+    // Weak, as the consumer holds the listener holding this
+    consumer: OnceLock<Weak<dyn MessageConsumer>>,
+}
+
+impl PartitionDrain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Commit drained offsets through `consumer`; only the first one attached is used
+    pub fn attach<C: MessageConsumer>(&self, consumer: &Arc<C>) {
+        let consumer: Weak<dyn MessageConsumer> = Arc::downgrade(consumer);
+        let _ = self.shared.consumer.set(consumer);
+    }
+
+    pub fn track(&self, message: &KafkaMessage) {
+        self.offsets().track(message);
+    }
+
+    pub fn complete(&self, message: &KafkaMessage) {
+        self.offsets().complete(message);
+        self.shared.completed.notify_all();
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.offsets().in_flight()
+    }
+
+    pub fn take_commits(&self) -> Vec<(TopicPartition, i64)> {
+        self.offsets().take_commits()
+    }
+
+    /// Stop tracking every partition, e.g. once a run has ended; records
+    /// left in flight are read again from the committed offsets
+    pub fn clear(&self) {
+        *self.offsets() = OffsetTracker::new();
+        self.shared.completed.notify_all();
+    }
+
+    /// Wait for the records of `partitions` in flight, then commit them and
+    /// stop tracking the partitions
+    ///
+    /// Records still in flight after 30 seconds are left to the partitions'
+    /// next owner, which processes them again.
+    pub fn drain(&self, partitions: &[TopicPartition]) {
+        let commits = block_in_place(|| {
+            let (mut offsets, waited) = self
+                .shared
+                .completed
+                .wait_timeout_while(self.offsets(), DRAIN_TIMEOUT, |offsets| offsets.in_flight_of(partitions) > 0)
+                .unwrap_or_else(PoisonError::into_inner);
+            if waited.timed_out() {
+                warn!("Gave up on {} records in flight of revoked partitions", offsets.in_flight_of(partitions));
+            }
+            offsets.remove(partitions)
+        });
+        if commits.is_empty() {
+            return;
+        }
+        let Some(consumer) = self.shared.consumer.get().and_then(Weak::upgrade) else {
+            return;
+        };
+        match consumer.commit_offsets_sync(&commits) {
+            Ok(()) => info!("Committed offsets of revoked partitions: {:?}", commits),
+            Err(e) => error!("Error committing offsets of revoked partitions: {}", e),
+        }
+    }
+
+    fn offsets(&self) -> MutexGuard<'_, OffsetTracker> {
+        self.shared.offsets.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl RebalanceListener for PartitionDrain {
+    fn on_partitions_revoked(&self, partitions: &[TopicPartition]) {
+        self.drain(partitions);
+    }
+
+    fn on_partitions_assigned(&self, _partitions: &[TopicPartition]) {}
+}
+
+// Rebalance callbacks run on the consuming task, whose worker may have lanes
+// queued that must run elsewhere while it waits for them
+fn block_in_place<R>(f: impl FnOnce() -> R) -> R {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => tokio::task::block_in_place(f),
+        _ => f(),
+    }
+}
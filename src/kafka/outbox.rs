@@ -0,0 +1,160 @@
+use crate::{retry_kafka_operation, MessageProducer, Result, RocksDBStore, SerdeValue, TopicSerdes};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+/// Header carrying the outbox entry id, so consumers can recognize a command
+/// the relay published more than once
+pub const OUTBOX_ID_HEADER: &str = "outbox-id";
+
+/// A command waiting to be published
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: String,
+    pub topic: String,
+    pub key: String,
+    pub payload: Vec<u8>,
+    /// Sent along with `OUTBOX_ID_HEADER`, e.g. `REQUEST_ID_HEADER`
+    pub headers: Vec<(String, String)>,
+}
+
+// Entries enqueued before entries had headers; bincode can't default a missing field
+#[derive(Deserialize)]
+struct HeaderlessOutboxEntry {
+    id: String,
+    topic: String,
+    key: String,
+    payload: Vec<u8>,
+}
+
+impl From<HeaderlessOutboxEntry> for OutboxEntry {
+    fn from(entry: HeaderlessOutboxEntry) -> Self {
+        Self {
+            id: entry.id,
+            topic: entry.topic,
+            key: entry.key,
+            payload: entry.payload,
+            headers: Vec::new(),
+        }
+    }
+}
+
+/// Commands persisted in RocksDB before they are published to Kafka
+///
+/// Entries are stored under a zero-padded sequence number, so they are
+/// published in the order they were enqueued, and removed once Kafka has
+/// acknowledged them.
+pub struct Outbox {
+    store: RocksDBStore,
+    next_sequence: AtomicU64,
+    enqueued: Notify,
+}
+
+impl Outbox {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        std::fs::create_dir_all(&path)?;
+        let store = RocksDBStore::new(path)?;
+        let next_sequence = store
+            .keys()
+            .last()
+            .transpose()?
+            .and_then(|key| key.parse::<u64>().ok())
+            .map_or(0, |sequence| sequence + 1);
+
+        Ok(Self {
+            store,
+            next_sequence: AtomicU64::new(next_sequence),
+            enqueued: Notify::new(),
+        })
+    }
+
+    /// Encode `value` with the topic's serde and persist it; returns the entry id
+    pub async fn enqueue<T: SerdeValue>(&self, serdes: &TopicSerdes, topic: &str, key: &str, value: &T) -> Result<String> {
+        self.enqueue_with_headers(serdes, topic, key, value, &[]).await
+    }
+
+    /// `enqueue` with headers to publish the command with
+    pub async fn enqueue_with_headers<T: SerdeValue>(
+        &self,
+        serdes: &TopicSerdes,
+        topic: &str,
+        key: &str,
+        value: &T,
+        headers: &[(&str, &str)],
+    ) -> Result<String> {
+        let payload = serdes.serde_for::<T>(topic)?.serialize(topic, value).await?;
+        let entry = OutboxEntry {
+            id: Uuid::new_v4().to_string(),
+            topic: topic.to_string(),
+            key: key.to_string(),
+            payload,
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+        };
+
+        let sequence = self.next_sequence.fetch_add(1, Ordering::SeqCst);
+        self.store.put(&format!("{:020}", sequence), &entry)?;
+        self.enqueued.notify_one();
+        Ok(entry.id)
+    }
+
+    /// Entries not published yet, oldest first, with their store keys
+    pub fn pending(&self) -> Result<Vec<(String, OutboxEntry)>> {
+        let mut pending = Vec::new();
+        for key in self.store.keys() {
+            let key = key?;
+            let entry = match self.store.get::<OutboxEntry>(&key) {
+                Ok(entry) => entry,
+                Err(_) => self.store.get::<HeaderlessOutboxEntry>(&key)?.map(OutboxEntry::from),
+            };
+            pending.extend(entry.map(|entry| (key, entry)));
+        }
+        Ok(pending)
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.store.delete(key)
+    }
+}
+
+/// Publish outbox entries as they are enqueued, including any left over from
+/// a previous run
+///
+/// Entries are retried with backoff; if one still fails, the relay waits for
+/// `retry_interval` and starts again from the oldest entry, so commands keep
+/// their order. Every message carries its entry id in `OUTBOX_ID_HEADER`,
+/// which stays the same if a crash between publishing and removing an entry
+/// causes it to be published again.
+pub fn spawn_outbox_relay<P: MessageProducer>(outbox: Arc<Outbox>, producer: P, retry_interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = relay_pending(&outbox, &producer).await {
+                error!("Outbox relay failed, retrying in {:?}: {}", retry_interval, e);
+                sleep(retry_interval).await;
+                continue;
+            }
+            outbox.enqueued.notified().await;
+        }
+    })
+}
+
+async fn relay_pending<P: MessageProducer>(outbox: &Outbox, producer: &P) -> Result<()> {
+    for (key, entry) in outbox.pending()? {
+        let headers: Vec<(&str, &[u8])> = std::iter::once((OUTBOX_ID_HEADER, entry.id.as_bytes()))
+            .chain(entry.headers.iter().map(|(name, value)| (name.as_str(), value.as_bytes())))
+            .collect();
+        retry_kafka_operation("publish outbox entry", || {
+            producer.send_bytes_with_headers(&entry.topic, None, &entry.key, Some(&entry.payload), &headers)
+        })
+        .await?;
+        outbox.remove(&key)?;
+        debug!("Published outbox entry {} to {}", entry.id, entry.topic);
+    }
+    Ok(())
+}
@@ -1,35 +1,177 @@
-use crate::{Result, TicketMasterError};
+use crate::{
+    kafka_error_code, trace_context_headers, CircuitBreaker, Metrics, Result, SerdeValue,
+    StatisticsContext, TicketMasterError, TopicResolver, TopicSerdes, TRACEPARENT_HEADER,
+};
+use rdkafka::error::KafkaError;
+use rdkafka::message::{Header, OwnedHeaders};
 use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
 use rdkafka::ClientConfig;
-use serde::Serialize;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{error, info_span, Instrument};
+
+/// Outcome of delivering a single message
+#[derive(Debug, Clone)]
+pub struct DeliveryReport {
+    pub topic: String,
+    pub key: String,
+    /// Partition and offset the message was written to; `None` if delivery failed
+    pub partition: Option<i32>,
+    pub offset: Option<i64>,
+    pub error: Option<KafkaError>,
+}
+
+impl DeliveryReport {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
 
 #[derive(Clone)]
 pub struct KafkaProducer {
-    producer: FutureProducer,
+    producer: FutureProducer<StatisticsContext>,
+    serdes: Arc<TopicSerdes>,
+    topics: TopicResolver,
+    delivery_reports: Option<mpsc::UnboundedSender<DeliveryReport>>,
+    metrics: Option<Metrics>,
+    // Shared with the client, which keeps its context to itself
+    statistics: StatisticsContext,
+    breaker: Option<Arc<CircuitBreaker>>,
 }
 
 impl KafkaProducer {
     pub fn new(config: ClientConfig) -> Result<Self> {
-        let producer: FutureProducer = config.create()?;
-        Ok(Self { producer })
+        Self::with_serdes(config, Arc::new(TopicSerdes::json()))
+    }
+
+    /// Create a producer that encodes values with the serde configured for each topic
+    pub fn with_serdes(config: ClientConfig, serdes: Arc<TopicSerdes>) -> Result<Self> {
+        let statistics = StatisticsContext::default();
+        let producer = config.create_with_context(statistics.clone())?;
+        Ok(Self {
+            producer,
+            serdes,
+            topics: TopicResolver::default(),
+            delivery_reports: None,
+            metrics: None,
+            statistics,
+            breaker: None,
+        })
+    }
+
+    /// Send to the cluster topics `topics` resolves logical topic names to
+    pub fn with_topic_resolver(mut self, topics: TopicResolver) -> Self {
+        self.topics = topics;
+        self
+    }
+
+    /// Record sends, their durations, payload sizes and failures per topic in
+    /// `metrics`, along with the client's librdkafka statistics
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.statistics.attach(metrics.clone());
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Fail sends fast with `CircuitOpen` while `breaker` is open; every
+    /// failed send counts towards opening it
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    /// Report the outcome of every message sent through this producer (and its clones)
+    pub fn with_delivery_reports(mut self) -> (Self, mpsc::UnboundedReceiver<DeliveryReport>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.delivery_reports = Some(sender);
+        (self, receiver)
+    }
+
+    pub fn serdes(&self) -> &TopicSerdes {
+        &self.serdes
     }
 
     pub async fn send<T>(&self, topic: &str, key: &str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: SerdeValue,
     {
-        let payload = serde_json::to_string(value)?;
-        
-        let record = FutureRecord::to(topic)
-            .key(key)
-            .payload(&payload);
+        let payload = self.serdes.serde_for::<T>(topic)?.serialize(topic, value).await?;
+        self.send_bytes(topic, None, key, Some(&payload)).await
+    }
 
-        self.producer
-            .send(record, Duration::from_secs(10))
-            .await
-            .map_err(|(kafka_err, _)| TicketMasterError::Kafka(kafka_err))?;
+    /// Send an already-encoded payload, optionally to a fixed partition;
+    /// `None` produces a tombstone
+    pub async fn send_bytes(&self, topic: &str, partition: Option<i32>, key: &str, payload: Option<&[u8]>) -> Result<()> {
+        self.send_bytes_with_headers(topic, partition, key, payload, &[]).await
+    }
+
+    /// `send_bytes` with message headers attached
+    ///
+    /// The trace context of the send is added to the headers, unless they
+    /// already carry one, so the consumer continues the trace.
+    pub async fn send_bytes_with_headers(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        key: &str,
+        payload: Option<&[u8]>,
+        headers: &[(&str, &[u8])],
+    ) -> Result<()> {
+        let span = info_span!("produce", otel.kind = "producer", topic = %topic, key = %key);
+        let trace_context = if headers.iter().any(|(name, _)| *name == TRACEPARENT_HEADER) {
+            Vec::new()
+        } else {
+            trace_context_headers(&span)
+        };
+        let resolved = self.topics.resolve(topic);
+        let mut record: FutureRecord<str, [u8]> = FutureRecord::to(&resolved).key(key);
+        if let Some(partition) = partition {
+            record = record.partition(partition);
+        }
+        if let Some(payload) = payload {
+            record = record.payload(payload);
+        }
+        if !headers.is_empty() || !trace_context.is_empty() {
+            let traced = trace_context.iter().map(|(key, value)| (key.as_str(), value.as_bytes()));
+            let owned = headers.iter().copied().chain(traced).fold(OwnedHeaders::new(), |owned, (key, value)| {
+                owned.insert(Header { key, value: Some(value) })
+            });
+            record = record.headers(owned);
+        }
+
+        let permit = self.breaker.as_ref().map(|breaker| breaker.permit()).transpose()?;
+        let started = Instant::now();
+        let result = self.producer.send(record, Duration::from_secs(10)).instrument(span).await;
+        match (permit, &result) {
+            (Some(permit), Ok(_)) => permit.succeeded(),
+            (Some(permit), Err(_)) => permit.failed(),
+            (None, _) => {}
+        }
+        if let Some(metrics) = &self.metrics {
+            let error = result.as_ref().err().map(|(kafka_err, _)| kafka_error_code(kafka_err));
+            let bytes = payload.map_or(0, <[u8]>::len);
+            metrics.record_kafka_send(topic, bytes, started.elapsed(), error.as_deref());
+        }
 
+        if let Some(reports) = &self.delivery_reports {
+            let (delivered, error) = match &result {
+                Ok((partition, offset)) => (Some((*partition, *offset)), None),
+                Err((kafka_err, _)) => (None, Some(kafka_err.clone())),
+            };
+            let report = DeliveryReport {
+                topic: topic.to_string(),
+                key: key.to_string(),
+                partition: delivered.map(|(partition, _)| partition),
+                offset: delivered.map(|(_, offset)| offset),
+                error,
+            };
+            // Nobody listening any more is not a send failure
+            let _ = reports.send(report);
+        }
+
+        result.map_err(|(kafka_err, _)| TicketMasterError::Kafka(kafka_err))?;
         Ok(())
     }
 
@@ -37,4 +179,19 @@ impl KafkaProducer {
         self.producer.flush(timeout)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+/// Log every failed delivery reported on `reports`
+///
+/// The monitor only alerts: the send already returned the error to its
+/// caller, which retries it, e.g. through `retry_transient` or a redelivery,
+/// so resending here too would publish the message twice. Failed sends are
+/// counted by a producer created `with_metrics`.
+pub fn spawn_delivery_monitor(mut reports: mpsc::UnboundedReceiver<DeliveryReport>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(report) = reports.recv().await {
+            if let Some(error) = &report.error {
+                error!("Delivery of {} to {} failed: {}", report.key, report.topic, error);
+            }
+        }
+    })
+}
@@ -0,0 +1,381 @@
+use crate::{
+    event_area_key, Availability, CheckAvailability, CircuitBreaker, MessageConsumer, MessageProducer, Metrics,
+    RebalanceListener, Result, TicketMasterError, TopicPartition, Topics,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use dashmap::DashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+// Queries failing to reach an instance in a row before the client fails fast
+const QUERY_FAILURE_THRESHOLD: u32 = 5;
+const QUERY_RECOVERY_TIMEOUT: Duration = Duration::from_secs(30);
+const STATE_QUERIES_BREAKER: &str = "state_queries";
+
+/// An instance serving state queries, and the partitions whose state it holds
+///
+/// Instances announce themselves on `internal.query.hosts`, keyed by
+/// application and server, so the compacted topic keeps the latest
+/// announcement of each.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryHost {
+    pub application_id: String,
+    /// `host:port` state queries are served on
+    pub server: String,
+    /// Assigned partitions of the topic the application's state is keyed by
+    pub partitions: Vec<i32>,
+    pub partition_count: i32,
+}
+
+impl QueryHost {
+    pub fn new(application_id: &str, server: &str, partition_count: i32) -> Self {
+        Self {
+            application_id: application_id.to_string(),
+            server: server.to_string(),
+            partitions: Vec::new(),
+            partition_count,
+        }
+    }
+
+    /// Key of the host's announcements
+    pub fn announcement_key(&self) -> String {
+        format!("{}#{}", self.application_id, self.server)
+    }
+
+    pub fn owns(&self, key: &str) -> bool {
+        self.partitions.contains(&key_partition(key, self.partition_count))
+    }
+}
+
+/// Partition a message keyed by `key` is produced to
+///
+/// Matches librdkafka's default `consistent_random` partitioner, the CRC32
+/// of the key modulo the partition count.
+pub fn key_partition(key: &str, partition_count: i32) -> i32 {
+    (crc32(key.as_bytes()) % partition_count.max(1) as u32) as i32
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Looks up values in this instance's stores for other instances
+pub trait StateQueryHandler: Send + Sync + 'static {
+    /// The value under `key` in `store` as JSON, or `None` if this instance doesn't hold it
+    fn query(&self, store: &str, key: &str) -> Result<Option<serde_json::Value>>;
+
+    /// Answer `request` from the area's status, or `None` if this instance doesn't hold the area
+    fn check_availability(&self, _request: &CheckAvailability) -> Result<Option<Availability>> {
+        Ok(None)
+    }
+}
+
+/// `GET /state/:store/:key` and `POST /availability`, answered by `handler`;
+/// 404 when the key or area isn't held here
+pub fn state_query_router(handler: Arc<dyn StateQueryHandler>) -> Router {
+    Router::new()
+        .route("/state/:store/:key", get(query_state))
+        .route("/availability", post(check_availability))
+        .with_state(handler)
+}
+
+async fn query_state(
+    State(handler): State<Arc<dyn StateQueryHandler>>,
+    Path((store, key)): Path<(String, String)>,
+) -> Response {
+    match handler.query(&store, &key) {
+        Ok(Some(value)) => Json(value).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            error!("Error querying {} in store {}: {}", key, store, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+async fn check_availability(
+    State(handler): State<Arc<dyn StateQueryHandler>>,
+    Json(request): Json<CheckAvailability>,
+) -> Response {
+    match handler.check_availability(&request) {
+        Ok(Some(availability)) => Json(availability).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(TicketMasterError::InvalidArgument(message)) => (StatusCode::BAD_REQUEST, message).into_response(),
+        Err(e) => {
+            error!("Error checking availability in {}#{}: {}", request.event_id, request.area_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+/// Serve state queries on every interface, on the port of `server` (`host:port`)
+pub async fn serve_state_queries(server: &str, handler: Arc<dyn StateQueryHandler>) -> Result<JoinHandle<()>> {
+    let port: u16 = server
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse().ok())
+        .ok_or_else(|| TicketMasterError::InvalidArgument(format!("Expected host:port for application.server, got {}", server)))?;
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+    info!("Serving state queries for {} on port {}", server, port);
+
+    let router = state_query_router(handler);
+    Ok(tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!("State query server stopped: {}", e);
+        }
+    }))
+}
+
+/// Partitions of one topic currently assigned to this instance
+///
+/// Usable as the consumer's rebalance listener, or fed by one that also does
+/// other work on rebalances.
+pub struct AssignedPartitions {
+    topic: String,
+    sender: watch::Sender<BTreeSet<i32>>,
+}
+
+impl AssignedPartitions {
+    pub fn new(topic: &str) -> Self {
+        Self {
+            topic: topic.to_string(),
+            sender: watch::channel(BTreeSet::new()).0,
+        }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<BTreeSet<i32>> {
+        self.sender.subscribe()
+    }
+
+    pub fn current(&self) -> BTreeSet<i32> {
+        self.sender.borrow().clone()
+    }
+
+    fn update(&self, partitions: &[TopicPartition], assigned: bool) {
+        self.sender.send_if_modified(|current| {
+            let mut changed = false;
+            for tp in partitions.iter().filter(|tp| tp.topic == self.topic) {
+                changed |= if assigned { current.insert(tp.partition) } else { current.remove(&tp.partition) };
+            }
+            changed
+        });
+    }
+}
+
+impl RebalanceListener for AssignedPartitions {
+    fn on_partitions_revoked(&self, partitions: &[TopicPartition]) {
+        self.update(partitions, false);
+    }
+
+    fn on_partitions_assigned(&self, partitions: &[TopicPartition]) {
+        self.update(partitions, true);
+    }
+}
+
+/// Announce `host` on `internal.query.hosts`, with its partitions kept in
+/// step with `partitions`
+pub fn spawn_query_host_announcer<P: MessageProducer>(
+    producer: P,
+    mut host: QueryHost,
+    mut partitions: watch::Receiver<BTreeSet<i32>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            host.partitions = partitions.borrow_and_update().iter().copied().collect();
+            if let Err(e) = announce(&producer, &host).await {
+                error!("Failed to announce state query host {}: {}", host.server, e);
+            }
+            if partitions.changed().await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+async fn announce<P: MessageProducer>(producer: &P, host: &QueryHost) -> Result<()> {
+    // Always JSON, whatever serde the other topics use
+    let payload = serde_json::to_vec(host)?;
+    producer.send_bytes(Topics::INTERNAL_QUERY_HOSTS, None, &host.announcement_key(), Some(&payload)).await?;
+    debug!("Announced {} with partitions {:?}", host.server, host.partitions);
+    Ok(())
+}
+
+/// Where each application's state lives, as announced by its instances
+#[derive(Clone, Default)]
+pub struct QueryDirectory {
+    hosts: Arc<DashMap<String, QueryHost>>,
+}
+
+impl QueryDirectory {
+    pub fn update(&self, host: QueryHost) {
+        self.hosts.insert(host.announcement_key(), host);
+    }
+
+    pub fn remove(&self, announcement_key: &str) {
+        self.hosts.remove(announcement_key);
+    }
+
+    pub fn hosts(&self, application_id: &str) -> Vec<QueryHost> {
+        self.hosts
+            .iter()
+            .filter(|host| host.application_id == application_id)
+            .map(|host| host.clone())
+            .collect()
+    }
+
+    /// The instance of `application_id` holding the state for `key`, if known
+    pub fn owner(&self, application_id: &str, key: &str) -> Option<QueryHost> {
+        self.hosts
+            .iter()
+            .find(|host| host.application_id == application_id && host.owns(key))
+            .map(|host| host.clone())
+    }
+}
+
+/// Keep `directory` up to date from `internal.query.hosts`
+///
+/// The consumer should use a group of its own reading from the earliest
+/// offset, so every announcement still on the topic is seen.
+pub fn spawn_query_directory<C: MessageConsumer>(consumer: C, directory: QueryDirectory) -> Result<JoinHandle<()>> {
+    consumer.subscribe(&[Topics::INTERNAL_QUERY_HOSTS])?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let message = match consumer.recv_message(Duration::from_secs(1)).await {
+                Ok(Some(message)) => message,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("Query directory failed to receive: {}", e);
+                    continue;
+                }
+            };
+
+            match (&message.payload, &message.key) {
+                (None, Some(key)) => directory.remove(key),
+                (Some(payload), _) => match serde_json::from_slice::<QueryHost>(payload) {
+                    Ok(host) => directory.update(host),
+                    Err(e) => warn!("Skipping undecodable query host at offset {}: {}", message.offset, e),
+                },
+                (None, None) => {}
+            }
+        }
+    }))
+}
+
+/// Reads values from the stores of whichever instance owns the key
+///
+/// Once instances can't be reached several times in a row, queries fail fast
+/// with `CircuitOpen` until the recovery timeout has passed.
+#[derive(Clone)]
+pub struct StateQueryClient {
+    directory: QueryDirectory,
+    http: reqwest::Client,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl StateQueryClient {
+    pub fn new(directory: QueryDirectory) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(QUERY_TIMEOUT)
+            .build()
+            .map_err(query_error)?;
+        Ok(Self {
+            directory,
+            http,
+            breaker: Arc::new(query_breaker()),
+        })
+    }
+
+    /// Export the state of the breaker in `metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.breaker = Arc::new(query_breaker().with_metrics(metrics));
+        self
+    }
+
+    pub fn directory(&self) -> &QueryDirectory {
+        &self.directory
+    }
+
+    /// The breaker guarding requests to other instances
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// The value under `key` in `store` of `application_id`; `None` if no
+    /// instance has announced owning the key, or the owner doesn't have it
+    pub async fn get<T: DeserializeOwned>(&self, application_id: &str, store: &str, key: &str) -> Result<Option<T>> {
+        let Some(host) = self.directory.owner(application_id, key) else {
+            debug!("No {} instance owns {}", application_id, key);
+            return Ok(None);
+        };
+
+        let mut url = reqwest::Url::parse(&format!("http://{}/state", host.server))
+            .map_err(|e| TicketMasterError::InvalidArgument(format!("Invalid query host {}: {}", host.server, e)))?;
+        url.path_segments_mut()
+            .map_err(|_| TicketMasterError::InvalidArgument(format!("Invalid query host {}", host.server)))?
+            .push(store)
+            .push(key);
+
+        let response = self
+            .breaker
+            .call(|| async { self.http.get(url).send().await.map_err(query_error) })
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status().map_err(query_error)?;
+        Ok(Some(response.json().await.map_err(query_error)?))
+    }
+
+    /// Run `request` on the instance of `application_id` owning the area; `None`
+    /// if no instance has announced owning it, or the owner doesn't have it
+    pub async fn check_availability(&self, application_id: &str, request: &CheckAvailability) -> Result<Option<Availability>> {
+        let key = event_area_key(&request.event_id, &request.area_id);
+        let Some(host) = self.directory.owner(application_id, &key) else {
+            debug!("No {} instance owns {}", application_id, key);
+            return Ok(None);
+        };
+
+        let url = format!("http://{}/availability", host.server);
+        let response = self
+            .breaker
+            .call(|| async { self.http.post(url).json(request).send().await.map_err(query_error) })
+            .await?;
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            reqwest::StatusCode::BAD_REQUEST => Err(TicketMasterError::InvalidArgument(
+                response.text().await.map_err(query_error)?,
+            )),
+            _ => {
+                let response = response.error_for_status().map_err(query_error)?;
+                Ok(Some(response.json().await.map_err(query_error)?))
+            }
+        }
+    }
+}
+
+fn query_breaker() -> CircuitBreaker {
+    CircuitBreaker::new(QUERY_FAILURE_THRESHOLD, QUERY_RECOVERY_TIMEOUT).with_name(STATE_QUERIES_BREAKER)
+}
+
+fn query_error(e: reqwest::Error) -> TicketMasterError {
+    TicketMasterError::StateQuery(e.to_string())
+}
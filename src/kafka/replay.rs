@@ -0,0 +1,92 @@
+use crate::{KafkaMessage, MessageConsumer, Result, TopicPartition};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// Where each replayed partition had got to before it was rewound
+///
+/// Messages below that offset were processed before and are processed again
+/// on purpose, so they must bypass the `DedupStore`; later ones are new, and
+/// a redelivered one is still caught.
+#[derive(Debug, Default)]
+pub struct ReplayWatermarks {
+    marks: Mutex<HashMap<TopicPartition, i64>>,
+}
+
+impl ReplayWatermarks {
+    /// Whether `message` was processed before the replay rewound its partition
+    pub fn is_replayed(&self, message: &KafkaMessage) -> bool {
+        let partition = TopicPartition::new(&message.topic, message.partition);
+        self.marks.lock().unwrap().get(&partition).is_some_and(|mark| message.offset < *mark)
+    }
+
+    // A replay restarted after a crash rewinds again from where it got to,
+    // which must not lower the mark
+    fn raise(&self, partition: TopicPartition, offset: i64) {
+        let mut marks = self.marks.lock().unwrap();
+        let mark = marks.entry(partition).or_insert(offset);
+        *mark = (*mark).max(offset);
+    }
+}
+
+/// Rewinds every assigned partition to a timestamp once, so history is
+/// reprocessed and the area status stores are rebuilt
+pub struct Replay {
+    timestamp_ms: i64,
+    rewound: HashSet<TopicPartition>,
+    watermarks: Arc<ReplayWatermarks>,
+}
+
+impl Replay {
+    /// Record in `watermarks` where each partition was when it was rewound
+    pub fn new(timestamp_ms: i64, watermarks: Arc<ReplayWatermarks>) -> Self {
+        Self {
+            timestamp_ms,
+            rewound: HashSet::new(),
+            watermarks,
+        }
+    }
+
+    /// Rewind the message's partition if it has not been rewound yet; the
+    /// message was fetched from the old position and must then be skipped
+    pub fn skip<C: MessageConsumer>(&mut self, consumer: &C, message: &KafkaMessage) -> Result<bool> {
+        let partition = TopicPartition::new(&message.topic, message.partition);
+        if self.rewound.contains(&partition) {
+            return Ok(false);
+        }
+        // Everything before the first message fetched was processed
+        self.watermarks.raise(partition.clone(), message.offset);
+        self.rewind(consumer, vec![partition])?;
+        Ok(true)
+    }
+
+    /// Rewind assigned partitions that have not delivered a message yet
+    pub fn rewind_assigned<C: MessageConsumer>(&mut self, consumer: &C) -> Result<()> {
+        let pending: Vec<_> = consumer
+            .assignment()?
+            .into_iter()
+            .filter(|partition| !self.rewound.contains(partition))
+            .collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        // Nothing was fetched from these, so the group's commits mark what was processed
+        let topics: HashSet<&str> = pending.iter().map(|partition| partition.topic.as_str()).collect();
+        let topics: Vec<&str> = topics.into_iter().collect();
+        for lag in consumer.group_lag(&topics)? {
+            let partition = TopicPartition::new(&lag.topic, lag.partition);
+            if let (true, Some(committed)) = (pending.contains(&partition), lag.committed) {
+                self.watermarks.raise(partition, committed);
+            }
+        }
+        self.rewind(consumer, pending)
+    }
+
+    fn rewind<C: MessageConsumer>(&mut self, consumer: &C, partitions: Vec<TopicPartition>) -> Result<()> {
+        consumer.seek_to_timestamp(&partitions, self.timestamp_ms)?;
+        info!("Replaying {:?} from timestamp {}", partitions, self.timestamp_ms);
+        self.rewound.extend(partitions);
+        Ok(())
+    }
+}
@@ -1,13 +1,130 @@
-use crate::Result;
-use rocksdb::{DB, Options};
+use crate::{ChangelogTarget, Metrics, Result, TicketMasterError, ALL_STORES};
+use rocksdb::checkpoint::Checkpoint;
+use chrono::{DateTime, TimeZone, Utc};
+use rocksdb::{CompactionDecision, Direction, IteratorMode, DB, Options, WriteOptions};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::{debug_span, Span};
+
+// Leads every bincode value; JSON text never starts with it
+const BINCODE_TAG: u8 = 0x00;
+// Leads a soft-deleted value, followed by the deletion time in big-endian
+// epoch millis and then the value as it was
+const SOFT_DELETE_TAG: u8 = 0x01;
+
+/// How a `RocksDBStore` encodes the values it writes
+///
+/// Values are decoded by their format rather than the codec, so a store
+/// switched to bincode still reads JSON values written before; they are
+/// rewritten in the new format the next time they are put.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreCodec {
+    #[default]
+    Bincode,
+    Json,
+}
+
+impl StoreCodec {
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            Self::Bincode => {
+                let mut bytes = vec![BINCODE_TAG];
+                bincode::serialize_into(&mut bytes, value)?;
+                Ok(bytes)
+            }
+            Self::Json => Ok(serde_json::to_vec(value)?),
+        }
+    }
+
+    /// Decode a value written with either codec
+    pub fn decode<T>(bytes: &[u8]) -> Result<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        if let Some((_, value)) = soft_deleted(bytes) {
+            return Self::decode(value);
+        }
+        match bytes.split_first() {
+            Some((&BINCODE_TAG, value)) => Ok(bincode::deserialize(value)?),
+            _ => Ok(serde_json::from_slice(bytes)?),
+        }
+    }
+}
+
+/// Sizes RocksDB reports for a store, in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct StoreSizeStats {
+    pub estimated_live_data: u64,
+    pub live_sst_files: u64,
+    pub memtables: u64,
+}
+
+impl std::ops::AddAssign for StoreSizeStats {
+    fn add_assign(&mut self, other: Self) {
+        self.estimated_live_data += other.estimated_live_data;
+        self.live_sst_files += other.live_sst_files;
+        self.memtables += other.memtables;
+    }
+}
+
+// Where a store records its reads and writes
+struct StoreMetrics {
+    metrics: Metrics,
+    // Set when metrics are tagged with the store name
+    name: Option<String>,
+}
+
+impl StoreMetrics {
+    fn record_read(&self, started: Instant) {
+        self.metrics.record_store_operation(self.store(), "read", started.elapsed());
+    }
+
+    fn record_write(&self, started: Instant) {
+        self.metrics.record_store_operation(self.store(), "write", started.elapsed());
+    }
+
+    fn store(&self) -> &str {
+        self.name.as_deref().unwrap_or(ALL_STORES)
+    }
+}
+
+/// A page of entries from `RocksDBStore::scan_page`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorePage<T> {
+    pub entries: Vec<(String, T)>,
+    /// Pass to `scan_page` for the next page; `None` on the last page
+    pub next_page_token: Option<String>,
+}
+
+/// Key order of a `RocksDBStore::scan_page_where` scan
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScanOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
 
 /// RocksDB-based state store for persistent storage
 pub struct RocksDBStore {
     db: DB,
+    changelog: Option<ChangelogTarget>,
+    codec: StoreCodec,
+    key_locks: Vec<Mutex<()>>,
+    metrics: Option<StoreMetrics>,
+    write_options: WriteOptions,
+    // Read by the compaction filter; 0 keeps soft-deleted values forever
+    soft_delete_retention_ms: Arc<AtomicU64>,
 }
 
+const KEY_LOCK_STRIPES: usize = 64;
+
 impl RocksDBStore {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
         let mut opts = Options::default();
@@ -25,49 +142,599 @@ impl RocksDBStore {
         opts.set_level_zero_slowdown_writes_trigger(0);
         opts.set_compaction_style(rocksdb::DBCompactionStyle::Universal);
 
+        let soft_delete_retention_ms = Arc::new(AtomicU64::new(0));
+        let retention_ms = soft_delete_retention_ms.clone();
+        // Index entries of a soft-deleted value are soft-deleted with it (see
+        // `IndexedStore::put_soft_deleted`), so they are purged along with it
+        opts.set_compaction_filter("soft_delete_retention", move |_level: u32, _key: &[u8], value: &[u8]| {
+            let retention_ms = retention_ms.load(Ordering::Relaxed);
+            match soft_deleted(value) {
+                Some((deleted_at, _)) if retention_ms > 0 && Utc::now().timestamp_millis() - deleted_at >= retention_ms as i64 => {
+                    CompactionDecision::Remove
+                }
+                _ => CompactionDecision::Keep,
+            }
+        });
+
         let db = DB::open(&opts, path)?;
-        Ok(Self { db })
+        Ok(Self {
+            db,
+            changelog: None,
+            codec: StoreCodec::default(),
+            key_locks: (0..KEY_LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+            metrics: None,
+            write_options: WriteOptions::default(),
+            soft_delete_retention_ms,
+        })
+    }
+
+    /// Open a store whose puts and deletes are mirrored to a changelog topic
+    pub fn with_changelog<P: AsRef<Path>>(path: P, changelog: ChangelogTarget) -> Result<Self> {
+        let mut store = Self::new(path)?;
+        store.changelog = Some(changelog);
+        Ok(store)
+    }
+
+    /// Encode values written from now on with `codec`
+    pub fn with_codec(mut self, codec: StoreCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn codec(&self) -> StoreCodec {
+        self.codec
+    }
+
+    /// Record reads, writes and sizes in `metrics`, labelled with `name` if
+    /// given and with `ALL_STORES` otherwise
+    pub fn with_metrics(mut self, metrics: Metrics, name: Option<&str>) -> Self {
+        self.metrics = Some(StoreMetrics {
+            metrics,
+            name: name.map(str::to_string),
+        });
+        self
+    }
+
+    /// Sync the WAL on every write, so an acknowledged write survives a
+    /// machine crash and not just a process crash
+    pub fn with_sync_writes(mut self, sync: bool) -> Self {
+        self.write_options.set_sync(sync);
+        self
+    }
+
+    /// Let compactions purge values soft-deleted more than `retention` ago
+    pub fn with_soft_delete_retention(self, retention: Duration) -> Self {
+        self.soft_delete_retention_ms.store(retention.as_millis() as u64, Ordering::Relaxed);
+        self
     }
 
     pub fn get<T>(&self, key: &str) -> Result<Option<T>>
     where
         T: for<'de> Deserialize<'de>,
     {
-        match self.db.get(key)? {
-            Some(value) => {
-                let deserialized: T = serde_json::from_slice(&value)?;
-                Ok(Some(deserialized))
-            }
+        let _span = self.span("get").entered();
+        let started = Instant::now();
+        let value = self.db.get(key)?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_read(started);
+        }
+        match value {
+            Some(value) => Ok(Some(StoreCodec::decode(&value)?)),
             None => Ok(None),
         }
     }
 
+    /// The stored bytes of `key`, codec tag included
+    pub fn get_raw(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.db.get(key)?)
+    }
+
+    /// Values of `keys` in one native multi-get, in the order of `keys`
+    pub fn multi_get<T, K>(&self, keys: &[K]) -> Result<Vec<Option<T>>>
+    where
+        T: for<'de> Deserialize<'de>,
+        K: AsRef<str>,
+    {
+        let _span = self.span("multi_get").entered();
+        let started = Instant::now();
+        let values = self.db.multi_get(keys.iter().map(|key| key.as_ref().as_bytes()));
+        if let Some(metrics) = &self.metrics {
+            metrics.record_read(started);
+        }
+        values
+            .into_iter()
+            .map(|value| match value? {
+                Some(value) => Ok(Some(StoreCodec::decode(&value)?)),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
     pub fn put<T>(&self, key: &str, value: &T) -> Result<()>
     where
         T: Serialize,
     {
-        let serialized = serde_json::to_vec(value)?;
-        self.db.put(key, serialized)?;
+        let _span = self.span("put").entered();
+        let serialized = self.codec.encode(value)?;
+        let started = Instant::now();
+        self.db.put_opt(key, &serialized, &self.write_options)?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_write(started);
+        }
+        if let Some(target) = &self.changelog {
+            target.changelog.log_put(&target.topic, target.partition, key, &serialized);
+        }
         Ok(())
     }
 
     pub fn delete(&self, key: &str) -> Result<()> {
-        self.db.delete(key)?;
+        let _span = self.span("delete").entered();
+        let started = Instant::now();
+        self.db.delete_opt(key, &self.write_options)?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_write(started);
+        }
+        if let Some(target) = &self.changelog {
+            target.changelog.log_delete(&target.topic, target.partition, key);
+        }
+        Ok(())
+    }
+
+    /// Mark `key` deleted now while keeping its value readable, until a
+    /// compaction after the soft-delete retention purges it; returns false if
+    /// there is no such key
+    pub fn soft_delete(&self, key: &str) -> Result<bool> {
+        let _guard = self.lock_key(key);
+        let Some(value) = self.db.get(key)? else {
+            return Ok(false);
+        };
+        if soft_deleted(&value).is_some() {
+            return Ok(true);
+        }
+
+        let mut batch = self.write_batch();
+        batch.put_raw(key.as_bytes(), &mark_soft_deleted(&value, Utc::now()));
+        batch.commit()?;
+        Ok(true)
+    }
+
+    /// When `key` was soft-deleted, if it was
+    pub fn deleted_at(&self, key: &str) -> Result<Option<DateTime<Utc>>> {
+        Ok(self.db
+            .get(key)?
+            .and_then(|value| soft_deleted(&value).map(|(deleted_at, _)| deleted_at))
+            .and_then(|deleted_at| Utc.timestamp_millis_opt(deleted_at).single()))
+    }
+
+    /// Start a batch of puts and deletes that is written atomically on commit
+    pub fn write_batch(&self) -> StoreWriteBatch<'_> {
+        StoreWriteBatch {
+            store: self,
+            batch: rocksdb::WriteBatch::default(),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Write a consistent snapshot of the store to `path`, which must not exist yet
+    pub fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Checkpoint::new(&self.db)?.create_checkpoint(path)?;
+        Ok(())
+    }
+
+    /// Replace the contents of the store with a checkpoint taken by
+    /// `create_checkpoint`; returns the number of entries restored
+    ///
+    /// The change is written as one batch, and mirrored to the changelog like
+    /// any other write.
+    pub fn restore_from_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        let checkpoint = DB::open_for_read_only(&Options::default(), path, false)?;
+        let mut batch = self.write_batch();
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (key, _) = item?;
+            if checkpoint.get(&key)?.is_none() {
+                batch.delete_raw(&key);
+            }
+        }
+
+        let mut restored = 0;
+        for item in checkpoint.iterator(IteratorMode::Start) {
+            let (key, value) = item?;
+            batch.put_raw(&key, &value);
+            restored += 1;
+        }
+        batch.commit()?;
+        Ok(restored)
+    }
+
+    /// Apply a changelog record without mirroring it back to the changelog
+    pub fn restore_raw(&self, key: &[u8], value: Option<&[u8]>) -> Result<()> {
+        match value {
+            Some(value) => self.db.put(key, value)?,
+            None => self.db.delete(key)?,
+        }
         Ok(())
     }
 
+    /// All entries in key order, with values as stored
+    pub fn raw_entries(&self) -> impl Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + '_ {
+        self.db
+            .iterator(IteratorMode::Start)
+            .map(|item| {
+                let (key, value) = item?;
+                Ok((key.to_vec(), value.to_vec()))
+            })
+    }
+
+    /// Replace the contents of the store with `entries`, e.g. as read by
+    /// `raw_entries`; returns the number of entries restored
+    ///
+    /// Like `restore_from_checkpoint`, the change is written as one batch and
+    /// mirrored to the changelog.
+    pub fn replace_raw(&self, entries: &BTreeMap<Vec<u8>, Vec<u8>>) -> Result<usize> {
+        let mut batch = self.write_batch();
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (key, _) = item?;
+            if !entries.contains_key(&*key) {
+                batch.delete_raw(&key);
+            }
+        }
+        for (key, value) in entries {
+            batch.put_raw(key, value);
+        }
+        batch.commit()?;
+        Ok(entries.len())
+    }
+
+    /// All entries in key order
+    pub fn entries<T>(&self) -> Result<Vec<(String, T)>>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        self.db.iterator(IteratorMode::Start).map(decode_entry).collect()
+    }
+
+    /// All keys in order, without decoding values
+    pub fn keys(&self) -> impl Iterator<Item = Result<String>> + '_ {
+        self.db
+            .iterator(IteratorMode::Start)
+            .map(|item| Ok(String::from_utf8_lossy(&item?.0).to_string()))
+    }
+
+    /// Entries whose key starts with `prefix`, in key order
+    pub fn scan_prefix<'a, T>(&'a self, prefix: &'a str) -> impl Iterator<Item = Result<(String, T)>> + 'a
+    where
+        T: for<'de> Deserialize<'de> + 'a,
+    {
+        self.db
+            .iterator(IteratorMode::From(prefix.as_bytes(), Direction::Forward))
+            .take_while(move |item| item.as_ref().map_or(true, |(key, _)| key.starts_with(prefix.as_bytes())))
+            .map(decode_entry)
+    }
+
+    /// Entries with keys from `start` (inclusive) to `end` (exclusive), in key order
+    pub fn range<'a, T>(&'a self, start: &'a str, end: &'a str) -> impl Iterator<Item = Result<(String, T)>> + 'a
+    where
+        T: for<'de> Deserialize<'de> + 'a,
+    {
+        self.db
+            .iterator(IteratorMode::From(start.as_bytes(), Direction::Forward))
+            .take_while(move |item| item.as_ref().map_or(true, |(key, _)| &key[..] < end.as_bytes()))
+            .map(decode_entry)
+    }
+
+    /// Up to `limit` entries whose key starts with `prefix`, in key order,
+    /// continuing after the page that returned `page_token`
+    ///
+    /// The token marks the last key returned, so keys written or deleted
+    /// between pages neither shift nor repeat entries.
+    pub fn scan_page<T>(&self, prefix: &str, page_token: Option<&str>, limit: usize) -> Result<StorePage<T>>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        if limit == 0 {
+            return Err(TicketMasterError::InvalidArgument("Page limit must be positive".to_string()));
+        }
+        let start = match page_token {
+            Some(token) => {
+                let mut last_key = decode_page_token(token)?;
+                if !last_key.starts_with(prefix.as_bytes()) {
+                    return Err(TicketMasterError::InvalidArgument(format!("Page token is not for prefix {}", prefix)));
+                }
+                // The smallest key after the last one returned
+                last_key.push(0);
+                last_key
+            }
+            None => prefix.as_bytes().to_vec(),
+        };
+
+        // One extra entry tells whether there is another page
+        let mut raw: Vec<RawEntry> = self.db
+            .iterator(IteratorMode::From(&start, Direction::Forward))
+            .take_while(|item| item.as_ref().map_or(true, |(key, _)| key.starts_with(prefix.as_bytes())))
+            .take(limit + 1)
+            .collect();
+        let more = raw.len() > limit;
+        raw.truncate(limit);
+
+        let entries = raw.into_iter().map(decode_entry).collect::<Result<Vec<(String, T)>>>()?;
+        let next_page_token = match entries.last() {
+            Some((key, _)) if more => Some(encode_page_token(key.as_bytes())),
+            _ => None,
+        };
+        Ok(StorePage { entries, next_page_token })
+    }
+
+    /// Like `scan_page`, but only entries `filter` accepts count towards the
+    /// page, in either key order
+    ///
+    /// Keys starting with `__` are bookkeeping, not values, and are skipped.
+    /// Rejected entries are read again on the next page, so a filter that
+    /// matches little makes every page a longer scan.
+    pub fn scan_page_where<T, F>(
+        &self,
+        prefix: &str,
+        page_token: Option<&str>,
+        limit: usize,
+        order: ScanOrder,
+        mut filter: F,
+    ) -> Result<StorePage<T>>
+    where
+        T: for<'de> Deserialize<'de>,
+        F: FnMut(&str, &T) -> bool,
+    {
+        if limit == 0 {
+            return Err(TicketMasterError::InvalidArgument("Page limit must be positive".to_string()));
+        }
+        let last_key = match page_token {
+            Some(token) => {
+                let last_key = decode_page_token(token)?;
+                if !last_key.starts_with(prefix.as_bytes()) {
+                    return Err(TicketMasterError::InvalidArgument(format!("Page token is not for prefix {}", prefix)));
+                }
+                Some(last_key)
+            }
+            None => None,
+        };
+        let (start, direction) = match (order, &last_key) {
+            (ScanOrder::Ascending, Some(last_key)) => ([last_key.as_slice(), &[0]].concat(), Direction::Forward),
+            (ScanOrder::Ascending, None) => (prefix.as_bytes().to_vec(), Direction::Forward),
+            // Reverse scans start at the last key at or before `start`; the last key returned is skipped below
+            (ScanOrder::Descending, Some(last_key)) => (last_key.clone(), Direction::Reverse),
+            // Keys are UTF-8, which never has a 0xff byte, so this is past every key with the prefix
+            (ScanOrder::Descending, None) => ([prefix.as_bytes(), &[0xff]].concat(), Direction::Reverse),
+        };
+
+        let mut entries = Vec::new();
+        let mut more = false;
+        for item in self.db.iterator(IteratorMode::From(&start, direction)) {
+            let (key, value) = item?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            if key.starts_with(b"__") || last_key.as_deref() == Some(&key[..]) {
+                continue;
+            }
+            let (key, value) = decode_entry::<T>(Ok((key, value)))?;
+            if !filter(&key, &value) {
+                continue;
+            }
+            // One extra match tells whether there is another page
+            if entries.len() == limit {
+                more = true;
+                break;
+            }
+            entries.push((key, value));
+        }
+
+        let next_page_token = match entries.last() {
+            Some((key, _)) if more => Some(encode_page_token(key.as_bytes())),
+            _ => None,
+        };
+        Ok(StorePage { entries, next_page_token })
+    }
+
+    // Span of one operation, named after the store when it has a metrics name
+    fn span(&self, operation: &'static str) -> Span {
+        let store = self.metrics.as_ref().and_then(|metrics| metrics.name.as_deref()).unwrap_or_default();
+        debug_span!("store", store = store, operation = operation)
+    }
+
+    /// Serialize read-modify-write sequences on `key` within this process;
+    /// keys share a fixed number of locks
+    pub fn lock_key(&self, key: &str) -> MutexGuard<'_, ()> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let stripe = hasher.finish() as usize % self.key_locks.len();
+        self.key_locks[stripe].lock().unwrap()
+    }
+
     pub fn contains_key(&self, key: &str) -> Result<bool> {
         Ok(self.db.get(key)?.is_some())
     }
 
+    pub fn path(&self) -> &Path {
+        self.db.path()
+    }
+
+    /// Whether RocksDB is currently stopping or delaying writes, e.g. while
+    /// compaction catches up
+    pub fn is_write_stalled(&self) -> Result<bool> {
+        let stopped = self.db.property_int_value("rocksdb.is-write-stopped")?.unwrap_or(0) > 0;
+        let delayed = self.db.property_int_value("rocksdb.actual-delayed-write-rate")?.unwrap_or(0) > 0;
+        Ok(stopped || delayed)
+    }
+
+    /// Estimated live data, live SST file and memtable sizes
+    pub fn size_stats(&self) -> Result<StoreSizeStats> {
+        Ok(StoreSizeStats {
+            estimated_live_data: self.db.property_int_value("rocksdb.estimate-live-data-size")?.unwrap_or(0),
+            live_sst_files: self.db.property_int_value("rocksdb.live-sst-files-size")?.unwrap_or(0),
+            memtables: self.db.property_int_value("rocksdb.cur-size-all-mem-tables")?.unwrap_or(0),
+        })
+    }
+
+    /// Query the store's sizes and record them under its name, if it has one
+    pub fn report_size(&self) -> Result<StoreSizeStats> {
+        let stats = self.size_stats()?;
+        if let Some(StoreMetrics { metrics, name: Some(name) }) = &self.metrics {
+            metrics.update_store_size(name, &stats);
+        }
+        Ok(stats)
+    }
+
     pub fn flush(&self) -> Result<()> {
         self.db.flush()?;
         Ok(())
     }
+
+    /// Compact keys in `[start, end]`, or the whole store when both are `None`
+    ///
+    /// Blocks until the compaction is done, dropping overwritten and deleted
+    /// values from disk.
+    pub fn compact_range(&self, start: Option<&str>, end: Option<&str>) {
+        self.db.compact_range(start, end);
+    }
+
+    pub fn compact(&self) {
+        self.compact_range(None, None);
+    }
+
+    /// Write buffered WAL entries to disk and fsync them
+    pub fn sync_wal(&self) -> Result<()> {
+        self.db.flush_wal(true)?;
+        Ok(())
+    }
+
+    /// Sync the WAL and flush memtables, leaving nothing to replay on the next open
+    pub fn persist(&self) -> Result<()> {
+        self.sync_wal()?;
+        self.flush()
+    }
+}
+
+/// Puts and deletes against one store, applied all or nothing
+///
+/// Nothing is written until `commit`; dropping the batch discards it. With a
+/// changelog, the records are logged once the batch has been written.
+pub struct StoreWriteBatch<'a> {
+    store: &'a RocksDBStore,
+    batch: rocksdb::WriteBatch,
+    changes: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl StoreWriteBatch<'_> {
+    pub fn put<T>(&mut self, key: &str, value: &T) -> Result<&mut Self>
+    where
+        T: Serialize,
+    {
+        let serialized = self.store.codec.encode(value)?;
+        self.batch.put(key, &serialized);
+        self.changes.push((key.to_string(), Some(serialized)));
+        Ok(self)
+    }
+
+    /// Like `put`, with the value marked soft-deleted at `deleted_at`
+    pub fn put_soft_deleted<T>(&mut self, key: &str, value: &T, deleted_at: DateTime<Utc>) -> Result<&mut Self>
+    where
+        T: Serialize,
+    {
+        let marked = mark_soft_deleted(&self.store.codec.encode(value)?, deleted_at);
+        self.put_raw(key.as_bytes(), &marked);
+        Ok(self)
+    }
+
+    pub fn delete(&mut self, key: &str) -> &mut Self {
+        self.batch.delete(key);
+        self.changes.push((key.to_string(), None));
+        self
+    }
+
+    fn put_raw(&mut self, key: &[u8], value: &[u8]) {
+        self.batch.put(key, value);
+        self.changes.push((String::from_utf8_lossy(key).to_string(), Some(value.to_vec())));
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) {
+        self.batch.delete(key);
+        self.changes.push((String::from_utf8_lossy(key).to_string(), None));
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn commit(self) -> Result<()> {
+        let started = Instant::now();
+        self.store.db.write_opt(self.batch, &self.store.write_options)?;
+        if let Some(metrics) = &self.store.metrics {
+            metrics.record_write(started);
+        }
+        if let Some(target) = &self.store.changelog {
+            for (key, value) in &self.changes {
+                match value {
+                    Some(value) => target.changelog.log_put(&target.topic, target.partition, key, value),
+                    None => target.changelog.log_delete(&target.topic, target.partition, key),
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Drop for RocksDBStore {
     fn drop(&mut self) {
-        let _ = self.db.flush();
+        let _ = self.persist();
+    }
+}
+
+// Key-value pair as yielded by a RocksDB iterator
+type RawEntry = std::result::Result<(Box<[u8]>, Box<[u8]>), rocksdb::Error>;
+
+fn decode_entry<T>(item: RawEntry) -> Result<(String, T)>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let (key, value) = item?;
+    Ok((String::from_utf8_lossy(&key).to_string(), StoreCodec::decode(&value)?))
+}
+
+// `value` as soft-deleted at `deleted_at`
+fn mark_soft_deleted(value: &[u8], deleted_at: DateTime<Utc>) -> Vec<u8> {
+    let mut marked = vec![SOFT_DELETE_TAG];
+    marked.extend_from_slice(&deleted_at.timestamp_millis().to_be_bytes());
+    marked.extend_from_slice(value);
+    marked
+}
+
+// Deletion time and original value of a soft-deleted value
+fn soft_deleted(bytes: &[u8]) -> Option<(i64, &[u8])> {
+    match bytes.split_first() {
+        Some((&SOFT_DELETE_TAG, rest)) if rest.len() >= 8 => {
+            let (deleted_at, value) = rest.split_at(8);
+            Some((i64::from_be_bytes(deleted_at.try_into().ok()?), value))
+        }
+        _ => None,
     }
-}
\ No newline at end of file
+}
+
+fn encode_page_token(key: &[u8]) -> String {
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_page_token(token: &str) -> Result<Vec<u8>> {
+    token
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .filter(|hex| hex.len() == 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| TicketMasterError::InvalidArgument(format!("Invalid page token: {}", token)))
+        })
+        .collect()
+}
@@ -0,0 +1,299 @@
+use crate::{AvroSerializer, CircuitBreaker, ProtobufMessage, Result, ServiceConfig, TicketMasterError, PROTO_SCHEMA};
+use schema_registry_converter::async_impl::proto_raw::{ProtoRawDecoder, ProtoRawEncoder};
+use schema_registry_converter::async_impl::schema_registry::SrSettings;
+use schema_registry_converter::error::SRCError;
+use schema_registry_converter::schema_registry_common::{SchemaType, SubjectNameStrategy, SuppliedSchema};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Serializer/deserializer for values of type `T` on a given topic
+#[async_trait::async_trait]
+pub trait Serde<T>: Send + Sync {
+    async fn serialize(&self, topic: &str, value: &T) -> Result<Vec<u8>>;
+    async fn deserialize(&self, topic: &str, data: &[u8]) -> Result<T>;
+}
+
+/// Values that can be sent with any of the supported serdes
+pub trait SerdeValue: Serialize + DeserializeOwned + ProtobufMessage + Send + Sync {}
+
+impl<T> SerdeValue for T where T: Serialize + DeserializeOwned + ProtobufMessage + Send + Sync {}
+
+/// Schema Registry subject for a topic's values (TopicNameStrategy)
+pub fn value_subject(topic: &str) -> String {
+    format!("{}-value", topic)
+}
+
+/// Wire formats a topic can be configured with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SerdeFormat {
+    #[default]
+    Json,
+    Avro,
+    Protobuf,
+}
+
+impl FromStr for SerdeFormat {
+    type Err = TicketMasterError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "avro" => Ok(Self::Avro),
+            "protobuf" | "proto" => Ok(Self::Protobuf),
+            other => Err(TicketMasterError::InvalidArgument(format!("Unknown serde format: {}", other))),
+        }
+    }
+}
+
+/// Plain JSON encoding
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSerde;
+
+#[async_trait::async_trait]
+impl<T> Serde<T> for JsonSerde
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+{
+    async fn serialize(&self, _topic: &str, value: &T) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    async fn deserialize(&self, _topic: &str, data: &[u8]) -> Result<T> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// An encoding or decoding failure; `SchemaRegistry` when the registry
+/// couldn't be reached and a retry may succeed
+pub fn codec_error(operation: &str, e: SRCError) -> TicketMasterError {
+    if e.retriable {
+        TicketMasterError::SchemaRegistry(format!("{} error: {}", operation, e))
+    } else {
+        TicketMasterError::InvalidArgument(format!("{} error: {}", operation, e))
+    }
+}
+
+// Only failures to reach the registry count against the breaker; any other
+// outcome means it answered
+async fn guarded<T>(breaker: Option<&CircuitBreaker>, call: impl Future<Output = Result<T>>) -> Result<T> {
+    let Some(breaker) = breaker else {
+        return call.await;
+    };
+    let permit = breaker.permit()?;
+    let result = call.await;
+    match &result {
+        Err(TicketMasterError::SchemaRegistry(_)) => permit.failed(),
+        _ => permit.succeeded(),
+    }
+    result
+}
+
+/// Avro encoding using the Schema Registry wire format
+#[derive(Clone)]
+pub struct AvroSerde {
+    serializer: Arc<AvroSerializer>,
+    breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl AvroSerde {
+    pub fn new(serializer: Arc<AvroSerializer>) -> Self {
+        Self { serializer, breaker: None }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Serde<T> for AvroSerde
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+{
+    async fn serialize(&self, topic: &str, value: &T) -> Result<Vec<u8>> {
+        guarded(self.breaker.as_deref(), self.serializer.serialize(&value_subject(topic), value)).await
+    }
+
+    async fn deserialize(&self, _topic: &str, data: &[u8]) -> Result<T> {
+        guarded(self.breaker.as_deref(), self.serializer.deserialize(data)).await
+    }
+}
+
+/// Protobuf encoding using the Schema Registry wire format
+///
+/// The schema in `PROTO_SCHEMA` is registered under each topic's value subject
+/// on first use.
+#[derive(Clone)]
+pub struct ProtobufSerde {
+    encoder: Arc<ProtoRawEncoder<'static>>,
+    decoder: Arc<ProtoRawDecoder<'static>>,
+    breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl ProtobufSerde {
+    pub fn new(schema_registry_url: &str) -> Self {
+        Self::with_settings(SrSettings::new(schema_registry_url.to_string()))
+    }
+
+    pub fn with_settings(sr_settings: SrSettings) -> Self {
+        Self {
+            encoder: Arc::new(ProtoRawEncoder::new(sr_settings.clone())),
+            decoder: Arc::new(ProtoRawDecoder::new(sr_settings)),
+            breaker: None,
+        }
+    }
+}
+
+fn supplied_proto_schema() -> SuppliedSchema {
+    SuppliedSchema {
+        name: None,
+        schema_type: SchemaType::Protobuf,
+        schema: PROTO_SCHEMA.to_string(),
+        references: vec![],
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Serde<T> for ProtobufSerde
+where
+    T: ProtobufMessage + Send + Sync,
+{
+    async fn serialize(&self, topic: &str, value: &T) -> Result<Vec<u8>> {
+        let strategy = SubjectNameStrategy::TopicNameStrategyWithSchema(topic.to_string(), false, supplied_proto_schema());
+        let bytes = value.encode_protobuf();
+        guarded(self.breaker.as_deref(), async {
+            self.encoder
+                .encode(&bytes, T::FULL_NAME, strategy)
+                .await
+                .map_err(|e| codec_error("Protobuf encoding", e))
+        })
+        .await
+    }
+
+    async fn deserialize(&self, topic: &str, data: &[u8]) -> Result<T> {
+        let decoded = guarded(self.breaker.as_deref(), async {
+            self.decoder.decode(Some(data)).await.map_err(|e| codec_error("Protobuf decoding", e))
+        })
+        .await?
+        .ok_or_else(|| TicketMasterError::InvalidArgument("Empty message payload".to_string()))?;
+
+        if decoded.full_name.as_str() != T::FULL_NAME {
+            return Err(TicketMasterError::InvalidArgument(format!(
+                "Expected {} on topic {}, got {}",
+                T::FULL_NAME,
+                topic,
+                decoded.full_name
+            )));
+        }
+        T::decode_protobuf(&decoded.bytes)
+    }
+}
+
+/// Per-topic serde selection shared by producers and consumers
+#[derive(Clone, Default)]
+pub struct TopicSerdes {
+    default_format: SerdeFormat,
+    topic_formats: HashMap<String, SerdeFormat>,
+    json: JsonSerde,
+    avro: Option<AvroSerde>,
+    protobuf: Option<ProtobufSerde>,
+}
+
+impl TopicSerdes {
+    /// JSON for every topic
+    pub fn json() -> Self {
+        Self::default()
+    }
+
+    /// Build from the `serde.*` settings in the service config, connecting to
+    /// the Schema Registry only if some topic uses Avro or Protobuf
+    ///
+    /// Avro schemas are registered up front, so an incompatible schema fails here.
+    pub async fn from_config(config: &ServiceConfig) -> Result<Self> {
+        let mut serdes = Self {
+            default_format: config.serdes.default_format,
+            topic_formats: config.serdes.topic_formats.clone(),
+            ..Self::default()
+        };
+
+        let registry = || {
+            let url = config.kafka.schema_registry_url.as_deref().ok_or_else(|| {
+                TicketMasterError::InvalidArgument("Avro and Protobuf serdes require schema.registry.url".to_string())
+            })?;
+            let mut settings = SrSettings::new_builder(url.to_string());
+            if let Some((user, password)) = config.kafka.schema_registry_credentials() {
+                settings.set_basic_authorization(user, password);
+            }
+            settings
+                .build()
+                .map_err(|e| TicketMasterError::SchemaRegistry(format!("Invalid Schema Registry settings: {}", e)))
+        };
+        if serdes.uses(SerdeFormat::Avro) {
+            let serializer = AvroSerializer::with_settings(registry()?).await?;
+            serializer.register_schemas().await?;
+            serdes.avro = Some(AvroSerde::new(Arc::new(serializer)));
+        }
+        if serdes.uses(SerdeFormat::Protobuf) {
+            serdes.protobuf = Some(ProtobufSerde::with_settings(registry()?));
+        }
+
+        Ok(serdes)
+    }
+
+    /// Fail Schema Registry lookups fast while `breaker` is open
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        if let Some(avro) = &mut self.avro {
+            avro.breaker = Some(breaker.clone());
+        }
+        if let Some(protobuf) = &mut self.protobuf {
+            protobuf.breaker = Some(breaker);
+        }
+        self
+    }
+
+    pub fn with_topic_format(mut self, topic: &str, format: SerdeFormat) -> Self {
+        self.topic_formats.insert(topic.to_string(), format);
+        self
+    }
+
+    pub fn format_for(&self, topic: &str) -> SerdeFormat {
+        self.topic_formats.get(topic).copied().unwrap_or(self.default_format)
+    }
+
+    /// Serde to use for values of `topic`
+    pub fn serde_for<T: SerdeValue>(&self, topic: &str) -> Result<&dyn Serde<T>> {
+        match self.format_for(topic) {
+            SerdeFormat::Json => Ok(&self.json),
+            SerdeFormat::Avro => self
+                .avro
+                .as_ref()
+                .map(|avro| avro as &dyn Serde<T>)
+                .ok_or_else(|| TicketMasterError::InvalidArgument(format!("No Avro serde configured for topic {}", topic))),
+            SerdeFormat::Protobuf => self
+                .protobuf
+                .as_ref()
+                .map(|protobuf| protobuf as &dyn Serde<T>)
+                .ok_or_else(|| TicketMasterError::InvalidArgument(format!("No Protobuf serde configured for topic {}", topic))),
+        }
+    }
+
+    /// Whether any topic is encoded with a serde backed by the Schema Registry
+    pub fn uses_schema_registry(&self) -> bool {
+        self.uses(SerdeFormat::Avro) || self.uses(SerdeFormat::Protobuf)
+    }
+
+    fn uses(&self, format: SerdeFormat) -> bool {
+        self.default_format == format || self.topic_formats.values().any(|f| *f == format)
+    }
+}
+
+impl fmt::Debug for TopicSerdes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TopicSerdes")
+            .field("default_format", &self.default_format)
+            .field("topic_formats", &self.topic_formats)
+            .finish()
+    }
+}
@@ -0,0 +1,32 @@
+use crate::Metrics;
+use rdkafka::statistics::Statistics;
+use rdkafka::ClientContext;
+use std::sync::{Arc, OnceLock};
+
+/// The librdkafka property setting how often clients emit statistics
+pub const STATISTICS_INTERVAL_PROPERTY: &str = "statistics.interval.ms";
+
+/// Client context recording the statistics librdkafka emits every
+/// `statistics.interval.ms` in the metrics attached to it
+///
+/// Statistics emitted before metrics are attached are dropped. Clones share
+/// the metrics, so metrics can be attached through a clone kept aside.
+#[derive(Default, Clone)]
+pub struct StatisticsContext {
+    metrics: Arc<OnceLock<Metrics>>,
+}
+
+impl StatisticsContext {
+    /// Record statistics in `metrics` from now on; only the first metrics attached are used
+    pub fn attach(&self, metrics: Metrics) {
+        let _ = self.metrics.set(metrics);
+    }
+}
+
+impl ClientContext for StatisticsContext {
+    fn stats(&self, statistics: Statistics) {
+        if let Some(metrics) = self.metrics.get() {
+            metrics.record_kafka_statistics(&statistics);
+        }
+    }
+}
@@ -1,7 +1,18 @@
-use crate::{Result, RocksDBStore};
+use crate::{
+    changelog_topic, partition_store_name, CacheStore, CachedStore, Changelog, ChangelogTarget, Metrics, Result, RocksDBStore,
+    StoreSizeStats, TicketMasterError, ALL_STORES,
+};
 use dashmap::DashMap;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::Any;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::path::Path;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::error;
 
 // Simple in-memory state store implementation
 // In a production system, you'd want to use RocksDB or similar
@@ -58,10 +69,81 @@ pub trait StreamProcessor<K, V, R> {
     fn process(&self, key: K, value: V) -> Result<Option<(K, R)>>;
 }
 
+/// Types a store's keys can have
+pub trait StoreKey: std::hash::Hash + Eq + Clone + ToString + Send + Sync + 'static {}
+
+impl<T> StoreKey for T where T: std::hash::Hash + Eq + Clone + ToString + Send + Sync + 'static {}
+
+/// Types a store's values can have
+pub trait StoreValue: Clone + Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+impl<T> StoreValue for T where T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+/// Name and key/value types of a state store, used both to register the
+/// store and to look it up, so the two cannot disagree on its types
+pub struct StoreHandle<K, V> {
+    name: &'static str,
+    partition: Option<i32>,
+    _types: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> StoreHandle<K, V> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            partition: None,
+            _types: PhantomData,
+        }
+    }
+
+    /// The instance of this store owning `partition`
+    pub const fn partition(&self, partition: i32) -> Self {
+        Self {
+            name: self.name,
+            partition: Some(partition),
+            _types: PhantomData,
+        }
+    }
+
+    /// Name the store is registered under
+    pub fn name(&self) -> String {
+        match self.partition {
+            Some(partition) => partition_store_name(self.name, partition),
+            None => self.name.to_string(),
+        }
+    }
+
+    /// Name shared by every partition of the store, which names its changelog
+    pub fn base_name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<K, V> Clone for StoreHandle<K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for StoreHandle<K, V> {}
+
 // Enhanced state store that can use either in-memory or RocksDB
 pub enum StateStoreBackend<K, V> {
     InMemory(StateStore<K, V>),
     RocksDB(Arc<RocksDBStore>),
+    Cache(CacheStore<K, V>),
+    Cached(CachedStore<K, V>),
+}
+
+impl<K, V> Clone for StateStoreBackend<K, V> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::InMemory(store) => Self::InMemory(store.clone()),
+            Self::RocksDB(store) => Self::RocksDB(store.clone()),
+            Self::Cache(store) => Self::Cache(store.clone()),
+            Self::Cached(store) => Self::Cached(store.clone()),
+        }
+    }
 }
 
 impl<K, V> StateStoreBackend<K, V>
@@ -82,6 +164,8 @@ where
         match self {
             Self::InMemory(store) => Ok(store.get(key)),
             Self::RocksDB(store) => store.get(&key.to_string()),
+            Self::Cache(store) => Ok(store.get(key)),
+            Self::Cached(store) => store.get(key),
         }
     }
 
@@ -92,6 +176,11 @@ where
                 Ok(())
             }
             Self::RocksDB(store) => store.put(&key.to_string(), &value),
+            Self::Cache(store) => {
+                store.put(key, value);
+                Ok(())
+            }
+            Self::Cached(store) => store.put(key, value),
         }
     }
 
@@ -103,6 +192,8 @@ where
                 store.delete(&key.to_string())?;
                 Ok(existing)
             }
+            Self::Cache(store) => Ok(store.remove(key)),
+            Self::Cached(store) => store.remove(key),
         }
     }
 
@@ -110,60 +201,384 @@ where
         match self {
             Self::InMemory(store) => Ok(store.contains_key(key)),
             Self::RocksDB(store) => store.contains_key(&key.to_string()),
+            Self::Cache(store) => Ok(store.get(key).is_some()),
+            Self::Cached(store) => store.contains_key(key),
+        }
+    }
+
+    /// The underlying RocksDB store, for scans and batches
+    pub fn rocksdb(&self) -> Option<&Arc<RocksDBStore>> {
+        match self {
+            Self::RocksDB(store) => Some(store),
+            Self::Cached(store) => Some(store.store()),
+            _ => None,
         }
     }
 }
 
+struct RegisteredStore {
+    // A `StateStoreBackend<K, V>` of the types the store was registered with
+    backend: Box<dyn Any + Send + Sync>,
+    types: &'static str,
+    rocksdb: Option<Arc<RocksDBStore>>,
+    // Sets the capacity of an in-memory cache
+    resize: Option<Box<dyn Fn(usize) + Send + Sync>>,
+}
+
 // Simple stream processing context
 pub struct ProcessingContext {
-    pub stores: DashMap<String, Box<dyn std::any::Any + Send + Sync>>,
+    stores: DashMap<String, RegisteredStore>,
     pub state_dir: String,
+    changelog: Option<Changelog>,
+    metrics: Option<Metrics>,
+    per_store_metrics: bool,
+    sync_writes: bool,
+    soft_delete_retention: Option<Duration>,
+    // Set by `set_cache_capacity`; 0 until then
+    cache_capacity: AtomicUsize,
 }
 
 impl ProcessingContext {
     pub fn new() -> Self {
-        Self {
-            stores: DashMap::new(),
-            state_dir: "/tmp/kafka-streams".to_string(),
-        }
+        Self::with_state_dir("/tmp/kafka-streams".to_string())
     }
 
     pub fn with_state_dir(state_dir: String) -> Self {
         Self {
             stores: DashMap::new(),
             state_dir,
+            changelog: None,
+            metrics: None,
+            per_store_metrics: false,
+            sync_writes: false,
+            soft_delete_retention: None,
+            cache_capacity: AtomicUsize::new(0),
         }
     }
 
-    pub fn add_store<K, V>(&self, name: String, store: StateStore<K, V>)
-    where
-        K: 'static + Send + Sync,
-        V: 'static + Send + Sync,
-    {
-        self.stores.insert(name, Box::new(store));
+    /// Register the stores a service needs up front; `build` fails if any of
+    /// them cannot be opened
+    pub fn builder(state_dir: String) -> ProcessingContextBuilder {
+        ProcessingContextBuilder {
+            context: Self::with_state_dir(state_dir),
+            registrations: Vec::new(),
+        }
+    }
+
+    /// Mirror RocksDB stores added from now on to `<store>-changelog` topics,
+    /// restoring each store from its changelog when it is opened
+    pub fn with_changelog(mut self, changelog: Changelog) -> Self {
+        self.changelog = Some(changelog);
+        self
+    }
+
+    pub fn changelog(&self) -> Option<&Changelog> {
+        self.changelog.as_ref()
+    }
+
+    /// Record reads, writes and sizes of RocksDB stores opened from now on;
+    /// with `per_store`, also labelled by store name
+    pub fn with_metrics(mut self, metrics: Metrics, per_store: bool) -> Self {
+        self.metrics = Some(metrics);
+        self.per_store_metrics = per_store;
+        self
     }
 
-    pub fn add_rocksdb_store(&self, name: String, store_path: &str) -> Result<()> {
+    /// Sync the WAL on every write to RocksDB stores opened from now on
+    pub fn with_sync_writes(mut self, sync: bool) -> Self {
+        self.sync_writes = sync;
+        self
+    }
+
+    /// Purge values soft-deleted more than `retention` ago from RocksDB
+    /// stores opened from now on, as they are compacted
+    pub fn with_soft_delete_retention(mut self, retention: Duration) -> Self {
+        self.soft_delete_retention = Some(retention);
+        self
+    }
+
+    /// Resize the in-memory caches of every store, and use `capacity` in
+    /// place of the one given for caches added from now on
+    pub fn set_cache_capacity(&self, capacity: usize) {
+        self.cache_capacity.store(capacity, Ordering::Relaxed);
+        for store in self.stores.iter() {
+            if let Some(resize) = &store.resize {
+                resize(capacity);
+            }
+        }
+    }
+
+    fn cache_capacity(&self, capacity: usize) -> usize {
+        match self.cache_capacity.load(Ordering::Relaxed) {
+            0 => capacity,
+            set => set,
+        }
+    }
+
+    pub fn add_in_memory_store<K: StoreKey, V: StoreValue>(&self, handle: &StoreHandle<K, V>) {
+        self.register(handle, StateStoreBackend::new_in_memory(), None);
+    }
+
+    /// Add an in-memory store bounded to `capacity` entries that each live for `ttl`
+    pub fn add_cache_store<K: StoreKey, V: StoreValue>(&self, handle: &StoreHandle<K, V>, capacity: usize, ttl: Duration) {
+        self.register(handle, StateStoreBackend::Cache(CacheStore::new(self.cache_capacity(capacity), ttl)), None);
+    }
+
+    /// Open a RocksDB store at `<state dir>/<store_path>`
+    ///
+    /// With a changelog, the store is restored from `<store>-changelog`; a
+    /// partitioned handle uses the partition of that topic it owns.
+    pub fn open_rocksdb_store<K: StoreKey, V: StoreValue>(&self, handle: &StoreHandle<K, V>, store_path: &str) -> Result<()> {
+        let store = self.open_rocksdb(handle, store_path)?;
+        self.register(handle, StateStoreBackend::RocksDB(store.clone()), Some(store));
+        Ok(())
+    }
+
+    /// Like `open_rocksdb_store`, keeping up to `capacity` recently used
+    /// values in memory in front of RocksDB
+    pub fn open_cached_rocksdb_store<K: StoreKey, V: StoreValue>(&self, handle: &StoreHandle<K, V>, store_path: &str, capacity: usize) -> Result<()> {
+        let store = self.open_rocksdb(handle, store_path)?;
+        self.register(handle, StateStoreBackend::Cached(CachedStore::new(store.clone(), self.cache_capacity(capacity))), Some(store));
+        Ok(())
+    }
+
+    fn open_rocksdb<K, V>(&self, handle: &StoreHandle<K, V>, store_path: &str) -> Result<Arc<RocksDBStore>> {
         let full_path = format!("{}/{}", self.state_dir, store_path);
         std::fs::create_dir_all(&full_path)?;
-        let store = RocksDBStore::new(full_path)?;
-        self.stores.insert(name, Box::new(Arc::new(store)));
+        let topic = changelog_topic(handle.base_name());
+        let partition = handle.partition.unwrap_or(0);
+        let store = match &self.changelog {
+            Some(changelog) => {
+                let target = ChangelogTarget {
+                    changelog: changelog.clone(),
+                    topic: topic.clone(),
+                    partition: Some(partition),
+                };
+                let store = RocksDBStore::with_changelog(full_path, target)?;
+                changelog.restore(&store, &topic, partition)?;
+                store
+            }
+            None => RocksDBStore::new(full_path)?,
+        };
+        let mut store = store.with_sync_writes(self.sync_writes);
+        if let Some(retention) = self.soft_delete_retention {
+            store = store.with_soft_delete_retention(retention);
+        }
+        let store = match &self.metrics {
+            Some(metrics) => {
+                let name = handle.name();
+                store.with_metrics(metrics.clone(), self.per_store_metrics.then_some(name.as_str()))
+            }
+            None => store,
+        };
+        Ok(Arc::new(store))
+    }
+
+    fn register<K: StoreKey, V: StoreValue>(&self, handle: &StoreHandle<K, V>, backend: StateStoreBackend<K, V>, rocksdb: Option<Arc<RocksDBStore>>) {
+        let resize: Option<Box<dyn Fn(usize) + Send + Sync>> = match &backend {
+            StateStoreBackend::Cache(cache) => {
+                let cache = cache.clone();
+                Some(Box::new(move |capacity| cache.set_capacity(capacity)))
+            }
+            StateStoreBackend::Cached(cached) => {
+                let cached = cached.clone();
+                Some(Box::new(move |capacity| cached.set_capacity(capacity)))
+            }
+            _ => None,
+        };
+        self.stores.insert(handle.name(), RegisteredStore {
+            backend: Box::new(backend),
+            types: std::any::type_name::<(K, V)>(),
+            rocksdb,
+            resize,
+        });
+    }
+
+    /// Remove a store from the context, closing it once no other handles remain
+    pub fn remove_store<K, V>(&self, handle: &StoreHandle<K, V>) -> bool {
+        self.stores.remove(&handle.name()).is_some()
+    }
+
+    pub fn has_store<K, V>(&self, handle: &StoreHandle<K, V>) -> bool {
+        self.stores.contains_key(&handle.name())
+    }
+
+    /// Partitions with a store of `handle`'s base name registered, in order
+    pub fn store_partitions<K, V>(&self, handle: &StoreHandle<K, V>) -> Vec<i32> {
+        let prefix = format!("{}-", handle.base_name());
+        let mut partitions: Vec<i32> = self
+            .stores
+            .iter()
+            .filter_map(|entry| entry.key().strip_prefix(&prefix)?.parse().ok())
+            .collect();
+        partitions.sort_unstable();
+        partitions
+    }
+
+    /// The store registered for `handle`; an error if there is none or it was
+    /// registered with other key or value types
+    pub fn store<K: StoreKey, V: StoreValue>(&self, handle: &StoreHandle<K, V>) -> Result<StateStoreBackend<K, V>> {
+        let name = handle.name();
+        let entry = self.stores
+            .get(&name)
+            .ok_or_else(|| TicketMasterError::StoreNotFound(name.clone()))?;
+        entry.backend
+            .downcast_ref::<StateStoreBackend<K, V>>()
+            .cloned()
+            .ok_or_else(|| TicketMasterError::InvalidArgument(format!(
+                "State store {} holds {}, not {}",
+                name,
+                entry.types,
+                std::any::type_name::<(K, V)>()
+            )))
+    }
+
+    /// The RocksDB store registered for `handle`, for scans, batches and checkpoints
+    pub fn rocksdb_store<K: StoreKey, V: StoreValue>(&self, handle: &StoreHandle<K, V>) -> Result<Arc<RocksDBStore>> {
+        self.store(handle)?
+            .rocksdb()
+            .cloned()
+            .ok_or_else(|| TicketMasterError::InvalidArgument(format!("State store {} is not a RocksDB store", handle.name())))
+    }
+
+    /// Whether any RocksDB store in the context is stalling writes
+    pub fn write_stalled(&self) -> Result<bool> {
+        for entry in self.stores.iter() {
+            if let Some(store) = &entry.value().rocksdb {
+                if store.is_write_stalled()? {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Compact every RocksDB store; blocks until done
+    pub fn compact_stores(&self) {
+        for entry in self.stores.iter() {
+            if let Some(store) = &entry.value().rocksdb {
+                store.compact();
+            }
+        }
+    }
+
+    /// Sync the WAL and flush memtables of every RocksDB store, e.g. on shutdown
+    pub fn persist_stores(&self) -> Result<()> {
+        for entry in self.stores.iter() {
+            if let Some(store) = &entry.value().rocksdb {
+                store.persist()?;
+            }
+        }
         Ok(())
     }
 
-    pub fn get_store<K, V>(&self, name: &str) -> Option<StateStore<K, V>>
-    where
-        K: 'static + Send + Sync + Clone,
-        V: 'static + Send + Sync + Clone,
-    {
-        self.stores.get(name).and_then(|entry| {
-            entry.value().downcast_ref::<StateStore<K, V>>().cloned()
-        })
+    /// Query the size of every RocksDB store, recording each store if stores
+    /// are labelled, and the total under `ALL_STORES` otherwise; returns the total
+    pub fn report_store_sizes(&self) -> Result<StoreSizeStats> {
+        let mut total = StoreSizeStats::default();
+        for entry in self.stores.iter() {
+            if let Some(store) = &entry.value().rocksdb {
+                total += store.report_size()?;
+            }
+        }
+        if let Some(metrics) = self.metrics.as_ref().filter(|_| !self.per_store_metrics) {
+            metrics.update_store_size(ALL_STORES, &total);
+        }
+        Ok(total)
+    }
+}
+
+/// Report the sizes of the context's stores every `every`
+pub fn spawn_store_size_reporter(context: Arc<ProcessingContext>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut timer = tokio::time::interval(every);
+        loop {
+            timer.tick().await;
+            if let Err(e) = context.report_store_sizes() {
+                error!("Failed to report state store sizes: {}", e);
+            }
+        }
+    })
+}
+
+/// Compact the context's stores every `every`, so overwritten seat updates
+/// don't pile up on disk during a long on-sale
+pub fn spawn_store_compaction(context: Arc<ProcessingContext>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut timer = tokio::time::interval(every);
+        // The first tick is immediate; nothing needs compacting at startup
+        timer.tick().await;
+        loop {
+            timer.tick().await;
+            let context = context.clone();
+            if let Err(e) = tokio::task::spawn_blocking(move || context.compact_stores()).await {
+                error!("State store compaction failed: {}", e);
+            }
+        }
+    })
+}
+
+type Registration = Box<dyn FnOnce(&ProcessingContext) -> Result<()>>;
+
+/// Registers stores on a `ProcessingContext` at startup
+pub struct ProcessingContextBuilder {
+    context: ProcessingContext,
+    registrations: Vec<Registration>,
+}
+
+impl ProcessingContextBuilder {
+    pub fn changelog(mut self, changelog: Changelog) -> Self {
+        self.context = self.context.with_changelog(changelog);
+        self
     }
 
-    pub fn get_rocksdb_store(&self, name: &str) -> Option<Arc<RocksDBStore>> {
-        self.stores.get(name).and_then(|entry| {
-            entry.value().downcast_ref::<Arc<RocksDBStore>>().cloned()
-        })
+    pub fn metrics(mut self, metrics: Metrics, per_store: bool) -> Self {
+        self.context = self.context.with_metrics(metrics, per_store);
+        self
     }
-}
\ No newline at end of file
+
+    pub fn sync_writes(mut self, sync: bool) -> Self {
+        self.context = self.context.with_sync_writes(sync);
+        self
+    }
+
+    pub fn soft_delete_retention(mut self, retention: Duration) -> Self {
+        self.context = self.context.with_soft_delete_retention(retention);
+        self
+    }
+
+    pub fn rocksdb_store<K: StoreKey, V: StoreValue>(mut self, handle: StoreHandle<K, V>, store_path: &str) -> Self {
+        let store_path = store_path.to_string();
+        self.registrations.push(Box::new(move |context| context.open_rocksdb_store(&handle, &store_path)));
+        self
+    }
+
+    pub fn cached_rocksdb_store<K: StoreKey, V: StoreValue>(mut self, handle: StoreHandle<K, V>, store_path: &str, capacity: usize) -> Self {
+        let store_path = store_path.to_string();
+        self.registrations.push(Box::new(move |context| context.open_cached_rocksdb_store(&handle, &store_path, capacity)));
+        self
+    }
+
+    pub fn in_memory_store<K: StoreKey, V: StoreValue>(mut self, handle: StoreHandle<K, V>) -> Self {
+        self.registrations.push(Box::new(move |context| {
+            context.add_in_memory_store(&handle);
+            Ok(())
+        }));
+        self
+    }
+
+    pub fn cache_store<K: StoreKey, V: StoreValue>(mut self, handle: StoreHandle<K, V>, capacity: usize, ttl: Duration) -> Self {
+        self.registrations.push(Box::new(move |context| {
+            context.add_cache_store(&handle, capacity, ttl);
+            Ok(())
+        }));
+        self
+    }
+
+    pub fn build(self) -> Result<ProcessingContext> {
+        for register in self.registrations {
+            register(&self.context)?;
+        }
+        Ok(self.context)
+    }
+}
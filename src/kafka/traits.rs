@@ -0,0 +1,200 @@
+use crate::{
+    KafkaConsumer, KafkaMessage, KafkaProducer, MockConsumer, MockProducer, PartitionLag, Result, SerdeValue,
+    TopicPartition, TopicSerdes,
+};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::time::Duration;
+
+/// Sends messages to topics; implemented by `KafkaProducer` and `MockProducer`
+/// so services can be run against either
+#[async_trait::async_trait]
+pub trait MessageProducer: Send + Sync + 'static {
+    /// Serdes used to encode values per topic
+    fn serdes(&self) -> &TopicSerdes;
+
+    /// Send an already-encoded payload with headers, optionally to a fixed
+    /// partition; `None` produces a tombstone
+    async fn send_bytes_with_headers(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        key: &str,
+        payload: Option<&[u8]>,
+        headers: &[(&str, &[u8])],
+    ) -> Result<()>;
+
+    async fn flush(&self, timeout: Duration) -> Result<()>;
+
+    async fn send<T>(&self, topic: &str, key: &str, value: &T) -> Result<()>
+    where
+        T: SerdeValue,
+    {
+        self.send_with_headers(topic, key, value, &[]).await
+    }
+
+    /// `send` with message headers attached
+    async fn send_with_headers<T>(&self, topic: &str, key: &str, value: &T, headers: &[(&str, &[u8])]) -> Result<()>
+    where
+        T: SerdeValue,
+    {
+        let payload = self.serdes().serde_for::<T>(topic)?.serialize(topic, value).await?;
+        self.send_bytes_with_headers(topic, None, key, Some(&payload), headers).await
+    }
+
+    async fn send_bytes(&self, topic: &str, partition: Option<i32>, key: &str, payload: Option<&[u8]>) -> Result<()> {
+        self.send_bytes_with_headers(topic, partition, key, payload, &[]).await
+    }
+
+    /// Delete `key` from a compacted state topic
+    async fn send_tombstone(&self, topic: &str, key: &str) -> Result<()> {
+        self.send_bytes_with_headers(topic, None, key, None, &[]).await
+    }
+}
+
+/// Receives messages and commits offsets; implemented by `KafkaConsumer` and
+/// `MockConsumer` so services can be run against either
+#[async_trait::async_trait]
+pub trait MessageConsumer: Send + Sync + 'static {
+    fn subscribe(&self, topics: &[&str]) -> Result<()>;
+
+    /// Leave the consumer group, e.g. on shutdown once offsets are committed
+    fn unsubscribe(&self) -> Result<()>;
+
+    fn assignment(&self) -> Result<Vec<TopicPartition>>;
+
+    fn partition_count(&self, topic: &str) -> Result<i32>;
+
+    async fn recv_message(&self, timeout: Duration) -> Result<Option<KafkaMessage>>;
+
+    /// Received messages as a stream; it never ends on its own
+    fn stream(&self) -> BoxStream<'_, Result<KafkaMessage>>;
+
+    fn pause(&self) -> Result<()>;
+
+    fn resume(&self) -> Result<()>;
+
+    fn seek_to_offset(&self, partition: &TopicPartition, offset: i64) -> Result<()>;
+
+    fn seek_to_timestamp(&self, partitions: &[TopicPartition], timestamp_ms: i64) -> Result<()>;
+
+    fn seek_to_beginning(&self, partitions: &[TopicPartition]) -> Result<()>;
+
+    fn commit_offsets(&self, offsets: &[(TopicPartition, i64)]) -> Result<()>;
+
+    fn commit_offsets_sync(&self, offsets: &[(TopicPartition, i64)]) -> Result<()>;
+
+    /// How far the consumer's group is behind on every partition of `topics`
+    fn group_lag(&self, topics: &[&str]) -> Result<Vec<PartitionLag>>;
+}
+
+#[async_trait::async_trait]
+impl MessageProducer for KafkaProducer {
+    fn serdes(&self) -> &TopicSerdes {
+        KafkaProducer::serdes(self)
+    }
+
+    async fn send_bytes_with_headers(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        key: &str,
+        payload: Option<&[u8]>,
+        headers: &[(&str, &[u8])],
+    ) -> Result<()> {
+        KafkaProducer::send_bytes_with_headers(self, topic, partition, key, payload, headers).await
+    }
+
+    async fn flush(&self, timeout: Duration) -> Result<()> {
+        KafkaProducer::flush(self, timeout).await
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageProducer for MockProducer {
+    fn serdes(&self) -> &TopicSerdes {
+        MockProducer::serdes(self)
+    }
+
+    async fn send_bytes_with_headers(
+        &self,
+        topic: &str,
+        partition: Option<i32>,
+        key: &str,
+        payload: Option<&[u8]>,
+        headers: &[(&str, &[u8])],
+    ) -> Result<()> {
+        MockProducer::send_bytes_with_headers(self, topic, partition, key, payload, headers).await
+    }
+
+    async fn flush(&self, timeout: Duration) -> Result<()> {
+        MockProducer::flush(self, timeout).await
+    }
+}
+
+// Both consumers expose the same inherent methods; forward the trait to them
+macro_rules! impl_message_consumer {
+    ($consumer:ty) => {
+        #[async_trait::async_trait]
+        impl MessageConsumer for $consumer {
+            fn subscribe(&self, topics: &[&str]) -> Result<()> {
+                <$consumer>::subscribe(self, topics)
+            }
+
+            fn unsubscribe(&self) -> Result<()> {
+                <$consumer>::unsubscribe(self)
+            }
+
+            fn assignment(&self) -> Result<Vec<TopicPartition>> {
+                <$consumer>::assignment(self)
+            }
+
+            fn partition_count(&self, topic: &str) -> Result<i32> {
+                <$consumer>::partition_count(self, topic)
+            }
+
+            async fn recv_message(&self, timeout: Duration) -> Result<Option<KafkaMessage>> {
+                <$consumer>::recv_message(self, timeout).await
+            }
+
+            fn stream(&self) -> BoxStream<'_, Result<KafkaMessage>> {
+                <$consumer>::stream(self).boxed()
+            }
+
+            fn pause(&self) -> Result<()> {
+                <$consumer>::pause(self)
+            }
+
+            fn resume(&self) -> Result<()> {
+                <$consumer>::resume(self)
+            }
+
+            fn seek_to_offset(&self, partition: &TopicPartition, offset: i64) -> Result<()> {
+                <$consumer>::seek_to_offset(self, partition, offset)
+            }
+
+            fn seek_to_timestamp(&self, partitions: &[TopicPartition], timestamp_ms: i64) -> Result<()> {
+                <$consumer>::seek_to_timestamp(self, partitions, timestamp_ms)
+            }
+
+            fn seek_to_beginning(&self, partitions: &[TopicPartition]) -> Result<()> {
+                <$consumer>::seek_to_beginning(self, partitions)
+            }
+
+            fn commit_offsets(&self, offsets: &[(TopicPartition, i64)]) -> Result<()> {
+                <$consumer>::commit_offsets(self, offsets)
+            }
+
+            fn commit_offsets_sync(&self, offsets: &[(TopicPartition, i64)]) -> Result<()> {
+                <$consumer>::commit_offsets_sync(self, offsets)
+            }
+
+            fn group_lag(&self, topics: &[&str]) -> Result<Vec<PartitionLag>> {
+                <$consumer>::group_lag(self, topics)
+            }
+        }
+    };
+}
+
+impl_message_consumer!(KafkaConsumer);
+impl_message_consumer!(MockConsumer);
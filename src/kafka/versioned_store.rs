@@ -0,0 +1,128 @@
+use crate::{Result, RocksDBStore, StoreWriteBatch, TicketMasterError};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// A value read from a `VersionedStore` with the version it was written at
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub value: T,
+    pub version: u64,
+}
+
+/// Wraps a `RocksDBStore` so every write bumps a per-key version, and
+/// `compare_and_put` only writes if the key is still at the version read
+///
+/// Values are stored as before, readable with a plain `get`; versions live
+/// under `__version#<key>` and are written in the same batch. Keys never
+/// written are at version 0. Wrappers around the same store share its key
+/// locks, so they can be created as needed.
+pub struct VersionedStore {
+    store: Arc<RocksDBStore>,
+}
+
+impl VersionedStore {
+    pub fn new(store: Arc<RocksDBStore>) -> Self {
+        Self { store }
+    }
+
+    pub fn store(&self) -> &Arc<RocksDBStore> {
+        &self.store
+    }
+
+    pub fn get<T>(&self, key: &str) -> Result<Option<Versioned<T>>>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let _guard = self.store.lock_key(key);
+        let version = self.version_unlocked(key)?;
+        Ok(self.store.get(key)?.map(|value| Versioned { value, version }))
+    }
+
+    pub fn version(&self, key: &str) -> Result<u64> {
+        let _guard = self.store.lock_key(key);
+        self.version_unlocked(key)
+    }
+
+    /// Write `value` regardless of the current version; returns the new version
+    pub fn put_with<T, F>(&self, key: &str, value: &T, extra: F) -> Result<u64>
+    where
+        T: Serialize,
+        F: FnOnce(&mut StoreWriteBatch<'_>) -> Result<()>,
+    {
+        self.write_with(key, None, Some(value), |batch, _| extra(batch))
+    }
+
+    /// Write `value` only if `key` is still at `expected_version`; returns the
+    /// new version, or `VersionConflict` if another writer got there first
+    pub fn compare_and_put<T>(&self, key: &str, expected_version: u64, value: &T) -> Result<u64>
+    where
+        T: Serialize,
+    {
+        self.write_with(key, Some(expected_version), Some(value), |_, _| Ok(()))
+    }
+
+    /// Like `compare_and_put`, also writing whatever `extra` adds to the batch
+    pub fn compare_and_put_with<T, F>(&self, key: &str, expected_version: u64, value: &T, extra: F) -> Result<u64>
+    where
+        T: Serialize,
+        F: FnOnce(&mut StoreWriteBatch<'_>) -> Result<()>,
+    {
+        self.write_with(key, Some(expected_version), Some(value), |batch, _| extra(batch))
+    }
+
+    /// The general form of the writes above: `value` is left as it is when
+    /// `None`, so only the version moves, e.g. when the change is recorded
+    /// under other keys; `extra` is given the new version
+    pub fn write_with<T, F>(&self, key: &str, expected_version: Option<u64>, value: Option<&T>, extra: F) -> Result<u64>
+    where
+        T: Serialize,
+        F: FnOnce(&mut StoreWriteBatch<'_>, u64) -> Result<()>,
+    {
+        let _guard = self.store.lock_key(key);
+        let current = self.version_unlocked(key)?;
+        if let Some(expected) = expected_version {
+            if expected != current {
+                return Err(TicketMasterError::VersionConflict {
+                    key: key.to_string(),
+                    expected,
+                    actual: current,
+                });
+            }
+        }
+
+        let version = current + 1;
+        let mut batch = self.store.write_batch();
+        if let Some(value) = value {
+            batch.put(key, value)?;
+        }
+        batch.put(&version_key(key), &version)?;
+        extra(&mut batch, version)?;
+        batch.commit()?;
+        Ok(version)
+    }
+
+    /// Delete the value under `key`, writing whatever `extra` adds to the
+    /// batch; the version moves on as for a write, so a `compare_and_put`
+    /// against a version read before fails. Returns the new version
+    pub fn delete_with<F>(&self, key: &str, extra: F) -> Result<u64>
+    where
+        F: FnOnce(&mut StoreWriteBatch<'_>) -> Result<()>,
+    {
+        let _guard = self.store.lock_key(key);
+        let version = self.version_unlocked(key)? + 1;
+        let mut batch = self.store.write_batch();
+        batch.delete(key);
+        batch.put(&version_key(key), &version)?;
+        extra(&mut batch)?;
+        batch.commit()?;
+        Ok(version)
+    }
+
+    fn version_unlocked(&self, key: &str) -> Result<u64> {
+        Ok(self.store.get(&version_key(key))?.unwrap_or(0))
+    }
+}
+
+fn version_key(key: &str) -> String {
+    format!("__version#{}", key)
+}
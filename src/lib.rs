@@ -7,6 +7,28 @@ pub mod avro_schemas;
 pub mod retry;
 pub mod metrics;
 pub mod shutdown;
+pub mod auth;
+pub mod waiting_room;
+pub mod notify;
+pub mod health;
+pub mod api;
+pub mod validation;
+pub mod object_storage;
+pub mod strategies;
+pub mod secrets;
+pub mod config_watch;
+pub mod logging;
+pub mod cli;
+pub mod telemetry;
+pub mod alerting;
+pub mod supervisor;
+pub mod runner;
+pub mod notification;
+pub mod analytics;
+pub mod payment;
+pub mod reconciliation;
+pub mod export;
+pub mod sink;
 
 pub use domain::*;
 pub use error::*;
@@ -16,4 +38,26 @@ pub use kafka::*;
 pub use avro_schemas::*;
 pub use retry::*;
 pub use metrics::*;
-pub use shutdown::*;
\ No newline at end of file
+pub use shutdown::*;
+pub use auth::*;
+pub use waiting_room::*;
+pub use notify::*;
+pub use health::*;
+pub use api::*;
+pub use validation::*;
+pub use object_storage::*;
+pub use strategies::*;
+pub use secrets::*;
+pub use config_watch::*;
+pub use logging::*;
+pub use cli::*;
+pub use telemetry::*;
+pub use alerting::*;
+pub use supervisor::*;
+pub use runner::*;
+pub use notification::*;
+pub use analytics::*;
+pub use payment::*;
+pub use reconciliation::*;
+pub use export::*;
+pub use sink::*;
\ No newline at end of file
@@ -0,0 +1,190 @@
+use crate::telemetry::otel_layer;
+use crate::{Result, TicketMasterError};
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+// Span fields repeated at the top level of JSON log lines, so lines can be
+// searched by request or reservation
+const CORRELATION_FIELDS: [&str; 2] = ["request_id", "reservation_id"];
+
+/// How log lines are written to stdout
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with the service name and the request and
+    /// reservation ids of the spans the line was logged in
+    Json,
+}
+
+/// Changes the filter of the subscriber installed by `init_logging`
+#[derive(Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogFilter {
+    /// Filter with `filter`, in `RUST_LOG` syntax, or with `RUST_LOG` itself when `None`
+    pub fn set(&self, filter: Option<&str>) -> Result<()> {
+        let filter = match filter {
+            Some(filter) => EnvFilter::try_new(filter).map_err(|e| {
+                TicketMasterError::InvalidArgument(format!("Invalid log filter {}: {}", filter, e))
+            })?,
+            None => EnvFilter::from_default_env(),
+        };
+        self.handle
+            .reload(filter)
+            .map_err(|e| TicketMasterError::InvalidArgument(format!("Failed to change the log filter: {}", e)))
+    }
+
+    /// The filter in effect, in `RUST_LOG` syntax
+    pub fn current(&self) -> Result<String> {
+        self.handle
+            .with_current(|filter| filter.to_string())
+            .map_err(|e| TicketMasterError::InvalidArgument(format!("Failed to read the log filter: {}", e)))
+    }
+}
+
+/// Log to stdout in `format`, filtered by `RUST_LOG` until the filter is
+/// changed, and export the spans of `service_name` over OTLP when it is configured
+pub fn init_logging(service_name: &str, format: LogFormat) -> Result<LogFilter> {
+    let (filter, handle) = reload::Layer::new(EnvFilter::from_default_env());
+    let json = format == LogFormat::Json;
+    tracing_subscriber::registry()
+        .with(filter)
+        .with((!json).then(tracing_subscriber::fmt::layer))
+        .with(json.then_some(CorrelationLayer))
+        .with(json.then(|| {
+            tracing_subscriber::fmt::layer().event_format(JsonFormat {
+                service: service_name.to_string(),
+            })
+        }))
+        .with(otel_layer(service_name)?)
+        .init();
+    Ok(LogFilter { handle })
+}
+
+// The correlation fields of a span, kept in its extensions
+#[derive(Default)]
+struct CorrelationFields(Vec<(&'static str, String)>);
+
+impl CorrelationFields {
+    fn set(&mut self, name: &'static str, value: String) {
+        match self.0.iter_mut().find(|(field, _)| *field == name) {
+            Some((_, current)) => *current = value,
+            None => self.0.push((name, value)),
+        }
+    }
+}
+
+impl Visit for CorrelationFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if CORRELATION_FIELDS.contains(&field.name()) {
+            self.set(field.name(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if CORRELATION_FIELDS.contains(&field.name()) {
+            self.set(field.name(), format!("{:?}", value));
+        }
+    }
+}
+
+// Keeps the correlation fields of spans, including those recorded after the span was created
+struct CorrelationLayer;
+
+impl<S> Layer<S> for CorrelationLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = CorrelationFields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<CorrelationFields>() {
+                values.record(fields);
+            }
+        }
+    }
+}
+
+// Event fields as JSON values
+struct JsonFields<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonFields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+}
+
+// One JSON object per event; fields of the event win over those of its spans
+struct JsonFormat {
+    service: String,
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut line = Map::new();
+        line.insert(
+            "timestamp".to_string(),
+            Value::from(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+        );
+        line.insert("level".to_string(), Value::from(metadata.level().as_str()));
+        line.insert("service".to_string(), Value::from(self.service.as_str()));
+        line.insert("target".to_string(), Value::from(metadata.target()));
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                if let Some(fields) = span.extensions().get::<CorrelationFields>() {
+                    for (name, value) in &fields.0 {
+                        line.insert(name.to_string(), Value::from(value.as_str()));
+                    }
+                }
+            }
+        }
+        event.record(&mut JsonFields(&mut line));
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
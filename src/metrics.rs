@@ -1,28 +1,55 @@
 use prometheus::{
-    Counter, Histogram, Gauge, Registry, Opts, HistogramOpts,
-    register_counter_with_registry, register_histogram_with_registry, 
-    register_gauge_with_registry, Encoder, TextEncoder,
+    Counter, CounterVec, HistogramVec, Gauge, GaugeVec, Registry, Opts, HistogramOpts,
+    register_counter_with_registry, register_histogram_vec_with_registry,
+    register_gauge_with_registry, register_counter_vec_with_registry,
+    register_gauge_vec_with_registry, exponential_buckets, Encoder, TextEncoder,
 };
+use rdkafka::error::KafkaError;
+use rdkafka::statistics::Statistics;
 use std::sync::Arc;
-use crate::Result;
+use crate::{CircuitBreakerState, Result, RetryOperation, StoreSizeStats, TicketMasterError};
+
+/// Store label of metrics recorded for stores that aren't labelled with their own name
+pub const ALL_STORES: &str = "all";
+// Route label of requests that matched no route, so unknown paths don't add series
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Machine-readable code of a failed response, put in its extensions so the
+/// metrics middleware can label `errors_total` with it
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorCode(pub &'static str);
 
 /// Metrics collector for the ticket master system
 #[derive(Clone)]
 pub struct Metrics {
     registry: Arc<Registry>,
     
-    // Kafka metrics
-    pub kafka_messages_sent: Counter,
-    pub kafka_messages_received: Counter,
-    pub kafka_send_duration: Histogram,
-    pub kafka_errors: Counter,
+    // Kafka metrics, labelled by logical topic
+    pub kafka_messages_sent: CounterVec,
+    pub kafka_messages_received: CounterVec,
+    pub kafka_send_duration: HistogramVec,
+    pub kafka_sent_bytes: HistogramVec,
+    pub kafka_received_bytes: HistogramVec,
+    pub kafka_commits: CounterVec,
+    pub kafka_commit_duration: HistogramVec,
+    // Also labelled by librdkafka error code; receive errors have no topic
+    pub kafka_errors: CounterVec,
     
-    // State store metrics
-    pub state_store_reads: Counter,
-    pub state_store_writes: Counter,
-    pub state_store_read_duration: Histogram,
-    pub state_store_write_duration: Histogram,
-    pub state_store_size: Gauge,
+    // librdkafka statistics, labelled by client instance
+    pub kafka_client_queue_messages: GaugeVec,
+    pub kafka_client_queue_bytes: GaugeVec,
+    pub kafka_client_tx_bytes: GaugeVec,
+    pub kafka_client_rx_bytes: GaugeVec,
+    // Also labelled by broker, and by quantile for round-trip times
+    pub kafka_broker_rtt: GaugeVec,
+    pub kafka_broker_outbuf_requests: GaugeVec,
+    // Also labelled by cluster topic and partition
+    pub kafka_consumer_lag: GaugeVec,
+    
+    // State store metrics, labelled by store name or `ALL_STORES`
+    pub store_operations: CounterVec,
+    pub store_operation_duration: HistogramVec,
+    pub store_size: GaugeVec,
     
     // Business metrics
     pub events_created: Counter,
@@ -32,11 +59,42 @@ pub struct Metrics {
     pub seats_reserved: Counter,
     pub available_seats: Gauge,
     
-    // Service metrics
+    // Service metrics; requests are labelled by method, route and status
     pub service_uptime: Gauge,
     pub active_connections: Gauge,
-    pub request_duration: Histogram,
-    pub error_rate: Counter,
+    pub request_duration: HistogramVec,
+    // Failed requests, labelled by route, status and error code
+    pub request_errors: CounterVec,
+
+    // Circuit breakers by name; 0 closed, 1 half-open, 2 open
+    pub circuit_breaker_state: GaugeVec,
+    pub circuit_breaker_rejections: CounterVec,
+
+    // Retries of messages that failed on a transient error, by the operation that failed
+    pub retries: CounterVec,
+    pub retries_exhausted: CounterVec,
+
+    // Errors with a context, by the component and operation of the outermost one
+    pub component_errors: CounterVec,
+
+    // Restarts of supervised loops, by loop and whether it panicked or failed
+    pub supervisor_restarts: CounterVec,
+
+    // Notifications of reservation outcomes, by channel, outcome and whether they were sent
+    pub notifications: CounterVec,
+
+    // Charges made, by provider and status
+    pub payments: CounterVec,
+
+    // Seat inventory discrepancies found by the last reconciliation, by kind
+    pub seat_discrepancies: GaugeVec,
+    // Areas re-emitted to repair their counters
+    pub seat_counter_repairs: Counter,
+
+    // Rows written by data exports, by table
+    pub exported_rows: CounterVec,
+    // Unix time of the last snapshot exported in full
+    pub export_last_success: Gauge,
 }
 
 impl Metrics {
@@ -44,52 +102,119 @@ impl Metrics {
         let registry = Arc::new(Registry::new());
         
         // Kafka metrics
-        let kafka_messages_sent = register_counter_with_registry!(
+        let kafka_messages_sent = register_counter_vec_with_registry!(
             Opts::new("kafka_messages_sent_total", "Total number of Kafka messages sent"),
+            &["topic"],
             registry
         )?;
         
-        let kafka_messages_received = register_counter_with_registry!(
+        let kafka_messages_received = register_counter_vec_with_registry!(
             Opts::new("kafka_messages_received_total", "Total number of Kafka messages received"),
+            &["topic"],
             registry
         )?;
         
-        let kafka_send_duration = register_histogram_with_registry!(
+        let kafka_send_duration = register_histogram_vec_with_registry!(
             HistogramOpts::new("kafka_send_duration_seconds", "Time spent sending Kafka messages")
                 .buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]),
+            &["topic"],
+            registry
+        )?;
+        
+        // 64 bytes to 4 MiB
+        let kafka_sent_bytes = register_histogram_vec_with_registry!(
+            HistogramOpts::new("kafka_message_sent_bytes", "Payload sizes of Kafka messages sent")
+                .buckets(exponential_buckets(64.0, 4.0, 9)?),
+            &["topic"],
             registry
         )?;
         
-        let kafka_errors = register_counter_with_registry!(
+        let kafka_received_bytes = register_histogram_vec_with_registry!(
+            HistogramOpts::new("kafka_message_received_bytes", "Payload sizes of Kafka messages received")
+                .buckets(exponential_buckets(64.0, 4.0, 9)?),
+            &["topic"],
+            registry
+        )?;
+        
+        let kafka_commits = register_counter_vec_with_registry!(
+            Opts::new("kafka_commits_total", "Total number of consumer offset commits"),
+            &["topic"],
+            registry
+        )?;
+        
+        let kafka_commit_duration = register_histogram_vec_with_registry!(
+            HistogramOpts::new("kafka_commit_duration_seconds", "Time spent committing consumer offsets")
+                .buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]),
+            &["topic"],
+            registry
+        )?;
+        
+        let kafka_errors = register_counter_vec_with_registry!(
             Opts::new("kafka_errors_total", "Total number of Kafka errors"),
+            &["topic", "error"],
             registry
         )?;
         
-        // State store metrics
-        let state_store_reads = register_counter_with_registry!(
-            Opts::new("state_store_reads_total", "Total number of state store reads"),
+        // librdkafka statistics
+        let kafka_client_queue_messages = register_gauge_vec_with_registry!(
+            Opts::new("kafka_client_queue_messages", "Messages waiting in librdkafka producer queues"),
+            &["client"],
             registry
         )?;
         
-        let state_store_writes = register_counter_with_registry!(
-            Opts::new("state_store_writes_total", "Total number of state store writes"),
+        let kafka_client_queue_bytes = register_gauge_vec_with_registry!(
+            Opts::new("kafka_client_queue_bytes", "Size of the messages waiting in librdkafka producer queues"),
+            &["client"],
             registry
         )?;
         
-        let state_store_read_duration = register_histogram_with_registry!(
-            HistogramOpts::new("state_store_read_duration_seconds", "Time spent reading from state store")
-                .buckets(vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1]),
+        let kafka_client_tx_bytes = register_gauge_vec_with_registry!(
+            Opts::new("kafka_client_tx_bytes", "Bytes sent to brokers since the client was created"),
+            &["client"],
+            registry
+        )?;
+        
+        let kafka_client_rx_bytes = register_gauge_vec_with_registry!(
+            Opts::new("kafka_client_rx_bytes", "Bytes received from brokers since the client was created"),
+            &["client"],
+            registry
+        )?;
+        
+        let kafka_broker_rtt = register_gauge_vec_with_registry!(
+            Opts::new("kafka_broker_rtt_seconds", "Broker round-trip times over the last statistics interval"),
+            &["client", "broker", "quantile"],
+            registry
+        )?;
+        
+        let kafka_broker_outbuf_requests = register_gauge_vec_with_registry!(
+            Opts::new("kafka_broker_outbuf_requests", "Requests waiting to be sent to a broker"),
+            &["client", "broker"],
+            registry
+        )?;
+        
+        let kafka_consumer_lag = register_gauge_vec_with_registry!(
+            Opts::new("kafka_consumer_lag", "Messages between the consumer's position and the end of a partition"),
+            &["client", "topic", "partition"],
+            registry
+        )?;
+        
+        // State store metrics
+        let store_operations = register_counter_vec_with_registry!(
+            Opts::new("state_store_operations_total", "State store reads and writes per store"),
+            &["store", "operation"],
             registry
         )?;
         
-        let state_store_write_duration = register_histogram_with_registry!(
-            HistogramOpts::new("state_store_write_duration_seconds", "Time spent writing to state store")
+        let store_operation_duration = register_histogram_vec_with_registry!(
+            HistogramOpts::new("state_store_operation_duration_seconds", "Time spent reading from and writing to state stores")
                 .buckets(vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1]),
+            &["store", "operation"],
             registry
         )?;
         
-        let state_store_size = register_gauge_with_registry!(
-            Opts::new("state_store_size_bytes", "Size of state store in bytes"),
+        let store_size = register_gauge_vec_with_registry!(
+            Opts::new("state_store_bytes", "State store sizes per store"),
+            &["store", "kind"],
             registry
         )?;
         
@@ -135,14 +260,86 @@ impl Metrics {
             registry
         )?;
         
-        let request_duration = register_histogram_with_registry!(
+        let request_duration = register_histogram_vec_with_registry!(
             HistogramOpts::new("request_duration_seconds", "Time spent processing requests")
                 .buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0]),
+            &["method", "route", "status"],
             registry
         )?;
         
-        let error_rate = register_counter_with_registry!(
+        let request_errors = register_counter_vec_with_registry!(
             Opts::new("errors_total", "Total number of errors"),
+            &["route", "status", "code"],
+            registry
+        )?;
+
+        let circuit_breaker_state = register_gauge_vec_with_registry!(
+            Opts::new("circuit_breaker_state", "Circuit breaker state: 0 closed, 1 half-open, 2 open"),
+            &["breaker"],
+            registry
+        )?;
+
+        let circuit_breaker_rejections = register_counter_vec_with_registry!(
+            Opts::new("circuit_breaker_rejections_total", "Calls failed fast by an open circuit breaker"),
+            &["breaker"],
+            registry
+        )?;
+
+        let retries = register_counter_vec_with_registry!(
+            Opts::new("retries_total", "Retries after a transient error"),
+            &["operation"],
+            registry
+        )?;
+
+        let retries_exhausted = register_counter_vec_with_registry!(
+            Opts::new("retries_exhausted_total", "Transient errors still failing after the last retry"),
+            &["operation"],
+            registry
+        )?;
+
+        let component_errors = register_counter_vec_with_registry!(
+            Opts::new("component_errors_total", "Errors by the component and operation they happened in"),
+            &["component", "operation"],
+            registry
+        )?;
+
+        let supervisor_restarts = register_counter_vec_with_registry!(
+            Opts::new("supervisor_restarts_total", "Restarts of processing loops that panicked or failed"),
+            &["loop", "reason"],
+            registry
+        )?;
+
+        let notifications = register_counter_vec_with_registry!(
+            Opts::new("notifications_total", "Notifications of reservation outcomes sent or failed"),
+            &["channel", "outcome", "result"],
+            registry
+        )?;
+
+        let payments = register_counter_vec_with_registry!(
+            Opts::new("payments_total", "Charges made, by provider and whether they succeeded, were declined or failed"),
+            &["provider", "status"],
+            registry
+        )?;
+
+        let seat_discrepancies = register_gauge_vec_with_registry!(
+            Opts::new("seat_discrepancies", "Seat inventory discrepancies found by the last reconciliation, by kind"),
+            &["kind"],
+            registry
+        )?;
+
+        let seat_counter_repairs = register_counter_with_registry!(
+            Opts::new("seat_counter_repairs_total", "Areas whose available seat counter reconciliation had re-emitted"),
+            registry
+        )?;
+
+        let exported_rows = register_counter_vec_with_registry!(
+            Opts::new("exported_rows_total", "Rows written to data exports, by table"),
+            &["table"],
+            registry
+        )?;
+
+        let export_last_success = register_gauge_with_registry!(
+            Opts::new("export_last_success_timestamp_seconds", "Unix time of the last data export snapshot uploaded in full"),
             registry
         )?;
         
@@ -151,12 +348,21 @@ impl Metrics {
             kafka_messages_sent,
             kafka_messages_received,
             kafka_send_duration,
+            kafka_sent_bytes,
+            kafka_received_bytes,
+            kafka_commits,
+            kafka_commit_duration,
             kafka_errors,
-            state_store_reads,
-            state_store_writes,
-            state_store_read_duration,
-            state_store_write_duration,
-            state_store_size,
+            kafka_client_queue_messages,
+            kafka_client_queue_bytes,
+            kafka_client_tx_bytes,
+            kafka_client_rx_bytes,
+            kafka_broker_rtt,
+            kafka_broker_outbuf_requests,
+            kafka_consumer_lag,
+            store_operations,
+            store_operation_duration,
+            store_size,
             events_created,
             reservations_created,
             reservations_successful,
@@ -166,7 +372,19 @@ impl Metrics {
             service_uptime,
             active_connections,
             request_duration,
-            error_rate,
+            request_errors,
+            circuit_breaker_state,
+            circuit_breaker_rejections,
+            retries,
+            retries_exhausted,
+            component_errors,
+            supervisor_restarts,
+            notifications,
+            payments,
+            seat_discrepancies,
+            seat_counter_repairs,
+            exported_rows,
+            export_last_success,
         })
     }
     
@@ -179,24 +397,74 @@ impl Metrics {
         Ok(String::from_utf8(buffer)?)
     }
     
-    /// Record a Kafka message send operation
-    pub fn record_kafka_send(&self, duration: std::time::Duration, success: bool) {
-        self.kafka_messages_sent.inc();
-        self.kafka_send_duration.observe(duration.as_secs_f64());
-        if !success {
-            self.kafka_errors.inc();
+    /// Record a Kafka message send to `topic`, with the error code if it failed
+    pub fn record_kafka_send(&self, topic: &str, bytes: usize, duration: std::time::Duration, error: Option<&str>) {
+        self.kafka_messages_sent.with_label_values(&[topic]).inc();
+        self.kafka_send_duration.with_label_values(&[topic]).observe(duration.as_secs_f64());
+        self.kafka_sent_bytes.with_label_values(&[topic]).observe(bytes as f64);
+        if let Some(error) = error {
+            self.record_kafka_error(topic, error);
         }
     }
     
-    /// Record a state store operation
-    pub fn record_state_store_read(&self, duration: std::time::Duration) {
-        self.state_store_reads.inc();
-        self.state_store_read_duration.observe(duration.as_secs_f64());
+    pub fn record_kafka_receive(&self, topic: &str, bytes: usize) {
+        self.kafka_messages_received.with_label_values(&[topic]).inc();
+        self.kafka_received_bytes.with_label_values(&[topic]).observe(bytes as f64);
     }
     
-    pub fn record_state_store_write(&self, duration: std::time::Duration) {
-        self.state_store_writes.inc();
-        self.state_store_write_duration.observe(duration.as_secs_f64());
+    /// Record a commit of offsets of `topic`, with the error code if it failed
+    pub fn record_kafka_commit(&self, topic: &str, duration: std::time::Duration, error: Option<&str>) {
+        self.kafka_commits.with_label_values(&[topic]).inc();
+        self.kafka_commit_duration.with_label_values(&[topic]).observe(duration.as_secs_f64());
+        if let Some(error) = error {
+            self.record_kafka_error(topic, error);
+        }
+    }
+    
+    pub fn record_kafka_error(&self, topic: &str, error: &str) {
+        self.kafka_errors.with_label_values(&[topic, error]).inc();
+    }
+    
+    /// Record the statistics a librdkafka client emitted
+    ///
+    /// Bootstrap brokers, whose ids aren't known yet, and partitions without
+    /// a known lag are left out.
+    pub fn record_kafka_statistics(&self, statistics: &Statistics) {
+        let client = statistics.name.as_str();
+        self.kafka_client_queue_messages.with_label_values(&[client]).set(statistics.msg_cnt as f64);
+        self.kafka_client_queue_bytes.with_label_values(&[client]).set(statistics.msg_size as f64);
+        self.kafka_client_tx_bytes.with_label_values(&[client]).set(statistics.tx_bytes as f64);
+        self.kafka_client_rx_bytes.with_label_values(&[client]).set(statistics.rx_bytes as f64);
+        for broker in statistics.brokers.values().filter(|broker| broker.nodeid >= 0) {
+            let name = broker.name.as_str();
+            self.kafka_broker_outbuf_requests.with_label_values(&[client, name]).set(broker.outbuf_cnt as f64);
+            if let Some(rtt) = &broker.rtt {
+                // librdkafka reports microseconds
+                for (quantile, micros) in [("0.5", rtt.p50), ("0.99", rtt.p99)] {
+                    self.kafka_broker_rtt.with_label_values(&[client, name, quantile]).set(micros as f64 / 1e6);
+                }
+            }
+        }
+        for (topic, stats) in &statistics.topics {
+            let known = stats.partitions.values().filter(|partition| partition.partition >= 0 && partition.consumer_lag >= 0);
+            for partition in known {
+                self.kafka_consumer_lag
+                    .with_label_values(&[client, topic, &partition.partition.to_string()])
+                    .set(partition.consumer_lag as f64);
+            }
+        }
+    }
+    
+    /// Record a read or write against `store`
+    pub fn record_store_operation(&self, store: &str, operation: &str, duration: std::time::Duration) {
+        self.store_operations.with_label_values(&[store, operation]).inc();
+        self.store_operation_duration.with_label_values(&[store, operation]).observe(duration.as_secs_f64());
+    }
+    
+    pub fn update_store_size(&self, store: &str, stats: &StoreSizeStats) {
+        self.store_size.with_label_values(&[store, "estimated_live_data"]).set(stats.estimated_live_data as f64);
+        self.store_size.with_label_values(&[store, "live_sst_files"]).set(stats.live_sst_files as f64);
+        self.store_size.with_label_values(&[store, "memtables"]).set(stats.memtables as f64);
     }
     
     /// Record business events
@@ -218,11 +486,23 @@ impl Metrics {
         self.available_seats.set(count as f64);
     }
     
-    /// Record service metrics
-    pub fn record_request(&self, duration: std::time::Duration, success: bool) {
-        self.request_duration.observe(duration.as_secs_f64());
-        if !success {
-            self.error_rate.inc();
+    /// Record a request to `route`; failed ones are also counted by error code
+    pub fn record_request(
+        &self,
+        method: &str,
+        route: &str,
+        status: axum::http::StatusCode,
+        error_code: Option<&str>,
+        duration: std::time::Duration,
+    ) {
+        let status_label = status.as_u16().to_string();
+        self.request_duration
+            .with_label_values(&[method, route, &status_label])
+            .observe(duration.as_secs_f64());
+        if status.is_client_error() || status.is_server_error() {
+            self.request_errors
+                .with_label_values(&[route, &status_label, error_code.unwrap_or_default()])
+                .inc();
         }
     }
     
@@ -233,24 +513,111 @@ impl Metrics {
     pub fn update_uptime(&self, uptime: std::time::Duration) {
         self.service_uptime.set(uptime.as_secs_f64());
     }
+
+    pub fn record_circuit_breaker_state(&self, breaker: &str, state: CircuitBreakerState) {
+        self.circuit_breaker_state.with_label_values(&[breaker]).set(state.gauge_value());
+    }
+
+    pub fn record_circuit_breaker_rejection(&self, breaker: &str) {
+        self.circuit_breaker_rejections.with_label_values(&[breaker]).inc();
+    }
+
+    pub fn record_retry(&self, operation: RetryOperation) {
+        self.retries.with_label_values(&[operation.as_str()]).inc();
+    }
+
+    pub fn record_retries_exhausted(&self, operation: RetryOperation) {
+        self.retries_exhausted.with_label_values(&[operation.as_str()]).inc();
+    }
+
+    /// Count `error` by its outermost context; errors without one aren't counted
+    pub fn record_error(&self, error: &TicketMasterError) {
+        if let Some(context) = error.contexts().first() {
+            self.component_errors.with_label_values(&[&context.component, &context.operation]).inc();
+        }
+    }
+
+    pub fn record_restart(&self, name: &str, reason: &str) {
+        self.supervisor_restarts.with_label_values(&[name, reason]).inc();
+    }
+
+    pub fn record_notification(&self, channel: &str, outcome: &str, sent: bool) {
+        let result = if sent { "sent" } else { "failed" };
+        self.notifications.with_label_values(&[channel, outcome, result]).inc();
+    }
+
+    pub fn record_payment(&self, provider: &str, status: &str) {
+        self.payments.with_label_values(&[provider, status]).inc();
+    }
+
+    /// Set the discrepancies of each kind to those of the last reconciliation
+    pub fn record_seat_discrepancies(&self, counts: &[(&str, usize)]) {
+        for (kind, count) in counts {
+            self.seat_discrepancies.with_label_values(&[kind]).set(*count as f64);
+        }
+    }
 }
 
-/// Metrics middleware for HTTP requests
-pub async fn metrics_middleware<B>(
-    req: axum::extract::Request<B>,
-    next: axum::middleware::Next<B>,
+/// The librdkafka error code of `error`, as labelled in metrics
+pub fn kafka_error_code(error: &KafkaError) -> String {
+    match error.rdkafka_error_code() {
+        Some(code) => format!("{:?}", code),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Metrics middleware for HTTP requests, labelled by the route they matched;
+/// client and server errors count as errors
+pub async fn metrics_middleware(
+    axum::extract::State(metrics): axum::extract::State<Metrics>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
 ) -> axum::response::Response {
     let start = std::time::Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<axum::extract::MatchedPath>()
+        .map_or(UNMATCHED_ROUTE.to_string(), |path| path.as_str().to_string());
     let response = next.run(req).await;
-    let duration = start.elapsed();
-    
-    // Record metrics (would need access to metrics instance)
-    // This is a simplified version - in practice you'd inject metrics via state
-    tracing::info!("Request completed in {:?}", duration);
-    
+    let error_code = response.extensions().get::<ErrorCode>().map(|code| code.0);
+    metrics.record_request(&method, &route, response.status(), error_code, start.elapsed());
     response
 }
 
+/// Serve `metrics` on `router` at `/metrics`, and time every request it handles
+pub fn serve_metrics<S: Clone + Send + Sync + 'static>(router: axum::Router<S>, metrics: Metrics) -> axum::Router<S> {
+    router
+        .route("/metrics", axum::routing::get(metrics_endpoint).with_state(metrics.clone()))
+        .layer(axum::middleware::from_fn_with_state(metrics, metrics_middleware))
+}
+
+/// Serve only `/metrics`, on every interface, for services without an HTTP API
+pub async fn serve_metrics_on(port: u16, metrics: Metrics) -> Result<tokio::task::JoinHandle<()>> {
+    serve_router_on(port, serve_metrics(axum::Router::new(), metrics)).await
+}
+
+/// Serve `/metrics` along with the `health_router` endpoints for `readiness`
+pub async fn serve_metrics_and_health_on(
+    port: u16,
+    metrics: Metrics,
+    readiness: crate::ReadinessProbe,
+) -> Result<tokio::task::JoinHandle<()>> {
+    serve_router_on(port, serve_metrics(crate::health_router(readiness), metrics)).await
+}
+
+/// Serve `router` on every interface, e.g. an API with `serve_metrics` applied
+pub async fn serve_router_on(port: u16, router: axum::Router) -> Result<tokio::task::JoinHandle<()>> {
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+    tracing::info!("Serving HTTP on port {}", port);
+
+    Ok(tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            tracing::error!("HTTP server stopped: {}", e);
+        }
+    }))
+}
+
 /// Health check endpoint that includes metrics
 pub async fn health_with_metrics(
     metrics: Option<axum::extract::State<Arc<Metrics>>>,
@@ -273,7 +640,7 @@ pub async fn health_with_metrics(
 
 /// Metrics endpoint for Prometheus scraping
 pub async fn metrics_endpoint(
-    axum::extract::State(metrics): axum::extract::State<Arc<Metrics>>,
+    axum::extract::State(metrics): axum::extract::State<Metrics>,
 ) -> axum::response::Result<String> {
     match metrics.export() {
         Ok(metrics_data) => Ok(metrics_data),
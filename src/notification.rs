@@ -0,0 +1,366 @@
+use crate::{Metrics, Reservation, ReservationState, Result, RocksDBStore, TicketMasterError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reservation outcomes users are notified of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationOutcome {
+    Reserved,
+    Failed,
+    Paid,
+}
+
+impl NotificationOutcome {
+    pub const ALL: [Self; 3] = [Self::Reserved, Self::Failed, Self::Paid];
+
+    /// The outcome a reservation in `state` is notified of, if any
+    pub fn of(state: &ReservationState) -> Option<Self> {
+        match state {
+            ReservationState::Reserved => Some(Self::Reserved),
+            ReservationState::Failed => Some(Self::Failed),
+            ReservationState::Paid => Some(Self::Paid),
+            ReservationState::Processing | ReservationState::Cancelled => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reserved => "reserved",
+            Self::Failed => "failed",
+            Self::Paid => "paid",
+        }
+    }
+}
+
+impl FromStr for NotificationOutcome {
+    type Err = TicketMasterError;
+
+    fn from_str(outcome: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|known| known.as_str().eq_ignore_ascii_case(outcome.trim()))
+            .ok_or_else(|| {
+                TicketMasterError::InvalidArgument(format!(
+                    "Unknown notification outcome {}, use reserved, failed or paid",
+                    outcome
+                ))
+            })
+    }
+}
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection, usually on port 587
+    #[default]
+    StartTls,
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// Plain text, e.g. to a relay on the same host
+    None,
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = TicketMasterError;
+
+    fn from_str(security: &str) -> Result<Self> {
+        match security.trim().to_ascii_lowercase().as_str() {
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Tls),
+            "none" => Ok(Self::None),
+            _ => Err(TicketMasterError::InvalidArgument(format!(
+                "Unknown SMTP security {}, use starttls, tls or none",
+                security
+            ))),
+        }
+    }
+}
+
+/// Subject and body of an outcome's notifications
+///
+/// `{user_id}`, `{reservation_id}`, `{event_id}`, `{area_id}`,
+/// `{num_of_seats}`, `{seats}` and `{reason}` are replaced with the
+/// reservation's values; `{reason}` is empty unless it failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationTemplate {
+    pub subject: String,
+    pub body: String,
+}
+
+impl NotificationTemplate {
+    pub fn new(subject: &str, body: &str) -> Self {
+        Self { subject: subject.to_string(), body: body.to_string() }
+    }
+
+    /// The built-in template of `outcome`
+    pub fn default_for(outcome: NotificationOutcome) -> Self {
+        match outcome {
+            NotificationOutcome::Reserved => Self::new(
+                "Seats reserved for {event_id}",
+                "Your {num_of_seats} seat(s) in area {area_id} of {event_id} are reserved: {seats}. \
+                 Pay to keep them. Reservation {reservation_id}.",
+            ),
+            NotificationOutcome::Failed => Self::new(
+                "Reservation for {event_id} failed",
+                "We could not reserve {num_of_seats} seat(s) in area {area_id} of {event_id}: {reason}. \
+                 Reservation {reservation_id}.",
+            ),
+            NotificationOutcome::Paid => Self::new(
+                "Tickets confirmed for {event_id}",
+                "Payment received. Your seats in area {area_id} of {event_id} are confirmed: {seats}. \
+                 Reservation {reservation_id}.",
+            ),
+        }
+    }
+
+    fn fill(template: &str, reservation: &Reservation) -> String {
+        let seats = reservation
+            .seats
+            .iter()
+            .map(|seat| format!("row {} seat {}", seat.row, seat.col))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let reason = reservation.failed_reason.as_ref().map_or("", |reason| reason.message.as_str());
+        [
+            ("{user_id}", reservation.user_id.as_str()),
+            ("{reservation_id}", &reservation.reservation_id),
+            ("{event_id}", &reservation.event_id),
+            ("{area_id}", &reservation.area_id),
+            ("{num_of_seats}", &reservation.num_of_seats.to_string()),
+            ("{seats}", &seats),
+            ("{reason}", reason),
+        ]
+        .into_iter()
+        .fold(template.to_string(), |text, (placeholder, value)| text.replace(placeholder, value))
+    }
+}
+
+/// A reservation outcome rendered for the reservation's user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    pub user_id: String,
+    pub reservation_id: String,
+    pub event_id: String,
+    pub area_id: String,
+    pub outcome: NotificationOutcome,
+    pub subject: String,
+    pub body: String,
+}
+
+/// The template of every outcome, built-in unless overridden
+#[derive(Debug, Clone, Default)]
+pub struct NotificationTemplates {
+    overrides: BTreeMap<NotificationOutcome, NotificationTemplate>,
+}
+
+impl NotificationTemplates {
+    pub fn with_template(mut self, outcome: NotificationOutcome, template: NotificationTemplate) -> Self {
+        self.overrides.insert(outcome, template);
+        self
+    }
+
+    pub fn template(&self, outcome: NotificationOutcome) -> NotificationTemplate {
+        self.overrides.get(&outcome).cloned().unwrap_or_else(|| NotificationTemplate::default_for(outcome))
+    }
+
+    /// The notification of `reservation`'s outcome; none while it is still
+    /// processing or once cancelled
+    pub fn render(&self, reservation: &Reservation) -> Option<Notification> {
+        let outcome = NotificationOutcome::of(&reservation.state)?;
+        let template = self.template(outcome);
+        Some(Notification {
+            user_id: reservation.user_id.clone(),
+            reservation_id: reservation.reservation_id.clone(),
+            event_id: reservation.event_id.clone(),
+            area_id: reservation.area_id.clone(),
+            outcome,
+            subject: NotificationTemplate::fill(&template.subject, reservation),
+            body: NotificationTemplate::fill(&template.body, reservation),
+        })
+    }
+}
+
+/// Delivers notifications one way, e.g. by email
+#[async_trait::async_trait]
+pub trait NotificationChannel: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn send(&self, notification: &Notification) -> Result<()>;
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(NOTIFICATION_TIMEOUT)
+        .build()
+        .map_err(|e| TicketMasterError::Notification(format!("Failed to create the HTTP client: {}", e)))
+}
+
+/// Posts every notification as JSON to a webhook
+pub struct WebhookChannel {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl WebhookChannel {
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self { http: http_client()?, url: url.to_string() })
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for WebhookChannel {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        self.http
+            .post(&self.url)
+            .json(notification)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| TicketMasterError::Notification(format!("Webhook failed: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Sends text messages to users; the gateway looks up their numbers
+#[async_trait::async_trait]
+pub trait SmsGateway: Send + Sync {
+    async fn send_sms(&self, user_id: &str, text: &str) -> Result<()>;
+}
+
+/// A gateway taking `{"user_id", "text"}` posted as JSON
+pub struct HttpSmsGateway {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl HttpSmsGateway {
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self { http: http_client()?, url: url.to_string() })
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsGateway for HttpSmsGateway {
+    async fn send_sms(&self, user_id: &str, text: &str) -> Result<()> {
+        self.http
+            .post(&self.url)
+            .json(&serde_json::json!({ "user_id": user_id, "text": text }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| TicketMasterError::Notification(format!("SMS gateway failed: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Texts the subject of every notification through an `SmsGateway`
+pub struct SmsChannel<G> {
+    gateway: G,
+}
+
+impl<G: SmsGateway> SmsChannel<G> {
+    pub fn new(gateway: G) -> Self {
+        Self { gateway }
+    }
+}
+
+#[async_trait::async_trait]
+impl<G: SmsGateway> NotificationChannel for SmsChannel<G> {
+    fn name(&self) -> &str {
+        "sms"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        self.gateway.send_sms(&notification.user_id, &notification.subject).await
+    }
+}
+
+/// Sends each reservation outcome on every channel, once
+///
+/// The last outcome notified of is kept per reservation in RocksDB, so
+/// redelivered and republished reservations are not notified of again.
+pub struct Notifier {
+    templates: NotificationTemplates,
+    channels: Vec<Arc<dyn NotificationChannel>>,
+    notified: RocksDBStore,
+    metrics: Option<Metrics>,
+}
+
+impl Notifier {
+    pub fn open<P: AsRef<Path>>(path: P, templates: NotificationTemplates) -> Result<Self> {
+        std::fs::create_dir_all(&path)?;
+        Ok(Self {
+            templates,
+            channels: Vec::new(),
+            notified: RocksDBStore::new(path)?,
+            metrics: None,
+        })
+    }
+
+    pub fn with_channel<N: NotificationChannel + 'static>(mut self, channel: N) -> Self {
+        self.channels.push(Arc::new(channel));
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn channels(&self) -> impl Iterator<Item = &str> {
+        self.channels.iter().map(|channel| channel.name())
+    }
+
+    /// Notify of `reservation`'s outcome unless it was already; returns
+    /// whether it was sent
+    ///
+    /// A channel failing doesn't stop the others, and the outcome is not sent
+    /// again on any of them; the error names the channels that failed.
+    pub async fn notify(&self, reservation: &Reservation) -> Result<bool> {
+        let Some(notification) = self.templates.render(reservation) else {
+            return Ok(false);
+        };
+        let notified = self.notified.get::<NotificationOutcome>(&reservation.reservation_id)?;
+        if notified == Some(notification.outcome) {
+            debug!("Already notified of {} being {}", reservation.reservation_id, notification.outcome.as_str());
+            return Ok(false);
+        }
+
+        let mut failed = Vec::new();
+        for channel in &self.channels {
+            let result = channel.send(&notification).await;
+            if let Some(metrics) = &self.metrics {
+                metrics.record_notification(channel.name(), notification.outcome.as_str(), result.is_ok());
+            }
+            if let Err(e) = result {
+                warn!("Failed to notify {} by {}: {}", notification.user_id, channel.name(), e);
+                failed.push(channel.name().to_string());
+            }
+        }
+        self.notified.put(&reservation.reservation_id, &notification.outcome)?;
+
+        if failed.is_empty() {
+            Ok(true)
+        } else {
+            Err(TicketMasterError::Notification(format!(
+                "{} of reservation {} not sent by {}",
+                notification.outcome.as_str(),
+                reservation.reservation_id,
+                failed.join(", ")
+            )))
+        }
+    }
+}
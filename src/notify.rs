@@ -0,0 +1,68 @@
+use dashmap::DashMap;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Wakes tasks waiting for a key to change, e.g. long-poll requests for a
+/// reservation's result
+///
+/// Only keys someone is waiting on are tracked; notifying any other key is
+/// a no-op.
+#[derive(Clone, Default)]
+pub struct KeyNotifier {
+    senders: Arc<DashMap<String, watch::Sender<()>>>,
+}
+
+impl KeyNotifier {
+    /// Start watching `key`; changes notified from now on are seen by the watch
+    pub fn watch(&self, key: &str) -> KeyWatch {
+        let receiver = self
+            .senders
+            .entry(key.to_string())
+            .or_insert_with(|| watch::channel(()).0)
+            .subscribe();
+        KeyWatch {
+            key: key.to_string(),
+            receiver,
+            senders: self.senders.clone(),
+        }
+    }
+
+    /// Wake everyone watching `key`
+    pub fn notify(&self, key: &str) {
+        if let Some(sender) = self.senders.get(key) {
+            sender.send_replace(());
+        }
+    }
+
+    /// Keys currently watched
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+}
+
+/// A watch on one key of a `KeyNotifier`; the key is forgotten once its last watch is dropped
+pub struct KeyWatch {
+    key: String,
+    receiver: watch::Receiver<()>,
+    senders: Arc<DashMap<String, watch::Sender<()>>>,
+}
+
+impl KeyWatch {
+    /// Wait for the next notification of the key
+    pub async fn changed(&mut self) {
+        // The sender lives in the map for as long as this watch does
+        let _ = self.receiver.changed().await;
+    }
+}
+
+impl Drop for KeyWatch {
+    fn drop(&mut self) {
+        // This watch's receiver still counts until the drop completes
+        self.senders
+            .remove_if(&self.key, |_, sender| sender.receiver_count() <= 1);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{ArtworkConfig, Result, TicketMasterError};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tracing::debug;
+
+const DEFAULT_S3_REGION: &str = "us-east-1";
+
+/// Where uploaded files such as event artwork or data exports are kept
+#[async_trait::async_trait]
+pub trait ObjectStorage: Send + Sync {
+    /// Store `data` under `key`, replacing what was there; returns the URL to load it from
+    async fn put(&self, key: &str, content_type: &str, data: Vec<u8>) -> Result<String>;
+}
+
+/// Objects as files under a directory, with URLs under `base_url`
+pub struct LocalObjectStorage {
+    dir: PathBuf,
+    base_url: String,
+}
+
+impl LocalObjectStorage {
+    pub fn new(dir: impl Into<PathBuf>, base_url: &str) -> Self {
+        Self {
+            dir: dir.into(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// The object under `key`, if one is stored
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Keys are relative paths; any that could leave the directory is refused
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let valid = key
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != ".." && !segment.contains('\\'));
+        if !valid {
+            return Err(TicketMasterError::InvalidArgument(format!("Invalid object key: {:?}", key)));
+        }
+        Ok(self.dir.join(key))
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectStorage for LocalObjectStorage {
+    async fn put(&self, key: &str, _content_type: &str, data: Vec<u8>) -> Result<String> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Renamed into place so a reader never sees half a file
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, &data).await?;
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(format!("{}/{}", self.base_url, key))
+    }
+}
+
+/// Objects in an S3 bucket, or in a bucket of an S3-compatible store
+///
+/// Requests are signed with AWS Signature Version 4. Buckets on AWS are
+/// addressed virtual-hosted style, those behind a custom endpoint path-style.
+pub struct S3ObjectStorage {
+    http: reqwest::Client,
+    bucket_url: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    public_url: Option<String>,
+}
+
+/// An S3 bucket and the credentials to write to it
+#[derive(Debug, Clone, Copy)]
+pub struct S3Bucket<'a> {
+    pub name: &'a str,
+    /// `us-east-1` when unset
+    pub region: Option<&'a str>,
+    /// An S3-compatible store such as MinIO, addressed path-style
+    pub endpoint: Option<&'a str>,
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` are used when unset
+    pub access_key: Option<&'a str>,
+    pub secret_key: Option<&'a str>,
+}
+
+impl S3ObjectStorage {
+    pub fn new(bucket: S3Bucket) -> Result<Self> {
+        let region = bucket.region.unwrap_or(DEFAULT_S3_REGION).to_string();
+        let credential = |configured: Option<&str>, variable: &str| {
+            configured.map(str::to_string).or_else(|| std::env::var(variable).ok()).ok_or_else(|| {
+                TicketMasterError::InvalidArgument(format!(
+                    "S3 storage in bucket {} needs credentials in the properties file or {}",
+                    bucket.name, variable
+                ))
+            })
+        };
+        let bucket_url = match bucket.endpoint {
+            Some(endpoint) => format!("{}/{}", endpoint.trim_end_matches('/'), bucket.name),
+            None => format!("https://{}.s3.{}.amazonaws.com", bucket.name, region),
+        };
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            bucket_url,
+            region,
+            access_key: credential(bucket.access_key, "AWS_ACCESS_KEY_ID")?,
+            secret_key: credential(bucket.secret_key, "AWS_SECRET_ACCESS_KEY")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            public_url: None,
+        })
+    }
+
+    /// From the `artwork.s3.*` settings; `None` when no bucket is set
+    pub fn from_config(config: &ArtworkConfig) -> Result<Option<Self>> {
+        let Some(bucket) = &config.s3_bucket else {
+            return Ok(None);
+        };
+        let storage = Self::new(S3Bucket {
+            name: bucket,
+            region: config.s3_region.as_deref(),
+            endpoint: config.s3_endpoint.as_deref(),
+            access_key: config.s3_access_key.as_deref(),
+            secret_key: config.s3_secret_key.as_deref(),
+        })?;
+        Ok(Some(storage.with_public_url(config.public_url.as_deref())))
+    }
+
+    /// Answer URLs under `public_url`, e.g. a CDN in front of the bucket, rather than the bucket's
+    pub fn with_public_url(mut self, public_url: Option<&str>) -> Self {
+        self.public_url = public_url.map(|url| url.trim_end_matches('/').to_string());
+        self
+    }
+
+    fn authorization(&self, method: &str, path: &str, headers: &[(&str, String)], payload_hash: &str, amz_date: &str) -> String {
+        let date = &amz_date[..8];
+        // Headers are passed sorted by name, as signing needs them
+        let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_request = format!("{}\n{}\n\n{}\n{}\n{}", method, path, canonical_headers, signed_headers, payload_hash);
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let signing_key = [date, self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(format!("AWS4{}", self.secret_key).into_bytes(), |key, part| hmac_sha256(&key, part.as_bytes()));
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key,
+            scope,
+            signed_headers,
+            hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectStorage for S3ObjectStorage {
+    async fn put(&self, key: &str, content_type: &str, data: Vec<u8>) -> Result<String> {
+        let object_url = format!("{}/{}", self.bucket_url, encode_key(key));
+        let url = reqwest::Url::parse(&object_url)
+            .map_err(|e| TicketMasterError::ObjectStorage(format!("Invalid object URL {}: {}", object_url, e)))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(TicketMasterError::ObjectStorage(format!("No host in {}", object_url))),
+        };
+
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex(&Sha256::digest(&data));
+        let mut headers = vec![
+            ("content-type", content_type.to_string()),
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let authorization = self.authorization("PUT", url.path(), &headers, &payload_hash, &amz_date);
+
+        let mut request = self.http.put(url).header("authorization", authorization).body(data);
+        // reqwest sets the host header itself
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| TicketMasterError::ObjectStorage(format!("Failed to upload {}: {}", key, e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(TicketMasterError::ObjectStorage(format!("Upload of {} failed with {}: {}", key, status, body)));
+        }
+        debug!("Uploaded {} to {}", key, object_url);
+
+        Ok(match &self.public_url {
+            Some(public_url) => format!("{}/{}", public_url, encode_key(key)),
+            None => object_url,
+        })
+    }
+}
+
+// Percent-encode everything but unreserved characters and the `/` between segments
+fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,247 @@
+use crate::{ChargePayment, Metrics, PaymentResult, PaymentStatus, Result, RocksDBStore, TicketMasterError};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const PAYMENT_TIMEOUT: Duration = Duration::from_secs(30);
+// Key prefixes of the payment store
+const CHARGE_PREFIX: &str = "charge#";
+const PAID_PREFIX: &str = "paid#";
+
+/// Which `PaymentProvider` payment-service charges through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentProviderKind {
+    /// `MockPaymentProvider`, for development and tests
+    #[default]
+    Mock,
+    /// `HttpPaymentProvider`, against a Stripe-like API
+    Http,
+}
+
+impl FromStr for PaymentProviderKind {
+    type Err = TicketMasterError;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "mock" => Ok(Self::Mock),
+            "http" => Ok(Self::Http),
+            _ => Err(TicketMasterError::InvalidArgument(format!(
+                "Unknown payment provider {}, use mock or http",
+                kind
+            ))),
+        }
+    }
+}
+
+/// What a provider did with a charge it could handle
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaymentOutcome {
+    /// Charged, under the provider's id of the charge
+    Charged { reference: String },
+    Declined { reason: String },
+}
+
+/// Charges payment methods; errors mean the charge may not have been made
+///
+/// Implementations pass `ChargePayment::idempotency_key` on, so a charge
+/// retried after an error is made at most once.
+#[async_trait::async_trait]
+pub trait PaymentProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn charge(&self, charge: &ChargePayment) -> Result<PaymentOutcome>;
+}
+
+/// Charges every payment method except `DECLINED_METHOD`, without any money moving
+#[derive(Debug, Clone, Default)]
+pub struct MockPaymentProvider;
+
+impl MockPaymentProvider {
+    /// The payment method always declined, named like Stripe's test token
+    pub const DECLINED_METHOD: &'static str = "tok_chargeDeclined";
+}
+
+#[async_trait::async_trait]
+impl PaymentProvider for MockPaymentProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn charge(&self, charge: &ChargePayment) -> Result<PaymentOutcome> {
+        if charge.payment_method == Self::DECLINED_METHOD {
+            return Ok(PaymentOutcome::Declined { reason: "Your card was declined.".to_string() });
+        }
+        Ok(PaymentOutcome::Charged { reference: format!("mock_{}", charge.idempotency_key) })
+    }
+}
+
+/// Charges through a Stripe-like API: `POST <url>/v1/charges` with a form
+/// body, the API key as bearer token and the `Idempotency-Key` header
+///
+/// A 2xx answer carries the charge's `id`; 402 is a decline, with the reason
+/// in `error.message`. Anything else is an error.
+pub struct HttpPaymentProvider {
+    http: reqwest::Client,
+    url: String,
+    api_key: String,
+}
+
+#[derive(Deserialize)]
+struct ChargeResponse {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    message: String,
+}
+
+impl HttpPaymentProvider {
+    pub fn new(url: &str, api_key: &str) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(PAYMENT_TIMEOUT)
+            .build()
+            .map_err(|e| TicketMasterError::Payment(format!("Failed to create the HTTP client: {}", e)))?;
+        Ok(Self {
+            http,
+            url: format!("{}/v1/charges", url.trim_end_matches('/')),
+            api_key: api_key.to_string(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl PaymentProvider for HttpPaymentProvider {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn charge(&self, charge: &ChargePayment) -> Result<PaymentOutcome> {
+        let amount = charge.amount.to_string();
+        let form = [
+            ("amount", amount.as_str()),
+            ("currency", charge.currency.as_str()),
+            ("source", charge.payment_method.as_str()),
+            ("metadata[reservation_id]", charge.reservation_id.as_str()),
+            ("metadata[user_id]", charge.user_id.as_str()),
+        ];
+        let response = self
+            .http
+            .post(&self.url)
+            .bearer_auth(&self.api_key)
+            .header("Idempotency-Key", &charge.idempotency_key)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| TicketMasterError::Payment(format!("Charge request failed: {}", e)))?;
+
+        let status = response.status();
+        if status.is_success() {
+            let charged: ChargeResponse = response
+                .json()
+                .await
+                .map_err(|e| TicketMasterError::Payment(format!("Unreadable charge response: {}", e)))?;
+            return Ok(PaymentOutcome::Charged { reference: charged.id });
+        }
+        if status == reqwest::StatusCode::PAYMENT_REQUIRED {
+            let reason = match response.json::<ErrorResponse>().await {
+                Ok(declined) => declined.error.message,
+                Err(_) => "Declined".to_string(),
+            };
+            return Ok(PaymentOutcome::Declined { reason });
+        }
+        Err(TicketMasterError::Payment(format!("Charge answered {}", status)))
+    }
+}
+
+/// Charges through a provider, each idempotency key at most once
+///
+/// Results of successful and declined charges are kept by idempotency key
+/// and answer repeated commands without charging again; a reservation
+/// already paid is not charged under another key either. Failed charges
+/// are not kept, so they can be retried. Charges of one reservation must
+/// not run concurrently, as payment-service ensures by keying them by
+/// reservation id.
+pub struct Payments {
+    provider: Arc<dyn PaymentProvider>,
+    results: RocksDBStore,
+    metrics: Option<Metrics>,
+}
+
+impl Payments {
+    pub fn open<P: AsRef<Path>, T: PaymentProvider + 'static>(path: P, provider: T) -> Result<Self> {
+        std::fs::create_dir_all(&path)?;
+        Ok(Self {
+            provider: Arc::new(provider),
+            results: RocksDBStore::new(path)?,
+            metrics: None,
+        })
+    }
+
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn provider(&self) -> &str {
+        self.provider.name()
+    }
+
+    /// Charge unless `charge`'s key or reservation was charged already, in
+    /// which case that result is returned
+    pub async fn charge(&self, charge: &ChargePayment) -> Result<PaymentResult> {
+        if charge.amount <= 0 || charge.idempotency_key.is_empty() {
+            return Ok(PaymentResult::new(charge, self.provider.name(), PaymentStatus::Declined, Utc::now())
+                .with_message("A charge needs a positive amount and an idempotency key"));
+        }
+        let charge_key = format!("{}{}", CHARGE_PREFIX, charge.idempotency_key);
+        let paid_key = format!("{}{}", PAID_PREFIX, charge.reservation_id);
+        if let Some(result) = self.results.get::<PaymentResult>(&charge_key)? {
+            debug!("Charge {} already made", charge.idempotency_key);
+            return Ok(result);
+        }
+        if let Some(result) = self.results.get::<PaymentResult>(&paid_key)? {
+            info!("Reservation {} already paid, not charging it again", charge.reservation_id);
+            return Ok(result);
+        }
+
+        let now = Utc::now();
+        let result = match self.provider.charge(charge).await {
+            Ok(PaymentOutcome::Charged { reference }) => {
+                PaymentResult::new(charge, self.provider.name(), PaymentStatus::Succeeded, now).with_reference(reference)
+            }
+            Ok(PaymentOutcome::Declined { reason }) => {
+                PaymentResult::new(charge, self.provider.name(), PaymentStatus::Declined, now).with_message(reason)
+            }
+            Err(e) => {
+                warn!("Charge {} of reservation {} failed: {}", charge.idempotency_key, charge.reservation_id, e);
+                PaymentResult::new(charge, self.provider.name(), PaymentStatus::Failed, now).with_message(e.to_string())
+            }
+        };
+        if let Some(metrics) = &self.metrics {
+            metrics.record_payment(self.provider.name(), result.status.as_str());
+        }
+
+        match result.status {
+            PaymentStatus::Succeeded => {
+                let mut batch = self.results.write_batch();
+                batch.put(&charge_key, &result)?;
+                batch.put(&paid_key, &result)?;
+                batch.commit()?;
+            }
+            PaymentStatus::Declined => self.results.put(&charge_key, &result)?,
+            PaymentStatus::Failed => {}
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{event_area_key, AreaStatus, Reservation, ReservationState, Result, RocksDBStore, Seat};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+// Key prefixes of the reconciliation store
+const AREA_PREFIX: &str = "area#";
+const HELD_PREFIX: &str = "held#";
+// The held key of each reservation holding seats, for its tombstone to find
+const HOLDER_PREFIX: &str = "holder#";
+
+/// What is wrong with an area's seat inventory
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    /// `available_seats` differs from the available seats of the grid
+    CounterMismatch { reported: i32, counted: i32 },
+    /// Taken in the grid, but no reserved or paid reservation holds it;
+    /// only seats once seen available count, as layout gaps are never available
+    UnheldSeat { row: i32, col: i32 },
+    /// Held by a reserved or paid reservation, but available in the grid
+    UnmarkedSeat { row: i32, col: i32, reservation_id: String },
+    /// Held by more than one reserved or paid reservation
+    DoubleBooked { row: i32, col: i32, reservation_ids: Vec<String> },
+}
+
+impl DiscrepancyKind {
+    pub const ALL: [&'static str; 4] = ["counter_mismatch", "unheld_seat", "unmarked_seat", "double_booked"];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CounterMismatch { .. } => "counter_mismatch",
+            Self::UnheldSeat { .. } => "unheld_seat",
+            Self::UnmarkedSeat { .. } => "unmarked_seat",
+            Self::DoubleBooked { .. } => "double_booked",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SeatDiscrepancy {
+    pub event_id: String,
+    pub area_id: String,
+    #[serde(flatten)]
+    pub kind: DiscrepancyKind,
+}
+
+/// Discrepancies of one reconciliation run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReconciliationReport {
+    pub areas_checked: usize,
+    pub discrepancies: Vec<SeatDiscrepancy>,
+}
+
+impl ReconciliationReport {
+    /// The discrepancies also found by the run before, leaving out those
+    /// of records still on their way, e.g. seats taken by event-service
+    /// whose reservation isn't published as reserved yet
+    pub fn confirmed_by(&self, previous: &ReconciliationReport) -> ReconciliationReport {
+        let previous: HashSet<&SeatDiscrepancy> = previous.discrepancies.iter().collect();
+        ReconciliationReport {
+            areas_checked: self.areas_checked,
+            discrepancies: self.discrepancies.iter().filter(|d| previous.contains(d)).cloned().collect(),
+        }
+    }
+
+    /// Discrepancies by kind, with every kind listed
+    pub fn counts(&self) -> Vec<(&'static str, usize)> {
+        DiscrepancyKind::ALL
+            .iter()
+            .map(|kind| (*kind, self.discrepancies.iter().filter(|d| d.kind.as_str() == *kind).count()))
+            .collect()
+    }
+
+    /// Areas whose counter is off, as `(event_id, area_id)`
+    pub fn miscounted_areas(&self) -> Vec<(String, String)> {
+        self.discrepancies
+            .iter()
+            .filter(|d| matches!(d.kind, DiscrepancyKind::CounterMismatch { .. }))
+            .map(|d| (d.event_id.clone(), d.area_id.clone()))
+            .collect()
+    }
+}
+
+// An area's latest status, with the grid positions ever seen available,
+// which are seats rather than gaps of the layout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrackedArea {
+    status: AreaStatus,
+    ever_available: Vec<bool>,
+}
+
+impl TrackedArea {
+    fn is_seat(&self, row: i32, col: i32) -> bool {
+        let index = row as usize * self.status.col_count.max(0) as usize + col as usize;
+        self.ever_available.get(index).copied().unwrap_or(false)
+    }
+}
+
+/// Keeps the latest area statuses and the seats of reserved and paid
+/// reservations in a RocksDB store, and checks them against each other
+#[derive(Clone)]
+pub struct SeatReconciler {
+    store: Arc<RocksDBStore>,
+}
+
+impl SeatReconciler {
+    pub fn new(store: Arc<RocksDBStore>) -> Self {
+        Self { store }
+    }
+
+    pub fn apply_area_status(&self, area_status: &AreaStatus) -> Result<()> {
+        let key = area_key(&area_status.event_id, &area_status.area_id);
+        let _guard = self.store.lock_key(&key);
+        let positions = (area_status.row_count.max(0) * area_status.col_count.max(0)) as usize;
+        let mut ever_available = match self.store.get::<TrackedArea>(&key)? {
+            // A new layout starts over
+            Some(tracked) if tracked.ever_available.len() == positions => tracked.ever_available,
+            _ => vec![false; positions],
+        };
+        for seat in area_status.seats.iter().flatten().filter(|seat| seat.is_available) {
+            let index = seat.row as usize * area_status.col_count as usize + seat.col as usize;
+            if let Some(seen) = ever_available.get_mut(index) {
+                *seen = true;
+            }
+        }
+        self.store.put(&key, &TrackedArea { status: area_status.clone(), ever_available })
+    }
+
+    /// Keep the seats of reserved and paid reservations; others hold none
+    pub fn apply_reservation(&self, reservation: &Reservation) -> Result<()> {
+        let key = held_key(&reservation.event_id, &reservation.area_id, &reservation.reservation_id);
+        let holder = holder_key(&reservation.reservation_id);
+        let mut batch = self.store.write_batch();
+        match reservation.state {
+            ReservationState::Reserved | ReservationState::Paid => {
+                batch.put(&key, &reservation.seats)?;
+                batch.put(&holder, &key)?;
+            }
+            _ => {
+                batch.delete(&key);
+                batch.delete(&holder);
+            }
+        }
+        batch.commit()
+    }
+
+    /// Forget the seats of a deleted reservation
+    pub fn delete_reservation(&self, reservation_id: &str) -> Result<()> {
+        let holder = holder_key(reservation_id);
+        let Some(held) = self.store.get::<String>(&holder)? else {
+            return Ok(());
+        };
+        let mut batch = self.store.write_batch();
+        batch.delete(&held);
+        batch.delete(&holder);
+        batch.commit()
+    }
+
+    /// Stop checking a deleted area
+    pub fn delete_area(&self, event_area_id: &str) -> Result<()> {
+        let key = format!("{}{}", AREA_PREFIX, event_area_id);
+        let _guard = self.store.lock_key(&key);
+        self.store.delete(&key)
+    }
+
+    /// Check every area seen
+    pub fn reconcile(&self) -> Result<ReconciliationReport> {
+        let mut report = ReconciliationReport::default();
+        for entry in self.store.scan_prefix::<TrackedArea>(AREA_PREFIX) {
+            let (_, area) = entry?;
+            report.areas_checked += 1;
+            report.discrepancies.extend(self.reconcile_area(&area)?);
+        }
+        Ok(report)
+    }
+
+    fn reconcile_area(&self, area: &TrackedArea) -> Result<Vec<SeatDiscrepancy>> {
+        let area_status = &area.status;
+        let discrepancy = |kind| SeatDiscrepancy {
+            event_id: area_status.event_id.clone(),
+            area_id: area_status.area_id.clone(),
+            kind,
+        };
+        let mut discrepancies = Vec::new();
+
+        let counted = area_status.seats.iter().flatten().filter(|seat| seat.is_available).count() as i32;
+        if counted != area_status.available_seats {
+            discrepancies.push(discrepancy(DiscrepancyKind::CounterMismatch {
+                reported: area_status.available_seats,
+                counted,
+            }));
+        }
+
+        let mut holders: BTreeMap<(i32, i32), Vec<String>> = BTreeMap::new();
+        let prefix = format!("{}{}#", HELD_PREFIX, event_area_key(&area_status.event_id, &area_status.area_id));
+        for entry in self.store.scan_prefix::<Vec<Seat>>(&prefix) {
+            let (key, seats) = entry?;
+            let reservation_id = key.strip_prefix(&prefix).unwrap_or(&key).to_string();
+            for seat in seats {
+                holders.entry((seat.row, seat.col)).or_default().push(reservation_id.clone());
+            }
+        }
+
+        for seat in area_status.seats.iter().flatten() {
+            let held_by = holders.remove(&(seat.row, seat.col)).unwrap_or_default();
+            if held_by.len() > 1 {
+                discrepancies.push(discrepancy(DiscrepancyKind::DoubleBooked {
+                    row: seat.row,
+                    col: seat.col,
+                    reservation_ids: held_by.clone(),
+                }));
+            }
+            match (seat.is_available, held_by.into_iter().next()) {
+                (false, None) if area.is_seat(seat.row, seat.col) => {
+                    discrepancies.push(discrepancy(DiscrepancyKind::UnheldSeat { row: seat.row, col: seat.col }))
+                }
+                (true, Some(reservation_id)) => discrepancies.push(discrepancy(DiscrepancyKind::UnmarkedSeat {
+                    row: seat.row,
+                    col: seat.col,
+                    reservation_id,
+                })),
+                _ => {}
+            }
+        }
+        Ok(discrepancies)
+    }
+}
+
+fn area_key(event_id: &str, area_id: &str) -> String {
+    format!("{}{}", AREA_PREFIX, event_area_key(event_id, area_id))
+}
+
+fn held_key(event_id: &str, area_id: &str, reservation_id: &str) -> String {
+    format!("{}{}#{}", HELD_PREFIX, event_area_key(event_id, area_id), reservation_id)
+}
+
+fn holder_key(reservation_id: &str) -> String {
+    format!("{}{}", HOLDER_PREFIX, reservation_id)
+}
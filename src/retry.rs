@@ -1,4 +1,5 @@
-use crate::{Result, TicketMasterError};
+use crate::{Metrics, Result, TicketMasterError};
+use serde::Serialize;
 use std::time::Duration;
 use tokio::time::sleep;
 use tracing::{warn, error, info};
@@ -99,21 +100,120 @@ where
                 );
 
                 sleep(delay).await;
+                delay = next_delay(config, delay);
+                attempt += 1;
+            }
+        }
+    }
+}
 
-                // Calculate next delay with exponential backoff
-                delay = Duration::from_millis(
-                    ((delay.as_millis() as f64) * config.backoff_multiplier) as u64
-                ).min(config.max_delay);
+// Exponential backoff from `delay`, with jitter if enabled
+fn next_delay(config: &RetryConfig, delay: Duration) -> Duration {
+    let mut delay = Duration::from_millis(
+        ((delay.as_millis() as f64) * config.backoff_multiplier) as u64
+    ).min(config.max_delay);
 
-                // Add jitter if enabled
-                if config.jitter {
-                    let jitter_ms = (delay.as_millis() as f64 * 0.1 * rand::random::<f64>()) as u64;
-                    delay = delay + Duration::from_millis(jitter_ms);
-                }
+    if config.jitter {
+        let jitter_ms = (delay.as_millis() as f64 * 0.1 * rand::random::<f64>()) as u64;
+        delay += Duration::from_millis(jitter_ms);
+    }
+    delay
+}
 
-                attempt += 1;
+/// The kind of operation a transient error came from, which picks its retry policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryOperation {
+    KafkaSend,
+    StateStore,
+}
+
+impl RetryOperation {
+    /// Label of the operation in retry metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetryOperation::KafkaSend => "kafka_send",
+            RetryOperation::StateStore => "state_store",
+        }
+    }
+}
+
+/// Retry policy of each kind of transient error
+#[derive(Debug, Clone)]
+pub struct RetryPolicies {
+    pub kafka_send: RetryConfig,
+    pub state_store: RetryConfig,
+}
+
+impl Default for RetryPolicies {
+    fn default() -> Self {
+        Self {
+            kafka_send: RetryConfig::kafka_producer(),
+            state_store: RetryConfig::state_store(),
+        }
+    }
+}
+
+impl RetryPolicies {
+    pub fn policy(&self, operation: RetryOperation) -> &RetryConfig {
+        match operation {
+            RetryOperation::KafkaSend => &self.kafka_send,
+            RetryOperation::StateStore => &self.state_store,
+        }
+    }
+}
+
+/// Retry `operation` while it fails with a transient error, under the policy
+/// of the kind of operation that failed; other errors are returned at once
+///
+/// Each kind counts its own attempts, and every retry and every transient
+/// error given up on is counted in `metrics`.
+pub async fn retry_transient<F, Fut, T>(
+    policies: &RetryPolicies,
+    operation_name: &str,
+    metrics: Option<&Metrics>,
+    mut operation: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let mut kafka_send = (1, policies.kafka_send.initial_delay);
+    let mut state_store = (1, policies.state_store.initial_delay);
+
+    loop {
+        let e = match operation().await {
+            Ok(result) => return Ok(result),
+            Err(e) => e,
+        };
+        let Some(kind) = e.transient_operation() else {
+            return Err(e);
+        };
+        let config = policies.policy(kind);
+        let (attempt, delay) = match kind {
+            RetryOperation::KafkaSend => &mut kafka_send,
+            RetryOperation::StateStore => &mut state_store,
+        };
+        if *attempt >= config.max_attempts {
+            error!(
+                "Operation '{}' failed after {} attempts on {}. Final error: {}",
+                operation_name, attempt, kind.as_str(), e
+            );
+            if let Some(metrics) = metrics {
+                metrics.record_retries_exhausted(kind);
             }
+            return Err(e);
+        }
+
+        warn!(
+            "Operation '{}' failed on {} attempt {} ({}). Retrying in {:?}...",
+            operation_name, kind.as_str(), attempt, e, delay
+        );
+        if let Some(metrics) = metrics {
+            metrics.record_retry(kind);
         }
+        sleep(*delay).await;
+        *delay = next_delay(config, *delay);
+        *attempt += 1;
     }
 }
 
@@ -142,139 +242,218 @@ where
 }
 
 /// Circuit breaker for preventing cascading failures
-#[derive(Debug)]
+///
+/// After `failure_threshold` failures in a row the breaker opens and calls
+/// fail fast with `CircuitOpen`. Once `recovery_timeout` has passed it turns
+/// half-open and lets a limited number of probe calls through at a time: a
+/// probe succeeding closes it, a probe failing opens it again.
 pub struct CircuitBreaker {
-    failure_count: std::sync::atomic::AtomicU32,
-    last_failure_time: std::sync::Mutex<Option<std::time::Instant>>,
+    name: String,
     failure_threshold: u32,
     recovery_timeout: Duration,
-    state: std::sync::Mutex<CircuitBreakerState>,
+    half_open_probes: u32,
+    inner: std::sync::Mutex<BreakerInner>,
+    metrics: Option<Metrics>,
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    state: CircuitBreakerState,
+    failure_count: u32,
+    last_failure_time: Option<std::time::Instant>,
+    probes_in_flight: u32,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum CircuitBreakerState {
+/// Whether a circuit breaker lets calls through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitBreakerState {
     Closed,
+    /// Calls fail fast until the recovery timeout has passed
     Open,
+    /// Letting probe calls through again; the next failure opens it
     HalfOpen,
 }
 
+impl CircuitBreakerState {
+    /// As exported in the `circuit_breaker_state` gauge
+    pub fn gauge_value(self) -> f64 {
+        match self {
+            Self::Closed => 0.0,
+            Self::HalfOpen => 1.0,
+            Self::Open => 2.0,
+        }
+    }
+}
+
+const DEFAULT_HALF_OPEN_PROBES: u32 = 1;
+
 impl CircuitBreaker {
     pub fn new(failure_threshold: u32, recovery_timeout: Duration) -> Self {
         Self {
-            failure_count: std::sync::atomic::AtomicU32::new(0),
-            last_failure_time: std::sync::Mutex::new(None),
-            failure_threshold,
+            name: String::new(),
+            failure_threshold: failure_threshold.max(1),
             recovery_timeout,
-            state: std::sync::Mutex::new(CircuitBreakerState::Closed),
+            half_open_probes: DEFAULT_HALF_OPEN_PROBES,
+            inner: std::sync::Mutex::new(BreakerInner {
+                state: CircuitBreakerState::Closed,
+                failure_count: 0,
+                last_failure_time: None,
+                probes_in_flight: 0,
+            }),
+            metrics: None,
         }
     }
 
+    /// Breaker for sends to the Kafka brokers
+    pub fn kafka_producer() -> Self {
+        Self::new(10, Duration::from_secs(30)).with_name("kafka_producer")
+    }
+
+    /// Breaker for Schema Registry lookups
+    pub fn schema_registry() -> Self {
+        Self::new(5, Duration::from_secs(30)).with_name("schema_registry")
+    }
+
+    /// What the breaker guards, as reported in status and metrics
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Calls let through at a time while half-open
+    pub fn with_half_open_probes(mut self, probes: u32) -> Self {
+        self.half_open_probes = probes.max(1);
+        self
+    }
+
+    /// Export the state and rejected calls in `metrics`, labelled with the name
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        metrics.record_circuit_breaker_state(&self.name, self.state());
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The state as of the last call; an open breaker only turns half-open on a call
+    pub fn state(&self) -> CircuitBreakerState {
+        self.inner.lock().unwrap().state
+    }
+
     pub async fn call<F, Fut, T>(&self, operation: F) -> Result<T>
     where
         F: FnOnce() -> Fut,
         Fut: std::future::Future<Output = Result<T>>,
     {
-        // Check if circuit breaker should allow the call
-        if !self.should_allow_call() {
-            return Err(TicketMasterError::InvalidArgument(
-                "Circuit breaker is open".to_string()
-            ));
-        }
-
+        let permit = self.permit()?;
         match operation().await {
             Ok(result) => {
-                self.on_success();
+                permit.succeeded();
                 Ok(result)
             }
             Err(e) => {
-                self.on_failure();
+                permit.failed();
                 Err(e)
             }
         }
     }
 
-    fn should_allow_call(&self) -> bool {
-        let state = self.state.lock().unwrap();
-        match *state {
-            CircuitBreakerState::Closed => true,
+    /// Permission for one call, for callers that tell failures of the
+    /// dependency from other errors themselves; `CircuitOpen` when refused
+    ///
+    /// A permit dropped without an outcome counts as neither.
+    pub fn permit(&self) -> Result<CircuitPermit<'_>> {
+        let mut inner = self.inner.lock().unwrap();
+        let probe = match inner.state {
+            CircuitBreakerState::Closed => false,
             CircuitBreakerState::Open => {
-                // Check if recovery timeout has passed
-                if let Some(last_failure) = *self.last_failure_time.lock().unwrap() {
-                    if last_failure.elapsed() >= self.recovery_timeout {
-                        drop(state);
-                        *self.state.lock().unwrap() = CircuitBreakerState::HalfOpen;
-                        true
-                    } else {
-                        false
-                    }
-                } else {
-                    false
+                let recovered = inner.last_failure_time.is_some_and(|last| last.elapsed() >= self.recovery_timeout);
+                if !recovered {
+                    return Err(self.reject());
                 }
+                self.transition(&mut inner, CircuitBreakerState::HalfOpen);
+                true
             }
             CircuitBreakerState::HalfOpen => true,
+        };
+        if probe {
+            if inner.probes_in_flight >= self.half_open_probes {
+                return Err(self.reject());
+            }
+            inner.probes_in_flight += 1;
         }
+        Ok(CircuitPermit { breaker: self, probe })
+    }
+
+    fn reject(&self) -> TicketMasterError {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_circuit_breaker_rejection(&self.name);
+        }
+        TicketMasterError::CircuitOpen
     }
 
     fn on_success(&self) {
-        self.failure_count.store(0, std::sync::atomic::Ordering::Relaxed);
-        *self.state.lock().unwrap() = CircuitBreakerState::Closed;
+        let mut inner = self.inner.lock().unwrap();
+        inner.failure_count = 0;
+        self.transition(&mut inner, CircuitBreakerState::Closed);
     }
 
     fn on_failure(&self) {
-        let failure_count = self.failure_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
-        *self.last_failure_time.lock().unwrap() = Some(std::time::Instant::now());
+        let mut inner = self.inner.lock().unwrap();
+        inner.failure_count += 1;
+        inner.last_failure_time = Some(std::time::Instant::now());
 
-        if failure_count >= self.failure_threshold {
-            *self.state.lock().unwrap() = CircuitBreakerState::Open;
-            warn!("Circuit breaker opened after {} failures", failure_count);
+        let reopen = inner.state == CircuitBreakerState::HalfOpen;
+        if (reopen || inner.failure_count >= self.failure_threshold) && inner.state != CircuitBreakerState::Open {
+            warn!("Circuit breaker {} opened after {} failures", self.name, inner.failure_count);
+            self.transition(&mut inner, CircuitBreakerState::Open);
         }
     }
-}
 
-/// Enhanced error context for better debugging
-#[derive(Debug)]
-pub struct ErrorContext {
-    pub operation: String,
-    pub component: String,
-    pub metadata: std::collections::HashMap<String, String>,
+    fn transition(&self, inner: &mut BreakerInner, state: CircuitBreakerState) {
+        if inner.state == state {
+            return;
+        }
+        inner.state = state;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_circuit_breaker_state(&self.name, state);
+        }
+    }
 }
 
-impl ErrorContext {
-    pub fn new(operation: &str, component: &str) -> Self {
-        Self {
-            operation: operation.to_string(),
-            component: component.to_string(),
-            metadata: std::collections::HashMap::new(),
-        }
+impl std::fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("name", &self.name)
+            .field("state", &self.state())
+            .finish()
     }
+}
 
-    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
-        self.metadata.insert(key.to_string(), value.to_string());
-        self
+/// One call let through by a `CircuitBreaker`; report how it went
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl CircuitPermit<'_> {
+    pub fn succeeded(self) {
+        self.breaker.on_success();
     }
 
-    pub fn add_metadata(&mut self, key: &str, value: &str) {
-        self.metadata.insert(key.to_string(), value.to_string());
+    pub fn failed(self) {
+        self.breaker.on_failure();
     }
 }
 
-/// Wrap errors with additional context
-pub fn wrap_error(error: TicketMasterError, context: ErrorContext) -> TicketMasterError {
-    let context_str = format!(
-        "Operation: {}, Component: {}, Metadata: {:?}",
-        context.operation, context.component, context.metadata
-    );
-    
-    match error {
-        TicketMasterError::InvalidArgument(msg) => {
-            TicketMasterError::InvalidArgument(format!("{} | Context: {}", msg, context_str))
-        }
-        TicketMasterError::Kafka(e) => {
-            TicketMasterError::InvalidArgument(format!("Kafka error: {} | Context: {}", e, context_str))
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            let mut inner = self.breaker.inner.lock().unwrap();
+            inner.probes_in_flight = inner.probes_in_flight.saturating_sub(1);
         }
-        TicketMasterError::RocksDB(e) => {
-            TicketMasterError::InvalidArgument(format!("RocksDB error: {} | Context: {}", e, context_str))
-        }
-        other => other,
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,481 @@
+use crate::{
+    serve_metrics, serve_metrics_on, serve_router_on, shutdown_telemetry, Backpressure, CircuitBreaker,
+    HeartbeatPublisher, HttpServerShutdown, KafkaConsumer, KafkaConsumerShutdown, KafkaMessage, KafkaProducer,
+    KafkaProducerShutdown, KafkaReadiness, KeyedDispatcher, MessageConsumer, MessageHandler, Metrics, PartitionDrain,
+    ReadinessProbe, Replay, ReplayWatermarks, Result, ServiceConfig, ShutdownComponent, ShutdownCoordinator,
+    Supervisor, TicketMasterError, TopicSerdes,
+};
+use axum::Router;
+use futures::StreamExt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::{interval, Interval};
+use tracing::{error, info, warn};
+
+/// Handles the records a `ConsumerRunner` reads, on all its dispatcher lanes
+#[async_trait::async_trait]
+pub trait RecordProcessor: MessageHandler {
+    /// Prepare before each run reads a record, e.g. create tables
+    async fn start(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// What a record failing with `e` does: `Ok` logs it and commits past
+    /// it, `Err` ends the run with its offset uncommitted, so the record is
+    /// processed again once the service is restarted
+    fn on_failure(&self, e: TicketMasterError) -> Result<()> {
+        error!("Error processing record: {}", e);
+        Ok(())
+    }
+
+    /// When `tick` runs, created anew for each run; `None` when the
+    /// processor has no periodic work
+    fn timer(&self) -> Option<Interval> {
+        None
+    }
+
+    /// Periodic work, e.g. exporting a snapshot; no record is read until it
+    /// is done
+    async fn tick(&self) {}
+
+    /// Whether the processor's stores are stalling writes, which pauses
+    /// consumption like too many records in flight do
+    fn write_stalled(&self) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Consumes topics through a `KeyedDispatcher`, committing each record once
+/// it and every record before it on its partition are processed
+///
+/// Consumption pauses while too many records are in flight, or while the
+/// processor's stores stall writes. On shutdown the records in flight are
+/// finished and committed before `run` returns.
+pub struct ConsumerRunner<H, C = KafkaConsumer> {
+    name: String,
+    topics: &'static [&'static str],
+    consumer: Arc<C>,
+    processor: Arc<H>,
+    metrics: Metrics,
+    parallelism: usize,
+    commit_interval: Duration,
+    max_in_flight: usize,
+    // Records in flight, completed by the lanes and drained as partitions are revoked
+    offsets: PartitionDrain,
+    // Timestamp each run rewinds the assigned partitions to, if replaying
+    replay: Option<(i64, Arc<ReplayWatermarks>)>,
+    // As in `consumers.<name>.` properties, for services with several consumers
+    consumer_name: Option<String>,
+    // Whether this runner does the processor's periodic work
+    ticks: bool,
+    // Stopped with the consumer once `run` has returned
+    components: Vec<Box<dyn ShutdownComponent + Send + Sync>>,
+}
+
+impl<H: RecordProcessor, C: MessageConsumer> ConsumerRunner<H, C> {
+    /// Subscribe `consumer` to `topics` for `processor`; `metrics` are those
+    /// the processor records in, so restarts are counted alongside
+    pub fn new(
+        config: &ServiceConfig,
+        consumer: C,
+        topics: &'static [&'static str],
+        processor: H,
+        metrics: Metrics,
+    ) -> Result<Self> {
+        Self::with_processor(config, consumer, topics, Arc::new(processor), metrics)
+    }
+
+    /// Run the consumer `name`, as in `consumers.<name>.` properties, on its
+    /// own lanes for a `processor` shared with the runners of the service's
+    /// other consumers, so a backlog on one doesn't hold up the others
+    pub fn for_consumer(
+        config: &ServiceConfig,
+        name: &str,
+        consumer: C,
+        topics: &'static [&'static str],
+        processor: Arc<H>,
+        metrics: Metrics,
+    ) -> Result<Self> {
+        let mut runner = Self::with_processor(config, consumer, topics, processor, metrics)?;
+        runner.name = format!("{} ({})", config.application_id, name);
+        runner.consumer_name = Some(name.to_string());
+        runner.parallelism = config.consumer_parallelism(name);
+        Ok(runner)
+    }
+
+    fn with_processor(
+        config: &ServiceConfig,
+        consumer: C,
+        topics: &'static [&'static str],
+        processor: Arc<H>,
+        metrics: Metrics,
+    ) -> Result<Self> {
+        consumer.subscribe(topics)?;
+
+        // Records of a key are processed in order on one lane
+        let parallelism = config
+            .processing_parallelism
+            .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+
+        let consumer = Arc::new(consumer);
+        let offsets = PartitionDrain::new();
+        offsets.attach(&consumer);
+
+        Ok(Self {
+            name: config.application_id.clone(),
+            topics,
+            consumer,
+            processor,
+            metrics,
+            parallelism,
+            commit_interval: config.commit_interval(),
+            max_in_flight: config.max_in_flight(),
+            offsets,
+            replay: None,
+            consumer_name: None,
+            ticks: true,
+            components: Vec::new(),
+        })
+    }
+
+    /// Track the records in flight in `drain`, which the consumer's rebalance
+    /// listener drains revoked partitions through
+    pub fn with_partition_drain(mut self, drain: PartitionDrain) -> Self {
+        drain.attach(&self.consumer);
+        self.offsets = drain;
+        self
+    }
+
+    /// Leave the processor's periodic work to another runner sharing it
+    pub fn without_timer(mut self) -> Self {
+        self.ticks = false;
+        self
+    }
+
+    /// Reprocess every assigned partition from `timestamp_ms` (epoch millis)
+    /// instead of the committed offsets; `watermarks` mark the records that
+    /// were processed before, so the processor can let them past its dedup
+    pub fn with_replay_from(mut self, timestamp_ms: i64, watermarks: Arc<ReplayWatermarks>) -> Self {
+        self.replay = Some((timestamp_ms, watermarks));
+        self
+    }
+
+    pub fn consumer(&self) -> &Arc<C> {
+        &self.consumer
+    }
+
+    pub fn topics(&self) -> &'static [&'static str] {
+        self.topics
+    }
+
+    pub fn processor(&self) -> &Arc<H> {
+        &self.processor
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Also stop `component` on shutdown, e.g. an HTTP server or the heartbeats
+    pub fn add_shutdown_component<S: ShutdownComponent + Send + Sync + 'static>(&mut self, component: S) {
+        self.components.push(Box::new(component));
+    }
+
+    /// Have `coordinator` leave the consumer group and stop the added
+    /// components once `run` has returned
+    pub async fn register_shutdown(&mut self, coordinator: &ShutdownCoordinator) {
+        let mut consumer = KafkaConsumerShutdown::new(self.consumer.clone());
+        if let Some(name) = &self.consumer_name {
+            consumer = consumer.with_name(&format!("kafka-consumer-{}", name));
+        }
+        coordinator.register_component(Box::new(consumer)).await;
+        for component in self.components.drain(..) {
+            coordinator.register_component(component).await;
+        }
+    }
+
+    /// Leave and join the consumer group again, so processing resumes from
+    /// the committed offsets, e.g. before `run` is restarted after a crash
+    pub fn rejoin(&self) -> Result<()> {
+        self.consumer.unsubscribe()?;
+        self.consumer.subscribe(self.topics)
+    }
+
+    /// Process records until `shutdown` is signalled, then finish the
+    /// records in flight and commit their offsets
+    ///
+    /// A record the processor won't skip, see `RecordProcessor::on_failure`,
+    /// stops the run the same way and its error is returned.
+    pub async fn run(&self, mut shutdown: broadcast::Receiver<()>) -> Result<()> {
+        self.processor.start().await?;
+        info!("{} is running with {} processing lanes...", self.name, self.parallelism);
+
+        let tracked = Arc::new(Tracked {
+            processor: self.processor.clone(),
+            offsets: self.offsets.clone(),
+        });
+        let (dispatcher, mut completions) = KeyedDispatcher::new(self.parallelism, tracked);
+        let mut backpressure = Backpressure::new(self.max_in_flight);
+        let mut replay = self.replay.as_ref().map(|(timestamp_ms, watermarks)| Replay::new(*timestamp_ms, watermarks.clone()));
+        let mut commit_timer = interval(self.commit_interval);
+        let mut timer = self.processor.timer().filter(|_| self.ticks);
+        let mut messages = self.consumer.stream();
+        let mut failed = None;
+        loop {
+            tokio::select! {
+                _ = shutdown.recv() => {
+                    info!("Received shutdown signal");
+                    break;
+                }
+
+                Some(message_result) = messages.next() => {
+                    let message = message_result?;
+                    if let Some(replay) = &mut replay {
+                        if replay.skip(&*self.consumer, &message)? {
+                            continue;
+                        }
+                    }
+                    self.offsets.track(&message);
+                    dispatcher.dispatch(message).await?;
+                    self.apply_backpressure(&mut backpressure);
+                }
+
+                Some(completion) = completions.recv() => {
+                    if let Err(e) = completion.result {
+                        failed = Some(e);
+                        break;
+                    }
+                    self.apply_backpressure(&mut backpressure);
+                }
+
+                _ = commit_timer.tick() => {
+                    if let Err(e) = self.consumer.commit_offsets(&self.offsets.take_commits()) {
+                        error!("Error committing offsets: {}", e);
+                    }
+                    // Also picks up stalls clearing while nothing is being consumed
+                    self.apply_backpressure(&mut backpressure);
+
+                    // Partitions without new records are rewound here
+                    if let Some(replay) = &mut replay {
+                        if let Err(e) = replay.rewind_assigned(&*self.consumer) {
+                            warn!("Failed to rewind partitions for replay: {}", e);
+                        }
+                    }
+                }
+
+                _ = next_tick(&mut timer) => self.processor.tick().await,
+            }
+        }
+
+        // Let in-flight records finish so their offsets are committed, up to
+        // a failed one
+        dispatcher.shutdown().await;
+        while let Some(completion) = completions.recv().await {
+            if let Err(e) = completion.result {
+                failed = failed.or(Some(e));
+            }
+        }
+        if let Err(e) = self.consumer.commit_offsets_sync(&self.offsets.take_commits()) {
+            error!("Error committing offsets: {}", e);
+        }
+        // A failed record is read again by the next run
+        self.offsets.clear();
+
+        info!("{} shutting down...", self.name);
+        failed.map_or(Ok(()), Err)
+    }
+
+    fn apply_backpressure(&self, backpressure: &mut Backpressure) {
+        let write_stalled = self.processor.write_stalled().unwrap_or_else(|e| {
+            warn!("Failed to check stores for write stalls: {}", e);
+            false
+        });
+        if let Err(e) = backpressure.update(&*self.consumer, self.offsets.in_flight(), write_stalled) {
+            error!("Error applying backpressure: {}", e);
+        }
+    }
+}
+
+// Completes records as soon as a lane is done with them, so a rebalance
+// listener draining revoked partitions needn't wait for the consuming loop
+struct Tracked<H> {
+    processor: Arc<H>,
+    offsets: PartitionDrain,
+}
+
+#[async_trait::async_trait]
+impl<H: RecordProcessor> MessageHandler for Tracked<H> {
+    // A failure the processor won't skip leaves the record in flight, so
+    // nothing from it on is committed
+    async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+        if let Err(e) = self.processor.handle(message).await {
+            self.processor.on_failure(e)?;
+        }
+        self.offsets.complete(message);
+        Ok(())
+    }
+}
+
+// Never completes without a timer
+async fn next_tick(timer: &mut Option<Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Longest a readiness probe waits for any one dependency
+pub const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What every service sets up before its own parts: a metrics registry and
+/// the topic serdes, whose schema registry lookups go through a circuit
+/// breaker, and the Kafka clients, servers and heartbeats built on them
+pub struct ServiceBootstrap {
+    config: ServiceConfig,
+    metrics: Metrics,
+    registry_breaker: Arc<CircuitBreaker>,
+    serdes: Arc<TopicSerdes>,
+}
+
+impl ServiceBootstrap {
+    pub async fn new(config: &ServiceConfig) -> Result<Self> {
+        let metrics = Metrics::new()?;
+        let registry_breaker = Arc::new(CircuitBreaker::schema_registry().with_metrics(metrics.clone()));
+        let serdes = Arc::new(TopicSerdes::from_config(config).await?.with_circuit_breaker(registry_breaker.clone()));
+        Ok(Self {
+            config: config.clone(),
+            metrics,
+            registry_breaker,
+            serdes,
+        })
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn registry_breaker(&self) -> &Arc<CircuitBreaker> {
+        &self.registry_breaker
+    }
+
+    pub fn serdes(&self) -> &Arc<TopicSerdes> {
+        &self.serdes
+    }
+
+    /// Consumer decoding records with the topic serdes
+    pub fn consumer(&self) -> Result<KafkaConsumer> {
+        Ok(KafkaConsumer::new(self.config.to_kafka_config())?
+            .with_serdes(self.serdes.clone())
+            .with_topic_resolver(self.config.topic_resolver())
+            .with_metrics(self.metrics.clone()))
+    }
+
+    /// Check passing once the brokers list `topics`, on a consumer of its own
+    pub fn kafka_readiness(&self, topics: &[&'static str]) -> Result<KafkaReadiness> {
+        let metadata_consumer =
+            KafkaConsumer::new(self.config.to_kafka_config())?.with_topic_resolver(self.config.topic_resolver());
+        Ok(KafkaReadiness::new(metadata_consumer, topics))
+    }
+
+    /// Probe reporting ready once the brokers list `topics`
+    pub fn readiness(&self, topics: &[&'static str]) -> Result<ReadinessProbe> {
+        Ok(ReadinessProbe::new(READINESS_TIMEOUT).with_check(self.kafka_readiness(topics)?))
+    }
+
+    /// Producer without the topic serdes, for services that produce nothing
+    /// but heartbeats and admin commands, which are always JSON
+    pub fn plain_producer(&self) -> Result<KafkaProducer> {
+        Ok(KafkaProducer::new(self.config.to_kafka_config())?
+            .with_topic_resolver(self.config.topic_resolver())
+            .with_metrics(self.metrics.clone()))
+    }
+
+    /// Serve `router` and the metrics on `port` until `runner` shuts down
+    pub async fn serve<H: RecordProcessor, C: MessageConsumer>(
+        &self,
+        runner: &mut ConsumerRunner<H, C>,
+        name: &str,
+        port: u16,
+        router: Router,
+    ) -> Result<()> {
+        let server = serve_router_on(port, serve_metrics(router, self.metrics.clone())).await?;
+        runner.add_shutdown_component(HttpServerShutdown::new(name).with_task(server));
+        Ok(())
+    }
+
+    /// Serve the metrics alone on `metrics.port`, if one is set, until
+    /// `runner` shuts down
+    pub async fn serve_metrics_port<H: RecordProcessor, C: MessageConsumer>(
+        &self,
+        runner: &mut ConsumerRunner<H, C>,
+    ) -> Result<()> {
+        if let Some(port) = self.config.metrics_port {
+            let server = serve_metrics_on(port, self.metrics.clone()).await?;
+            runner.add_shutdown_component(HttpServerShutdown::new("metrics-server").with_task(server));
+        }
+        Ok(())
+    }
+
+    /// Publish heartbeats of the service at `version` through `producer`,
+    /// with the lag of `runner`'s consumer; both stop when `runner` does
+    pub fn start_heartbeat<H: RecordProcessor, C: MessageConsumer>(
+        &self,
+        runner: &mut ConsumerRunner<H, C>,
+        producer: KafkaProducer,
+        version: &str,
+    ) {
+        let heartbeat = HeartbeatPublisher::from_config(producer.clone(), &self.config, version)
+            .with_consumer(runner.consumer().clone(), runner.topics());
+        heartbeat.start();
+        runner.add_shutdown_component(heartbeat);
+        runner.add_shutdown_component(KafkaProducerShutdown::new(producer));
+    }
+}
+
+/// Run the consuming service `build` makes of `config` until SIGTERM or
+/// Ctrl+C, restarting it from the committed offsets if it crashes
+///
+/// The records in flight are finished and committed before the components
+/// the runner registered are shut down.
+pub async fn run_service<H, C, F, Fut>(config: ServiceConfig, build: F) -> Result<()>
+where
+    H: RecordProcessor,
+    C: MessageConsumer,
+    F: FnOnce(ServiceConfig) -> Fut,
+    Fut: Future<Output = Result<ConsumerRunner<H, C>>>,
+{
+    let name = config.application_id.clone();
+    let coordinator = ShutdownCoordinator::default();
+    let shutdown = coordinator.subscribe();
+    let restarts_stopped = coordinator.subscribe();
+    coordinator.signal_on_termination();
+
+    let restart_policy = config.supervisor.policy();
+    let mut runner = build(config).await?;
+    runner.register_shutdown(&coordinator).await;
+
+    info!("{} started successfully", name);
+
+    let supervisor = Supervisor::new(&name, restart_policy).with_metrics(runner.metrics().clone());
+    let runner = &runner;
+    let mut first_run = Some(shutdown);
+    supervisor
+        .run(restarts_stopped, |restarts| {
+            let shutdown = first_run.take().unwrap_or_else(|| coordinator.subscribe());
+            async move {
+                if restarts > 0 {
+                    runner.rejoin()?;
+                }
+                runner.run(shutdown).await
+            }
+        })
+        .await?;
+    coordinator.shutdown().await?;
+    info!("{} shut down", name);
+    shutdown_telemetry();
+    Ok(())
+}
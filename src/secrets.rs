@@ -0,0 +1,189 @@
+use crate::{Result, ServiceConfig, TicketMasterError};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Where secrets referenced from the config as `${<scheme>:<reference>}` are read from
+#[async_trait::async_trait]
+pub trait SecretProvider: Send + Sync {
+    /// The scheme of the references this provider reads
+    fn scheme(&self) -> &str;
+
+    async fn get(&self, reference: &str) -> Result<String>;
+}
+
+/// `${env:KAFKA_PASSWORD}` reads an environment variable
+pub struct EnvSecretProvider;
+
+#[async_trait::async_trait]
+impl SecretProvider for EnvSecretProvider {
+    fn scheme(&self) -> &str {
+        "env"
+    }
+
+    async fn get(&self, reference: &str) -> Result<String> {
+        std::env::var(reference)
+            .map_err(|e| TicketMasterError::Secret(format!("Environment variable {}: {}", reference, e)))
+    }
+}
+
+/// `${file:/run/secrets/kafka-password}` reads a file, like `sasl.password.file`
+pub struct FileSecretProvider;
+
+#[async_trait::async_trait]
+impl SecretProvider for FileSecretProvider {
+    fn scheme(&self) -> &str {
+        "file"
+    }
+
+    async fn get(&self, reference: &str) -> Result<String> {
+        read_secret_file(reference)
+    }
+}
+
+/// `${vault:secret/data/kafka#password}` reads the `password` field of a
+/// secret in HashiCorp Vault
+///
+/// The path is read with `GET /v1/<path>`, so KV version 2 paths include
+/// `data/`. Fields are looked up in the secret's data for both KV versions.
+pub struct VaultSecretProvider {
+    http: reqwest::Client,
+    addr: String,
+    token: String,
+}
+
+impl VaultSecretProvider {
+    pub fn new(addr: &str, token: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            addr: addr.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    /// From `VAULT_ADDR` and `VAULT_TOKEN`, if both are set
+    pub fn from_env() -> Option<Self> {
+        let addr = std::env::var("VAULT_ADDR").ok()?;
+        let token = std::env::var("VAULT_TOKEN").ok()?;
+        Some(Self::new(&addr, &token))
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretProvider for VaultSecretProvider {
+    fn scheme(&self) -> &str {
+        "vault"
+    }
+
+    async fn get(&self, reference: &str) -> Result<String> {
+        let (path, field) = reference.split_once('#').ok_or_else(|| {
+            TicketMasterError::Secret(format!("Vault reference {} has no #field", reference))
+        })?;
+        let error = |e: &dyn std::fmt::Display| TicketMasterError::Secret(format!("Vault secret {}: {}", path, e));
+
+        let body = self
+            .http
+            .get(format!("{}/v1/{}", self.addr, path.trim_start_matches('/')))
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| error(&e))?
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| error(&e))?;
+        // KV version 2 nests the secret's data under another `data`
+        let data = &body["data"];
+        let data = if data["data"].is_object() { &data["data"] } else { data };
+        match &data[field] {
+            serde_json::Value::String(value) => Ok(value.clone()),
+            serde_json::Value::Null => Err(error(&format!("no field {}", field))),
+            value => Ok(value.to_string()),
+        }
+    }
+}
+
+/// Resolves secret references in config values through the providers for their schemes
+///
+/// Values that aren't a reference are used as they are.
+#[derive(Clone)]
+pub struct SecretResolver {
+    providers: HashMap<String, Arc<dyn SecretProvider>>,
+}
+
+impl Default for SecretResolver {
+    fn default() -> Self {
+        Self { providers: HashMap::new() }
+            .with_provider(EnvSecretProvider)
+            .with_provider(FileSecretProvider)
+    }
+}
+
+impl SecretResolver {
+    /// Environment and file secrets, and Vault when `VAULT_ADDR` and `VAULT_TOKEN` are set
+    pub fn from_env() -> Self {
+        match VaultSecretProvider::from_env() {
+            Some(vault) => Self::default().with_provider(vault),
+            None => Self::default(),
+        }
+    }
+
+    /// Add a provider, replacing any for the same scheme
+    pub fn with_provider<P: SecretProvider + 'static>(mut self, provider: P) -> Self {
+        self.providers.insert(provider.scheme().to_string(), Arc::new(provider));
+        self
+    }
+
+    pub async fn resolve(&self, value: &str) -> Result<String> {
+        let Some((scheme, reference)) = value
+            .strip_prefix("${")
+            .and_then(|value| value.strip_suffix('}'))
+            .and_then(|reference| reference.split_once(':'))
+        else {
+            return Ok(value.to_string());
+        };
+        let provider = self.providers.get(scheme).ok_or_else(|| {
+            TicketMasterError::Secret(format!("No secret provider for {} in {}", scheme, value))
+        })?;
+        provider.get(reference).await
+    }
+
+    /// Replace references in the config's credentials and in the properties
+    /// passed through to Kafka
+    pub async fn resolve_config(&self, config: &mut ServiceConfig) -> Result<()> {
+        let secrets = [
+            &mut config.kafka.sasl_username,
+            &mut config.kafka.sasl_password,
+            &mut config.kafka.ssl_key_password,
+            &mut config.kafka.schema_registry_basic_auth,
+            &mut config.admin_token,
+            &mut config.artwork.s3_access_key,
+            &mut config.artwork.s3_secret_key,
+            &mut config.export.s3_access_key,
+            &mut config.export.s3_secret_key,
+            &mut config.sink.postgres_url,
+            &mut config.alerts.webhook_url,
+            &mut config.alerts.pagerduty_routing_key,
+            &mut config.notification.smtp_password,
+            &mut config.notification.webhook_url,
+            &mut config.payment.api_key,
+            &mut config.gateway_service.ticket_api_key,
+            &mut config.gateway_service.token_secret,
+        ];
+        for secret in secrets.into_iter().flatten() {
+            *secret = self.resolve(secret).await?;
+        }
+        for value in config.kafka.additional_properties.values_mut() {
+            *value = self.resolve(value).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The contents of a file holding one secret, without a trailing newline
+pub fn read_secret_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let secret = std::fs::read_to_string(path)
+        .map_err(|e| TicketMasterError::Secret(format!("Failed to read secret file {:?}: {}", path, e)))?;
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
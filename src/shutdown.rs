@@ -1,7 +1,8 @@
 use crate::Result;
 use std::sync::Arc;
 use tokio::sync::{broadcast, Mutex};
-use tokio::time::{sleep, Duration};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
 use tracing::{info, warn, error};
 
 /// Graceful shutdown coordinator
@@ -9,7 +10,7 @@ use tracing::{info, warn, error};
 pub struct ShutdownCoordinator {
     shutdown_tx: broadcast::Sender<()>,
     shutdown_rx: Arc<Mutex<broadcast::Receiver<()>>>,
-    components: Arc<Mutex<Vec<Box<dyn ShutdownComponent + Send + Sync>>>>,
+    components: Arc<Mutex<Vec<Arc<dyn ShutdownComponent + Send + Sync>>>>,
     shutdown_timeout: Duration,
 }
 
@@ -40,7 +41,7 @@ impl ShutdownCoordinator {
     pub async fn register_component(&self, component: Box<dyn ShutdownComponent + Send + Sync>) {
         let mut components = self.components.lock().await;
         info!("Registering component '{}' for graceful shutdown", component.name());
-        components.push(component);
+        components.push(Arc::from(component));
     }
 
     /// Get a shutdown signal receiver
@@ -48,20 +49,27 @@ impl ShutdownCoordinator {
         self.shutdown_tx.subscribe()
     }
 
+    /// Tell subscribers to stop without shutting components down yet, e.g.
+    /// so a server drains its requests before the producer is flushed
+    pub fn signal(&self) {
+        if let Err(e) = self.shutdown_tx.send(()) {
+            warn!("Failed to send shutdown signal: {}", e);
+        }
+    }
+
     /// Trigger graceful shutdown
     pub async fn shutdown(&self) -> Result<()> {
         info!("Initiating graceful shutdown...");
         
         // Send shutdown signal to all subscribers
-        if let Err(e) = self.shutdown_tx.send(()) {
-            warn!("Failed to send shutdown signal: {}", e);
-        }
+        self.signal();
 
         // Shutdown all registered components
         let components = self.components.lock().await;
         let mut shutdown_tasks = Vec::new();
 
         for component in components.iter() {
+            let component = component.clone();
             let component_name = component.name().to_string();
             let component_shutdown = async move {
                 info!("Shutting down component '{}'", component_name);
@@ -102,6 +110,15 @@ impl ShutdownCoordinator {
         }
     }
 
+    /// Signal subscribers once the process receives SIGTERM or SIGINT
+    pub fn signal_on_termination(&self) -> JoinHandle<()> {
+        let coordinator = self.clone();
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            coordinator.signal();
+        })
+    }
+
     /// Wait for shutdown signal
     pub async fn wait_for_shutdown(&self) {
         let mut rx = self.shutdown_rx.lock().await;
@@ -134,6 +151,65 @@ impl ShutdownComponent for KafkaProducerShutdown {
     }
 }
 
+/// Kafka consumer shutdown component; leaves the consumer group, so offsets
+/// must already be committed
+pub struct KafkaConsumerShutdown<C = crate::KafkaConsumer> {
+    consumer: Arc<C>,
+    name: String,
+}
+
+impl<C: crate::MessageConsumer> KafkaConsumerShutdown<C> {
+    pub fn new(consumer: Arc<C>) -> Self {
+        Self {
+            consumer,
+            name: "kafka-consumer".to_string(),
+        }
+    }
+
+    /// Name it after the consumer, for services with several
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: crate::MessageConsumer> ShutdownComponent for KafkaConsumerShutdown<C> {
+    async fn shutdown(&self) -> Result<()> {
+        info!("Leaving the consumer group of '{}'", self.name);
+        self.consumer.unsubscribe()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Flushes every RocksDB store of a processing context
+pub struct StateStoresShutdown {
+    context: Arc<crate::ProcessingContext>,
+}
+
+impl StateStoresShutdown {
+    pub fn new(context: Arc<crate::ProcessingContext>) -> Self {
+        Self { context }
+    }
+}
+
+#[async_trait::async_trait]
+impl ShutdownComponent for StateStoresShutdown {
+    async fn shutdown(&self) -> Result<()> {
+        info!("Persisting state stores...");
+        self.context.persist_stores()?;
+        info!("State stores persisted successfully");
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "state-stores"
+    }
+}
+
 /// RocksDB store shutdown component
 pub struct RocksDBShutdown {
     store: Arc<crate::RocksDBStore>,
@@ -160,25 +236,38 @@ impl ShutdownComponent for RocksDBShutdown {
 }
 
 /// HTTP server shutdown component
+///
+/// Stops the server task given `with_task`, e.g. a metrics or state query
+/// server; servers that drain their requests are stopped through `signal`.
 pub struct HttpServerShutdown {
     name: String,
+    task: std::sync::Mutex<Option<JoinHandle<()>>>,
 }
 
 impl HttpServerShutdown {
     pub fn new(name: &str) -> Self {
         Self {
             name: name.to_string(),
+            task: std::sync::Mutex::new(None),
         }
     }
+
+    pub fn with_task(self, task: JoinHandle<()>) -> Self {
+        *self.task.lock().unwrap_or_else(std::sync::PoisonError::into_inner) = Some(task);
+        self
+    }
 }
 
 #[async_trait::async_trait]
 impl ShutdownComponent for HttpServerShutdown {
     async fn shutdown(&self) -> Result<()> {
         info!("Shutting down HTTP server '{}'", self.name);
-        // In a real implementation, you'd gracefully shutdown the HTTP server
-        // For now, we'll just simulate the shutdown
-        sleep(Duration::from_millis(100)).await;
+        let task = self.task.lock().unwrap_or_else(std::sync::PoisonError::into_inner).take();
+        if let Some(task) = task {
+            task.abort();
+            // Cancelled is how an aborted server ends
+            let _ = task.await;
+        }
         info!("HTTP server '{}' shutdown successfully", self.name);
         Ok(())
     }
@@ -188,42 +277,47 @@ impl ShutdownComponent for HttpServerShutdown {
     }
 }
 
-/// Signal handler for graceful shutdown
-pub async fn setup_signal_handlers(coordinator: ShutdownCoordinator) {
-    tokio::spawn(async move {
-        #[cfg(unix)]
-        {
-            use tokio::signal::unix::{signal, SignalKind};
-            
-            let mut sigterm = signal(SignalKind::terminate()).expect("Failed to setup SIGTERM handler");
-            let mut sigint = signal(SignalKind::interrupt()).expect("Failed to setup SIGINT handler");
-            
-            tokio::select! {
-                _ = sigterm.recv() => {
-                    info!("Received SIGTERM, initiating graceful shutdown");
-                }
-                _ = sigint.recv() => {
-                    info!("Received SIGINT (Ctrl+C), initiating graceful shutdown");
-                }
+/// Wait for SIGTERM or SIGINT (Ctrl+C / Ctrl+Break on Windows)
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        
+        let mut sigterm = signal(SignalKind::terminate()).expect("Failed to setup SIGTERM handler");
+        let mut sigint = signal(SignalKind::interrupt()).expect("Failed to setup SIGINT handler");
+        
+        tokio::select! {
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM, initiating graceful shutdown");
+            }
+            _ = sigint.recv() => {
+                info!("Received SIGINT (Ctrl+C), initiating graceful shutdown");
             }
         }
+    }
+    
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows::{ctrl_c, ctrl_break};
         
-        #[cfg(windows)]
-        {
-            use tokio::signal::windows::{ctrl_c, ctrl_break};
-            
-            let mut ctrl_c = ctrl_c().expect("Failed to setup Ctrl+C handler");
-            let mut ctrl_break = ctrl_break().expect("Failed to setup Ctrl+Break handler");
-            
-            tokio::select! {
-                _ = ctrl_c.recv() => {
-                    info!("Received Ctrl+C, initiating graceful shutdown");
-                }
-                _ = ctrl_break.recv() => {
-                    info!("Received Ctrl+Break, initiating graceful shutdown");
-                }
+        let mut ctrl_c = ctrl_c().expect("Failed to setup Ctrl+C handler");
+        let mut ctrl_break = ctrl_break().expect("Failed to setup Ctrl+Break handler");
+        
+        tokio::select! {
+            _ = ctrl_c.recv() => {
+                info!("Received Ctrl+C, initiating graceful shutdown");
+            }
+            _ = ctrl_break.recv() => {
+                info!("Received Ctrl+Break, initiating graceful shutdown");
             }
         }
+    }
+}
+
+/// Signal handler for graceful shutdown
+pub async fn setup_signal_handlers(coordinator: ShutdownCoordinator) {
+    tokio::spawn(async move {
+        wait_for_signal().await;
         
         if let Err(e) = coordinator.shutdown().await {
             error!("Graceful shutdown failed: {}", e);
@@ -0,0 +1,91 @@
+use crate::{Result, TicketMasterError};
+
+/// Columns of the reservations table, in the order the upsert binds them
+pub const RESERVATION_COLUMNS: [&str; 10] = [
+    "reservation_id",
+    "user_id",
+    "event_id",
+    "area_id",
+    "state",
+    "reservation_type",
+    "num_of_seats",
+    "seats",
+    "failed_reason",
+    "kafka_offset",
+];
+
+/// The PostgreSQL table reservations are mirrored into, and the SQL writing it
+///
+/// Rows remember the offset of the record they were written from, so a
+/// redelivered older record never overwrites a newer one.
+#[derive(Debug, Clone)]
+pub struct ReservationTable {
+    name: String,
+}
+
+impl ReservationTable {
+    /// `name` may be schema-qualified, e.g. `analytics.reservations`
+    pub fn new(name: &str) -> Result<Self> {
+        let valid = |part: &str| {
+            part.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        let parts: Vec<&str> = name.split('.').collect();
+        if parts.len() > 2 || !parts.iter().all(|part| valid(part)) {
+            return Err(TicketMasterError::InvalidArgument(format!("Invalid table name: {:?}", name)));
+        }
+        Ok(Self { name: name.to_ascii_lowercase() })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Statements creating the table and its indexes unless they exist
+    pub fn create_statements(&self) -> Vec<String> {
+        // Indexes live in the table's schema and are named after the table alone
+        let base = self.name.rsplit('.').next().unwrap_or(&self.name);
+        vec![
+            format!(
+                "CREATE TABLE IF NOT EXISTS {} (\
+                 reservation_id TEXT PRIMARY KEY, \
+                 user_id TEXT NOT NULL, \
+                 event_id TEXT NOT NULL, \
+                 area_id TEXT NOT NULL, \
+                 state TEXT NOT NULL, \
+                 reservation_type TEXT NOT NULL, \
+                 num_of_seats INTEGER NOT NULL, \
+                 seats JSONB NOT NULL, \
+                 failed_reason TEXT, \
+                 kafka_offset BIGINT NOT NULL, \
+                 updated_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+                self.name
+            ),
+            format!("CREATE INDEX IF NOT EXISTS {}_user_id_idx ON {} (user_id)", base, self.name),
+            format!("CREATE INDEX IF NOT EXISTS {}_event_id_idx ON {} (event_id, area_id)", base, self.name),
+        ]
+    }
+
+    /// Insert or update one reservation, bound as `RESERVATION_COLUMNS`
+    pub fn upsert_statement(&self) -> String {
+        let placeholders: Vec<String> = (1..=RESERVATION_COLUMNS.len()).map(|n| format!("${}", n)).collect();
+        let updates: Vec<String> = RESERVATION_COLUMNS[1..]
+            .iter()
+            .map(|column| format!("{} = EXCLUDED.{}", column, column))
+            .collect();
+        format!(
+            "INSERT INTO {} AS mirrored ({}) VALUES ({}) \
+             ON CONFLICT (reservation_id) DO UPDATE SET {}, updated_at = now() \
+             WHERE mirrored.kafka_offset <= EXCLUDED.kafka_offset",
+            self.name,
+            RESERVATION_COLUMNS.join(", "),
+            placeholders.join(", "),
+            updates.join(", ")
+        )
+    }
+
+    /// Delete one reservation, bound as its id and the offset of its tombstone
+    pub fn delete_statement(&self) -> String {
+        format!("DELETE FROM {} WHERE reservation_id = $1 AND kafka_offset <= $2", self.name)
+    }
+}
@@ -0,0 +1,410 @@
+use crate::{
+    Result, TicketMasterError, CompactAreaStatus, ReserveSeat, ReservationResult,
+    ReservationResultEnum, ReservationErrorCode, Seat, ReservationType, SeatPreference,
+    CheckAvailability, Availability
+};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const SELF_PICK_STRATEGY: &str = "self_pick";
+pub const RANDOM_STRATEGY: &str = "random";
+pub const CONTINUOUS_RANDOM_STRATEGY: &str = "continuous_random";
+
+/// Carries an area's strategy on its internal area status message, as area
+/// statuses themselves don't hold one
+pub const AREA_STRATEGY_HEADER: &str = "area-strategy";
+
+// Scoring weights; penalties are taken off an ideal score of 0
+const ROW_PENALTY: f64 = 4.0;
+const GAP_PENALTY: f64 = 1.0;
+const CENTER_PENALTY: f64 = 2.0;
+const FRONT_PENALTY: f64 = 3.0;
+const AISLE_BONUS: f64 = 3.0;
+// How much more extra rows and gaps count for parties wanting to sit together
+const TOGETHER_FACTOR: f64 = 4.0;
+// Random draws the random strategy scores before picking one
+const RANDOM_DRAWS: usize = 8;
+
+/// How good `seats` in `area_status` are for a party with `preferences`; higher is better
+///
+/// Seats in a single row, without gaps between them and close to the center
+/// column score best. `Front` also favours low rows, `Aisle` a seat at either
+/// end of a row, and `Together` makes extra rows and gaps cost more.
+pub fn score_seats(area_status: &CompactAreaStatus, seats: &[Seat], preferences: &[SeatPreference]) -> f64 {
+    if seats.is_empty() {
+        return 0.0;
+    }
+    let row_count = area_status.seats.row_count();
+    let col_count = area_status.seats.col_count();
+    let together = if preferences.contains(&SeatPreference::Together) { TOGETHER_FACTOR } else { 1.0 };
+
+    // First col, last col and seats taken of every row used
+    let mut rows: BTreeMap<i32, (i32, i32, i32)> = BTreeMap::new();
+    for seat in seats {
+        let span = rows.entry(seat.row).or_insert((seat.col, seat.col, 0));
+        span.0 = span.0.min(seat.col);
+        span.1 = span.1.max(seat.col);
+        span.2 += 1;
+    }
+    let gaps: i32 = rows.values().map(|(first, last, taken)| last - first + 1 - taken).sum();
+    let mut score = -((rows.len() - 1) as f64 * ROW_PENALTY + gaps as f64 * GAP_PENALTY) * together;
+
+    let center = (col_count - 1).max(0) as f64 / 2.0;
+    let off_center = seats.iter().map(|seat| (seat.col as f64 - center).abs()).sum::<f64>() / seats.len() as f64;
+    score -= off_center / center.max(1.0) * CENTER_PENALTY;
+
+    if preferences.contains(&SeatPreference::Front) {
+        let depth = seats.iter().map(|seat| seat.row as f64).sum::<f64>() / seats.len() as f64;
+        score -= depth / (row_count - 1).max(1) as f64 * FRONT_PENALTY;
+    }
+    if preferences.contains(&SeatPreference::Aisle)
+        && seats.iter().any(|seat| seat.col == 0 || seat.col == col_count - 1)
+    {
+        score += AISLE_BONUS;
+    }
+    score
+}
+
+// `len` adjacent seats worth scoring from every long enough stretch of
+// available seats: both of its ends and the spot closest to the center
+fn run_candidates(area_status: &CompactAreaStatus, len: i32) -> Vec<Vec<Seat>> {
+    if len <= 0 {
+        return Vec::new();
+    }
+    let centered = (area_status.seats.col_count() - len) / 2;
+    let mut candidates = Vec::new();
+    for (row, first, end) in area_status.seats.available_runs() {
+        if end - first < len {
+            continue;
+        }
+        let mut starts = vec![first, centered.clamp(first, end - len), end - len];
+        starts.dedup();
+        for start in starts {
+            candidates.push((start..start + len).map(|col| Seat { row, col }).collect());
+        }
+    }
+    candidates
+}
+
+// The best scored of `candidates`, shuffled first so ties go to a random one
+fn best_candidate(
+    mut candidates: Vec<Vec<Seat>>,
+    area_status: &CompactAreaStatus,
+    preferences: &[SeatPreference],
+) -> Option<Vec<Seat>> {
+    candidates.shuffle(&mut rand::thread_rng());
+    candidates
+        .into_iter()
+        .map(|seats| (score_seats(area_status, &seats, preferences), seats))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, seats)| seats)
+}
+
+pub trait ReservationStrategy: Send + Sync {
+    fn reserve(&self, area_status: &mut CompactAreaStatus, request: &ReserveSeat) -> Result<ReservationResult>;
+
+    /// One line on how seats are chosen, shown when strategies are listed
+    fn description(&self) -> &str;
+}
+
+pub struct SelfPickStrategy;
+
+impl ReservationStrategy for SelfPickStrategy {
+    fn reserve(&self, area_status: &mut CompactAreaStatus, request: &ReserveSeat) -> Result<ReservationResult> {
+        let mut result = ReservationResult {
+            reservation_id: request.reservation_id.clone(),
+            result: ReservationResultEnum::Failed,
+            error_code: None,
+            error_message: None,
+            seats: Vec::new(),
+        };
+
+        // Validate requested seats
+        for seat in &request.seats {
+            // Check bounds
+            if !area_status.seats.contains(seat.row, seat.col) {
+                result.error_code = Some(ReservationErrorCode::InvalidArgument);
+                result.error_message = Some(format!("Seat out of bounds: row {}, col {}", seat.row, seat.col));
+                return Ok(result);
+            }
+
+            // Check availability
+            if !area_status.seats.is_available(seat.row, seat.col) {
+                result.error_code = Some(ReservationErrorCode::SeatNotAvailable);
+                result.error_message = Some(format!("Seat not available: row {}, col {}", seat.row, seat.col));
+                return Ok(result);
+            }
+        }
+
+        // All seats are available, reserve them
+        result.result = ReservationResultEnum::Success;
+        result.seats = request.seats.clone();
+        
+        Ok(result)
+    }
+
+    fn description(&self) -> &str {
+        "The seats named in the request"
+    }
+}
+
+pub struct RandomStrategy;
+
+impl ReservationStrategy for RandomStrategy {
+    fn reserve(&self, area_status: &mut CompactAreaStatus, request: &ReserveSeat) -> Result<ReservationResult> {
+        let mut result = ReservationResult {
+            reservation_id: request.reservation_id.clone(),
+            result: ReservationResultEnum::Failed,
+            error_code: None,
+            error_message: None,
+            seats: Vec::new(),
+        };
+
+        let num_seats_requested = request.num_of_seats;
+        let available_seats = area_status.available_seats();
+        
+        // Check if enough seats are available
+        if available_seats < num_seats_requested {
+            result.error_code = Some(ReservationErrorCode::InsufficientSeats);
+            result.error_message = Some(format!(
+                "Not enough seats available. Requested: {}, Available: {}", 
+                num_seats_requested, available_seats
+            ));
+            return Ok(result);
+        }
+
+        // Draw a few random picks among the available seats and keep the best
+        // scored, adding adjacent ones when the party wants to sit together
+        let available_seats: Vec<Seat> = area_status.seats.available().collect();
+        let mut rng = rand::thread_rng();
+        let mut candidates: Vec<Vec<Seat>> = (0..RANDOM_DRAWS)
+            .map(|_| {
+                available_seats
+                    .choose_multiple(&mut rng, num_seats_requested.max(0) as usize)
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        if request.preferences.contains(&SeatPreference::Together) {
+            candidates.extend(run_candidates(area_status, num_seats_requested));
+        }
+        let selected_seats = best_candidate(candidates, area_status, &request.preferences).unwrap_or_default();
+
+        if selected_seats.len() == num_seats_requested as usize {
+            result.result = ReservationResultEnum::Success;
+            result.seats = selected_seats;
+        } else {
+            result.error_code = Some(ReservationErrorCode::InsufficientSeats);
+            result.error_message = Some("Could not allocate enough seats".to_string());
+        }
+
+        Ok(result)
+    }
+
+    fn description(&self) -> &str {
+        "The best scored of a few random picks among the available seats"
+    }
+}
+
+// Continuous random strategy that tries to find adjacent seats
+pub struct ContinuousRandomStrategy;
+
+impl ReservationStrategy for ContinuousRandomStrategy {
+    fn reserve(&self, area_status: &mut CompactAreaStatus, request: &ReserveSeat) -> Result<ReservationResult> {
+        let mut result = ReservationResult {
+            reservation_id: request.reservation_id.clone(),
+            result: ReservationResultEnum::Failed,
+            error_code: None,
+            error_message: None,
+            seats: Vec::new(),
+        };
+
+        let num_seats_requested = request.num_of_seats;
+        let available_seats = area_status.available_seats();
+        
+        // Check if enough seats are available
+        if available_seats < num_seats_requested {
+            result.error_code = Some(ReservationErrorCode::InsufficientSeats);
+            result.error_message = Some(format!(
+                "Not enough seats available. Requested: {}, Available: {}", 
+                num_seats_requested, available_seats
+            ));
+            return Ok(result);
+        }
+
+        // Try to find the best scored continuous seats in a row
+        let candidates = run_candidates(area_status, num_seats_requested);
+        if let Some(continuous_seats) = best_candidate(candidates, area_status, &request.preferences) {
+            result.result = ReservationResultEnum::Success;
+            result.seats = continuous_seats;
+            return Ok(result);
+        }
+
+        // If no continuous seats found, fall back to random selection
+        let random_strategy = RandomStrategy;
+        random_strategy.reserve(area_status, request)
+    }
+
+    fn description(&self) -> &str {
+        "The best scored adjacent seats in one row, or random seats when no row has enough"
+    }
+}
+
+/// A reservation strategy as listed by the API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StrategyInfo {
+    pub name: String,
+    pub description: String,
+}
+
+/// Reservation strategies by name
+///
+/// Requests naming their seats use `self_pick` and continuous random ones
+/// `continuous_random`. Random requests use the strategy set on their area,
+/// or `random` when the area has none. Both defaults can be changed with
+/// `set_default`.
+pub struct StrategyRegistry {
+    strategies: BTreeMap<String, Box<dyn ReservationStrategy>>,
+    random_default: String,
+    continuous_random_default: String,
+}
+
+impl StrategyRegistry {
+    /// A registry without any strategies
+    pub fn new() -> Self {
+        Self {
+            strategies: BTreeMap::new(),
+            random_default: RANDOM_STRATEGY.to_string(),
+            continuous_random_default: CONTINUOUS_RANDOM_STRATEGY.to_string(),
+        }
+    }
+
+    /// Add `strategy` under `name`, replacing any registered under it before
+    pub fn register(&mut self, name: &str, strategy: impl ReservationStrategy + 'static) -> &mut Self {
+        self.strategies.insert(name.to_string(), Box::new(strategy));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn ReservationStrategy> {
+        self.strategies.get(name).map(|strategy| strategy.as_ref())
+    }
+
+    /// Registered strategies, sorted by name
+    pub fn list(&self) -> Vec<StrategyInfo> {
+        self.strategies
+            .iter()
+            .map(|(name, strategy)| StrategyInfo {
+                name: name.clone(),
+                description: strategy.description().to_string(),
+            })
+            .collect()
+    }
+
+    /// Use the strategy registered as `name` for `reservation_type` requests
+    /// in areas without a strategy of their own
+    pub fn set_default(&mut self, reservation_type: &ReservationType, name: &str) -> Result<&mut Self> {
+        self.check_area_strategy(name)?;
+        match reservation_type {
+            ReservationType::Random => self.random_default = name.to_string(),
+            ReservationType::ContinuousRandom => self.continuous_random_default = name.to_string(),
+            ReservationType::SelfPick | ReservationType::Invalid => {
+                return Err(TicketMasterError::InvalidReservationStrategy(format!(
+                    "{:?} reservations have no default strategy",
+                    reservation_type
+                )))
+            }
+        }
+        Ok(self)
+    }
+
+    /// Check `name` can be an area's strategy; `self_pick` can't, as it
+    /// needs the request to name its seats
+    pub fn check_area_strategy(&self, name: &str) -> Result<()> {
+        if name == SELF_PICK_STRATEGY || !self.strategies.contains_key(name) {
+            let names: Vec<&str> = self
+                .strategies
+                .keys()
+                .map(String::as_str)
+                .filter(|name| *name != SELF_PICK_STRATEGY)
+                .collect();
+            return Err(TicketMasterError::InvalidReservationStrategy(format!(
+                "{} is not an area strategy; use one of {}",
+                name,
+                names.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// The strategy for `request` in an area with `area_strategy` set
+    pub fn select(&self, area_strategy: Option<&str>, request: &ReserveSeat) -> Result<&dyn ReservationStrategy> {
+        let name = match request.reservation_type {
+            ReservationType::SelfPick => SELF_PICK_STRATEGY,
+            ReservationType::Random => match area_strategy {
+                Some(strategy) => {
+                    self.check_area_strategy(strategy)?;
+                    strategy
+                }
+                None => &self.random_default,
+            },
+            ReservationType::ContinuousRandom => &self.continuous_random_default,
+            ReservationType::Invalid => {
+                return Err(TicketMasterError::InvalidReservationStrategy(format!("{:?}", request.reservation_type)))
+            }
+        };
+        self.get(name)
+            .ok_or_else(|| TicketMasterError::InvalidReservationStrategy(name.to_string()))
+    }
+
+    /// Run the strategy `request` would get on a copy of `area_status`, which is left as it is
+    pub fn check_availability(
+        &self,
+        area_strategy: Option<&str>,
+        area_status: &CompactAreaStatus,
+        request: &CheckAvailability,
+    ) -> Result<Availability> {
+        if matches!(request.reservation_type, ReservationType::SelfPick) {
+            return Err(TicketMasterError::InvalidArgument(
+                "Availability is checked for random and continuous random reservations".to_string(),
+            ));
+        }
+        let reserve_request = request.to_reserve_seat();
+        let strategy = self.select(area_strategy, &reserve_request)?;
+        let result = strategy.reserve(&mut area_status.clone(), &reserve_request)?;
+        Ok(Availability {
+            event_id: request.event_id.clone(),
+            area_id: request.area_id.clone(),
+            num_of_seats: request.num_of_seats,
+            reservation_type: request.reservation_type.clone(),
+            available: result.result == ReservationResultEnum::Success,
+            adjacent: is_adjacent(&result.seats),
+            seats: result.seats,
+            available_seats: area_status.available_seats(),
+            error_code: result.error_code,
+            error_message: result.error_message,
+        })
+    }
+}
+
+// Whether `seats` form one run in a single row
+fn is_adjacent(seats: &[Seat]) -> bool {
+    let Some(first) = seats.first() else {
+        return false;
+    };
+    let mut cols: Vec<i32> = seats.iter().map(|seat| seat.col).collect();
+    cols.sort_unstable();
+    seats.iter().all(|seat| seat.row == first.row) && cols.windows(2).all(|pair| pair[1] == pair[0] + 1)
+}
+
+impl Default for StrategyRegistry {
+    /// The built-in strategies
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register(SELF_PICK_STRATEGY, SelfPickStrategy)
+            .register(RANDOM_STRATEGY, RandomStrategy)
+            .register(CONTINUOUS_RANDOM_STRATEGY, ContinuousRandomStrategy);
+        registry
+    }
+}
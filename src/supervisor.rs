@@ -0,0 +1,113 @@
+use crate::{ErrorContext, Metrics, Result, TicketMasterError};
+use futures::FutureExt;
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+/// How a supervised loop is restarted after it panics or fails
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Restarts allowed in a row before giving up
+    pub max_restarts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// A run lasting this long no longer counts towards a crash loop
+    pub stable_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            stable_after: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Runs a processing loop again when it panics or returns an error
+///
+/// Restarts back off exponentially. The loop only stays down once it has
+/// crashed more than `max_restarts` times without a stable run in between.
+pub struct Supervisor {
+    name: String,
+    policy: RestartPolicy,
+    metrics: Option<Metrics>,
+}
+
+impl Supervisor {
+    pub fn new(name: &str, policy: RestartPolicy) -> Self {
+        Self {
+            name: name.to_string(),
+            policy,
+            metrics: None,
+        }
+    }
+
+    /// Count restarts in `metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Run `run` until it returns `Ok`, restarting it as the policy allows;
+    /// it is passed how often it was restarted so far
+    ///
+    /// A restart still backing off is called off by `shutdown`. Giving up
+    /// returns the last error, or `Panicked`, with the crash count as context.
+    pub async fn run<F, Fut>(&self, mut shutdown: broadcast::Receiver<()>, mut run: F) -> Result<()>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut restarts = 0;
+        let mut crashes = 0;
+        let mut backoff = self.policy.initial_backoff;
+        loop {
+            let started = Instant::now();
+            let (reason, e) = match AssertUnwindSafe(run(restarts)).catch_unwind().await {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(e)) => ("error", e),
+                Err(panic) => ("panic", TicketMasterError::Panicked(panic_message(panic.as_ref()))),
+            };
+            if started.elapsed() >= self.policy.stable_after {
+                crashes = 0;
+                backoff = self.policy.initial_backoff;
+            }
+            crashes += 1;
+
+            if crashes > self.policy.max_restarts {
+                error!("{} crashed {} times in a row, giving up: {}", self.name, crashes, e);
+                return Err(e.with_context(ErrorContext::new("supervise", &self.name).with_metadata("crashes", crashes)));
+            }
+            if let Some(metrics) = &self.metrics {
+                metrics.record_restart(&self.name, reason);
+            }
+            warn!("{} stopped on {} ({}), restarting in {:?}", self.name, reason, e, backoff);
+
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = shutdown.recv() => {
+                    info!("Shutting down instead of restarting {}", self.name);
+                    return Ok(());
+                }
+            }
+            backoff = (backoff * 2).min(self.policy.max_backoff);
+            restarts += 1;
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
@@ -0,0 +1,103 @@
+use crate::Result;
+use tracing::{Span, Subscriber};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Header carrying the W3C trace context of the span a message was sent
+/// from, so its processing joins the same distributed trace
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+/// Vendor trace state that travels with `TRACEPARENT_HEADER`
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+/// Layer exporting spans over OTLP/HTTP to `OTEL_EXPORTER_OTLP_ENDPOINT`;
+/// none when the variable isn't set or the `otel` feature is off
+///
+/// Spans are exported from a thread of their own, so this works before the
+/// service's runtime is started.
+#[cfg(feature = "otel")]
+pub(crate) fn otel_layer<S>(service_name: &str) -> Result<Option<impl Layer<S>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::{global, KeyValue};
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        return Ok(None);
+    }
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_protocol(opentelemetry_otlp::Protocol::HttpBinary)
+        .build()
+        .map_err(|e| crate::TicketMasterError::InvalidArgument(format!("Failed to create the OTLP exporter: {}", e)))?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::TokioCurrentThread)
+        .with_resource(Resource::new([KeyValue::new("service.name", service_name.to_string())]))
+        .build();
+    let tracer = provider.tracer(service_name.to_string());
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider);
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
+
+#[cfg(not(feature = "otel"))]
+pub(crate) fn otel_layer<S>(_service_name: &str) -> Result<Option<impl Layer<S>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    Ok(None::<tracing_subscriber::layer::Identity>)
+}
+
+/// Trace context headers to send with a message or request made in `span`;
+/// none without the `otel` feature
+#[cfg(feature = "otel")]
+pub fn trace_context_headers(span: &Span) -> Vec<(String, String)> {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let mut carrier = std::collections::HashMap::new();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&span.context(), &mut carrier)
+    });
+    carrier.into_iter().collect()
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn trace_context_headers(_span: &Span) -> Vec<(String, String)> {
+    Vec::new()
+}
+
+/// Make `span` a child of the trace context in `headers`, if they carry one
+#[cfg(feature = "otel")]
+pub fn continue_trace<'a, I>(span: &Span, headers: I)
+where
+    I: IntoIterator<Item = (&'a str, &'a [u8])>,
+{
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let carrier: std::collections::HashMap<String, String> = headers
+        .into_iter()
+        .filter(|(name, _)| *name == TRACEPARENT_HEADER || *name == TRACESTATE_HEADER)
+        .filter_map(|(name, value)| Some((name.to_string(), std::str::from_utf8(value).ok()?.to_string())))
+        .collect();
+    if carrier.is_empty() {
+        return;
+    }
+    let context = opentelemetry::global::get_text_map_propagator(|propagator| propagator.extract(&carrier));
+    span.set_parent(context);
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn continue_trace<'a, I>(_span: &Span, _headers: I)
+where
+    I: IntoIterator<Item = (&'a str, &'a [u8])>,
+{
+}
+
+/// Export the spans still buffered; call before the process exits
+pub fn shutdown_telemetry() {
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
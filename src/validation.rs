@@ -0,0 +1,243 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::{
+    Area, CreateEvent, FieldError, ReservationPriority, Result, Seat, SeatPreference, StrategyRegistry,
+    TicketMasterError,
+};
+
+/// Body of `POST /events`; times are RFC 3339 or epoch milliseconds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateEventRequest {
+    pub artist: String,
+    pub event_name: String,
+    pub reservation_opening_time: String,
+    pub reservation_closing_time: String,
+    pub event_start_time: String,
+    pub event_end_time: String,
+    pub areas: Vec<Area>,
+}
+
+impl CreateEventRequest {
+    /// The command creating the event; fails if a time can't be parsed
+    pub fn into_create_event(self) -> Result<CreateEvent> {
+        Ok(CreateEvent {
+            reservation_opening_time: parse_timestamp(&self.reservation_opening_time)?,
+            reservation_closing_time: parse_timestamp(&self.reservation_closing_time)?,
+            event_start_time: parse_timestamp(&self.event_start_time)?,
+            event_end_time: parse_timestamp(&self.event_end_time)?,
+            artist: self.artist,
+            event_name: self.event_name,
+            areas: self.areas,
+        })
+    }
+}
+
+/// Body of `POST /reservations`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateReservationRequest {
+    pub user_id: String,
+    pub event_id: String,
+    pub area_id: String,
+    pub num_of_seats: i32,
+    pub reservation_type: String,
+    pub seats: Option<Vec<Seat>>,
+    /// Any of "aisle", "front" or "together"; random reservations only
+    #[serde(default)]
+    pub preferences: Vec<String>,
+    /// "general" (default) or "priority", for presale and VIP requests
+    pub priority: Option<String>,
+}
+
+/// Body of `POST /reservations/:reservation_id/payment`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PayReservationRequest {
+    /// Token of the card or other method to charge, from the provider's client SDK
+    pub payment_method: String,
+}
+
+/// Requests checked before they are queued
+pub trait Validate {
+    /// Every problem found; empty when the request is valid
+    fn validate(&self) -> Vec<FieldError>;
+}
+
+pub fn parse_timestamp(timestamp_str: &str) -> Result<DateTime<Utc>> {
+    // Try parsing as ISO 8601 format first
+    if let Ok(dt) = DateTime::parse_from_rfc3339(timestamp_str) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    // Try parsing as timestamp millis
+    if let Ok(millis) = timestamp_str.parse::<i64>() {
+        if let Some(dt) = DateTime::from_timestamp_millis(millis) {
+            return Ok(dt);
+        }
+    }
+
+    Err(TicketMasterError::InvalidArgument(format!("Invalid timestamp format: {}", timestamp_str)))
+}
+
+fn require_non_empty(errors: &mut Vec<FieldError>, field: &str, value: &str) {
+    if value.trim().is_empty() {
+        errors.push(FieldError::new(field, "must not be empty"));
+    }
+}
+
+// Event and area ids are joined with `#` into the keys areas are stored and
+// partitioned by, so an id containing one would be split wrongly
+fn require_key_part(errors: &mut Vec<FieldError>, field: &str, value: &str) {
+    require_non_empty(errors, field, value);
+    if value.contains('#') {
+        errors.push(FieldError::new(field, "must not contain #"));
+    }
+}
+
+fn check_event(errors: &mut Vec<FieldError>, artist: &str, event_name: &str, areas: &[Area]) {
+    require_non_empty(errors, "artist", artist);
+    require_key_part(errors, "event_name", event_name);
+
+    if areas.is_empty() {
+        errors.push(FieldError::new("areas", "must contain at least one area"));
+    }
+    let mut area_ids = HashSet::new();
+    let strategies = StrategyRegistry::default();
+    for (i, area) in areas.iter().enumerate() {
+        let field = |name: &str| format!("areas[{}].{}", i, name);
+        require_key_part(errors, &field("area_id"), &area.area_id);
+        if !area_ids.insert(area.area_id.as_str()) {
+            errors.push(FieldError::new(field("area_id"), format!("duplicate area {}", area.area_id)));
+        }
+        if area.price < 0 {
+            errors.push(FieldError::new(field("price"), "must not be negative"));
+        }
+        if area.row_count <= 0 {
+            errors.push(FieldError::new(field("row_count"), "must be positive"));
+        }
+        if area.col_count <= 0 {
+            errors.push(FieldError::new(field("col_count"), "must be positive"));
+        }
+        if let Some(Err(e)) = area.strategy.as_deref().map(|name| strategies.check_area_strategy(name)) {
+            errors.push(FieldError::new(field("strategy"), e.to_string()));
+        }
+    }
+}
+
+// Opening < closing <= start < end
+fn check_times(
+    errors: &mut Vec<FieldError>,
+    opening: DateTime<Utc>,
+    closing: DateTime<Utc>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) {
+    if closing <= opening {
+        errors.push(FieldError::new("reservation_closing_time", "must be after reservation_opening_time"));
+    }
+    if start < closing {
+        errors.push(FieldError::new("event_start_time", "must not be before reservation_closing_time"));
+    }
+    if end <= start {
+        errors.push(FieldError::new("event_end_time", "must be after event_start_time"));
+    }
+}
+
+impl Validate for CreateEventRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_event(&mut errors, &self.artist, &self.event_name, &self.areas);
+
+        // Ordering is only checked between valid times
+        let times = [
+            ("reservation_opening_time", &self.reservation_opening_time),
+            ("reservation_closing_time", &self.reservation_closing_time),
+            ("event_start_time", &self.event_start_time),
+            ("event_end_time", &self.event_end_time),
+        ]
+        .map(|(field, value)| {
+            let parsed = parse_timestamp(value).ok();
+            if parsed.is_none() {
+                errors.push(FieldError::new(field, "must be RFC 3339 or epoch milliseconds"));
+            }
+            parsed
+        });
+        if let [Some(opening), Some(closing), Some(start), Some(end)] = times {
+            check_times(&mut errors, opening, closing, start, end);
+        }
+        errors
+    }
+}
+
+/// The checks of `POST /events`, for commands built without it
+impl Validate for CreateEvent {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_event(&mut errors, &self.artist, &self.event_name, &self.areas);
+        check_times(
+            &mut errors,
+            self.reservation_opening_time,
+            self.reservation_closing_time,
+            self.event_start_time,
+            self.event_end_time,
+        );
+        errors
+    }
+}
+
+impl Validate for CreateReservationRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        require_non_empty(&mut errors, "user_id", &self.user_id);
+        require_key_part(&mut errors, "event_id", &self.event_id);
+        require_key_part(&mut errors, "area_id", &self.area_id);
+        if self.num_of_seats <= 0 {
+            errors.push(FieldError::new("num_of_seats", "must be positive"));
+        }
+
+        let self_pick = match self.reservation_type.to_lowercase().as_str() {
+            "self_pick" | "selfpick" => true,
+            "random" | "continuous_random" | "continuousrandom" => false,
+            _ => {
+                errors.push(FieldError::new("reservation_type", "must be self_pick, random or continuous_random"));
+                false
+            }
+        };
+
+        match &self.seats {
+            Some(seats) => {
+                if seats.len() != self.num_of_seats.max(0) as usize {
+                    errors.push(FieldError::new("seats", format!("must list exactly {} seats", self.num_of_seats)));
+                }
+                for (i, seat) in seats.iter().enumerate() {
+                    if seat.row < 0 {
+                        errors.push(FieldError::new(format!("seats[{}].row", i), "must not be negative"));
+                    }
+                    if seat.col < 0 {
+                        errors.push(FieldError::new(format!("seats[{}].col", i), "must not be negative"));
+                    }
+                }
+            }
+            None if self_pick => errors.push(FieldError::new("seats", "is required for self_pick reservations")),
+            None => {}
+        }
+
+        for (i, preference) in self.preferences.iter().enumerate() {
+            if SeatPreference::parse(preference).is_none() {
+                errors.push(FieldError::new(format!("preferences[{}]", i), "must be aisle, front or together"));
+            }
+        }
+        if self.priority.as_deref().is_some_and(|priority| ReservationPriority::parse(priority).is_none()) {
+            errors.push(FieldError::new("priority", "must be general or priority"));
+        }
+        errors
+    }
+}
+
+impl Validate for PayReservationRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        require_non_empty(&mut errors, "payment_method", &self.payment_method);
+        errors
+    }
+}
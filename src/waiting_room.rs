@@ -0,0 +1,172 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// Tokens not used within this long of joining are dropped
+const TOKEN_TTL: Duration = Duration::from_secs(30 * 60);
+// Expired tokens are swept after this many joins
+const PURGE_EVERY: u64 = 1024;
+
+/// A place in an event's queue, returned on joining
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueTicket {
+    pub token: String,
+    pub event_id: String,
+    /// 0-based place in the event's queue, in joining order
+    pub position: u64,
+}
+
+/// Where a queue token stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum QueueStatus {
+    Admitted,
+    Waiting { ahead: u64 },
+    /// Unknown, expired, already used or issued for another event
+    Invalid,
+}
+
+struct Ticket {
+    event_id: String,
+    position: u64,
+    joined_at: Instant,
+}
+
+struct EventQueue {
+    joined: u64,
+    admitted: f64,
+    updated_at: Instant,
+}
+
+impl EventQueue {
+    // Admission accrues over time but never ahead of who has joined, so an
+    // idle queue doesn't bank capacity for a later spike
+    fn admit(&mut self, rate: f64, now: Instant) -> u64 {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.admitted = (self.admitted + elapsed * rate).min(self.joined as f64);
+        self.updated_at = now;
+        self.admitted as u64
+    }
+}
+
+/// Virtual waiting room admitting each event's queue at a fixed rate
+///
+/// Callers join an event's queue and get a token, which is admitted once
+/// `admission_rate` tokens per second have been let through ahead of it.
+/// An admitted token is good for one reservation. State is per instance.
+pub struct WaitingRoom {
+    admission_rate: AtomicU32,
+    tickets: DashMap<String, Ticket>,
+    queues: DashMap<String, Arc<Mutex<EventQueue>>>,
+    joins: AtomicU64,
+}
+
+impl WaitingRoom {
+    /// Admit up to `admission_rate` tokens per second per event
+    pub fn new(admission_rate: u32) -> Self {
+        Self {
+            admission_rate: AtomicU32::new(admission_rate.max(1)),
+            tickets: DashMap::new(),
+            queues: DashMap::new(),
+            joins: AtomicU64::new(0),
+        }
+    }
+
+    pub fn admission_rate(&self) -> u32 {
+        self.admission_rate.load(Ordering::Relaxed)
+    }
+
+    /// Admit at `admission_rate` from now on
+    pub fn set_admission_rate(&self, admission_rate: u32) {
+        // Admission so far accrues at the old rate
+        let now = Instant::now();
+        let previous = self.admission_rate() as f64;
+        for queue in self.queues.iter() {
+            queue.lock().unwrap().admit(previous, now);
+        }
+        self.admission_rate.store(admission_rate.max(1), Ordering::Relaxed);
+    }
+
+    pub fn join(&self, event_id: &str) -> QueueTicket {
+        if self.joins.fetch_add(1, Ordering::Relaxed) % PURGE_EVERY == PURGE_EVERY - 1 {
+            self.purge_expired();
+        }
+
+        let now = Instant::now();
+        let queue = self.queue(event_id, now);
+        let position = {
+            let mut queue = queue.lock().unwrap();
+            // Bring admission up to date before the queue grows
+            queue.admit(self.admission_rate() as f64, now);
+            queue.joined += 1;
+            queue.joined - 1
+        };
+
+        let token = Uuid::new_v4().simple().to_string();
+        self.tickets.insert(
+            token.clone(),
+            Ticket {
+                event_id: event_id.to_string(),
+                position,
+                joined_at: now,
+            },
+        );
+        QueueTicket {
+            token,
+            event_id: event_id.to_string(),
+            position,
+        }
+    }
+
+    pub fn status(&self, token: &str, event_id: &str) -> QueueStatus {
+        let now = Instant::now();
+        let Some(ticket) = self.tickets.get(token) else {
+            return QueueStatus::Invalid;
+        };
+        if ticket.event_id != event_id || now.duration_since(ticket.joined_at) > TOKEN_TTL {
+            return QueueStatus::Invalid;
+        }
+        let Some(queue) = self.queues.get(event_id).map(|queue| queue.clone()) else {
+            return QueueStatus::Invalid;
+        };
+        let admitted = queue.lock().unwrap().admit(self.admission_rate() as f64, now);
+        if ticket.position < admitted {
+            QueueStatus::Admitted
+        } else {
+            QueueStatus::Waiting {
+                ahead: ticket.position - admitted,
+            }
+        }
+    }
+
+    /// Use up an admitted token; tokens still waiting are left as they are
+    pub fn consume(&self, token: &str, event_id: &str) -> QueueStatus {
+        let status = self.status(token, event_id);
+        if status == QueueStatus::Admitted && self.tickets.remove(token).is_none() {
+            // Used by a concurrent request in between
+            return QueueStatus::Invalid;
+        }
+        status
+    }
+
+    fn queue(&self, event_id: &str, now: Instant) -> Arc<Mutex<EventQueue>> {
+        self.queues
+            .entry(event_id.to_string())
+            .or_insert_with(|| {
+                Arc::new(Mutex::new(EventQueue {
+                    joined: 0,
+                    admitted: 0.0,
+                    updated_at: now,
+                }))
+            })
+            .clone()
+    }
+
+    fn purge_expired(&self) {
+        let now = Instant::now();
+        self.tickets.retain(|_, ticket| now.duration_since(ticket.joined_at) <= TOKEN_TTL);
+    }
+}
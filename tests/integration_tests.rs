@@ -50,13 +50,15 @@ async fn test_rocksdb_state_store_integration() {
     
     // Test area status storage
     let area_status = AreaStatus {
-        event_name: "Taylor Swift Concert".to_string(),
+        event_id: "Taylor Swift Concert".to_string(),
         area_id: "VIP".to_string(),
-        available_seats: 150,
-        total_seats: 200,
+        price: 500,
+        row_count: 2,
+        col_count: 1,
+        available_seats: 1,
         seats: vec![
             vec![SeatStatus { row: 0, col: 0, is_available: true }],
-            vec![SeatStatus { row: 0, col: 1, is_available: false }],
+            vec![SeatStatus { row: 1, col: 0, is_available: false }],
         ],
     };
     
@@ -67,11 +69,12 @@ async fn test_rocksdb_state_store_integration() {
     assert!(retrieved.is_some());
     
     let retrieved = retrieved.unwrap();
-    assert_eq!(retrieved.event_name, "Taylor Swift Concert");
+    assert_eq!(retrieved.event_id, "Taylor Swift Concert");
     assert_eq!(retrieved.area_id, "VIP");
-    assert_eq!(retrieved.available_seats, 150);
-    assert_eq!(retrieved.total_seats, 200);
+    assert_eq!(retrieved.price, 500);
+    assert_eq!(retrieved.available_seats, 1);
     assert_eq!(retrieved.seats.len(), 2);
+    assert!(!retrieved.seats[1][0].is_available);
     
     // Test reservation storage
     let reservation = Reservation {
@@ -80,14 +83,14 @@ async fn test_rocksdb_state_store_integration() {
         event_id: "Taylor Swift Concert".to_string(),
         area_id: "VIP".to_string(),
         num_of_seats: 2,
+        num_of_seat: 2,
         reservation_type: ReservationType::SelfPick,
         seats: vec![
             Seat { row: 0, col: 5 },
             Seat { row: 0, col: 6 },
         ],
-        state: ReservationState::Confirmed,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
+        state: ReservationState::Reserved,
+        failed_reason: None,
     };
     
     store.put("res-123", &reservation).unwrap();
@@ -100,6 +103,7 @@ async fn test_rocksdb_state_store_integration() {
     assert_eq!(retrieved_reservation.user_id, "user-456");
     assert_eq!(retrieved_reservation.num_of_seats, 2);
     assert_eq!(retrieved_reservation.seats.len(), 2);
+    assert_eq!(retrieved_reservation.state, ReservationState::Reserved);
 }
 
 #[tokio::test]
@@ -108,18 +112,21 @@ async fn test_processing_context_integration() {
     let context = ProcessingContext::with_state_dir(temp_dir.path().to_string_lossy().to_string());
     
     // Add RocksDB stores
-    context.add_rocksdb_store(Stores::AREA_STATUS.to_string(), "area-status").unwrap();
-    context.add_rocksdb_store(Stores::RESERVATION.to_string(), "reservations").unwrap();
+    context.open_rocksdb_store(&Stores::AREA_STATUS_STORE, "area-status").unwrap();
+    context.open_rocksdb_store(&Stores::RESERVATION_STORE, "reservations").unwrap();
     
     // Test area status store
-    let area_store = context.get_rocksdb_store(Stores::AREA_STATUS).unwrap();
-    let test_area = AreaStatus {
-        event_name: "Test Event".to_string(),
-        area_id: "General".to_string(),
-        available_seats: 500,
-        total_seats: 500,
-        seats: vec![],
-    };
+    let area_store = context.rocksdb_store(&Stores::AREA_STATUS_STORE).unwrap();
+    let test_area = AreaStatus::from_area(
+        "Test Event",
+        &Area {
+            area_id: "General".to_string(),
+            price: 100,
+            row_count: 20,
+            col_count: 25,
+            strategy: None,
+        },
+    );
     
     let key = event_area_key("Test Event", "General");
     area_store.put(&key, &test_area).unwrap();
@@ -129,18 +136,18 @@ async fn test_processing_context_integration() {
     assert_eq!(retrieved.unwrap().available_seats, 500);
     
     // Test reservation store
-    let reservation_store = context.get_rocksdb_store(Stores::RESERVATION).unwrap();
+    let reservation_store = context.rocksdb_store(&Stores::RESERVATION_STORE).unwrap();
     let test_reservation = Reservation {
         reservation_id: "test-res".to_string(),
         user_id: "test-user".to_string(),
         event_id: "Test Event".to_string(),
         area_id: "General".to_string(),
         num_of_seats: 3,
+        num_of_seat: 3,
         reservation_type: ReservationType::Random,
         seats: vec![],
-        state: ReservationState::Pending,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
+        state: ReservationState::Processing,
+        failed_reason: None,
     };
     
     reservation_store.put("test-res", &test_reservation).unwrap();
@@ -166,12 +173,14 @@ async fn test_domain_model_serialization() {
                 price: 500,
                 row_count: 10,
                 col_count: 20,
+                strategy: None,
             },
             Area {
                 area_id: "General".to_string(),
                 price: 100,
                 row_count: 50,
                 col_count: 30,
+                strategy: None,
             },
         ],
     };
@@ -199,6 +208,8 @@ async fn test_domain_model_serialization() {
             Seat { row: 5, col: 10 },
             Seat { row: 5, col: 11 },
         ],
+        preferences: vec![],
+        priority: ReservationPriority::General,
     };
     
     let json = serde_json::to_string(&create_reservation).unwrap();
@@ -212,66 +223,3488 @@ async fn test_domain_model_serialization() {
 }
 
 #[tokio::test]
-async fn test_reservation_strategies() {
-    use ticket_master::*;
-    
-    // Create a test area status
-    let mut area_status = AreaStatus {
-        event_name: "Test Event".to_string(),
-        area_id: "Test Area".to_string(),
-        available_seats: 100,
-        total_seats: 100,
-        seats: (0..10).map(|row| {
-            (0..10).map(|col| SeatStatus {
-                row,
-                col,
-                is_available: true,
-            }).collect()
-        }).collect(),
+async fn test_domain_model_protobuf_round_trip() {
+    let result = ReservationResult {
+        reservation_id: "res-789".to_string(),
+        result: ReservationResultEnum::Failed,
+        error_code: Some(ReservationErrorCode::SeatNotAvailable),
+        error_message: Some("Seat 5-10 is taken".to_string()),
+        seats: vec![Seat { row: 5, col: 10 }],
     };
-    
-    // Test SelfPick strategy
-    let self_pick_strategy = event_service::strategies::SelfPickStrategy;
-    let reserve_request = ReserveSeat {
-        reservation_id: "test-123".to_string(),
-        user_id: "user-123".to_string(),
-        event_id: "Test Event".to_string(),
-        area_id: "Test Area".to_string(),
+
+    let bytes = result.encode_protobuf();
+    let decoded = ReservationResult::decode_protobuf(&bytes).unwrap();
+
+    assert_eq!(ReservationResult::FULL_NAME, "ticket_master.ReservationResult");
+    assert_eq!(decoded.reservation_id, "res-789");
+    assert_eq!(decoded.result, ReservationResultEnum::Failed);
+    assert!(matches!(decoded.error_code, Some(ReservationErrorCode::SeatNotAvailable)));
+    assert_eq!(decoded.error_message.as_deref(), Some("Seat 5-10 is taken"));
+    assert_eq!(decoded.seats.len(), 1);
+    assert_eq!(decoded.seats[0].col, 10);
+}
+
+#[tokio::test]
+async fn test_offset_tracker_commits_contiguous_offsets() {
+    let message = |offset: i64| KafkaMessage {
+        topic: "test-topic".to_string(),
+        partition: 0,
+        offset,
+        key: None,
+        payload: None,
+        headers: Vec::new(),
+        serdes: std::sync::Arc::new(TopicSerdes::json()),
+    };
+
+    let mut offsets = OffsetTracker::new();
+    for offset in 10..13 {
+        offsets.track(&message(offset));
+    }
+
+    // 11 finished before 10, so nothing can be committed yet
+    offsets.complete(&message(11));
+    assert!(offsets.take_commits().is_empty());
+
+    offsets.complete(&message(10));
+    assert_eq!(offsets.take_commits(), vec![(TopicPartition::new("test-topic", 0), 12)]);
+    assert!(offsets.take_commits().is_empty());
+
+    offsets.complete(&message(12));
+    assert_eq!(offsets.take_commits(), vec![(TopicPartition::new("test-topic", 0), 13)]);
+}
+
+#[tokio::test]
+async fn test_mock_kafka_produce_consume_commit() {
+    let kafka = MockKafka::new();
+    kafka.create_topic(Topics::COMMAND_EVENT_RESERVE_SEAT, 3);
+
+    let producer = kafka.producer();
+    for i in 0..6 {
+        let reserve_seat = ReserveSeat {
+            reservation_id: format!("reservation-{}", i),
+            event_id: "event1".to_string(),
+            area_id: "A".to_string(),
+            num_of_seats: 1,
+            num_of_seat: 1,
+            reservation_type: ReservationType::Random,
+            seats: vec![],
+            preferences: vec![],
+        };
+        producer.send(Topics::COMMAND_EVENT_RESERVE_SEAT, "event1#A", &reserve_seat).await.unwrap();
+    }
+
+    // Same key, same partition, in send order
+    let consumer = kafka.consumer("event-service");
+    consumer.subscribe(&[Topics::COMMAND_EVENT_RESERVE_SEAT]).unwrap();
+    assert_eq!(consumer.assignment().unwrap().len(), 3);
+    let mut last = None;
+    for i in 0..6 {
+        let message = consumer.recv_message(Duration::from_secs(1)).await.unwrap().unwrap();
+        let reserve_seat: ReserveSeat = message.deserialize_value().await.unwrap();
+        assert_eq!(reserve_seat.reservation_id, format!("reservation-{}", i));
+        assert_eq!(message.offset, i);
+        last = Some(message);
+    }
+    assert!(consumer.recv_message(Duration::from_millis(10)).await.unwrap().is_none());
+
+    let last = last.unwrap();
+    consumer.commit_message(&last).unwrap();
+    let partition = TopicPartition::new(&last.topic, last.partition);
+    assert_eq!(kafka.committed_offset("event-service", &partition), Some(6));
+
+    // A new consumer of the group resumes from the committed offset
+    let restarted = kafka.consumer("event-service");
+    restarted.subscribe(&[Topics::COMMAND_EVENT_RESERVE_SEAT]).unwrap();
+    assert!(restarted.recv_message(Duration::from_millis(10)).await.unwrap().is_none());
+    restarted.seek_to_beginning(&[partition]).unwrap();
+    assert_eq!(restarted.recv_message(Duration::from_secs(1)).await.unwrap().unwrap().offset, 0);
+}
+
+#[tokio::test]
+async fn test_outbox_relay_publishes_enqueued_commands() {
+    let temp_dir = tempdir().unwrap();
+    let outbox = std::sync::Arc::new(Outbox::open(temp_dir.path()).unwrap());
+    let serdes = TopicSerdes::json();
+
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        let reserve_seat = ReserveSeat {
+            reservation_id: format!("reservation-{}", i),
+            event_id: "event1".to_string(),
+            area_id: "A".to_string(),
+            num_of_seats: 1,
+            num_of_seat: 1,
+            reservation_type: ReservationType::Random,
+            seats: vec![],
+            preferences: vec![],
+        };
+        ids.push(outbox.enqueue(&serdes, "test-topic", "event1#A", &reserve_seat).await.unwrap());
+    }
+    assert_eq!(outbox.pending().unwrap().len(), 3);
+
+    let kafka = MockKafka::new();
+    let relay = spawn_outbox_relay(outbox.clone(), kafka.producer(), Duration::from_millis(10));
+    for _ in 0..100 {
+        if outbox.pending().unwrap().is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    relay.abort();
+
+    let messages = kafka.messages("test-topic");
+    assert_eq!(messages.len(), 3);
+    for (message, id) in messages.iter().zip(&ids) {
+        assert_eq!(message.header(OUTBOX_ID_HEADER), Some(id.as_bytes()));
+    }
+    let first: ReserveSeat = messages[0].deserialize_value().await.unwrap();
+    assert_eq!(first.reservation_id, "reservation-0");
+}
+
+#[tokio::test]
+async fn test_dedup_store_detects_redelivered_messages() {
+    let temp_dir = tempdir().unwrap();
+    let message = |offset: i64, payload: &str| KafkaMessage {
+        topic: "test-topic".to_string(),
+        partition: 0,
+        offset,
+        key: Some("event1#A".to_string()),
+        payload: Some(payload.as_bytes().to_vec()),
+        headers: Vec::new(),
+        serdes: std::sync::Arc::new(TopicSerdes::json()),
+    };
+
+    let dedup = DedupStore::open(temp_dir.path().join("dedup"), Duration::from_secs(3600)).unwrap();
+    assert!(!dedup.is_duplicate(&message(1, "reserve")).unwrap());
+    dedup.mark_processed(&message(1, "reserve")).unwrap();
+
+    // Redelivered at a different offset, but same key and content
+    assert!(dedup.is_duplicate(&message(7, "reserve")).unwrap());
+    assert!(!dedup.is_duplicate(&message(8, "reserve again")).unwrap());
+
+    // The same payload sent again for another request is a new message
+    let with_headers = |offset: i64, payload: &str, headers: &[(&str, &str)]| KafkaMessage {
+        headers: headers.iter().map(|(name, value)| (name.to_string(), value.as_bytes().to_vec())).collect(),
+        ..message(offset, payload)
+    };
+    dedup.mark_processed(&with_headers(9, "reserve", &[(REQUEST_ID_HEADER, "req-1")])).unwrap();
+    assert!(dedup.is_duplicate(&with_headers(10, "reserve", &[(REQUEST_ID_HEADER, "req-1")])).unwrap());
+    assert!(!dedup.is_duplicate(&with_headers(11, "reserve", &[(REQUEST_ID_HEADER, "req-2")])).unwrap());
+
+    // A republished outbox entry is caught by its id alone
+    dedup.mark_processed(&with_headers(12, "create", &[(OUTBOX_ID_HEADER, "entry-1")])).unwrap();
+    assert!(dedup.is_duplicate(&with_headers(13, "create re-encoded", &[(OUTBOX_ID_HEADER, "entry-1")])).unwrap());
+    assert!(!dedup.is_duplicate(&with_headers(14, "create", &[(OUTBOX_ID_HEADER, "entry-2")])).unwrap());
+
+    // Seat releases repeat legitimately, so they are never skipped
+    let release = |offset: i64| KafkaMessage {
+        topic: Topics::COMMAND_EVENT_ADMIN.to_string(),
+        ..message(offset, r#"{"command":"release_seat"}"#)
+    };
+    dedup.mark_processed(&release(15)).unwrap();
+    assert!(!dedup.is_duplicate(&release(16)).unwrap());
+    // Nor is an area status that is the same as one seen before
+    let area_status = |offset: i64| KafkaMessage {
+        topic: Topics::STATE_EVENT_AREA_STATUS.to_string(),
+        ..message(offset, "status")
+    };
+    dedup.mark_processed(&area_status(17)).unwrap();
+    assert!(!dedup.is_duplicate(&area_status(18)).unwrap());
+
+    let expiring = DedupStore::open(temp_dir.path().join("expiring"), Duration::ZERO).unwrap();
+    expiring.mark_processed(&message(1, "reserve")).unwrap();
+    sleep(Duration::from_millis(5)).await;
+    assert!(!expiring.is_duplicate(&message(1, "reserve")).unwrap());
+    assert_eq!(expiring.purge_expired().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_replay_bypasses_dedup_below_the_replay_watermark() {
+    let temp_dir = tempdir().unwrap();
+    let kafka = MockKafka::new();
+    kafka.create_topic("test-topic", 2);
+    let producer = kafka.producer();
+    let dedup = DedupStore::open(temp_dir.path().join("dedup"), Duration::from_secs(3600)).unwrap();
+
+    // Processed and committed before the replay
+    for (partition, payload) in [(0, "first"), (0, "second"), (1, "third")] {
+        producer.send_bytes("test-topic", Some(partition), "key", Some(payload.as_bytes())).await.unwrap();
+    }
+    let consumer = kafka.consumer("test-group");
+    consumer.subscribe(&["test-topic"]).unwrap();
+    for _ in 0..3 {
+        let message = consumer.recv_message(Duration::from_secs(1)).await.unwrap().unwrap();
+        dedup.mark_processed(&message).unwrap();
+        consumer.commit_message(&message).unwrap();
+    }
+    producer.send_bytes("test-topic", Some(0), "key", Some(b"new")).await.unwrap();
+
+    let watermarks = std::sync::Arc::new(ReplayWatermarks::default());
+    let mut replay = Replay::new(0, watermarks.clone());
+    let consumer = kafka.consumer("test-group");
+    consumer.subscribe(&["test-topic"]).unwrap();
+    let fetched = consumer.recv_message(Duration::from_secs(1)).await.unwrap().unwrap();
+    assert_eq!((fetched.partition, fetched.offset), (0, 2));
+    assert!(replay.skip(&consumer, &fetched).unwrap());
+    // Partition 1 had nothing new to fetch
+    replay.rewind_assigned(&consumer).unwrap();
+
+    let mut replayed = Vec::new();
+    for _ in 0..4 {
+        let message = consumer.recv_message(Duration::from_secs(1)).await.unwrap().unwrap();
+        assert!(!replay.skip(&consumer, &message).unwrap());
+        if watermarks.is_replayed(&message) {
+            replayed.push(String::from_utf8(message.payload.unwrap()).unwrap());
+        } else {
+            // Past the watermark, dedup applies as usual
+            assert!(!dedup.is_duplicate(&message).unwrap());
+            assert_eq!(message.payload.as_deref(), Some(&b"new"[..]));
+        }
+    }
+    replayed.sort();
+    assert_eq!(replayed, ["first", "second", "third"]);
+
+    // A redelivery after the replay is still caught
+    dedup.mark_processed(&fetched).unwrap();
+    assert!(!watermarks.is_replayed(&fetched));
+    assert!(dedup.is_duplicate(&fetched).unwrap());
+}
+
+#[tokio::test]
+async fn test_topic_resolver_applies_environment_prefix() {
+    let topics = TopicResolver::new(Some("staging."));
+    assert_eq!(topics.resolve(Topics::COMMAND_EVENT_CREATE_EVENT), "staging.command.event.create_event");
+    assert_eq!(topics.logical("staging.command.event.create_event"), Topics::COMMAND_EVENT_CREATE_EVENT);
+    assert_eq!(topics.logical("other.topic"), "other.topic");
+
+    let unprefixed = TopicResolver::new(None);
+    assert_eq!(unprefixed.resolve(Topics::COMMAND_EVENT_CREATE_EVENT), Topics::COMMAND_EVENT_CREATE_EVENT);
+}
+
+#[tokio::test]
+async fn test_area_status_bridge_fans_out_updates() {
+    let kafka = MockKafka::new();
+    kafka.create_topic(Topics::STATE_EVENT_AREA_STATUS, 2);
+
+    let hub = AreaStatusHub::new(16);
+    let mut first = hub.subscribe();
+    let mut second = hub.subscribe();
+    let bridge = spawn_area_status_bridge(kafka.consumer("ticket-service-bridge"), hub.clone()).unwrap();
+
+    let area = Area {
+        area_id: "A".to_string(),
+        price: 100,
+        row_count: 2,
+        col_count: 2,
+        strategy: None,
+    };
+    let area_status = AreaStatus::from_area("event1", &area);
+    kafka.producer().send(Topics::STATE_EVENT_AREA_STATUS, "event1#A", &area_status).await.unwrap();
+
+    for receiver in [&mut first, &mut second] {
+        let update = tokio::time::timeout(Duration::from_secs(1), receiver.recv()).await.unwrap().unwrap();
+        assert_eq!(update.event_id, "event1");
+        assert_eq!(update.available_seats, 4);
+    }
+    bridge.abort();
+}
+
+#[tokio::test]
+async fn test_rocksdb_prefix_and_range_scans() {
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path()).unwrap();
+    for key in ["event1#A", "event1#B", "event10#A", "event2#A"] {
+        store.put(key, &key.to_string()).unwrap();
+    }
+
+    let event1: Vec<(String, String)> = store.scan_prefix("event1#").collect::<Result<_>>().unwrap();
+    let keys: Vec<&str> = event1.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, vec!["event1#A", "event1#B"]);
+
+    let range: Vec<(String, String)> = store.range("event1#B", "event2#A").collect::<Result<_>>().unwrap();
+    let keys: Vec<&str> = range.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, vec!["event1#B", "event10#A"]);
+
+    assert_eq!(store.scan_prefix::<String>("event3#").count(), 0);
+}
+
+#[tokio::test]
+async fn test_rocksdb_write_batch_is_atomic() {
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path()).unwrap();
+    store.put("stale", &1).unwrap();
+
+    let mut discarded = store.write_batch();
+    discarded.put("event1#A", &10).unwrap();
+    drop(discarded);
+    assert_eq!(store.get::<i32>("event1#A").unwrap(), None);
+
+    let mut batch = store.write_batch();
+    batch.put("event1#A", &10).unwrap().put("offset", &42).unwrap();
+    batch.delete("stale");
+    assert_eq!(batch.len(), 3);
+    assert_eq!(store.get::<i32>("offset").unwrap(), None);
+    batch.commit().unwrap();
+
+    assert_eq!(store.get::<i32>("event1#A").unwrap(), Some(10));
+    assert_eq!(store.get::<i32>("offset").unwrap(), Some(42));
+    assert_eq!(store.get::<i32>("stale").unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_cache_store_evicts_least_recently_used_and_expired() {
+    let cache = CacheStore::new(2, Duration::from_millis(50));
+
+    cache.put("a".to_string(), 1);
+    cache.put("b".to_string(), 2);
+    assert_eq!(cache.get(&"a".to_string()), Some(1));
+    cache.put("c".to_string(), 3);
+
+    // "b" was used least recently
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&"b".to_string()), None);
+    assert_eq!(cache.get(&"a".to_string()), Some(1));
+
+    sleep(Duration::from_millis(60)).await;
+    assert_eq!(cache.get(&"a".to_string()), None);
+    assert_eq!(cache.purge_expired(), 1);
+    assert!(cache.is_empty());
+}
+
+#[tokio::test]
+async fn test_versioned_store_rejects_stale_writes() {
+    let temp_dir = tempdir().unwrap();
+    let store = VersionedStore::new(std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap()));
+    assert_eq!(store.version("event1#A").unwrap(), 0);
+
+    assert_eq!(store.compare_and_put("event1#A", 0, &10).unwrap(), 1);
+    let read = store.get::<i32>("event1#A").unwrap().unwrap();
+    assert_eq!(read, Versioned { value: 10, version: 1 });
+
+    // A second writer that read version 1 loses to the first
+    assert_eq!(store.compare_and_put("event1#A", 1, &9).unwrap(), 2);
+    assert!(matches!(
+        store.compare_and_put("event1#A", 1, &8),
+        Err(TicketMasterError::VersionConflict { expected: 1, actual: 2, .. })
+    ));
+    assert_eq!(store.store().get::<i32>("event1#A").unwrap(), Some(9));
+
+    let version = store.put_with("event1#A", &7, |batch| {
+        batch.put("marker", &1)?;
+        Ok(())
+    }).unwrap();
+    assert_eq!(version, 3);
+    assert_eq!(store.store().get::<i32>("marker").unwrap(), Some(1));
+}
+
+#[tokio::test]
+async fn test_rocksdb_checkpoint_restore_rolls_back_state() {
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path().join("store")).unwrap();
+    store.put("event1#A", &10).unwrap();
+    store.put("event1#B", &20).unwrap();
+
+    let checkpoint = temp_dir.path().join("backup");
+    store.create_checkpoint(&checkpoint).unwrap();
+    assert!(store.create_checkpoint(&checkpoint).is_err());
+
+    store.put("event1#A", &9).unwrap();
+    store.delete("event1#B").unwrap();
+    store.put("event1#C", &30).unwrap();
+
+    assert_eq!(store.restore_from_checkpoint(&checkpoint).unwrap(), 2);
+    assert_eq!(store.get::<i32>("event1#A").unwrap(), Some(10));
+    assert_eq!(store.get::<i32>("event1#B").unwrap(), Some(20));
+    assert_eq!(store.get::<i32>("event1#C").unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_rocksdb_raw_entries_replace_store_contents() {
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path().join("store")).unwrap();
+    store.put("event1#A", &10).unwrap();
+    store.put("event1#B", &"twenty".to_string()).unwrap();
+    let dump: std::collections::BTreeMap<Vec<u8>, Vec<u8>> =
+        store.raw_entries().collect::<Result<_>>().unwrap();
+    assert_eq!(dump.len(), 2);
+
+    store.put("event1#A", &9).unwrap();
+    store.delete("event1#B").unwrap();
+    store.put("event1#C", &30).unwrap();
+
+    let other = RocksDBStore::new(temp_dir.path().join("other")).unwrap();
+    assert_eq!(other.replace_raw(&dump).unwrap(), 2);
+    assert_eq!(store.replace_raw(&dump).unwrap(), 2);
+    for restored in [&store, &other] {
+        assert_eq!(restored.get::<i32>("event1#A").unwrap(), Some(10));
+        assert_eq!(restored.get::<String>("event1#B").unwrap(), Some("twenty".to_string()));
+        assert_eq!(restored.get::<i32>("event1#C").unwrap(), None);
+    }
+}
+
+#[tokio::test]
+async fn test_processing_context_typed_store_handles() {
+    const COUNTS: StoreHandle<String, i64> = StoreHandle::new("counts");
+    const NAMES: StoreHandle<String, String> = StoreHandle::new("counts");
+
+    let temp_dir = tempdir().unwrap();
+    let context = ProcessingContext::builder(temp_dir.path().to_string_lossy().to_string())
+        .rocksdb_store(COUNTS.partition(0), "counts/0")
+        .cache_store(Stores::EVENT_AREA_STATUS_CACHE_STORE, 10, Duration::from_secs(60))
+        .build()
+        .unwrap();
+
+    let counts = context.store(&COUNTS.partition(0)).unwrap();
+    counts.put("event1#A".to_string(), 3).unwrap();
+    assert_eq!(counts.get(&"event1#A".to_string()).unwrap(), Some(3));
+    assert!(context.rocksdb_store(&COUNTS.partition(0)).is_ok());
+    assert!(context.rocksdb_store(&Stores::EVENT_AREA_STATUS_CACHE_STORE).is_err());
+
+    // Missing stores and mismatched types are errors, not silent misses
+    assert!(matches!(context.store(&COUNTS.partition(1)), Err(TicketMasterError::StoreNotFound(_))));
+    assert!(context.store(&NAMES.partition(0)).is_err());
+
+    assert!(context.remove_store(&COUNTS.partition(0)));
+    assert!(!context.has_store(&COUNTS.partition(0)));
+}
+
+#[tokio::test]
+async fn test_rocksdb_store_codec_reads_legacy_json() {
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path()).unwrap();
+    assert_eq!(store.codec(), StoreCodec::Bincode);
+
+    let area = Area {
+        area_id: "A".to_string(),
+        price: 100,
+        row_count: 20,
+        col_count: 20,
+        strategy: None,
+    };
+    let area_status = AreaStatus::from_area("event1", &area);
+    let json = serde_json::to_vec(&area_status).unwrap();
+    let bincode = StoreCodec::Bincode.encode(&area_status).unwrap();
+    assert!(bincode.len() < json.len());
+
+    // Written before the store switched to bincode
+    store.restore_raw(b"event1#A", Some(&json)).unwrap();
+    let legacy: AreaStatus = store.get("event1#A").unwrap().unwrap();
+    assert_eq!(legacy.available_seats, 400);
+
+    store.put("event1#A", &legacy).unwrap();
+    let (_, rewritten): (String, AreaStatus) = store.entries().unwrap().pop().unwrap();
+    assert_eq!(rewritten.seats.len(), 20);
+    assert_eq!(StoreCodec::decode::<AreaStatus>(&json).unwrap().area_id, "A");
+}
+
+#[tokio::test]
+async fn test_cached_store_writes_through_and_evicts() {
+    const COUNTS: StoreHandle<String, i64> = StoreHandle::new("counts");
+
+    let temp_dir = tempdir().unwrap();
+    let context = ProcessingContext::builder(temp_dir.path().to_string_lossy().to_string())
+        .cached_rocksdb_store(COUNTS, "counts", 10)
+        .build()
+        .unwrap();
+    let counts = context.store(&COUNTS).unwrap();
+    let rocksdb = context.rocksdb_store(&COUNTS).unwrap();
+
+    counts.put("event1#A".to_string(), 1).unwrap();
+    assert_eq!(rocksdb.get::<i64>("event1#A").unwrap(), Some(1));
+
+    // Reads come from memory until the key is invalidated
+    rocksdb.put("event1#A", &2i64).unwrap();
+    assert_eq!(counts.get(&"event1#A".to_string()).unwrap(), Some(1));
+    let StateStoreBackend::Cached(cached) = &counts else {
+        panic!("expected a cached store");
+    };
+    cached.invalidate(&"event1#A".to_string());
+    assert_eq!(counts.get(&"event1#A".to_string()).unwrap(), Some(2));
+
+    for i in 0..25 {
+        counts.put(format!("event2#{}", i), i).unwrap();
+    }
+    assert!(cached.len() <= cached.capacity());
+    assert_eq!(counts.get(&"event2#0".to_string()).unwrap(), Some(0));
+
+    assert_eq!(counts.remove(&"event1#A".to_string()).unwrap(), Some(2));
+    assert_eq!(rocksdb.get::<i64>("event1#A").unwrap(), None);
+    assert!(!counts.contains_key(&"event1#A".to_string()).unwrap());
+}
+
+#[tokio::test]
+async fn test_per_service_config_sections() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("services.properties");
+    std::fs::write(&config_path, r#"
+ticket.service.port=9080
+ticket.service.rate.limit.requests.per.second=200
+ticket.service.rate.limit.burst=50
+ticket.service.cors.allowed.origins=https://tickets.example.com
+cors.allow.credentials=true
+event.service.strategy.default.random=continuous_random
+event.service.worker.threads=4
+reservation.service.hold.ttl.ms=600000
+"#).unwrap();
+
+    let config = parse_properties_file(&config_path, "ticket-service").unwrap();
+    assert_eq!(config.ticket_service.port, Some(9080));
+    let rate_limit = config.ticket_service.rate_limit.as_ref().unwrap();
+    assert_eq!((rate_limit.requests_per_second, rate_limit.burst), (200, Some(50)));
+    assert_eq!(config.ticket_service.cors.allowed_origins, vec!["https://tickets.example.com"]);
+    assert!(config.ticket_service.cors.allow_credentials);
+    assert_eq!(config.event_service.worker_threads, Some(4));
+    assert_eq!(config.reservation_service.hold_ttl(), Some(Duration::from_secs(600)));
+    assert!(config.kafka.additional_properties.keys().all(|key| !key.contains(".service.")));
+
+    // Random requests in areas without a strategy use the configured default
+    let mut registry = StrategyRegistry::default();
+    for (reservation_type, name) in &config.event_service.strategy_defaults {
+        registry.set_default(reservation_type, name).unwrap();
+    }
+    let request = ReserveSeat {
+        reservation_id: "r1".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
         num_of_seats: 2,
-        reservation_type: ReservationType::SelfPick,
-        seats: vec![
-            Seat { row: 0, col: 0 },
-            Seat { row: 0, col: 1 },
-        ],
+        num_of_seat: 2,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        preferences: vec![],
     };
-    
-    let result = self_pick_strategy.reserve(&mut area_status, &reserve_request).unwrap();
-    assert_eq!(result.result, ReservationResultEnum::Success);
-    assert_eq!(result.seats.len(), 2);
-    assert_eq!(result.seats[0].row, 0);
-    assert_eq!(result.seats[0].col, 0);
-    
-    // Test Random strategy
-    let random_strategy = event_service::strategies::RandomStrategy;
-    let random_request = ReserveSeat {
-        reservation_id: "test-456".to_string(),
-        user_id: "user-456".to_string(),
+    assert_eq!(
+        registry.select(None, &request).unwrap().description(),
+        registry.get(CONTINUOUS_RANDOM_STRATEGY).unwrap().description()
+    );
+    assert!(registry.set_default(&ReservationType::Random, SELF_PICK_STRATEGY).is_err());
+    assert!(registry.set_default(&ReservationType::SelfPick, RANDOM_STRATEGY).is_err());
+
+    for invalid in ["ticket.service.rate.limit.burst=50", "event.service.strategy.default.self_pick=random", "ticket.service.port=http"] {
+        std::fs::write(&config_path, invalid).unwrap();
+        assert!(parse_properties_file(&config_path, "ticket-service").is_err(), "{}", invalid);
+    }
+}
+
+#[tokio::test]
+async fn test_mtls_properties_reach_the_kafka_client() {
+    let temp_dir = tempdir().unwrap();
+    let key_password_path = temp_dir.path().join("key-password");
+    std::fs::write(&key_password_path, "changeit\n").unwrap();
+    let config_path = temp_dir.path().join("mtls.properties");
+    std::fs::write(&config_path, format!(r#"
+security.protocol=SSL
+ssl.ca.location=/etc/kafka/ca.pem
+ssl.certificate.location=/etc/kafka/client.pem
+ssl.key.location=/etc/kafka/client.key
+ssl.key.password=in-plain-text
+ssl.key.password.file={}
+ssl.endpoint.identification.algorithm=
+"#, key_password_path.display())).unwrap();
+
+    let config = parse_properties_file(&config_path, "event-service").unwrap();
+    assert_eq!(config.kafka.ssl_certificate_location.as_deref(), Some("/etc/kafka/client.pem"));
+    assert_eq!(config.kafka.ssl_key_location.as_deref(), Some("/etc/kafka/client.key"));
+    assert_eq!(config.kafka.ssl_key_password.as_deref(), Some("changeit"));
+    assert_eq!(config.kafka.ssl_endpoint_identification_algorithm.as_deref(), Some("none"));
+    assert!(config.kafka.additional_properties.is_empty());
+
+    let client = config.to_kafka_config();
+    assert_eq!(client.get("security.protocol"), Some("SSL"));
+    assert_eq!(client.get("ssl.certificate.location"), Some("/etc/kafka/client.pem"));
+    assert_eq!(client.get("ssl.key.location"), Some("/etc/kafka/client.key"));
+    assert_eq!(client.get("ssl.key.password"), Some("changeit"));
+    assert_eq!(client.get("ssl.endpoint.identification.algorithm"), Some("none"));
+    assert!(!redacted_config(&config).unwrap().contains("changeit"));
+}
+
+#[tokio::test]
+async fn test_event_and_reservation_requests_are_validated_per_field() {
+    let area = |area_id: &str| Area {
+        area_id: area_id.to_string(),
+        price: 50,
+        row_count: 2,
+        col_count: 5,
+        strategy: None,
+    };
+    let event = |event_name: &str, times: [&str; 4], areas: Vec<Area>| CreateEventRequest {
+        artist: "Ada".to_string(),
+        event_name: event_name.to_string(),
+        reservation_opening_time: times[0].to_string(),
+        reservation_closing_time: times[1].to_string(),
+        event_start_time: times[2].to_string(),
+        event_end_time: times[3].to_string(),
+        areas,
+    };
+    let fields = |errors: Vec<FieldError>| errors.into_iter().map(|error| error.field).collect::<Vec<_>>();
+    let ordered = ["2030-01-01T00:00:00Z", "2030-02-01T00:00:00Z", "2030-02-01T00:00:00Z", "1896220800000"];
+
+    let valid = event("concert", ordered, vec![area("A"), area("B")]);
+    assert!(valid.validate().is_empty());
+    let create_event = valid.into_create_event().unwrap();
+    assert_eq!(create_event.event_end_time.to_rfc3339(), "2030-02-02T00:00:00+00:00");
+    assert!(create_event.validate().is_empty());
+
+    // Opening < closing <= start < end; each out-of-order time is named
+    let reversed = ["2030-02-01T00:00:00Z", "2030-01-01T00:00:00Z", "2029-12-01T00:00:00Z", "2029-12-01T00:00:00Z"];
+    assert_eq!(
+        fields(event("concert", reversed, vec![area("A")]).validate()),
+        ["reservation_closing_time", "event_start_time", "event_end_time"]
+    );
+    // Unparsable times are reported instead of being ordered
+    let unparsable = ["tomorrow", ordered[1], ordered[2], ordered[3]];
+    assert_eq!(fields(event("concert", unparsable, vec![area("A")]).validate()), ["reservation_opening_time"]);
+
+    assert_eq!(fields(event("concert", ordered, vec![area("A"), area("A")]).validate()), ["areas[1].area_id"]);
+    // `#` joins event and area ids into store keys
+    assert_eq!(
+        fields(event("con#cert", ordered, vec![area("A#1")]).validate()),
+        ["event_name", "areas[0].area_id"]
+    );
+
+    let reservation = |num_of_seats: i32, seats: Option<Vec<Seat>>| CreateReservationRequest {
+        user_id: "ada".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats,
+        reservation_type: "self_pick".to_string(),
+        seats,
+        preferences: vec![],
+        priority: None,
+    };
+    let seats = |count: i32| Some((0..count).map(|col| Seat { row: 0, col }).collect());
+    assert!(reservation(2, seats(2)).validate().is_empty());
+    assert_eq!(fields(reservation(2, seats(3)).validate()), ["seats"]);
+    assert_eq!(fields(reservation(2, None).validate()), ["seats"]);
+    assert_eq!(fields(reservation(0, seats(0)).validate()), ["num_of_seats"]);
+    let keyed = CreateReservationRequest {
+        event_id: "con#cert".to_string(),
+        area_id: "A#1".to_string(),
+        ..reservation(2, seats(2))
+    };
+    assert_eq!(fields(keyed.validate()), ["event_id", "area_id"]);
+}
+
+#[tokio::test]
+async fn test_api_errors_map_to_status_and_code() {
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    let cases = [
+        (TicketMasterError::InvalidArgument("bad".to_string()), StatusCode::BAD_REQUEST, "invalid_argument"),
+        (TicketMasterError::InvalidEventArea("concert#A".to_string()), StatusCode::NOT_FOUND, "event_area_not_found"),
+        (
+            TicketMasterError::InvalidReservationStrategy("nearest".to_string()),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid_reservation_strategy",
+        ),
+        (TicketMasterError::SeatNotAvailable { row: 0, col: 1 }, StatusCode::CONFLICT, "seat_not_available"),
+        (TicketMasterError::InsufficientSeats, StatusCode::CONFLICT, "insufficient_seats"),
+        (
+            TicketMasterError::VersionConflict { key: "concert#A".to_string(), expected: 1, actual: 2 },
+            StatusCode::CONFLICT,
+            "version_conflict",
+        ),
+        (TicketMasterError::StoreNotFound("areas".to_string()), StatusCode::NOT_FOUND, "store_not_found"),
+        (
+            TicketMasterError::IllegalTransition {
+                reservation_id: "res-1".to_string(),
+                state: "paid".to_string(),
+                event: "cancelled".to_string(),
+            },
+            StatusCode::CONFLICT,
+            "illegal_transition",
+        ),
+        (TicketMasterError::UnknownUser("ada".to_string()), StatusCode::UNPROCESSABLE_ENTITY, "unknown_user"),
+        (TicketMasterError::UserExists("ada".to_string()), StatusCode::CONFLICT, "user_exists"),
+        (TicketMasterError::CircuitOpen, StatusCode::SERVICE_UNAVAILABLE, "circuit_open"),
+        (TicketMasterError::Sink("down".to_string()), StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+    ];
+    for (error, status, code) in cases {
+        let api_error = ApiError::from(error);
+        assert_eq!((api_error.status(), api_error.code()), (status, code));
+    }
+
+    // Context added on the way up doesn't change what the client is told
+    let wrapped = TicketMasterError::InsufficientSeats.with_context(ErrorContext::new("reserve", "event-service"));
+    assert_eq!(ApiError::from(wrapped).code(), "insufficient_seats");
+
+    // The response carries the code in its body and for the error metrics
+    let response = ApiError::validation(vec![FieldError::new("seats", "must list exactly 2 seats")]).into_response();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.extensions().get::<ErrorCode>().map(|code| code.0), Some("validation_failed"));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["success"], false);
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(body["details"][0]["field"], "seats");
+}
+
+#[tokio::test]
+async fn test_user_profiles_register_update_and_round_trip() {
+    let registered_at = chrono::Utc::now();
+    let register = |user_id: &str, email: &str| RegisterUser {
+        user_id: user_id.to_string(),
+        email: email.to_string(),
+        display_name: " Ada Lovelace ".to_string(),
+        phone: Some("+44 20-7946-0958".to_string()),
+    };
+    assert!(matches!(UserProfile::register(register(" ", "ada@example.com"), registered_at), Err(TicketMasterError::InvalidArgument(_))));
+    assert!(matches!(UserProfile::register(register("ada", "ada.example.com"), registered_at), Err(TicketMasterError::InvalidArgument(_))));
+
+    let mut profile = UserProfile::register(register("ada", "ada@example.com"), registered_at).unwrap();
+    assert_eq!(profile.display_name, "Ada Lovelace");
+    assert_eq!(profile.phone.as_deref(), Some("+442079460958"));
+
+    // A bad field leaves the whole profile as it was
+    let later = registered_at + chrono::Duration::seconds(5);
+    let bad = UpdateUserProfile { display_name: Some("Ada".to_string()), phone: Some("call me".to_string()), ..Default::default() };
+    assert!(profile.apply(bad, later).is_err());
+    assert_eq!(profile.display_name, "Ada Lovelace");
+
+    profile.apply(UpdateUserProfile { phone: Some(String::new()), ..Default::default() }, later).unwrap();
+    assert_eq!(profile.phone, None);
+    assert_eq!(profile.email, "ada@example.com");
+    assert_eq!(profile.updated_at, later);
+
+    let decoded = UserProfile::decode_protobuf(&profile.encode_protobuf()).unwrap();
+    assert_eq!(UserProfile::FULL_NAME, "ticket_master.UserProfile");
+    assert_eq!(decoded.user_id, "ada");
+    assert_eq!(decoded.created_at.timestamp_millis(), registered_at.timestamp_millis());
+
+    let mut config = TicketServiceConfig::default();
+    assert!(config.apply_property("ticket.service.users.required", "true").unwrap());
+    assert!(config.users_required);
+}
+
+#[tokio::test]
+async fn test_sales_aggregator_counts_each_reservation_once() {
+    let temp_dir = tempdir().unwrap();
+    let aggregator = SalesAggregator::new(std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap()));
+    let mut area_status = AreaStatus::from_layout("concert", &SeatLayout::from(&Area {
+        area_id: "A".to_string(),
+        price: 50,
+        row_count: 2,
+        col_count: 5,
+        strategy: None,
+    }));
+    aggregator.apply_area_status(&area_status).unwrap();
+
+    let mut reservation = Reservation::new(CreateReservation {
+        reservation_id: "res-1".to_string(),
+        user_id: "ada".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 2,
+        num_of_seat: 0,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        preferences: vec![],
+        priority: ReservationPriority::General,
+    });
+    let reserved_at = chrono::Utc::now();
+    reservation
+        .transition(ReservationEvent::SeatsReserved { seats: vec![Seat { row: 0, col: 0 }, Seat { row: 0, col: 1 }] })
+        .unwrap();
+    area_status.available_seats = 8;
+    aggregator.apply_area_status(&area_status).unwrap();
+    // Redelivered
+    aggregator.apply_reservation(&reservation, reserved_at).unwrap();
+    aggregator.apply_reservation(&reservation, reserved_at).unwrap();
+
+    let report = aggregator.event("concert").unwrap().unwrap();
+    assert_eq!(report.tickets_sold, 2);
+    assert_eq!(report.revenue, 0);
+    assert!((report.sell_through - 0.2).abs() < 1e-9);
+
+    // Paid later is still sold in the hour it was reserved
+    reservation.transition(ReservationEvent::PaymentReceived).unwrap();
+    aggregator.apply_reservation(&reservation, reserved_at + chrono::Duration::hours(3)).unwrap();
+    let report = aggregator.event("concert").unwrap().unwrap();
+    assert_eq!((report.tickets_sold, report.tickets_paid, report.revenue), (2, 2, 100));
+    let hourly = aggregator.hourly("concert").unwrap();
+    assert_eq!(hourly.len(), 1);
+    assert_eq!(hourly[0].tickets_sold, 2);
+    assert!(hourly[0].hour <= reserved_at);
+
+    let csv = area_sales_csv(&aggregator.events().unwrap());
+    assert_eq!(csv.lines().nth(1), Some("concert,A,50,2,2,8,100,0.2000"));
+
+    // Paid is final to the state machine; cancelled counts as unsold however it got there
+    reservation.state = ReservationState::Cancelled;
+    aggregator.apply_reservation(&reservation, reserved_at).unwrap();
+    let report = aggregator.event("concert").unwrap().unwrap();
+    assert_eq!((report.tickets_sold, report.revenue), (0, 0));
+    assert!(aggregator.hourly("concert").unwrap().is_empty());
+    assert!(aggregator.event("festival").unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_seat_reconciler_reports_confirmed_discrepancies() {
+    let temp_dir = tempdir().unwrap();
+    let reconciler = SeatReconciler::new(std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap()));
+    let mut area_status = AreaStatus::from_layout("concert", &SeatLayout::from(&Area {
+        area_id: "A".to_string(),
+        price: 50,
+        row_count: 2,
+        col_count: 5,
+        strategy: None,
+    }));
+    // A gap in the layout, never available
+    area_status.seats[1][4].is_available = false;
+    area_status.available_seats = 9;
+    reconciler.apply_area_status(&area_status).unwrap();
+
+    let reserve = |reservation_id: &str, seats: Vec<Seat>| {
+        let mut reservation = Reservation::new(CreateReservation {
+            reservation_id: reservation_id.to_string(),
+            user_id: "ada".to_string(),
+            event_id: "concert".to_string(),
+            area_id: "A".to_string(),
+            num_of_seats: seats.len() as i32,
+            num_of_seat: 0,
+            reservation_type: ReservationType::Random,
+            seats: vec![],
+            preferences: vec![],
+            priority: ReservationPriority::General,
+        });
+        reservation.transition(ReservationEvent::SeatsReserved { seats }).unwrap();
+        reconciler.apply_reservation(&reservation).unwrap();
+        reservation
+    };
+    reserve("res-1", vec![Seat { row: 0, col: 0 }, Seat { row: 0, col: 1 }]);
+    reserve("res-2", vec![Seat { row: 1, col: 0 }]);
+    reserve("res-3", vec![Seat { row: 0, col: 0 }]);
+    let mut cancelled = reserve("res-4", vec![Seat { row: 0, col: 3 }]);
+    cancelled.state = ReservationState::Cancelled;
+    reconciler.apply_reservation(&cancelled).unwrap();
+
+    // (0, 2) is taken without a reservation and (1, 0) is left available
+    for col in 0..3 {
+        area_status.seats[0][col].is_available = false;
+    }
+    reconciler.apply_area_status(&area_status).unwrap();
+
+    let first = reconciler.reconcile().unwrap();
+    assert_eq!(first.areas_checked, 1);
+    let kinds: Vec<DiscrepancyKind> = first.discrepancies.iter().map(|d| d.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            DiscrepancyKind::CounterMismatch { reported: 9, counted: 6 },
+            DiscrepancyKind::DoubleBooked { row: 0, col: 0, reservation_ids: vec!["res-1".to_string(), "res-3".to_string()] },
+            DiscrepancyKind::UnheldSeat { row: 0, col: 2 },
+            DiscrepancyKind::UnmarkedSeat { row: 1, col: 0, reservation_id: "res-2".to_string() },
+        ]
+    );
+
+    // Only what two runs in a row find is reported
+    assert!(first.confirmed_by(&ReconciliationReport::default()).discrepancies.is_empty());
+    let confirmed = reconciler.reconcile().unwrap().confirmed_by(&first);
+    assert_eq!(confirmed.discrepancies, first.discrepancies);
+    assert_eq!(
+        confirmed.counts(),
+        vec![("counter_mismatch", 1), ("unheld_seat", 1), ("unmarked_seat", 1), ("double_booked", 1)]
+    );
+    assert_eq!(confirmed.miscounted_areas(), vec![("concert".to_string(), "A".to_string())]);
+
+    let mut config = ReconciliationServiceConfig::default();
+    assert_eq!(config.interval(), Duration::from_secs(60));
+    assert!(config.apply_property("reconciliation.service.auto.repair", "true").unwrap());
+    assert!(config.auto_repair);
+}
+
+#[tokio::test]
+async fn test_snapshot_exporter_partitions_by_date_and_event() {
+    let temp_dir = tempdir().unwrap();
+    let store = std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap());
+    let exporter = SnapshotExporter::new(store.clone(), ExportFormat::Jsonl).unwrap().with_prefix("/warehouse/");
+    let reservation = |reservation_id: &str, event_id: &str| {
+        Reservation::new(CreateReservation {
+            reservation_id: reservation_id.to_string(),
+            user_id: "ada".to_string(),
+            event_id: event_id.to_string(),
+            area_id: "A".to_string(),
+            num_of_seats: 1,
+            num_of_seat: 0,
+            reservation_type: ReservationType::Random,
+            seats: vec![],
+            preferences: vec![],
+            priority: ReservationPriority::General,
+        })
+    };
+    let mut reserved = reservation("res-1", "concert");
+    exporter.apply_reservation(&reserved).unwrap();
+    // Only the latest of a reservation is exported
+    reserved.transition(ReservationEvent::SeatsReserved { seats: vec![Seat { row: 0, col: 3 }] }).unwrap();
+    exporter.apply_reservation(&reserved).unwrap();
+    exporter.apply_reservation(&reservation("res-2", "concert")).unwrap();
+    exporter.apply_reservation(&reservation("res-3", "festival/day-1")).unwrap();
+    exporter.apply_area_status(&AreaStatus::from_layout("concert", &SeatLayout::from(&Area {
+        area_id: "A".to_string(),
+        price: 50,
+        row_count: 2,
+        col_count: 5,
+        strategy: None,
+    })))
+    .unwrap();
+
+    let snapshot_at = chrono::DateTime::parse_from_rfc3339("2026-10-16T08:30:00Z").unwrap().with_timezone(&chrono::Utc);
+    let files = exporter.snapshot(snapshot_at).unwrap();
+    let keys: Vec<&str> = files.iter().map(|file| file.key.as_str()).collect();
+    assert_eq!(
+        keys,
+        vec![
+            "warehouse/reservations/date=2026-10-16/event_id=concert/reservations-20261016T083000Z.jsonl",
+            "warehouse/reservations/date=2026-10-16/event_id=festival_day-1/reservations-20261016T083000Z.jsonl",
+            "warehouse/area_statuses/date=2026-10-16/event_id=concert/area_statuses-20261016T083000Z.jsonl",
+        ]
+    );
+    assert_eq!(files.iter().map(|file| file.rows).collect::<Vec<_>>(), vec![2, 1, 1]);
+
+    let rows: Vec<serde_json::Value> = std::str::from_utf8(&files[0].data)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(rows[0]["reservation_id"], "res-1");
+    assert_eq!(rows[0]["state"], "Reserved");
+    assert_eq!(rows[0]["seats"], serde_json::json!([{"row": 0, "col": 3}]));
+    let area: serde_json::Value = serde_json::from_slice(&files[2].data).unwrap();
+    assert_eq!((area["available_seats"].as_i64(), area.get("seats")), (Some(10), None));
+
+    assert_eq!("NDJSON".parse::<ExportFormat>().unwrap(), ExportFormat::Jsonl);
+    assert!("csv".parse::<ExportFormat>().is_err());
+    assert_eq!(SnapshotExporter::new(store, ExportFormat::Parquet).is_ok(), ExportFormat::Parquet.is_supported());
+
+    let mut config = ExportConfig::default();
+    assert_eq!(config.interval(), Duration::from_secs(3600));
+    assert!(config.apply_property("export.format", "parquet").unwrap());
+    assert!(config.apply_property("export.s3.bucket", "warehouse").unwrap());
+    assert_eq!((config.format, config.s3_bucket.as_deref()), (ExportFormat::Parquet, Some("warehouse")));
+}
+
+#[tokio::test]
+async fn test_reservation_table_upserts_newer_records_only() {
+    let table = ReservationTable::new("Analytics.reservations").unwrap();
+    assert_eq!(table.name(), "analytics.reservations");
+    let statements = table.create_statements();
+    assert!(statements[0].starts_with("CREATE TABLE IF NOT EXISTS analytics.reservations (reservation_id TEXT PRIMARY KEY"));
+    assert_eq!(statements[1], "CREATE INDEX IF NOT EXISTS reservations_user_id_idx ON analytics.reservations (user_id)");
+
+    let upsert = table.upsert_statement();
+    assert!(upsert.contains("VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"));
+    assert!(upsert.contains("ON CONFLICT (reservation_id) DO UPDATE SET user_id = EXCLUDED.user_id"));
+    assert!(!upsert.contains("reservation_id = EXCLUDED.reservation_id"));
+    assert!(upsert.ends_with("WHERE mirrored.kafka_offset <= EXCLUDED.kafka_offset"));
+
+    // Names are put into SQL as they are, so only plain identifiers pass
+    for invalid in ["", "reservations; DROP TABLE users", "a.b.c", "1reservations", "\"quoted\""] {
+        assert!(ReservationTable::new(invalid).is_err(), "{:?} passed", invalid);
+    }
+
+    let mut config = SinkConfig::default();
+    assert_eq!((config.table(), config.max_connections()), ("reservations", 5));
+    assert!(config.apply_property("sink.postgres.url", "postgres://sink@postgres/ticket_master").unwrap());
+    assert!(config.apply_property("sink.postgres.max.connections", "0").unwrap());
+    assert_eq!(config.max_connections(), 1);
+    assert!(!config.apply_property("sink.url", "postgres://postgres").unwrap());
+}
+
+#[tokio::test]
+async fn test_tombstones_delete_from_read_models() {
+    let temp_dir = tempdir().unwrap();
+    let store = |name: &str| std::sync::Arc::new(RocksDBStore::new(temp_dir.path().join(name)).unwrap());
+    let aggregator = SalesAggregator::new(store("analytics"));
+    let reconciler = SeatReconciler::new(store("reconciliation"));
+    let area_status = AreaStatus::from_layout("concert", &SeatLayout::from(&Area {
+        area_id: "A".to_string(),
+        price: 50,
+        row_count: 2,
+        col_count: 5,
+        strategy: None,
+    }));
+    aggregator.apply_area_status(&area_status).unwrap();
+    reconciler.apply_area_status(&area_status).unwrap();
+
+    let mut reservation = Reservation::new(CreateReservation {
+        reservation_id: "res-1".to_string(),
+        user_id: "ada".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 1,
+        num_of_seat: 0,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        preferences: vec![],
+        priority: ReservationPriority::General,
+    });
+    reservation.transition(ReservationEvent::SeatsReserved { seats: vec![Seat { row: 0, col: 0 }] }).unwrap();
+    aggregator.apply_reservation(&reservation, chrono::Utc::now()).unwrap();
+    reconciler.apply_reservation(&reservation).unwrap();
+    assert_eq!(aggregator.event("concert").unwrap().unwrap().tickets_sold, 1);
+    assert_eq!(reconciler.reconcile().unwrap().counts()[2], ("unmarked_seat", 1));
+
+    // A reservation's tombstone takes back what it counted and held; unknown keys change nothing
+    for reservation_id in ["res-1", "res-1", "res-2"] {
+        aggregator.delete_reservation(reservation_id).unwrap();
+        reconciler.delete_reservation(reservation_id).unwrap();
+    }
+    assert_eq!(aggregator.event("concert").unwrap().unwrap().tickets_sold, 0);
+    assert!(aggregator.hourly("concert").unwrap().is_empty());
+    assert!(reconciler.reconcile().unwrap().discrepancies.is_empty());
+
+    let area = event_area_key("concert", "A");
+    aggregator.delete_area(&area).unwrap();
+    reconciler.delete_area(&area).unwrap();
+    assert!(aggregator.event("concert").unwrap().is_none());
+    assert_eq!(reconciler.reconcile().unwrap().areas_checked, 0);
+
+    // Only reservations holding no seats may be deleted
+    assert!(!reservation.state.is_deletable());
+    reservation.transition(ReservationEvent::Cancelled).unwrap();
+    assert!(reservation.state.is_deletable());
+    let command = ReservationAdminCommand::DeleteReservation { reservation_id: "res-1".to_string() };
+    assert_eq!(
+        serde_json::to_value(&command).unwrap(),
+        serde_json::json!({"command": "delete_reservation", "reservation_id": "res-1"})
+    );
+    assert_eq!(command.key(), "res-1");
+    let command = AreaAdminCommand::DeleteArea { event_id: "concert".to_string(), area_id: "A".to_string() };
+    assert_eq!(command.key(), area);
+
+    assert!(Topics::STATE_TOPICS.contains(&Topics::STATE_USER_RESERVATION));
+    assert_eq!(
+        ReservationTable::new("reservations").unwrap().delete_statement(),
+        "DELETE FROM reservations WHERE reservation_id = $1 AND kafka_offset <= $2"
+    );
+}
+
+#[tokio::test]
+async fn test_state_change_tells_tombstones_from_upserts() {
+    let area_status = AreaStatus::from_layout("concert", &SeatLayout::from(&Area {
+        area_id: "A".to_string(),
+        price: 50,
+        row_count: 1,
+        col_count: 2,
+        strategy: None,
+    }));
+    let message = |key: Option<&str>, payload: Option<Vec<u8>>| KafkaMessage {
+        topic: Topics::STATE_EVENT_AREA_STATUS.to_string(),
+        partition: 0,
+        offset: 0,
+        key: key.map(str::to_string),
+        payload,
+        headers: Vec::new(),
+        serdes: std::sync::Arc::new(TopicSerdes::json()),
+    };
+
+    let upsert = message(Some("concert#A"), Some(serde_json::to_vec(&area_status).unwrap()));
+    match upsert.state_change::<AreaStatus>().await.unwrap() {
+        StateChange::Upsert(decoded) => assert_eq!(decoded.event_id, "concert"),
+        change => panic!("expected an upsert, got {:?}", change),
+    }
+
+    let tombstone = message(Some("concert#A"), None);
+    assert!(matches!(tombstone.state_change::<AreaStatus>().await.unwrap(), StateChange::Delete("concert#A")));
+
+    // Without a key there is nothing to delete
+    assert!(message(None, None).state_change::<AreaStatus>().await.is_err());
+}
+
+#[tokio::test]
+async fn test_user_tokens_name_their_user_until_expiry() {
+    let tokens = UserTokens::new("gateway-secret").unwrap();
+    let now = chrono::Utc::now();
+    let token = tokens.issue("ada.lovelace", chrono::Duration::minutes(15), now);
+    assert_eq!(tokens.verify(&token, now), Some("ada.lovelace".to_string()));
+    assert_eq!(tokens.verify(&token, now + chrono::Duration::minutes(16)), None);
+
+    // Another user's name under the same signature, or another key's tokens, don't pass
+    let forged = token.replacen("ada.lovelace", "grace", 1);
+    assert_eq!(tokens.verify(&forged, now), None);
+    assert_eq!(UserTokens::new("other-secret").unwrap().verify(&token, now), None);
+    assert!(UserTokens::new("").is_err());
+
+    let mut config = GatewayServiceConfig::default();
+    assert!(config.apply_property("gateway.service.ticket.url", "http://ticket-service:8080").unwrap());
+    assert!(config.apply_property("gateway.service.timeout.ms", "2500").unwrap());
+    assert_eq!(config.timeout(), Duration::from_millis(2500));
+    assert!(!config.apply_property("gateway.port", "8084").unwrap());
+}
+
+#[tokio::test]
+async fn test_payments_charge_each_key_once() {
+    let temp_dir = tempdir().unwrap();
+    let payments = Payments::open(temp_dir.path(), MockPaymentProvider).unwrap();
+    let charge = |payment_method: &str| ChargePayment {
+        reservation_id: "res-1".to_string(),
+        user_id: "ada".to_string(),
+        amount: 10000,
+        currency: "usd".to_string(),
+        payment_method: payment_method.to_string(),
+        idempotency_key: ChargePayment::default_idempotency_key("res-1", payment_method),
+    };
+
+    let declined = payments.charge(&charge(MockPaymentProvider::DECLINED_METHOD)).await.unwrap();
+    assert_eq!(declined.status, PaymentStatus::Declined);
+    assert!(declined.reference.is_none());
+
+    let paid = payments.charge(&charge("tok_visa")).await.unwrap();
+    assert_eq!(paid.status, PaymentStatus::Succeeded);
+    assert_eq!(paid.reference.as_deref(), Some("mock_res-1:tok_visa"));
+    // Redelivered, and paid again with another method: the first charge answers both
+    assert_eq!(payments.charge(&charge("tok_visa")).await.unwrap(), paid);
+    assert_eq!(payments.charge(&charge("tok_mastercard")).await.unwrap(), paid);
+
+    let mut config = PaymentConfig::default();
+    assert_eq!(config.currency(), "usd");
+    assert!(config.apply_property("payment.provider", "HTTP").unwrap());
+    assert!(config.apply_property("payment.currency", "EUR").unwrap());
+    assert_eq!((config.provider, config.currency()), (PaymentProviderKind::Http, "eur"));
+    assert!(config.apply_property("payment.provider", "paypal").is_err());
+}
+
+#[tokio::test]
+async fn test_notifier_sends_each_outcome_once_on_every_channel() {
+    #[derive(Clone, Default)]
+    struct Recorded(std::sync::Arc<std::sync::Mutex<Vec<Notification>>>);
+
+    #[async_trait::async_trait]
+    impl NotificationChannel for Recorded {
+        fn name(&self) -> &str {
+            "recorded"
+        }
+
+        async fn send(&self, notification: &Notification) -> ticket_master::Result<()> {
+            self.0.lock().unwrap().push(notification.clone());
+            Ok(())
+        }
+    }
+
+    struct Down;
+
+    #[async_trait::async_trait]
+    impl SmsGateway for Down {
+        async fn send_sms(&self, _user_id: &str, _text: &str) -> ticket_master::Result<()> {
+            Err(TicketMasterError::Notification("gateway down".to_string()))
+        }
+    }
+
+    let mut config = NotificationConfig::default();
+    assert!(config.apply_property("notification.template.paid.subject", "Enjoy {event_id}, {user_id}!").unwrap());
+    assert!(config.apply_property("notification.smtp.security", "tls").unwrap());
+    assert!(!config.apply_property("notification.template.paid.footer", "x").unwrap());
+    assert!(config.apply_property("notification.template.refunded.subject", "x").is_err());
+
+    let temp_dir = tempdir().unwrap();
+    let recorded = Recorded::default();
+    let notifier = Notifier::open(temp_dir.path(), config.templates()).unwrap().with_channel(recorded.clone());
+
+    let mut reservation = Reservation::new(CreateReservation {
+        reservation_id: "res-1".to_string(),
+        user_id: "ada".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 2,
+        num_of_seat: 0,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        preferences: vec![],
+        priority: ReservationPriority::General,
+    });
+    // Nothing to tell while processing
+    assert!(!notifier.notify(&reservation).await.unwrap());
+
+    reservation
+        .transition(ReservationEvent::SeatsReserved { seats: vec![Seat { row: 1, col: 2 }, Seat { row: 1, col: 3 }] })
+        .unwrap();
+    assert!(notifier.notify(&reservation).await.unwrap());
+    // Redelivered
+    assert!(!notifier.notify(&reservation).await.unwrap());
+    reservation.transition(ReservationEvent::PaymentReceived).unwrap();
+    assert!(notifier.notify(&reservation).await.unwrap());
+
+    let sent = recorded.0.lock().unwrap().clone();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0].outcome, NotificationOutcome::Reserved);
+    assert_eq!(sent[0].subject, "Seats reserved for concert");
+    assert!(sent[0].body.contains("row 1 seat 2, row 1 seat 3"), "{}", sent[0].body);
+    assert_eq!(sent[1].subject, "Enjoy concert, ada!");
+
+    // A failing channel doesn't hold back the others, nor is the outcome sent again
+    let notifier = Notifier::open(temp_dir.path().join("failing"), NotificationTemplates::default())
+        .unwrap()
+        .with_channel(SmsChannel::new(Down))
+        .with_channel(recorded.clone());
+    assert!(matches!(notifier.notify(&reservation).await, Err(TicketMasterError::Notification(message)) if message.contains("sms")));
+    assert!(!notifier.notify(&reservation).await.unwrap());
+    assert_eq!(recorded.0.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_heartbeats_make_up_the_cluster_overview() {
+    let mut config = HeartbeatConfig::default();
+    assert!(config.apply_property("heartbeat.interval.ms", "50").unwrap());
+    assert!(config.apply_property("heartbeat.instance.id", "reservation-1").unwrap());
+    assert!(config.apply_property("heartbeat.interval.ms", "0").is_err());
+    assert_eq!(config.interval(), Duration::from_millis(50));
+
+    let kafka = MockKafka::new();
+    kafka.create_topic(Topics::COMMAND_RESERVATION_CREATE_RESERVATION, 2);
+    let producer = kafka.producer();
+    for i in 0..3 {
+        producer
+            .send_bytes(Topics::COMMAND_RESERVATION_CREATE_RESERVATION, Some(i % 2), "key", Some(b"command"))
+            .await
+            .unwrap();
+    }
+    let consumer = kafka.consumer("reservation-service");
+    consumer.subscribe(&[Topics::COMMAND_RESERVATION_CREATE_RESERVATION]).unwrap();
+
+    let publisher = HeartbeatPublisher::new(producer.clone(), "reservation-service", "reservation-1", "1.2.3", config.interval())
+        .with_consumer(std::sync::Arc::new(consumer), &[Topics::COMMAND_RESERVATION_CREATE_RESERVATION]);
+    let heartbeat = publisher.heartbeat().await.unwrap();
+    assert_eq!(heartbeat.partitions[Topics::COMMAND_RESERVATION_CREATE_RESERVATION], vec![0, 1]);
+    assert_eq!(heartbeat.lag[Topics::COMMAND_RESERVATION_CREATE_RESERVATION], 3);
+
+    let directory = ClusterDirectory::default();
+    spawn_cluster_directory(kafka.consumer("cluster-overview"), directory.clone()).unwrap();
+    publisher.start();
+    // An instance that stopped sending without shutting down
+    let stale = InstanceHeartbeat {
+        instance_id: "event-1".to_string(),
+        application_id: "event-service".to_string(),
+        sent_at_ms: chrono::Utc::now().timestamp_millis() - 60_000,
+        ..heartbeat
+    };
+    producer
+        .send_bytes(Topics::STATE_SERVICE_HEARTBEAT, None, "event-1", Some(&serde_json::to_vec(&stale).unwrap()))
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let overview = directory.overview();
+    let instances: Vec<_> = overview.iter().map(|i| (i.heartbeat.instance_id.as_str(), i.alive)).collect();
+    assert_eq!(instances, vec![("event-1", false), ("reservation-1", true)]);
+    assert_eq!(overview[1].heartbeat.version, "1.2.3");
+    assert!(overview[1].age_ms < 200);
+
+    // Shutting down leaves a tombstone, and no more heartbeats
+    publisher.shutdown().await.unwrap();
+    sleep(Duration::from_millis(150)).await;
+    let instances: Vec<_> = directory.overview().into_iter().map(|i| i.heartbeat.instance_id).collect();
+    assert_eq!(instances, vec!["event-1".to_string()]);
+}
+
+#[tokio::test]
+async fn test_consumer_runner_commits_processed_records_and_stops_on_unskippable_failures() {
+    // Records reading "fail" fail; only `strict` processors stop on them
+    struct Recording {
+        seen: std::sync::Mutex<Vec<String>>,
+        strict: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageHandler for Recording {
+        async fn handle(&self, message: &KafkaMessage) -> ticket_master::Result<()> {
+            let payload = String::from_utf8(message.payload.clone().unwrap()).unwrap();
+            if payload == "fail" {
+                return Err(TicketMasterError::InvalidArgument("unreadable".to_string()));
+            }
+            self.seen.lock().unwrap().push(payload);
+            Ok(())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl RecordProcessor for Recording {
+        fn on_failure(&self, e: TicketMasterError) -> ticket_master::Result<()> {
+            if self.strict {
+                return Err(e);
+            }
+            Ok(())
+        }
+    }
+
+    let kafka = MockKafka::new();
+    kafka.create_topic("test-topic", 1);
+    let producer = kafka.producer();
+    for payload in ["first", "fail", "second"] {
+        producer.send_bytes("test-topic", Some(0), "key", Some(payload.as_bytes())).await.unwrap();
+    }
+    let config = ConfigLoader::new("test-service").with_override("commit.interval.ms", "10").load().unwrap();
+    let partition = TopicPartition::new("test-topic", 0);
+
+    // A strict processor stops at the failure, which is read again on restart
+    let strict = Recording { seen: Default::default(), strict: true };
+    let runner = ConsumerRunner::new(&config, kafka.consumer("strict"), &["test-topic"], strict, Metrics::new().unwrap()).unwrap();
+    let (stop, _) = tokio::sync::broadcast::channel::<()>(1);
+    let result = tokio::time::timeout(Duration::from_secs(5), runner.run(stop.subscribe())).await.unwrap();
+    assert!(matches!(result.unwrap_err().root_cause(), TicketMasterError::InvalidArgument(_)));
+    assert_eq!(runner.processor().seen.lock().unwrap()[0], "first");
+    assert_eq!(kafka.committed_offset("strict", &partition), Some(1));
+
+    // Otherwise the failure is skipped and everything is committed by shutdown
+    let lenient = Recording { seen: Default::default(), strict: false };
+    let runner = ConsumerRunner::new(&config, kafka.consumer("lenient"), &["test-topic"], lenient, Metrics::new().unwrap()).unwrap();
+    let run = runner.run(stop.subscribe());
+    let stopper = async {
+        while runner.processor().seen.lock().unwrap().len() < 2 {
+            sleep(Duration::from_millis(5)).await;
+        }
+        stop.send(()).unwrap();
+    };
+    let (result, _) = tokio::join!(run, stopper);
+    result.unwrap();
+    assert_eq!(*runner.processor().seen.lock().unwrap(), ["first", "second"]);
+    assert_eq!(kafka.committed_offset("lenient", &partition), Some(3));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_revoked_partitions_are_drained_and_committed_before_the_rebalance_completes() {
+    // Records are held in flight until the gate opens
+    struct Gated {
+        gate: tokio::sync::Semaphore,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageHandler for Gated {
+        async fn handle(&self, _message: &KafkaMessage) -> ticket_master::Result<()> {
+            self.gate.acquire().await.unwrap().forget();
+            Ok(())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl RecordProcessor for Gated {}
+
+    let kafka = MockKafka::new();
+    kafka.create_topic("test-topic", 1);
+    let producer = kafka.producer();
+    for payload in ["first", "second", "third"] {
+        producer.send_bytes("test-topic", Some(0), payload, Some(payload.as_bytes())).await.unwrap();
+    }
+    let config = ConfigLoader::new("test-service").with_override("commit.interval.ms", "60000").load().unwrap();
+    let partition = TopicPartition::new("test-topic", 0);
+
+    let drain = PartitionDrain::new();
+    let consumer = kafka.consumer_with_rebalance_listener("drained", std::sync::Arc::new(drain.clone()));
+    let gated = Gated { gate: tokio::sync::Semaphore::new(0) };
+    let runner = std::sync::Arc::new(
+        ConsumerRunner::new(&config, consumer, &["test-topic"], gated, Metrics::new().unwrap())
+            .unwrap()
+            .with_partition_drain(drain.clone()),
+    );
+    let (stop, _) = tokio::sync::broadcast::channel::<()>(1);
+    let run = tokio::spawn({
+        let runner = runner.clone();
+        let stop = stop.subscribe();
+        async move { runner.run(stop).await }
+    });
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while drain.in_flight() < 3 {
+            sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    // The revocation waits for the records in flight of the partition
+    let consumer = runner.consumer().clone();
+    let revoke = tokio::task::spawn_blocking(move || consumer.unsubscribe());
+    sleep(Duration::from_millis(50)).await;
+    assert!(!revoke.is_finished());
+    assert_eq!(kafka.committed_offset("drained", &partition), None);
+
+    // and commits them once they are done
+    runner.processor().gate.add_permits(3);
+    revoke.await.unwrap().unwrap();
+    assert_eq!(kafka.committed_offset("drained", &partition), Some(3));
+    assert_eq!(drain.in_flight(), 0);
+
+    stop.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), run).await.unwrap().unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_supervisor_restarts_crashed_loops_with_backoff() {
+    let mut config = SupervisorConfig::default();
+    assert!(config.apply_property("supervisor.max.restarts", "2").unwrap());
+    assert!(config.apply_property("supervisor.initial.backoff.ms", "1").unwrap());
+    assert!(config.apply_property("supervisor.max.backoff.ms", "5").unwrap());
+    let policy = config.policy();
+    assert_eq!(policy.stable_after, RestartPolicy::default().stable_after);
+
+    let metrics = Metrics::new().unwrap();
+    let supervisor = Supervisor::new("event-service", policy).with_metrics(metrics.clone());
+    let (stop, _) = tokio::sync::broadcast::channel::<()>(1);
+
+    // A panic and a failure are restarted until the loop stops cleanly
+    let mut runs = Vec::new();
+    let result = supervisor
+        .run(stop.subscribe(), |restarts| {
+            runs.push(restarts);
+            async move {
+                match restarts {
+                    0 => panic!("lane poisoned"),
+                    1 => Err(TicketMasterError::InvalidArgument("consumer closed".to_string())),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(runs, vec![0, 1, 2]);
+    assert_eq!(metrics.supervisor_restarts.with_label_values(&["event-service", "panic"]).get(), 1.0);
+    assert_eq!(metrics.supervisor_restarts.with_label_values(&["event-service", "error"]).get(), 1.0);
+
+    // A crash loop is given up on with the last failure
+    let result = supervisor
+        .run(stop.subscribe(), |_| async { panic!("still poisoned") })
+        .await;
+    let e = result.unwrap_err();
+    assert!(matches!(e.root_cause(), TicketMasterError::Panicked(message) if message == "still poisoned"));
+    assert_eq!(e.contexts()[0].metadata["crashes"], "3");
+
+    // Shutting down calls off a pending restart
+    let slow = Supervisor::new("reservation-service", RestartPolicy {
+        initial_backoff: Duration::from_secs(60),
+        ..RestartPolicy::default()
+    });
+    let shutdown = stop.subscribe();
+    stop.send(()).unwrap();
+    let result = tokio::time::timeout(
+        Duration::from_secs(1),
+        slow.run(shutdown, |_| async { Err(TicketMasterError::InsufficientSeats) }),
+    )
+    .await;
+    assert!(result.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn test_shutdown_components_leave_the_group_and_stop_servers() {
+    let kafka = MockKafka::new();
+    let consumer = std::sync::Arc::new(kafka.consumer("reservation-service"));
+    consumer.subscribe(&["commands"]).unwrap();
+    assert!(!consumer.assignment().unwrap().is_empty());
+
+    let temp_dir = tempdir().unwrap();
+    let context = std::sync::Arc::new(
+        ProcessingContext::builder(temp_dir.path().to_string_lossy().to_string())
+            .rocksdb_store(Stores::RESERVATION_STORE, "reservations")
+            .build()
+            .unwrap(),
+    );
+    let server = tokio::spawn(std::future::pending::<()>());
+    let server_handle = server.abort_handle();
+
+    let coordinator = ShutdownCoordinator::new(Duration::from_secs(1));
+    let consumer_shutdown = KafkaConsumerShutdown::new(consumer.clone()).with_name("kafka-consumer-commands");
+    assert_eq!(consumer_shutdown.name(), "kafka-consumer-commands");
+    coordinator.register_component(Box::new(consumer_shutdown)).await;
+    coordinator.register_component(Box::new(StateStoresShutdown::new(context))).await;
+    coordinator
+        .register_component(Box::new(HttpServerShutdown::new("metrics-server").with_task(server)))
+        .await;
+    coordinator.shutdown().await.unwrap();
+
+    assert!(consumer.assignment().unwrap().is_empty());
+    assert!(server_handle.is_finished());
+}
+
+#[tokio::test]
+async fn test_contextual_errors_keep_their_source() {
+    use std::error::Error as _;
+
+    let io = TicketMasterError::Io(std::io::Error::other("disk full"));
+    let error = wrap_error(
+        io,
+        ErrorContext::new("put", "reservation-store").with_metadata("key", "r-1").with_metadata("partition", 3),
+    )
+    .with_context(ErrorContext::new("process message", "reservation-service"));
+
+    assert_eq!(
+        error.to_string(),
+        "process message in reservation-service: put in reservation-store (key=r-1, partition=3): IO error: disk full"
+    );
+    assert!(matches!(error.root_cause(), TicketMasterError::Io(_)));
+    assert_eq!(
+        error.source().unwrap().to_string(),
+        "put in reservation-store (key=r-1, partition=3): IO error: disk full"
+    );
+    let components: Vec<&str> = error.contexts().iter().map(|context| context.component.as_str()).collect();
+    assert_eq!(components, vec!["reservation-service", "reservation-store"]);
+    // Still retried as the store error it is
+    assert_eq!(error.transient_operation(), Some(RetryOperation::StateStore));
+
+    let metrics = Metrics::new().unwrap();
+    metrics.record_error(&error);
+    metrics.record_error(&TicketMasterError::InsufficientSeats);
+    assert_eq!(metrics.component_errors.with_label_values(&["reservation-service", "process message"]).get(), 1.0);
+    assert!(metrics.export().unwrap().contains("component_errors_total"));
+}
+
+#[tokio::test]
+async fn test_transient_errors_are_retried_by_their_policy() {
+    let mut config = RetryPoliciesConfig::default();
+    assert!(config.apply_property("retry.kafka.send.max.attempts", "3").unwrap());
+    assert!(config.apply_property("retry.kafka.send.initial.delay.ms", "1").unwrap());
+    assert!(config.apply_property("retry.state.store.initial.delay.ms", "1").unwrap());
+    assert!(!config.apply_property("retry.unknown.max.attempts", "3").unwrap());
+    assert!(config.apply_property("retry.state.store.max.attempts", "0").is_err());
+    let policies = config.policies();
+    assert_eq!(policies.kafka_send.max_attempts, 3);
+    assert_eq!(policies.state_store.max_attempts, RetryConfig::state_store().max_attempts);
+
+    let timed_out = || {
+        TicketMasterError::Kafka(rdkafka::error::KafkaError::MessageProduction(
+            rdkafka::types::RDKafkaErrorCode::MessageTimedOut,
+        ))
+    };
+    assert_eq!(timed_out().transient_operation(), Some(RetryOperation::KafkaSend));
+    assert_eq!(TicketMasterError::CircuitOpen.transient_operation(), None);
+
+    // A send timing out twice succeeds on its third attempt
+    let metrics = Metrics::new().unwrap();
+    let attempts = std::sync::atomic::AtomicU32::new(0);
+    let sent = retry_transient(&policies, "send", Some(&metrics), || async {
+        match attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+            0 | 1 => Err(timed_out()),
+            _ => Ok("sent"),
+        }
+    })
+    .await;
+    assert_eq!(sent.unwrap(), "sent");
+    assert_eq!(metrics.retries.with_label_values(&["kafka_send"]).get(), 2.0);
+
+    // Store IO gives up after its policy's attempts
+    attempts.store(0, std::sync::atomic::Ordering::SeqCst);
+    let stored: Result<()> = retry_transient(&policies, "store", Some(&metrics), || async {
+        attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Err(std::io::Error::other("disk busy").into())
+    })
+    .await;
+    assert!(matches!(stored, Err(TicketMasterError::Io(_))));
+    assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), RetryConfig::state_store().max_attempts);
+    assert_eq!(metrics.retries_exhausted.with_label_values(&["state_store"]).get(), 1.0);
+
+    // Anything else is not retried
+    attempts.store(0, std::sync::atomic::Ordering::SeqCst);
+    let invalid: Result<()> = retry_transient(&policies, "parse", Some(&metrics), || async {
+        attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Err(TicketMasterError::InvalidArgument("bad payload".to_string()))
+    })
+    .await;
+    assert!(invalid.is_err());
+    assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_circuit_breaker_limits_half_open_probes_and_exports_state() {
+    let metrics = Metrics::new().unwrap();
+    let breaker = CircuitBreaker::new(1, Duration::from_millis(20))
+        .with_name("schema_registry")
+        .with_half_open_probes(1)
+        .with_metrics(metrics.clone());
+
+    breaker.permit().unwrap().failed();
+    assert_eq!(breaker.state(), CircuitBreakerState::Open);
+    assert!(matches!(breaker.permit(), Err(TicketMasterError::CircuitOpen)));
+    assert_eq!(metrics.circuit_breaker_state.with_label_values(&["schema_registry"]).get(), 2.0);
+
+    // One probe at a time once the recovery timeout has passed
+    sleep(Duration::from_millis(30)).await;
+    let probe = breaker.permit().unwrap();
+    assert_eq!(breaker.state(), CircuitBreakerState::HalfOpen);
+    assert!(matches!(breaker.permit(), Err(TicketMasterError::CircuitOpen)));
+    assert_eq!(metrics.circuit_breaker_rejections.with_label_values(&["schema_registry"]).get(), 2.0);
+
+    // A failed probe opens it again, a successful one closes it
+    probe.failed();
+    assert_eq!(breaker.state(), CircuitBreakerState::Open);
+    sleep(Duration::from_millis(30)).await;
+    breaker.permit().unwrap().succeeded();
+    assert_eq!(breaker.state(), CircuitBreakerState::Closed);
+    assert_eq!(metrics.circuit_breaker_state.with_label_values(&["schema_registry"]).get(), 0.0);
+
+    // Sends fail fast while the producer's breaker is open
+    let mut config = rdkafka::ClientConfig::new();
+    config.set("bootstrap.servers", "127.0.0.1:1").set("message.timeout.ms", "200");
+    let breaker = std::sync::Arc::new(CircuitBreaker::new(1, Duration::from_secs(60)));
+    let producer = KafkaProducer::new(config).unwrap().with_circuit_breaker(breaker.clone());
+    assert!(matches!(producer.send_bytes("t", None, "k", Some(b"v")).await, Err(TicketMasterError::Kafka(_))));
+    assert_eq!(breaker.state(), CircuitBreakerState::Open);
+    assert!(matches!(producer.send_bytes("t", None, "k", Some(b"v")).await, Err(TicketMasterError::CircuitOpen)));
+}
+
+#[tokio::test]
+async fn test_alert_webhooks_fire_once_and_resolve() {
+    let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::<serde_json::Value>::new()));
+    let sink = received.clone();
+    let router = axum::Router::new().route(
+        "/hook",
+        axum::routing::post(move |axum::Json(body): axum::Json<serde_json::Value>| async move {
+            sink.lock().unwrap().push(body);
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    let properties = std::collections::HashMap::from([
+        ("alert.webhook.url".to_string(), url),
+        ("alert.webhook.format".to_string(), "pagerduty".to_string()),
+        ("alert.pagerduty.routing.key".to_string(), "routing-key".to_string()),
+        ("alert.reservation.failure.rate".to_string(), "0.5".to_string()),
+        ("alert.reservation.min.results".to_string(), "4".to_string()),
+    ]);
+    let config = parse_properties(properties, "reservation-service").unwrap();
+    let notifier = WebhookNotifier::from_config(&config).unwrap().unwrap();
+    let mut monitor = AlertMonitor::new(notifier);
+
+    let metrics = Metrics::new().unwrap();
+    let mut failure_rate = ReservationFailureRate::new(metrics.clone(), 0.5, config.alerts.reservation_min_results());
+    for success in [true, false, false] {
+        metrics.record_reservation_attempt(success, 1);
+    }
+    // Too few results to judge
+    assert_eq!(failure_rate.check(), None);
+    metrics.record_reservation_attempt(false, 0);
+    let check = failure_rate.check().unwrap();
+    assert_eq!(check.problem.as_deref(), Some("75% of the last 4 reservations failed"));
+
+    // Firing twice notifies once
+    monitor.update(vec![check.clone()]).await;
+    monitor.update(vec![check]).await;
+    assert!(monitor.is_firing("reservation_failure_rate"));
+    for _ in 0..4 {
+        metrics.record_reservation_attempt(true, 1);
+    }
+    monitor.update(failure_rate.check().into_iter().collect()).await;
+    assert!(!monitor.is_firing("reservation_failure_rate"));
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0]["event_action"], "trigger");
+    assert_eq!(received[0]["routing_key"], "routing-key");
+    assert_eq!(received[0]["dedup_key"], "reservation-service/reservation_failure_rate");
+    assert_eq!(received[1]["event_action"], "resolve");
+    assert_eq!(received[1]["dedup_key"], received[0]["dedup_key"]);
+}
+
+#[tokio::test]
+async fn test_circuit_breaker_reports_its_state() {
+    let breaker = CircuitBreaker::new(2, Duration::from_millis(50));
+    assert_eq!(breaker.state(), CircuitBreakerState::Closed);
+
+    for _ in 0..2 {
+        let result: Result<()> = breaker.call(|| async { Err(TicketMasterError::StateQuery("down".to_string())) }).await;
+        assert!(result.is_err());
+    }
+    assert_eq!(breaker.state(), CircuitBreakerState::Open);
+    assert_eq!(serde_json::to_value(breaker.state()).unwrap(), "open");
+    let result = breaker.call(|| async { Ok(()) }).await;
+    assert!(matches!(result, Err(TicketMasterError::CircuitOpen)));
+
+    sleep(Duration::from_millis(60)).await;
+    breaker.call(|| async { Ok(()) }).await.unwrap();
+    assert_eq!(breaker.state(), CircuitBreakerState::Closed);
+}
+
+#[tokio::test]
+async fn test_librdkafka_statistics_become_gauges() {
+    use rdkafka::statistics::{Broker, Partition, Statistics, Topic, Window};
+
+    let statistics = Statistics {
+        name: "rdkafka#consumer-1".to_string(),
+        msg_cnt: 12,
+        tx_bytes: 4096,
+        brokers: [
+            ("kafka-1:9092/1".to_string(), Broker {
+                name: "kafka-1:9092/1".to_string(),
+                nodeid: 1,
+                outbuf_cnt: 3,
+                rtt: Some(Window { p50: 2_000, p99: 40_000, ..Default::default() }),
+                ..Default::default()
+            }),
+            ("kafka:9092/bootstrap".to_string(), Broker { nodeid: -1, ..Default::default() }),
+        ].into_iter().collect(),
+        topics: [("orders".to_string(), Topic {
+            partitions: [
+                (0, Partition { partition: 0, consumer_lag: 7, ..Default::default() }),
+                (-1, Partition { partition: -1, consumer_lag: -1, ..Default::default() }),
+            ].into_iter().collect(),
+            ..Default::default()
+        })].into_iter().collect(),
+        ..Default::default()
+    };
+    let metrics = Metrics::new().unwrap();
+    metrics.record_kafka_statistics(&statistics);
+
+    let client = "rdkafka#consumer-1";
+    assert_eq!(metrics.kafka_client_queue_messages.with_label_values(&[client]).get(), 12.0);
+    assert_eq!(metrics.kafka_client_tx_bytes.with_label_values(&[client]).get(), 4096.0);
+    assert_eq!(metrics.kafka_broker_rtt.with_label_values(&[client, "kafka-1:9092/1", "0.99"]).get(), 0.04);
+    assert_eq!(metrics.kafka_broker_outbuf_requests.with_label_values(&[client, "kafka-1:9092/1"]).get(), 3.0);
+    assert_eq!(metrics.kafka_consumer_lag.with_label_values(&[client, "orders", "0"]).get(), 7.0);
+    let exported = metrics.export().unwrap();
+    assert!(!exported.contains("bootstrap"));
+    assert!(!exported.contains(r#"partition="-1""#));
+
+    // Clients emit statistics every 30 seconds unless told otherwise
+    let config = ConfigLoader::new("event-service").load().unwrap();
+    assert_eq!(config.to_kafka_config().get(STATISTICS_INTERVAL_PROPERTY), Some("30000"));
+}
+
+#[tokio::test]
+async fn test_producer_records_sends_in_metrics() {
+    // Nothing listens here, so the send times out
+    let mut config = rdkafka::ClientConfig::new();
+    config.set("bootstrap.servers", "127.0.0.1:1");
+    config.set("message.timeout.ms", "200");
+    let metrics = Metrics::new().unwrap();
+    let producer = KafkaProducer::new(config).unwrap().with_metrics(metrics.clone());
+
+    assert!(producer.send_bytes("orders", None, "key", Some(&[0; 300])).await.is_err());
+    assert_eq!(metrics.kafka_messages_sent.with_label_values(&["orders"]).get(), 1.0);
+    assert_eq!(metrics.kafka_send_duration.with_label_values(&["orders"]).get_sample_count(), 1);
+    assert_eq!(metrics.kafka_sent_bytes.with_label_values(&["orders"]).get_sample_sum(), 300.0);
+    assert_eq!(metrics.kafka_errors.with_label_values(&["orders", "MessageTimedOut"]).get(), 1.0);
+}
+
+#[tokio::test]
+async fn test_metrics_are_labelled_by_route_status_and_error_code() {
+    let metrics = Metrics::new().unwrap();
+    let router = serve_metrics(
+        axum::Router::new().route(
+            "/events/:event_id",
+            axum::routing::get(|| async {
+                let mut response = axum::response::IntoResponse::into_response(axum::http::StatusCode::NOT_FOUND);
+                response.extensions_mut().insert(ErrorCode("event_not_found"));
+                response
+            }),
+        ),
+        metrics.clone(),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    // Routes are labelled by their pattern, not by the path requested
+    for event_id in ["a", "b"] {
+        reqwest::get(format!("{}/events/{}", url, event_id)).await.unwrap();
+    }
+    reqwest::get(format!("{}/nowhere", url)).await.unwrap();
+    assert_eq!(
+        metrics.request_errors.with_label_values(&["/events/:event_id", "404", "event_not_found"]).get(),
+        2.0
+    );
+    assert_eq!(metrics.request_errors.with_label_values(&["unmatched", "404", ""]).get(), 1.0);
+
+    metrics.record_kafka_send("command.event.reserve_seat", 100, Duration::from_millis(3), None);
+    metrics.record_kafka_send("command.event.reserve_seat", 100, Duration::from_millis(3), Some("MessageTimedOut"));
+    metrics.record_kafka_receive("state.event.area_status", 100);
+    assert_eq!(metrics.kafka_messages_sent.with_label_values(&["command.event.reserve_seat"]).get(), 2.0);
+    assert_eq!(
+        metrics.kafka_errors.with_label_values(&["command.event.reserve_seat", "MessageTimedOut"]).get(),
+        1.0
+    );
+    assert_eq!(metrics.kafka_messages_received.with_label_values(&["state.event.area_status"]).get(), 1.0);
+
+    // Stores not told apart are recorded together
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path()).unwrap().with_metrics(metrics.clone(), None);
+    store.put("a", &1i64).unwrap();
+    assert_eq!(metrics.store_operations.with_label_values(&[ALL_STORES, "write"]).get(), 1.0);
+}
+
+#[tokio::test]
+async fn test_log_filter_changes_at_runtime() {
+    // The only test installing the global subscriber
+    let log_filter = init_logging("integration-tests", LogFormat::Json).unwrap();
+
+    log_filter.set(Some("ticket_master=debug")).unwrap();
+    assert_eq!(log_filter.current().unwrap(), "ticket_master=debug");
+    tracing::info_span!(target: "ticket_master", "request", request_id = "req-1", reservation_id = "res-1")
+        .in_scope(|| tracing::debug!(target: "ticket_master", seats = 2, "Logged as JSON"));
+
+    // A bad filter leaves the current one in place
+    assert!(log_filter.set(Some("ticket_master=loudest")).is_err());
+    assert_eq!(log_filter.current().unwrap(), "ticket_master=debug");
+}
+
+#[tokio::test]
+async fn test_profiles_pick_the_config_file_and_defaults() {
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        common: CommonArgs,
+    }
+
+    let temp_dir = tempdir().unwrap();
+    std::fs::write(temp_dir.path().join("client.dev.properties"), "bootstrap.servers=localhost:9092\n").unwrap();
+    std::fs::write(
+        temp_dir.path().join("client.prod.properties"),
+        "bootstrap.servers=prod:9092\ncors.allowed.origins=https://tickets.example.com\n",
+    )
+    .unwrap();
+    let parse = |args: &[&str]| {
+        let mut argv = vec!["ticket-service", "--config-dir", temp_dir.path().to_str().unwrap()];
+        argv.extend_from_slice(args);
+        let args = Args::try_parse_from(argv).unwrap();
+        args.common.load_config(args.common.config_loader("ticket-service")).unwrap()
+    };
+
+    let dev = parse(&[]);
+    assert_eq!(dev.kafka.bootstrap_servers, "localhost:9092");
+    assert!(dev.ticket_service.cors.permissive);
+    assert_eq!(dev.kafka.additional_properties.get("allow.auto.create.topics").map(String::as_str), Some("true"));
+
+    let prod = parse(&["--profile", "prod"]);
+    assert_eq!(prod.kafka.bootstrap_servers, "prod:9092");
+    assert!(!prod.ticket_service.cors.permissive);
+    assert_eq!(prod.kafka.additional_properties.get("allow.auto.create.topics").map(String::as_str), Some("false"));
+
+    // --config replaces the profile's file but keeps its defaults
+    let other = temp_dir.path().join("other.properties");
+    std::fs::write(&other, "bootstrap.servers=other:9092\nallow.auto.create.topics=true\n").unwrap();
+    let config = parse(&["--profile", "prod", "-c", other.to_str().unwrap()]);
+    assert_eq!(config.kafka.bootstrap_servers, "other:9092");
+    assert_eq!(config.kafka.additional_properties.get("allow.auto.create.topics").map(String::as_str), Some("true"));
+
+    let args = Args::try_parse_from(["ticket-service", "--config-dir", temp_dir.path().to_str().unwrap(), "--profile", "staging"]).unwrap();
+    assert!(args.common.load_config(args.common.config_loader("ticket-service")).is_err());
+}
+
+#[tokio::test]
+async fn test_consumer_group_and_offset_reset_are_configurable() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("group.properties");
+    std::fs::write(
+        &config_path,
+        "topic.prefix=staging\ngroup.id=reservations-v2\nauto.offset.reset=none\nconsumers.area-status.auto.offset.reset=latest\n",
+    )
+    .unwrap();
+
+    let defaults = ConfigLoader::new("reservation-service").load().unwrap().to_kafka_config();
+    assert_eq!(defaults.get("auto.offset.reset"), Some("earliest"));
+    assert_eq!(defaults.get("group.instance.id"), None);
+
+    let config = ConfigLoader::new("reservation-service")
+        .with_file(&config_path)
+        .with_env_vars([("GROUP_INSTANCE_ID".to_string(), "reservation-service-0".to_string())])
+        .load()
+        .unwrap();
+    assert_eq!(config.group_id(), "staging.reservations-v2");
+    let client = config.to_kafka_config();
+    assert_eq!(client.get("group.id"), Some("staging.reservations-v2"));
+    assert_eq!(client.get("auto.offset.reset"), Some("error"));
+    assert_eq!(client.get("group.instance.id"), Some("reservation-service-0"));
+
+    // Consumers sharing the group need instance ids of their own
+    let area_status = config.consumer_kafka_config("area-status");
+    assert_eq!(area_status.get("auto.offset.reset"), Some("latest"));
+    assert_eq!(area_status.get("group.instance.id"), Some("reservation-service-0-area-status"));
+    assert_eq!(config.consumer_kafka_config("commands").get("group.instance.id"), Some("reservation-service-0-commands"));
+    assert!(config.kafka.additional_properties.is_empty());
+
+    std::fs::write(&config_path, "auto.offset.reset=sometimes\n").unwrap();
+    assert!(parse_properties_file(&config_path, "reservation-service").is_err());
+}
+
+#[tokio::test]
+async fn test_common_args_load_and_print_config() {
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        common: CommonArgs,
+    }
+
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("service.properties");
+    let stream_config_path = temp_dir.path().join("stream.properties");
+    std::fs::write(&config_path, "bootstrap.servers=kafka:9092\nsasl.password=hunter2\nauth.admin.token=${env:ADMIN_TOKEN}\n").unwrap();
+    std::fs::write(&stream_config_path, "sasl.jaas.config=secret-jaas\nsocket.timeout.ms=5000\n").unwrap();
+
+    let args = Args::try_parse_from([
+        "event-service".as_ref(),
+        "-c".as_ref(),
+        config_path.as_os_str(),
+        "--stream-config".as_ref(),
+        stream_config_path.as_os_str(),
+        "-d".as_ref(),
+        temp_dir.path().join("state").as_os_str(),
+        "--print-config".as_ref(),
+    ])
+    .unwrap();
+    assert!(args.common.print_config);
+    let config = args.common.load_config(args.common.config_loader("event-service")).unwrap();
+    assert_eq!(config.kafka.bootstrap_servers, "kafka:9092");
+    assert_eq!(config.state_dir, temp_dir.path().join("state").to_string_lossy());
+    assert_eq!(config.kafka.additional_properties.get("socket.timeout.ms").map(String::as_str), Some("5000"));
+
+    let printed = redacted_config(&config).unwrap();
+    assert!(!printed.contains("hunter2") && !printed.contains("secret-jaas"));
+    assert!(printed.contains("${env:ADMIN_TOKEN}"));
+    assert!(printed.contains("kafka:9092"));
+
+    // Help is clap's own, not a flag of the service
+    assert!(Args::try_parse_from(["event-service", "-h"]).is_err_and(|e| e.kind() == clap::error::ErrorKind::DisplayHelp));
+}
+
+#[tokio::test]
+async fn test_config_watcher_applies_runtime_settings() {
+    const CACHE: StoreHandle<String, i64> = StoreHandle::new("cache");
+
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("service.properties");
+    std::fs::write(&config_path, "state.cache.capacity=10\nwaiting.room.admission.rate=10\n").unwrap();
+    let loader = ConfigLoader::new("ticket-service").with_file(&config_path);
+    let config = loader.clone().load().unwrap();
+    let watcher = ConfigWatcher::start(loader, &config).unwrap();
+    assert_eq!(watcher.settings().store_cache_capacity, 10);
+
+    let context = std::sync::Arc::new(ProcessingContext::new());
+    context.add_cache_store(&CACHE, 10, Duration::from_secs(60));
+    let room = std::sync::Arc::new(WaitingRoom::new(10));
+    let (applied_context, applied_room) = (context.clone(), room.clone());
+    watcher.on_change(move |settings| {
+        applied_context.set_cache_capacity(settings.store_cache_capacity);
+        applied_room.set_admission_rate(settings.waiting_room_admission_rate.unwrap());
+    });
+
+    let mut settings = watcher.subscribe();
+    // Other files in the directory are ignored
+    std::fs::write(temp_dir.path().join("other.properties"), "state.cache.capacity=1\n").unwrap();
+    std::fs::write(&config_path, "state.cache.capacity=2\nwaiting.room.admission.rate=50\nlog.filter=debug\n").unwrap();
+    tokio::time::timeout(Duration::from_secs(5), settings.changed()).await.unwrap().unwrap();
+    let applied = settings.borrow_and_update().clone();
+    assert_eq!(applied.store_cache_capacity, 2);
+    assert_eq!(applied.waiting_room_admission_rate, Some(50));
+    assert_eq!(applied.log_filter.as_deref(), Some("debug"));
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(room.admission_rate(), 50);
+    let StateStoreBackend::Cache(cache) = context.store(&CACHE).unwrap() else {
+        panic!("expected a cache store");
+    };
+    assert_eq!(cache.capacity(), 2);
+    for key in ["a", "b", "c"] {
+        cache.put(key.to_string(), 1);
+    }
+    assert_eq!(cache.len(), 2);
+    // Caches added later take the new capacity too
+    const LATER: StoreHandle<String, i64> = StoreHandle::new("later");
+    context.add_cache_store(&LATER, 10, Duration::from_secs(60));
+    let StateStoreBackend::Cache(later) = context.store(&LATER).unwrap() else {
+        panic!("expected a cache store");
+    };
+    assert_eq!(later.capacity(), 2);
+
+    // A file that fails to load keeps the settings
+    std::fs::write(&config_path, "state.cache.capacity=2\narea.status.snapshot.interval=0\n").unwrap();
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(watcher.settings(), applied);
+}
+
+#[tokio::test]
+async fn test_secrets_from_files_and_providers() {
+    struct StaticSecrets;
+
+    #[async_trait::async_trait]
+    impl SecretProvider for StaticSecrets {
+        fn scheme(&self) -> &str {
+            "static"
+        }
+
+        async fn get(&self, reference: &str) -> ticket_master::Result<String> {
+            Ok(format!("secret-{}", reference))
+        }
+    }
+
+    let temp_dir = tempdir().unwrap();
+    let password_path = temp_dir.path().join("kafka-password");
+    std::fs::write(&password_path, "from-file\n").unwrap();
+    let registry_auth_path = temp_dir.path().join("registry-auth");
+    std::fs::write(&registry_auth_path, "registry-user:registry-pass").unwrap();
+    let config_path = temp_dir.path().join("secrets.properties");
+    std::fs::write(&config_path, format!(
+        "sasl.username=${{static:user}}\nsasl.password=plain\nsasl.password.file={}\nschema.registry.basic.auth.file={}\nauth.admin.token=${{file:{}}}\nssl.key.password=${{env:TEST_SECRETS_KEY_PASSWORD}}\n",
+        password_path.display(), registry_auth_path.display(), password_path.display(),
+    )).unwrap();
+
+    let mut config = parse_properties_file(&config_path, "ticket-service").unwrap();
+    assert_eq!(config.kafka.sasl_password.as_deref(), Some("from-file"));
+    assert_eq!(config.kafka.schema_registry_credentials(), Some(("registry-user", Some("registry-pass"))));
+    assert!(!config.kafka.additional_properties.contains_key("sasl.password.file"));
+
+    std::env::set_var("TEST_SECRETS_KEY_PASSWORD", "from-env");
+    SecretResolver::default().with_provider(StaticSecrets).resolve_config(&mut config).await.unwrap();
+    assert_eq!(config.kafka.sasl_username.as_deref(), Some("secret-user"));
+    assert_eq!(config.admin_token.as_deref(), Some("from-file"));
+    assert_eq!(config.kafka.ssl_key_password.as_deref(), Some("from-env"));
+
+    let resolver = SecretResolver::default();
+    assert_eq!(resolver.resolve("not-a-reference").await.unwrap(), "not-a-reference");
+    assert!(resolver.resolve("${static:user}").await.is_err());
+    assert!(parse_properties(
+        std::collections::HashMap::from([("sasl.password.file".to_string(), temp_dir.path().join("missing").display().to_string())]),
+        "ticket-service",
+    ).is_err());
+}
+
+#[tokio::test]
+async fn test_config_loader_reads_yaml_and_toml() {
+    let temp_dir = tempdir().unwrap();
+    let yaml_path = temp_dir.path().join("services.yaml");
+    std::fs::write(&yaml_path, r#"
+kafka:
+  bootstrap.servers: yaml:9092
+  socket.timeout.ms: 5000
+sasl:
+  username: yaml-user
+cors:
+  allowed:
+    origins:
+      - https://tickets.example.com
+      - https://admin.example.com
+metrics.port: 9100
+services:
+  event-service:
+    metrics.port: 9101
+  reservation-service:
+    metrics.port: 9102
+"#).unwrap();
+
+    let config = ConfigLoader::new("event-service").with_file(&yaml_path).load().unwrap();
+    assert_eq!(config.kafka.bootstrap_servers, "yaml:9092");
+    assert_eq!(config.kafka.sasl_username.as_deref(), Some("yaml-user"));
+    assert_eq!(config.kafka.additional_properties.get("socket.timeout.ms").map(String::as_str), Some("5000"));
+    assert_eq!(config.ticket_service.cors.allowed_origins, vec!["https://tickets.example.com", "https://admin.example.com"]);
+    assert_eq!(config.metrics_port, Some(9101));
+    let config = ConfigLoader::new("ticket-service").with_file(&yaml_path).load().unwrap();
+    assert_eq!(config.metrics_port, Some(9100));
+
+    let toml_path = temp_dir.path().join("services.toml");
+    std::fs::write(&toml_path, r#"
+state.dir = "/var/lib/reservation-service"
+
+[kafka]
+"bootstrap.servers" = "toml:9092"
+
+[consumers.commands]
+parallelism = 4
+"#).unwrap();
+    let config = ConfigLoader::new("reservation-service")
+        .with_file(&toml_path)
+        .with_env_vars(vec![("BOOTSTRAP_SERVERS".to_string(), "env:9092".to_string())])
+        .load()
+        .unwrap();
+    assert_eq!(config.kafka.bootstrap_servers, "env:9092");
+    assert_eq!(config.state_dir, "/var/lib/reservation-service");
+    assert_eq!(config.consumers.consumers.get("commands").and_then(|consumer| consumer.parallelism), Some(4));
+    assert!(config.kafka.additional_properties.is_empty());
+    assert_eq!(parse_properties_file(&toml_path, "reservation-service").unwrap().kafka.bootstrap_servers, "toml:9092");
+}
+
+#[tokio::test]
+async fn test_config_loader_layers_env_over_file() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("layered.properties");
+    std::fs::write(&config_path, "bootstrap.servers=file:9092\nsasl.username=file-user\nmetrics.port=9100\n").unwrap();
+    let env = |vars: &[(&str, &str)]| vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<Vec<_>>();
+
+    let config = ConfigLoader::new("event-service")
+        .with_file(&config_path)
+        .with_env_vars(env(&[
+            ("BOOTSTRAP_SERVERS", "env:9092"),
+            ("SASL_PASSWORD", "from-env"),
+            ("STATE_DIR", "/var/lib/event-service"),
+            ("METRICS_PORT", "9200"),
+            ("HOME", "/root"),
+            ("PATH", "/usr/bin"),
+        ]))
+        .with_override("metrics.port", "9300")
+        .load()
+        .unwrap();
+    assert_eq!(config.application_id, "event-service");
+    assert_eq!(config.kafka.bootstrap_servers, "env:9092");
+    assert_eq!(config.kafka.sasl_username.as_deref(), Some("file-user"));
+    assert_eq!(config.kafka.sasl_password.as_deref(), Some("from-env"));
+    assert_eq!(config.state_dir, "/var/lib/event-service");
+    assert_eq!(config.metrics_port, Some(9300));
+    // Variables naming no known property aren't passed on to Kafka
+    assert!(config.kafka.additional_properties.is_empty());
+
+    let defaults = ConfigLoader::new("ticket-service").load().unwrap();
+    assert_eq!(defaults.state_dir, "/tmp/kafka-streams");
+    assert_eq!(defaults.kafka.bootstrap_servers, KafkaConfig::default().bootstrap_servers);
+    assert!(ConfigLoader::new("ticket-service")
+        .with_env_vars(env(&[("AREA_STATUS_SNAPSHOT_INTERVAL", "0")]))
+        .load()
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_failed_reason_keeps_error_code() {
+    let mut reservation = Reservation::new(CreateReservation {
+        reservation_id: "res-1".to_string(),
+        user_id: "user-1".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 1,
+        num_of_seat: 0,
+        reservation_type: ReservationType::SelfPick,
+        seats: vec![Seat { row: 0, col: 0 }],
+        preferences: vec![],
+        priority: ReservationPriority::General,
+    });
+    reservation
+        .update_from_result(&ReservationResult {
+            reservation_id: "res-1".to_string(),
+            result: ReservationResultEnum::Failed,
+            error_code: Some(ReservationErrorCode::SeatNotAvailable),
+            error_message: Some("Seat not available: row 0, col 0".to_string()),
+            seats: vec![],
+        })
+        .unwrap();
+
+    let json = serde_json::to_value(&reservation).unwrap();
+    assert_eq!(json["failed_reason"]["code"], "SeatNotAvailable");
+    assert_eq!(json["failed_reason"]["message"], "Seat not available: row 0, col 0");
+    let decoded = Reservation::decode_protobuf(&reservation.encode_protobuf()).unwrap();
+    assert!(matches!(
+        decoded.failed_reason.and_then(|reason| reason.code),
+        Some(ReservationErrorCode::SeatNotAvailable)
+    ));
+
+    // Reservations stored with only a message, in either codec, still decode
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path()).unwrap();
+    store.put("res-1", &reservation).unwrap();
+    let stored: Reservation = store.get("res-1").unwrap().unwrap();
+    assert!(matches!(stored.failed_reason.unwrap().code, Some(ReservationErrorCode::SeatNotAvailable)));
+
+    #[derive(serde::Serialize)]
+    struct LegacyReservation {
+        reservation_id: String,
+        user_id: String,
+        event_id: String,
+        area_id: String,
+        num_of_seats: i32,
+        num_of_seat: i32,
+        reservation_type: ReservationType,
+        seats: Vec<Seat>,
+        state: ReservationState,
+        failed_reason: String,
+    }
+    let legacy = |failed_reason: &str| LegacyReservation {
+        reservation_id: "res-2".to_string(),
+        user_id: "user-1".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 1,
+        num_of_seat: 0,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        state: ReservationState::Failed,
+        failed_reason: failed_reason.to_string(),
+    };
+    let bincode = StoreCodec::Bincode.encode(&legacy("Not enough seats available")).unwrap();
+    let reason = StoreCodec::decode::<Reservation>(&bincode).unwrap().failed_reason.unwrap();
+    assert!(reason.code.is_none());
+    assert_eq!(reason.message, "Not enough seats available");
+    let json = StoreCodec::Json.encode(&legacy("")).unwrap();
+    assert!(StoreCodec::decode::<Reservation>(&json).unwrap().failed_reason.is_none());
+}
+
+#[tokio::test]
+async fn test_check_availability_leaves_area_untouched() {
+    let mut area_status = CompactAreaStatus {
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        price: 100,
+        seats: SeatBitmap::new(2, 5),
+    };
+    // Row 0 is split in two, row 1 only has 3 seats left
+    area_status.reserve(&[Seat { row: 0, col: 2 }, Seat { row: 1, col: 0 }, Seat { row: 1, col: 1 }]);
+    let before = area_status.clone();
+    let strategies = StrategyRegistry::default();
+    let request = |num_of_seats: i32, reservation_type: ReservationType| CheckAvailability {
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats,
+        reservation_type,
+        preferences: vec![],
+    };
+
+    let three = strategies.check_availability(None, &area_status, &request(3, ReservationType::ContinuousRandom)).unwrap();
+    assert!(three.available && three.adjacent);
+    assert!(three.seats.iter().all(|seat| seat.row == 1));
+    assert_eq!(three.available_seats, 7);
+
+    // No row has 4 adjacent seats, so continuous random falls back to random seats
+    let four = strategies.check_availability(None, &area_status, &request(4, ReservationType::ContinuousRandom)).unwrap();
+    assert!(four.available && !four.adjacent);
+    assert_eq!(four.seats.len(), 4);
+
+    let eight = strategies.check_availability(Some(CONTINUOUS_RANDOM_STRATEGY), &area_status, &request(8, ReservationType::Random)).unwrap();
+    assert!(!eight.available);
+    assert!(eight.seats.is_empty());
+    assert!(matches!(eight.error_code, Some(ReservationErrorCode::InsufficientSeats)));
+
+    assert!(strategies.check_availability(None, &area_status, &request(1, ReservationType::SelfPick)).is_err());
+    assert_eq!(area_status, before);
+}
+
+#[tokio::test]
+async fn test_dispatcher_takes_priority_messages_first() {
+    struct Recorder {
+        started: tokio::sync::Notify,
+        release: tokio::sync::Semaphore,
+        handled: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageHandler for Recorder {
+        async fn handle(&self, message: &KafkaMessage) -> Result<()> {
+            // Hold the lane on the first message until everything else is queued
+            if message.offset == 0 {
+                self.started.notify_one();
+                self.release.acquire().await.unwrap().forget();
+            }
+            self.handled.lock().unwrap().push(message.key.clone().unwrap());
+            Ok(())
+        }
+    }
+
+    let message = |offset: i64, key: &str, priority: ReservationPriority| KafkaMessage {
+        topic: Topics::COMMAND_EVENT_RESERVE_SEAT.to_string(),
+        partition: 0,
+        offset,
+        key: Some(key.to_string()),
+        payload: None,
+        headers: vec![(PRIORITY_HEADER.to_string(), priority.as_str().as_bytes().to_vec())],
+        serdes: std::sync::Arc::new(TopicSerdes::json()),
+    };
+    let handler = std::sync::Arc::new(Recorder {
+        started: tokio::sync::Notify::new(),
+        release: tokio::sync::Semaphore::new(0),
+        handled: std::sync::Mutex::new(Vec::new()),
+    });
+    let (dispatcher, mut completed) = KeyedDispatcher::new(1, handler.clone());
+
+    dispatcher.dispatch(message(0, "general-0", ReservationPriority::General)).await.unwrap();
+    handler.started.notified().await;
+    dispatcher.dispatch(message(1, "general-1", ReservationPriority::General)).await.unwrap();
+    dispatcher.dispatch(message(2, "presale-2", ReservationPriority::Priority)).await.unwrap();
+    dispatcher.dispatch(message(3, "general-3", ReservationPriority::General)).await.unwrap();
+    dispatcher.dispatch(message(4, "presale-4", ReservationPriority::Priority)).await.unwrap();
+    handler.release.add_permits(1);
+    for _ in 0..5 {
+        completed.recv().await.unwrap().result.unwrap();
+    }
+    dispatcher.shutdown().await;
+
+    assert_eq!(
+        *handler.handled.lock().unwrap(),
+        vec!["general-0", "presale-2", "presale-4", "general-1", "general-3"]
+    );
+    // Messages without the header stay general
+    let mut plain = message(5, "plain", ReservationPriority::Priority);
+    plain.headers.clear();
+    assert_eq!(plain.priority(), ReservationPriority::General);
+
+    let create = CreateReservation {
+        reservation_id: "res-1".to_string(),
+        user_id: "user-1".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 2,
+        num_of_seat: 0,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        preferences: vec![],
+        priority: ReservationPriority::Priority,
+    };
+    let decoded = CreateReservation::decode_protobuf(&create.encode_protobuf()).unwrap();
+    assert_eq!(decoded.priority, ReservationPriority::Priority);
+    let legacy: CreateReservation = serde_json::from_str(
+        r#"{"reservation_id":"res-2","user_id":"u","event_id":"e","area_id":"A","num_of_seats":1,"num_of_seat":0,"reservation_type":"Random","seats":[]}"#,
+    )
+    .unwrap();
+    assert_eq!(legacy.priority, ReservationPriority::General);
+}
+
+#[tokio::test]
+async fn test_consumers_take_their_own_group_and_parallelism() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("consumers.properties");
+    std::fs::write(
+        &config_path,
+        "topic.prefix=staging\nprocessing.parallelism=4\nconsumers.results.group.id=reservation-results\nconsumers.commands.parallelism=16\n",
+    )
+    .unwrap();
+    let config = parse_properties_file(&config_path, "reservation-service").unwrap();
+
+    assert_eq!(config.consumer_parallelism("commands"), 16);
+    assert_eq!(config.consumer_parallelism("results"), 4);
+    assert_eq!(
+        config.consumer_kafka_config("results").get("group.id"),
+        Some("staging.reservation-results")
+    );
+    // Unconfigured consumers stay in the application's group
+    assert_eq!(
+        config.consumer_kafka_config("commands").get("group.id"),
+        config.to_kafka_config().get("group.id")
+    );
+
+    std::fs::write(&config_path, "consumers.results.parallelism=many\n").unwrap();
+    assert!(parse_properties_file(&config_path, "reservation-service").is_err());
+}
+
+#[tokio::test]
+async fn test_versioned_store_bumps_version_without_rewriting_value() {
+    let temp_dir = tempdir().unwrap();
+    let store = VersionedStore::new(std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap()));
+    store.put_with("event1#A", &10, |_| Ok(())).unwrap();
+
+    // A change kept under its own key, as event-service records seat events
+    let version = store
+        .write_with("event1#A", Some(1), None::<&i32>, |batch, version| {
+            batch.put(&format!("event#{}", version), &-1)?;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(version, 2);
+    assert_eq!(store.get::<i32>("event1#A").unwrap(), Some(Versioned { value: 10, version: 2 }));
+    assert_eq!(store.store().get::<i32>("event#2").unwrap(), Some(-1));
+    assert!(store.write_with("event1#A", Some(1), Some(&9), |_, _| Ok(())).is_err());
+
+    let config_path = temp_dir.path().join("event-sourcing.properties");
+    std::fs::write(&config_path, "area.status.event.sourcing.enabled=true\narea.status.snapshot.interval=20\n").unwrap();
+    let config = parse_properties_file(&config_path, "event-service").unwrap();
+    assert!(config.event_sourcing.enabled);
+    assert_eq!(config.event_sourcing.snapshot_interval, 20);
+    assert!(!EventSourcingConfig::default().enabled);
+
+    std::fs::write(&config_path, "area.status.snapshot.interval=0\n").unwrap();
+    assert!(parse_properties_file(&config_path, "event-service").is_err());
+}
+
+#[tokio::test]
+async fn test_audit_log_keeps_each_change_once() {
+    let kafka = MockKafka::new();
+    kafka.create_topic(Topics::STATE_AUDIT, 2);
+    let temp_dir = tempdir().unwrap();
+    let log = AuditLog::new(std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap()));
+    let consumer = spawn_audit_log(kafka.consumer("ticket-service-audit"), log.clone()).unwrap();
+
+    let mut reservation = Reservation::new(CreateReservation {
+        reservation_id: "res-1".to_string(),
+        user_id: "user-1".to_string(),
+        event_id: "event-1".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 1,
+        num_of_seat: 0,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        preferences: vec![],
+        priority: ReservationPriority::General,
+    });
+    let created = AuditRecord::reservation_created("res-1", "user-1", &reservation.state);
+    let transition = reservation
+        .transition(ReservationEvent::SeatsReserved { seats: vec![Seat { row: 0, col: 0 }] })
+        .unwrap();
+    let reserved = AuditRecord::from_transition(&transition, "event-service");
+    let producer = kafka.producer();
+    for record in [&created, &reserved, &reserved] {
+        send_audit_record(&producer, record).await.unwrap();
+    }
+    let area = AuditRecord::new(AuditEntity::Area, "event-1#A", "reserve#res-1", "res-1", "SeatsReserved")
+        .with_change(Some("4 available".to_string()), "3 available".to_string());
+    send_audit_record(&producer, &area).await.unwrap();
+
+    let mut history = Vec::new();
+    for _ in 0..100 {
+        history = log.history(AuditEntity::Reservation, "res-1").unwrap();
+        if history.len() == 2 && !log.history(AuditEntity::Area, "event-1#A").unwrap().is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(history, vec![created, reserved.clone()]);
+    assert_eq!(reserved.actor, "event-service");
+    assert_eq!((reserved.from.as_deref(), reserved.to.as_str()), (Some("Processing"), "Reserved"));
+    assert_eq!(log.history(AuditEntity::Area, "event-1#A").unwrap(), vec![area]);
+    assert!(log.history(AuditEntity::Reservation, "res-2").unwrap().is_empty());
+    consumer.abort();
+}
+
+#[tokio::test]
+async fn test_reservation_rejects_illegal_transitions() {
+    let mut reservation = Reservation::new(CreateReservation {
+        reservation_id: "res-1".to_string(),
+        user_id: "user-1".to_string(),
+        event_id: "event-1".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 1,
+        num_of_seat: 0,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        preferences: vec![],
+        priority: ReservationPriority::General,
+    });
+
+    let reserved = reservation
+        .transition(ReservationEvent::SeatsReserved { seats: vec![Seat { row: 0, col: 3 }] })
+        .unwrap();
+    assert_eq!((reserved.from, reserved.to), (ReservationState::Processing, ReservationState::Reserved));
+    assert_eq!(reservation.seats.len(), 1);
+
+    // A late failure doesn't undo the reservation
+    let late = reservation.transition(ReservationEvent::ReservationFailed {
+        reason: FailedReason { code: Some(ReservationErrorCode::Timeout), message: "timeout".to_string() },
+    });
+    assert!(matches!(late, Err(TicketMasterError::IllegalTransition { .. })));
+    assert_eq!(reservation.state, ReservationState::Reserved);
+    assert!(reservation.failed_reason.is_none());
+
+    let paid = reservation.transition(ReservationEvent::PaymentReceived).unwrap();
+    assert_eq!(paid.to, ReservationState::Paid);
+    assert!(reservation.transition(ReservationEvent::Cancelled).is_err());
+    assert_eq!(ReservationState::Paid.next(&ReservationEvent::SeatsReserved { seats: vec![] }), None);
+}
+
+#[tokio::test]
+async fn test_deadline_index_returns_due_ids_earliest_first() {
+    let temp_dir = tempdir().unwrap();
+    let deadlines = DeadlineIndex::new(std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap()));
+    let late = deadlines.schedule("reservation-3", 3_000).unwrap();
+    let first = deadlines.schedule("reservation-1", 1_000).unwrap();
+    let second = deadlines.schedule("reservation-2", 2_000).unwrap();
+    // A due time past a power of ten still sorts after the shorter ones
+    deadlines.schedule("reservation-4", 10_000).unwrap();
+
+    assert_eq!(deadlines.due(2_000, 10).unwrap(), vec![first.clone(), second.clone()]);
+    assert_eq!(deadlines.due(5_000, 1).unwrap(), vec![first.clone()]);
+
+    deadlines.remove(&first).unwrap();
+    assert_eq!(deadlines.due(5_000, 10).unwrap(), vec![second, late]);
+    assert!(deadlines.due(500, 10).unwrap().is_empty());
+
+    let timed_out = ReservationResult {
+        reservation_id: "reservation-1".to_string(),
+        result: ReservationResultEnum::Failed,
+        error_code: Some(ReservationErrorCode::Timeout),
+        error_message: Some("No reservation result within 30000ms".to_string()),
+        seats: vec![],
+    };
+    let decoded = ReservationResult::decode_protobuf(&timed_out.encode_protobuf()).unwrap();
+    assert!(matches!(decoded.error_code, Some(ReservationErrorCode::Timeout)));
+}
+
+#[tokio::test]
+async fn test_health_router_is_not_ready_until_warmed_up() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("warmup.properties");
+    std::fs::write(&config_path, "warmup.areas=concert#A, concert#B,\nwarmup.recent.areas=5\n").unwrap();
+    let config = parse_properties_file(&config_path, "event-service").unwrap();
+    assert_eq!(config.warmup.areas, vec!["concert#A", "concert#B"]);
+    assert_eq!(config.warmup.recent_areas, 5);
+    assert_eq!(WarmupConfig::default().recent_areas, 100);
+    std::fs::write(&config_path, "warmup.recent.areas=all\n").unwrap();
+    assert!(parse_properties_file(&config_path, "event-service").is_err());
+
+    let serve = |check: StubCheck| async move {
+        let probe = ReadinessProbe::new(Duration::from_millis(100)).with_check(check);
+        let router = serve_metrics(health_router(probe), Metrics::new().unwrap());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        url
+    };
+
+    let warming = serve(StubCheck { name: "area_status_warmup", delay: Duration::ZERO, error: Some("still loading") }).await;
+    let response = reqwest::get(format!("{}/health/ready", warming)).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["dependencies"][0]["error"], "still loading");
+    let response = reqwest::get(format!("{}/health/live", warming)).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let warmed = serve(StubCheck { name: "area_status_warmup", delay: Duration::ZERO, error: None }).await;
+    let response = reqwest::get(format!("{}/health/ready", warmed)).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let response = reqwest::get(format!("{}/metrics", warmed)).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_random_strategies_score_seats_by_preference() {
+    let area = Area {
+        area_id: "A".to_string(),
+        price: 100,
+        row_count: 5,
+        col_count: 9,
+        strategy: None,
+    };
+    let area_status = CompactAreaStatus::from(&AreaStatus::from_area("concert", &area));
+    let request = |reservation_type: ReservationType, preferences: Vec<SeatPreference>| ReserveSeat {
+        reservation_id: "r1".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 3,
+        num_of_seat: 3,
+        reservation_type,
+        seats: vec![],
+        preferences,
+    };
+    let cols = |seats: &[Seat]| {
+        let mut cols: Vec<i32> = seats.iter().map(|seat| seat.col).collect();
+        cols.sort();
+        cols
+    };
+
+    // Side by side in one row beats spread out, and centered beats off to a side
+    let together = [Seat { row: 2, col: 3 }, Seat { row: 2, col: 4 }, Seat { row: 2, col: 5 }];
+    let spread = [Seat { row: 2, col: 3 }, Seat { row: 2, col: 5 }, Seat { row: 3, col: 4 }];
+    let side = [Seat { row: 2, col: 0 }, Seat { row: 2, col: 1 }, Seat { row: 2, col: 2 }];
+    assert!(score_seats(&area_status, &together, &[]) > score_seats(&area_status, &spread, &[]));
+    assert!(score_seats(&area_status, &together, &[]) > score_seats(&area_status, &side, &[]));
+    assert!(score_seats(&area_status, &side, &[SeatPreference::Aisle]) > score_seats(&area_status, &together, &[SeatPreference::Aisle]));
+
+    let registry = StrategyRegistry::default();
+    let reserve = |request: &ReserveSeat| {
+        let strategy = registry.select(None, request).unwrap();
+        let result = strategy.reserve(&mut area_status.clone(), request).unwrap();
+        assert_eq!(result.result, ReservationResultEnum::Success);
+        result.seats
+    };
+
+    let seats = reserve(&request(ReservationType::ContinuousRandom, vec![]));
+    assert_eq!(cols(&seats), vec![3, 4, 5]);
+
+    let seats = reserve(&request(ReservationType::ContinuousRandom, vec![SeatPreference::Front]));
+    assert!(seats.iter().all(|seat| seat.row == 0));
+    assert_eq!(cols(&seats), vec![3, 4, 5]);
+
+    let seats = reserve(&request(ReservationType::ContinuousRandom, vec![SeatPreference::Aisle]));
+    assert!(cols(&seats) == vec![0, 1, 2] || cols(&seats) == vec![6, 7, 8]);
+
+    // Random picks side by side seats when asked to keep the party together
+    let seats = reserve(&request(ReservationType::Random, vec![SeatPreference::Together]));
+    assert!(seats.iter().all(|seat| seat.row == seats[0].row));
+    let seat_cols = cols(&seats);
+    assert_eq!(seat_cols[2] - seat_cols[0], 2);
+
+    let decoded = ReserveSeat::decode_protobuf(
+        &request(ReservationType::Random, vec![SeatPreference::Aisle, SeatPreference::Front]).encode_protobuf(),
+    )
+    .unwrap();
+    assert_eq!(decoded.preferences, vec![SeatPreference::Aisle, SeatPreference::Front]);
+    assert_eq!(SeatPreference::parse("Together"), Some(SeatPreference::Together));
+    assert_eq!(SeatPreference::parse("middle"), None);
+}
+
+#[tokio::test]
+async fn test_continuous_random_reservations_fall_back_to_random_seats() {
+    let area = Area {
+        area_id: "A".to_string(),
+        price: 100,
+        row_count: 2,
+        col_count: 4,
+        strategy: None,
+    };
+    let mut area_status = CompactAreaStatus::from(&AreaStatus::from_area("concert", &area));
+    let request = ReserveSeat {
+        reservation_id: "r1".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 3,
+        num_of_seat: 3,
+        reservation_type: ReservationType::ContinuousRandom,
+        seats: vec![],
+        preferences: vec![],
+    };
+    let registry = StrategyRegistry::default();
+    let strategy = registry.select(None, &request).unwrap();
+
+    // Three side by side while a row has them
+    let result = strategy.reserve(&mut area_status, &request).unwrap();
+    assert_eq!(result.result, ReservationResultEnum::Success);
+    let mut cols: Vec<i32> = result.seats.iter().map(|seat| seat.col).collect();
+    cols.sort();
+    assert!(result.seats.iter().all(|seat| seat.row == result.seats[0].row));
+    assert_eq!(cols[2] - cols[0], 2);
+
+    // Rows broken up: a pair and two singles are left, no three side by side
+    area_status.reserve(&result.seats);
+    let taken_row = result.seats[0].row;
+    area_status.reserve(&[Seat { row: 1 - taken_row, col: 2 }]);
+    assert_eq!(area_status.available_seats(), 4);
+    assert!(area_status.seats.find_run(3).is_none());
+    let result = strategy.reserve(&mut area_status, &request).unwrap();
+    assert_eq!(result.result, ReservationResultEnum::Success);
+    assert_eq!(result.seats.len(), 3);
+    assert!(result.seats.iter().all(|seat| area_status.seats.is_available(seat.row, seat.col)));
+
+    // Not enough seats at all is still a failure
+    area_status.reserve(&result.seats);
+    let result = strategy.reserve(&mut area_status, &request).unwrap();
+    assert!(matches!(result.error_code, Some(ReservationErrorCode::InsufficientSeats)));
+
+    let decoded = ReservationType::from(pb::ReservationType::from(&ReservationType::ContinuousRandom));
+    assert_eq!(decoded, ReservationType::ContinuousRandom);
+}
+
+#[tokio::test]
+async fn test_processing_context_lists_store_partitions() {
+    const COUNTS: StoreHandle<String, i64> = StoreHandle::new("counts");
+    const EXTRA: StoreHandle<String, i64> = StoreHandle::new("counts-extra");
+
+    let temp_dir = tempdir().unwrap();
+    let context = ProcessingContext::with_state_dir(temp_dir.path().to_string_lossy().to_string());
+    for partition in [10, 2, 0] {
+        context.open_rocksdb_store(&COUNTS.partition(partition), &format!("counts/{}", partition)).unwrap();
+    }
+    context.open_rocksdb_store(&EXTRA, "counts-extra").unwrap();
+    assert_eq!(context.store_partitions(&COUNTS), vec![0, 2, 10]);
+
+    context.remove_store(&COUNTS.partition(2));
+    assert_eq!(context.store_partitions(&COUNTS.partition(0)), vec![0, 10]);
+}
+
+#[tokio::test]
+async fn test_strategy_registry_uses_the_area_strategy() {
+    struct FirstSeatStrategy;
+
+    impl ReservationStrategy for FirstSeatStrategy {
+        fn reserve(&self, area_status: &mut CompactAreaStatus, request: &ReserveSeat) -> Result<ReservationResult> {
+            Ok(ReservationResult {
+                reservation_id: request.reservation_id.clone(),
+                result: ReservationResultEnum::Success,
+                error_code: None,
+                error_message: None,
+                seats: area_status.seats.available().take(request.num_of_seats as usize).collect(),
+            })
+        }
+
+        fn description(&self) -> &str {
+            "The first available seats"
+        }
+    }
+
+    let area_status = || {
+        let area = Area {
+            area_id: "A".to_string(),
+            price: 100,
+            row_count: 4,
+            col_count: 10,
+            strategy: None,
+        };
+        CompactAreaStatus::from(&AreaStatus::from_area("concert", &area))
+    };
+    let request = |reservation_type: ReservationType| ReserveSeat {
+        reservation_id: "r1".to_string(),
+        event_id: "concert".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 4,
+        num_of_seat: 4,
+        reservation_type,
+        seats: vec![Seat { row: 1, col: 1 }],
+        preferences: vec![],
+    };
+
+    let mut registry = StrategyRegistry::default();
+    let names: Vec<String> = registry.list().into_iter().map(|info| info.name).collect();
+    assert_eq!(names, [CONTINUOUS_RANDOM_STRATEGY, RANDOM_STRATEGY, SELF_PICK_STRATEGY]);
+
+    // Continuous random keeps the party together in one row
+    let mut continuous = area_status();
+    let strategy = registry.select(Some(CONTINUOUS_RANDOM_STRATEGY), &request(ReservationType::Random)).unwrap();
+    let result = strategy.reserve(&mut continuous, &request(ReservationType::Random)).unwrap();
+    assert_eq!(result.result, ReservationResultEnum::Success);
+    assert_eq!(result.seats.len(), 4);
+    assert!(result.seats.iter().all(|seat| seat.row == result.seats[0].row));
+
+    // Requests naming their seats ignore the area strategy
+    let strategy = registry.select(Some(CONTINUOUS_RANDOM_STRATEGY), &request(ReservationType::SelfPick)).unwrap();
+    let result = strategy.reserve(&mut continuous, &request(ReservationType::SelfPick)).unwrap();
+    assert_eq!(result.seats.len(), 1);
+
+    assert!(registry.check_area_strategy(SELF_PICK_STRATEGY).is_err());
+    assert!(registry.check_area_strategy("first_seat").is_err());
+    assert!(registry.select(Some("first_seat"), &request(ReservationType::Random)).is_err());
+    assert!(registry.select(None, &request(ReservationType::Invalid)).is_err());
+
+    registry.register("first_seat", FirstSeatStrategy);
+    assert!(registry.check_area_strategy("first_seat").is_ok());
+    let mut first_seat = area_status();
+    let strategy = registry.select(Some("first_seat"), &request(ReservationType::Random)).unwrap();
+    let result = strategy.reserve(&mut first_seat, &request(ReservationType::Random)).unwrap();
+    assert_eq!((result.seats[0].row, result.seats[0].col), (0, 0));
+}
+
+#[tokio::test]
+async fn test_local_object_storage_keeps_objects_inside_its_directory() {
+    let temp_dir = tempdir().unwrap();
+    let storage = LocalObjectStorage::new(temp_dir.path(), "/artwork/");
+
+    let url = storage.put("events/concert/poster.png", "image/png", b"png".to_vec()).await.unwrap();
+    assert_eq!(url, "/artwork/events/concert/poster.png");
+    assert_eq!(storage.get("events/concert/poster.png").await.unwrap(), Some(b"png".to_vec()));
+    assert_eq!(storage.get("events/concert/missing.png").await.unwrap(), None);
+
+    for key in ["../escape.png", "events/../../escape.png", "/etc/passwd", "events//poster.png", ""] {
+        assert!(storage.put(key, "image/png", Vec::new()).await.is_err(), "{:?} was accepted", key);
+    }
+    assert!(!temp_dir.path().parent().unwrap().join("escape.png").exists());
+}
+
+#[tokio::test]
+async fn test_cors_properties_are_parsed() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("cors.properties");
+    std::fs::write(&config_path, r#"
+bootstrap.servers=localhost:9092
+cors.allowed.origins=https://tickets.example.com, https://admin.example.com
+cors.allowed.methods=GET,POST
+cors.allow.credentials=true
+cors.max.age.secs=600
+"#).unwrap();
+
+    let config = parse_properties_file(&config_path, "ticket-service").unwrap();
+    assert_eq!(config.ticket_service.cors.allowed_origins, vec!["https://tickets.example.com", "https://admin.example.com"]);
+    assert_eq!(config.ticket_service.cors.allowed_methods, vec!["GET", "POST"]);
+    assert!(config.ticket_service.cors.allowed_headers.is_empty());
+    assert!(config.ticket_service.cors.allow_credentials);
+    assert_eq!(config.ticket_service.cors.max_age_secs, Some(600));
+    assert!(!config.ticket_service.cors.permissive);
+    assert!(!config.kafka.additional_properties.keys().any(|key| key.starts_with("cors.")));
+
+    std::fs::write(&config_path, "cors.max.age.secs=forever\n").unwrap();
+    assert!(parse_properties_file(&config_path, "ticket-service").is_err());
+}
+
+#[tokio::test]
+async fn test_serve_metrics_times_requests_and_exports_them() {
+    let metrics = Metrics::new().unwrap();
+    let router = serve_metrics(
+        axum::Router::new().route("/ping", axum::routing::get(|| async { "pong" })),
+        metrics.clone(),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    let response = reqwest::get(format!("{}/ping", url)).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(metrics.request_duration.with_label_values(&["GET", "/ping", "200"]).get_sample_count(), 1);
+
+    let body = reqwest::get(format!("{}/metrics", url)).await.unwrap().text().await.unwrap();
+    assert!(body.contains(r#"request_duration_seconds_count{method="GET",route="/ping",status="200"} 1"#));
+    assert!(!body.contains("errors_total{"));
+}
+
+struct StubCheck {
+    name: &'static str,
+    delay: Duration,
+    error: Option<&'static str>,
+}
+
+#[async_trait::async_trait]
+impl ReadinessCheck for StubCheck {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn check(&self) -> std::result::Result<(), String> {
+        sleep(self.delay).await;
+        self.error.map_or(Ok(()), |error| Err(error.to_string()))
+    }
+}
+
+#[tokio::test]
+async fn test_readiness_probe_requires_every_dependency() {
+    let ready = |name| StubCheck { name, delay: Duration::ZERO, error: None };
+    let probe = ReadinessProbe::new(Duration::from_millis(100)).with_check(ready("kafka"));
+    assert!(probe.check().await.ready);
+
+    let readiness = probe
+        .with_check(StubCheck { name: "schema_registry", delay: Duration::ZERO, error: Some("connection refused") })
+        .with_check(StubCheck { name: "area_status_store", delay: Duration::from_secs(5), error: None })
+        .check()
+        .await;
+    assert!(!readiness.ready);
+    let statuses: Vec<(&str, bool)> = readiness.dependencies.iter().map(|d| (d.name.as_str(), d.ready)).collect();
+    assert_eq!(statuses, vec![("kafka", true), ("schema_registry", false), ("area_status_store", false)]);
+    assert_eq!(readiness.dependencies[1].error.as_deref(), Some("connection refused"));
+    assert!(readiness.dependencies[2].error.as_ref().unwrap().contains("no answer"));
+}
+
+#[tokio::test]
+async fn test_seat_layout_from_csv_leaves_gaps_unavailable() {
+    let csv = "row;col;type;price\n0;0;seat;100\n0;1;aisle;\n0;2;Seat;100\n1;0;accessible;100\n\n1;2;\"seat\";100\n";
+    let layout = SeatLayout::from_csv("A", csv).unwrap();
+    assert_eq!((layout.row_count, layout.col_count, layout.price), (2, 3, 100));
+    assert_eq!(layout.seat_count(), 4);
+
+    let area_status = AreaStatus::from_layout("event1", &layout);
+    assert_eq!(area_status.available_seats, 4);
+    assert!(!area_status.seats[0][1].is_available);
+    // Positions not listed have no seat either
+    assert!(!area_status.seats[1][1].is_available);
+    assert!(area_status.seats[1][2].is_available);
+
+    let mixed = "row,col,type,price\n0,0,seat,100\n0,1,seat,200\n0,0,seat,100\n0,x,seat,100\n";
+    let error = SeatLayout::from_csv("A", mixed).unwrap_err().to_string();
+    assert!(error.contains("line 4: seat 0-0 is listed twice"), "{}", error);
+    assert!(error.contains("line 5: col must be a number"), "{}", error);
+    assert!(error.contains("several prices (100, 200)"), "{}", error);
+    assert!(SeatLayout::from_csv("A", "row,col,price\n0,0,100\n").is_err());
+}
+
+#[tokio::test]
+async fn test_mock_consumer_group_lag_counts_uncommitted_messages() {
+    let kafka = MockKafka::new();
+    kafka.create_topic("test-topic", 2);
+    let producer = kafka.producer();
+    for i in 0..5 {
+        producer.send_bytes("test-topic", Some(i % 2), "key", Some(b"value")).await.unwrap();
+    }
+
+    let consumer = kafka.consumer("test-group");
+    consumer.commit_offsets(&[(TopicPartition::new("test-topic", 0), 2)]).unwrap();
+
+    let lag = consumer.group_lag(&["test-topic"]).unwrap();
+    assert_eq!(lag.len(), 2);
+    assert_eq!((lag[0].committed, lag[0].high_watermark, lag[0].lag), (Some(2), 3, 1));
+    // Without a commit every retained message counts
+    assert_eq!((lag[1].committed, lag[1].high_watermark, lag[1].lag), (None, 2, 2));
+}
+
+#[tokio::test]
+async fn test_compact_area_status_release_frees_reserved_seats() {
+    let area = Area {
+        area_id: "A".to_string(),
+        price: 100,
+        row_count: 2,
+        col_count: 2,
+        strategy: None,
+    };
+    let mut compact = CompactAreaStatus::from(&AreaStatus::from_area("event1", &area));
+    compact.reserve(&[Seat { row: 0, col: 1 }, Seat { row: 1, col: 1 }]);
+    assert_eq!(compact.available_seats(), 2);
+
+    compact.release(&[Seat { row: 0, col: 1 }]);
+    assert_eq!(compact.available_seats(), 3);
+    assert!(compact.seats.is_available(0, 1));
+    assert!(!compact.seats.is_available(1, 1));
+
+    // Admin commands for the area go to the partition of its reserve-seat commands
+    let command = AreaAdminCommand::ReleaseSeat {
+        event_id: "event1".to_string(),
+        area_id: "A".to_string(),
+        row: 0,
+        col: 1,
+    };
+    assert_eq!(command.key(), event_area_key("event1", "A"));
+}
+
+#[tokio::test]
+async fn test_area_status_version_follows_seat_changes() {
+    let area = Area {
+        area_id: "A".to_string(),
+        price: 100,
+        row_count: 2,
+        col_count: 2,
+        strategy: None,
+    };
+    let status = AreaStatus::from_area("event1", &area);
+    assert_eq!(status.version(), AreaStatus::from_area("event1", &area).version());
+
+    let mut reserved = status.clone();
+    reserved.seats[1][0].is_available = false;
+    reserved.available_seats -= 1;
+    assert_ne!(reserved.version(), status.version());
+
+    // Releasing the seat restores the earlier version
+    reserved.seats[1][0].is_available = true;
+    reserved.available_seats += 1;
+    assert_eq!(reserved.version(), status.version());
+}
+
+#[tokio::test]
+async fn test_outbox_relays_request_id_header() {
+    let temp_dir = tempdir().unwrap();
+    let outbox = std::sync::Arc::new(Outbox::open(temp_dir.path()).unwrap());
+    let serdes = TopicSerdes::json();
+    let reserve_seat = ReserveSeat {
+        reservation_id: "reservation-1".to_string(),
+        event_id: "event1".to_string(),
+        area_id: "A".to_string(),
+        num_of_seats: 1,
+        num_of_seat: 1,
+        reservation_type: ReservationType::Random,
+        seats: vec![],
+        preferences: vec![],
+    };
+    let id = outbox
+        .enqueue_with_headers(&serdes, "test-topic", "event1#A", &reserve_seat, &[(REQUEST_ID_HEADER, "request-1")])
+        .await
+        .unwrap();
+
+    let kafka = MockKafka::new();
+    let relay = spawn_outbox_relay(outbox.clone(), kafka.producer(), Duration::from_millis(10));
+    for _ in 0..100 {
+        if outbox.pending().unwrap().is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    relay.abort();
+
+    let messages = kafka.messages("test-topic");
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].request_id(), Some("request-1"));
+    assert_eq!(messages[0].header(OUTBOX_ID_HEADER), Some(id.as_bytes()));
+}
+
+struct CountingComponent {
+    shutdowns: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl ShutdownComponent for CountingComponent {
+    async fn shutdown(&self) -> Result<()> {
+        self.shutdowns.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "counting"
+    }
+}
+
+#[tokio::test]
+async fn test_shutdown_signal_precedes_component_shutdown() {
+    let coordinator = ShutdownCoordinator::new(Duration::from_secs(1));
+    let shutdowns = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    coordinator
+        .register_component(Box::new(CountingComponent { shutdowns: shutdowns.clone() }))
+        .await;
+    let mut stopping = coordinator.subscribe();
+
+    // Subscribers stop first, components only once asked to
+    coordinator.signal();
+    tokio::time::timeout(Duration::from_millis(100), stopping.recv()).await.unwrap().unwrap();
+    assert_eq!(shutdowns.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    coordinator.shutdown().await.unwrap();
+    assert_eq!(shutdowns.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_rocksdb_scan_page_where_filters_in_either_order() {
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path()).unwrap();
+    for i in 0..7 {
+        store.put(&format!("res{}", i), &i).unwrap();
+    }
+    store.put("__global_offset#topic#0", &99).unwrap();
+    let even = |_: &str, value: &i32| value % 2 == 0;
+    let values = |page: &StorePage<i32>| page.entries.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+
+    let first = store.scan_page_where("", None, 2, ScanOrder::Ascending, even).unwrap();
+    assert_eq!(values(&first), vec![0, 2]);
+    let last = store
+        .scan_page_where("", first.next_page_token.as_deref(), 2, ScanOrder::Ascending, even)
+        .unwrap();
+    assert_eq!(values(&last), vec![4, 6]);
+    assert_eq!(last.next_page_token, None);
+
+    // Bookkeeping keys sort first and are never returned
+    let first = store.scan_page_where("", None, 3, ScanOrder::Descending, |_, _: &i32| true).unwrap();
+    assert_eq!(values(&first), vec![6, 5, 4]);
+    let second = store
+        .scan_page_where("", first.next_page_token.as_deref(), 3, ScanOrder::Descending, |_, _: &i32| true)
+        .unwrap();
+    assert_eq!(values(&second), vec![3, 2, 1]);
+    let last = store
+        .scan_page_where("", second.next_page_token.as_deref(), 3, ScanOrder::Descending, |_, _: &i32| true)
+        .unwrap();
+    assert_eq!(values(&last), vec![0]);
+    assert_eq!(last.next_page_token, None);
+
+    let prefixed = store.scan_page_where("res1", None, 5, ScanOrder::Descending, |_, _: &i32| true).unwrap();
+    assert_eq!(values(&prefixed), vec![1]);
+}
+
+#[tokio::test]
+async fn test_key_notifier_wakes_watchers_of_the_key() {
+    let notifier = KeyNotifier::default();
+    let mut watch = notifier.watch("res-1");
+    let mut other = notifier.watch("res-2");
+    assert_eq!(notifier.len(), 2);
+
+    // Notified before waiting still counts
+    notifier.notify("res-1");
+    tokio::time::timeout(Duration::from_millis(100), watch.changed()).await.unwrap();
+    assert!(tokio::time::timeout(Duration::from_millis(50), other.changed()).await.is_err());
+
+    let waiter = tokio::spawn(async move {
+        watch.changed().await;
+    });
+    sleep(Duration::from_millis(20)).await;
+    notifier.notify("res-1");
+    tokio::time::timeout(Duration::from_millis(100), waiter).await.unwrap().unwrap();
+
+    // Keys are forgotten with their last watch
+    drop(other);
+    assert!(notifier.is_empty());
+    notifier.notify("res-2");
+}
+
+#[tokio::test]
+async fn test_area_status_delta_lists_changed_seats() {
+    let area = Area {
+        area_id: "A".to_string(),
+        price: 100,
+        row_count: 2,
+        col_count: 3,
+        strategy: None,
+    };
+    let before = AreaStatus::from_area("event1", &area);
+    let mut after = before.clone();
+    after.seats[0][1].is_available = false;
+    after.seats[1][2].is_available = false;
+    after.available_seats -= 2;
+
+    let delta = after.delta_since(&before).unwrap();
+    assert_eq!(delta.available_seats, 4);
+    let changed: Vec<(i32, i32)> = delta.changed.iter().map(|seat| (seat.row, seat.col)).collect();
+    assert_eq!(changed, vec![(0, 1), (1, 2)]);
+    assert!(delta.changed.iter().all(|seat| !seat.is_available));
+    assert!(after.delta_since(&after).unwrap().is_empty());
+
+    // A different layout can't be expressed as a delta
+    let resized = AreaStatus::from_area("event1", &Area { row_count: 3, ..area });
+    assert!(resized.delta_since(&before).is_none());
+}
+
+#[tokio::test]
+async fn test_waiting_room_admits_at_rate_and_tokens_are_single_use() {
+    let room = WaitingRoom::new(10);
+    let first = room.join("event1");
+    let second = room.join("event1");
+    let third = room.join("event1");
+    assert_eq!((first.position, third.position), (0, 2));
+    assert_eq!(room.status(&first.token, "event1"), QueueStatus::Waiting { ahead: 0 });
+    assert_eq!(room.status(&first.token, "event2"), QueueStatus::Invalid);
+    assert_eq!(room.status("unknown", "event1"), QueueStatus::Invalid);
+
+    // One admitted per 100ms
+    sleep(Duration::from_millis(150)).await;
+    assert_eq!(room.status(&first.token, "event1"), QueueStatus::Admitted);
+    assert_eq!(room.status(&second.token, "event1"), QueueStatus::Waiting { ahead: 0 });
+    assert_eq!(room.consume(&third.token, "event1"), QueueStatus::Waiting { ahead: 1 });
+
+    assert_eq!(room.consume(&first.token, "event1"), QueueStatus::Admitted);
+    assert_eq!(room.consume(&first.token, "event1"), QueueStatus::Invalid);
+
+    // Each event has a queue of its own
+    let other = room.join("event2");
+    assert_eq!(other.position, 0);
+}
+
+#[tokio::test]
+async fn test_api_key_store_verifies_and_revokes() {
+    let temp_dir = tempdir().unwrap();
+    let keys = ApiKeyStore::open(temp_dir.path()).unwrap();
+    assert!(keys.issue(" ").is_err());
+
+    let (issued, secret) = keys.issue("load-test").unwrap();
+    assert!(secret.starts_with("tm_"));
+    assert!(!secret.contains(&issued.secret_hash));
+    assert_eq!(keys.verify(&secret).unwrap().map(|key| key.id), Some(issued.id.clone()));
+
+    // Right id, wrong secret
+    let (id_part, _) = secret.split_once('.').unwrap();
+    assert!(keys.verify(&format!("{}.guess", id_part)).unwrap().is_none());
+    assert!(keys.verify("not-a-key").unwrap().is_none());
+
+    assert!(keys.revoke(&issued.id).unwrap());
+    assert!(!keys.revoke("unknown").unwrap());
+    assert!(keys.verify(&secret).unwrap().is_none());
+    let listed = keys.list().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert!(!listed[0].is_active());
+}
+
+#[tokio::test]
+async fn test_global_table_materializes_topic_and_resumes() {
+    let kafka = MockKafka::new();
+    kafka.create_topic(Topics::STATE_EVENT_AREA_STATUS, 1);
+    let producer = kafka.producer();
+    let area = |area_id: &str| Area {
+        area_id: area_id.to_string(),
+        price: 100,
+        row_count: 2,
+        col_count: 5,
+        strategy: None,
+    };
+    let mut area_a = AreaStatus::from_area("event1", &area("A"));
+    producer.send(Topics::STATE_EVENT_AREA_STATUS, "event1#A", &area_a).await.unwrap();
+    producer.send(Topics::STATE_EVENT_AREA_STATUS, "event1#B", &AreaStatus::from_area("event1", &area("B"))).await.unwrap();
+    producer.send_bytes(Topics::STATE_EVENT_AREA_STATUS, None, "event1#B", None).await.unwrap();
+
+    let temp_dir = tempdir().unwrap();
+    let store = std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap());
+    let convert = |area_status: AreaStatus| CompactAreaStatus::from(&area_status);
+    let mut table = GlobalTable::new(kafka.consumer("ticket-1"), Topics::STATE_EVENT_AREA_STATUS, store.clone(), convert).unwrap();
+    while table.apply_next(Duration::from_millis(50)).await.unwrap() {}
+    assert_eq!(store.get::<CompactAreaStatus>("event1#A").unwrap().unwrap().available_seats(), 10);
+    assert!(store.get::<CompactAreaStatus>("event1#B").unwrap().is_none());
+    assert_eq!(table.position(0).unwrap(), Some(3));
+
+    // A restarted table skips what the store already holds
+    area_a.seats[0][0].is_available = false;
+    area_a.available_seats = 9;
+    producer.send(Topics::STATE_EVENT_AREA_STATUS, "event1#A", &area_a).await.unwrap();
+    let applied = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = applied.clone();
+    let mut table = GlobalTable::new(kafka.consumer("ticket-2"), Topics::STATE_EVENT_AREA_STATUS, store.clone(), convert)
+        .unwrap()
+        .with_on_applied(move |key| recorded.lock().unwrap().push(key.to_string()));
+    while table.apply_next(Duration::from_millis(50)).await.unwrap() {}
+    assert_eq!(*applied.lock().unwrap(), vec!["event1#A".to_string()]);
+    assert_eq!(store.get::<CompactAreaStatus>("event1#A").unwrap().unwrap().available_seats(), 9);
+    assert_eq!(table.position(0).unwrap(), Some(4));
+}
+
+struct StoreQueries(std::sync::Arc<RocksDBStore>);
+
+impl StateQueryHandler for StoreQueries {
+    fn query(&self, store: &str, key: &str) -> Result<Option<serde_json::Value>> {
+        if store != Stores::AREA_STATUS {
+            return Ok(None);
+        }
+        Ok(self.0.get::<String>(key)?.map(serde_json::Value::String))
+    }
+}
+
+#[tokio::test]
+async fn test_state_query_client_reads_from_owning_instance() {
+    // CRC32 check value, as librdkafka partitions keys
+    assert_eq!(key_partition("123456789", i32::MAX), (0xCBF4_3926u32 % i32::MAX as u32) as i32);
+
+    let temp_dir = tempdir().unwrap();
+    let store = std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap());
+    store.put("event1#A", &"42 seats left".to_string()).unwrap();
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let server = format!("127.0.0.1:{}", port);
+    serve_state_queries(&server, std::sync::Arc::new(StoreQueries(store))).await.unwrap();
+
+    let directory = QueryDirectory::default();
+    let client = StateQueryClient::new(directory.clone()).unwrap();
+    // Nobody has announced the key yet
+    assert!(client.get::<String>("event-service", Stores::AREA_STATUS, "event1#A").await.unwrap().is_none());
+
+    let mut host = QueryHost::new("event-service", &server, 2);
+    host.partitions = vec![key_partition("event1#A", 2)];
+    directory.update(host.clone());
+    assert_eq!(directory.owner("event-service", "event1#A"), Some(host));
+
+    let found: Option<String> = client.get("event-service", Stores::AREA_STATUS, "event1#A").await.unwrap();
+    assert_eq!(found, Some("42 seats left".to_string()));
+    let missing: Option<String> = client.get("event-service", Stores::AREA_STATUS, "event1#A#B").await.unwrap();
+    assert!(missing.is_none());
+}
+
+#[tokio::test]
+async fn test_rocksdb_soft_delete_purged_after_retention() {
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path()).unwrap();
+    store.put("reservation1", &"cancelled".to_string()).unwrap();
+    store.put("reservation2", &"confirmed".to_string()).unwrap();
+
+    assert!(store.soft_delete("reservation1").unwrap());
+    assert!(!store.soft_delete("missing").unwrap());
+    assert!(store.deleted_at("reservation1").unwrap().is_some());
+    assert!(store.deleted_at("reservation2").unwrap().is_none());
+    assert_eq!(store.get::<String>("reservation1").unwrap(), Some("cancelled".to_string()));
+
+    // Kept for good until a retention is set
+    store.compact();
+    assert_eq!(store.get::<String>("reservation1").unwrap(), Some("cancelled".to_string()));
+
+    let store = store.with_soft_delete_retention(Duration::from_millis(1));
+    std::thread::sleep(Duration::from_millis(10));
+    store.compact();
+    assert_eq!(store.get::<String>("reservation1").unwrap(), None);
+    assert_eq!(store.get::<String>("reservation2").unwrap(), Some("confirmed".to_string()));
+}
+
+#[tokio::test]
+async fn test_rocksdb_multi_get_keeps_key_order() {
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path()).unwrap();
+    store.put("event1#A", &1).unwrap();
+    store.put("event1#C", &3).unwrap();
+
+    let values = store.multi_get::<i32, _>(&["event1#C", "event1#B", "event1#A"]).unwrap();
+    assert_eq!(values, vec![Some(3), None, Some(1)]);
+    assert!(store.multi_get::<i32, String>(&[]).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_rocksdb_scan_page_continues_from_token() {
+    let temp_dir = tempdir().unwrap();
+    let store = RocksDBStore::new(temp_dir.path()).unwrap();
+    for i in 0..5 {
+        store.put(&format!("event1#{}", i), &i).unwrap();
+    }
+    store.put("event10#0", &10).unwrap();
+
+    let first = store.scan_page::<i32>("event1#", None, 2).unwrap();
+    assert_eq!(first.entries, vec![("event1#0".to_string(), 0), ("event1#1".to_string(), 1)]);
+    let token = first.next_page_token.unwrap();
+
+    // Deleting a returned key doesn't shift the next page
+    store.delete("event1#1").unwrap();
+    let second = store.scan_page::<i32>("event1#", Some(&token), 2).unwrap();
+    assert_eq!(second.entries, vec![("event1#2".to_string(), 2), ("event1#3".to_string(), 3)]);
+
+    let last = store.scan_page::<i32>("event1#", second.next_page_token.as_deref(), 2).unwrap();
+    assert_eq!(last.entries, vec![("event1#4".to_string(), 4)]);
+    assert_eq!(last.next_page_token, None);
+
+    assert!(store.scan_page::<i32>("event2#", Some(&token), 2).is_err());
+    assert!(store.scan_page::<i32>("event1#", Some("not hex"), 2).is_err());
+    assert!(store.scan_page::<i32>("event1#", None, 0).is_err());
+}
+
+#[tokio::test]
+async fn test_indexed_store_keeps_secondary_keys_in_step() {
+    let temp_dir = tempdir().unwrap();
+    let store = std::sync::Arc::new(RocksDBStore::new(temp_dir.path()).unwrap());
+    let indexed = IndexedStore::<(String, i32)>::new(store.clone())
+        .with_index("byUser", |(user, _)| vec![user.clone()]);
+
+    indexed.put("res-1", &("user-1".to_string(), 1)).unwrap();
+    indexed.put("res-2", &("user-1".to_string(), 2)).unwrap();
+    indexed.put("res-3", &("user-1#vip".to_string(), 3)).unwrap();
+    let keys = |user: &str| -> Vec<String> {
+        indexed.find("byUser", user).unwrap().into_iter().map(|(key, _)| key).collect()
+    };
+    assert_eq!(keys("user-1"), vec!["res-1", "res-2"]);
+    assert_eq!(keys("user-1#vip"), vec!["res-3"]);
+
+    // Updates move the entry, deletes drop it
+    indexed.put("res-2", &("user-2".to_string(), 2)).unwrap();
+    indexed.delete("res-1").unwrap();
+    assert!(keys("user-1").is_empty());
+    assert_eq!(keys("user-2"), vec!["res-2"]);
+
+    // Values written before the index existed are picked up by a rebuild
+    store.put("res-4", &("user-1".to_string(), 4)).unwrap();
+    store.put("__version#res-4", &1u64).unwrap();
+    assert!(keys("user-1").is_empty());
+    assert_eq!(indexed.rebuild_indexes().unwrap(), 3);
+    assert_eq!(keys("user-1"), vec!["res-4"]);
+    assert_eq!(indexed.get("res-4").unwrap(), Some(("user-1".to_string(), 4)));
+
+    // A soft-deleted value is found until compaction purges it with its entries
+    let retaining = std::sync::Arc::new(
+        RocksDBStore::new(temp_dir.path().join("retaining"))
+            .unwrap()
+            .with_soft_delete_retention(Duration::from_millis(1)),
+    );
+    let indexed = IndexedStore::<(String, i32)>::new(retaining.clone())
+        .with_index("byUser", |(user, _)| vec![user.clone()]);
+    indexed.put("res-1", &("user-1".to_string(), 1)).unwrap();
+    indexed.put_soft_deleted("res-2", &("user-1".to_string(), 2)).unwrap();
+    assert!(retaining.deleted_at("res-2").unwrap().is_some());
+    assert_eq!(indexed.find("byUser", "user-1").unwrap().len(), 2);
+    std::thread::sleep(Duration::from_millis(10));
+    retaining.compact();
+    let found = indexed.find("byUser", "user-1").unwrap();
+    assert_eq!(found.into_iter().map(|(key, _)| key).collect::<Vec<_>>(), vec!["res-1"]);
+    assert_eq!(retaining.scan_prefix::<(String, String)>("__index#byUser#").count(), 1);
+}
+
+#[tokio::test]
+async fn test_rocksdb_store_records_metrics_per_store() {
+    const COUNTS: StoreHandle<String, i64> = StoreHandle::new("counts");
+
+    let temp_dir = tempdir().unwrap();
+    let metrics = Metrics::new().unwrap();
+    let context = ProcessingContext::builder(temp_dir.path().to_string_lossy().to_string())
+        .metrics(metrics.clone(), true)
+        .rocksdb_store(COUNTS.partition(0), "counts/0")
+        .build()
+        .unwrap();
+
+    let store = context.rocksdb_store(&COUNTS.partition(0)).unwrap();
+    store.put("a", &1i64).unwrap();
+    let mut batch = store.write_batch();
+    batch.put("b", &2i64).unwrap();
+    batch.commit().unwrap();
+    assert_eq!(store.get::<i64>("a").unwrap(), Some(1));
+
+    let name = COUNTS.partition(0).name();
+    assert_eq!(metrics.store_operation_duration.with_label_values(&[&name, "write"]).get_sample_count(), 2);
+    assert_eq!(metrics.store_operations.with_label_values(&[&name, "write"]).get(), 2.0);
+    assert_eq!(metrics.store_operations.with_label_values(&[&name, "read"]).get(), 1.0);
+
+    let total = context.report_store_sizes().unwrap();
+    assert_eq!(total, store.size_stats().unwrap());
+    assert!(metrics.export().unwrap().contains("state_store_bytes"));
+}
+
+#[tokio::test]
+async fn test_rocksdb_store_compaction_and_sync_keep_data() {
+    let temp_dir = tempdir().unwrap();
+    {
+        let store = RocksDBStore::new(temp_dir.path()).unwrap().with_sync_writes(true);
+        for i in 0..100 {
+            store.put(&format!("event1#{:03}", i), &i).unwrap();
+        }
+        for i in 0..50 {
+            store.delete(&format!("event1#{:03}", i)).unwrap();
+        }
+        store.compact_range(Some("event1#000"), Some("event1#049"));
+        store.compact();
+        store.sync_wal().unwrap();
+        store.persist().unwrap();
+        assert_eq!(store.entries::<i32>().unwrap().len(), 50);
+    }
+
+    let reopened = RocksDBStore::new(temp_dir.path()).unwrap();
+    assert_eq!(reopened.get::<i32>("event1#000").unwrap(), None);
+    assert_eq!(reopened.get::<i32>("event1#099").unwrap(), Some(99));
+}
+
+#[tokio::test]
+async fn test_seat_bitmap_matches_verbose_area_status() {
+    let area = Area {
+        area_id: "A".to_string(),
+        price: 100,
+        row_count: 3,
+        col_count: 70,
+        strategy: None,
+    };
+    let verbose = AreaStatus::from_area("event1", &area);
+    let mut compact = CompactAreaStatus::from(&verbose);
+    assert_eq!(compact.seats, SeatBitmap::new(3, 70));
+    assert_eq!(compact.available_seats(), 210);
+
+    // Row 0 is broken up, row 1 has a run crossing the first word boundary
+    compact.reserve(&[Seat { row: 0, col: 2 }, Seat { row: 0, col: 5 }]);
+    compact.reserve(&(0..60).map(|col| Seat { row: 1, col }).collect::<Vec<_>>());
+    compact.reserve(&[Seat { row: 1, col: 69 }]);
+    assert!(!compact.seats.is_available(0, 2));
+    assert!(!compact.seats.is_available(3, 0));
+    assert!(!compact.seats.contains(-1, 0));
+    assert_eq!(compact.available_seats(), 210 - 63);
+
+    let run = compact.seats.find_run(8).unwrap();
+    assert_eq!((run[0].row, run[0].col), (0, 6));
+    let run = compact.seats.find_run(65).unwrap();
+    assert_eq!((run[0].row, run[0].col), (2, 0));
+    assert!(compact.seats.find_run(71).is_none());
+
+    let available: Vec<Seat> = compact.seats.available().collect();
+    assert_eq!(available.len(), 147);
+    assert_eq!((available[2].row, available[2].col), (0, 3));
+    assert_eq!((available[68].row, available[68].col), (1, 60));
+
+    // The verbose form round-trips and is what gets sent and returned
+    let expanded = compact.to_area_status();
+    assert_eq!(expanded.available_seats, 147);
+    assert!(!expanded.seats[1][69].is_available);
+    assert!(expanded.seats[2][69].is_available);
+    assert_eq!(CompactAreaStatus::from(&expanded), compact);
+
+    let compact_size = bincode::serialize(&compact).unwrap().len();
+    let verbose_size = bincode::serialize(&expanded).unwrap().len();
+    assert!(compact_size * 10 < verbose_size);
+}
+
+#[tokio::test]
+async fn test_reservation_strategies() {
+    use ticket_master::*;
+    
+    // Create a test area status
+    let mut area_status = CompactAreaStatus::from(&AreaStatus {
+        event_id: "Test Event".to_string(),
+        area_id: "Test Area".to_string(),
+        price: 100,
+        row_count: 10,
+        col_count: 10,
+        available_seats: 100,
+        seats: (0..10).map(|row| {
+            (0..10).map(|col| SeatStatus {
+                row,
+                col,
+                is_available: true,
+            }).collect()
+        }).collect(),
+    });
+    
+    // Test SelfPick strategy
+    let self_pick_strategy = SelfPickStrategy;
+    let reserve_request = ReserveSeat {
+        reservation_id: "test-123".to_string(),
+        event_id: "Test Event".to_string(),
+        area_id: "Test Area".to_string(),
+        num_of_seats: 2,
+        num_of_seat: 2,
+        reservation_type: ReservationType::SelfPick,
+        seats: vec![
+            Seat { row: 0, col: 0 },
+            Seat { row: 0, col: 1 },
+        ],
+        preferences: vec![],
+    };
+    
+    let result = self_pick_strategy.reserve(&mut area_status, &reserve_request).unwrap();
+    assert_eq!(result.result, ReservationResultEnum::Success);
+    assert_eq!(result.seats.len(), 2);
+    assert_eq!(result.seats[0].row, 0);
+    assert_eq!(result.seats[0].col, 0);
+    area_status.reserve(&result.seats);
+    
+    // Test Random strategy
+    let random_strategy = RandomStrategy;
+    let random_request = ReserveSeat {
+        reservation_id: "test-456".to_string(),
         event_id: "Test Event".to_string(),
         area_id: "Test Area".to_string(),
         num_of_seats: 3,
+        num_of_seat: 3,
         reservation_type: ReservationType::Random,
         seats: vec![],
+        preferences: vec![],
     };
     
     let result = random_strategy.reserve(&mut area_status, &random_request).unwrap();
     assert_eq!(result.result, ReservationResultEnum::Success);
     assert_eq!(result.seats.len(), 3);
+    area_status.reserve(&result.seats);
     
-    // Verify seats are different
-    let seat1 = &result.seats[0];
-    let seat2 = &result.seats[1];
-    let seat3 = &result.seats[2];
-    assert!(seat1 != seat2 || seat1 != seat3 || seat2 != seat3);
+    // Verify seats are different, and not the ones taken above
+    let picked: std::collections::HashSet<(i32, i32)> =
+        result.seats.iter().map(|seat| (seat.row, seat.col)).collect();
+    assert_eq!(picked.len(), 3);
+    assert!(!picked.contains(&(0, 0)) && !picked.contains(&(0, 1)));
+    assert_eq!(area_status.available_seats(), 95);
 }
 
 #[tokio::test]
@@ -287,6 +3720,12 @@ async fn test_service_config_to_kafka_config() {
             sasl_username: Some("user".to_string()),
             sasl_password: Some("pass".to_string()),
             ssl_ca_location: Some("/path/to/ca.pem".to_string()),
+            ssl_certificate_location: None,
+            ssl_key_location: None,
+            ssl_key_password: None,
+            ssl_endpoint_identification_algorithm: None,
+            schema_registry_basic_auth: None,
+            statistics_interval_ms: None,
             additional_properties: [
                 ("num.stream.threads".to_string(), "4".to_string()),
                 ("replication.factor".to_string(), "3".to_string()),
@@ -294,11 +3733,63 @@ async fn test_service_config_to_kafka_config() {
         },
         commit_interval_ms: Some(100),
         processing_guarantee: Some("exactly_once_v2".to_string()),
+        changelog_enabled: false,
+        serdes: SerdeConfig::default(),
+        processing_parallelism: None,
+        max_in_flight: None,
+        dedup_ttl_ms: None,
+        topic_prefix: None,
+        area_status_bridge_enabled: false,
+        store_metrics_per_store: false,
+        store_compaction_interval_ms: None,
+        store_sync_writes: false,
+        store_cache_capacity: None,
+        soft_delete_retention_ms: None,
+        application_server: None,
+        interactive_queries_enabled: false,
+        global_tables_enabled: false,
+        api_keys_enabled: false,
+        admin_token: None,
+        waiting_room_admission_rate: None,
+        metrics_port: None,
+        reservation_timeout_ms: None,
+        log_filter: None,
+        producer: ProducerConfig::default(),
+        ticket_service: TicketServiceConfig::default(),
+        event_service: EventServiceConfig::default(),
+        reservation_service: ReservationServiceConfig::default(),
+        user_service: UserServiceConfig::default(),
+        analytics_service: AnalyticsServiceConfig::default(),
+        gateway_service: GatewayServiceConfig::default(),
+        reconciliation_service: ReconciliationServiceConfig::default(),
+        export: ExportConfig::default(),
+        sink: SinkConfig::default(),
+        artwork: ArtworkConfig::default(),
+        warmup: WarmupConfig::default(),
+        event_sourcing: EventSourcingConfig::default(),
+        consumers: ConsumersConfig::default(),
+        alerts: AlertConfig::default(),
+        retry: RetryPoliciesConfig::default(),
+        supervisor: SupervisorConfig::default(),
+        heartbeat: HeartbeatConfig::default(),
+        notification: NotificationConfig::default(),
+        payment: PaymentConfig::default(),
     };
     
     let kafka_config = service_config.to_kafka_config();
     
-    // Verify the conversion works (we can't easily test the internal state of ClientConfig)
-    // but we can verify it doesn't panic and creates a valid config
-    assert!(true); // If we get here, the conversion worked
+    assert_eq!(kafka_config.get("bootstrap.servers"), Some("localhost:9092"));
+    assert_eq!(kafka_config.get("group.id"), Some("test-app"));
+    assert_eq!(kafka_config.get("enable.auto.commit"), Some("false"));
+    assert_eq!(kafka_config.get("compression.type"), Some("lz4"));
+    assert_eq!(kafka_config.get("security.protocol"), Some("SASL_SSL"));
+    assert_eq!(kafka_config.get("sasl.mechanism"), Some("PLAIN"));
+    assert_eq!(kafka_config.get("sasl.username"), Some("user"));
+    assert_eq!(kafka_config.get("sasl.password"), Some("pass"));
+    assert_eq!(kafka_config.get("ssl.ca.location"), Some("/path/to/ca.pem"));
+    assert_eq!(kafka_config.get("ssl.key.location"), None);
+    assert_eq!(kafka_config.get("statistics.interval.ms"), Some("30000"));
+    // Additional properties are passed through as they are
+    assert_eq!(kafka_config.get("num.stream.threads"), Some("4"));
+    assert_eq!(kafka_config.get("replication.factor"), Some("3"));
 }
\ No newline at end of file
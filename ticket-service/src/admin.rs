@@ -0,0 +1,230 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware,
+    response::Json,
+    routing::{delete, get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use ticket_master::{
+    event_area_key, ApiError, AreaAdminCommand, AuditEntity, AuditRecord, CompactAreaStatus, EventArtwork, InstanceStatus,
+    LogFilter, Reservation, ReservationAdminCommand, RuntimeSettings, StoreCodec, Stores, TicketMasterError,
+};
+use tracing::info;
+
+use crate::auth::{require_admin_token, AuthState};
+use crate::request_id::RequestId;
+use crate::service::{ConsumerGroupLag, ServiceStatus, TicketService};
+use crate::{ApiResponse, ApiResult};
+
+const DEFAULT_KEY_LIMIT: usize = 100;
+const MAX_KEY_LIMIT: usize = 1000;
+
+/// Operator endpoints over seats, stores and consumer groups, guarded by the admin token
+pub fn operator_routes(auth: AuthState) -> Router<TicketService> {
+    Router::new()
+        .route("/admin/events/:event_id/areas/:area_id/release-seat", post(release_seat))
+        .route("/admin/events/:event_id/areas/:area_id/reemit", post(reemit_area_status))
+        .route("/admin/events/:event_id/areas/:area_id", delete(delete_area))
+        .route("/admin/reservations/:reservation_id", delete(delete_reservation))
+        .route("/admin/stores/:store/keys", get(list_store_keys))
+        .route("/admin/stores/:store/keys/*key", get(get_store_entry))
+        .route("/admin/stores/:store/compact", post(compact_store))
+        .route("/admin/consumer-lag", get(consumer_lag))
+        .route("/admin/status", get(status))
+        .route("/admin/cluster", get(cluster_overview))
+        .route("/admin/settings", get(runtime_settings))
+        .route("/admin/audit/reservations/:reservation_id", get(reservation_audit))
+        .route("/admin/audit/events/:event_id/areas/:area_id", get(area_audit))
+        .route_layer(middleware::from_fn_with_state(auth, require_admin_token))
+}
+
+/// Read and change the log filter while running, guarded by the admin token
+///
+/// A changed filter holds until `log.filter` in the config file changes.
+pub fn log_level_routes(auth: AuthState, log_filter: LogFilter) -> Router {
+    Router::new()
+        .route("/admin/log-level", get(log_level).put(set_log_level))
+        .route_layer(middleware::from_fn_with_state(auth, require_admin_token))
+        .with_state(log_filter)
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleaseSeatRequest {
+    row: i32,
+    col: i32,
+}
+
+/// A filter in `RUST_LOG` syntax; null goes back to `RUST_LOG`
+#[derive(Debug, Deserialize)]
+struct LogLevelRequest {
+    filter: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyQuery {
+    prefix: Option<String>,
+    limit: Option<usize>,
+}
+
+/// A stored entry as it is on disk, and decoded when it decodes
+#[derive(Debug, Serialize)]
+struct StoreEntry {
+    key: String,
+    /// Stored bytes in hex, codec tag included
+    raw: String,
+    size: usize,
+    value: Option<serde_json::Value>,
+}
+
+type Accepted<T> = std::result::Result<(StatusCode, Json<ApiResponse<T>>), ApiError>;
+
+// The command only reaches the event service once it is consumed, hence 202
+async fn release_seat(
+    State(service): State<TicketService>,
+    Path((event_id, area_id)): Path<(String, String)>,
+    request_id: RequestId,
+    Json(request): Json<ReleaseSeatRequest>,
+) -> Accepted<AreaAdminCommand> {
+    let command = AreaAdminCommand::ReleaseSeat {
+        event_id,
+        area_id,
+        row: request.row,
+        col: request.col,
+    };
+    service.send_admin_command(&command, &request_id.0).await?;
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(command))))
+}
+
+async fn reemit_area_status(
+    State(service): State<TicketService>,
+    Path((event_id, area_id)): Path<(String, String)>,
+    request_id: RequestId,
+) -> Accepted<AreaAdminCommand> {
+    let command = AreaAdminCommand::ReemitAreaStatus { event_id, area_id };
+    service.send_admin_command(&command, &request_id.0).await?;
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(command))))
+}
+
+async fn delete_area(
+    State(service): State<TicketService>,
+    Path((event_id, area_id)): Path<(String, String)>,
+    request_id: RequestId,
+) -> Accepted<AreaAdminCommand> {
+    let command = AreaAdminCommand::DeleteArea { event_id, area_id };
+    service.send_admin_command(&command, &request_id.0).await?;
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(command))))
+}
+
+// Only failed and cancelled reservations are deleted; others are left as they are
+async fn delete_reservation(
+    State(service): State<TicketService>,
+    Path(reservation_id): Path<String>,
+    request_id: RequestId,
+) -> Accepted<ReservationAdminCommand> {
+    let command = ReservationAdminCommand::DeleteReservation { reservation_id };
+    service.send_reservation_admin_command(&command, &request_id.0).await?;
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(command))))
+}
+
+// Bookkeeping keys starting with `__` are listed too
+async fn list_store_keys(
+    State(service): State<TicketService>,
+    Path(store): Path<String>,
+    Query(query): Query<KeyQuery>,
+) -> ApiResult<Vec<String>> {
+    let store = service.store(&store)?;
+    let prefix = query.prefix.unwrap_or_default();
+    let limit = query.limit.unwrap_or(DEFAULT_KEY_LIMIT).clamp(1, MAX_KEY_LIMIT);
+    let keys = store
+        .keys()
+        .filter(|key| key.as_ref().map_or(true, |key| key.starts_with(&prefix)))
+        .take(limit)
+        .collect::<ticket_master::Result<Vec<_>>>()?;
+    Ok(Json(ApiResponse::success(keys)))
+}
+
+async fn get_store_entry(
+    State(service): State<TicketService>,
+    Path((name, key)): Path<(String, String)>,
+) -> ApiResult<StoreEntry> {
+    let store = service.store(&name)?;
+    let raw = store.get_raw(&key)?.ok_or_else(|| ApiError::not_found("Key not found"))?;
+    let value = decode(&name, &raw);
+    Ok(Json(ApiResponse::success(StoreEntry {
+        key,
+        raw: raw.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        size: raw.len(),
+        value,
+    })))
+}
+
+// Values are decoded as the store's type; bookkeeping values stay undecoded
+fn decode(store: &str, raw: &[u8]) -> Option<serde_json::Value> {
+    let value = match store {
+        Stores::AREA_STATUS => serde_json::to_value(StoreCodec::decode::<CompactAreaStatus>(raw).ok()?.to_area_status()),
+        Stores::RESERVATION => serde_json::to_value(StoreCodec::decode::<Reservation>(raw).ok()?),
+        Stores::EVENT_ARTWORK => serde_json::to_value(StoreCodec::decode::<EventArtwork>(raw).ok()?),
+        Stores::AUDIT_LOG => serde_json::to_value(StoreCodec::decode::<AuditRecord>(raw).ok()?),
+        _ => return None,
+    };
+    value.ok()
+}
+
+async fn compact_store(State(service): State<TicketService>, Path(name): Path<String>) -> ApiResult<String> {
+    let store = service.store(&name)?;
+    // A full compaction rewrites the store; keep it off the request threads
+    tokio::task::spawn_blocking(move || store.compact())
+        .await
+        .map_err(|e| TicketMasterError::Io(std::io::Error::other(e)))?;
+    info!("Compacted store {}", name);
+    Ok(Json(ApiResponse::success(name)))
+}
+
+async fn consumer_lag(State(service): State<TicketService>) -> ApiResult<Vec<ConsumerGroupLag>> {
+    Ok(Json(ApiResponse::success(service.consumer_lag().await?)))
+}
+
+async fn status(State(service): State<TicketService>) -> ApiResult<ServiceStatus> {
+    Ok(Json(ApiResponse::success(service.status().await?)))
+}
+
+async fn cluster_overview(State(service): State<TicketService>) -> ApiResult<Vec<InstanceStatus>> {
+    Ok(Json(ApiResponse::success(service.cluster_overview())))
+}
+
+// As last loaded from the config file; log filter and reservation timeout
+// are those of the other services too when they share the file
+async fn runtime_settings(State(service): State<TicketService>) -> ApiResult<RuntimeSettings> {
+    Ok(Json(ApiResponse::success(service.settings())))
+}
+
+async fn log_level(State(log_filter): State<LogFilter>) -> ApiResult<String> {
+    Ok(Json(ApiResponse::success(log_filter.current()?)))
+}
+
+async fn set_log_level(
+    State(log_filter): State<LogFilter>,
+    Json(request): Json<LogLevelRequest>,
+) -> ApiResult<String> {
+    log_filter.set(request.filter.as_deref())?;
+    let current = log_filter.current()?;
+    info!("Log filter changed to {}", current);
+    Ok(Json(ApiResponse::success(current)))
+}
+
+async fn reservation_audit(
+    State(service): State<TicketService>,
+    Path(reservation_id): Path<String>,
+) -> ApiResult<Vec<AuditRecord>> {
+    Ok(Json(ApiResponse::success(service.audit_history(AuditEntity::Reservation, &reservation_id)?)))
+}
+
+async fn area_audit(
+    State(service): State<TicketService>,
+    Path((event_id, area_id)): Path<(String, String)>,
+) -> ApiResult<Vec<AuditRecord>> {
+    let history = service.audit_history(AuditEntity::Area, &event_area_key(&event_id, &area_id))?;
+    Ok(Json(ApiResponse::success(history)))
+}
@@ -0,0 +1,125 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router,
+};
+use ticket_master::{ApiError, EventArtwork, FieldError};
+
+use crate::request_id::RequestId;
+use crate::service::TicketService;
+use crate::{ApiResponse, ApiResult};
+
+/// Path artwork kept on local disk is served under
+pub const ARTWORK_PATH: &str = "/artwork";
+// Room for the multipart boundaries and headers around the image
+const MULTIPART_OVERHEAD: usize = 64 * 1024;
+const IMAGE_FIELD: &str = "image";
+
+/// Image formats accepted as artwork, recognized by their leading bytes
+/// rather than the content type the client claims
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x89, b'P', b'N', b'G', ..] => Some(Self::Png),
+            [0xFF, 0xD8, 0xFF, ..] => Some(Self::Jpeg),
+            [b'G', b'I', b'F', b'8', ..] => Some(Self::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        [Self::Png, Self::Jpeg, Self::Gif, Self::Webp]
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Gif => "gif",
+            Self::Webp => "webp",
+        }
+    }
+}
+
+/// Artwork upload, taking images of up to `max_bytes`
+pub fn upload_routes(max_bytes: usize) -> Router<TicketService> {
+    Router::new().route(
+        "/events/:event_name/artwork",
+        post(upload_artwork).layer(DefaultBodyLimit::max(max_bytes + MULTIPART_OVERHEAD)),
+    )
+}
+
+/// Artwork kept on local disk; public, as browsers load it without API keys
+pub fn serve_routes() -> Router<TicketService> {
+    Router::new().route(&format!("{}/*key", ARTWORK_PATH), get(get_artwork))
+}
+
+// Multipart with the image in an `image` field, e.g. `curl -F image=@poster.png`
+async fn upload_artwork(
+    State(service): State<TicketService>,
+    Path(event_name): Path<String>,
+    request_id: RequestId,
+    mut multipart: Multipart,
+) -> ApiResult<EventArtwork> {
+    let mut image = None;
+    while let Some(field) = multipart.next_field().await.map_err(invalid_multipart)? {
+        if field.name() == Some(IMAGE_FIELD) {
+            image = Some(field.bytes().await.map_err(invalid_multipart)?);
+            break;
+        }
+    }
+    let image = image.ok_or_else(|| ApiError::validation(vec![FieldError::new(IMAGE_FIELD, "is required")]))?;
+    let format = ImageFormat::sniff(&image).ok_or_else(|| {
+        ApiError::validation(vec![FieldError::new(IMAGE_FIELD, "must be a PNG, JPEG, GIF or WebP image")])
+    })?;
+    if service.list_area_statuses(&event_name).await?.is_empty() {
+        return Err(ApiError::not_found("Event not found"));
+    }
+
+    let artwork = service.upload_artwork(&event_name, format, image.to_vec(), &request_id.0).await?;
+    Ok(Json(ApiResponse::success(artwork)))
+}
+
+async fn get_artwork(State(service): State<TicketService>, Path(key): Path<String>) -> Result<Response, ApiError> {
+    let storage = service.local_artwork().ok_or_else(|| ApiError::not_found("Artwork is not served here"))?;
+    let image = storage.get(&key).await?.ok_or_else(|| ApiError::not_found("Artwork not found"))?;
+    let format = key
+        .rsplit_once('.')
+        .and_then(|(_, extension)| ImageFormat::from_extension(extension))
+        .ok_or_else(|| ApiError::not_found("Artwork not found"))?;
+    // Keys are never reused, so the image never changes
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type()),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        image,
+    )
+        .into_response())
+}
+
+fn invalid_multipart(e: axum::extract::multipart::MultipartError) -> ApiError {
+    ApiError::new(StatusCode::BAD_REQUEST, "invalid_multipart", e.body_text())
+}
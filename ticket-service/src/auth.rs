@@ -0,0 +1,131 @@
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{Json, Response},
+    routing::{delete, get},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use ticket_master::{constant_time_eq, ApiError, ApiKey, ApiKeyStore};
+use tracing::{debug, info};
+
+use crate::{ApiResponse, ApiResult};
+
+/// Header API keys are sent in
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// API keys and the token guarding their admin endpoints
+#[derive(Clone)]
+pub struct AuthState {
+    keys: Arc<ApiKeyStore>,
+    admin_token: Option<Arc<str>>,
+}
+
+impl AuthState {
+    pub fn new(keys: ApiKeyStore, admin_token: Option<String>) -> Self {
+        Self {
+            keys: Arc::new(keys),
+            admin_token: admin_token.map(Arc::from),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueApiKeyRequest {
+    name: String,
+}
+
+/// A key as listed by the admin endpoints, without its hash
+#[derive(Debug, Serialize)]
+struct ApiKeyInfo {
+    id: String,
+    name: String,
+    created_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKey> for ApiKeyInfo {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            id: key.id,
+            name: key.name,
+            created_at: key.created_at,
+            revoked_at: key.revoked_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct IssuedApiKey {
+    #[serde(flatten)]
+    info: ApiKeyInfo,
+    /// Shown once; only its hash is kept
+    key: String,
+}
+
+/// Reject requests without an active API key in `x-api-key`
+pub async fn require_api_key(State(auth): State<AuthState>, request: Request, next: Next) -> Result<Response, ApiError> {
+    let presented = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "missing_api_key", "An API key is required"))?;
+    let key = auth
+        .keys
+        .verify(presented)?
+        .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "invalid_api_key", "The API key is invalid or revoked"))?;
+    debug!("Request authorized by API key {} ({})", key.id, key.name);
+    Ok(next.run(request).await)
+}
+
+/// Endpoints to issue, list and revoke API keys, guarded by the admin token
+pub fn admin_routes(auth: AuthState) -> Router {
+    Router::new()
+        .route("/admin/api-keys", get(list_api_keys).post(issue_api_key))
+        .route("/admin/api-keys/:id", delete(revoke_api_key))
+        .route_layer(middleware::from_fn_with_state(auth.clone(), require_admin_token))
+        .with_state(auth)
+}
+
+/// Reject requests without the admin token as a bearer token
+pub async fn require_admin_token(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match (&auth.admin_token, presented) {
+        (Some(token), Some(presented)) if constant_time_eq(token, presented) => Ok(next.run(request).await),
+        _ => Err(ApiError::new(StatusCode::UNAUTHORIZED, "invalid_admin_token", "A valid admin token is required")),
+    }
+}
+
+async fn issue_api_key(State(auth): State<AuthState>, Json(request): Json<IssueApiKeyRequest>) -> ApiResult<IssuedApiKey> {
+    let (key, secret) = auth.keys.issue(&request.name)?;
+    info!("Issued API key {} for {}", key.id, key.name);
+    Ok(Json(ApiResponse::success(IssuedApiKey {
+        info: key.into(),
+        key: secret,
+    })))
+}
+
+async fn list_api_keys(State(auth): State<AuthState>) -> ApiResult<Vec<ApiKeyInfo>> {
+    let keys = auth.keys.list()?;
+    Ok(Json(ApiResponse::success(keys.into_iter().map(ApiKeyInfo::from).collect())))
+}
+
+async fn revoke_api_key(State(auth): State<AuthState>, Path(id): Path<String>) -> ApiResult<ApiKeyInfo> {
+    if !auth.keys.revoke(&id)? {
+        return Err(ApiError::not_found("API key not found"));
+    }
+    let key = auth.keys.get(&id)?.ok_or_else(|| ApiError::not_found("API key not found"))?;
+    info!("Revoked API key {} ({})", key.id, key.name);
+    Ok(Json(ApiResponse::success(key.into())))
+}
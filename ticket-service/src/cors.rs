@@ -0,0 +1,63 @@
+use axum::http::{HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use ticket_master::{CorsConfig, Result, TicketMasterError};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+const DEFAULT_METHODS: [Method; 4] = [Method::GET, Method::POST, Method::PUT, Method::DELETE];
+const DEFAULT_HEADERS: [&str; 4] = ["content-type", "authorization", "x-api-key", "x-request-id"];
+// Headers scripts on an allowed origin may read from responses
+const EXPOSED_HEADERS: [&str; 2] = ["etag", "x-request-id"];
+
+/// The CORS layer `config` describes; entries that don't parse fail startup
+pub fn cors_layer(config: &CorsConfig) -> Result<CorsLayer> {
+    if config.permissive {
+        return Ok(CorsLayer::permissive());
+    }
+
+    // Browsers refuse credentials for a wildcard origin, and tower-http panics on it
+    let any_origin = config.allowed_origins.iter().any(|origin| origin == "*");
+    if any_origin && config.allow_credentials {
+        return Err(TicketMasterError::InvalidArgument(
+            "cors.allow.credentials=true needs explicit cors.allowed.origins, not *".to_string(),
+        ));
+    }
+    let origins = if any_origin {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(parse(&config.allowed_origins, "origin", |origin| {
+            HeaderValue::from_str(origin.trim_end_matches('/')).ok()
+        })?)
+    };
+    let methods = if config.allowed_methods.is_empty() {
+        DEFAULT_METHODS.to_vec()
+    } else {
+        parse(&config.allowed_methods, "method", |method| {
+            Method::from_bytes(method.to_uppercase().as_bytes()).ok()
+        })?
+    };
+    let headers = if config.allowed_headers.is_empty() {
+        DEFAULT_HEADERS.iter().map(|header| HeaderName::from_static(header)).collect()
+    } else {
+        parse(&config.allowed_headers, "header", |header| HeaderName::from_bytes(header.as_bytes()).ok())?
+    };
+
+    let mut layer = CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .expose_headers(EXPOSED_HEADERS.map(HeaderName::from_static))
+        .allow_credentials(config.allow_credentials);
+    if let Some(max_age) = config.max_age_secs {
+        layer = layer.max_age(Duration::from_secs(max_age));
+    }
+    Ok(layer)
+}
+
+fn parse<T>(items: &[String], what: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Vec<T>> {
+    items
+        .iter()
+        .map(|item| {
+            parse(item).ok_or_else(|| TicketMasterError::InvalidArgument(format!("Invalid CORS {}: {:?}", what, item)))
+        })
+        .collect()
+}
@@ -0,0 +1,53 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use std::hash::{Hash, Hasher};
+use ticket_master::AreaStatus;
+
+use crate::ApiResponse;
+
+/// Weak ETag of area statuses, built from their versions
+///
+/// Weak because bodies also carry the request id and may be compressed, so
+/// equal tags mean equal seats rather than equal bytes.
+pub fn area_etag(area_statuses: &[AreaStatus]) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for area_status in area_statuses {
+        area_status.version().hash(&mut hasher);
+    }
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// `304 Not Modified` if `If-None-Match` names `etag`, else `data`; both tagged with `etag`
+pub fn conditional<T: Serialize>(headers: &HeaderMap, etag: &str, data: T) -> Response {
+    let mut response = if not_modified(headers, etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(ApiResponse::success(data)).into_response()
+    };
+    if let Ok(value) = HeaderValue::from_str(etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    // Seats change all the time: caches may keep the body but must revalidate
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
+
+fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || opaque_tag(candidate) == opaque_tag(etag))
+}
+
+// If-None-Match compares weakly: `W/"x"` matches `"x"`
+fn opaque_tag(etag: &str) -> &str {
+    etag.strip_prefix("W/").unwrap_or(etag)
+}
@@ -0,0 +1,240 @@
+use async_graphql::{http::GraphiQLSource, Context, EmptyMutation, EmptySubscription, Enum, Object, Schema, SimpleObject};
+use axum::{
+    extract::State,
+    response::{Html, Json},
+};
+use ticket_master::{AreaStatus, Reservation};
+
+use crate::service::TicketService;
+
+// Deep enough for reservation -> area -> seat map
+const MAX_QUERY_DEPTH: usize = 8;
+
+pub type TicketSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Read-only schema over the stores ticket-service serves from
+pub fn schema(service: TicketService) -> TicketSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(service)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .finish()
+}
+
+pub async fn graphql(
+    State(schema): State<TicketSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// An event with the areas it has; null when no area of it is known
+    async fn event(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Option<EventNode>> {
+        let areas = ctx.data::<TicketService>()?.list_area_statuses(&id).await?;
+        Ok((!areas.is_empty()).then_some(EventNode { id, areas }))
+    }
+
+    async fn area(&self, ctx: &Context<'_>, event_id: String, area_id: String) -> async_graphql::Result<Option<AreaNode>> {
+        let area_status = ctx.data::<TicketService>()?.get_area_status(&event_id, &area_id).await?;
+        Ok(area_status.map(AreaNode))
+    }
+
+    async fn reservation(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Option<ReservationNode>> {
+        let reservation = ctx.data::<TicketService>()?.get_reservation(&id).await?;
+        Ok(reservation.map(ReservationNode))
+    }
+}
+
+pub struct EventNode {
+    id: String,
+    areas: Vec<AreaStatus>,
+}
+
+#[Object(name = "Event")]
+impl EventNode {
+    async fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Every area of the event, or only those in `ids`
+    async fn areas(&self, ids: Option<Vec<String>>) -> Vec<AreaNode> {
+        self.areas
+            .iter()
+            .filter(|area| ids.as_ref().is_none_or(|ids| ids.contains(&area.area_id)))
+            .cloned()
+            .map(AreaNode)
+            .collect()
+    }
+
+    async fn available_seats(&self) -> i32 {
+        self.areas.iter().map(|area| area.available_seats).sum()
+    }
+}
+
+pub struct AreaNode(AreaStatus);
+
+#[Object(name = "Area")]
+impl AreaNode {
+    async fn event_id(&self) -> &str {
+        &self.0.event_id
+    }
+
+    async fn area_id(&self) -> &str {
+        &self.0.area_id
+    }
+
+    async fn price(&self) -> i32 {
+        self.0.price
+    }
+
+    async fn row_count(&self) -> i32 {
+        self.0.row_count
+    }
+
+    async fn col_count(&self) -> i32 {
+        self.0.col_count
+    }
+
+    async fn available_seats(&self) -> i32 {
+        self.0.available_seats
+    }
+
+    /// Every seat, row by row
+    async fn seat_map(&self) -> Vec<Vec<SeatNode>> {
+        self.0
+            .seats
+            .iter()
+            .map(|row| row.iter().map(|seat| SeatNode::new(seat.row, seat.col, seat.is_available)).collect())
+            .collect()
+    }
+
+    /// Only the seats still available, in row order
+    async fn free_seats(&self) -> Vec<SeatNode> {
+        self.0
+            .seats
+            .iter()
+            .flatten()
+            .filter(|seat| seat.is_available)
+            .map(|seat| SeatNode::new(seat.row, seat.col, true))
+            .collect()
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Seat")]
+pub struct SeatNode {
+    row: i32,
+    col: i32,
+    /// Unset for seats of a reservation
+    is_available: Option<bool>,
+}
+
+impl SeatNode {
+    fn new(row: i32, col: i32, is_available: bool) -> Self {
+        Self {
+            row,
+            col,
+            is_available: Some(is_available),
+        }
+    }
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "ticket_master::ReservationState")]
+pub enum ReservationStateNode {
+    Processing,
+    Reserved,
+    Failed,
+    Paid,
+    Cancelled,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "ticket_master::ReservationType")]
+pub enum ReservationTypeNode {
+    SelfPick,
+    Random,
+    Invalid,
+    ContinuousRandom,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "ticket_master::ReservationErrorCode")]
+pub enum ReservationErrorCodeNode {
+    InvalidEventArea,
+    InvalidArgument,
+    SeatNotAvailable,
+    InsufficientSeats,
+    Timeout,
+}
+
+pub struct ReservationNode(Reservation);
+
+#[Object(name = "Reservation")]
+impl ReservationNode {
+    async fn reservation_id(&self) -> &str {
+        &self.0.reservation_id
+    }
+
+    async fn user_id(&self) -> &str {
+        &self.0.user_id
+    }
+
+    async fn event_id(&self) -> &str {
+        &self.0.event_id
+    }
+
+    async fn area_id(&self) -> &str {
+        &self.0.area_id
+    }
+
+    async fn num_of_seats(&self) -> i32 {
+        self.0.num_of_seats
+    }
+
+    async fn reservation_type(&self) -> ReservationTypeNode {
+        self.0.reservation_type.clone().into()
+    }
+
+    async fn state(&self) -> ReservationStateNode {
+        self.0.state.clone().into()
+    }
+
+    /// Why the reservation failed; null unless it did
+    async fn failed_reason(&self) -> Option<&str> {
+        self.0.failed_reason.as_ref().map(|reason| reason.message.as_str())
+    }
+
+    /// What kind of failure it was; null unless it failed with a code
+    async fn failed_code(&self) -> Option<ReservationErrorCodeNode> {
+        self.0.failed_reason.as_ref()?.code.clone().map(Into::into)
+    }
+
+    async fn seats(&self) -> Vec<SeatNode> {
+        self.0
+            .seats
+            .iter()
+            .map(|seat| SeatNode {
+                row: seat.row,
+                col: seat.col,
+                is_available: None,
+            })
+            .collect()
+    }
+
+    /// The area the reservation is for, as it stands now
+    async fn area(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<AreaNode>> {
+        let area_status = ctx
+            .data::<TicketService>()?
+            .get_area_status(&self.0.event_id, &self.0.area_id)
+            .await?;
+        Ok(area_status.map(AreaNode))
+    }
+}
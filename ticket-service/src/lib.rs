@@ -0,0 +1,19 @@
+use axum::response::Json;
+use ticket_master::{ApiError, ApiResponse};
+
+pub mod admin;
+pub mod artwork;
+pub mod auth;
+pub mod cors;
+pub mod etag;
+pub mod graphql;
+pub mod pagination;
+pub mod queue;
+pub mod rate_limit;
+pub mod request_id;
+pub mod service;
+pub mod validation;
+
+pub use service::TicketService;
+
+pub type ApiResult<T> = std::result::Result<Json<ApiResponse<T>>, ApiError>;
@@ -1,213 +1,550 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{HeaderMap, StatusCode},
+    response::{Json, Response},
+    middleware,
     routing::{get, post},
     Router,
 };
 use clap::Parser;
 use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, path::PathBuf};
-use ticket_master::{Result, ServiceConfig};
-use tower_http::cors::CorsLayer;
-use tracing::{info, error};
-
-mod service;
+use std::{net::SocketAddr, time::Duration};
+use ticket_master::{
+    serve_metrics, init_logging, redacted_config, shutdown_telemetry, ApiKeyStore, CommonArgs, ConfigWatcher, AreaStatus, AreaStatusDelta, KafkaProducerShutdown, StateStoresShutdown, Readiness, Reservation, Result,
+    SeatLayout, SecretResolver, ShutdownCoordinator, StrategyInfo, StrategyRegistry, TicketMasterError,
+    Availability, CheckAvailability, ReservationType, SeatPreference, ChargePayment,
+    ApiError, ApiResponse, CreateEventRequest, CreateReservationRequest, FieldError, PayReservationRequest,
+};
+use ticket_service::{admin, artwork, cors, etag, graphql, queue, request_id, ApiResult};
+use ticket_service::auth::{admin_routes, require_api_key, AuthState};
+use ticket_service::pagination::{Filter, Pagination};
+use ticket_service::rate_limit::{limit_rate, RateLimiter};
+use ticket_service::request_id::RequestId;
+use ticket_service::service::{parse_wait, EventSummary, Ticket, TicketService};
+use ticket_service::validation::ValidatedJson;
+use tower_http::compression::CompressionLayer;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, error, warn};
 
-use service::TicketService;
+// Longest in-flight requests may keep shutdown waiting, e.g. long polls
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(20);
+const DEFAULT_PORT: u16 = 8080;
+// Set by clients so a retried payment request is the same charge
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
 
 #[derive(Parser, Debug)]
 #[command(name = "ticket-service")]
 #[command(about = "Ticket Service REST API for Ticket Master")]
 struct Args {
-    /// Port to listen on
-    #[arg(short = 'p', long = "port", default_value = "8080")]
-    port: u16,
-
-    /// Config file path
-    #[arg(short = 'c', long = "config", default_value = "../client.dev.properties")]
-    config: PathBuf,
+    /// Port to listen on; overrides `ticket.service.port`, 8080 when neither is set
+    #[arg(short = 'p', long = "port")]
+    port: Option<u16>,
 
-    /// Producer config file path
-    #[arg(long = "producer-config")]
-    producer_config: Option<PathBuf>,
+    #[command(flatten)]
+    common: CommonArgs,
+}
 
-    /// Show help information
-    #[arg(short = 'h', long = "help")]
-    help: bool,
+#[derive(Debug, Deserialize)]
+struct AreaListQuery {
+    /// Comma-separated area ids; every area of the event when absent
+    areas: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct CreateEventRequest {
-    artist: String,
-    event_name: String,
-    reservation_opening_time: String,
-    reservation_closing_time: String,
-    event_start_time: String,
-    event_end_time: String,
-    areas: Vec<AreaRequest>,
+#[derive(Debug, Deserialize)]
+struct AvailabilityQuery {
+    seats: i32,
+    /// "random" (default) or "continuous"
+    #[serde(rename = "type")]
+    reservation_type: Option<String>,
+    /// Comma-separated seat preferences
+    preferences: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct AreaRequest {
+#[derive(Debug, Deserialize)]
+struct AreaImportQuery {
     area_id: String,
-    price: i32,
-    row_count: i32,
-    col_count: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct CreateReservationRequest {
-    user_id: String,
+/// What `POST /events/:event_name/areas/import` queued
+#[derive(Debug, Serialize)]
+struct ImportedArea {
     event_id: String,
     area_id: String,
-    num_of_seats: i32,
-    reservation_type: String,
-    seats: Option<Vec<SeatRequest>>,
+    row_count: i32,
+    col_count: i32,
+    seats: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SeatRequest {
-    row: i32,
-    col: i32,
+#[derive(Debug, Deserialize)]
+struct ReservationQuery {
+    /// Wait this long for the reservation to leave `Processing`, e.g. `30s`
+    wait: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ApiResponse<T> {
-    success: bool,
-    data: Option<T>,
-    error: Option<String>,
+#[derive(Debug, Deserialize)]
+struct AreaStatusFilter {
+    event: Option<String>,
 }
 
-impl<T> ApiResponse<T> {
-    fn success(data: T) -> Self {
-        Self {
-            success: true,
-            data: Some(data),
-            error: None,
-        }
-    }
-
-    fn error(message: String) -> Self {
-        Self {
-            success: false,
-            data: None,
-            error: Some(message),
-        }
-    }
+/// What `/ws/events/:event_id/areas/:area_id` sends: the area once, then what changed
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AreaFeedMessage {
+    Snapshot(AreaStatus),
+    Delta(AreaStatusDelta),
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = Args::parse();
+
     // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    let log_filter = init_logging("ticket-service", args.common.log_format)?;
 
-    let args = Args::parse();
+    info!("Starting Ticket Service");
 
-    if args.help {
-        println!("Ticket Service REST API for Ticket Master");
+    // Load configuration
+    let loader = args.common.config_loader("ticket-service");
+    let mut config = args.common.load_config(loader.clone())?;
+    if let Some(port) = args.port {
+        config.ticket_service.port = Some(port);
+    }
+    if args.common.print_config {
+        println!("{}", redacted_config(&config)?);
         return Ok(());
     }
+    SecretResolver::from_env().resolve_config(&mut config).await?;
+    log_filter.set(config.log_filter.as_deref())?;
 
-    info!("Starting Ticket Service on port {}", args.port);
-    info!("Config file: {:?}", args.config);
-
-    // Load configuration
-    let config = load_config(&args.config)?;
+    // Runtime-tunable settings follow the config file
+    let config_watcher = ConfigWatcher::start(loader, &config)?;
+    config_watcher.watch_log_filter(log_filter.clone());
 
-    if let Some(producer_config_path) = args.producer_config {
-        info!("Loading producer config from: {:?}", producer_config_path);
-        config = ticket_master::merge_stream_properties(config, producer_config_path)?;
+    let port = config.ticket_service.port.unwrap_or(DEFAULT_PORT);
+    if config.ticket_service.cors.permissive {
+        warn!("CORS is permissive: any origin may call the API");
     }
+    let cors = cors::cors_layer(&config.ticket_service.cors)?;
+    let rate_limiter = config.ticket_service.rate_limit.as_ref().map(RateLimiter::new);
+    let artwork_max_bytes = config.artwork.max_bytes();
+
+    // API keys are kept under the state directory, opened only when used
+    let api_keys_enabled = config.api_keys_enabled;
+    let admin_enabled = config.admin_token.is_some();
+    let auth = if api_keys_enabled || admin_enabled {
+        let keys = ApiKeyStore::open(format!("{}/api-keys", config.state_dir))?;
+        Some(AuthState::new(keys, config.admin_token.clone()))
+    } else {
+        None
+    };
 
     // Create the ticket service
-    let ticket_service = TicketService::new(config).await?;
+    let ticket_service = TicketService::new(config).await?.watch_settings(&config_watcher);
+
+    let coordinator = ShutdownCoordinator::default();
+    coordinator
+        .register_component(Box::new(KafkaProducerShutdown::new(ticket_service.producer().clone())))
+        .await;
+    coordinator
+        .register_component(Box::new(StateStoresShutdown::new(ticket_service.context().clone())))
+        .await;
+    if let Some(heartbeat) = ticket_service.heartbeat() {
+        coordinator.register_component(Box::new(heartbeat.clone())).await;
+    }
+
+    let schema = graphql::schema(ticket_service.clone());
+    let metrics = ticket_service.metrics().clone();
 
     // Build the router
-    let app = Router::new()
-        .route("/events", post(create_event))
+    let mut api = Router::new()
+        .route("/events", get(list_events).post(create_event))
+        .route("/events/:event_name/areas", get(list_area_statuses))
+        .route("/events/:event_name/areas/import", post(import_area))
         .route("/events/:event_name/areas/:area_id", get(get_area_status))
-        .route("/reservations", post(create_reservation))
+        .route("/events/:event_name/areas/:area_id/availability", get(check_availability))
+        .route("/reservations", get(list_reservations).post(create_reservation))
         .route("/reservations/:reservation_id", get(get_reservation))
-        .route("/health", get(health_check))
-        .layer(CorsLayer::permissive())
-        .with_state(ticket_service);
+        .route("/reservations/:reservation_id/payment", post(pay_reservation))
+        .route("/tickets", get(list_tickets))
+        .route("/strategies", get(list_strategies))
+        .route("/queue/:event_id/join", post(queue::join_queue))
+        .route("/queue/:event_id/status", get(queue::queue_status))
+        .route("/ws/area-status", get(area_status_ws))
+        .route("/ws/events/:event_id/areas/:area_id", get(area_feed_ws))
+        .route("/graphql", get(graphql::graphiql).post(graphql::graphql).with_state(schema))
+        .merge(artwork::upload_routes(artwork_max_bytes));
+    if let Some(auth) = auth.clone().filter(|_| api_keys_enabled) {
+        info!("API keys required");
+        api = api.route_layer(middleware::from_fn_with_state(auth, require_api_key));
+    }
+    // Checked before the API key, so floods don't reach the key store
+    if let Some(limiter) = rate_limiter {
+        info!("Requests limited per instance");
+        api = api.route_layer(middleware::from_fn_with_state(limiter, limit_rate));
+    }
+    let mut app = api
+        .route("/health/live", get(liveness_check))
+        .route("/health/ready", get(readiness_check))
+        .merge(artwork::serve_routes());
+    if let Some(auth) = auth.clone().filter(|_| admin_enabled) {
+        app = app.merge(admin::operator_routes(auth));
+    }
+    let mut app = app.with_state(ticket_service);
+    if let Some(auth) = auth.filter(|_| admin_enabled) {
+        app = app.merge(admin::log_level_routes(auth.clone(), log_filter)).merge(admin_routes(auth));
+    }
+    // Scraped without an API key, like the health checks
+    let app = serve_metrics(app, metrics)
+        .layer(CompressionLayer::new())
+        .layer(cors)
+        .layer(middleware::from_fn(request_id::propagate_request_id));
 
     // Start the server
-    let addr = SocketAddr::from(([0, 0, 0, 0], args.port));
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
     info!("Ticket Service listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
 
+    // On SIGTERM stop accepting connections, drain in-flight requests, then flush the producer
+    let mut stopping = coordinator.subscribe();
+    let mut stopped = coordinator.subscribe();
+    coordinator.signal_on_termination();
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        let _ = stopping.recv().await;
+    });
+    tokio::select! {
+        result = async { server.await } => result?,
+        _ = async {
+            let _ = stopped.recv().await;
+            tokio::time::sleep(DRAIN_TIMEOUT).await;
+        } => warn!("Requests still in flight after {:?}, closing them", DRAIN_TIMEOUT),
+    }
+    info!("HTTP server stopped");
+
+    coordinator.shutdown().await?;
+    info!("Ticket Service shut down");
+    shutdown_telemetry();
     Ok(())
 }
 
 async fn create_event(
     State(service): State<TicketService>,
-    Json(request): Json<CreateEventRequest>,
-) -> std::result::Result<Json<ApiResponse<String>>, StatusCode> {
-    match service.create_event(request).await {
-        Ok(event_name) => Ok(Json(ApiResponse::success(event_name))),
-        Err(e) => {
-            error!("Error creating event: {}", e);
-            Ok(Json(ApiResponse::error(e.to_string())))
-        }
-    }
+    request_id: RequestId,
+    ValidatedJson(request): ValidatedJson<CreateEventRequest>,
+) -> ApiResult<String> {
+    let event_name = service.create_event(request, &request_id.0).await?;
+    Ok(Json(ApiResponse::success(event_name)))
+}
+
+async fn list_events(
+    State(service): State<TicketService>,
+    pagination: Pagination,
+) -> ApiResult<Vec<EventSummary>> {
+    let page = service.list_events(&pagination).await?;
+    Ok(Json(ApiResponse::page(page.items, page.next_cursor)))
 }
 
 async fn get_area_status(
     State(service): State<TicketService>,
     Path((event_name, area_id)): Path<(String, String)>,
-) -> std::result::Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
-    match service.get_area_status(&event_name, &area_id).await {
-        Ok(Some(area_status)) => Ok(Json(ApiResponse::success(serde_json::to_value(area_status).unwrap()))),
-        Ok(None) => Ok(Json(ApiResponse::error("Area not found".to_string()))),
-        Err(e) => {
-            error!("Error getting area status: {}", e);
-            Ok(Json(ApiResponse::error(e.to_string())))
+    headers: HeaderMap,
+) -> std::result::Result<Response, ApiError> {
+    let area_status = service
+        .get_area_status(&event_name, &area_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Area not found"))?;
+    let etag = etag::area_etag(std::slice::from_ref(&area_status));
+    Ok(etag::conditional(&headers, &etag, area_status))
+}
+
+async fn check_availability(
+    State(service): State<TicketService>,
+    Path((event_name, area_id)): Path<(String, String)>,
+    Query(query): Query<AvailabilityQuery>,
+) -> ApiResult<Availability> {
+    let mut errors = Vec::new();
+    if query.seats <= 0 {
+        errors.push(FieldError::new("seats", "must be positive"));
+    }
+    let reservation_type = match query.reservation_type.as_deref().unwrap_or("random").to_lowercase().as_str() {
+        "random" => ReservationType::Random,
+        "continuous" | "continuous_random" => ReservationType::ContinuousRandom,
+        _ => {
+            errors.push(FieldError::new("type", "must be random or continuous"));
+            ReservationType::Invalid
         }
+    };
+    let mut preferences = Vec::new();
+    for name in query.preferences.iter().flat_map(|names| names.split(',')).map(str::trim).filter(|name| !name.is_empty()) {
+        match SeatPreference::parse(name) {
+            Some(preference) => preferences.push(preference),
+            None => errors.push(FieldError::new("preferences", format!("{} is not aisle, front or together", name))),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::validation(errors));
     }
+
+    let request = CheckAvailability {
+        event_id: event_name,
+        area_id,
+        num_of_seats: query.seats,
+        reservation_type,
+        preferences,
+    };
+    let availability = service
+        .check_availability(&request)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Area not found"))?;
+    Ok(Json(ApiResponse::success(availability)))
 }
 
-async fn create_reservation(
+async fn list_area_statuses(
     State(service): State<TicketService>,
-    Json(request): Json<CreateReservationRequest>,
-) -> std::result::Result<Json<ApiResponse<String>>, StatusCode> {
-    match service.create_reservation(request).await {
-        Ok(reservation_id) => Ok(Json(ApiResponse::success(reservation_id))),
-        Err(e) => {
-            error!("Error creating reservation: {}", e);
-            Ok(Json(ApiResponse::error(e.to_string())))
+    Path(event_name): Path<String>,
+    Query(query): Query<AreaListQuery>,
+    headers: HeaderMap,
+) -> std::result::Result<Response, ApiError> {
+    let area_statuses = match query.areas {
+        Some(areas) => {
+            let area_ids: Vec<String> = areas.split(',').map(|area| area.trim().to_string()).collect();
+            service.get_area_statuses(&event_name, &area_ids).await?
         }
+        None => service.list_area_statuses(&event_name).await?,
+    };
+    let etag = etag::area_etag(&area_statuses);
+    Ok(etag::conditional(&headers, &etag, area_statuses))
+}
+
+// The body is the CSV itself, e.g. `curl --data-binary @layout.csv`
+async fn import_area(
+    State(service): State<TicketService>,
+    Path(event_name): Path<String>,
+    Query(query): Query<AreaImportQuery>,
+    request_id: RequestId,
+    body: String,
+) -> ApiResult<ImportedArea> {
+    if query.area_id.trim().is_empty() {
+        return Err(ApiError::validation(vec![FieldError::new("area_id", "must not be empty")]));
     }
+    // Importing over an area on sale would drop its reservations
+    if service.get_area_status(&event_name, &query.area_id).await?.is_some() {
+        return Err(ApiError::new(StatusCode::CONFLICT, "area_exists", "The area already exists"));
+    }
+
+    let layout = SeatLayout::from_csv(&query.area_id, &body)?;
+    service.import_area(&event_name, &layout, &request_id.0).await?;
+    Ok(Json(ApiResponse::success(ImportedArea {
+        event_id: event_name,
+        area_id: layout.area_id.clone(),
+        row_count: layout.row_count,
+        col_count: layout.col_count,
+        seats: layout.seat_count(),
+    })))
+}
+
+async fn create_reservation(
+    State(service): State<TicketService>,
+    headers: HeaderMap,
+    request_id: RequestId,
+    ValidatedJson(request): ValidatedJson<CreateReservationRequest>,
+) -> ApiResult<String> {
+    queue::admit(&service, &headers, &request.event_id)?;
+    let reservation_id = service.create_reservation(request, &request_id.0).await?;
+    Ok(Json(ApiResponse::success(reservation_id)))
+}
+
+async fn list_reservations(
+    State(service): State<TicketService>,
+    pagination: Pagination,
+    filter: Filter,
+) -> ApiResult<Vec<Reservation>> {
+    let page = service.list_reservations(&pagination, &filter).await?;
+    Ok(Json(ApiResponse::page(page.items, page.next_cursor)))
+}
+
+// The strategies event-service runs, which areas can pick from
+async fn list_strategies() -> ApiResult<Vec<StrategyInfo>> {
+    Ok(Json(ApiResponse::success(StrategyRegistry::default().list())))
+}
+
+async fn list_tickets(
+    State(service): State<TicketService>,
+    pagination: Pagination,
+    filter: Filter,
+) -> ApiResult<Vec<Ticket>> {
+    let page = service.list_tickets(&pagination, &filter).await?;
+    Ok(Json(ApiResponse::page(page.items, page.next_cursor)))
 }
 
 async fn get_reservation(
     State(service): State<TicketService>,
     Path(reservation_id): Path<String>,
-) -> std::result::Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
-    match service.get_reservation(&reservation_id).await {
-        Ok(Some(reservation)) => Ok(Json(ApiResponse::success(serde_json::to_value(reservation).unwrap()))),
-        Ok(None) => Ok(Json(ApiResponse::error("Reservation not found".to_string()))),
+    Query(query): Query<ReservationQuery>,
+) -> ApiResult<serde_json::Value> {
+    let reservation = match query.wait {
+        Some(wait) => service.wait_for_reservation(&reservation_id, parse_wait(&wait)?).await?,
+        None => service.get_reservation(&reservation_id).await?,
+    };
+    let reservation = reservation.ok_or_else(|| ApiError::not_found("Reservation not found"))?;
+    let user = service.get_user(&reservation.user_id)?;
+    let mut body = serde_json::to_value(reservation).map_err(TicketMasterError::from)?;
+    // The profile of the user who made it, when user profiles are read
+    if let (Some(user), Some(fields)) = (user, body.as_object_mut()) {
+        fields.insert("user".to_string(), serde_json::to_value(user).map_err(TicketMasterError::from)?);
+    }
+    Ok(Json(ApiResponse::success(body)))
+}
+
+// Answers 202 with the charge; the reservation turns paid once it is made
+async fn pay_reservation(
+    State(service): State<TicketService>,
+    Path(reservation_id): Path<String>,
+    headers: HeaderMap,
+    request_id: RequestId,
+    ValidatedJson(request): ValidatedJson<PayReservationRequest>,
+) -> std::result::Result<(StatusCode, Json<ApiResponse<ChargePayment>>), ApiError> {
+    let idempotency_key = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string);
+    let charge = service
+        .pay_reservation(&reservation_id, request.payment_method.trim(), idempotency_key, &request_id.0)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Reservation not found"))?;
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(charge))))
+}
+
+async fn area_status_ws(
+    State(service): State<TicketService>,
+    Query(filter): Query<AreaStatusFilter>,
+    ws: WebSocketUpgrade,
+) -> std::result::Result<Response, ApiError> {
+    // Only served when the area status bridge is enabled
+    let hub = service
+        .area_status_hub()
+        .ok_or_else(|| ApiError::not_found("Area status updates are not enabled"))?;
+    let updates = hub.subscribe();
+    Ok(ws.on_upgrade(move |socket| push_area_status(socket, updates, filter)))
+}
+
+async fn push_area_status(
+    mut socket: WebSocket,
+    mut updates: tokio::sync::broadcast::Receiver<std::sync::Arc<ticket_master::AreaStatus>>,
+    filter: AreaStatusFilter,
+) {
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(area_status) => {
+                    if filter.event.as_ref().is_some_and(|event| *event != area_status.event_id) {
+                        continue;
+                    }
+                    let text = match serde_json::to_string(&*area_status) {
+                        Ok(text) => text,
+                        Err(e) => {
+                            error!("Error encoding area status: {}", e);
+                            continue;
+                        }
+                    };
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                // The next update carries the whole area, so skipped ones are not resent
+                Err(RecvError::Lagged(skipped)) => warn!("WebSocket client skipped {} area status updates", skipped),
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn area_feed_ws(
+    State(service): State<TicketService>,
+    Path((event_id, area_id)): Path<(String, String)>,
+    ws: WebSocketUpgrade,
+) -> std::result::Result<Response, ApiError> {
+    let hub = service
+        .area_status_hub()
+        .ok_or_else(|| ApiError::not_found("Area status updates are not enabled"))?;
+    // Subscribe before reading the snapshot so no update falls in between
+    let updates = hub.subscribe();
+    let snapshot = service
+        .get_area_status(&event_id, &area_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Area not found"))?;
+    Ok(ws.on_upgrade(move |socket| push_area_feed(socket, updates, snapshot)))
+}
+
+async fn push_area_feed(
+    mut socket: WebSocket,
+    mut updates: tokio::sync::broadcast::Receiver<std::sync::Arc<AreaStatus>>,
+    snapshot: AreaStatus,
+) {
+    if !send_area_feed(&mut socket, &AreaFeedMessage::Snapshot(snapshot.clone())).await {
+        return;
+    }
+    let mut last = snapshot;
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(area_status) => {
+                    if area_status.event_id != last.event_id || area_status.area_id != last.area_id {
+                        continue;
+                    }
+                    let message = match area_status.delta_since(&last) {
+                        Some(delta) if delta.is_empty() => continue,
+                        Some(delta) => AreaFeedMessage::Delta(delta),
+                        None => AreaFeedMessage::Snapshot((*area_status).clone()),
+                    };
+                    last = (*area_status).clone();
+                    if !send_area_feed(&mut socket, &message).await {
+                        break;
+                    }
+                }
+                // Deltas are taken against the last status sent, so skipped updates are folded into the next one
+                Err(RecvError::Lagged(skipped)) => warn!("Area feed client skipped {} area status updates", skipped),
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+// Returns false once the client is gone
+async fn send_area_feed(socket: &mut WebSocket, message: &AreaFeedMessage) -> bool {
+    match serde_json::to_string(message) {
+        Ok(text) => socket.send(Message::Text(text)).await.is_ok(),
         Err(e) => {
-            error!("Error getting reservation: {}", e);
-            Ok(Json(ApiResponse::error(e.to_string())))
+            error!("Error encoding area feed message: {}", e);
+            true
         }
     }
 }
 
-async fn health_check() -> Json<ApiResponse<String>> {
+// Answering at all is enough to be alive; dependencies are for readiness
+async fn liveness_check() -> Json<ApiResponse<String>> {
     Json(ApiResponse::success("OK".to_string()))
 }
 
-fn load_config(config_path: &PathBuf) -> Result<ServiceConfig> {
-    use ticket_master::parse_properties_file;
-    
-    // Parse the Java properties file
-    let config = parse_properties_file(config_path, "ticket-service")?;
-    
-    Ok(config)
+async fn readiness_check(State(service): State<TicketService>) -> (StatusCode, Json<ApiResponse<Readiness>>) {
+    let readiness = service.readiness().check().await;
+    if readiness.ready {
+        return (StatusCode::OK, Json(ApiResponse::success(readiness)));
+    }
+    let mut body = ApiResponse::error("not_ready", "Dependencies are not ready".to_string());
+    body.data = Some(readiness);
+    (StatusCode::SERVICE_UNAVAILABLE, Json(body))
 }
\ No newline at end of file
@@ -0,0 +1,130 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::Deserialize;
+use ticket_master::{ApiError, FieldError, Reservation, ReservationState, ScanOrder};
+
+
+const DEFAULT_PAGE_LIMIT: usize = 50;
+const MAX_PAGE_LIMIT: usize = 200;
+
+/// `?limit=&cursor=&sort=` of a list endpoint
+///
+/// Lists are ordered by id, `asc` unless `sort=desc`. `cursor` is the
+/// `next_cursor` of the previous page and is opaque to clients.
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    pub limit: usize,
+    pub cursor: Option<String>,
+    pub order: ScanOrder,
+}
+
+/// One page of a list endpoint
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// `?state=&user_id=&event_id=` of a list endpoint; each matches all when absent
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub state: Option<ReservationState>,
+    pub user_id: Option<String>,
+    pub event_id: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, reservation: &Reservation) -> bool {
+        self.state.as_ref().is_none_or(|wanted| *wanted == reservation.state)
+            && self.user_id.as_ref().is_none_or(|wanted| *wanted == reservation.user_id)
+            && self.event_id.as_ref().is_none_or(|wanted| *wanted == reservation.event_id)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PaginationQuery {
+    limit: Option<usize>,
+    cursor: Option<String>,
+    sort: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FilterQuery {
+    state: Option<String>,
+    user_id: Option<String>,
+    event_id: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Pagination {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = query::<PaginationQuery, S>(parts, state).await?;
+        let mut errors = Vec::new();
+
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+            errors.push(FieldError::new("limit", format!("must be between 1 and {}", MAX_PAGE_LIMIT)));
+        }
+        let order = match query.sort.as_deref().map(str::to_lowercase).as_deref() {
+            None | Some("asc") => ScanOrder::Ascending,
+            Some("desc") => ScanOrder::Descending,
+            Some(_) => {
+                errors.push(FieldError::new("sort", "must be asc or desc"));
+                ScanOrder::Ascending
+            }
+        };
+
+        if !errors.is_empty() {
+            return Err(ApiError::validation(errors));
+        }
+        Ok(Self {
+            limit,
+            cursor: query.cursor.filter(|cursor| !cursor.is_empty()),
+            order,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Filter {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = query::<FilterQuery, S>(parts, state).await?;
+        let state = match query.state.as_deref().map(str::to_lowercase).as_deref() {
+            None => None,
+            Some("processing") => Some(ReservationState::Processing),
+            Some("reserved") => Some(ReservationState::Reserved),
+            Some("failed") => Some(ReservationState::Failed),
+            Some("paid") => Some(ReservationState::Paid),
+            Some("cancelled") => Some(ReservationState::Cancelled),
+            Some(_) => {
+                return Err(ApiError::validation(vec![FieldError::new(
+                    "state",
+                    "must be processing, reserved, failed, paid or cancelled",
+                )]))
+            }
+        };
+        Ok(Self {
+            state,
+            user_id: query.user_id.filter(|user_id| !user_id.is_empty()),
+            event_id: query.event_id.filter(|event_id| !event_id.is_empty()),
+        })
+    }
+}
+
+async fn query<T, S>(parts: &mut Parts, state: &S) -> Result<Query<T>, ApiError>
+where
+    T: serde::de::DeserializeOwned,
+    S: Send + Sync,
+{
+    Query::<T>::from_request_parts(parts, state)
+        .await
+        .map_err(|rejection| ApiError::new(rejection.status(), "invalid_query", rejection.body_text()))
+}
@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::Json,
+};
+use ticket_master::{ApiError, QueueStatus, QueueTicket, WaitingRoom};
+
+use crate::service::TicketService;
+use crate::{ApiResponse, ApiResult};
+
+/// Header carrying the token from `POST /queue/:event/join`
+pub const QUEUE_TOKEN_HEADER: &str = "x-queue-token";
+
+pub async fn join_queue(
+    State(service): State<TicketService>,
+    Path(event_id): Path<String>,
+) -> ApiResult<QueueTicket> {
+    let waiting_room = enabled(&service)?;
+    Ok(Json(ApiResponse::success(waiting_room.join(&event_id))))
+}
+
+pub async fn queue_status(
+    State(service): State<TicketService>,
+    Path(event_id): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult<QueueStatus> {
+    let waiting_room = enabled(&service)?;
+    let token = queue_token(&headers)?;
+    Ok(Json(ApiResponse::success(waiting_room.status(token, &event_id))))
+}
+
+/// Use up the request's queue token for `event_id`; passes when there is no waiting room
+pub fn admit(service: &TicketService, headers: &HeaderMap, event_id: &str) -> Result<(), ApiError> {
+    let Some(waiting_room) = service.waiting_room() else {
+        return Ok(());
+    };
+    match waiting_room.consume(queue_token(headers)?, event_id) {
+        QueueStatus::Admitted => Ok(()),
+        QueueStatus::Waiting { ahead } => Err(ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "queue_not_admitted",
+            format!("Not admitted yet, {} ahead in the queue", ahead),
+        )),
+        QueueStatus::Invalid => Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "invalid_queue_token",
+            format!("Queue token is not valid for event {}", event_id),
+        )),
+    }
+}
+
+fn enabled(service: &TicketService) -> Result<&WaitingRoom, ApiError> {
+    service
+        .waiting_room()
+        .ok_or_else(|| ApiError::not_found("Waiting room is not enabled"))
+}
+
+fn queue_token(headers: &HeaderMap) -> Result<&str, ApiError> {
+    headers
+        .get(QUEUE_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::FORBIDDEN,
+                "queue_token_required",
+                format!("Join the event's queue and send the token in {}", QUEUE_TOKEN_HEADER),
+            )
+        })
+}
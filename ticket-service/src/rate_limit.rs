@@ -0,0 +1,57 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use ticket_master::{ApiError, RateLimitConfig};
+
+
+/// A token bucket shared by every request this instance serves
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    // Tokens left, as of when they were last counted
+    bucket: Arc<Mutex<(f64, Instant)>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let rate = f64::from(config.requests_per_second);
+        let burst = f64::from(config.burst.unwrap_or(config.requests_per_second).max(1));
+        Self {
+            rate,
+            burst,
+            bucket: Arc::new(Mutex::new((burst, Instant::now()))),
+        }
+    }
+
+    // Take a token if one is left
+    fn try_acquire(&self) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+        let (tokens, counted) = &mut *bucket;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*counted).as_secs_f64() * self.rate).min(self.burst);
+        *counted = now;
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+}
+
+/// Answer 429 once requests come faster than the configured rate
+pub async fn limit_rate(State(limiter): State<RateLimiter>, request: Request, next: Next) -> Result<Response, ApiError> {
+    if !limiter.try_acquire() {
+        return Err(ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "rate_limited",
+            "Too many requests, try again shortly",
+        ));
+    }
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,72 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::convert::Infallible;
+use ticket_master::{continue_trace, with_request_id};
+use tracing::{info_span, Instrument};
+use uuid::Uuid;
+
+// Header a request id is read from and returned in
+const X_REQUEST_ID: &str = "x-request-id";
+// Longer ids from callers are replaced rather than logged
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Id of the request being handled, taken from `x-request-id` or generated
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Present whenever the middleware runs; a fresh id keeps handlers working without it
+        Ok(parts
+            .extensions
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_else(|| RequestId(Uuid::new_v4().to_string())))
+    }
+}
+
+/// Give every request an id, log it in a span and return it in `x-request-id`
+///
+/// The span continues the trace of a caller that sent a `traceparent`.
+pub async fn propagate_request_id(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    request.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let span = info_span!(
+        "request",
+        otel.kind = "server",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        reservation_id = tracing::field::Empty,
+    );
+    continue_trace(
+        &span,
+        request.headers().iter().map(|(name, value)| (name.as_str(), value.as_bytes())),
+    );
+    let mut response = with_request_id(request_id.clone(), next.run(request).instrument(span)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(X_REQUEST_ID, value);
+    }
+    response
+}
+
+fn is_valid(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.len() <= MAX_REQUEST_ID_LEN
+        && request_id.bytes().all(|byte| byte.is_ascii_graphic())
+}
@@ -1,82 +1,635 @@
 use ticket_master::{
-    Result, TicketMasterError, ServiceConfig, KafkaProducer, KafkaConsumer,
-    CreateEvent, CreateReservation, Reservation, AreaStatus, Area, Seat,
-    ReservationType, Topics, Stores, event_area_key, ProcessingContext, RocksDBStore
+    Result, TicketMasterError, ServiceConfig, KafkaProducer, MessageProducer,
+    CreateReservation, Reservation, AreaStatus, CompactAreaStatus, Seat, SeatPreference,
+    ReservationType, Topics, Stores, event_area_key, ProcessingContext, StateStoreBackend, TopicSerdes,
+    Outbox, spawn_outbox_relay, KafkaConsumer, AreaStatusHub, spawn_area_status_bridge,
+    QueryDirectory, StateQueryClient, spawn_query_directory, GlobalTable, spawn_global_table, WaitingRoom,
+    KeyNotifier, ReservationState, ScanOrder, REQUEST_ID_HEADER, AreaAdminCommand, MessageConsumer,
+    ReservationPriority, PRIORITY_HEADER, CheckAvailability, Availability, StrategyRegistry,
+    PartitionLag, RocksDBStore, SeatLayout, ReadinessProbe, SchemaRegistryReadiness,
+    GlobalTableReadiness, ReadinessCheck, Metrics, EventArtwork, ObjectStorage, LocalObjectStorage, S3ObjectStorage,
+    AuditLog, AuditEntity, AuditRecord, spawn_audit_log, ConfigWatcher, RuntimeSettings, CircuitBreakerState,
+    StoreSizeStats, CircuitBreaker, AlertCheck, WebhookNotifier, spawn_alert_monitor, ClusterDirectory,
+    spawn_cluster_directory, HeartbeatPublisher, InstanceStatus, UserProfile, ChargePayment, ReservationEvent,
+    ReservationAdminCommand, CreateEventRequest, CreateReservationRequest, ServiceBootstrap, READINESS_TIMEOUT
 };
-use crate::{CreateEventRequest, CreateReservationRequest};
-use chrono::{DateTime, Utc};
+use crate::artwork::{ImageFormat, ARTWORK_PATH};
+use crate::pagination::{Filter, Page, Pagination};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
 use uuid::Uuid;
-use tracing::info;
+use tracing::{info, warn, Span};
+
+const OUTBOX_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+// Longest a request may wait for a reservation result
+const MAX_RESERVATION_WAIT: Duration = Duration::from_secs(60);
+// Reservations read from other instances aren't notified, so waits poll too
+const RESERVATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+// Application ids the stateful services announce their state query hosts under
+const EVENT_SERVICE: &str = "event-service";
+const RESERVATION_SERVICE: &str = "reservation-service";
+
+/// An event as listed by `GET /events`
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSummary {
+    pub event_id: String,
+    pub area_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artwork_url: Option<String>,
+}
+
+/// The seats a reserved or paid reservation holds, as listed by `GET /tickets`
+#[derive(Debug, Clone, Serialize)]
+pub struct Ticket {
+    pub reservation_id: String,
+    pub user_id: String,
+    pub event_id: String,
+    pub area_id: String,
+    pub seats: Vec<Seat>,
+    pub state: ReservationState,
+}
+
+impl From<Reservation> for Ticket {
+    fn from(reservation: Reservation) -> Self {
+        Self {
+            reservation_id: reservation.reservation_id,
+            user_id: reservation.user_id,
+            event_id: reservation.event_id,
+            area_id: reservation.area_id,
+            seats: reservation.seats,
+            state: reservation.state,
+        }
+    }
+}
+
+/// Lag of one service's consumer group, as reported by `GET /admin/consumer-lag`
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsumerGroupLag {
+    pub application_id: String,
+    pub total_lag: i64,
+    pub partitions: Vec<PartitionLag>,
+}
+
+/// A snapshot of the service for dashboards, as reported by `GET /admin/status`
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub build: BuildInfo,
+    pub uptime_seconds: u64,
+    /// Lag of the watched consumer groups, summed over each topic's partitions
+    pub consumer_lag: Vec<TopicLag>,
+    pub stores: BTreeMap<String, StoreSizeStats>,
+    pub in_flight_reservations: InFlightReservations,
+    /// Circuit breaker states by what they guard
+    pub circuit_breakers: BTreeMap<String, CircuitBreakerState>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub profile: &'static str,
+}
+
+impl BuildInfo {
+    fn current() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            profile: if cfg!(debug_assertions) { "debug" } else { "release" },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicLag {
+    pub application_id: String,
+    pub topic: String,
+    pub lag: i64,
+}
+
+/// Reservations requested but not yet decided
+#[derive(Debug, Clone, Serialize)]
+pub struct InFlightReservations {
+    /// Requests still in the outbox, not yet published
+    pub unpublished: usize,
+    /// Reservations stored as `Processing`
+    pub processing: usize,
+}
+
+// Another service's consumer group and the topics it reads
+#[derive(Clone)]
+struct WatchedGroup {
+    application_id: String,
+    topics: Vec<&'static str>,
+    consumer: Arc<dyn MessageConsumer>,
+}
 
 #[derive(Clone)]
-pub struct TicketService {
-    producer: KafkaProducer,
-    consumer: KafkaConsumer,
-    context: ProcessingContext,
+pub struct TicketService<P = KafkaProducer> {
+    producer: P,
+    outbox: Arc<Outbox>,
+    area_statuses: StateStoreBackend<String, CompactAreaStatus>,
+    reservations: StateStoreBackend<String, Reservation>,
+    artwork: StateStoreBackend<String, EventArtwork>,
+    users: StateStoreBackend<String, UserProfile>,
+    // Reject reservations of users missing from `users`
+    users_required: bool,
+    // Of area prices, and so of charges
+    currency: String,
+    audit_log: AuditLog,
+    artwork_storage: Arc<dyn ObjectStorage>,
+    // Set when artwork is on local disk and so served by this service
+    local_artwork: Option<Arc<LocalObjectStorage>>,
+    area_status_hub: Option<AreaStatusHub>,
+    state_queries: Option<StateQueryClient>,
+    waiting_room: Option<Arc<WaitingRoom>>,
+    reservation_updates: KeyNotifier,
+    watched_groups: Vec<WatchedGroup>,
+    readiness: ReadinessProbe,
+    metrics: Metrics,
+    settings: watch::Receiver<RuntimeSettings>,
+    started: Instant,
+    circuit_breakers: Vec<Arc<CircuitBreaker>>,
+    context: Arc<ProcessingContext>,
+    cluster: ClusterDirectory,
+    heartbeat: Option<HeartbeatPublisher<P>>,
 }
 
 impl TicketService {
     pub async fn new(config: ServiceConfig) -> Result<Self> {
-        let kafka_config = config.to_kafka_config();
-        let producer = KafkaProducer::new(kafka_config.clone())?;
-        let consumer = KafkaConsumer::new(kafka_config)?;
+        let mut kafka_config = config.to_kafka_config();
+        // Broker-side dedup of the relay's own retries
+        kafka_config.set("enable.idempotence", "true");
+        let bootstrap = ServiceBootstrap::new(&config).await?;
+        let metrics = bootstrap.metrics().clone();
+        let kafka_breaker = Arc::new(CircuitBreaker::kafka_producer().with_metrics(metrics.clone()));
+        let serdes = bootstrap.serdes().clone();
+        let producer = KafkaProducer::with_serdes(kafka_config, serdes.clone())?
+            .with_topic_resolver(config.topic_resolver())
+            .with_metrics(metrics.clone())
+            .with_circuit_breaker(kafka_breaker.clone());
+        let mut service = Self::with_producer(&config, producer)?
+            .with_metrics(metrics)
+            .with_circuit_breaker(kafka_breaker);
+        if serdes.uses_schema_registry() {
+            service = service.with_circuit_breaker(bootstrap.registry_breaker().clone());
+        }
 
-        // Initialize state stores for querying
-        let context = ProcessingContext::with_state_dir(config.state_dir.clone());
-        
-        // Add RocksDB stores for reading state
-        context.add_rocksdb_store(Stores::AREA_STATUS.to_string(), "area-status")?;
-        context.add_rocksdb_store(Stores::RESERVATION.to_string(), "reservations")?;
+        // Commands can't be queued for long without the brokers and their topics
+        service.add_readiness_check(bootstrap.kafka_readiness(&[
+            Topics::COMMAND_EVENT_CREATE_EVENT,
+            Topics::COMMAND_RESERVATION_CREATE_RESERVATION,
+        ])?);
+        if let Some(url) = config.kafka.schema_registry_url.as_deref().filter(|_| serdes.uses_schema_registry()) {
+            let mut readiness = SchemaRegistryReadiness::new(url);
+            if let Some((user, password)) = config.kafka.schema_registry_credentials() {
+                readiness = readiness.with_basic_auth(user, password);
+            }
+            service.add_readiness_check(readiness);
+        }
+
+        // Nothing to report but being up; the commands it publishes are
+        // relayed from the outbox, not consumed
+        let heartbeat = HeartbeatPublisher::from_config(service.producer.clone(), &config, env!("CARGO_PKG_VERSION"));
+        heartbeat.start();
+        service = service.with_heartbeat(heartbeat);
+
+        if let Some(rate) = config.waiting_room_admission_rate {
+            service = service.with_waiting_room(WaitingRoom::new(rate));
+            info!("Waiting room admitting {} per second per event", rate);
+        }
+
+        if config.admin_token.is_some() || config.alerts.consumer_lag_threshold.is_some() {
+            service = service.watch_service_groups(&config)?;
+        }
+
+        if config.admin_token.is_some() {
+
+            // Appends are idempotent, so each start reads the whole log again with a group of its own
+            let mut audit_config = config.to_kafka_config();
+            let group_id = format!("{}-audit-{}", config.topic_resolver().resolve(&config.application_id), Uuid::new_v4());
+            audit_config.set("group.id", group_id);
+            audit_config.set("auto.offset.reset", "earliest");
+            audit_config.set("enable.auto.commit", "false");
+            let consumer = KafkaConsumer::new(audit_config)?.with_topic_resolver(config.topic_resolver());
+            spawn_audit_log(consumer, service.audit_log.clone())?;
+
+            // Every heartbeat still on the topic is needed for the cluster overview
+            let mut cluster_config = config.to_kafka_config();
+            let group_id = format!("{}-cluster-{}", config.topic_resolver().resolve(&config.application_id), Uuid::new_v4());
+            cluster_config.set("group.id", group_id);
+            cluster_config.set("auto.offset.reset", "earliest");
+            cluster_config.set("enable.auto.commit", "false");
+            let consumer = KafkaConsumer::new(cluster_config)?.with_topic_resolver(config.topic_resolver());
+            spawn_cluster_directory(consumer, service.cluster.clone())?;
+        }
+
+        if config.global_tables_enabled || config.ticket_service.users_required {
+            service.spawn_user_table(&config, serdes.clone())?;
+        }
+
+        if config.global_tables_enabled {
+            service.spawn_global_tables(&config, serdes.clone())?;
+            info!("Global tables enabled");
+        }
+
+        if config.interactive_queries_enabled {
+            // Every announcement still on the topic is needed, so read it all with a group of our own
+            let mut directory_config = config.to_kafka_config();
+            let group_id = format!("{}-query-{}", config.topic_resolver().resolve(&config.application_id), Uuid::new_v4());
+            directory_config.set("group.id", group_id);
+            directory_config.set("auto.offset.reset", "earliest");
+            directory_config.set("enable.auto.commit", "false");
+            let consumer = KafkaConsumer::new(directory_config)?.with_topic_resolver(config.topic_resolver());
+
+            let directory = QueryDirectory::default();
+            spawn_query_directory(consumer, directory.clone())?;
+            info!("Interactive queries enabled");
+            let client = StateQueryClient::new(directory)?.with_metrics(service.metrics.clone());
+            service = service.with_state_queries(client);
+        }
+
+        if let Some(notifier) = WebhookNotifier::from_config(&config)? {
+            let watched = service.clone();
+            let lag_threshold = config.alerts.consumer_lag_threshold;
+            spawn_alert_monitor(notifier, config.alerts.check_interval(), move || {
+                let service = watched.clone();
+                async move { service.alert_checks(lag_threshold).await }
+            });
+            info!("Alert webhook enabled");
+        }
+
+        if !config.area_status_bridge_enabled {
+            return Ok(service);
+        }
+
+        // Every instance needs every update, so the bridge gets a group of its own
+        let mut bridge_config = config.to_kafka_config();
+        let group_id = format!("{}-bridge-{}", config.topic_resolver().resolve(&config.application_id), Uuid::new_v4());
+        bridge_config.set("group.id", group_id);
+        bridge_config.set("auto.offset.reset", "latest");
+        let consumer = KafkaConsumer::new(bridge_config)?
+            .with_serdes(serdes)
+            .with_topic_resolver(config.topic_resolver())
+            .with_metrics(service.metrics.clone());
+
+        let hub = AreaStatusHub::default();
+        spawn_area_status_bridge(consumer, hub.clone())?;
+        info!("Area status bridge enabled");
+        Ok(service.with_area_status_hub(hub))
+    }
+
+    // The admin API reports how far the other services' consumer groups are
+    // behind; their offsets are only read, the groups are never joined
+    fn watch_service_groups(mut self, config: &ServiceConfig) -> Result<Self> {
+        let services: [(&str, &[&'static str]); 2] = [
+            (EVENT_SERVICE, &[
+                Topics::COMMAND_EVENT_CREATE_EVENT,
+                Topics::COMMAND_EVENT_RESERVE_SEAT,
+                Topics::INTERNAL_EVENT_AREA_STATUS,
+                Topics::COMMAND_EVENT_ADMIN,
+            ]),
+            (RESERVATION_SERVICE, &[
+                Topics::COMMAND_RESERVATION_CREATE_RESERVATION,
+                Topics::COMMAND_RESERVATION_ADMIN,
+                Topics::RESPONSE_RESERVATION_RESULT,
+                Topics::STATE_EVENT_AREA_STATUS,
+            ]),
+        ];
+        for (application_id, topics) in services {
+            let mut group_config = config.to_kafka_config();
+            group_config.set("group.id", config.topic_resolver().resolve(application_id));
+            let consumer = KafkaConsumer::new(group_config)?.with_topic_resolver(config.topic_resolver());
+            self = self.with_watched_group(application_id, topics, consumer);
+        }
+        Ok(self)
+    }
+
+    // Every instance reads both state topics whole, so any instance can
+    // answer any read; the local stores are only written from these topics
+    fn spawn_global_tables(&mut self, config: &ServiceConfig, serdes: Arc<TopicSerdes>) -> Result<()> {
+        let area_statuses = self.area_statuses.rocksdb().cloned().ok_or_else(|| {
+            TicketMasterError::InvalidArgument("Area status store is not a RocksDB store".to_string())
+        })?;
+        let cache = match &self.area_statuses {
+            StateStoreBackend::Cached(cache) => Some(cache.clone()),
+            _ => None,
+        };
+        self.add_readiness_check(GlobalTableReadiness::new(
+            "area_status_store",
+            Topics::STATE_EVENT_AREA_STATUS,
+            area_statuses.clone(),
+            global_table_consumer(config, serdes.clone())?,
+        ));
+        let consumer = global_table_consumer(config, serdes.clone())?;
+        let table = GlobalTable::new(consumer, Topics::STATE_EVENT_AREA_STATUS, area_statuses, |area_status: AreaStatus| {
+            CompactAreaStatus::from(&area_status)
+        })?
+        .with_on_applied(move |key| {
+            if let Some(cache) = &cache {
+                cache.invalidate(&key.to_string());
+            }
+        });
+        spawn_global_table(table);
+
+        let reservations = self.reservations.rocksdb().cloned().ok_or_else(|| {
+            TicketMasterError::InvalidArgument("Reservation store is not a RocksDB store".to_string())
+        })?;
+        self.add_readiness_check(GlobalTableReadiness::new(
+            "reservation_store",
+            Topics::STATE_USER_RESERVATION,
+            reservations.clone(),
+            global_table_consumer(config, serdes.clone())?,
+        ));
+        let consumer = global_table_consumer(config, serdes.clone())?;
+        let updates = self.reservation_updates.clone();
+        let table = GlobalTable::new(consumer, Topics::STATE_USER_RESERVATION, reservations, |reservation: Reservation| reservation)?
+            .with_on_applied(move |key| updates.notify(key));
+        spawn_global_table(table);
+
+        let artwork = self.artwork.rocksdb().cloned().ok_or_else(|| {
+            TicketMasterError::InvalidArgument("Event artwork store is not a RocksDB store".to_string())
+        })?;
+        self.add_readiness_check(GlobalTableReadiness::new(
+            "event_artwork_store",
+            Topics::STATE_EVENT_ARTWORK,
+            artwork.clone(),
+            global_table_consumer(config, serdes.clone())?,
+        ));
+        let consumer = global_table_consumer(config, serdes)?;
+        let table = GlobalTable::new(consumer, Topics::STATE_EVENT_ARTWORK, artwork, |artwork: EventArtwork| artwork)?;
+        spawn_global_table(table);
+        Ok(())
+    }
+
+    // User-service's profiles, to check reservations against and to show
+    // with them; read whole by every instance like the other global tables
+    fn spawn_user_table(&mut self, config: &ServiceConfig, serdes: Arc<TopicSerdes>) -> Result<()> {
+        let users = self.users.rocksdb().cloned().ok_or_else(|| {
+            TicketMasterError::InvalidArgument("User profile store is not a RocksDB store".to_string())
+        })?;
+        self.add_readiness_check(GlobalTableReadiness::new(
+            "user_profile_store",
+            Topics::STATE_USER_PROFILE,
+            users.clone(),
+            global_table_consumer(config, serdes.clone())?,
+        ));
+        let consumer = global_table_consumer(config, serdes)?;
+        let table = GlobalTable::new(consumer, Topics::STATE_USER_PROFILE, users, |profile: UserProfile| profile)?;
+        spawn_global_table(table);
+        info!("Reading user profiles{}", if self.users_required { "; reservations need a registered user" } else { "" });
+        Ok(())
+    }
+}
+
+impl<P: MessageProducer + Clone> TicketService<P> {
+    /// Build the service on the given producer, e.g. from a `MockKafka`
+    ///
+    /// Commands are written to an outbox under the state directory and
+    /// published by a background relay, which also picks up commands left
+    /// over from a previous run.
+    pub fn with_producer(config: &ServiceConfig, producer: P) -> Result<Self> {
+        let outbox = Arc::new(Outbox::open(format!("{}/outbox", config.state_dir))?);
+        spawn_outbox_relay(outbox.clone(), producer.clone(), OUTBOX_RETRY_INTERVAL);
+
+        // RocksDB stores for reading state; areas of an on-sale are read
+        // constantly, so recently read ones are also kept in memory
+        let context = ProcessingContext::builder(config.state_dir.clone())
+            .cached_rocksdb_store(Stores::AREA_STATUS_STORE, "area-status", config.store_cache_capacity())
+            .rocksdb_store(Stores::RESERVATION_STORE, "reservations")
+            .rocksdb_store(Stores::EVENT_ARTWORK_STORE, "event-artwork")
+            .rocksdb_store(Stores::AUDIT_LOG_STORE, "audit-log")
+            .rocksdb_store(Stores::USER_PROFILE_STORE, "user-profiles")
+            .build()?;
+
+        let (artwork_storage, local_artwork) = match S3ObjectStorage::from_config(&config.artwork)? {
+            Some(s3) => (Arc::new(s3) as Arc<dyn ObjectStorage>, None),
+            None => {
+                let dir = config.artwork.local_dir.clone().unwrap_or_else(|| format!("{}/artwork", config.state_dir));
+                let base_url = config.artwork.public_url.as_deref().unwrap_or(ARTWORK_PATH);
+                let local = Arc::new(LocalObjectStorage::new(dir, base_url));
+                (local.clone() as Arc<dyn ObjectStorage>, Some(local))
+            }
+        };
 
         Ok(Self { 
             producer,
-            consumer,
-            context,
+            outbox,
+            area_statuses: context.store(&Stores::AREA_STATUS_STORE)?,
+            reservations: context.store(&Stores::RESERVATION_STORE)?,
+            artwork: context.store(&Stores::EVENT_ARTWORK_STORE)?,
+            users: context.store(&Stores::USER_PROFILE_STORE)?,
+            users_required: config.ticket_service.users_required,
+            currency: config.payment.currency().to_string(),
+            audit_log: AuditLog::new(context.rocksdb_store(&Stores::AUDIT_LOG_STORE)?),
+            artwork_storage,
+            local_artwork,
+            area_status_hub: None,
+            state_queries: None,
+            waiting_room: None,
+            reservation_updates: KeyNotifier::default(),
+            watched_groups: Vec::new(),
+            readiness: ReadinessProbe::new(READINESS_TIMEOUT),
+            metrics: Metrics::new()?,
+            // The settings read at startup, until `watch_settings`
+            settings: watch::channel(RuntimeSettings::from_config(config)).1,
+            started: Instant::now(),
+            circuit_breakers: Vec::new(),
+            context: Arc::new(context),
+            cluster: ClusterDirectory::default(),
+            heartbeat: None,
         })
     }
 
-    pub async fn create_event(&self, request: CreateEventRequest) -> Result<String> {
-        info!("Creating event: {}", request.event_name);
+    /// Publish this instance's heartbeats with `heartbeat`, stopped on shutdown
+    pub fn with_heartbeat(mut self, heartbeat: HeartbeatPublisher<P>) -> Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+
+    pub fn heartbeat(&self) -> Option<&HeartbeatPublisher<P>> {
+        self.heartbeat.as_ref()
+    }
+
+    /// Every instance of every service, as of their last heartbeat
+    pub fn cluster_overview(&self) -> Vec<InstanceStatus> {
+        self.cluster.overview()
+    }
+
+    /// Read state missing from the local stores from the instances owning it
+    pub fn with_state_queries(mut self, client: StateQueryClient) -> Self {
+        self.state_queries = Some(client);
+        self
+    }
+
+    /// Report `breaker`'s state in the status and alert when it opens
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.circuit_breakers.push(breaker);
+        self
+    }
+
+    /// Serve live area status updates from `hub` on the WebSocket endpoint
+    pub fn with_area_status_hub(mut self, hub: AreaStatusHub) -> Self {
+        self.area_status_hub = Some(hub);
+        self
+    }
+
+    /// Report the lag of `consumer`'s group on `topics` as `application_id`'s
+    pub fn with_watched_group<C: MessageConsumer>(mut self, application_id: &str, topics: &[&'static str], consumer: C) -> Self {
+        self.watched_groups.push(WatchedGroup {
+            application_id: application_id.to_string(),
+            topics: topics.to_vec(),
+            consumer: Arc::new(consumer),
+        });
+        self
+    }
+
+    /// Also require `check` to pass before reporting ready
+    pub fn add_readiness_check<R: ReadinessCheck + 'static>(&mut self, check: R) {
+        self.readiness = self.readiness.clone().with_check(check);
+    }
+
+    pub fn readiness(&self) -> &ReadinessProbe {
+        &self.readiness
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
 
-        // Parse timestamps
-        let reservation_opening_time = parse_timestamp(&request.reservation_opening_time)?;
-        let reservation_closing_time = parse_timestamp(&request.reservation_closing_time)?;
-        let event_start_time = parse_timestamp(&request.event_start_time)?;
-        let event_end_time = parse_timestamp(&request.event_end_time)?;
-
-        // Convert areas
-        let areas: Vec<Area> = request.areas.into_iter().map(|area_req| {
-            Area {
-                area_id: area_req.area_id,
-                price: area_req.price,
-                row_count: area_req.row_count,
-                col_count: area_req.col_count,
+    /// The producer the outbox relay publishes commands with
+    pub fn producer(&self) -> &P {
+        &self.producer
+    }
+
+    /// The context the service's RocksDB stores are registered in
+    pub fn context(&self) -> &Arc<ProcessingContext> {
+        &self.context
+    }
+
+    pub fn area_status_hub(&self) -> Option<&AreaStatusHub> {
+        self.area_status_hub.as_ref()
+    }
+
+    /// Record metrics in `metrics` rather than in a registry of the service's own
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Only accept reservations with a queue token admitted by `waiting_room`
+    pub fn with_waiting_room(mut self, waiting_room: WaitingRoom) -> Self {
+        self.waiting_room = Some(Arc::new(waiting_room));
+        self
+    }
+
+    pub fn waiting_room(&self) -> Option<&WaitingRoom> {
+        self.waiting_room.as_deref()
+    }
+
+    /// Apply the waiting room rate and area status cache capacity of the
+    /// watched config file as it changes
+    pub fn watch_settings(mut self, watcher: &ConfigWatcher) -> Self {
+        let waiting_room = self.waiting_room.clone();
+        let area_statuses = self.area_statuses.clone();
+        watcher.on_change(move |settings| {
+            if let (Some(waiting_room), Some(rate)) = (&waiting_room, settings.waiting_room_admission_rate) {
+                waiting_room.set_admission_rate(rate);
             }
-        }).collect();
-
-        let create_event = CreateEvent {
-            artist: request.artist,
-            event_name: request.event_name.clone(),
-            reservation_opening_time,
-            reservation_closing_time,
-            event_start_time,
-            event_end_time,
-            areas,
-        };
+            if let StateStoreBackend::Cached(cache) = &area_statuses {
+                cache.set_capacity(settings.store_cache_capacity);
+            }
+        });
+        self.settings = watcher.subscribe();
+        self
+    }
+
+    /// The runtime-tunable settings in effect
+    pub fn settings(&self) -> RuntimeSettings {
+        self.settings.borrow().clone()
+    }
+
+    pub async fn create_event(&self, request: CreateEventRequest, request_id: &str) -> Result<String> {
+        info!("Creating event: {}", request.event_name);
+
+        let event_name = request.event_name.clone();
+        let create_event = request.into_create_event()?;
 
-        // Send create event command
-        self.producer.send(
+        // Persist the create event command; the outbox relay publishes it
+        self.outbox.enqueue_with_headers(
+            self.producer.serdes(),
             Topics::COMMAND_EVENT_CREATE_EVENT,
-            &request.event_name,
+            &event_name,
             &create_event,
+            &[(REQUEST_ID_HEADER, request_id)],
         ).await?;
 
-        info!("Event creation command sent: {}", request.event_name);
-        Ok(request.event_name)
+        info!("Event creation command queued: {}", event_name);
+        Ok(event_name)
     }
 
-    pub async fn create_reservation(&self, request: CreateReservationRequest) -> Result<String> {
+    /// Queue a new area laid out as `layout` for the event service to initialize
+    pub async fn import_area(&self, event_name: &str, layout: &SeatLayout, request_id: &str) -> Result<()> {
+        let area_status = AreaStatus::from_layout(event_name, layout);
+        // Sent the way the event service re-keys the areas of a new event
+        self.outbox.enqueue_with_headers(
+            self.producer.serdes(),
+            Topics::INTERNAL_EVENT_AREA_STATUS,
+            &event_area_key(event_name, &layout.area_id),
+            &area_status,
+            &[(REQUEST_ID_HEADER, request_id)],
+        ).await?;
+
+        info!("Area import queued: {} {} with {} seats", event_name, layout.area_id, layout.seat_count());
+        Ok(())
+    }
+
+    /// Store `image` as the artwork of `event_id` and publish where it is
+    ///
+    /// Every upload gets a key of its own, so caches of earlier artwork never
+    /// serve the new image.
+    pub async fn upload_artwork(&self, event_id: &str, format: ImageFormat, image: Vec<u8>, request_id: &str) -> Result<EventArtwork> {
+        let object_key = format!("events/{}/{}.{}", event_id, Uuid::new_v4(), format.extension());
+        let size = image.len() as u64;
+        let url = self.artwork_storage.put(&object_key, format.content_type(), image).await?;
+        let artwork = EventArtwork {
+            event_id: event_id.to_string(),
+            url,
+            object_key,
+            content_type: format.content_type().to_string(),
+            size,
+            uploaded_at: Utc::now(),
+        };
+
+        self.outbox.enqueue_with_headers(
+            self.producer.serdes(),
+            Topics::STATE_EVENT_ARTWORK,
+            event_id,
+            &artwork,
+            &[(REQUEST_ID_HEADER, request_id)],
+        ).await?;
+
+        info!("Artwork for {} stored at {}", event_id, artwork.url);
+        Ok(artwork)
+    }
+
+    /// Artwork kept on local disk, when this service serves it
+    pub fn local_artwork(&self) -> Option<&Arc<LocalObjectStorage>> {
+        self.local_artwork.as_ref()
+    }
+
+    pub async fn create_reservation(&self, request: CreateReservationRequest, request_id: &str) -> Result<String> {
+        if self.users_required && self.get_user(&request.user_id)?.is_none() {
+            return Err(TicketMasterError::UnknownUser(request.user_id));
+        }
+
         let reservation_id = Uuid::new_v4().to_string();
+        Span::current().record("reservation_id", reservation_id.as_str());
         
         info!("Creating reservation: {}", reservation_id);
 
@@ -84,18 +637,19 @@ impl TicketService {
         let reservation_type = match request.reservation_type.to_lowercase().as_str() {
             "self_pick" | "selfpick" => ReservationType::SelfPick,
             "random" => ReservationType::Random,
+            "continuous_random" | "continuousrandom" => ReservationType::ContinuousRandom,
             _ => return Err(TicketMasterError::InvalidArgument(
                 format!("Invalid reservation type: {}", request.reservation_type)
             )),
         };
 
-        // Convert seats if provided
-        let seats: Vec<Seat> = request.seats.unwrap_or_default().into_iter().map(|seat_req| {
-            Seat {
-                row: seat_req.row,
-                col: seat_req.col,
-            }
-        }).collect();
+        // Validation already rejected unknown preferences
+        let preferences: Vec<SeatPreference> = request.preferences.iter()
+            .filter_map(|preference| SeatPreference::parse(preference))
+            .collect();
+        let priority = request.priority.as_deref()
+            .and_then(ReservationPriority::parse)
+            .unwrap_or_default();
 
         let create_reservation = CreateReservation {
             reservation_id: reservation_id.clone(),
@@ -105,77 +659,428 @@ impl TicketService {
             num_of_seats: request.num_of_seats,
             num_of_seat: 0, // This seems to be used for numbering, defaulting to 0
             reservation_type,
-            seats,
+            seats: request.seats.unwrap_or_default(),
+            preferences,
+            priority,
         };
 
-        // Send create reservation command
-        self.producer.send(
+        // Persist the create reservation command; the outbox relay publishes it
+        self.outbox.enqueue_with_headers(
+            self.producer.serdes(),
             Topics::COMMAND_RESERVATION_CREATE_RESERVATION,
             &reservation_id,
             &create_reservation,
+            &[(REQUEST_ID_HEADER, request_id), (PRIORITY_HEADER, priority.as_str())],
         ).await?;
 
-        info!("Reservation creation command sent: {}", reservation_id);
+        info!("Reservation creation command queued: {}", reservation_id);
         Ok(reservation_id)
     }
 
+    /// Have payment-service charge `payment_method` for a reserved
+    /// reservation's seats at its area's price; `None` if there is no such
+    /// reservation
+    ///
+    /// The outcome arrives as the reservation turning paid; without an
+    /// idempotency key, paying again with the same method is the same charge.
+    pub async fn pay_reservation(
+        &self,
+        reservation_id: &str,
+        payment_method: &str,
+        idempotency_key: Option<String>,
+        request_id: &str,
+    ) -> Result<Option<ChargePayment>> {
+        let Some(reservation) = self.get_reservation(reservation_id).await? else {
+            return Ok(None);
+        };
+        // Only reserved reservations can be paid
+        reservation.clone().transition(ReservationEvent::PaymentReceived)?;
+        let area_status = self
+            .get_area_status(&reservation.event_id, &reservation.area_id)
+            .await?
+            .ok_or_else(|| TicketMasterError::InvalidEventArea(event_area_key(&reservation.event_id, &reservation.area_id)))?;
+
+        let charge = ChargePayment {
+            reservation_id: reservation_id.to_string(),
+            user_id: reservation.user_id.clone(),
+            amount: area_status.price as i64 * reservation.seats.len() as i64,
+            currency: self.currency.clone(),
+            payment_method: payment_method.to_string(),
+            idempotency_key: idempotency_key
+                .unwrap_or_else(|| ChargePayment::default_idempotency_key(reservation_id, payment_method)),
+        };
+        self.outbox.enqueue_with_headers(
+            self.producer.serdes(),
+            Topics::COMMAND_PAYMENT_CHARGE,
+            reservation_id,
+            &charge,
+            &[(REQUEST_ID_HEADER, request_id)],
+        ).await?;
+
+        info!("Charge {} of reservation {} queued", charge.idempotency_key, reservation_id);
+        Ok(Some(charge))
+    }
+
     pub async fn get_area_status(&self, event_name: &str, area_id: &str) -> Result<Option<AreaStatus>> {
         info!("Getting area status for event: {}, area: {}", event_name, area_id);
         
         let key = event_area_key(event_name, area_id);
         
-        if let Some(store) = self.context.get_rocksdb_store(Stores::AREA_STATUS) {
-            match store.get::<AreaStatus>(&key)? {
-                Some(area_status) => {
-                    info!("Found area status for {}: {} available seats", key, area_status.available_seats);
-                    Ok(Some(area_status))
-                }
+        match self.area_statuses.get(&key)? {
+            Some(area_status) => {
+                info!("Found area status for {}: {} available seats", key, area_status.available_seats());
+                Ok(Some(area_status.to_area_status()))
+            }
+            None => match &self.state_queries {
+                Some(client) => client.get(EVENT_SERVICE, Stores::AREA_STATUS, &key).await,
                 None => {
                     info!("No area status found for key: {}", key);
                     Ok(None)
                 }
+            },
+        }
+    }
+
+    /// Dry run of `request`, preferably by the event-service instance owning
+    /// the area, as it knows the area's strategy; otherwise on the area status
+    /// here, where random requests get the default strategy
+    pub async fn check_availability(&self, request: &CheckAvailability) -> Result<Option<Availability>> {
+        if let Some(client) = &self.state_queries {
+            if let Some(availability) = client.check_availability(EVENT_SERVICE, request).await? {
+                return Ok(Some(availability));
             }
-        } else {
-            info!("Area status store not available");
-            Ok(None)
+        }
+
+        let key = event_area_key(&request.event_id, &request.area_id);
+        match self.area_statuses.get(&key)? {
+            Some(area_status) => StrategyRegistry::default().check_availability(None, &area_status, request).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn list_area_statuses(&self, event_name: &str) -> Result<Vec<AreaStatus>> {
+        info!("Listing area statuses for event: {}", event_name);
+
+        let prefix = event_area_key(event_name, "");
+        match self.area_statuses.rocksdb() {
+            Some(store) => store
+                .scan_prefix::<CompactAreaStatus>(&prefix)
+                .map(|entry| entry.map(|(_, area_status)| area_status.to_area_status()))
+                .collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The given areas of an event, fetched together; unknown areas are left out
+    pub async fn get_area_statuses(&self, event_name: &str, area_ids: &[String]) -> Result<Vec<AreaStatus>> {
+        info!("Getting {} area statuses for event: {}", area_ids.len(), event_name);
+
+        let keys: Vec<String> = area_ids.iter().map(|area_id| event_area_key(event_name, area_id)).collect();
+        match self.area_statuses.rocksdb() {
+            Some(store) => Ok(store
+                .multi_get::<CompactAreaStatus, _>(&keys)?
+                .into_iter()
+                .flatten()
+                .map(|area_status| area_status.to_area_status())
+                .collect()),
+            None => Ok(Vec::new()),
         }
     }
 
     pub async fn get_reservation(&self, reservation_id: &str) -> Result<Option<Reservation>> {
         info!("Getting reservation: {}", reservation_id);
         
-        if let Some(store) = self.context.get_rocksdb_store(Stores::RESERVATION) {
-            match store.get::<Reservation>(reservation_id)? {
-                Some(reservation) => {
-                    info!("Found reservation: {} for user {}", reservation_id, reservation.user_id);
-                    Ok(Some(reservation))
-                }
+        match self.reservations.get(&reservation_id.to_string())? {
+            Some(reservation) => {
+                info!("Found reservation: {} for user {}", reservation_id, reservation.user_id);
+                Ok(Some(reservation))
+            }
+            None => match &self.state_queries {
+                Some(client) => client.get(RESERVATION_SERVICE, Stores::RESERVATION, reservation_id).await,
                 None => {
                     info!("No reservation found for id: {}", reservation_id);
                     Ok(None)
                 }
+            },
+        }
+    }
+
+    /// A user's profile, if user profiles are read and the user is registered
+    pub fn get_user(&self, user_id: &str) -> Result<Option<UserProfile>> {
+        self.users.get(&user_id.to_string())
+    }
+
+    /// Events with a locally stored area, from the area keys alone
+    pub async fn list_events(&self, pagination: &Pagination) -> Result<Page<EventSummary>> {
+        let Some(store) = self.area_statuses.rocksdb() else {
+            return Ok(Page { items: Vec::new(), next_cursor: None });
+        };
+        let mut events: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for key in store.keys() {
+            let key = key?;
+            if key.starts_with("__") {
+                continue;
+            }
+            if let Some((event_id, area_id)) = key.rsplit_once('#') {
+                events.entry(event_id.to_string()).or_default().push(area_id.to_string());
             }
-        } else {
-            info!("Reservation store not available");
-            Ok(None)
         }
+
+        // The cursor is the last event of the previous page
+        let cursor = pagination.cursor.as_deref();
+        let after = |event_id: &String| match (pagination.order, cursor) {
+            (_, None) => true,
+            (ScanOrder::Ascending, Some(cursor)) => event_id.as_str() > cursor,
+            (ScanOrder::Descending, Some(cursor)) => event_id.as_str() < cursor,
+        };
+        let ordered: Box<dyn Iterator<Item = (String, Vec<String>)>> = match pagination.order {
+            ScanOrder::Ascending => Box::new(events.into_iter()),
+            ScanOrder::Descending => Box::new(events.into_iter().rev()),
+        };
+        let mut items: Vec<EventSummary> = ordered
+            .filter(|(event_id, _)| after(event_id))
+            .take(pagination.limit + 1)
+            .map(|(event_id, area_ids)| EventSummary { event_id, area_ids, artwork_url: None })
+            .collect();
+        let more = items.len() > pagination.limit;
+        items.truncate(pagination.limit);
+        for event in &mut items {
+            event.artwork_url = self.artwork.get(&event.event_id)?.map(|artwork| artwork.url);
+        }
+        let next_cursor = items.last().filter(|_| more).map(|event| event.event_id.clone());
+        Ok(Page { items, next_cursor })
+    }
+
+    /// Reservations in the local store, which global tables keep filled
+    pub async fn list_reservations(&self, pagination: &Pagination, filter: &Filter) -> Result<Page<Reservation>> {
+        self.reservation_page(pagination, |reservation| filter.matches(reservation))
+    }
+
+    /// Reservations holding seats, i.e. reserved or paid ones
+    pub async fn list_tickets(&self, pagination: &Pagination, filter: &Filter) -> Result<Page<Ticket>> {
+        let page = self.reservation_page(pagination, |reservation| {
+            matches!(reservation.state, ReservationState::Reserved | ReservationState::Paid)
+                && filter.matches(reservation)
+        })?;
+        Ok(Page {
+            items: page.items.into_iter().map(Ticket::from).collect(),
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    fn reservation_page<F>(&self, pagination: &Pagination, filter: F) -> Result<Page<Reservation>>
+    where
+        F: Fn(&Reservation) -> bool,
+    {
+        let Some(store) = self.reservations.rocksdb() else {
+            return Ok(Page { items: Vec::new(), next_cursor: None });
+        };
+        let page = store.scan_page_where::<Reservation, _>(
+            "",
+            pagination.cursor.as_deref(),
+            pagination.limit,
+            pagination.order,
+            |_, reservation| filter(reservation),
+        )?;
+        Ok(Page {
+            items: page.entries.into_iter().map(|(_, reservation)| reservation).collect(),
+            next_cursor: page.next_page_token,
+        })
+    }
+
+    /// Read the reservation once it has left `Processing`, waiting up to
+    /// `wait`; after that it is returned as it stands, possibly not yet stored
+    pub async fn wait_for_reservation(&self, reservation_id: &str, wait: Duration) -> Result<Option<Reservation>> {
+        let deadline = Instant::now() + wait.min(MAX_RESERVATION_WAIT);
+        // Watch before reading, so an update in between still wakes us
+        let mut updates = self.reservation_updates.watch(reservation_id);
+        loop {
+            let reservation = self.get_reservation(reservation_id).await?;
+            let pending = reservation.as_ref().is_none_or(|r| r.state == ReservationState::Processing);
+            let now = Instant::now();
+            if !pending || now >= deadline {
+                return Ok(reservation);
+            }
+            let _ = tokio::time::timeout((deadline - now).min(RESERVATION_POLL_INTERVAL), updates.changed()).await;
+        }
+    }
+
+    /// Send an operator command to the event service instance owning its area
+    pub async fn send_admin_command(&self, command: &AreaAdminCommand, request_id: &str) -> Result<()> {
+        // Always JSON, whatever serde the other topics use
+        let payload = serde_json::to_vec(command)?;
+        self.producer.send_bytes_with_headers(
+            Topics::COMMAND_EVENT_ADMIN,
+            None,
+            &command.key(),
+            Some(&payload),
+            &[(REQUEST_ID_HEADER, request_id.as_bytes())],
+        ).await?;
+        info!("Admin command sent: {:?}", command);
+        Ok(())
+    }
+
+    /// Send an operator command to the reservation service
+    pub async fn send_reservation_admin_command(&self, command: &ReservationAdminCommand, request_id: &str) -> Result<()> {
+        // Always JSON, whatever serde the other topics use
+        let payload = serde_json::to_vec(command)?;
+        self.producer.send_bytes_with_headers(
+            Topics::COMMAND_RESERVATION_ADMIN,
+            None,
+            command.key(),
+            Some(&payload),
+            &[(REQUEST_ID_HEADER, request_id.as_bytes())],
+        ).await?;
+        info!("Admin command sent: {:?}", command);
+        Ok(())
+    }
+
+    /// A local RocksDB store by its `Stores` name
+    pub fn store(&self, name: &str) -> Result<Arc<RocksDBStore>> {
+        let store = match name {
+            Stores::AREA_STATUS => self.area_statuses.rocksdb(),
+            Stores::RESERVATION => self.reservations.rocksdb(),
+            Stores::EVENT_ARTWORK => self.artwork.rocksdb(),
+            Stores::AUDIT_LOG => Some(self.audit_log.store()),
+            _ => None,
+        };
+        store.cloned().ok_or_else(|| TicketMasterError::StoreNotFound(name.to_string()))
+    }
+
+    /// Recorded state changes of one reservation or area, oldest first
+    pub fn audit_history(&self, entity: AuditEntity, entity_id: &str) -> Result<Vec<AuditRecord>> {
+        self.audit_log.history(entity, entity_id)
+    }
+
+    /// Lag of every watched consumer group, per partition and in total
+    pub async fn consumer_lag(&self) -> Result<Vec<ConsumerGroupLag>> {
+        let mut lags = Vec::new();
+        for group in &self.watched_groups {
+            let watched = group.clone();
+            // Offset and watermark requests block on the brokers
+            let partitions = tokio::task::spawn_blocking(move || watched.consumer.group_lag(&watched.topics))
+                .await
+                .map_err(|e| TicketMasterError::Io(std::io::Error::other(e)))??;
+            lags.push(ConsumerGroupLag {
+                application_id: group.application_id.clone(),
+                total_lag: partitions.iter().map(|partition| partition.lag).sum(),
+                partitions,
+            });
+        }
+        Ok(lags)
+    }
+
+    /// Consumer lag per topic, store sizes, in-flight reservations, circuit
+    /// breakers, uptime and build in one snapshot
+    pub async fn status(&self) -> Result<ServiceStatus> {
+        let mut stores = BTreeMap::new();
+        for name in [Stores::AREA_STATUS, Stores::RESERVATION, Stores::EVENT_ARTWORK, Stores::AUDIT_LOG] {
+            if let Ok(store) = self.store(name) {
+                stores.insert(name.to_string(), store.size_stats()?);
+            }
+        }
+
+        let unpublished = self
+            .outbox
+            .pending()?
+            .iter()
+            .filter(|(_, entry)| entry.topic == Topics::COMMAND_RESERVATION_CREATE_RESERVATION)
+            .count();
+        let processing = match self.reservations.rocksdb() {
+            Some(store) => store
+                .scan_page_where::<Reservation, _>("", None, usize::MAX, ScanOrder::Ascending, |_, reservation| {
+                    reservation.state == ReservationState::Processing
+                })?
+                .entries
+                .len(),
+            None => 0,
+        };
+
+        Ok(ServiceStatus {
+            build: BuildInfo::current(),
+            uptime_seconds: self.started.elapsed().as_secs(),
+            consumer_lag: self.topic_lag().await?,
+            stores,
+            in_flight_reservations: InFlightReservations { unpublished, processing },
+            circuit_breakers: self.circuit_breakers(),
+        })
     }
-}
 
-fn parse_timestamp(timestamp_str: &str) -> Result<DateTime<Utc>> {
-    // Try parsing as ISO 8601 format first
-    if let Ok(dt) = DateTime::parse_from_rfc3339(timestamp_str) {
-        return Ok(dt.with_timezone(&Utc));
+    /// Lag of every watched consumer group, summed per topic
+    pub async fn topic_lag(&self) -> Result<Vec<TopicLag>> {
+        let mut lags: BTreeMap<(String, String), i64> = BTreeMap::new();
+        for group in self.consumer_lag().await? {
+            for partition in group.partitions {
+                *lags.entry((group.application_id.clone(), partition.topic)).or_default() += partition.lag;
+            }
+        }
+        Ok(lags
+            .into_iter()
+            .map(|((application_id, topic), lag)| TopicLag { application_id, topic, lag })
+            .collect())
     }
-    
-    // Try parsing as timestamp millis
-    if let Ok(millis) = timestamp_str.parse::<i64>() {
-        if let Some(dt) = DateTime::from_timestamp_millis(millis) {
-            return Ok(dt);
+
+    /// Circuit breaker states by what they guard
+    pub fn circuit_breakers(&self) -> BTreeMap<String, CircuitBreakerState> {
+        let state_queries = self.state_queries.as_ref().map(StateQueryClient::circuit_breaker);
+        self.circuit_breakers
+            .iter()
+            .map(Arc::as_ref)
+            .chain(state_queries)
+            .map(|breaker| (breaker.name().to_string(), breaker.state()))
+            .collect()
+    }
+
+    /// Alert on topics lagging more than `lag_threshold` and on open breakers;
+    /// lag isn't judged when it can't be read
+    pub async fn alert_checks(&self, lag_threshold: Option<i64>) -> Vec<AlertCheck> {
+        let mut checks = Vec::new();
+        if let Some(threshold) = lag_threshold {
+            match self.topic_lag().await {
+                Ok(lags) => checks.extend(lags.into_iter().map(|lag| {
+                    let name = format!("consumer_lag:{}/{}", lag.application_id, lag.topic);
+                    if lag.lag > threshold {
+                        AlertCheck::firing(name, format!("{} messages behind, over {}", lag.lag, threshold))
+                    } else {
+                        AlertCheck::ok(name)
+                    }
+                })),
+                Err(e) => warn!("Failed to read consumer lag for alerts: {}", e),
+            }
         }
+        for (name, state) in self.circuit_breakers() {
+            let name = format!("circuit_breaker:{}", name);
+            checks.push(match state {
+                CircuitBreakerState::Open => AlertCheck::firing(name, "circuit breaker is open"),
+                _ => AlertCheck::ok(name),
+            });
+        }
+        checks
+    }
+}
+
+// Positions are kept in the stores, so a throwaway group reading from the start is enough
+fn global_table_consumer(config: &ServiceConfig, serdes: Arc<TopicSerdes>) -> Result<KafkaConsumer> {
+    let mut consumer_config = config.to_kafka_config();
+    let group_id = format!("{}-global-{}", config.topic_resolver().resolve(&config.application_id), Uuid::new_v4());
+    consumer_config.set("group.id", group_id);
+    consumer_config.set("auto.offset.reset", "earliest");
+    consumer_config.set("enable.auto.commit", "false");
+    Ok(KafkaConsumer::new(consumer_config)?
+        .with_serdes(serdes)
+        .with_topic_resolver(config.topic_resolver()))
+}
+
+/// Parse a wait such as `30s`, `500ms` or `30` (seconds)
+pub fn parse_wait(wait: &str) -> Result<Duration> {
+    let wait = wait.trim();
+    let (number, unit) = wait.split_at(wait.find(|c: char| !c.is_ascii_digit()).unwrap_or(wait.len()));
+    let number: u64 = number
+        .parse()
+        .map_err(|_| TicketMasterError::InvalidArgument(format!("Invalid wait: {}", wait)))?;
+    match unit {
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "ms" => Ok(Duration::from_millis(number)),
+        _ => Err(TicketMasterError::InvalidArgument(format!("Invalid wait unit in {}, use s or ms", wait))),
     }
-    
-    Err(TicketMasterError::InvalidArgument(
-        format!("Invalid timestamp format: {}", timestamp_str)
-    ))
 }
\ No newline at end of file
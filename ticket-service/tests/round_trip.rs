@@ -0,0 +1,124 @@
+use chrono::{Duration as TimeDelta, Utc};
+use event_service::EventService;
+use reservation_service::{ReservationConsumers, ReservationService};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::tempdir;
+use ticket_master::*;
+use ticket_service::TicketService;
+use tokio::time::{sleep, timeout, Duration};
+
+// Poll `done` until it holds, failing the test after 10 seconds
+async fn wait_until(mut done: impl FnMut() -> bool) {
+    timeout(Duration::from_secs(10), async {
+        while !done() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting");
+}
+
+// Each service keeps its stores in a state directory of its own
+fn config(application_id: &str, state_dir: &Path) -> ServiceConfig {
+    ConfigLoader::new(application_id)
+        .with_override("state.dir", &state_dir.to_string_lossy())
+        .with_override("commit.interval.ms", "10")
+        .load()
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_reservation_round_trip_through_all_three_services_over_mock_kafka() {
+    let kafka = MockKafka::new();
+    let state_dirs = [tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap()];
+
+    let ticket_config = config("ticket-service", state_dirs[0].path());
+    let ticket = TicketService::with_producer(&ticket_config, kafka.producer()).unwrap();
+    // Reservations reach the ticket service as `new` reads them, as a global table
+    let reservations = ticket.context().rocksdb_store(&Stores::RESERVATION_STORE).unwrap();
+    let table = GlobalTable::new(
+        kafka.consumer("ticket-service-reservations"),
+        Topics::STATE_USER_RESERVATION,
+        reservations,
+        |reservation: Reservation| reservation,
+    )
+    .unwrap();
+    spawn_global_table(table);
+
+    let event_config = config("event-service", state_dirs[1].path());
+    let event = EventService::with_clients(
+        &event_config,
+        |listener| Ok(kafka.consumer_with_rebalance_listener("event-service", listener)),
+        kafka.producer(),
+        Arc::new(ProcessingContext::with_state_dir(event_config.state_dir.clone())),
+        Metrics::new().unwrap(),
+    )
+    .unwrap();
+
+    let reservation_config = config("reservation-service", state_dirs[2].path());
+    let consumers = ReservationConsumers {
+        commands: kafka.consumer("reservation-service"),
+        results: kafka.consumer("reservation-service"),
+        area_statuses: kafka.consumer("reservation-service"),
+        payments: kafka.consumer("reservation-service"),
+    };
+    let reservation =
+        ReservationService::with_clients(&reservation_config, consumers, kafka.producer(), Metrics::new().unwrap()).unwrap();
+
+    let now = Utc::now();
+    let create_event = CreateEventRequest {
+        artist: "artist".to_string(),
+        event_name: "concert".to_string(),
+        reservation_opening_time: now.to_rfc3339(),
+        reservation_closing_time: (now + TimeDelta::hours(1)).to_rfc3339(),
+        event_start_time: (now + TimeDelta::hours(2)).to_rfc3339(),
+        event_end_time: (now + TimeDelta::hours(3)).to_rfc3339(),
+        areas: vec![Area {
+            area_id: "A".to_string(),
+            price: 100,
+            row_count: 2,
+            col_count: 5,
+            strategy: None,
+        }],
+    };
+    ticket.create_event(create_event, "request-1").await.unwrap();
+
+    let (stop, _) = tokio::sync::broadcast::channel(1);
+    let drive = async {
+        // Event-service initializes the area before seats of it can be reserved
+        wait_until(|| !kafka.messages(Topics::STATE_EVENT_AREA_STATUS).is_empty()).await;
+
+        let create_reservation = CreateReservationRequest {
+            user_id: "user-1".to_string(),
+            event_id: "concert".to_string(),
+            area_id: "A".to_string(),
+            num_of_seats: 2,
+            reservation_type: "random".to_string(),
+            seats: None,
+            preferences: Vec::new(),
+            priority: None,
+        };
+        let reservation_id = ticket.create_reservation(create_reservation, "request-2").await.unwrap();
+        let decided = ticket.wait_for_reservation(&reservation_id, Duration::from_secs(10)).await.unwrap();
+        stop.send(()).unwrap();
+        decided
+    };
+    let (event_result, reservation_result, decided) =
+        tokio::join!(event.run(stop.subscribe()), reservation.run(stop.subscribe()), drive);
+    event_result.unwrap();
+    reservation_result.unwrap();
+
+    // The reservation went ticket → reservation → event-service and its
+    // result back to reservation-service, which stored it reserved
+    let decided = decided.expect("reservation not stored");
+    assert_eq!(decided.state, ReservationState::Reserved);
+    assert_eq!(decided.user_id, "user-1");
+    assert_eq!(decided.seats.len(), 2);
+
+    let results = kafka.messages(Topics::RESPONSE_RESERVATION_RESULT);
+    assert_eq!(results.len(), 1);
+    let result: ReservationResult = results[0].deserialize_value().await.unwrap();
+    assert_eq!(result.reservation_id, decided.reservation_id);
+    assert_eq!(result.result, ReservationResultEnum::Success);
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+use std::sync::Arc;
+use ticket_master::{
+    AreaAdminCommand, CreateEvent, KafkaProducer, Result, ServiceConfig, TicketMasterError, Topics, TopicSerdes, Validate,
+    REQUEST_ID_HEADER,
+};
+use tracing::info;
+use uuid::Uuid;
+
+/// Send the command creating the event in `file`, encoded with the serde
+/// configured for `command.event.create_event`
+///
+/// The file is checked as ticket-service checks `POST /events` first; with
+/// `dry_run` the command is printed instead of sent.
+pub async fn create_event(config: &ServiceConfig, file: &Path, dry_run: bool) -> Result<()> {
+    let create_event = read_event(file)?;
+    if dry_run {
+        println!("{}", serde_json::to_string_pretty(&create_event)?);
+        return Ok(());
+    }
+
+    let topic = Topics::COMMAND_EVENT_CREATE_EVENT;
+    let serdes = Arc::new(TopicSerdes::from_config(config).await?);
+    let producer = KafkaProducer::with_serdes(config.to_kafka_config(), serdes)?.with_topic_resolver(config.topic_resolver());
+    let payload = producer.serdes().serde_for::<CreateEvent>(topic)?.serialize(topic, &create_event).await?;
+    let request_id = Uuid::new_v4().to_string();
+    producer
+        .send_bytes_with_headers(
+            topic,
+            None,
+            &create_event.event_name,
+            Some(&payload),
+            &[(REQUEST_ID_HEADER, request_id.as_bytes())],
+        )
+        .await?;
+    info!("Event creation command sent: {} (request id {})", create_event.event_name, request_id);
+    Ok(())
+}
+
+/// Send a `ReleaseSeat` admin command for each of `seats`, as (row, col)
+///
+/// The commands share a request id, so the releases can be found together
+/// in the area's audit records.
+pub async fn release_seats(config: &ServiceConfig, event_id: &str, area_id: &str, seats: &[(i32, i32)]) -> Result<()> {
+    // Admin commands are always JSON, so no serdes are needed
+    let producer = KafkaProducer::new(config.to_kafka_config())?.with_topic_resolver(config.topic_resolver());
+    let request_id = Uuid::new_v4().to_string();
+    for &(row, col) in seats {
+        let command = AreaAdminCommand::ReleaseSeat {
+            event_id: event_id.to_string(),
+            area_id: area_id.to_string(),
+            row,
+            col,
+        };
+        let payload = serde_json::to_vec(&command)?;
+        producer
+            .send_bytes_with_headers(
+                Topics::COMMAND_EVENT_ADMIN,
+                None,
+                &command.key(),
+                Some(&payload),
+                &[(REQUEST_ID_HEADER, request_id.as_bytes())],
+            )
+            .await?;
+        info!("Release of seat {}:{} sent", row, col);
+    }
+    info!("Sent {} seat releases for {} area {} (request id {})", seats.len(), event_id, area_id, request_id);
+    Ok(())
+}
+
+fn read_event(file: &Path) -> Result<CreateEvent> {
+    let invalid = |reason: String| TicketMasterError::InvalidArgument(format!("Invalid event file {}: {}", file.display(), reason));
+    let content = std::fs::read_to_string(file)?;
+    let create_event: CreateEvent = serde_yaml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+    // The checks ticket-service makes of `POST /events`
+    let problems: Vec<String> = create_event
+        .validate()
+        .into_iter()
+        .map(|problem| format!("{} {}", problem.field, problem.message))
+        .collect();
+    if !problems.is_empty() {
+        return Err(invalid(problems.join("; ")));
+    }
+    Ok(create_event)
+}
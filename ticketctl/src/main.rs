@@ -0,0 +1,134 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+use ticket_master::{redacted_config, CommonArgs, Result, SecretResolver};
+use tracing_subscriber::EnvFilter;
+
+mod event;
+mod store;
+mod topic;
+
+const TICKETCTL: &str = "ticketctl";
+
+#[derive(Parser, Debug)]
+#[command(name = "ticketctl")]
+#[command(about = "Operator tool for Ticket Master")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create the event described in a YAML file, laid out like the body of `POST /events`
+    CreateEvent {
+        file: PathBuf,
+
+        /// Check the file and print the command as JSON without sending it
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+    /// Print the latest record of a reservation on `state.user.reservation`
+    InspectReservation {
+        reservation_id: String,
+
+        /// Stop reading once no record has arrived for this many seconds
+        #[arg(long = "timeout-secs", default_value_t = 10)]
+        timeout_secs: u64,
+    },
+    /// Write every entry of a RocksDB store as JSON lines; stop the service owning it first
+    DumpStore {
+        /// Directory of the store, e.g. `<state.dir>/area-status/0`
+        path: PathBuf,
+
+        /// File to write instead of stdout
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Replace the contents of a RocksDB store with a dump; stop the service owning it first
+    RestoreStore {
+        path: PathBuf,
+
+        /// Dump to read instead of stdin
+        #[arg(short = 'i', long = "input")]
+        input: Option<PathBuf>,
+    },
+    /// Print the records of topics as JSON lines, values decoded
+    Tail {
+        /// Logical topic names, e.g. `state.user.reservation`
+        #[arg(required = true)]
+        topics: Vec<String>,
+
+        /// Start from the earliest retained records instead of new ones
+        #[arg(long = "from-beginning")]
+        from_beginning: bool,
+
+        /// Stop after printing this many records
+        #[arg(short = 'n', long = "limit")]
+        limit: Option<usize>,
+
+        /// Print only records with this key
+        #[arg(long = "key")]
+        key: Option<String>,
+    },
+    /// Make seats of an area available again, whatever holds them
+    ReleaseSeats {
+        event_id: String,
+        area_id: String,
+
+        /// Seat as `row:col`; repeat for more seats
+        #[arg(long = "seat", required = true, value_parser = parse_seat)]
+        seats: Vec<(i32, i32)>,
+    },
+}
+
+fn parse_seat(seat: &str) -> std::result::Result<(i32, i32), String> {
+    let (row, col) = seat.split_once(':').ok_or_else(|| format!("expected row:col, got {}", seat))?;
+    let parse = |n: &str| n.trim().parse::<i32>().map_err(|e| format!("invalid seat {}: {}", seat, e));
+    Ok((parse(row)?, parse(col)?))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    // Logs go to stderr, so records and dumps on stdout can be piped
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn,ticketctl=info")))
+        .init();
+
+    // Stores are opened by path, without any config
+    let command = match args.command {
+        Command::DumpStore { path, output } => return store::dump_store(&path, output.as_deref()),
+        Command::RestoreStore { path, input } => return store::restore_store(&path, input.as_deref()),
+        command => command,
+    };
+
+    // Load configuration
+    let loader = args.common.config_loader(TICKETCTL);
+    let mut config = args.common.load_config(loader)?;
+    config.application_id = TICKETCTL.to_string();
+    if args.common.print_config {
+        println!("{}", redacted_config(&config)?);
+        return Ok(());
+    }
+    SecretResolver::from_env().resolve_config(&mut config).await?;
+
+    match command {
+        Command::CreateEvent { file, dry_run } => event::create_event(&config, &file, dry_run).await,
+        Command::InspectReservation { reservation_id, timeout_secs } => {
+            topic::inspect_reservation(&config, &reservation_id, Duration::from_secs(timeout_secs)).await
+        }
+        Command::Tail { topics, from_beginning, limit, key } => {
+            topic::tail(&config, &topics, from_beginning, limit, key.as_deref()).await
+        }
+        Command::ReleaseSeats { event_id, area_id, seats } => {
+            event::release_seats(&config, &event_id, &area_id, &seats).await
+        }
+        Command::DumpStore { .. } | Command::RestoreStore { .. } => unreachable!("handled above"),
+    }
+}
@@ -0,0 +1,91 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use ticket_master::{Result, RocksDBStore, StoreCodec, TicketMasterError};
+use tracing::info;
+
+// One line of a dump; the decoded value is only there to be read
+#[derive(Deserialize)]
+struct DumpEntry {
+    key: String,
+    value: String,
+}
+
+/// Write every entry of the store at `path` to `output`, or stdout, one
+/// JSON object per line: the key, the stored bytes in hex and, when they
+/// decode without knowing the type, e.g. JSON values, the decoded value
+///
+/// RocksDB lets a single process open a store, so stop the service owning
+/// it first.
+pub fn dump_store(path: &Path, output: Option<&Path>) -> Result<()> {
+    // Opening a missing store would create an empty one
+    if !path.exists() {
+        return Err(TicketMasterError::InvalidArgument(format!("No store at {}", path.display())));
+    }
+    let store = RocksDBStore::new(path)?;
+    let mut writer: Box<dyn Write> = match output {
+        Some(output) => Box::new(BufWriter::new(File::create(output)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    let mut dumped = 0;
+    for entry in store.raw_entries() {
+        let (key, value) = entry?;
+        let key = String::from_utf8(key)
+            .map_err(|e| TicketMasterError::InvalidArgument(format!("Store key is not UTF-8: {}", e)))?;
+        let mut line = json!({ "key": key, "value": hex(&value) });
+        if let Ok(decoded) = StoreCodec::decode::<Value>(&value) {
+            line["decoded"] = decoded;
+        }
+        serde_json::to_writer(&mut writer, &line)?;
+        writer.write_all(b"\n")?;
+        dumped += 1;
+    }
+    writer.flush()?;
+    info!("Dumped {} entries of {}", dumped, path.display());
+    Ok(())
+}
+
+/// Replace the contents of the store at `path` with a dump written by
+/// `dump_store`, read from `input` or stdin
+///
+/// Entries missing from the dump are deleted. The dump is read in full
+/// before the store is touched, so a malformed one changes nothing.
+pub fn restore_store(path: &Path, input: Option<&Path>) -> Result<()> {
+    let reader: Box<dyn BufRead> = match input {
+        Some(input) => Box::new(BufReader::new(File::open(input)?)),
+        None => Box::new(io::stdin().lock()),
+    };
+
+    let mut entries = BTreeMap::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |reason: String| TicketMasterError::InvalidArgument(format!("Invalid dump line {}: {}", number + 1, reason));
+        let entry: DumpEntry = serde_json::from_str(&line).map_err(|e| invalid(e.to_string()))?;
+        let value = unhex(&entry.value).ok_or_else(|| invalid("value is not hex".to_string()))?;
+        entries.insert(entry.key.into_bytes(), value);
+    }
+
+    let store = RocksDBStore::new(path)?;
+    let restored = store.replace_raw(&entries)?;
+    store.persist()?;
+    info!("Restored {} entries to {}", restored, path.display());
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
@@ -0,0 +1,170 @@
+use serde_json::{json, Value};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use ticket_master::{
+    key_partition, AreaStatus, ChargePayment, CreateEvent, CreateReservation, EventArtwork, KafkaConsumer, KafkaMessage,
+    PaymentResult, Reservation, ReservationResult, ReserveSeat, Result, SerdeValue, ServiceConfig, TicketMasterError,
+    Topics, TopicSerdes, UserProfile,
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// How long `tail` waits for a record before polling again
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Print the records of `topics` as JSON lines, only those keyed `key` if
+/// given, until `limit` are printed or Ctrl+C is pressed
+///
+/// Starts from the records sent after it subscribed, or from the earliest
+/// retained ones with `from_beginning`. Tombstones have a null value.
+pub async fn tail(
+    config: &ServiceConfig,
+    topics: &[String],
+    from_beginning: bool,
+    limit: Option<usize>,
+    key: Option<&str>,
+) -> Result<()> {
+    let consumer = scan_consumer(config, "tail", if from_beginning { "earliest" } else { "latest" }).await?;
+    let topics: Vec<&str> = topics.iter().map(String::as_str).collect();
+    consumer.subscribe(&topics)?;
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut printed = 0;
+    loop {
+        if limit.is_some_and(|limit| printed >= limit) {
+            break;
+        }
+        let message = tokio::select! {
+            _ = &mut ctrl_c => break,
+            message = consumer.recv_message(POLL_INTERVAL) => message?,
+        };
+        let Some(message) = message else { continue };
+        if key.is_some_and(|key| message.key.as_deref() != Some(key)) {
+            continue;
+        }
+        // Stop quietly once the reader has gone, e.g. `| head`
+        if writeln!(std::io::stdout(), "{}", record_json(&message).await).is_err() {
+            break;
+        }
+        printed += 1;
+    }
+    consumer.unsubscribe()
+}
+
+/// Print the latest record of `reservation_id` on `state.user.reservation`
+///
+/// Reads the partition the reservation is keyed to from its earliest
+/// retained record up to where it ended when the command started, or until
+/// no record has arrived for `idle_timeout`.
+pub async fn inspect_reservation(config: &ServiceConfig, reservation_id: &str, idle_timeout: Duration) -> Result<()> {
+    let topic = Topics::STATE_USER_RESERVATION;
+    let consumer = scan_consumer(config, "inspect", "earliest").await?;
+    let partition_count = consumer.partition_count(topic)?;
+    if partition_count == 0 {
+        return Err(TicketMasterError::InvalidArgument(format!("Topic {} does not exist", topic)));
+    }
+    let partition = key_partition(reservation_id, partition_count);
+    let end = consumer
+        .group_lag(&[topic])?
+        .into_iter()
+        .find(|lag| lag.partition == partition)
+        .map_or(0, |lag| lag.high_watermark);
+
+    consumer.subscribe(&[topic])?;
+    let mut latest = None;
+    while end > 0 {
+        let Some(message) = consumer.recv_message(idle_timeout).await? else {
+            warn!("No record for {:?}, stopped reading partition {}", idle_timeout, partition);
+            break;
+        };
+        if message.partition != partition {
+            continue;
+        }
+        let read_to_end = message.offset + 1 >= end;
+        if message.key.as_deref() == Some(reservation_id) {
+            latest = Some(message);
+        }
+        if read_to_end {
+            break;
+        }
+    }
+    consumer.unsubscribe()?;
+
+    let Some(message) = latest else {
+        return Err(TicketMasterError::InvalidArgument(format!(
+            "No record of reservation {} on {}",
+            reservation_id, topic
+        )));
+    };
+    if message.is_tombstone() {
+        info!("Reservation {} has been deleted", reservation_id);
+    }
+    println!("{}", serde_json::to_string_pretty(&record_json(&message).await)?);
+    Ok(())
+}
+
+/// A consumer with a throwaway group of its own, so reading moves no
+/// service's offsets; `offset_reset` is where it starts without any
+async fn scan_consumer(config: &ServiceConfig, purpose: &str, offset_reset: &str) -> Result<KafkaConsumer> {
+    let mut kafka_config = config.to_kafka_config();
+    let group_id = format!("{}-{}-{}", config.topic_resolver().resolve(&config.application_id), purpose, Uuid::new_v4());
+    kafka_config.set("group.id", group_id);
+    kafka_config.set("auto.offset.reset", offset_reset);
+    kafka_config.set("enable.auto.commit", "false");
+    let serdes = Arc::new(TopicSerdes::from_config(config).await?);
+    Ok(KafkaConsumer::new(kafka_config)?
+        .with_serdes(serdes)
+        .with_topic_resolver(config.topic_resolver()))
+}
+
+// Where a record was read, its key and request id, and its value decoded;
+// a value that fails to decode is shown as it is
+async fn record_json(message: &KafkaMessage) -> Value {
+    let value = match decode_value(message).await {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("Failed to decode {} offset {} of partition {}: {}", message.topic, message.offset, message.partition, e);
+            message.payload.as_deref().map_or(Value::Null, raw_value)
+        }
+    };
+    json!({
+        "topic": message.topic,
+        "partition": message.partition,
+        "offset": message.offset,
+        "key": message.key,
+        "request_id": message.request_id(),
+        "value": value,
+    })
+}
+
+// The value as JSON, decoded as the type its topic carries with the topic's serde
+async fn decode_value(message: &KafkaMessage) -> Result<Value> {
+    let Some(payload) = &message.payload else {
+        return Ok(Value::Null);
+    };
+    match message.topic.as_str() {
+        Topics::COMMAND_EVENT_CREATE_EVENT => decode_as::<CreateEvent>(message).await,
+        Topics::COMMAND_EVENT_RESERVE_SEAT => decode_as::<ReserveSeat>(message).await,
+        Topics::RESPONSE_RESERVATION_RESULT => decode_as::<ReservationResult>(message).await,
+        Topics::STATE_EVENT_AREA_STATUS | Topics::INTERNAL_EVENT_AREA_STATUS => decode_as::<AreaStatus>(message).await,
+        Topics::COMMAND_RESERVATION_CREATE_RESERVATION => decode_as::<CreateReservation>(message).await,
+        Topics::STATE_USER_RESERVATION => decode_as::<Reservation>(message).await,
+        Topics::STATE_EVENT_ARTWORK => decode_as::<EventArtwork>(message).await,
+        Topics::STATE_USER_PROFILE => decode_as::<UserProfile>(message).await,
+        Topics::COMMAND_PAYMENT_CHARGE => decode_as::<ChargePayment>(message).await,
+        Topics::RESPONSE_PAYMENT_RESULT => decode_as::<PaymentResult>(message).await,
+        // Admin commands, audit records, heartbeats and query hosts are always JSON
+        _ => Ok(raw_value(payload)),
+    }
+}
+
+async fn decode_as<T: SerdeValue>(message: &KafkaMessage) -> Result<Value> {
+    Ok(serde_json::to_value(message.deserialize_value::<T>().await?)?)
+}
+
+// JSON as it is, anything else as text
+fn raw_value(payload: &[u8]) -> Value {
+    serde_json::from_slice(payload).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(payload).into_owned()))
+}
@@ -0,0 +1,61 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::get,
+    Router,
+};
+use ticket_master::{ApiError, ApiResponse, Readiness, RegisterUser, UpdateUserProfile, UserProfile};
+
+use crate::service::UserService;
+
+type ApiResult<T> = std::result::Result<Json<ApiResponse<T>>, ApiError>;
+
+pub fn routes(service: UserService) -> Router {
+    Router::new()
+        .route("/users", axum::routing::post(register_user))
+        .route("/users/:user_id", get(get_user).patch(update_user))
+        .route("/health/live", get(liveness_check))
+        .route("/health/ready", get(readiness_check))
+        .with_state(service)
+}
+
+async fn register_user(
+    State(service): State<UserService>,
+    Json(request): Json<RegisterUser>,
+) -> std::result::Result<(StatusCode, Json<ApiResponse<UserProfile>>), ApiError> {
+    let profile = service.register(request).await?;
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(profile))))
+}
+
+async fn get_user(State(service): State<UserService>, Path(user_id): Path<String>) -> ApiResult<UserProfile> {
+    let profile = service.get(&user_id)?.ok_or_else(|| ApiError::not_found("User not found"))?;
+    Ok(Json(ApiResponse::success(profile)))
+}
+
+async fn update_user(
+    State(service): State<UserService>,
+    Path(user_id): Path<String>,
+    Json(update): Json<UpdateUserProfile>,
+) -> ApiResult<UserProfile> {
+    let profile = service
+        .update(&user_id, update)
+        .await?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+    Ok(Json(ApiResponse::success(profile)))
+}
+
+// Answering at all is enough to be alive; dependencies are for readiness
+async fn liveness_check() -> Json<ApiResponse<String>> {
+    Json(ApiResponse::success("OK".to_string()))
+}
+
+async fn readiness_check(State(service): State<UserService>) -> (StatusCode, Json<ApiResponse<Readiness>>) {
+    let readiness = service.readiness().check().await;
+    if readiness.ready {
+        return (StatusCode::OK, Json(ApiResponse::success(readiness)));
+    }
+    let mut body = ApiResponse::error("not_ready", "Dependencies are not ready".to_string());
+    body.data = Some(readiness);
+    (StatusCode::SERVICE_UNAVAILABLE, Json(body))
+}
@@ -0,0 +1,74 @@
+use clap::Parser;
+use std::net::SocketAddr;
+use ticket_master::{
+    serve_metrics, shutdown_telemetry, CommonArgs, KafkaProducerShutdown, Result, ShutdownCoordinator, StateStoresShutdown,
+};
+use tracing::info;
+
+mod api;
+mod service;
+
+use service::UserService;
+
+const DEFAULT_PORT: u16 = 8082;
+
+#[derive(Parser, Debug)]
+#[command(name = "user-service")]
+#[command(about = "User Service for Ticket Master")]
+struct Args {
+    /// Port to listen on; overrides `user.service.port`, 8082 when neither is set
+    #[arg(short = 'p', long = "port")]
+    port: Option<u16>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let configured = args.common.service_config("user-service", |config| {
+        if let Some(port) = args.port {
+            config.user_service.port = Some(port);
+        }
+    });
+    let Some(config) = configured.await? else {
+        return Ok(());
+    };
+
+    let port = config.user_service.port.unwrap_or(DEFAULT_PORT);
+    let service = UserService::new(config).await?;
+
+    let coordinator = ShutdownCoordinator::default();
+    coordinator
+        .register_component(Box::new(KafkaProducerShutdown::new(service.producer().clone())))
+        .await;
+    coordinator
+        .register_component(Box::new(StateStoresShutdown::new(service.context().clone())))
+        .await;
+    if let Some(heartbeat) = service.heartbeat() {
+        coordinator.register_component(Box::new(heartbeat.clone())).await;
+    }
+
+    // Scraped alongside the API, like on ticket-service
+    let app = serve_metrics(api::routes(service.clone()), service.metrics().clone());
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("User Service listening on {}", addr);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+
+    // On SIGTERM stop accepting connections, finish in-flight requests, then flush the producer
+    let mut stopping = coordinator.subscribe();
+    coordinator.signal_on_termination();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            let _ = stopping.recv().await;
+        })
+        .await?;
+    info!("HTTP server stopped");
+
+    coordinator.shutdown().await?;
+    info!("User Service shut down");
+    shutdown_telemetry();
+    Ok(())
+}
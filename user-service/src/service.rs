@@ -0,0 +1,161 @@
+use ticket_master::{
+    CircuitBreaker, GlobalTable, GlobalTableReadiness, HeartbeatPublisher, KafkaConsumer, KafkaProducer,
+    MessageProducer, Metrics, ProcessingContext, ReadinessCheck, ReadinessProbe, RegisterUser, Result, RocksDBStore,
+    ServiceBootstrap, ServiceConfig, Stores, TicketMasterError, TopicSerdes, Topics, UpdateUserProfile, UserProfile,
+    spawn_global_table, READINESS_TIMEOUT,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::info;
+use uuid::Uuid;
+
+/// Registers users and keeps their profiles
+///
+/// Profiles are published on the compacted `state.user.profile` topic, which
+/// every instance reads whole into its profile store, so a new instance
+/// starts with every user and instances pick up each other's changes. A
+/// change is also written to the local store once published, so it can be
+/// read back at once.
+#[derive(Clone)]
+pub struct UserService<P = KafkaProducer> {
+    producer: P,
+    profiles: Arc<RocksDBStore>,
+    context: Arc<ProcessingContext>,
+    // Registrations and updates on this instance, one at a time
+    writes: Arc<Mutex<()>>,
+    readiness: ReadinessProbe,
+    metrics: Metrics,
+    heartbeat: Option<HeartbeatPublisher<P>>,
+}
+
+impl UserService {
+    pub async fn new(config: ServiceConfig) -> Result<Self> {
+        let bootstrap = ServiceBootstrap::new(&config).await?;
+        let metrics = bootstrap.metrics().clone();
+        let serdes = bootstrap.serdes().clone();
+        let producer = KafkaProducer::with_serdes(config.to_kafka_config(), serdes.clone())?
+            .with_topic_resolver(config.topic_resolver())
+            .with_metrics(metrics.clone())
+            .with_circuit_breaker(Arc::new(CircuitBreaker::kafka_producer().with_metrics(metrics.clone())));
+        let mut service = Self::with_producer(&config, producer)?.with_metrics(metrics);
+
+        service.add_readiness_check(bootstrap.kafka_readiness(&[Topics::STATE_USER_PROFILE])?);
+        service.add_readiness_check(GlobalTableReadiness::new(
+            "user_profile_store",
+            Topics::STATE_USER_PROFILE,
+            service.profiles.clone(),
+            profile_table_consumer(&config, serdes.clone())?,
+        ));
+        let table = GlobalTable::new(
+            profile_table_consumer(&config, serdes)?,
+            Topics::STATE_USER_PROFILE,
+            service.profiles.clone(),
+            |profile: UserProfile| profile,
+        )?;
+        spawn_global_table(table);
+
+        let heartbeat = HeartbeatPublisher::from_config(service.producer.clone(), &config, env!("CARGO_PKG_VERSION"));
+        heartbeat.start();
+        service.heartbeat = Some(heartbeat);
+        Ok(service)
+    }
+}
+
+impl<P: MessageProducer + Clone> UserService<P> {
+    /// Build the service on the given producer, e.g. from a `MockKafka`;
+    /// the profile store is only filled by the service's own writes
+    pub fn with_producer(config: &ServiceConfig, producer: P) -> Result<Self> {
+        let context = ProcessingContext::builder(config.state_dir.clone())
+            .rocksdb_store(Stores::USER_PROFILE_STORE, "user-profiles")
+            .build()?;
+        Ok(Self {
+            producer,
+            profiles: context.rocksdb_store(&Stores::USER_PROFILE_STORE)?,
+            context: Arc::new(context),
+            writes: Arc::new(Mutex::new(())),
+            readiness: ReadinessProbe::new(READINESS_TIMEOUT),
+            metrics: Metrics::new()?,
+            heartbeat: None,
+        })
+    }
+
+    /// Record metrics in `metrics` rather than in a registry of the service's own
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Also require `check` to pass before reporting ready
+    pub fn add_readiness_check<R: ReadinessCheck + 'static>(&mut self, check: R) {
+        self.readiness = self.readiness.clone().with_check(check);
+    }
+
+    pub fn readiness(&self) -> &ReadinessProbe {
+        &self.readiness
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn producer(&self) -> &P {
+        &self.producer
+    }
+
+    /// The context the profile store is registered in
+    pub fn context(&self) -> &Arc<ProcessingContext> {
+        &self.context
+    }
+
+    pub fn heartbeat(&self) -> Option<&HeartbeatPublisher<P>> {
+        self.heartbeat.as_ref()
+    }
+
+    pub fn get(&self, user_id: &str) -> Result<Option<UserProfile>> {
+        self.profiles.get(user_id)
+    }
+
+    /// Register a new user; fails with `UserExists` if the id is taken
+    pub async fn register(&self, request: RegisterUser) -> Result<UserProfile> {
+        let profile = UserProfile::register(request, Utc::now())?;
+        let _guard = self.writes.lock().await;
+        if self.profiles.contains_key(&profile.user_id)? {
+            return Err(TicketMasterError::UserExists(profile.user_id));
+        }
+        self.publish(&profile).await?;
+        info!("Registered user {}", profile.user_id);
+        Ok(profile)
+    }
+
+    /// Apply `update` to a user's profile; `None` if the user isn't registered
+    pub async fn update(&self, user_id: &str, update: UpdateUserProfile) -> Result<Option<UserProfile>> {
+        let _guard = self.writes.lock().await;
+        let Some(mut profile) = self.profiles.get::<UserProfile>(user_id)? else {
+            return Ok(None);
+        };
+        profile.apply(update, Utc::now())?;
+        self.publish(&profile).await?;
+        info!("Updated profile of user {}", user_id);
+        Ok(Some(profile))
+    }
+
+    // Stored only once published, so the store never has a profile the topic
+    // lacks; the profile table applies the same profile again later
+    async fn publish(&self, profile: &UserProfile) -> Result<()> {
+        self.producer.send(Topics::STATE_USER_PROFILE, &profile.user_id, profile).await?;
+        self.profiles.put(&profile.user_id, profile)
+    }
+}
+
+// Positions are kept in the store, so a throwaway group reading from the start is enough
+fn profile_table_consumer(config: &ServiceConfig, serdes: Arc<TopicSerdes>) -> Result<KafkaConsumer> {
+    let mut consumer_config = config.to_kafka_config();
+    let group_id = format!("{}-profiles-{}", config.topic_resolver().resolve(&config.application_id), Uuid::new_v4());
+    consumer_config.set("group.id", group_id);
+    consumer_config.set("auto.offset.reset", "earliest");
+    consumer_config.set("enable.auto.commit", "false");
+    Ok(KafkaConsumer::new(consumer_config)?
+        .with_serdes(serdes)
+        .with_topic_resolver(config.topic_resolver()))
+}